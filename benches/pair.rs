@@ -0,0 +1,147 @@
+//! Benchmarks of the read path over a pseudo-terminal pair.
+//!
+//! Run with `cargo bench --bench pair`.
+//! Each benchmark prints one line with the measured value,
+//! so the output of two runs can be compared directly to catch regressions.
+//!
+//! Pseudo-terminals are much faster than real serial ports,
+//! so these benchmarks measure the overhead of the library and the kernel, not of the link.
+
+#[cfg(unix)]
+mod benches {
+	use serial2::buffered::BufferedReader;
+	use serial2::framing::{FrameReader, Framer, SlipFramer};
+	use serial2::ppp::HdlcFramer;
+	use serial2::SerialPort;
+	use std::time::{Duration, Instant};
+
+	/// The number of bytes transferred by the throughput benchmarks.
+	const THROUGHPUT_BYTES: usize = 16 << 20;
+
+	/// The number of round trips measured by the latency benchmark.
+	const ROUND_TRIPS: usize = 2000;
+
+	/// The number of frames decoded by the framer benchmarks.
+	const FRAMES: usize = 20_000;
+
+	/// Open a pseudo-terminal pair with a generous read timeout.
+	fn pair() -> (SerialPort, SerialPort) {
+		let (mut a, mut b) = SerialPort::pair().expect("failed to open pseudo-terminal pair");
+		a.set_read_timeout(Duration::from_secs(5)).expect("failed to set read timeout");
+		b.set_read_timeout(Duration::from_secs(5)).expect("failed to set read timeout");
+		(a, b)
+	}
+
+	/// Print the result of a benchmark.
+	fn report(name: &str, value: f64, unit: &str) {
+		println!("{name:<40} {value:>14.1} {unit}");
+	}
+
+	/// Write data to a port from a background thread.
+	fn spawn_writer(port: SerialPort, data: Vec<u8>) -> std::thread::JoinHandle<SerialPort> {
+		std::thread::spawn(move || {
+			for chunk in data.chunks(4096) {
+				port.write_all(chunk).expect("failed to write");
+			}
+			port
+		})
+	}
+
+	/// Measure raw throughput and the number of read calls with different buffer sizes.
+	pub fn throughput() {
+		for buffer_size in [64, 1024, 16384] {
+			let (a, b) = pair();
+			let writer = spawn_writer(a, vec![0x55; THROUGHPUT_BYTES]);
+			let mut buffer = vec![0; buffer_size];
+			let mut received = 0;
+			let mut reads = 0u64;
+			let start = Instant::now();
+			while received < THROUGHPUT_BYTES {
+				received += b.read(&mut buffer).expect("failed to read");
+				reads += 1;
+			}
+			let elapsed = start.elapsed();
+			let _a = writer.join().unwrap();
+			let mib = THROUGHPUT_BYTES as f64 / f64::from(1 << 20);
+			report(&format!("throughput, {buffer_size} byte reads"), mib / elapsed.as_secs_f64(), "MiB/s");
+			report(&format!("read calls, {buffer_size} byte reads"), reads as f64 / mib, "reads/MiB");
+		}
+	}
+
+	/// Measure throughput through a [`BufferedReader`].
+	pub fn buffered_throughput() {
+		let (a, b) = pair();
+		let reader = BufferedReader::new(b).expect("failed to create buffered reader");
+		let writer = spawn_writer(a, vec![0x55; THROUGHPUT_BYTES]);
+		let mut buffer = vec![0; 1024];
+		let mut received = 0;
+		let start = Instant::now();
+		while received < THROUGHPUT_BYTES {
+			received += reader.read(&mut buffer).expect("failed to read");
+		}
+		let elapsed = start.elapsed();
+		let _a = writer.join().unwrap();
+		let mib = THROUGHPUT_BYTES as f64 / f64::from(1 << 20);
+		report("throughput, buffered reader", mib / elapsed.as_secs_f64(), "MiB/s");
+	}
+
+	/// Measure the round trip time of a single byte.
+	pub fn latency() {
+		let (a, b) = pair();
+		let echo = std::thread::spawn(move || {
+			let mut byte = [0];
+			for _ in 0..ROUND_TRIPS {
+				b.read_exact(&mut byte).expect("failed to read");
+				b.write_all(&byte).expect("failed to write");
+			}
+		});
+		let mut round_trips = Vec::with_capacity(ROUND_TRIPS);
+		let mut byte = [0];
+		for i in 0..ROUND_TRIPS {
+			let start = Instant::now();
+			a.write_all(&[i as u8]).expect("failed to write");
+			a.read_exact(&mut byte).expect("failed to read");
+			round_trips.push(start.elapsed());
+		}
+		echo.join().unwrap();
+		round_trips.sort();
+		for (name, percentile) in [("p50", 50), ("p90", 90), ("p99", 99)] {
+			let index = (round_trips.len() * percentile / 100).min(round_trips.len() - 1);
+			report(&format!("round trip latency, {name}"), round_trips[index].as_secs_f64() * 1e6, "us");
+		}
+	}
+
+	/// Measure the number of frames per second decoded by a framer.
+	fn framer<F: Framer + Send + 'static>(name: &str, framer: F, encoded_frame: Vec<u8>) {
+		let (a, b) = pair();
+		let writer = spawn_writer(a, encoded_frame.repeat(FRAMES));
+		let mut reader = FrameReader::new(b, framer);
+		let start = Instant::now();
+		for _ in 0..FRAMES {
+			reader.read_frame().expect("failed to read frame");
+		}
+		let elapsed = start.elapsed();
+		let _a = writer.join().unwrap();
+		report(&format!("frames, {name}"), FRAMES as f64 / elapsed.as_secs_f64(), "frames/s");
+	}
+
+	/// Measure the frame rate of the framers of the crate.
+	pub fn framers() {
+		let payload: Vec<u8> = (0..64).collect();
+		framer("SLIP, 64 byte payload", SlipFramer, SlipFramer::encode(&payload));
+		framer("HDLC, 64 byte payload", HdlcFramer, HdlcFramer::encode(&payload, u32::MAX));
+	}
+}
+
+#[cfg(unix)]
+fn main() {
+	benches::throughput();
+	benches::buffered_throughput();
+	benches::latency();
+	benches::framers();
+}
+
+#[cfg(not(unix))]
+fn main() {
+	eprintln!("The benchmarks use pseudo-terminals, which are only available on Unix platforms.");
+}
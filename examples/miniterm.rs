@@ -0,0 +1,35 @@
+use serial2::terminal::Miniterm;
+use serial2::SerialPort;
+
+fn do_main() -> Result<(), ()> {
+	let args: Vec<_> = std::env::args().collect();
+	if args.len() != 3 {
+		let prog_name = args[0].rsplit_once('/').map(|(_parent, name)| name).unwrap_or(&args[0]);
+		eprintln!("Usage: {} PORT BAUD", prog_name);
+		return Err(());
+	}
+
+	let port_name = &args[1];
+	let baud_rate: u32 = args[2]
+		.parse()
+		.map_err(|_| eprintln!("Error: invalid baud rate: {}", args[2]))?;
+
+	let port = SerialPort::open(port_name, baud_rate)
+		.map_err(|e| eprintln!("Error: Failed to open {}: {}", port_name, e))?;
+
+	eprintln!("--- Connected to {} at {} baud", port_name, baud_rate);
+	eprintln!("--- Quit: Ctrl+] | Menu: Ctrl+T, then h (hex view) or e (local echo)");
+
+	let mut terminal = Miniterm::new(port);
+	terminal.run().map_err(|e| eprintln!("Error: {}", e))?;
+
+	eprintln!();
+	eprintln!("--- Disconnected");
+	Ok(())
+}
+
+fn main() {
+	if let Err(()) = do_main() {
+		std::process::exit(1);
+	}
+}
@@ -0,0 +1,254 @@
+//! Monitor a serial port and print the traffic as newline-delimited JSON.
+//!
+//! Each line on stdout is one JSON record:
+//! a `port` record for each available port with `--list`,
+//! a `settings` record when the port is opened,
+//! and a `frame` or `invalid` record for each frame or each invalid byte seen on the port.
+//! Lines typed on stdin are transmitted on the port, and show up as frames in the `tx` direction.
+
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use serial2::capture::{CaptureClock, CaptureSink, Direction, Tap};
+use serial2::framing::{Framer, SlipFramer};
+use serial2::ppp::HdlcFramer;
+use serial2::protocol::firmata::{FirmataFramer, Message};
+use serial2::SerialPort;
+
+/// The framer used to split the traffic into frames.
+#[derive(Copy, Clone)]
+enum FramerKind {
+	/// Each chunk of data is reported as it is received.
+	Raw,
+
+	/// SLIP (RFC 1055) frames.
+	Slip,
+
+	/// HDLC-like frames as used by PPP.
+	Hdlc,
+
+	/// Firmata messages.
+	Firmata,
+}
+
+impl FramerKind {
+	fn parse(name: &str) -> Option<Self> {
+		match name {
+			"raw" => Some(Self::Raw),
+			"slip" => Some(Self::Slip),
+			"hdlc" => Some(Self::Hdlc),
+			"firmata" => Some(Self::Firmata),
+			_ => None,
+		}
+	}
+
+	/// Decode a frame from the start of the data.
+	///
+	/// Returns the number of bytes used and the decoded fields.
+	fn decode(self, data: &[u8]) -> std::io::Result<Option<(usize, Value)>> {
+		match self {
+			Self::Raw => Ok(Some((data.len(), Value::Null))),
+			Self::Slip => Ok(SlipFramer.decode(data)?.map(|(frame, len)| (len, json!({ "payload": hex(&frame) })))),
+			Self::Hdlc => Ok(HdlcFramer.decode(data)?.map(|(frame, len)| (len, json!({ "payload": hex(&frame) })))),
+			Self::Firmata => Ok(FirmataFramer.decode(data)?.map(|(message, len)| (len, firmata_fields(&message)))),
+		}
+	}
+}
+
+/// A capture sink that splits the traffic into frames and prints them as JSON.
+struct JsonMonitor {
+	/// The framer to use.
+	framer: FramerKind,
+
+	/// Received data that does not form a complete frame yet.
+	rx_buffer: Vec<u8>,
+
+	/// Transmitted data that does not form a complete frame yet.
+	tx_buffer: Vec<u8>,
+}
+
+impl CaptureSink for JsonMonitor {
+	fn record(&mut self, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()> {
+		let framer = self.framer;
+		let buffer = match direction {
+			Direction::Rx => &mut self.rx_buffer,
+			Direction::Tx => &mut self.tx_buffer,
+		};
+		buffer.extend_from_slice(data);
+		while !buffer.is_empty() {
+			match framer.decode(buffer) {
+				Ok(Some((len, fields))) => {
+					let len = len.min(buffer.len());
+					let mut record = json!({
+						"type": "frame",
+						"timestamp": seconds(timestamp),
+						"direction": direction_name(direction),
+						"hex": hex(&buffer[..len]),
+					});
+					if !fields.is_null() {
+						record["decoded"] = fields;
+					}
+					print_record(&record)?;
+					buffer.drain(..len);
+				},
+				Ok(None) => break,
+				Err(e) => {
+					print_record(&json!({
+						"type": "invalid",
+						"timestamp": seconds(timestamp),
+						"direction": direction_name(direction),
+						"hex": hex(&buffer[..1]),
+						"error": e.to_string(),
+					}))?;
+					buffer.remove(0);
+				},
+			}
+		}
+		Ok(())
+	}
+}
+
+fn do_main() -> Result<(), ()> {
+	let args: Vec<_> = std::env::args().collect();
+	let prog_name = args[0].rsplit_once('/').map(|(_parent, name)| name).unwrap_or(&args[0]);
+	if args.len() == 2 && args[1] == "--list" {
+		return list_ports();
+	}
+	if args.len() != 3 && args.len() != 4 {
+		eprintln!("Usage: {} PORT BAUD [raw|slip|hdlc|firmata]", prog_name);
+		eprintln!("       {} --list", prog_name);
+		return Err(());
+	}
+
+	let port_name = &args[1];
+	let baud_rate: u32 = args[2]
+		.parse()
+		.map_err(|_| eprintln!("Error: invalid baud rate: {}", args[2]))?;
+	let framer = match args.get(3) {
+		None => FramerKind::Raw,
+		Some(name) => FramerKind::parse(name).ok_or_else(|| eprintln!("Error: unknown framer: {}", name))?,
+	};
+
+	let mut port = SerialPort::open(port_name, baud_rate)
+		.map_err(|e| eprintln!("Error: Failed to open {}: {}", port_name, e))?;
+	port.set_read_timeout(Duration::from_secs(1))
+		.map_err(|e| eprintln!("Error: Failed to set read timeout: {}", e))?;
+	print_settings(&port, port_name)?;
+
+	let monitor = JsonMonitor {
+		framer,
+		rx_buffer: Vec::new(),
+		tx_buffer: Vec::new(),
+	};
+	let tap = Arc::new(Tap::new(port, monitor));
+	tap.set_clock(CaptureClock::new());
+
+	// Spawn a thread to transmit lines read from stdin.
+	std::thread::spawn({
+		let tap = tap.clone();
+		move || {
+			for line in std::io::stdin().lock().lines() {
+				let Ok(mut line) = line else { break };
+				line.push('\n');
+				if let Err(e) = tap.write_all(line.as_bytes()) {
+					eprintln!("Error: Failed to write to serial port: {}", e);
+					std::process::exit(1);
+				}
+			}
+		}
+	});
+
+	// Read from the serial port in the main thread: the tap hands all data to the monitor.
+	let mut buffer = [0; 512];
+	loop {
+		match tap.read(&mut buffer) {
+			Ok(0) => return Ok(()),
+			Ok(_) => (),
+			Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => (),
+			Err(e) => {
+				eprintln!("Error: Failed to read from {}: {}", port_name, e);
+				return Err(());
+			},
+		}
+		if let Some(e) = tap.take_capture_error() {
+			eprintln!("Error: Failed to write to stdout: {}", e);
+			return Err(());
+		}
+	}
+}
+
+/// Print a record for each available serial port.
+fn list_ports() -> Result<(), ()> {
+	let ports = SerialPort::available_ports().map_err(|e| eprintln!("Error: Failed to enumerate serial ports: {}", e))?;
+	for port in ports {
+		print_record(&json!({ "type": "port", "path": port.display().to_string() }))
+			.map_err(|e| eprintln!("Error: Failed to write to stdout: {}", e))?;
+	}
+	Ok(())
+}
+
+/// Print a record with the settings of the serial port.
+fn print_settings(port: &SerialPort, port_name: &str) -> Result<(), ()> {
+	let settings = port
+		.get_configuration()
+		.map_err(|e| eprintln!("Error: Failed to read the configuration of {}: {}", port_name, e))?;
+	let record = json!({
+		"type": "settings",
+		"timestamp": seconds(SystemTime::now()),
+		"port": port_name,
+		"baud_rate": settings.get_baud_rate().ok(),
+		"char_size": settings.get_char_size().ok(),
+		"stop_bits": settings.get_stop_bits().ok(),
+		"parity": settings.get_parity().ok(),
+		"flow_control": settings.get_flow_control().ok(),
+	});
+	print_record(&record).map_err(|e| eprintln!("Error: Failed to write to stdout: {}", e))
+}
+
+/// Get the decoded fields of a Firmata message.
+fn firmata_fields(message: &Message) -> Value {
+	match message {
+		Message::ProtocolVersion(version) => json!({
+			"message": "protocol_version",
+			"major": version.major(),
+			"minor": version.minor(),
+		}),
+		Message::DigitalPort { port, value } => json!({ "message": "digital_port", "port": port, "value": value }),
+		Message::Analog { channel, value } => json!({ "message": "analog", "channel": channel, "value": value }),
+		Message::Sysex { command, data } => json!({ "message": "sysex", "command": command, "data": hex(data) }),
+	}
+}
+
+/// Print a JSON record on a line of its own.
+fn print_record(record: &Value) -> std::io::Result<()> {
+	let mut stdout = std::io::stdout().lock();
+	serde_json::to_writer(&mut stdout, record)?;
+	stdout.write_all(b"\n")?;
+	stdout.flush()
+}
+
+/// Get the name of a direction as used in the records.
+fn direction_name(direction: Direction) -> &'static str {
+	match direction {
+		Direction::Rx => "rx",
+		Direction::Tx => "tx",
+	}
+}
+
+/// Get a timestamp as seconds since the Unix epoch.
+fn seconds(timestamp: SystemTime) -> f64 {
+	timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+/// Format data as lowercase hexadecimal.
+fn hex(data: &[u8]) -> String {
+	data.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn main() {
+	if let Err(()) = do_main() {
+		std::process::exit(1);
+	}
+}
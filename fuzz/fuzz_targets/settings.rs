@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+	#[cfg(unix)]
+	serial2::fuzz::settings_round_trip(data);
+});
@@ -0,0 +1,82 @@
+/* C bindings for the serial2 crate.
+ *
+ * Build the shared library with:
+ *   cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * All functions return SERIAL2_OK on success or one of the SERIAL2_ERR_* codes on failure.
+ * A description of the last error on the calling thread is available with serial2_last_error().
+ */
+
+#ifndef SERIAL2_H
+#define SERIAL2_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SERIAL2_OK 0
+#define SERIAL2_ERR_INVALID_ARGUMENT -1
+#define SERIAL2_ERR_TIMED_OUT -2
+#define SERIAL2_ERR_NOT_FOUND -3
+#define SERIAL2_ERR_PERMISSION_DENIED -4
+#define SERIAL2_ERR_BUSY -5
+#define SERIAL2_ERR_INTERRUPTED -6
+#define SERIAL2_ERR_UNSUPPORTED -7
+#define SERIAL2_ERR_OTHER -99
+
+#define SERIAL2_PARITY_NONE 0
+#define SERIAL2_PARITY_ODD 1
+#define SERIAL2_PARITY_EVEN 2
+
+#define SERIAL2_FLOW_CONTROL_NONE 0
+#define SERIAL2_FLOW_CONTROL_XON_XOFF 1
+#define SERIAL2_FLOW_CONTROL_RTS_CTS 2
+
+/* An opaque handle to an open serial port. */
+typedef struct Serial2Port Serial2Port;
+
+/* The configuration of a serial port. */
+typedef struct Serial2Settings {
+	uint32_t baud_rate;
+	uint8_t char_size;
+	uint8_t stop_bits;
+	int parity;
+	int flow_control;
+} Serial2Settings;
+
+const char * serial2_last_error(void);
+
+int serial2_open(const char * name, uint32_t baud_rate, Serial2Port ** port);
+void serial2_close(Serial2Port * port);
+
+int serial2_get_configuration(const Serial2Port * port, Serial2Settings * settings);
+/* The setters need exclusive access: the handle must not be used by any other thread during the call. */
+int serial2_set_configuration(Serial2Port * port, const Serial2Settings * settings);
+int serial2_set_read_timeout(Serial2Port * port, uint32_t timeout_ms);
+int serial2_set_write_timeout(Serial2Port * port, uint32_t timeout_ms);
+
+int serial2_read(const Serial2Port * port, uint8_t * buffer, size_t len, size_t * read);
+int serial2_write(const Serial2Port * port, const uint8_t * data, size_t len, size_t * written);
+int serial2_write_all(const Serial2Port * port, const uint8_t * data, size_t len);
+int serial2_flush(const Serial2Port * port);
+int serial2_discard_buffers(const Serial2Port * port);
+
+int serial2_set_rts(const Serial2Port * port, bool state);
+int serial2_set_dtr(const Serial2Port * port, bool state);
+int serial2_read_cts(const Serial2Port * port, bool * state);
+int serial2_read_dsr(const Serial2Port * port, bool * state);
+int serial2_read_ri(const Serial2Port * port, bool * state);
+int serial2_read_cd(const Serial2Port * port, bool * state);
+
+/* Returns SERIAL2_ERR_INVALID_ARGUMENT if callback is NULL. */
+int serial2_available_ports(void (*callback)(const char * name, void * user_data), void * user_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
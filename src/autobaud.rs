@@ -0,0 +1,165 @@
+//! Detection of the baud rate of a peer.
+//!
+//! Many bootloaders and the LIN bus let the peer choose the baud rate:
+//! the peer sends a sync byte (normally `0x55`), optionally preceded by a break condition,
+//! and the receiver adjusts its baud rate to match.
+//!
+//! The timing of individual edges on the line is not visible through the serial port API of the operating system.
+//! Instead, an [`AutoBaud`] cycles through a list of candidate baud rates
+//! and listens at each rate until it receives the expected sync pattern.
+//! A sync byte received at the wrong baud rate decodes as a different value (or as a framing error),
+//! so the first rate that yields the expected pattern is the rate of the peer.
+//!
+//! The peer must repeat the sync pattern until it is answered.
+//! Make sure the dwell time at each baud rate is longer than the interval at which the peer repeats the pattern.
+
+use std::time::{Duration, Instant};
+
+use crate::{SerialPort, COMMON_BAUD_RATES};
+
+/// Detects the baud rate of a peer by listening for a sync pattern.
+///
+/// See the [module documentation][self] for more information.
+#[derive(Debug, Clone)]
+pub struct AutoBaud {
+	/// The baud rates to try, in order.
+	candidates: Vec<u32>,
+
+	/// The time to listen at each baud rate.
+	dwell_time: Duration,
+
+	/// The expected sync byte.
+	sync_byte: u8,
+
+	/// If true, the sync byte must be preceded by a break condition.
+	expect_break: bool,
+}
+
+impl AutoBaud {
+	/// Create a new detector that tries the given baud rates in order.
+	///
+	/// The detector listens for `0x55` without a preceding break, for 100 milliseconds at each baud rate.
+	pub fn new(candidates: impl Into<Vec<u32>>) -> Self {
+		Self {
+			candidates: candidates.into(),
+			dwell_time: Duration::from_millis(100),
+			sync_byte: 0x55,
+			expect_break: false,
+		}
+	}
+
+	/// Get the baud rates to try, in order.
+	pub fn candidates(&self) -> &[u32] {
+		&self.candidates
+	}
+
+	/// Set the time to listen at each baud rate.
+	pub fn set_dwell_time(&mut self, dwell_time: Duration) {
+		self.dwell_time = dwell_time;
+	}
+
+	/// Get the time to listen at each baud rate.
+	pub fn get_dwell_time(&self) -> Duration {
+		self.dwell_time
+	}
+
+	/// Set the expected sync byte.
+	pub fn set_sync_byte(&mut self, sync_byte: u8) {
+		self.sync_byte = sync_byte;
+	}
+
+	/// Get the expected sync byte.
+	pub fn get_sync_byte(&self) -> u8 {
+		self.sync_byte
+	}
+
+	/// Require a break condition before the sync byte, like the header of a LIN frame.
+	///
+	/// A break is received as a `0x00` byte,
+	/// so the serial port must not be configured to ignore break conditions or to mark them specially.
+	pub fn set_expect_break(&mut self, expect_break: bool) {
+		self.expect_break = expect_break;
+	}
+
+	/// Check if a break condition is required before the sync byte.
+	pub fn get_expect_break(&self) -> bool {
+		self.expect_break
+	}
+
+	/// Detect the baud rate of the peer and configure the serial port to use it.
+	///
+	/// This cycles through the candidate baud rates until the sync pattern is received or the timeout expires.
+	/// On success, the serial port keeps the detected baud rate and the detected rate is returned.
+	/// All data received before the end of the sync pattern is discarded,
+	/// data received after the sync pattern is left in the input buffer.
+	///
+	/// If the timeout expires, the original baud rate is restored
+	/// and an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	///
+	/// This function temporarily changes the read timeout of the serial port.
+	/// The original read timeout is restored before the function returns.
+	pub fn detect(&self, port: &mut SerialPort, timeout: Duration) -> std::io::Result<u32> {
+		if self.candidates.is_empty() {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "no candidate baud rates"));
+		}
+		let original_settings = port.get_configuration()?;
+		let original_timeout = port.get_read_timeout()?;
+		let result = self.detect_inner(port, Instant::now() + timeout);
+		if result.is_err() {
+			port.set_configuration(&original_settings)?;
+		}
+		port.set_read_timeout(original_timeout)?;
+		result
+	}
+
+	/// Cycle through the candidate baud rates until the sync pattern is received or the deadline passes.
+	fn detect_inner(&self, port: &mut SerialPort, deadline: Instant) -> std::io::Result<u32> {
+		let mut settings = port.get_configuration()?;
+		for &baud_rate in self.candidates.iter().cycle() {
+			if Instant::now() >= deadline {
+				return Err(std::io::ErrorKind::TimedOut.into());
+			}
+			settings.set_baud_rate(baud_rate)?;
+			port.set_configuration(&settings)?;
+			port.discard_input_buffer()?;
+			let listen_until = deadline.min(Instant::now() + self.dwell_time);
+			if self.listen(port, listen_until)? {
+				return Ok(baud_rate);
+			}
+		}
+		unreachable!("cycling through a non-empty list never ends")
+	}
+
+	/// Listen for the sync pattern until the deadline passes.
+	///
+	/// The pattern is read one byte at a time, to leave data after the pattern in the input buffer.
+	fn listen(&self, port: &mut SerialPort, deadline: Instant) -> std::io::Result<bool> {
+		let mut previous = None;
+		loop {
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return Ok(false);
+			}
+			port.set_read_timeout(remaining)?;
+			let mut byte = [0];
+			match port.read(&mut byte) {
+				Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+				Ok(_) => (),
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => return Ok(false),
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			}
+			if byte[0] == self.sync_byte && (!self.expect_break || previous == Some(0x00)) {
+				return Ok(true);
+			}
+			previous = Some(byte[0]);
+		}
+	}
+}
+
+impl Default for AutoBaud {
+	/// Create a detector for the [`COMMON_BAUD_RATES`].
+	fn default() -> Self {
+		Self::new(COMMON_BAUD_RATES)
+	}
+}
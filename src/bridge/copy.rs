@@ -0,0 +1,129 @@
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// The time to wait before retrying an operation that would block.
+const RETRY_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The number of bytes forwarded by [`copy_bidirectional()`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct CopyStats {
+	/// The number of bytes forwarded from `a` to `b`.
+	a_to_b: u64,
+
+	/// The number of bytes forwarded from `b` to `a`.
+	b_to_a: u64,
+}
+
+impl CopyStats {
+	/// Get the number of bytes forwarded from `a` to `b`.
+	pub fn a_to_b(&self) -> u64 {
+		self.a_to_b
+	}
+
+	/// Get the number of bytes forwarded from `b` to `a`.
+	pub fn b_to_a(&self) -> u64 {
+		self.b_to_a
+	}
+}
+
+/// Forward data in both directions between two streams until one of them ends.
+///
+/// This can be used to connect two serial ports, or a serial port and a network socket.
+/// Each direction is forwarded by a separate thread.
+/// Both threads are joined before the function returns.
+///
+/// Read timeouts and reads that would block are retried, so both streams should have a read timeout:
+/// the timeout determines how quickly the function notices that the other direction ended.
+/// Writes that time out are also retried with the remaining data,
+/// so data is never lost when transmission is held back by flow control.
+///
+/// The function returns when a stream reports the end of the data (a read of 0 bytes) or an error.
+/// On the end of the data, the number of forwarded bytes is returned.
+/// Otherwise, the first error is returned.
+pub fn copy_bidirectional<A, B>(a: &A, b: &B) -> std::io::Result<CopyStats>
+where
+	A: Sync + ?Sized,
+	B: Sync + ?Sized,
+	for<'x> &'x A: Read + Write,
+	for<'x> &'x B: Read + Write,
+{
+	let done = AtomicBool::new(false);
+	let (a_to_b, b_to_a) = std::thread::scope(|scope| {
+		let a_to_b = std::thread::Builder::new()
+			.name("serial2-copy".into())
+			.spawn_scoped(scope, || copy_until_done(a, b, &done));
+		let b_to_a = copy_until_done(b, a, &done);
+		let a_to_b = match a_to_b {
+			Ok(thread) => thread.join().unwrap_or_else(|e| std::panic::resume_unwind(e)),
+			Err(e) => Err(e),
+		};
+		(a_to_b, b_to_a)
+	});
+	Ok(CopyStats {
+		a_to_b: a_to_b?,
+		b_to_a: b_to_a?,
+	})
+}
+
+/// Forward data from `source` to `sink` until a stream ends or the other direction is done.
+///
+/// Returns the number of forwarded bytes.
+fn copy_until_done<S, D>(source: &S, sink: &D, done: &AtomicBool) -> std::io::Result<u64>
+where
+	S: ?Sized,
+	D: ?Sized,
+	for<'x> &'x S: Read,
+	for<'x> &'x D: Write,
+{
+	let result = copy_inner(source, sink, done);
+	done.store(true, Ordering::Relaxed);
+	result
+}
+
+/// Forward data from `source` to `sink` until a stream ends or `done` is set.
+fn copy_inner<S, D>(mut source: &S, mut sink: &D, done: &AtomicBool) -> std::io::Result<u64>
+where
+	S: ?Sized,
+	D: ?Sized,
+	for<'x> &'x S: Read,
+	for<'x> &'x D: Write,
+{
+	let mut total = 0;
+	let mut buffer = [0; 4096];
+	while !done.load(Ordering::Relaxed) {
+		let read = match source.read(&mut buffer) {
+			Ok(0) => break,
+			Ok(read) => read,
+			Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+				std::thread::sleep(RETRY_INTERVAL);
+				continue;
+			},
+			Err(e) if is_retryable(&e) => continue,
+			Err(e) => return Err(e),
+		};
+
+		let mut data = &buffer[..read];
+		while !data.is_empty() {
+			if done.load(Ordering::Relaxed) {
+				return Ok(total);
+			}
+			match sink.write(data) {
+				Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "failed to write whole buffer")),
+				Ok(written) => {
+					data = &data[written..];
+					total += written as u64;
+				},
+				Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => std::thread::sleep(RETRY_INTERVAL),
+				Err(e) if is_retryable(&e) => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+	Ok(total)
+}
+
+/// Check if an error means that the operation should simply be retried.
+fn is_retryable(error: &std::io::Error) -> bool {
+	matches!(error.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::Interrupted)
+}
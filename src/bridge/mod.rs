@@ -0,0 +1,21 @@
+//! Bridges between serial ports and network sockets.
+//!
+//! A bridge owns background threads that forward data between a serial port and the network,
+//! so a serial device can be used by applications on other machines.
+//!
+//! The [`udp`] module forwards serial frames as UDP datagrams, for example to fan out telemetry to multiple listeners.
+//! The [`tcp`] module shares a serial port with TCP clients, with policies for which clients may write.
+//! The `mqtt` module publishes serial frames to an MQTT broker and requires the `mqtt` feature.
+//!
+//! For simple byte stream proxies, [`copy_bidirectional()`] forwards data in both directions
+//! between two serial ports, or between a serial port and a socket.
+
+mod copy;
+pub use copy::{copy_bidirectional, CopyStats};
+
+#[cfg(any(feature = "doc", feature = "mqtt"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "mqtt")))]
+pub mod mqtt;
+
+pub mod tcp;
+pub mod udp;
@@ -0,0 +1,678 @@
+//! Publishing serial frames to an MQTT broker.
+//!
+//! An [`MqttBridge`] reads frames from a serial port with any [`Framer`] and publishes each frame to an MQTT topic.
+//! Messages published to a command topic are written to the serial port,
+//! optionally encoded with a frame encoder set with [`MqttBridge::set_frame_encoder()`].
+//!
+//! The serial side is an [`AutoReconnectPort`], so the bridge survives a serial adapter being unplugged.
+//! The bridge also reconnects to the broker with exponential backoff when the connection is lost.
+//! Frames received while the broker is not connected are discarded and counted in [`MqttBridgeStats::dropped_frames()`].
+//!
+//! The bridge implements the subset of MQTT 3.1.1 it needs, without external dependencies:
+//! all messages are published and subscribed with QoS 0 over a plain TCP connection.
+//! For TLS or higher QoS levels, use a full MQTT client library instead.
+
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::framing::Framer;
+use crate::reconnect::AutoReconnectPort;
+
+/// The read timeout used by the background threads.
+///
+/// This limits how long it takes for the background threads to notice that the bridge was dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The delay before the first reconnection attempt after the broker connection failed.
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+
+/// The maximum delay between reconnection attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The default keep alive interval.
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// The MQTT packet types used by the bridge.
+mod packet {
+	/// Connection request.
+	pub const CONNECT: u8 = 0x10;
+
+	/// Connection acknowledgement.
+	pub const CONNACK: u8 = 0x20;
+
+	/// Published message, without flags.
+	pub const PUBLISH: u8 = 0x30;
+
+	/// Subscription request, including the required flags.
+	pub const SUBSCRIBE: u8 = 0x82;
+
+	/// Ping request.
+	pub const PINGREQ: u8 = 0xC0;
+
+	/// Ping response.
+	pub const PINGRESP: u8 = 0xD0;
+
+	/// Disconnect notification.
+	pub const DISCONNECT: u8 = 0xE0;
+}
+
+/// A function that encodes the payload of a command message before it is written to the serial port.
+type FrameEncoder = Box<dyn Fn(&[u8]) -> Vec<u8> + Send>;
+
+/// The broker connection settings of an [`MqttBridge`].
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+	/// The address of the broker, as `host:port`.
+	broker: String,
+
+	/// The client identifier.
+	client_id: String,
+
+	/// The topic that frames from the serial port are published to.
+	frame_topic: String,
+
+	/// The topic with commands for the serial port.
+	command_topic: Option<String>,
+
+	/// The keep alive interval.
+	keep_alive: Duration,
+
+	/// The user name and password.
+	credentials: Option<(String, String)>,
+}
+
+impl MqttConfig {
+	/// Create a new configuration.
+	///
+	/// The broker address is given as `host:port`, for example `"localhost:1883"`.
+	/// Frames from the serial port are published to `frame_topic`.
+	pub fn new(broker: impl Into<String>, client_id: impl Into<String>, frame_topic: impl Into<String>) -> Self {
+		Self {
+			broker: broker.into(),
+			client_id: client_id.into(),
+			frame_topic: frame_topic.into(),
+			command_topic: None,
+			keep_alive: DEFAULT_KEEP_ALIVE,
+			credentials: None,
+		}
+	}
+
+	/// Get the address of the broker.
+	pub fn broker(&self) -> &str {
+		&self.broker
+	}
+
+	/// Get the client identifier.
+	pub fn client_id(&self) -> &str {
+		&self.client_id
+	}
+
+	/// Get the topic that frames from the serial port are published to.
+	pub fn frame_topic(&self) -> &str {
+		&self.frame_topic
+	}
+
+	/// Set the topic with commands for the serial port.
+	///
+	/// The bridge subscribes to this topic and writes all messages to the serial port.
+	/// By default, there is no command topic.
+	pub fn set_command_topic(&mut self, topic: Option<String>) {
+		self.command_topic = topic;
+	}
+
+	/// Get the topic with commands for the serial port.
+	pub fn get_command_topic(&self) -> Option<&str> {
+		self.command_topic.as_deref()
+	}
+
+	/// Set the keep alive interval.
+	///
+	/// The bridge sends a ping when nothing was sent for this long,
+	/// and reconnects when the broker does not respond within the same interval.
+	/// The interval is rounded down to whole seconds, with a minimum of one second.
+	///
+	/// The default is 30 seconds.
+	pub fn set_keep_alive(&mut self, keep_alive: Duration) {
+		self.keep_alive = Duration::from_secs(keep_alive.as_secs().clamp(1, u16::MAX.into()));
+	}
+
+	/// Get the keep alive interval.
+	pub fn get_keep_alive(&self) -> Duration {
+		self.keep_alive
+	}
+
+	/// Set the user name and password to log in to the broker.
+	pub fn set_credentials(&mut self, credentials: Option<(String, String)>) {
+		self.credentials = credentials;
+	}
+
+	/// Get the user name and password to log in to the broker.
+	pub fn get_credentials(&self) -> Option<(&str, &str)> {
+		self.credentials.as_ref().map(|(user, password)| (user.as_str(), password.as_str()))
+	}
+}
+
+/// Statistics of an [`MqttBridge`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct MqttBridgeStats {
+	/// The number of frames published to the broker.
+	frames_published: u64,
+
+	/// The number of command messages written to the serial port.
+	commands_received: u64,
+
+	/// The number of frames discarded because the broker was not connected.
+	dropped_frames: u64,
+
+	/// The number of corrupted frames skipped by the framer.
+	invalid_frames: u64,
+
+	/// The number of connections made to the broker.
+	connections: u64,
+}
+
+impl MqttBridgeStats {
+	/// Get the number of frames published to the broker.
+	pub fn frames_published(&self) -> u64 {
+		self.frames_published
+	}
+
+	/// Get the number of command messages written to the serial port.
+	pub fn commands_received(&self) -> u64 {
+		self.commands_received
+	}
+
+	/// Get the number of frames discarded because the broker was not connected.
+	pub fn dropped_frames(&self) -> u64 {
+		self.dropped_frames
+	}
+
+	/// Get the number of corrupted frames skipped by the framer.
+	pub fn invalid_frames(&self) -> u64 {
+		self.invalid_frames
+	}
+
+	/// Get the number of connections made to the broker.
+	pub fn connections(&self) -> u64 {
+		self.connections
+	}
+}
+
+/// Forwards frames between a serial port and an MQTT broker.
+///
+/// See the [module documentation][self] for more information.
+///
+/// Dropping the bridge disconnects from the broker and stops the background threads.
+pub struct MqttBridge {
+	/// The state shared with the background threads.
+	shared: Arc<Shared>,
+
+	/// The background threads.
+	threads: Vec<JoinHandle<()>>,
+}
+
+impl MqttBridge {
+	/// Create a new bridge between a serial port and an MQTT broker.
+	///
+	/// This spawns two background threads: one that reads frames from the serial port,
+	/// and one that maintains the connection to the broker.
+	/// The read timeout of the serial port is changed for use by the background thread.
+	///
+	/// This function does not wait for the connection to the broker.
+	pub fn new<F>(port: AutoReconnectPort, framer: F, config: MqttConfig) -> std::io::Result<Self>
+	where
+		F: Framer<Frame = Vec<u8>> + Send + 'static,
+	{
+		let mut port = port;
+		port.set_read_timeout(POLL_INTERVAL);
+		let shared = Arc::new(Shared {
+			port,
+			config,
+			stream: Mutex::new(None),
+			state: Mutex::new(State::default()),
+		});
+
+		let mut bridge = Self {
+			shared,
+			threads: Vec::with_capacity(2),
+		};
+		bridge.spawn("serial2-mqtt-serial", move |shared| run_serial(shared, framer))?;
+		bridge.spawn("serial2-mqtt", run_broker)?;
+		Ok(bridge)
+	}
+
+	/// Get the serial port.
+	pub fn port(&self) -> &AutoReconnectPort {
+		&self.shared.port
+	}
+
+	/// Get the broker connection settings.
+	pub fn config(&self) -> &MqttConfig {
+		&self.shared.config
+	}
+
+	/// Check if the bridge is connected to the broker.
+	pub fn is_connected(&self) -> bool {
+		self.shared.stream().is_some()
+	}
+
+	/// Set a function that encodes command messages before they are written to the serial port.
+	///
+	/// For example, use [`SlipFramer::encode()`][crate::framing::SlipFramer::encode] when the serial side uses SLIP.
+	/// By default, the payload is written as-is.
+	pub fn set_frame_encoder<F>(&self, encoder: F)
+	where
+		F: Fn(&[u8]) -> Vec<u8> + Send + 'static,
+	{
+		self.shared.lock().encoder = Some(Box::new(encoder));
+	}
+
+	/// Remove the frame encoder, so command messages are written as-is.
+	pub fn clear_frame_encoder(&self) {
+		self.shared.lock().encoder = None;
+	}
+
+	/// Get the statistics of the bridge.
+	pub fn stats(&self) -> MqttBridgeStats {
+		self.shared.lock().stats
+	}
+
+	/// Take the error that caused the last disconnect from the broker, if any.
+	pub fn take_error(&self) -> Option<std::io::Error> {
+		self.shared.lock().error.take()
+	}
+
+	/// Spawn a background thread.
+	fn spawn<F>(&mut self, name: &str, run: F) -> std::io::Result<()>
+	where
+		F: FnOnce(&Shared) + Send + 'static,
+	{
+		let shared = self.shared.clone();
+		let thread = std::thread::Builder::new()
+			.name(name.into())
+			.spawn(move || run(&shared))?;
+		self.threads.push(thread);
+		Ok(())
+	}
+}
+
+impl MqttBridge {
+	/// Stop the background threads and wait for them to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		crate::shutdown::join_threads(self.threads.drain(..), deadline)
+	}
+}
+
+impl Drop for MqttBridge {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl crate::shutdown::Shutdown for MqttBridge {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// State shared between the bridge and the background threads.
+struct Shared {
+	/// The serial port.
+	port: AutoReconnectPort,
+
+	/// The broker connection settings.
+	config: MqttConfig,
+
+	/// The connection to the broker, used for publishing, if connected.
+	stream: Mutex<Option<TcpStream>>,
+
+	/// The mutable state.
+	state: Mutex<State>,
+}
+
+/// The mutable state of a bridge.
+#[derive(Default)]
+struct State {
+	/// The function that encodes command messages.
+	encoder: Option<FrameEncoder>,
+
+	/// The time a packet was last sent to the broker.
+	last_sent: Option<Instant>,
+
+	/// The statistics.
+	stats: MqttBridgeStats,
+
+	/// The error that caused the last disconnect from the broker.
+	error: Option<std::io::Error>,
+
+	/// True if the bridge is being dropped.
+	closed: bool,
+}
+
+impl Shared {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Lock the broker connection, ignoring poisoning.
+	fn stream(&self) -> MutexGuard<'_, Option<TcpStream>> {
+		self.stream.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Send a packet to the broker.
+	///
+	/// If sending fails, the connection is shut down so the broker thread reconnects.
+	fn send(&self, packet: &[u8]) -> std::io::Result<()> {
+		let mut stream = self.stream();
+		let Some(connection) = stream.as_mut() else { return Err(std::io::ErrorKind::NotConnected.into()) };
+		if let Err(e) = connection.write_all(packet) {
+			let _ = connection.shutdown(Shutdown::Both);
+			*stream = None;
+			return Err(e);
+		}
+		drop(stream);
+		self.lock().last_sent = Some(Instant::now());
+		Ok(())
+	}
+}
+
+/// Read frames from the serial port and publish them.
+fn run_serial<F: Framer<Frame = Vec<u8>>>(shared: &Shared, mut framer: F) {
+	let mut buffer = Vec::new();
+	let mut chunk = [0; 1024];
+	loop {
+		if shared.lock().closed {
+			return;
+		}
+		match shared.port.read(&mut chunk) {
+			Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+			// The serial port reconnects by itself, so all errors are temporary.
+			Err(_) => continue,
+		}
+		while !buffer.is_empty() {
+			let frame = match framer.decode(&buffer) {
+				Ok(Some((frame, len))) => {
+					buffer.drain(..len.min(buffer.len()));
+					frame
+				},
+				Ok(None) => break,
+				Err(_) => {
+					buffer.remove(0);
+					shared.lock().stats.invalid_frames += 1;
+					continue;
+				},
+			};
+			let packet = encode_publish(&shared.config.frame_topic, &frame);
+			let result = shared.send(&packet);
+			let mut state = shared.lock();
+			match result {
+				Ok(()) => state.stats.frames_published += 1,
+				Err(_) => state.stats.dropped_frames += 1,
+			}
+		}
+	}
+}
+
+/// Maintain the connection to the broker, reconnecting with exponential backoff.
+fn run_broker(shared: &Shared) {
+	let mut backoff = MIN_BACKOFF;
+	loop {
+		let result = run_session(shared, &mut backoff);
+		*shared.stream() = None;
+		let mut state = shared.lock();
+		if state.closed {
+			return;
+		}
+		if let Err(e) = result {
+			state.error = Some(e);
+		}
+		drop(state);
+
+		let retry_at = Instant::now() + backoff;
+		backoff = (backoff * 2).min(MAX_BACKOFF);
+		while Instant::now() < retry_at {
+			if shared.lock().closed {
+				return;
+			}
+			std::thread::sleep(POLL_INTERVAL.min(retry_at.saturating_duration_since(Instant::now())));
+		}
+	}
+}
+
+/// Connect to the broker and handle incoming packets until the connection is lost or the bridge is dropped.
+fn run_session(shared: &Shared, backoff: &mut Duration) -> std::io::Result<()> {
+	let config = &shared.config;
+	let mut stream = TcpStream::connect(&config.broker)?;
+	stream.set_read_timeout(Some(POLL_INTERVAL))?;
+	stream.set_nodelay(true)?;
+	stream.write_all(&encode_connect(config))?;
+
+	let mut reader = PacketReader::new(stream.try_clone()?);
+	let deadline = Instant::now() + config.keep_alive;
+	loop {
+		match reader.read_packet()? {
+			Some((packet::CONNACK, body)) => {
+				if body.get(1) != Some(&0) {
+					let code = body.get(1).copied().unwrap_or(0xFF);
+					return Err(std::io::Error::new(
+						std::io::ErrorKind::ConnectionRefused,
+						format!("the broker refused the connection with return code {code}"),
+					));
+				}
+				break;
+			},
+			Some(_) => return Err(invalid_data("expected a CONNACK packet")),
+			None if Instant::now() >= deadline => return Err(timed_out()),
+			None => (),
+		}
+	}
+	if let Some(topic) = &config.command_topic {
+		stream.write_all(&encode_subscribe(topic))?;
+	}
+
+	*shared.stream() = Some(stream);
+	let mut state = shared.lock();
+	state.stats.connections += 1;
+	state.last_sent = Some(Instant::now());
+	drop(state);
+	*backoff = MIN_BACKOFF;
+
+	let mut ping_sent: Option<Instant> = None;
+	loop {
+		let state = shared.lock();
+		if state.closed {
+			drop(state);
+			let _ = shared.send(&[packet::DISCONNECT, 0]);
+			return Ok(());
+		}
+		let last_sent = state.last_sent;
+		drop(state);
+
+		match reader.read_packet()? {
+			Some((header, body)) if header & 0xF0 == packet::PUBLISH => handle_publish(shared, header, &body)?,
+			Some((packet::PINGRESP, _)) => ping_sent = None,
+			Some(_) => (),
+			None => (),
+		}
+
+		let now = Instant::now();
+		if let Some(ping_sent) = ping_sent {
+			if now - ping_sent >= config.keep_alive {
+				return Err(timed_out());
+			}
+		} else if last_sent.is_none_or(|last_sent| now - last_sent >= config.keep_alive) {
+			shared.send(&[packet::PINGREQ, 0])?;
+			ping_sent = Some(now);
+		}
+	}
+}
+
+/// Handle a PUBLISH packet from the broker.
+fn handle_publish(shared: &Shared, header: u8, body: &[u8]) -> std::io::Result<()> {
+	let (topic, rest) = decode_string(body)?;
+	let qos = (header >> 1) & 0x03;
+	// Messages with a QoS above 0 have a packet identifier. We subscribe with QoS 0, so we never need to acknowledge.
+	let payload = if qos > 0 { rest.get(2..).ok_or_else(|| invalid_data("truncated PUBLISH packet"))? } else { rest };
+	if shared.config.command_topic.as_deref() != Some(topic) {
+		return Ok(());
+	}
+
+	let mut state = shared.lock();
+	let data = match &state.encoder {
+		Some(encoder) => encoder(payload),
+		None => payload.to_vec(),
+	};
+	state.stats.commands_received += 1;
+	drop(state);
+	// Errors are not fatal for the broker connection: the serial port reconnects by itself.
+	let _ = shared.port.write_all(&data);
+	Ok(())
+}
+
+/// Reads MQTT packets from a TCP stream.
+struct PacketReader {
+	/// The TCP stream.
+	stream: TcpStream,
+
+	/// Received data that does not form a complete packet yet.
+	buffer: Vec<u8>,
+}
+
+impl PacketReader {
+	/// Create a new packet reader.
+	fn new(stream: TcpStream) -> Self {
+		Self {
+			stream,
+			buffer: Vec::new(),
+		}
+	}
+
+	/// Read a packet, or return `None` if no complete packet was received before the read timeout.
+	///
+	/// Returns the first byte of the fixed header and the rest of the packet.
+	fn read_packet(&mut self) -> std::io::Result<Option<(u8, Vec<u8>)>> {
+		loop {
+			if let Some((header, body, len)) = decode_packet(&self.buffer)? {
+				self.buffer.drain(..len);
+				return Ok(Some((header, body)));
+			}
+			let mut chunk = [0; 1024];
+			match self.stream.read(&mut chunk) {
+				Ok(0) => {
+					return Err(std::io::Error::new(
+						std::io::ErrorKind::UnexpectedEof,
+						"the broker closed the connection",
+					))
+				},
+				Ok(read) => self.buffer.extend_from_slice(&chunk[..read]),
+				Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(None),
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => return Ok(None),
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+}
+
+/// Decode a packet from the start of a buffer.
+///
+/// Returns the first byte of the fixed header, the rest of the packet and the total length of the packet.
+fn decode_packet(data: &[u8]) -> std::io::Result<Option<(u8, Vec<u8>, usize)>> {
+	let Some(&header) = data.first() else { return Ok(None) };
+	let mut len = 0usize;
+	for (i, &byte) in data[1..].iter().enumerate().take(4) {
+		len |= usize::from(byte & 0x7F) << (7 * i);
+		if byte & 0x80 == 0 {
+			let start = i + 2;
+			let Some(body) = data.get(start..start + len) else { return Ok(None) };
+			return Ok(Some((header, body.to_vec(), start + len)));
+		}
+	}
+	if data.len() > 5 {
+		return Err(invalid_data("invalid remaining length"));
+	}
+	Ok(None)
+}
+
+/// Encode a packet with the given fixed header byte and body.
+fn encode_packet(header: u8, body: &[u8]) -> Vec<u8> {
+	let mut packet = Vec::with_capacity(body.len() + 5);
+	packet.push(header);
+	let mut len = body.len();
+	loop {
+		let byte = (len & 0x7F) as u8;
+		len >>= 7;
+		if len == 0 {
+			packet.push(byte);
+			break;
+		}
+		packet.push(byte | 0x80);
+	}
+	packet.extend_from_slice(body);
+	packet
+}
+
+/// Append a length-prefixed string or binary field.
+fn push_field(body: &mut Vec<u8>, data: &[u8]) {
+	body.extend_from_slice(&(data.len() as u16).to_be_bytes());
+	body.extend_from_slice(data);
+}
+
+/// Decode a length-prefixed string, and return it with the remaining data.
+fn decode_string(data: &[u8]) -> std::io::Result<(&str, &[u8])> {
+	let len = data.get(..2).ok_or_else(|| invalid_data("truncated string"))?;
+	let len = usize::from(u16::from_be_bytes([len[0], len[1]]));
+	let string = data.get(2..2 + len).ok_or_else(|| invalid_data("truncated string"))?;
+	let string = std::str::from_utf8(string).map_err(|_| invalid_data("invalid UTF-8 in string"))?;
+	Ok((string, &data[2 + len..]))
+}
+
+/// Encode a CONNECT packet.
+fn encode_connect(config: &MqttConfig) -> Vec<u8> {
+	let mut body = Vec::new();
+	push_field(&mut body, b"MQTT");
+	body.push(4);
+	let mut flags = 0x02;
+	if config.credentials.is_some() {
+		flags |= 0xC0;
+	}
+	body.push(flags);
+	body.extend_from_slice(&(config.keep_alive.as_secs() as u16).to_be_bytes());
+	push_field(&mut body, config.client_id.as_bytes());
+	if let Some((user, password)) = &config.credentials {
+		push_field(&mut body, user.as_bytes());
+		push_field(&mut body, password.as_bytes());
+	}
+	encode_packet(packet::CONNECT, &body)
+}
+
+/// Encode a SUBSCRIBE packet for a single topic with QoS 0.
+fn encode_subscribe(topic: &str) -> Vec<u8> {
+	let mut body = vec![0, 1];
+	push_field(&mut body, topic.as_bytes());
+	body.push(0);
+	encode_packet(packet::SUBSCRIBE, &body)
+}
+
+/// Encode a PUBLISH packet with QoS 0.
+fn encode_publish(topic: &str, payload: &[u8]) -> Vec<u8> {
+	let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+	push_field(&mut body, topic.as_bytes());
+	body.extend_from_slice(payload);
+	encode_packet(packet::PUBLISH, &body)
+}
+
+/// Create an error of the type [`std::io::ErrorKind::InvalidData`].
+fn invalid_data(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_owned())
+}
+
+/// Create an error for a broker that did not respond in time.
+fn timed_out() -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::TimedOut, "no response from the broker")
+}
@@ -0,0 +1,486 @@
+//! Sharing a serial port with TCP clients.
+//!
+//! A [`TcpGateway`] accepts TCP connections and attaches each client to a serial port,
+//! so a lab instrument or other serial device can be used over the network without a separate gateway process.
+//! All data received from the serial port is sent to every client.
+//! Data received from a client is written to the serial port if the [`WriterPolicy`] allows that client to write,
+//! and discarded otherwise.
+//!
+//! With [`TcpGateway::set_idle_timeout()`], clients that may write but have not sent anything for a while are disconnected,
+//! so a forgotten session does not block other users of an exclusive port.
+
+use std::collections::BTreeMap;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::SerialPort;
+
+/// The read timeout used by the background threads.
+///
+/// This limits how long it takes for the background threads to notice that the gateway was dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The write timeout for client connections.
+///
+/// A client that does not accept data for this long is disconnected, so it can not stall the other clients.
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Decides which clients may write to the serial port.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WriterPolicy {
+	/// All clients may write.
+	///
+	/// Data from different clients may end up interleaved on the serial port.
+	AllClients,
+
+	/// One client at a time may write, the other clients are read-only observers.
+	///
+	/// The first client to connect becomes the writer.
+	/// When the writer disconnects, the client that has been connected the longest becomes the writer.
+	Exclusive,
+
+	/// No client may write, all clients are read-only observers.
+	ReadOnly,
+}
+
+/// Shares a serial port with TCP clients.
+///
+/// See the [module documentation][self] for more information.
+///
+/// Dropping the gateway disconnects all clients and stops the background threads.
+pub struct TcpGateway {
+	/// The state shared with the background threads.
+	shared: Arc<Shared>,
+
+	/// The background threads.
+	threads: Vec<JoinHandle<()>>,
+}
+
+/// Statistics of a [`TcpGateway`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct TcpGatewayStats {
+	/// The number of clients that connected.
+	clients_accepted: u64,
+
+	/// The number of clients that were disconnected because they were idle.
+	clients_kicked: u64,
+
+	/// The number of bytes written to the serial port.
+	bytes_written: u64,
+
+	/// The number of bytes from clients that were not allowed to write.
+	bytes_discarded: u64,
+}
+
+impl TcpGatewayStats {
+	/// Get the number of clients that connected.
+	pub fn clients_accepted(&self) -> u64 {
+		self.clients_accepted
+	}
+
+	/// Get the number of clients that were disconnected because they were idle.
+	pub fn clients_kicked(&self) -> u64 {
+		self.clients_kicked
+	}
+
+	/// Get the number of bytes from clients that were written to the serial port.
+	pub fn bytes_written(&self) -> u64 {
+		self.bytes_written
+	}
+
+	/// Get the number of bytes from clients that were discarded because the client was not allowed to write.
+	pub fn bytes_discarded(&self) -> u64 {
+		self.bytes_discarded
+	}
+}
+
+impl TcpGateway {
+	/// Create a new gateway that accepts clients on the given listener.
+	///
+	/// The gateway starts with [`WriterPolicy::Exclusive`], without idle timeout and without a limit on the number of clients.
+	///
+	/// This spawns a background thread that accepts clients, a background thread that reads from the serial port,
+	/// and one background thread for each connected client.
+	/// The read timeout of the serial port is changed for use by the background threads,
+	/// and the listener is put in non-blocking mode.
+	pub fn new(port: SerialPort, listener: TcpListener) -> std::io::Result<Self> {
+		let mut port = port;
+		port.set_read_timeout(POLL_INTERVAL)?;
+		listener.set_nonblocking(true)?;
+		let shared = Arc::new(Shared {
+			port,
+			listener,
+			state: Mutex::new(State {
+				policy: WriterPolicy::Exclusive,
+				idle_timeout: None,
+				max_clients: usize::MAX,
+				clients: BTreeMap::new(),
+				next_id: 0,
+				writer: None,
+				client_threads: Vec::new(),
+				stats: TcpGatewayStats::default(),
+				error: None,
+				closed: false,
+			}),
+		});
+
+		let mut gateway = Self {
+			shared,
+			threads: Vec::with_capacity(2),
+		};
+		gateway.spawn("serial2-tcp-accept", run_accept)?;
+		gateway.spawn("serial2-tcp-serial", run_serial)?;
+		Ok(gateway)
+	}
+
+	/// Get the local address of the listener.
+	pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+		self.shared.listener.local_addr()
+	}
+
+	/// Get a reference to the serial port.
+	pub fn port(&self) -> &SerialPort {
+		&self.shared.port
+	}
+
+	/// Set the policy that decides which clients may write to the serial port.
+	///
+	/// Switching to [`WriterPolicy::Exclusive`] makes the client that has been connected the longest the writer.
+	pub fn set_writer_policy(&self, policy: WriterPolicy) {
+		let mut state = self.shared.lock();
+		state.policy = policy;
+		state.writer = None;
+		state.elect_writer();
+	}
+
+	/// Get the policy that decides which clients may write to the serial port.
+	pub fn get_writer_policy(&self) -> WriterPolicy {
+		self.shared.lock().policy
+	}
+
+	/// Set the time after which a client that may write but did not send any data is disconnected.
+	///
+	/// Read-only observers are never disconnected for being idle.
+	/// Use `None` to disable the idle timeout.
+	pub fn set_idle_timeout(&self, idle_timeout: Option<Duration>) {
+		self.shared.lock().idle_timeout = idle_timeout;
+	}
+
+	/// Get the time after which a client that may write but did not send any data is disconnected.
+	pub fn get_idle_timeout(&self) -> Option<Duration> {
+		self.shared.lock().idle_timeout
+	}
+
+	/// Set the maximum number of connected clients.
+	///
+	/// New connections are closed immediately while the maximum number of clients is connected.
+	pub fn set_max_clients(&self, max_clients: usize) {
+		self.shared.lock().max_clients = max_clients;
+	}
+
+	/// Get the maximum number of connected clients.
+	pub fn get_max_clients(&self) -> usize {
+		self.shared.lock().max_clients
+	}
+
+	/// Get the addresses of the connected clients, in the order they connected.
+	pub fn clients(&self) -> Vec<SocketAddr> {
+		self.shared.lock().clients.values().map(|client| client.address).collect()
+	}
+
+	/// Get the address of the client that may write with [`WriterPolicy::Exclusive`], if any.
+	pub fn writer(&self) -> Option<SocketAddr> {
+		let state = self.shared.lock();
+		state.writer.and_then(|id| state.clients.get(&id)).map(|client| client.address)
+	}
+
+	/// Get the statistics of the gateway.
+	pub fn stats(&self) -> TcpGatewayStats {
+		self.shared.lock().stats
+	}
+
+	/// Take the error that stopped a background thread, if any.
+	///
+	/// Errors of individual client connections only disconnect that client and are not reported.
+	pub fn take_error(&self) -> Option<std::io::Error> {
+		self.shared.lock().error.take()
+	}
+
+	/// Spawn a background thread.
+	fn spawn(&mut self, name: &str, run: fn(&Arc<Shared>)) -> std::io::Result<()> {
+		let shared = self.shared.clone();
+		let thread = std::thread::Builder::new()
+			.name(name.into())
+			.spawn(move || run(&shared))?;
+		self.threads.push(thread);
+		Ok(())
+	}
+}
+
+impl TcpGateway {
+	/// Stop the background threads and wait for them to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		let threads_result = crate::shutdown::join_threads(self.threads.drain(..), deadline);
+		let client_threads = {
+			let mut state = self.shared.lock();
+			for client in state.clients.values() {
+				let _ = client.stream.shutdown(Shutdown::Both);
+			}
+			std::mem::take(&mut state.client_threads)
+		};
+		let client_threads_result = crate::shutdown::join_threads(client_threads, deadline);
+		threads_result.and(client_threads_result)
+	}
+}
+
+impl Drop for TcpGateway {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl crate::shutdown::Shutdown for TcpGateway {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// State shared between the gateway and the background threads.
+struct Shared {
+	/// The serial port.
+	port: SerialPort,
+
+	/// The listener for new clients.
+	listener: TcpListener,
+
+	/// The mutable state.
+	state: Mutex<State>,
+}
+
+/// The mutable state of a gateway.
+struct State {
+	/// The policy that decides which clients may write.
+	policy: WriterPolicy,
+
+	/// The time after which an idle client that may write is disconnected.
+	idle_timeout: Option<Duration>,
+
+	/// The maximum number of connected clients.
+	max_clients: usize,
+
+	/// The connected clients, by identifier in the order they connected.
+	clients: BTreeMap<u64, Client>,
+
+	/// The identifier for the next client.
+	next_id: u64,
+
+	/// The client that may write with [`WriterPolicy::Exclusive`].
+	writer: Option<u64>,
+
+	/// The threads of the clients.
+	client_threads: Vec<JoinHandle<()>>,
+
+	/// The statistics.
+	stats: TcpGatewayStats,
+
+	/// The error that stopped a background thread.
+	error: Option<std::io::Error>,
+
+	/// True if the gateway is being dropped.
+	closed: bool,
+}
+
+/// A connected client.
+struct Client {
+	/// The connection to the client.
+	stream: Arc<TcpStream>,
+
+	/// The address of the client.
+	address: SocketAddr,
+
+	/// The last time data was received from the client, or the time it connected.
+	last_activity: Instant,
+}
+
+impl Shared {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+impl State {
+	/// Check if a client may write to the serial port.
+	fn may_write(&self, id: u64) -> bool {
+		match self.policy {
+			WriterPolicy::AllClients => true,
+			WriterPolicy::Exclusive => self.writer == Some(id),
+			WriterPolicy::ReadOnly => false,
+		}
+	}
+
+	/// Make the client that has been connected the longest the writer, if there is no writer.
+	fn elect_writer(&mut self) {
+		if self.policy == WriterPolicy::Exclusive && self.writer.is_none() {
+			self.writer = self.clients.keys().next().copied();
+		}
+	}
+
+	/// Disconnect a client.
+	fn remove_client(&mut self, id: u64) {
+		if let Some(client) = self.clients.remove(&id) {
+			let _ = client.stream.shutdown(Shutdown::Both);
+		}
+		if self.writer == Some(id) {
+			self.writer = None;
+			self.elect_writer();
+		}
+	}
+}
+
+/// Accept new clients.
+fn run_accept(shared: &Arc<Shared>) {
+	loop {
+		if shared.lock().closed {
+			return;
+		}
+		let (stream, address) = match shared.listener.accept() {
+			Ok(accepted) => accepted,
+			Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+				shared.lock().client_threads.retain(|thread| !thread.is_finished());
+				std::thread::sleep(POLL_INTERVAL);
+				continue;
+			},
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			// A client that gave up before it was accepted should not stop the gateway.
+			Err(e) if e.kind() == std::io::ErrorKind::ConnectionAborted => continue,
+			Err(e) => {
+				shared.lock().error = Some(e);
+				return;
+			},
+		};
+		// A client that can not be configured is simply not accepted.
+		let _ = accept_client(shared, stream, address);
+	}
+}
+
+/// Register a new client and spawn its thread.
+fn accept_client(shared: &Arc<Shared>, stream: TcpStream, address: SocketAddr) -> std::io::Result<()> {
+	stream.set_nonblocking(false)?;
+	stream.set_read_timeout(Some(POLL_INTERVAL))?;
+	stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT))?;
+	let stream = Arc::new(stream);
+
+	let mut state = shared.lock();
+	if state.clients.len() >= state.max_clients {
+		return stream.shutdown(Shutdown::Both);
+	}
+	let id = state.next_id;
+	state.next_id += 1;
+	state.clients.insert(id, Client {
+		stream: stream.clone(),
+		address,
+		last_activity: Instant::now(),
+	});
+	state.elect_writer();
+	state.stats.clients_accepted += 1;
+
+	let thread = std::thread::Builder::new().name("serial2-tcp-client".into()).spawn({
+		let shared = shared.clone();
+		move || run_client(&shared, id, &stream)
+	});
+	match thread {
+		Ok(thread) => {
+			state.client_threads.push(thread);
+			Ok(())
+		},
+		Err(e) => {
+			state.remove_client(id);
+			Err(e)
+		},
+	}
+}
+
+/// Read from the serial port and send the data to all clients.
+fn run_serial(shared: &Arc<Shared>) {
+	let mut buffer = [0; 4096];
+	loop {
+		if shared.lock().closed {
+			return;
+		}
+		let read = match shared.port.read(&mut buffer) {
+			Ok(read) => read,
+			Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => {
+				shared.lock().error = Some(e);
+				return;
+			},
+		};
+
+		let clients: Vec<_> = shared
+			.lock()
+			.clients
+			.iter()
+			.map(|(&id, client)| (id, client.stream.clone()))
+			.collect();
+		for (id, stream) in clients {
+			if std::io::Write::write_all(&mut &*stream, &buffer[..read]).is_err() {
+				shared.lock().remove_client(id);
+			}
+		}
+	}
+}
+
+/// Read from a client and write the data to the serial port, if the client may write.
+fn run_client(shared: &Arc<Shared>, id: u64, stream: &TcpStream) {
+	let mut buffer = [0; 4096];
+	loop {
+		{
+			let mut state = shared.lock();
+			if state.closed || !state.clients.contains_key(&id) {
+				return;
+			}
+			if let (Some(idle_timeout), Some(client)) = (state.idle_timeout, state.clients.get(&id)) {
+				if client.last_activity.elapsed() >= idle_timeout && state.may_write(id) {
+					state.stats.clients_kicked += 1;
+					state.remove_client(id);
+					return;
+				}
+			}
+		}
+
+		let read = match std::io::Read::read(&mut &*stream, &mut buffer) {
+			Ok(0) => {
+				shared.lock().remove_client(id);
+				return;
+			},
+			Ok(read) => read,
+			Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+			Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(_) => {
+				shared.lock().remove_client(id);
+				return;
+			},
+		};
+
+		let mut state = shared.lock();
+		if let Some(client) = state.clients.get_mut(&id) {
+			client.last_activity = Instant::now();
+		}
+		if !state.may_write(id) {
+			state.stats.bytes_discarded += read as u64;
+			continue;
+		}
+		state.stats.bytes_written += read as u64;
+		drop(state);
+		if let Err(e) = shared.port.write_all(&buffer[..read]) {
+			shared.lock().error = Some(e);
+			return;
+		}
+	}
+}
@@ -0,0 +1,378 @@
+//! Forwarding serial frames as UDP datagrams.
+//!
+//! A [`UdpBridge`] reads frames from a serial port with any [`Framer`] and sends each frame as a datagram to all peers.
+//! Datagrams received from peers are written to the serial port,
+//! optionally encoded with a frame encoder set with [`UdpBridge::set_frame_encoder()`].
+//!
+//! Peers are configured with [`UdpBridge::add_peer()`].
+//! Datagrams from other addresses are discarded,
+//! unless the bridge is configured to add them as peers with [`UdpBridge::set_learn_peers()`].
+//!
+//! Optionally, datagrams can carry a sequence number (see [`UdpBridge::set_sequence_numbers()`]).
+//! The sequence number is a 32 bit big endian counter in front of the payload.
+//! It lets listeners detect lost datagrams, and it lets the bridge discard duplicated and reordered datagrams.
+
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::framing::{FrameReader, Framer};
+use crate::shutdown::Shutdown;
+use crate::SerialPort;
+
+/// The read timeout used by the background threads.
+///
+/// This limits how long it takes for the background threads to notice that the bridge was dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The maximum size of a received datagram.
+const MAX_DATAGRAM: usize = 65536;
+
+/// A function that encodes the payload of a datagram before it is written to the serial port.
+type FrameEncoder = Box<dyn Fn(&[u8]) -> Vec<u8> + Send>;
+
+/// Forwards frames between a serial port and UDP peers.
+///
+/// See the [module documentation][self] for more information.
+///
+/// Dropping the bridge stops the background threads.
+pub struct UdpBridge {
+	/// The state shared with the background threads.
+	shared: Arc<Shared>,
+
+	/// The background threads.
+	threads: Vec<JoinHandle<()>>,
+}
+
+/// Statistics of a [`UdpBridge`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct UdpBridgeStats {
+	/// The number of frames sent from the serial port to the peers.
+	frames_sent: u64,
+
+	/// The number of datagrams written to the serial port.
+	datagrams_received: u64,
+
+	/// The number of corrupted frames skipped by the framer.
+	invalid_frames: u64,
+
+	/// The number of datagrams discarded because of an unknown sender or an old sequence number.
+	dropped_datagrams: u64,
+}
+
+impl UdpBridgeStats {
+	/// Get the number of frames sent from the serial port to the peers.
+	///
+	/// A frame sent to multiple peers is counted once.
+	pub fn frames_sent(&self) -> u64 {
+		self.frames_sent
+	}
+
+	/// Get the number of datagrams written to the serial port.
+	pub fn datagrams_received(&self) -> u64 {
+		self.datagrams_received
+	}
+
+	/// Get the number of corrupted frames skipped by the framer.
+	pub fn invalid_frames(&self) -> u64 {
+		self.invalid_frames
+	}
+
+	/// Get the number of datagrams discarded because of an unknown sender or an old sequence number.
+	pub fn dropped_datagrams(&self) -> u64 {
+		self.dropped_datagrams
+	}
+}
+
+impl UdpBridge {
+	/// Create a new bridge between a serial port and a UDP socket.
+	///
+	/// This spawns two background threads: one that reads frames from the serial port,
+	/// and one that receives datagrams from the socket.
+	/// The read timeouts of the serial port and the socket are changed for use by the background threads.
+	pub fn new<F>(port: SerialPort, framer: F, socket: UdpSocket) -> std::io::Result<Self>
+	where
+		F: Framer<Frame = Vec<u8>> + Send + 'static,
+	{
+		let mut port = port;
+		port.set_read_timeout(POLL_INTERVAL)?;
+		socket.set_read_timeout(Some(POLL_INTERVAL))?;
+		let shared = Arc::new(Shared {
+			port: port.try_clone()?,
+			socket,
+			state: Mutex::new(State::default()),
+		});
+
+		let mut bridge = Self {
+			shared,
+			threads: Vec::with_capacity(2),
+		};
+		let reader = FrameReader::new(port, framer);
+		bridge.spawn("serial2-udp-serial", move |shared| run_serial(shared, reader))?;
+		bridge.spawn("serial2-udp-socket", run_socket)?;
+		Ok(bridge)
+	}
+
+	/// Get the local address of the socket.
+	pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+		self.shared.socket.local_addr()
+	}
+
+	/// Add a peer that receives the frames from the serial port.
+	///
+	/// Datagrams from peers are written to the serial port.
+	pub fn add_peer(&self, peer: SocketAddr) {
+		self.shared.lock().peers.entry(peer).or_default();
+	}
+
+	/// Remove a peer.
+	pub fn remove_peer(&self, peer: SocketAddr) {
+		self.shared.lock().peers.remove(&peer);
+	}
+
+	/// Get the list of peers.
+	pub fn peers(&self) -> Vec<SocketAddr> {
+		self.shared.lock().peers.keys().copied().collect()
+	}
+
+	/// Set whether datagrams from unknown senders add the sender as a peer.
+	///
+	/// This lets listeners subscribe by sending a datagram to the bridge.
+	/// By default, datagrams from unknown senders are discarded.
+	pub fn set_learn_peers(&self, learn_peers: bool) {
+		self.shared.lock().learn_peers = learn_peers;
+	}
+
+	/// Check whether datagrams from unknown senders add the sender as a peer.
+	pub fn get_learn_peers(&self) -> bool {
+		self.shared.lock().learn_peers
+	}
+
+	/// Enable or disable sequence numbers.
+	///
+	/// With sequence numbers enabled, each datagram sent to the peers starts with a 32 bit big endian sequence number.
+	/// Received datagrams must start with a sequence number too.
+	/// It is removed before the payload is written to the serial port,
+	/// and datagrams with a sequence number that is not newer than the last one from the same peer are discarded.
+	///
+	/// Sequence numbers are disabled by default.
+	pub fn set_sequence_numbers(&self, enabled: bool) {
+		self.shared.lock().sequence_numbers = enabled;
+	}
+
+	/// Check if sequence numbers are enabled.
+	pub fn get_sequence_numbers(&self) -> bool {
+		self.shared.lock().sequence_numbers
+	}
+
+	/// Set a function that encodes the payload of received datagrams before it is written to the serial port.
+	///
+	/// For example, use [`SlipFramer::encode()`][crate::framing::SlipFramer::encode] when the serial side uses SLIP.
+	/// By default, the payload is written as-is.
+	pub fn set_frame_encoder<F>(&self, encoder: F)
+	where
+		F: Fn(&[u8]) -> Vec<u8> + Send + 'static,
+	{
+		self.shared.lock().encoder = Some(Box::new(encoder));
+	}
+
+	/// Remove the frame encoder, so the payload of received datagrams is written as-is.
+	pub fn clear_frame_encoder(&self) {
+		self.shared.lock().encoder = None;
+	}
+
+	/// Get the statistics of the bridge.
+	pub fn stats(&self) -> UdpBridgeStats {
+		self.shared.lock().stats
+	}
+
+	/// Take the error that stopped a background thread, if any.
+	///
+	/// If a background thread stopped, the bridge only forwards data in the other direction.
+	pub fn take_error(&self) -> Option<std::io::Error> {
+		self.shared.lock().error.take()
+	}
+
+	/// Spawn a background thread.
+	fn spawn<F>(&mut self, name: &str, run: F) -> std::io::Result<()>
+	where
+		F: FnOnce(&Shared) + Send + 'static,
+	{
+		let shared = self.shared.clone();
+		let thread = std::thread::Builder::new()
+			.name(name.into())
+			.spawn(move || run(&shared))?;
+		self.threads.push(thread);
+		Ok(())
+	}
+}
+
+impl UdpBridge {
+	/// Stop the background threads and wait for them to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		crate::shutdown::join_threads(self.threads.drain(..), deadline)
+	}
+}
+
+impl Drop for UdpBridge {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl Shutdown for UdpBridge {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// State shared between the bridge and the background threads.
+struct Shared {
+	/// The serial port, used for writing.
+	port: SerialPort,
+
+	/// The UDP socket.
+	socket: UdpSocket,
+
+	/// The mutable state.
+	state: Mutex<State>,
+}
+
+/// The mutable state of a bridge.
+#[derive(Default)]
+struct State {
+	/// The peers, with the last sequence number received from each peer.
+	peers: HashMap<SocketAddr, Option<u32>>,
+
+	/// If true, unknown senders are added as peers.
+	learn_peers: bool,
+
+	/// If true, datagrams carry a sequence number.
+	sequence_numbers: bool,
+
+	/// The sequence number of the next datagram sent to the peers.
+	next_sequence: u32,
+
+	/// The function that encodes received datagrams.
+	encoder: Option<FrameEncoder>,
+
+	/// The statistics.
+	stats: UdpBridgeStats,
+
+	/// The error that stopped a background thread.
+	error: Option<std::io::Error>,
+
+	/// True if the bridge is being dropped.
+	closed: bool,
+}
+
+impl Shared {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+/// Read frames from the serial port and send them to the peers.
+fn run_serial<F: Framer<Frame = Vec<u8>>>(shared: &Shared, mut reader: FrameReader<F>) {
+	loop {
+		if shared.lock().closed {
+			return;
+		}
+		let frame = match reader.read_frame() {
+			Ok(frame) => frame,
+			Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+				shared.lock().stats.invalid_frames += 1;
+				continue;
+			},
+			Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => {
+				shared.lock().error = Some(e);
+				return;
+			},
+		};
+
+		let mut state = shared.lock();
+		let datagram = if state.sequence_numbers {
+			let mut datagram = state.next_sequence.to_be_bytes().to_vec();
+			datagram.extend_from_slice(&frame);
+			state.next_sequence = state.next_sequence.wrapping_add(1);
+			datagram
+		} else {
+			frame
+		};
+		let peers: Vec<_> = state.peers.keys().copied().collect();
+		state.stats.frames_sent += 1;
+		drop(state);
+		for peer in peers {
+			// A peer that is not listening should not stop the bridge.
+			let _ = shared.socket.send_to(&datagram, peer);
+		}
+	}
+}
+
+/// Receive datagrams from the peers and write them to the serial port.
+fn run_socket(shared: &Shared) {
+	let mut buffer = vec![0; MAX_DATAGRAM];
+	loop {
+		if shared.lock().closed {
+			return;
+		}
+		let (len, sender) = match shared.socket.recv_from(&mut buffer) {
+			Ok(received) => received,
+			Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+			Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			// On Windows, an ICMP port unreachable message from a previous send is reported on the next receive.
+			Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => continue,
+			Err(e) => {
+				shared.lock().error = Some(e);
+				return;
+			},
+		};
+
+		let mut state = shared.lock();
+		let Some(payload) = accept(&mut state, sender, &buffer[..len]) else {
+			state.stats.dropped_datagrams += 1;
+			continue;
+		};
+		let data = match &state.encoder {
+			Some(encoder) => encoder(payload),
+			None => payload.to_vec(),
+		};
+		state.stats.datagrams_received += 1;
+		drop(state);
+		if let Err(e) = shared.port.write_all(&data) {
+			shared.lock().error = Some(e);
+			return;
+		}
+	}
+}
+
+/// Check if a datagram should be forwarded, and return the payload.
+fn accept<'a>(state: &mut State, sender: SocketAddr, datagram: &'a [u8]) -> Option<&'a [u8]> {
+	if !state.peers.contains_key(&sender) {
+		if !state.learn_peers {
+			return None;
+		}
+		state.peers.insert(sender, None);
+	}
+	if !state.sequence_numbers {
+		return Some(datagram);
+	}
+
+	let sequence = u32::from_be_bytes(datagram.get(..4)?.try_into().ok()?);
+	let last_sequence = state.peers.get_mut(&sender)?;
+	if let Some(last_sequence) = *last_sequence {
+		// Compare with wrapping arithmetic, so the sequence number can overflow.
+		if (sequence.wrapping_sub(last_sequence) as i32) <= 0 {
+			return None;
+		}
+	}
+	*last_sequence = Some(sequence);
+	Some(&datagram[4..])
+}
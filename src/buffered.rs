@@ -0,0 +1,1069 @@
+//! Buffered reading with a background thread, and buffered writing for poll loops.
+//!
+//! A [`BufferedReader`] owns a background thread that continuously reads from a serial port into an internal buffer.
+//! Consumers take data from the buffer, and can choose when they want to be woken up.
+//!
+//! For example, with [`BufferedReader::read_until()`] a protocol handler only wakes up when a frame terminator arrives,
+//! rather than on every received byte.
+//! The background thread sees all received data, so this works the same on all platforms.
+//!
+//! Similarly, [`BufferedReader::set_read_watermark()`] configures [`BufferedReader::read()`] to only wake up
+//! when enough data has accumulated, or when the oldest buffered byte has waited long enough.
+//! This avoids waking up the consumer for every byte at high baud rates.
+//!
+//! What happens when the buffer is full is determined by the [`OverrunPolicy`].
+//! You can also register a callback that is invoked when the buffer level rises above a high watermark,
+//! with [`BufferedReader::set_high_watermark_callback()`].
+//!
+//! With [`BufferedReader::set_adaptive_capacity()`], the buffer grows when the consumer falls behind
+//! and shrinks again when the data rate drops, within configurable bounds.
+//!
+//! Byte patterns like an error message or the prompt of a bootloader can be watched with [`BufferedReader::add_trigger()`].
+//! The callback of a trigger is invoked by the background thread as soon as the pattern is received,
+//! regardless of what the consumer is doing with the buffered data.
+//! This can be used to detect unexpected resets or panic messages on console ports.
+//!
+//! Lines of text in encodings other than UTF-8 can be read with [`BufferedReader::read_line()`].
+//!
+//! To temporarily give another component direct access to the serial port,
+//! for example a PPP implementation after a modem connected, use [`BufferedReader::detach()`].
+//!
+//! For the other direction, a [`BufferedSerialPort`] queues data that can not be written without blocking,
+//! so a single threaded poll loop does not have to keep track of partial writes.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::shutdown::Shutdown;
+use crate::transcode::{DecodeMode, Encoding};
+use crate::{SerialPort, ThreadOptions};
+
+/// The default capacity of the internal buffer.
+const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+/// The read timeout used by the background thread.
+///
+/// This limits how long it takes for the background thread to notice that the reader was dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The interval over which the peak buffer level is observed before an adaptive buffer is shrunk.
+const ADAPT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What the background thread of a [`BufferedReader`] does when the buffer is full.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum OverrunPolicy {
+	/// Stop reading from the serial port until there is space in the buffer again.
+	///
+	/// Received data accumulates in the kernel buffer instead.
+	/// When that is full too, it depends on the platform, the driver and the flow control settings what happens to new data.
+	#[default]
+	Block,
+
+	/// Discard the oldest data in the buffer to make room for new data.
+	///
+	/// The number of discarded bytes is reported by [`BufferedReader::dropped_bytes()`].
+	DropOldest,
+
+	/// Discard newly received data that does not fit in the buffer.
+	///
+	/// The number of discarded bytes is reported by [`BufferedReader::dropped_bytes()`].
+	DropNewest,
+
+	/// Ask the peer to stop sending with [`SerialPort::pause_input()`] when the buffer is three quarters full,
+	/// and let it continue with [`SerialPort::resume_input()`] when the buffer is half empty again.
+	///
+	/// This requires flow control to be enabled on the serial port.
+	/// If the buffer becomes full anyway, the background thread stops reading like [`Self::Block`].
+	FlowControl,
+}
+
+/// The identifier of a trigger registered with [`BufferedReader::add_trigger()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct TriggerId(u64);
+
+/// A serial port reader that buffers received data in a background thread.
+///
+/// See the [module documentation][self] for more information.
+///
+/// The reader is intended to be used by a single consumer.
+/// If multiple threads wait for data at the same time, some may only wake up when their read timeout expires.
+pub struct BufferedReader {
+	/// The state shared with the background thread.
+	shared: Arc<Shared>,
+
+	/// The background thread.
+	thread: Option<JoinHandle<()>>,
+
+	/// The maximum time to wait for data in read functions.
+	read_timeout: Duration,
+
+	/// The maximum time received data may wait in the buffer before `read()` wakes up.
+	max_delay: Duration,
+}
+
+impl BufferedReader {
+	/// Create a new buffered reader with the default buffer capacity of 64 KiB.
+	///
+	/// See [`Self::with_capacity()`] for more information.
+	pub fn new(port: SerialPort) -> std::io::Result<Self> {
+		Self::with_capacity(port, DEFAULT_CAPACITY)
+	}
+
+	/// Create a new buffered reader with a specific buffer capacity.
+	///
+	/// This spawns a background thread that reads from the serial port.
+	/// The read timeout of the port is used as the read timeout of the buffered reader,
+	/// and the read timeout of the port itself is changed for use by the background thread.
+	///
+	/// When the buffer is full, the background thread stops reading from the serial port until there is space again.
+	pub fn with_capacity(port: SerialPort, capacity: usize) -> std::io::Result<Self> {
+		Self::with_thread_options(port, capacity, &ThreadOptions::default())
+	}
+
+	/// Create a new buffered reader with a specific buffer capacity and options for the background thread.
+	///
+	/// See [`Self::with_capacity()`] and [`ThreadOptions`] for more information.
+	pub fn with_thread_options(
+		mut port: SerialPort,
+		capacity: usize,
+		thread_options: &ThreadOptions,
+	) -> std::io::Result<Self> {
+		let read_timeout = port.get_read_timeout()?;
+		port.set_read_timeout(POLL_INTERVAL)?;
+		let shared = Arc::new(Shared {
+			port: Arc::new(port),
+			state: Mutex::new(State {
+				buffer: VecDeque::with_capacity(capacity),
+				capacity: capacity.max(1),
+				sentinel: None,
+				min_bytes: 1,
+				first_arrival: None,
+				overrun_policy: OverrunPolicy::Block,
+				dropped_bytes: 0,
+				adaptive: None,
+				peak_level: 0,
+				adapt_since: Instant::now(),
+				input_paused: false,
+				error: None,
+				eof: false,
+				detached: false,
+				reading: false,
+				closed: false,
+			}),
+			data_available: Condvar::new(),
+			space_available: Condvar::new(),
+			high_watermark: Mutex::new(None),
+			resize_callback: Mutex::new(None),
+			triggers: Mutex::new(Triggers {
+				next_id: 0,
+				list: Vec::new(),
+				history: Vec::new(),
+			}),
+		});
+		let thread = thread_options.spawn("serial2-reader", {
+			let shared = shared.clone();
+			move || shared.run()
+		})?;
+		Ok(Self {
+			shared,
+			thread: Some(thread),
+			read_timeout,
+			max_delay: Duration::ZERO,
+		})
+	}
+
+	/// Get a reference to the serial port.
+	///
+	/// You can use this to write to the serial port or to control the modem lines.
+	/// You should not read from the serial port directly, since the background thread is already reading from it.
+	pub fn port(&self) -> &Arc<SerialPort> {
+		&self.shared.port
+	}
+
+	/// Temporarily detach the serial port from the reader.
+	///
+	/// The background thread stops reading from the serial port until the returned [`DetachedPort`] is dropped.
+	/// Data that was already buffered is moved into the [`DetachedPort`].
+	/// Any data that is not taken from it with [`DetachedPort::take_buffered()`] is put back in the buffer when it is dropped.
+	///
+	/// While the port is detached, you can read from it directly or hand it to another component,
+	/// for example with [`SerialPort::try_clone()`].
+	/// Note that the read timeout of the port was changed for use by the background thread.
+	///
+	/// If input was paused by [`OverrunPolicy::FlowControl`], it is resumed.
+	pub fn detach(&mut self) -> DetachedPort<'_> {
+		let mut state = self.shared.lock();
+		state.detached = true;
+		while state.reading {
+			state = self.shared.space_available.wait(state).unwrap_or_else(PoisonError::into_inner);
+		}
+		let buffered = state.buffer.drain(..).collect();
+		state.first_arrival = None;
+		if state.input_paused {
+			let _ = self.shared.port.resume_input();
+			state.input_paused = false;
+		}
+		drop(state);
+		DetachedPort { reader: self, buffered }
+	}
+
+	/// Set the maximum time to wait for data in the read functions.
+	pub fn set_read_timeout(&mut self, timeout: Duration) {
+		self.read_timeout = timeout;
+	}
+
+	/// Get the maximum time to wait for data in the read functions.
+	pub fn get_read_timeout(&self) -> Duration {
+		self.read_timeout
+	}
+
+	/// Set the read watermark used by [`Self::read()`].
+	///
+	/// With a watermark, `read()` does not return until at least `min_bytes` are available in the buffer,
+	/// or until the oldest byte in the buffer has been waiting for `max_delay`.
+	/// The read timeout still applies: if no data is received at all, `read()` returns a timeout error.
+	///
+	/// This is similar to the `VMIN` and `VTIME` settings of Unix terminals,
+	/// but it works the same on all platforms.
+	///
+	/// By default, `min_bytes` is 1 and `read()` returns as soon as any data is available.
+	pub fn set_read_watermark(&mut self, min_bytes: usize, max_delay: Duration) {
+		self.shared.lock().min_bytes = min_bytes.max(1);
+		self.max_delay = max_delay;
+	}
+
+	/// Get the read watermark used by [`Self::read()`].
+	///
+	/// Returns the minimum number of bytes and the maximum delay.
+	pub fn get_read_watermark(&self) -> (usize, Duration) {
+		(self.shared.lock().min_bytes, self.max_delay)
+	}
+
+	/// Get the number of bytes available in the buffer.
+	pub fn available(&self) -> usize {
+		self.shared.lock().buffer.len()
+	}
+
+	/// Get the current capacity of the buffer.
+	pub fn capacity(&self) -> usize {
+		self.shared.lock().capacity
+	}
+
+	/// Let the buffer capacity adapt to the data rate and the consumer, within the given bounds.
+	///
+	/// The capacity doubles whenever the buffer would become more than three quarters full,
+	/// so a consumer that falls behind does not cause an overrun.
+	/// The capacity halves when the buffer level stayed below a quarter of the capacity for a second,
+	/// so ports with a low data rate do not waste memory.
+	///
+	/// The current capacity is clamped to the bounds immediately.
+	/// Use [`Self::set_resize_callback()`] to be notified when the capacity changes.
+	pub fn set_adaptive_capacity(&mut self, min: usize, max: usize) {
+		let min = min.max(1);
+		let max = max.max(min);
+		let mut state = self.shared.lock();
+		state.adaptive = Some((min, max));
+		let old = state.capacity;
+		state.set_capacity(old.clamp(min, max));
+		let new = state.capacity;
+		drop(state);
+		self.shared.space_available.notify_all();
+		if new != old {
+			self.shared.report_resize(Some((old, new)));
+		}
+	}
+
+	/// Stop adapting the buffer capacity.
+	///
+	/// The buffer keeps its current capacity.
+	pub fn clear_adaptive_capacity(&mut self) {
+		self.shared.lock().adaptive = None;
+	}
+
+	/// Get the bounds of the adaptive buffer capacity, if enabled.
+	pub fn get_adaptive_capacity(&self) -> Option<(usize, usize)> {
+		self.shared.lock().adaptive
+	}
+
+	/// Set a callback that is invoked when the buffer capacity changes.
+	///
+	/// The callback is invoked with the old and the new capacity,
+	/// from the background thread or from [`Self::set_adaptive_capacity()`].
+	/// The callback should return quickly, since the background thread does not read from the serial port while it runs.
+	///
+	/// This replaces any previously set callback.
+	pub fn set_resize_callback<F>(&mut self, callback: F)
+	where
+		F: FnMut(usize, usize) + Send + 'static,
+	{
+		*lock(&self.shared.resize_callback) = Some(Box::new(callback));
+	}
+
+	/// Remove the resize callback.
+	pub fn clear_resize_callback(&mut self) {
+		*lock(&self.shared.resize_callback) = None;
+	}
+
+	/// Set what the background thread does when the buffer is full.
+	///
+	/// The default policy is [`OverrunPolicy::Block`].
+	pub fn set_overrun_policy(&mut self, policy: OverrunPolicy) {
+		self.shared.lock().overrun_policy = policy;
+		self.shared.space_available.notify_all();
+	}
+
+	/// Get what the background thread does when the buffer is full.
+	pub fn get_overrun_policy(&self) -> OverrunPolicy {
+		self.shared.lock().overrun_policy
+	}
+
+	/// Get the total number of bytes discarded because the buffer was full.
+	///
+	/// Data is only discarded with [`OverrunPolicy::DropOldest`] and [`OverrunPolicy::DropNewest`].
+	pub fn dropped_bytes(&self) -> u64 {
+		self.shared.lock().dropped_bytes
+	}
+
+	/// Set a callback that is invoked when the buffer level rises above a high watermark.
+	///
+	/// The callback is invoked from the background thread with the current buffer level,
+	/// each time the level crosses from at or below `level` to above `level`.
+	/// The callback should return quickly, since the background thread does not read from the serial port while it runs.
+	///
+	/// This replaces any previously set callback.
+	pub fn set_high_watermark_callback<F>(&mut self, level: usize, callback: F)
+	where
+		F: FnMut(usize) + Send + 'static,
+	{
+		*lock(&self.shared.high_watermark) = Some(HighWatermark {
+			level,
+			callback: Box::new(callback),
+		});
+	}
+
+	/// Remove the high watermark callback.
+	pub fn clear_high_watermark_callback(&mut self) {
+		*lock(&self.shared.high_watermark) = None;
+	}
+
+	/// Register a callback that is invoked when a byte pattern is received.
+	///
+	/// The callback is invoked from the background thread for every occurrence of the pattern,
+	/// including occurrences that are split over multiple reads from the serial port and overlapping occurrences.
+	/// The data is still added to the buffer as usual.
+	/// The callback should return quickly, since the background thread does not read from the serial port while it runs.
+	///
+	/// An empty pattern never matches.
+	///
+	/// Returns an identifier that can be used to remove the trigger with [`Self::remove_trigger()`].
+	pub fn add_trigger<F>(&mut self, pattern: impl Into<Vec<u8>>, callback: F) -> TriggerId
+	where
+		F: FnMut() + Send + 'static,
+	{
+		let mut triggers = lock(&self.shared.triggers);
+		let id = TriggerId(triggers.next_id);
+		triggers.next_id += 1;
+		triggers.list.push(Trigger {
+			id,
+			pattern: pattern.into(),
+			callback: Box::new(callback),
+		});
+		id
+	}
+
+	/// Remove a trigger.
+	///
+	/// Returns `false` if there is no trigger with the given identifier.
+	pub fn remove_trigger(&mut self, id: TriggerId) -> bool {
+		let mut triggers = lock(&self.shared.triggers);
+		let len = triggers.list.len();
+		triggers.list.retain(|trigger| trigger.id != id);
+		triggers.list.len() != len
+	}
+
+	/// Read data from the buffer.
+	///
+	/// This waits until the read watermark is reached or the read timeout expires.
+	/// By default, that means it waits until at least one byte is available.
+	/// See [`Self::set_read_watermark()`] for more information.
+	///
+	/// On timeout, an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	///
+	/// Returns `Ok(0)` if the serial port reported end-of-file and the buffer is empty.
+	pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		if buf.is_empty() {
+			return Ok(0);
+		}
+		let max_delay = self.max_delay;
+		let mut state = self.shared.wait_until(self.read_timeout, |state| {
+			if state.buffer.len() >= state.min_bytes.min(buf.len()) {
+				Readiness::Ready
+			} else if let Some(first_arrival) = state.first_arrival {
+				Readiness::ReadyAt(first_arrival + max_delay)
+			} else {
+				Readiness::NotReady
+			}
+		})?;
+		if state.buffer.is_empty() {
+			return Ok(0);
+		}
+		let read = state.take(buf);
+		drop(state);
+		self.shared.space_available.notify_all();
+		Ok(read)
+	}
+
+	/// Read data until the sentinel byte is received.
+	///
+	/// This waits until the sentinel byte is in the buffer or the read timeout expires,
+	/// without waking up for other received data.
+	/// Then all data up to and including the sentinel is appended to `buf`.
+	///
+	/// On timeout, an error of the type [`std::io::ErrorKind::TimedOut`] is returned and no data is taken from the buffer.
+	///
+	/// If the serial port reports end-of-file before the sentinel is received, all remaining data is appended to `buf`.
+	/// Returns the number of bytes appended to `buf`.
+	pub fn read_until(&self, sentinel: u8, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+		let mut state = self.shared.lock();
+		state.sentinel = Some(sentinel);
+		drop(state);
+		let state = self.shared.wait_until(self.read_timeout, |state| {
+			if state.buffer.contains(&sentinel) {
+				Readiness::Ready
+			} else {
+				Readiness::NotReady
+			}
+		});
+		let mut state = match state {
+			Ok(state) => state,
+			Err(e) => {
+				self.shared.lock().sentinel = None;
+				return Err(e);
+			},
+		};
+		state.sentinel = None;
+
+		let len = match state.buffer.iter().position(|&byte| byte == sentinel) {
+			Some(index) => index + 1,
+			None => state.buffer.len(),
+		};
+		buf.extend(state.buffer.drain(..len));
+		state.update_first_arrival();
+		drop(state);
+		self.shared.space_available.notify_all();
+		Ok(len)
+	}
+
+	/// Read a line of text in the given encoding, and append it to `line` as UTF-8.
+	///
+	/// This reads data up to and including a newline with [`Self::read_until()`] and decodes it with [`Encoding::decode()`].
+	/// The newline is appended to `line` too.
+	///
+	/// In [`DecodeMode::Strict`] mode, a line with invalid input results in an error of the type [`std::io::ErrorKind::InvalidData`].
+	/// The line is taken from the buffer and `line` is left unchanged in that case.
+	///
+	/// Returns the number of bytes taken from the buffer.
+	pub fn read_line(&self, encoding: Encoding, mode: DecodeMode, line: &mut String) -> std::io::Result<usize> {
+		let mut data = Vec::new();
+		let read = self.read_until(b'\n', &mut data)?;
+		line.push_str(&encoding.decode(&data, mode)?);
+		Ok(read)
+	}
+}
+
+impl std::io::Read for BufferedReader {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		BufferedReader::read(self, buf)
+	}
+}
+
+impl std::io::Read for &'_ BufferedReader {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		BufferedReader::read(self, buf)
+	}
+}
+
+impl BufferedReader {
+	/// Stop the background thread and wait for it to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		self.shared.space_available.notify_all();
+		crate::shutdown::join_threads(self.thread.take(), deadline)
+	}
+}
+
+impl Drop for BufferedReader {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl Shutdown for BufferedReader {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// A serial port that is temporarily detached from a [`BufferedReader`].
+///
+/// See [`BufferedReader::detach()`] for more information.
+///
+/// The background thread of the reader resumes reading from the serial port when this is dropped.
+pub struct DetachedPort<'a> {
+	/// The reader the port was detached from.
+	reader: &'a mut BufferedReader,
+
+	/// The data that was buffered when the port was detached.
+	buffered: Vec<u8>,
+}
+
+impl DetachedPort<'_> {
+	/// Get a reference to the serial port.
+	pub fn port(&self) -> &Arc<SerialPort> {
+		&self.reader.shared.port
+	}
+
+	/// Get the data that was buffered by the reader when the port was detached.
+	pub fn buffered(&self) -> &[u8] {
+		&self.buffered
+	}
+
+	/// Take the data that was buffered by the reader when the port was detached.
+	///
+	/// The data is not put back in the buffer of the reader when the port is re-attached.
+	pub fn take_buffered(&mut self) -> Vec<u8> {
+		std::mem::take(&mut self.buffered)
+	}
+}
+
+impl Drop for DetachedPort<'_> {
+	fn drop(&mut self) {
+		let mut state = self.reader.shared.lock();
+		state.buffer.extend(self.buffered.drain(..));
+		state.update_first_arrival();
+		state.detached = false;
+		drop(state);
+		self.reader.shared.space_available.notify_all();
+	}
+}
+
+/// State shared between the reader and the background thread.
+struct Shared {
+	/// The serial port to read from.
+	port: Arc<SerialPort>,
+
+	/// The mutable state of the reader.
+	state: Mutex<State>,
+
+	/// Condition variable signalled when a waiting consumer should wake up.
+	data_available: Condvar,
+
+	/// Condition variable signalled when data is taken from the buffer.
+	space_available: Condvar,
+
+	/// The high watermark callback.
+	high_watermark: Mutex<Option<HighWatermark>>,
+
+	/// The callback invoked when the buffer capacity changes.
+	resize_callback: Mutex<Option<ResizeCallback>>,
+
+	/// The byte pattern triggers.
+	triggers: Mutex<Triggers>,
+}
+
+/// A callback invoked with the old and new capacity when the buffer is resized.
+type ResizeCallback = Box<dyn FnMut(usize, usize) + Send>;
+
+/// A callback invoked when the buffer level rises above a watermark.
+struct HighWatermark {
+	/// The buffer level above which the callback is invoked.
+	level: usize,
+
+	/// The callback.
+	callback: Box<dyn FnMut(usize) + Send>,
+}
+
+/// The byte pattern triggers of a reader.
+struct Triggers {
+	/// The identifier for the next trigger.
+	next_id: u64,
+
+	/// The registered triggers.
+	list: Vec<Trigger>,
+
+	/// The end of the received data, to match patterns that are split over multiple reads.
+	history: Vec<u8>,
+}
+
+/// A callback invoked when a byte pattern is received.
+struct Trigger {
+	/// The identifier of the trigger.
+	id: TriggerId,
+
+	/// The pattern to look for.
+	pattern: Vec<u8>,
+
+	/// The callback.
+	callback: Box<dyn FnMut() + Send>,
+}
+
+/// The mutable state of the reader.
+struct State {
+	/// The received data.
+	buffer: VecDeque<u8>,
+
+	/// The maximum number of bytes in the buffer.
+	capacity: usize,
+
+	/// The sentinel byte a consumer is waiting for, if any.
+	sentinel: Option<u8>,
+
+	/// The minimum number of bytes before a waiting consumer is woken up.
+	min_bytes: usize,
+
+	/// The time the oldest byte in the buffer was received.
+	first_arrival: Option<Instant>,
+
+	/// What to do when the buffer is full.
+	overrun_policy: OverrunPolicy,
+
+	/// The total number of bytes discarded because the buffer was full.
+	dropped_bytes: u64,
+
+	/// The minimum and maximum capacity, if the capacity adapts to the data rate.
+	adaptive: Option<(usize, usize)>,
+
+	/// The highest buffer level since `adapt_since`.
+	peak_level: usize,
+
+	/// The start of the current interval for observing the peak buffer level.
+	adapt_since: Instant,
+
+	/// True if the background thread paused the input with flow control.
+	input_paused: bool,
+
+	/// An error reported by the serial port.
+	error: Option<std::io::Error>,
+
+	/// True if the serial port reported end-of-file.
+	eof: bool,
+
+	/// True if the serial port is detached and the background thread should not read from it.
+	detached: bool,
+
+	/// True while the background thread is reading from the serial port.
+	reading: bool,
+
+	/// True if the reader is being dropped.
+	closed: bool,
+}
+
+impl Shared {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State> {
+		lock(&self.state)
+	}
+
+	/// Wait until the consumer is ready, the port reported an error or end-of-file, or the timeout expires.
+	///
+	/// If the port reported an error and the consumer is not ready, the error is returned.
+	fn wait_until<F>(&self, timeout: Duration, readiness: F) -> std::io::Result<MutexGuard<'_, State>>
+	where
+		F: Fn(&State) -> Readiness,
+	{
+		let deadline = Instant::now() + timeout;
+		let mut state = self.lock();
+		loop {
+			let now = Instant::now();
+			let wake_at = match readiness(&state) {
+				Readiness::Ready => return Ok(state),
+				Readiness::ReadyAt(ready_at) if ready_at <= now => return Ok(state),
+				Readiness::ReadyAt(ready_at) => ready_at.min(deadline),
+				Readiness::NotReady => deadline,
+			};
+			if let Some(error) = state.error.take() {
+				// The background thread stops after an error, so treat the rest of the stream as end-of-file.
+				state.eof = true;
+				return Err(error);
+			}
+			if state.eof {
+				return Ok(state);
+			}
+			if deadline <= now {
+				return Err(std::io::ErrorKind::TimedOut.into());
+			}
+			state = self
+				.data_available
+				.wait_timeout(state, wake_at - now)
+				.unwrap_or_else(PoisonError::into_inner)
+				.0;
+		}
+	}
+
+	/// Run the background thread.
+	fn run(&self) {
+		let mut chunk = vec![0; 4096];
+		loop {
+			let mut state = self.lock();
+			let resized = state.adapt_capacity(0);
+			self.update_flow_control(&mut state);
+			while state.must_wait() && !state.closed {
+				state = self.space_available.wait(state).unwrap_or_else(PoisonError::into_inner);
+				self.update_flow_control(&mut state);
+			}
+			if state.closed {
+				if state.input_paused {
+					let _ = self.port.resume_input();
+				}
+				return;
+			}
+			let max_read = if state.blocks_when_full() {
+				(state.capacity - state.buffer.len()).min(chunk.len())
+			} else {
+				chunk.len()
+			};
+			state.reading = true;
+			drop(state);
+			self.report_resize(resized);
+
+			let result = self.port.read(&mut chunk[..max_read]);
+
+			let mut state = self.lock();
+			state.reading = false;
+			if state.detached {
+				self.space_available.notify_all();
+			}
+			match result {
+				Ok(0) => {
+					state.eof = true;
+					drop(state);
+					self.data_available.notify_all();
+					return;
+				},
+				Ok(read) => {
+					let resized = state.adapt_capacity(read);
+					let level_before = state.buffer.len();
+					let data = state.push(&chunk[..read]);
+					// Waiting consumers must also wake up for the first byte, to start the watermark delay.
+					let wake = match state.sentinel {
+						Some(sentinel) => data.contains(&sentinel),
+						None => level_before == 0 || state.buffer.len() >= state.min_bytes,
+					};
+					let level = state.buffer.len();
+					drop(state);
+					if wake {
+						self.data_available.notify_all();
+					}
+					self.report_resize(resized);
+					self.check_high_watermark(level_before, level);
+					lock(&self.triggers).scan(&chunk[..read]);
+				},
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => {
+					state.error = Some(e);
+					drop(state);
+					self.data_available.notify_all();
+					return;
+				},
+			}
+		}
+	}
+
+	/// Pause or resume the input with flow control, depending on the buffer level and the overrun policy.
+	fn update_flow_control(&self, state: &mut State) {
+		// Errors are ignored: without flow control, the policy behaves like `OverrunPolicy::Block`.
+		if state.overrun_policy == OverrunPolicy::FlowControl {
+			if !state.input_paused && state.buffer.len() >= state.capacity / 4 * 3 {
+				let _ = self.port.pause_input();
+				state.input_paused = true;
+			} else if state.input_paused && state.buffer.len() <= state.capacity / 2 {
+				let _ = self.port.resume_input();
+				state.input_paused = false;
+			}
+		} else if state.input_paused {
+			let _ = self.port.resume_input();
+			state.input_paused = false;
+		}
+	}
+
+	/// Invoke the resize callback if the buffer was resized.
+	fn report_resize(&self, resized: Option<(usize, usize)>) {
+		if let Some((old, new)) = resized {
+			if let Some(callback) = lock(&self.resize_callback).as_mut() {
+				callback(old, new);
+			}
+		}
+	}
+
+	/// Invoke the high watermark callback if the buffer level crossed the watermark.
+	fn check_high_watermark(&self, level_before: usize, level: usize) {
+		if let Some(watermark) = lock(&self.high_watermark).as_mut() {
+			if level_before <= watermark.level && level > watermark.level {
+				(watermark.callback)(level);
+			}
+		}
+	}
+}
+
+impl Triggers {
+	/// Invoke the callbacks of all triggers that match newly received data.
+	fn scan(&mut self, data: &[u8]) {
+		let Some(longest) = self.list.iter().map(|trigger| trigger.pattern.len()).max() else {
+			self.history.clear();
+			return;
+		};
+		let start = self.history.len();
+		self.history.extend_from_slice(data);
+		for trigger in &mut self.list {
+			let len = trigger.pattern.len();
+			if len == 0 {
+				continue;
+			}
+			// Only consider matches that end in the new data, so no match is reported twice.
+			for end in (start + 1).max(len)..=self.history.len() {
+				if self.history[end - len..end] == trigger.pattern[..] {
+					(trigger.callback)();
+				}
+			}
+		}
+		let keep = longest.saturating_sub(1);
+		if self.history.len() > keep {
+			self.history.drain(..self.history.len() - keep);
+		}
+	}
+}
+
+/// The readiness of a consumer waiting for data.
+enum Readiness {
+	/// The consumer is ready.
+	Ready,
+
+	/// The consumer is not ready.
+	NotReady,
+
+	/// The consumer will be ready at the given time, unless it becomes ready earlier.
+	ReadyAt(Instant),
+}
+
+impl State {
+	/// Check if the background thread must wait before reading from the serial port.
+	fn must_wait(&self) -> bool {
+		self.detached || (self.buffer.len() >= self.capacity && self.blocks_when_full())
+	}
+
+	/// Check if the background thread stops reading when the buffer is full.
+	fn blocks_when_full(&self) -> bool {
+		match self.overrun_policy {
+			OverrunPolicy::Block | OverrunPolicy::FlowControl => true,
+			OverrunPolicy::DropOldest | OverrunPolicy::DropNewest => false,
+		}
+	}
+
+	/// Adapt the capacity of the buffer before adding the given number of bytes, if adaptive capacity is enabled.
+	///
+	/// Returns the old and new capacity if the capacity changed.
+	fn adapt_capacity(&mut self, incoming: usize) -> Option<(usize, usize)> {
+		let (min, max) = self.adaptive?;
+		let old = self.capacity;
+		let level = self.buffer.len() + incoming;
+		self.peak_level = self.peak_level.max(level);
+		let mut capacity = old;
+		while level > capacity / 4 * 3 && capacity < max {
+			capacity = capacity.saturating_mul(2).min(max);
+		}
+		if capacity == old && self.adapt_since.elapsed() >= ADAPT_INTERVAL {
+			if self.peak_level < capacity / 4 {
+				capacity = (capacity / 2).max(min).max(self.buffer.len());
+			}
+			self.peak_level = level;
+			self.adapt_since = Instant::now();
+		}
+		self.set_capacity(capacity);
+		(capacity != old).then_some((old, capacity))
+	}
+
+	/// Change the capacity of the buffer and its allocation.
+	fn set_capacity(&mut self, capacity: usize) {
+		if capacity > self.capacity {
+			self.buffer.reserve(capacity - self.buffer.len());
+		} else if capacity < self.capacity {
+			self.buffer.shrink_to(capacity);
+		}
+		self.capacity = capacity;
+	}
+
+	/// Add received data to the buffer, applying the overrun policy.
+	///
+	/// Returns the part of the data that was added to the buffer.
+	fn push<'a>(&mut self, data: &'a [u8]) -> &'a [u8] {
+		let was_empty = self.buffer.is_empty();
+		let mut data = data;
+		if self.overrun_policy == OverrunPolicy::DropNewest {
+			let space = self.capacity.saturating_sub(self.buffer.len());
+			if data.len() > space {
+				self.dropped_bytes += (data.len() - space) as u64;
+				data = &data[..space];
+			}
+		}
+		self.buffer.extend(data);
+		if self.buffer.len() > self.capacity {
+			let excess = self.buffer.len() - self.capacity;
+			self.buffer.drain(..excess);
+			self.dropped_bytes += excess as u64;
+			// The oldest data was discarded, so the remaining data is treated as if it just arrived.
+			self.first_arrival = Some(Instant::now());
+		}
+		if was_empty && !self.buffer.is_empty() {
+			self.first_arrival = Some(Instant::now());
+		}
+		data
+	}
+
+	/// Take data from the front of the buffer.
+	fn take(&mut self, buf: &mut [u8]) -> usize {
+		let len = buf.len().min(self.buffer.len());
+		for (dest, byte) in buf.iter_mut().zip(self.buffer.drain(..len)) {
+			*dest = byte;
+		}
+		self.update_first_arrival();
+		len
+	}
+
+	/// Update the arrival time of the oldest byte after taking data from the buffer.
+	///
+	/// We do not track the arrival time of individual bytes,
+	/// so the remaining data is treated as if it just arrived.
+	fn update_first_arrival(&mut self) {
+		if self.buffer.is_empty() {
+			self.first_arrival = None;
+		} else {
+			self.first_arrival = Some(Instant::now());
+		}
+	}
+}
+
+/// Lock a mutex, ignoring poisoning.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+	mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The write timeout used by a [`BufferedSerialPort`], so that writes do not block.
+#[cfg(unix)]
+const NON_BLOCKING_WRITE_TIMEOUT: Duration = Duration::ZERO;
+
+/// The write timeout used by a [`BufferedSerialPort`], so that writes do not block.
+///
+/// A timeout of zero disables the write timeout on Windows, so use the shortest timeout that is not zero instead.
+#[cfg(windows)]
+const NON_BLOCKING_WRITE_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// A serial port with an internal write queue, for single threaded poll loops.
+///
+/// Data queued with [`Self::queue_write()`] is written as far as possible without blocking.
+/// The rest stays in the queue until [`Self::write_pending()`] is called,
+/// typically when the poll loop reports that the serial port is writable.
+/// Use [`Self::wants_write()`] to decide if the poll loop should wait for the serial port to become writable.
+///
+/// The write timeout of the serial port is set to zero (or as close to zero as the platform allows),
+/// and restored by [`Self::into_inner()`].
+/// Reads are not affected: use [`Self::read()`] or [`Self::port()`] as usual.
+pub struct BufferedSerialPort {
+	/// The serial port.
+	port: SerialPort,
+
+	/// The data that has not been written yet.
+	pending: VecDeque<u8>,
+
+	/// The write timeout of the serial port before it was wrapped.
+	original_write_timeout: Duration,
+}
+
+impl BufferedSerialPort {
+	/// Wrap a serial port with an empty write queue.
+	pub fn new(mut port: SerialPort) -> std::io::Result<Self> {
+		let original_write_timeout = port.get_write_timeout()?;
+		port.set_write_timeout(NON_BLOCKING_WRITE_TIMEOUT)?;
+		Ok(Self {
+			port,
+			pending: VecDeque::new(),
+			original_write_timeout,
+		})
+	}
+
+	/// Get a reference to the serial port.
+	///
+	/// Use this to register the serial port with a poll loop, or to change its configuration.
+	/// Note that writing to the serial port directly bypasses the queue.
+	pub fn port(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Queue data to be written, and write as much of the queue as possible without blocking.
+	///
+	/// All data is queued, even if writing fails.
+	/// An error is only returned for failures other than a full output buffer.
+	pub fn queue_write(&mut self, data: &[u8]) -> std::io::Result<()> {
+		self.pending.extend(data);
+		self.write_pending()?;
+		Ok(())
+	}
+
+	/// Write as much of the queue as possible without blocking.
+	///
+	/// Returns the number of bytes written.
+	/// Call this when the poll loop reports that the serial port is writable.
+	pub fn write_pending(&mut self) -> std::io::Result<usize> {
+		let mut written = 0;
+		while !self.pending.is_empty() {
+			let (data, _) = self.pending.as_slices();
+			match self.port.write(data) {
+				Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+				Ok(count) => {
+					self.pending.drain(..count);
+					written += count;
+				},
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => break,
+				Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(written)
+	}
+
+	/// Get the number of bytes in the queue that have not been written yet.
+	pub fn pending_bytes(&self) -> usize {
+		self.pending.len()
+	}
+
+	/// Check if the queue contains data, so the poll loop should wait for the serial port to become writable.
+	pub fn wants_write(&self) -> bool {
+		!self.pending.is_empty()
+	}
+
+	/// Discard all data in the queue that has not been written yet.
+	///
+	/// Returns the number of bytes discarded.
+	/// Data that was already handed to the kernel is not affected: use [`SerialPort::discard_output_buffer()`] for that.
+	pub fn discard_pending(&mut self) -> usize {
+		let discarded = self.pending.len();
+		self.pending.clear();
+		discarded
+	}
+
+	/// Read data from the serial port.
+	///
+	/// See [`SerialPort::read()`] for more information.
+	pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		self.port.read(buf)
+	}
+
+	/// Restore the original write timeout and return the serial port, together with the data that was not written yet.
+	pub fn into_inner(mut self) -> std::io::Result<(SerialPort, Vec<u8>)> {
+		self.port.set_write_timeout(self.original_write_timeout)?;
+		Ok((self.port, self.pending.into()))
+	}
+}
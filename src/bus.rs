@@ -0,0 +1,537 @@
+//! Scheduling of request/response transactions on a shared half-duplex bus.
+//!
+//! On a half-duplex multidrop bus (such as two-wire RS-485) only one device may transmit at a time.
+//! A bus master typically sends a request to one device and then waits for the response,
+//! before the next request may be sent.
+//!
+//! The [`BusScheduler`] serializes these transactions, even when they are submitted from multiple threads.
+//! Pending transactions are started in order of their [`Priority`],
+//! and the scheduler enforces a minimum gap between transactions and a timeout for each transaction.
+//! It can also drive the RTS line to control the direction of the transceiver,
+//! if that is not already done by the hardware or the kernel.
+//!
+//! On a two-wire bus, the transmitter also receives its own data.
+//! With echo cancellation enabled, [`BusTransaction::write_all()`] reads back and verifies that echo,
+//! so the application only sees data from other devices.
+//! A mismatching echo is reported as an [`EchoMismatch`] error, which indicates contention on the bus.
+//! For multi-master buses without an arbiter, the scheduler can automatically retransmit after a collision,
+//! with a random backoff.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::{Priority, SerialPort};
+
+/// Configuration for a [`BusScheduler`].
+#[derive(Debug, Clone)]
+pub struct BusConfig {
+	/// The maximum duration of a single transaction.
+	transaction_timeout: Duration,
+
+	/// The minimum time between the end of one transaction and the start of the next.
+	inter_transaction_gap: Duration,
+
+	/// How the direction of the transceiver is controlled.
+	direction_control: DirectionControl,
+
+	/// If true, the echo of transmitted data is read back and verified.
+	echo_cancellation: bool,
+
+	/// The maximum number of retransmissions after a collision.
+	collision_retries: u32,
+
+	/// The base duration for the random backoff after a collision.
+	collision_backoff: Duration,
+}
+
+/// How the direction of a half-duplex transceiver is controlled.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DirectionControl {
+	/// The direction is controlled automatically by the hardware or the kernel.
+	///
+	/// Use this if the transceiver switches direction by itself,
+	/// or if the kernel has been configured to do it with `SerialPort::set_rs4xx_mode()`.
+	Automatic,
+
+	/// The scheduler sets the RTS line high while transmitting, and low otherwise.
+	Rts,
+
+	/// The scheduler sets the RTS line low while transmitting, and high otherwise.
+	RtsInverted,
+}
+
+/// Serializes request/response transactions on a shared half-duplex bus.
+///
+/// See the [module documentation][self] for more information.
+pub struct BusScheduler {
+	/// The serial port connected to the bus.
+	port: Mutex<SerialPort>,
+
+	/// The configuration of the scheduler.
+	config: BusConfig,
+
+	/// The queue of pending transactions.
+	queue: Mutex<Queue>,
+
+	/// Condition variable used to wake up pending transactions when the bus is released.
+	released: Condvar,
+}
+
+/// A transaction in progress on a [`BusScheduler`].
+///
+/// All reads and writes are limited by the deadline of the transaction.
+pub struct BusTransaction<'a> {
+	/// The serial port connected to the bus.
+	port: &'a mut SerialPort,
+
+	/// The deadline of the transaction.
+	deadline: Instant,
+
+	/// The configuration of the scheduler.
+	config: &'a BusConfig,
+
+	/// The read timeout of the serial port before the transaction, restored when the transaction ends.
+	read_timeout: Duration,
+
+	/// The write timeout of the serial port before the transaction, restored when the transaction ends.
+	write_timeout: Duration,
+}
+
+/// The echo of transmitted data did not match the transmitted data.
+///
+/// This usually means that another device was transmitting at the same time.
+///
+/// When echo cancellation is enabled, [`BusTransaction::write_all()`] returns an [`std::io::Error`]
+/// of the type [`std::io::ErrorKind::InvalidData`] that wraps a value of this type.
+/// You can retrieve it with [`std::io::Error::get_ref()`] followed by `downcast_ref::<EchoMismatch>()`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EchoMismatch {
+	/// The transmitted data.
+	sent: Vec<u8>,
+
+	/// The received echo.
+	received: Vec<u8>,
+}
+
+impl BusConfig {
+	/// Create a new configuration with a transaction timeout of 1 second and no gap between transactions.
+	pub fn new() -> Self {
+		Self {
+			transaction_timeout: Duration::from_secs(1),
+			inter_transaction_gap: Duration::ZERO,
+			direction_control: DirectionControl::Automatic,
+			echo_cancellation: false,
+			collision_retries: 0,
+			collision_backoff: Duration::from_millis(10),
+		}
+	}
+
+	/// Set the maximum duration of a single transaction.
+	///
+	/// The timeout starts when the transaction is granted access to the bus,
+	/// so time spent waiting for other transactions does not count.
+	pub fn set_transaction_timeout(&mut self, timeout: Duration) {
+		self.transaction_timeout = timeout;
+	}
+
+	/// Get the maximum duration of a single transaction.
+	pub fn get_transaction_timeout(&self) -> Duration {
+		self.transaction_timeout
+	}
+
+	/// Set the minimum time between the end of one transaction and the start of the next.
+	///
+	/// Many protocols require the bus to be silent for some time between messages.
+	pub fn set_inter_transaction_gap(&mut self, gap: Duration) {
+		self.inter_transaction_gap = gap;
+	}
+
+	/// Get the minimum time between the end of one transaction and the start of the next.
+	pub fn get_inter_transaction_gap(&self) -> Duration {
+		self.inter_transaction_gap
+	}
+
+	/// Set how the direction of the transceiver is controlled.
+	pub fn set_direction_control(&mut self, direction_control: DirectionControl) {
+		self.direction_control = direction_control;
+	}
+
+	/// Get how the direction of the transceiver is controlled.
+	pub fn get_direction_control(&self) -> DirectionControl {
+		self.direction_control
+	}
+
+	/// Enable or disable echo cancellation.
+	///
+	/// With echo cancellation enabled, [`BusTransaction::write_all()`] reads back the echo of the transmitted data
+	/// and verifies that it matches, before returning.
+	/// Use this on buses where the transmitter also receives its own data, such as two-wire RS-485.
+	///
+	/// Echo cancellation is disabled by default.
+	pub fn set_echo_cancellation(&mut self, enable: bool) {
+		self.echo_cancellation = enable;
+	}
+
+	/// Check if echo cancellation is enabled.
+	pub fn get_echo_cancellation(&self) -> bool {
+		self.echo_cancellation
+	}
+
+	/// Set the maximum number of retransmissions after a collision.
+	///
+	/// A collision is detected when the echo of transmitted data does not match,
+	/// so this only has an effect if echo cancellation is enabled.
+	///
+	/// After a collision, [`BusTransaction::write_all()`] waits until the bus has been silent for the backoff duration,
+	/// waits for a random time between zero and the backoff duration, and transmits the data again.
+	/// The backoff duration doubles after each collision.
+	///
+	/// The default is 0: collisions are reported as an error without retransmission.
+	pub fn set_collision_retries(&mut self, retries: u32) {
+		self.collision_retries = retries;
+	}
+
+	/// Get the maximum number of retransmissions after a collision.
+	pub fn get_collision_retries(&self) -> u32 {
+		self.collision_retries
+	}
+
+	/// Set the base duration for the random backoff after a collision.
+	///
+	/// See [`Self::set_collision_retries()`] for more information.
+	///
+	/// The default is 10 milliseconds.
+	pub fn set_collision_backoff(&mut self, backoff: Duration) {
+		self.collision_backoff = backoff;
+	}
+
+	/// Get the base duration for the random backoff after a collision.
+	pub fn get_collision_backoff(&self) -> Duration {
+		self.collision_backoff
+	}
+}
+
+impl Default for BusConfig {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl BusScheduler {
+	/// Create a new scheduler for a bus connected to the given serial port.
+	///
+	/// If the scheduler controls the direction of the transceiver,
+	/// this puts the transceiver in receive mode.
+	pub fn new(port: SerialPort, config: BusConfig) -> std::io::Result<Self> {
+		set_transmit(&port, config.direction_control, false)?;
+		Ok(Self {
+			port: Mutex::new(port),
+			config,
+			queue: Mutex::new(Queue::default()),
+			released: Condvar::new(),
+		})
+	}
+
+	/// Get the configuration of the scheduler.
+	pub fn config(&self) -> &BusConfig {
+		&self.config
+	}
+
+	/// Run a transaction on the bus.
+	///
+	/// This blocks until all transactions with a higher priority and all earlier transactions with the same priority have finished,
+	/// and the configured gap between transactions has passed.
+	/// Then the closure is called with exclusive access to the bus.
+	///
+	/// If the closure returns an error or panics, the bus is still released for the next transaction.
+	/// The transaction changes the read and write timeouts of the serial port to enforce its deadline,
+	/// and restores them when it ends.
+	pub fn transaction<F, T>(&self, priority: Priority, f: F) -> std::io::Result<T>
+	where
+		F: FnOnce(&mut BusTransaction<'_>) -> std::io::Result<T>,
+	{
+		let _grant = self.acquire(priority);
+		let mut port = lock(&self.port);
+		let read_timeout = port.get_read_timeout()?;
+		let write_timeout = port.get_write_timeout()?;
+		let mut transaction = BusTransaction {
+			port: &mut port,
+			deadline: Instant::now() + self.config.transaction_timeout,
+			config: &self.config,
+			read_timeout,
+			write_timeout,
+		};
+		f(&mut transaction)
+	}
+
+	/// Consume the scheduler and return the serial port.
+	pub fn into_inner(self) -> SerialPort {
+		self.port.into_inner().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Wait for our turn to use the bus.
+	fn acquire(&self, priority: Priority) -> Grant<'_> {
+		let mut queue = lock(&self.queue);
+		let ticket = Ticket {
+			priority,
+			sequence: Reverse(queue.next_sequence),
+		};
+		queue.next_sequence += 1;
+		queue.waiting.push(ticket);
+		while queue.busy || queue.waiting.peek() != Some(&ticket) {
+			queue = self.released.wait(queue).unwrap_or_else(PoisonError::into_inner);
+		}
+		queue.waiting.pop();
+		queue.busy = true;
+		let last_end = queue.last_end;
+		drop(queue);
+
+		if let Some(last_end) = last_end {
+			let elapsed = last_end.elapsed();
+			if elapsed < self.config.inter_transaction_gap {
+				std::thread::sleep(self.config.inter_transaction_gap - elapsed);
+			}
+		}
+
+		Grant { scheduler: self }
+	}
+
+	/// Release the bus for the next transaction.
+	fn release(&self) {
+		let mut queue = lock(&self.queue);
+		queue.busy = false;
+		queue.last_end = Some(Instant::now());
+		drop(queue);
+		self.released.notify_all();
+	}
+}
+
+impl BusTransaction<'_> {
+	/// Get the time remaining until the deadline of the transaction.
+	pub fn remaining(&self) -> Duration {
+		self.deadline.saturating_duration_since(Instant::now())
+	}
+
+	/// Get a reference to the underlying serial port.
+	pub fn port(&self) -> &SerialPort {
+		self.port
+	}
+
+	/// Transmit all bytes on the bus.
+	///
+	/// If the scheduler controls the direction of the transceiver,
+	/// it is switched to transmit mode before writing and back to receive mode after all data has been transmitted.
+	///
+	/// If echo cancellation is enabled, this also reads back the echo of the data.
+	/// If the echo does not match, the data is retransmitted if configured with [`BusConfig::set_collision_retries()`].
+	/// When no retries are left, an error wrapping an [`EchoMismatch`] is returned.
+	pub fn write_all(&mut self, data: &[u8]) -> std::io::Result<()> {
+		let mut backoff = self.config.collision_backoff;
+		let mut retries = self.config.collision_retries;
+		loop {
+			match self.transmit(data) {
+				Err(e) if retries > 0 && is_echo_mismatch(&e) => {
+					retries -= 1;
+					self.wait_for_silence(backoff)?;
+					std::thread::sleep(random_duration(backoff).min(self.remaining()));
+					backoff = backoff.saturating_mul(2);
+				},
+				result => return result,
+			}
+		}
+	}
+
+	/// Transmit all bytes on the bus once, and verify the echo if echo cancellation is enabled.
+	fn transmit(&mut self, data: &[u8]) -> std::io::Result<()> {
+		let direction_control = self.config.direction_control;
+		self.port.set_write_timeout(self.check_remaining()?)?;
+		set_transmit(self.port, direction_control, true)?;
+		let result = self.port.write_all(data).and_then(|()| self.port.flush());
+		let restored = set_transmit(self.port, direction_control, false);
+		result.and(restored)?;
+		if self.config.echo_cancellation {
+			self.verify_echo(data)?;
+		}
+		Ok(())
+	}
+
+	/// Discard received data until the bus has been silent for the given duration.
+	fn wait_for_silence(&mut self, duration: Duration) -> std::io::Result<()> {
+		let mut buffer = [0; 256];
+		let mut silent_since = Instant::now();
+		loop {
+			let silence = silent_since.elapsed();
+			if silence >= duration {
+				return Ok(());
+			}
+			let timeout = (duration - silence).min(self.check_remaining()?);
+			self.port.set_read_timeout(timeout)?;
+			match self.port.read(&mut buffer) {
+				Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+				Ok(_) => silent_since = Instant::now(),
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Read bytes from the bus.
+	///
+	/// Returns an error of the type [`std::io::ErrorKind::TimedOut`] if the deadline of the transaction passes before any data is received.
+	pub fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		self.port.set_read_timeout(self.check_remaining()?)?;
+		self.port.read(buf)
+	}
+
+	/// Read the exact number of bytes required to fill the buffer from the bus.
+	///
+	/// Returns an error of the type [`std::io::ErrorKind::TimedOut`] if the deadline of the transaction passes before the buffer is filled.
+	pub fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+		let mut buf = buf;
+		while !buf.is_empty() {
+			match self.read(buf) {
+				Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "failed to fill whole buffer")),
+				Ok(n) => buf = &mut buf[n..],
+				Err(e) => {
+					if e.kind() != std::io::ErrorKind::Interrupted {
+						return Err(e);
+					} else {
+						continue;
+					}
+				},
+			}
+		}
+		Ok(())
+	}
+
+	/// Read back the echo of transmitted data and verify that it matches.
+	fn verify_echo(&mut self, data: &[u8]) -> std::io::Result<()> {
+		let mut echo = vec![0; data.len()];
+		self.read_exact(&mut echo)?;
+		if echo != data {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				EchoMismatch {
+					sent: data.to_vec(),
+					received: echo,
+				},
+			));
+		}
+		Ok(())
+	}
+
+	/// Get the remaining time of the transaction, or a timeout error if the deadline has passed.
+	fn check_remaining(&self) -> std::io::Result<Duration> {
+		let remaining = self.remaining();
+		if remaining.is_zero() {
+			Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "bus transaction timed out"))
+		} else {
+			Ok(remaining)
+		}
+	}
+}
+
+impl Drop for BusTransaction<'_> {
+	fn drop(&mut self) {
+		let _ = self.port.set_read_timeout(self.read_timeout);
+		let _ = self.port.set_write_timeout(self.write_timeout);
+	}
+}
+
+impl EchoMismatch {
+	/// Get the transmitted data.
+	pub fn sent(&self) -> &[u8] {
+		&self.sent
+	}
+
+	/// Get the received echo.
+	pub fn received(&self) -> &[u8] {
+		&self.received
+	}
+
+	/// Get the position of the first byte that did not match.
+	pub fn position(&self) -> usize {
+		self.sent
+			.iter()
+			.zip(&self.received)
+			.position(|(sent, received)| sent != received)
+			.unwrap_or(self.sent.len().min(self.received.len()))
+	}
+}
+
+impl std::fmt::Display for EchoMismatch {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "echo mismatch at byte {}: possible bus contention", self.position())
+	}
+}
+
+impl std::error::Error for EchoMismatch {}
+
+/// The queue of transactions waiting for the bus.
+#[derive(Default)]
+struct Queue {
+	/// True if a transaction is currently using the bus.
+	busy: bool,
+
+	/// The tickets of all waiting transactions.
+	waiting: BinaryHeap<Ticket>,
+
+	/// The sequence number for the next ticket.
+	next_sequence: u64,
+
+	/// The time when the last transaction released the bus.
+	last_end: Option<Instant>,
+}
+
+/// A place in the queue of waiting transactions.
+///
+/// Tickets with a higher priority sort first, then tickets with a lower sequence number.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+struct Ticket {
+	priority: Priority,
+	sequence: Reverse<u64>,
+}
+
+/// Exclusive access to the bus, released when dropped.
+struct Grant<'a> {
+	scheduler: &'a BusScheduler,
+}
+
+impl Drop for Grant<'_> {
+	fn drop(&mut self) {
+		self.scheduler.release();
+	}
+}
+
+/// Check if an error was caused by a mismatching echo.
+fn is_echo_mismatch(error: &std::io::Error) -> bool {
+	error.get_ref().is_some_and(|inner| inner.is::<EchoMismatch>())
+}
+
+/// Get a random duration between zero and `max`.
+fn random_duration(max: Duration) -> Duration {
+	use std::hash::{BuildHasher, Hasher};
+	// The standard library seeds each `RandomState` randomly, which is good enough for a backoff.
+	let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+	let nanos = max.as_nanos().min(u64::MAX as u128) as u64;
+	Duration::from_nanos(random.checked_rem(nanos).unwrap_or(0))
+}
+
+/// Switch the transceiver to transmit or receive mode.
+fn set_transmit(port: &SerialPort, direction_control: DirectionControl, transmit: bool) -> std::io::Result<()> {
+	match direction_control {
+		DirectionControl::Automatic => Ok(()),
+		DirectionControl::Rts => port.set_rts(transmit),
+		DirectionControl::RtsInverted => port.set_rts(!transmit),
+	}
+}
+
+/// Lock a mutex, ignoring poisoning.
+///
+/// The data protected by the mutexes in this module remains consistent even if a transaction panics.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+	mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,142 @@
+use std::sync::{PoisonError, RwLock};
+
+/// A transformation applied to every transmitted and received byte.
+///
+/// Some links change the bits of every byte on the wire,
+/// for example inverted logic levels (like SBUS) read through a non-inverting adapter,
+/// devices that transmit the most significant bit first,
+/// or simple XOR scrambling.
+/// A byte transform undoes this below all other layers, so framers and decoders only ever see plain data.
+///
+/// A transform consists of a mapping for transmitted bytes and a mapping for received bytes.
+/// The built-in operations can be chained, and they always update both mappings:
+/// transmitted bytes have the operations applied in order,
+/// and received bytes have the inverse operations applied in reverse order.
+///
+/// Use [`SerialPort::set_byte_transform()`][crate::SerialPort::set_byte_transform] to enable a transform.
+///
+/// # Example
+/// ```
+/// # use serial2::ByteTransform;
+/// let transform = ByteTransform::new().invert().reverse_bits();
+/// assert_eq!(transform.transmit(0x01), 0x7F);
+/// assert_eq!(transform.receive(0x7F), 0x01);
+/// ```
+#[derive(Clone)]
+pub struct ByteTransform {
+	/// The mapping for transmitted bytes.
+	tx: [u8; 256],
+
+	/// The mapping for received bytes.
+	rx: [u8; 256],
+}
+
+impl ByteTransform {
+	/// Create a transform that does not change any byte.
+	pub fn new() -> Self {
+		let identity = std::array::from_fn(|i| i as u8);
+		Self { tx: identity, rx: identity }
+	}
+
+	/// Create a transform from custom functions for transmitted and received bytes.
+	///
+	/// The function for received bytes should normally be the inverse of the function for transmitted bytes.
+	pub fn from_fn(tx: impl Fn(u8) -> u8, rx: impl Fn(u8) -> u8) -> Self {
+		Self {
+			tx: std::array::from_fn(|i| tx(i as u8)),
+			rx: std::array::from_fn(|i| rx(i as u8)),
+		}
+	}
+
+	/// Invert all bits, to compensate for inverted logic levels.
+	pub fn invert(self) -> Self {
+		self.then(|byte| !byte, |byte| !byte)
+	}
+
+	/// Reverse the bit order, to talk to devices that transmit the most significant bit first.
+	pub fn reverse_bits(self) -> Self {
+		self.then(u8::reverse_bits, u8::reverse_bits)
+	}
+
+	/// XOR all bytes with a fixed key.
+	pub fn xor(self, key: u8) -> Self {
+		self.then(|byte| byte ^ key, |byte| byte ^ key)
+	}
+
+	/// Apply the transform to a transmitted byte.
+	pub fn transmit(&self, byte: u8) -> u8 {
+		self.tx[usize::from(byte)]
+	}
+
+	/// Apply the transform to a received byte.
+	pub fn receive(&self, byte: u8) -> u8 {
+		self.rx[usize::from(byte)]
+	}
+
+	/// Apply the transform to a buffer of transmitted bytes in place.
+	pub fn transmit_in_place(&self, data: &mut [u8]) {
+		for byte in data {
+			*byte = self.transmit(*byte);
+		}
+	}
+
+	/// Apply the transform to a buffer of received bytes in place.
+	pub fn receive_in_place(&self, data: &mut [u8]) {
+		for byte in data {
+			*byte = self.receive(*byte);
+		}
+	}
+
+	/// Add an operation after the existing operations for transmitted bytes,
+	/// and its inverse before the existing operations for received bytes.
+	fn then(mut self, tx: impl Fn(u8) -> u8, rx_inverse: impl Fn(u8) -> u8) -> Self {
+		for byte in &mut self.tx {
+			*byte = tx(*byte);
+		}
+		let rx = self.rx;
+		self.rx = std::array::from_fn(|i| rx[usize::from(rx_inverse(i as u8))]);
+		self
+	}
+}
+
+impl Default for ByteTransform {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl std::fmt::Debug for ByteTransform {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ByteTransform").finish_non_exhaustive()
+	}
+}
+
+/// The byte transform of a serial port, if any.
+#[derive(Default)]
+pub(crate) struct TransformSlot {
+	/// The transform.
+	transform: RwLock<Option<Box<ByteTransform>>>,
+}
+
+impl TransformSlot {
+	/// Set or clear the transform.
+	pub(crate) fn set(&self, transform: Option<ByteTransform>) {
+		*self.transform.write().unwrap_or_else(PoisonError::into_inner) = transform.map(Box::new);
+	}
+
+	/// Check if a transform is set.
+	pub(crate) fn is_set(&self) -> bool {
+		self.with(|_| ()).is_some()
+	}
+
+	/// Get a copy of the transform.
+	pub(crate) fn get(&self) -> Option<ByteTransform> {
+		self.with(|transform| transform.clone())
+	}
+
+	/// Run a function with the transform, if there is one.
+	pub(crate) fn with<T>(&self, f: impl FnOnce(&ByteTransform) -> T) -> Option<T> {
+		let transform = self.transform.read().unwrap_or_else(PoisonError::into_inner);
+		transform.as_deref().map(f)
+	}
+}
@@ -0,0 +1,164 @@
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{Annotation, CaptureSink, Direction};
+
+/// Writes captured data as a console session recording in the asciicast v2 format.
+///
+/// The recording can be replayed with `asciinema play` or embedded in a web page with the asciinema player.
+/// Received data is recorded as terminal output (`"o"` events) and transmitted data as keyboard input (`"i"` events).
+/// Annotations are recorded as markers (`"m"` events).
+///
+/// Event times are relative to the creation of the writer.
+/// Data that is not valid UTF-8 is replaced with the Unicode replacement character,
+/// but multi-byte characters split over multiple chunks are reassembled.
+pub struct AsciicastWriter<W: Write> {
+	/// The output stream.
+	output: W,
+
+	/// The start time of the recording.
+	start: SystemTime,
+
+	/// An incomplete UTF-8 sequence at the end of the received data.
+	rx_pending: Vec<u8>,
+
+	/// An incomplete UTF-8 sequence at the end of the transmitted data.
+	tx_pending: Vec<u8>,
+}
+
+impl<W: Write> AsciicastWriter<W> {
+	/// Create a new asciicast writer for a terminal of 80 by 24 characters.
+	///
+	/// This immediately writes the header.
+	pub fn new(output: W) -> std::io::Result<Self> {
+		Self::with_size(output, 80, 24)
+	}
+
+	/// Create a new asciicast writer for a terminal of the given size.
+	///
+	/// This immediately writes the header.
+	pub fn with_size(output: W, width: u16, height: u16) -> std::io::Result<Self> {
+		let start = SystemTime::now();
+		let mut writer = Self {
+			output,
+			start,
+			rx_pending: Vec::new(),
+			tx_pending: Vec::new(),
+		};
+		let timestamp = start.duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+		writeln!(
+			writer.output,
+			r#"{{"version": 2, "width": {width}, "height": {height}, "timestamp": {timestamp}}}"#
+		)?;
+		Ok(writer)
+	}
+
+	/// Get a reference to the output stream.
+	pub fn get_ref(&self) -> &W {
+		&self.output
+	}
+
+	/// Get a mutable reference to the output stream.
+	///
+	/// Writing directly to the output stream will corrupt the recording.
+	pub fn get_mut(&mut self) -> &mut W {
+		&mut self.output
+	}
+
+	/// Consume the writer and return the output stream.
+	pub fn into_inner(self) -> W {
+		self.output
+	}
+
+	/// Write an event for captured data.
+	///
+	/// Received data is written as output, transmitted data as input.
+	pub fn write_data(&mut self, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()> {
+		let (code, pending) = match direction {
+			Direction::Rx => ("o", &mut self.rx_pending),
+			Direction::Tx => ("i", &mut self.tx_pending),
+		};
+		let text = decode_utf8(pending, data);
+		if text.is_empty() {
+			return Ok(());
+		}
+		self.write_event(timestamp, code, &text)
+	}
+
+	/// Write an annotation as a marker.
+	pub fn write_marker(&mut self, timestamp: SystemTime, annotation: &Annotation) -> std::io::Result<()> {
+		self.write_event(timestamp, "m", annotation.label())
+	}
+
+	/// Write a single event line.
+	fn write_event(&mut self, timestamp: SystemTime, code: &str, data: &str) -> std::io::Result<()> {
+		let time = timestamp.duration_since(self.start).unwrap_or_default().as_secs_f64();
+		let mut line = format!("[{time:.6}, \"{code}\", \"");
+		escape_json(&mut line, data);
+		line.push_str("\"]\n");
+		self.output.write_all(line.as_bytes())
+	}
+}
+
+impl<W: Write> CaptureSink for AsciicastWriter<W> {
+	fn record(&mut self, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()> {
+		self.write_data(timestamp, direction, data)
+	}
+
+	fn annotate(&mut self, timestamp: SystemTime, annotation: &Annotation) -> std::io::Result<()> {
+		self.write_marker(timestamp, annotation)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.output.flush()
+	}
+}
+
+/// Decode data as UTF-8, keeping an incomplete sequence at the end for the next call.
+fn decode_utf8(pending: &mut Vec<u8>, data: &[u8]) -> String {
+	pending.extend_from_slice(data);
+	let mut text = String::with_capacity(pending.len());
+	let mut remaining = &pending[..];
+	loop {
+		match std::str::from_utf8(remaining) {
+			Ok(valid) => {
+				text.push_str(valid);
+				remaining = &[];
+				break;
+			},
+			Err(e) => {
+				let (valid, rest) = remaining.split_at(e.valid_up_to());
+				// The prefix was just validated, so this can not fail.
+				text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+				match e.error_len() {
+					Some(len) => {
+						text.push(char::REPLACEMENT_CHARACTER);
+						remaining = &rest[len..];
+					},
+					None => {
+						remaining = rest;
+						break;
+					},
+				}
+			},
+		}
+	}
+	let consumed = pending.len() - remaining.len();
+	pending.drain(..consumed);
+	text
+}
+
+/// Append a string to a JSON string literal, escaping special characters.
+fn escape_json(output: &mut String, data: &str) {
+	for c in data.chars() {
+		match c {
+			'"' => output.push_str("\\\""),
+			'\\' => output.push_str("\\\\"),
+			'\n' => output.push_str("\\n"),
+			'\r' => output.push_str("\\r"),
+			'\t' => output.push_str("\\t"),
+			c if u32::from(c) < 0x20 || c == '\u{7F}' => output.push_str(&format!("\\u{:04x}", u32::from(c))),
+			c => output.push(c),
+		}
+	}
+}
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+/// A clock for timestamps of captured traffic.
+///
+/// By default, the clock reads the wall clock once when it is created,
+/// and measures all later timestamps from that point with a monotonic clock.
+/// Timestamps from such a clock never go backwards, even if the wall clock of the system is adjusted.
+///
+/// Other clock sources can be selected when creating the clock:
+/// * [`Self::monotonic_from()`] counts from a configurable epoch instead of the current wall clock time,
+///   for timestamps relative to the start of a test run.
+/// * [`Self::realtime()`] reads the wall clock for every timestamp,
+///   so captures can be correlated with system logs, even if the wall clock is adjusted during the capture.
+/// * [`Self::custom()`] calls a user provided function for every timestamp,
+///   for example to read a PTP disciplined hardware clock on a test bench.
+///
+/// Use the same clock for multiple taps with [`Tap::set_clock()`][super::Tap::set_clock]
+/// to get timestamps that can be compared between ports.
+/// A [`CaptureMerger`][super::CaptureMerger] provides a clock for all merged ports.
+/// The clock can also be used to timestamp frames with [`FrameReader::set_clock()`][crate::framing::FrameReader::set_clock].
+///
+/// Cloning a clock is cheap, and all clones report the same time.
+#[derive(Clone)]
+pub struct CaptureClock {
+	/// The source of the timestamps.
+	source: ClockSource,
+}
+
+/// The source of the timestamps of a [`CaptureClock`].
+#[derive(Clone)]
+enum ClockSource {
+	/// A monotonic clock that started at a fixed time.
+	Monotonic {
+		/// The monotonic time at which the clock was created.
+		base_instant: Instant,
+
+		/// The time reported by the clock when it was created.
+		base_time: SystemTime,
+	},
+
+	/// The wall clock of the system.
+	Realtime,
+
+	/// A user provided function.
+	Custom(Arc<dyn Fn() -> SystemTime + Send + Sync>),
+}
+
+impl CaptureClock {
+	/// Create a new monotonic clock, starting at the current wall clock time.
+	pub fn new() -> Self {
+		Self::monotonic_from(SystemTime::now())
+	}
+
+	/// Create a new monotonic clock, starting at the given epoch.
+	///
+	/// The clock reports `epoch` right now, and measures all later timestamps from that point with a monotonic clock.
+	/// Pass [`std::time::UNIX_EPOCH`] to get timestamps relative to the creation of the clock.
+	pub fn monotonic_from(epoch: SystemTime) -> Self {
+		Self {
+			source: ClockSource::Monotonic {
+				base_instant: Instant::now(),
+				base_time: epoch,
+			},
+		}
+	}
+
+	/// Create a clock that reads the wall clock of the system for every timestamp.
+	///
+	/// Timestamps follow adjustments of the wall clock, so they may go backwards.
+	pub fn realtime() -> Self {
+		Self {
+			source: ClockSource::Realtime,
+		}
+	}
+
+	/// Create a clock that calls a function for every timestamp.
+	pub fn custom<F>(now: F) -> Self
+	where
+		F: Fn() -> SystemTime + Send + Sync + 'static,
+	{
+		Self {
+			source: ClockSource::Custom(Arc::new(now)),
+		}
+	}
+
+	/// Check if the timestamps of the clock never go backwards.
+	///
+	/// This is only guaranteed for clocks created with [`Self::new()`] or [`Self::monotonic_from()`].
+	pub fn is_monotonic(&self) -> bool {
+		matches!(self.source, ClockSource::Monotonic { .. })
+	}
+
+	/// Get the current time of the clock.
+	pub fn now(&self) -> SystemTime {
+		match &self.source {
+			ClockSource::Monotonic { base_instant, base_time } => *base_time + base_instant.elapsed(),
+			ClockSource::Realtime => SystemTime::now(),
+			ClockSource::Custom(now) => now(),
+		}
+	}
+
+	/// Get the time of the clock at a past instant.
+	///
+	/// For clocks that are not monotonic, this subtracts the time elapsed since `instant` from the current time.
+	pub(crate) fn at(&self, instant: Instant) -> SystemTime {
+		match &self.source {
+			ClockSource::Monotonic { base_instant, base_time } => match instant.checked_duration_since(*base_instant) {
+				Some(elapsed) => *base_time + elapsed,
+				None => *base_time - base_instant.duration_since(instant),
+			},
+			_ => {
+				let now = self.now();
+				now.checked_sub(instant.elapsed()).unwrap_or(now)
+			},
+		}
+	}
+}
+
+impl Default for CaptureClock {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl std::fmt::Debug for CaptureClock {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.source {
+			ClockSource::Monotonic { base_instant, base_time } => f
+				.debug_struct("CaptureClock::Monotonic")
+				.field("base_instant", base_instant)
+				.field("base_time", base_time)
+				.finish(),
+			ClockSource::Realtime => f.write_str("CaptureClock::Realtime"),
+			ClockSource::Custom(_) => f.write_str("CaptureClock::Custom"),
+		}
+	}
+}
@@ -0,0 +1,264 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::SystemTime;
+
+use super::{Annotation, CaptureClock, CaptureSink, Direction};
+
+/// A destination for the merged traffic of multiple serial ports.
+///
+/// This is the multi-port version of [`CaptureSink`]: every recorded chunk also carries the index of the port.
+pub trait MergedCaptureSink {
+	/// Register a new port.
+	///
+	/// Ports are numbered in the order they are added to the merger, starting at 0.
+	fn add_port(&mut self, port: usize, name: &str) -> std::io::Result<()>;
+
+	/// Record a chunk of captured data.
+	fn record(&mut self, port: usize, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()>;
+
+	/// Record an annotation produced by an [`Analyzer`][super::Analyzer].
+	///
+	/// The default implementation ignores the annotation.
+	fn annotate(&mut self, port: usize, timestamp: SystemTime, annotation: &Annotation) -> std::io::Result<()> {
+		let _ = (port, timestamp, annotation);
+		Ok(())
+	}
+
+	/// Flush any buffered data to the underlying storage.
+	///
+	/// The default implementation does nothing.
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+/// A chunk of traffic in a merged capture, as recorded by a `Vec<MergedEvent>`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MergedEvent {
+	/// The index of the port.
+	port: usize,
+
+	/// The time the data was captured.
+	timestamp: SystemTime,
+
+	/// The direction of the data.
+	direction: Direction,
+
+	/// The captured data.
+	data: Vec<u8>,
+}
+
+impl MergedEvent {
+	/// Get the index of the port.
+	pub fn port(&self) -> usize {
+		self.port
+	}
+
+	/// Get the time the data was captured.
+	pub fn timestamp(&self) -> SystemTime {
+		self.timestamp
+	}
+
+	/// Get the direction of the data.
+	pub fn direction(&self) -> Direction {
+		self.direction
+	}
+
+	/// Get the captured data.
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+}
+
+/// Records all traffic in memory.
+///
+/// Annotations are ignored.
+impl MergedCaptureSink for Vec<MergedEvent> {
+	fn add_port(&mut self, _port: usize, _name: &str) -> std::io::Result<()> {
+		Ok(())
+	}
+
+	fn record(&mut self, port: usize, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()> {
+		self.push(MergedEvent {
+			port,
+			timestamp,
+			direction,
+			data: data.to_vec(),
+		});
+		Ok(())
+	}
+}
+
+/// Merges the traffic of multiple taps into one trace.
+///
+/// Each port gets a [`MergerInput`] from [`Self::add_port()`], which is used as the capture sink of a [`Tap`][super::Tap].
+/// All inputs record into the same [`MergedCaptureSink`], in the order the traffic was captured.
+///
+/// Timestamps are only comparable between ports if all taps use the clock of the merger:
+/// pass [`Self::clock()`] to [`Tap::set_clock()`][super::Tap::set_clock] for each tap.
+/// Because each tap takes the timestamp before handing the data to the merger,
+/// two ports capturing data at nearly the same time may reach the merger in the opposite order.
+/// The merger then raises the timestamp of the later chunk to that of the earlier one,
+/// so the timestamps in the merged trace never go backwards.
+///
+/// # Example
+/// ```no_run
+/// # use serial2::SerialPort;
+/// # use serial2::capture::{CaptureMerger, PcapngWriter, Tap};
+/// # fn foo() -> std::io::Result<()> {
+/// let output = std::fs::File::create("bus.pcapng")?;
+/// let merger = CaptureMerger::new(PcapngWriter::for_merger(output, 147)?);
+/// let request_port = Tap::new(SerialPort::open("/dev/ttyUSB0", 115200)?, merger.add_port("request")?);
+/// let response_port = Tap::new(SerialPort::open("/dev/ttyUSB1", 115200)?, merger.add_port("response")?);
+/// request_port.set_clock(merger.clock());
+/// response_port.set_clock(merger.clock());
+/// # Ok(())
+/// # }
+/// ```
+pub struct CaptureMerger<S> {
+	/// The state shared with the inputs.
+	shared: Arc<Shared<S>>,
+}
+
+/// A capture sink that feeds the traffic of one port into a [`CaptureMerger`].
+pub struct MergerInput<S> {
+	/// The state shared with the merger.
+	shared: Arc<Shared<S>>,
+
+	/// The index of the port.
+	port: usize,
+}
+
+/// State shared between a merger and its inputs.
+struct Shared<S> {
+	/// The clock for all merged ports.
+	clock: CaptureClock,
+
+	/// The sink and related state.
+	state: Mutex<State<S>>,
+}
+
+/// The mutable state of a merger.
+struct State<S> {
+	/// The merged sink.
+	sink: S,
+
+	/// The number of ports.
+	ports: usize,
+
+	/// The timestamp of the last recorded chunk.
+	last_timestamp: Option<SystemTime>,
+}
+
+impl<S: MergedCaptureSink> CaptureMerger<S> {
+	/// Create a new merger that records into the given sink.
+	///
+	/// The merger uses a monotonic [`CaptureClock`] that starts at the current wall clock time.
+	pub fn new(sink: S) -> Self {
+		Self::with_clock(sink, CaptureClock::new())
+	}
+
+	/// Create a new merger that records into the given sink, using the given clock for all merged ports.
+	pub fn with_clock(sink: S, clock: CaptureClock) -> Self {
+		Self {
+			shared: Arc::new(Shared {
+				clock,
+				state: Mutex::new(State {
+					sink,
+					ports: 0,
+					last_timestamp: None,
+				}),
+			}),
+		}
+	}
+
+	/// Get the clock for all merged ports.
+	pub fn clock(&self) -> CaptureClock {
+		self.shared.clock.clone()
+	}
+
+	/// Add a port to the merger.
+	///
+	/// The returned input should be used as capture sink for the tap of the port.
+	pub fn add_port(&self, name: &str) -> std::io::Result<MergerInput<S>> {
+		let mut state = self.shared.lock();
+		let port = state.ports;
+		state.sink.add_port(port, name)?;
+		state.ports += 1;
+		Ok(MergerInput {
+			shared: self.shared.clone(),
+			port,
+		})
+	}
+
+	/// Get the number of ports added to the merger.
+	pub fn ports(&self) -> usize {
+		self.shared.lock().ports
+	}
+
+	/// Run a function with exclusive access to the merged sink.
+	pub fn with_sink<F, T>(&self, f: F) -> T
+	where
+		F: FnOnce(&mut S) -> T,
+	{
+		f(&mut self.shared.lock().sink)
+	}
+
+	/// Consume the merger and return the merged sink.
+	///
+	/// If inputs of the merger still exist, the merger is returned as error.
+	pub fn try_into_inner(self) -> Result<S, Self> {
+		match Arc::try_unwrap(self.shared) {
+			Ok(shared) => Ok(shared.state.into_inner().unwrap_or_else(PoisonError::into_inner).sink),
+			Err(shared) => Err(Self { shared }),
+		}
+	}
+}
+
+impl<S> MergerInput<S> {
+	/// Get the index of the port in the merged capture.
+	pub fn port(&self) -> usize {
+		self.port
+	}
+
+	/// Get the clock of the merger.
+	pub fn clock(&self) -> CaptureClock {
+		self.shared.clock.clone()
+	}
+}
+
+impl<S: MergedCaptureSink> CaptureSink for MergerInput<S> {
+	fn record(&mut self, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()> {
+		let mut state = self.shared.lock();
+		let timestamp = state.order(timestamp);
+		state.sink.record(self.port, timestamp, direction, data)
+	}
+
+	fn annotate(&mut self, timestamp: SystemTime, annotation: &Annotation) -> std::io::Result<()> {
+		let mut state = self.shared.lock();
+		let timestamp = state.order(timestamp);
+		state.sink.annotate(self.port, timestamp, annotation)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.shared.lock().sink.flush()
+	}
+}
+
+impl<S> Shared<S> {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State<S>> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+impl<S> State<S> {
+	/// Get the timestamp to record for a chunk, so timestamps never go backwards.
+	fn order(&mut self, timestamp: SystemTime) -> SystemTime {
+		let timestamp = match self.last_timestamp {
+			Some(last) if last > timestamp => last,
+			_ => timestamp,
+		};
+		self.last_timestamp = Some(timestamp);
+		timestamp
+	}
+}
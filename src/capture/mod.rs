@@ -0,0 +1,472 @@
+//! Capturing of serial port traffic.
+//!
+//! A [`Tap`] wraps a serial port and records all data read from and written to it in a [`CaptureSink`].
+//! Each recorded chunk of data carries a timestamp and a [`Direction`].
+//!
+//! The [`PcapngWriter`] sink writes the traffic in the pcapng format,
+//! so captures can be opened directly in Wireshark.
+//! The [`AsciicastWriter`] sink records a console session in the asciicast v2 format,
+//! for replay in asciinema compatible players.
+//!
+//! Errors reported by the sink do not interrupt the traffic on the serial port.
+//! Instead, the first error is stored and can be retrieved with [`Tap::take_capture_error()`].
+//!
+//! An [`Analyzer`] can be attached to a tap with [`Tap::set_analyzer()`] to label spans of the traffic,
+//! such as frame boundaries and field names.
+//! The resulting [`Annotation`]s are recorded in the sink alongside the captured data.
+//!
+//! Finally, [`Tap::observer()`] creates a read-only [`Observer`] that receives a copy of all traffic.
+//! The tap can keep a limited history of recent traffic, which is replayed to new observers.
+//!
+//! To capture multiple ports at once, a [`CaptureMerger`] combines the traffic of multiple taps in one trace,
+//! with timestamps from a shared [`CaptureClock`].
+
+use std::io::{IoSlice, IoSliceMut};
+use std::ops::Range;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::SystemTime;
+
+use crate::SerialPort;
+
+mod asciicast;
+pub use asciicast::AsciicastWriter;
+
+mod clock;
+pub use clock::CaptureClock;
+
+mod merge;
+pub use merge::{CaptureMerger, MergedCaptureSink, MergedEvent, MergerInput};
+
+mod observer;
+pub use observer::{CaptureEvent, Observer};
+use observer::Observers;
+
+mod pcapng;
+pub use pcapng::PcapngWriter;
+
+/// The direction of captured data.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Direction {
+	/// Data received from the serial port.
+	Rx,
+
+	/// Data transmitted on the serial port.
+	Tx,
+}
+
+/// A destination for captured data.
+pub trait CaptureSink {
+	/// Record a chunk of captured data.
+	fn record(&mut self, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()>;
+
+	/// Record an annotation produced by an [`Analyzer`].
+	///
+	/// The default implementation ignores the annotation.
+	fn annotate(&mut self, timestamp: SystemTime, annotation: &Annotation) -> std::io::Result<()> {
+		let _ = (timestamp, annotation);
+		Ok(())
+	}
+
+	/// Flush any buffered data to the underlying storage.
+	///
+	/// The default implementation does nothing.
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Labels spans of captured traffic.
+///
+/// Any closure with the same signature as [`Analyzer::analyze()`] can also be used as analyzer.
+pub trait Analyzer {
+	/// Analyze a chunk of captured data.
+	///
+	/// The `offset` is the position of the first byte of `data` in the stream of all data captured in the same direction.
+	/// New annotations should be appended to `annotations`.
+	/// Annotations may refer to data from earlier chunks, for example to mark a complete frame.
+	fn analyze(&mut self, direction: Direction, offset: u64, data: &[u8], annotations: &mut Vec<Annotation>);
+}
+
+impl<F> Analyzer for F
+where
+	F: FnMut(Direction, u64, &[u8], &mut Vec<Annotation>),
+{
+	fn analyze(&mut self, direction: Direction, offset: u64, data: &[u8], annotations: &mut Vec<Annotation>) {
+		self(direction, offset, data, annotations)
+	}
+}
+
+/// A label for a span of captured traffic.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Annotation {
+	/// The direction of the labeled traffic.
+	direction: Direction,
+
+	/// The labeled span, as offsets in the stream of all data captured in the same direction.
+	span: Range<u64>,
+
+	/// The label.
+	label: String,
+}
+
+impl Annotation {
+	/// Create a new annotation.
+	///
+	/// The span is given as offsets in the stream of all data captured in the given direction.
+	pub fn new(direction: Direction, span: Range<u64>, label: impl Into<String>) -> Self {
+		Self {
+			direction,
+			span,
+			label: label.into(),
+		}
+	}
+
+	/// Get the direction of the labeled traffic.
+	pub fn direction(&self) -> Direction {
+		self.direction
+	}
+
+	/// Get the labeled span, as offsets in the stream of all data captured in the same direction.
+	pub fn span(&self) -> Range<u64> {
+		self.span.clone()
+	}
+
+	/// Get the label.
+	pub fn label(&self) -> &str {
+		&self.label
+	}
+}
+
+impl<S: CaptureSink + ?Sized> CaptureSink for Box<S> {
+	fn record(&mut self, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()> {
+		S::record(self, timestamp, direction, data)
+	}
+
+	fn annotate(&mut self, timestamp: SystemTime, annotation: &Annotation) -> std::io::Result<()> {
+		S::annotate(self, timestamp, annotation)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		S::flush(self)
+	}
+}
+
+/// Records the data to all sinks in order.
+///
+/// If a sink reports an error, the data is still recorded in the remaining sinks and the first error is returned.
+impl<S: CaptureSink> CaptureSink for Vec<S> {
+	fn record(&mut self, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()> {
+		let mut result = Ok(());
+		for sink in self {
+			let sink_result = sink.record(timestamp, direction, data);
+			result = result.and(sink_result);
+		}
+		result
+	}
+
+	fn annotate(&mut self, timestamp: SystemTime, annotation: &Annotation) -> std::io::Result<()> {
+		let mut result = Ok(());
+		for sink in self {
+			let sink_result = sink.annotate(timestamp, annotation);
+			result = result.and(sink_result);
+		}
+		result
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		let mut result = Ok(());
+		for sink in self {
+			let sink_result = sink.flush();
+			result = result.and(sink_result);
+		}
+		result
+	}
+}
+
+/// A serial port that records all traffic in a [`CaptureSink`].
+///
+/// See the [module documentation][self] for more information.
+pub struct Tap<S> {
+	/// The serial port.
+	port: SerialPort,
+
+	/// The capture sink and related state.
+	sink: Mutex<SinkState<S>>,
+}
+
+/// The capture sink of a tap and related state.
+struct SinkState<S> {
+	/// The capture sink.
+	sink: S,
+
+	/// The first error reported by the sink.
+	error: Option<std::io::Error>,
+
+	/// The analyzer that labels the captured traffic.
+	analyzer: Option<Box<dyn Analyzer + Send>>,
+
+	/// The number of bytes captured in the receive direction.
+	rx_offset: u64,
+
+	/// The number of bytes captured in the transmit direction.
+	tx_offset: u64,
+
+	/// The observers of the traffic.
+	observers: Observers,
+
+	/// The clock for timestamps, or `None` to use the wall clock.
+	clock: Option<CaptureClock>,
+}
+
+impl<S: CaptureSink> Tap<S> {
+	/// Create a new tap that records the traffic of a serial port.
+	pub fn new(port: SerialPort, sink: S) -> Self {
+		Self {
+			port,
+			sink: Mutex::new(SinkState {
+				sink,
+				error: None,
+				analyzer: None,
+				rx_offset: 0,
+				tx_offset: 0,
+				observers: Observers::new(),
+				clock: None,
+			}),
+		}
+	}
+
+	/// Get a reference to the serial port.
+	///
+	/// Data read from or written to the serial port directly is not captured.
+	pub fn port(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Get a mutable reference to the serial port.
+	///
+	/// Data read from or written to the serial port directly is not captured.
+	pub fn port_mut(&mut self) -> &mut SerialPort {
+		&mut self.port
+	}
+
+	/// Run a function with exclusive access to the capture sink.
+	pub fn with_sink<F, T>(&self, f: F) -> T
+	where
+		F: FnOnce(&mut S) -> T,
+	{
+		f(&mut self.lock().sink)
+	}
+
+	/// Set the analyzer that labels the captured traffic.
+	///
+	/// The analyzer is called for each chunk of captured data, after the data is recorded in the sink.
+	/// The annotations it produces are recorded in the sink with [`CaptureSink::annotate()`].
+	///
+	/// This replaces any previously set analyzer.
+	pub fn set_analyzer(&self, analyzer: impl Analyzer + Send + 'static) {
+		self.lock().analyzer = Some(Box::new(analyzer));
+	}
+
+	/// Remove the analyzer.
+	pub fn clear_analyzer(&self) {
+		self.lock().analyzer = None;
+	}
+
+	/// Create an observer that receives a copy of all traffic.
+	///
+	/// The observer first receives the recent traffic kept in the history (see [`Self::set_observer_history()`]),
+	/// followed by all new traffic.
+	pub fn observer(&self) -> Observer {
+		self.lock().observers.subscribe()
+	}
+
+	/// Set the maximum number of bytes of recent traffic to keep for new observers.
+	///
+	/// The default is 0: new observers only receive traffic captured after they were created.
+	pub fn set_observer_history(&self, max_bytes: usize) {
+		self.lock().observers.set_history_limit(max_bytes);
+	}
+
+	/// Set the clock used to timestamp the captured traffic.
+	///
+	/// By default, the traffic is timestamped with the wall clock of the system.
+	/// Use the same [`CaptureClock`] for multiple taps to get timestamps that can be compared between ports.
+	pub fn set_clock(&self, clock: CaptureClock) {
+		self.lock().clock = Some(clock);
+	}
+
+	/// Take the first error reported by the capture sink since the last call.
+	pub fn take_capture_error(&self) -> Option<std::io::Error> {
+		self.lock().error.take()
+	}
+
+	/// Consume the tap and return the serial port and the capture sink.
+	pub fn into_inner(self) -> (SerialPort, S) {
+		let state = self.sink.into_inner().unwrap_or_else(PoisonError::into_inner);
+		(self.port, state.sink)
+	}
+
+	/// Read bytes from the serial port and record them.
+	///
+	/// See [`SerialPort::read()`] for more information.
+	pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let read = self.port.read(buf)?;
+		self.record(Direction::Rx, &buf[..read]);
+		Ok(read)
+	}
+
+	/// Read bytes from the serial port into a slice of buffers and record them.
+	///
+	/// See [`SerialPort::read_vectored()`] for more information.
+	pub fn read_vectored(&self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		let read = self.port.read_vectored(buf)?;
+		let mut data = Vec::with_capacity(read);
+		for part in buf.iter() {
+			let remaining = read - data.len();
+			data.extend_from_slice(&part[..part.len().min(remaining)]);
+		}
+		self.record(Direction::Rx, &data);
+		Ok(read)
+	}
+
+	/// Write bytes to the serial port and record them.
+	///
+	/// See [`SerialPort::write()`] for more information.
+	pub fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
+		let written = self.port.write(buf)?;
+		self.record(Direction::Tx, &buf[..written]);
+		Ok(written)
+	}
+
+	/// Write all bytes to the serial port and record them.
+	///
+	/// See [`SerialPort::write_all()`] for more information.
+	pub fn write_all(&self, buf: &[u8]) -> std::io::Result<()> {
+		let mut buf = buf;
+		while !buf.is_empty() {
+			match self.write(buf) {
+				Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "failed to write whole buffer")),
+				Ok(n) => buf = &buf[n..],
+				Err(e) => {
+					if e.kind() != std::io::ErrorKind::Interrupted {
+						return Err(e);
+					}
+				},
+			}
+		}
+		Ok(())
+	}
+
+	/// Write bytes to the serial port from a slice of buffers and record them.
+	///
+	/// See [`SerialPort::write_vectored()`] for more information.
+	pub fn write_vectored(&self, buf: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		let written = self.port.write_vectored(buf)?;
+		let mut data = Vec::with_capacity(written);
+		for part in buf {
+			let remaining = written - data.len();
+			data.extend_from_slice(&part[..part.len().min(remaining)]);
+		}
+		self.record(Direction::Tx, &data);
+		Ok(written)
+	}
+
+	/// Flush all data queued to be written, and flush the capture sink.
+	///
+	/// See [`SerialPort::flush()`] for more information.
+	pub fn flush(&self) -> std::io::Result<()> {
+		self.port.flush()?;
+		let mut state = self.lock();
+		if let Err(e) = state.sink.flush() {
+			state.error.get_or_insert(e);
+		}
+		Ok(())
+	}
+
+	/// Record data in the sink, storing the first error.
+	fn record(&self, direction: Direction, data: &[u8]) {
+		if data.is_empty() {
+			return;
+		}
+		let mut state = self.lock();
+		let state = &mut *state;
+		let timestamp = match &state.clock {
+			Some(clock) => clock.now(),
+			None => SystemTime::now(),
+		};
+		if let Err(e) = state.sink.record(timestamp, direction, data) {
+			state.error.get_or_insert(e);
+		}
+		state.observers.publish(timestamp, direction, data);
+
+		let offset = match direction {
+			Direction::Rx => &mut state.rx_offset,
+			Direction::Tx => &mut state.tx_offset,
+		};
+		let start = *offset;
+		*offset += data.len() as u64;
+
+		if let Some(analyzer) = &mut state.analyzer {
+			let mut annotations = Vec::new();
+			analyzer.analyze(direction, start, data, &mut annotations);
+			for annotation in &annotations {
+				if let Err(e) = state.sink.annotate(timestamp, annotation) {
+					state.error.get_or_insert(e);
+				}
+			}
+		}
+	}
+
+	/// Lock the sink state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, SinkState<S>> {
+		self.sink.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+impl<S: CaptureSink> std::io::Read for Tap<S> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		Tap::read(self, buf)
+	}
+
+	fn read_vectored(&mut self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		Tap::read_vectored(self, buf)
+	}
+}
+
+impl<S: CaptureSink> std::io::Read for &'_ Tap<S> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		Tap::read(self, buf)
+	}
+
+	fn read_vectored(&mut self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		Tap::read_vectored(self, buf)
+	}
+}
+
+impl<S: CaptureSink> std::io::Write for Tap<S> {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		Tap::write(self, buf)
+	}
+
+	fn write_vectored(&mut self, buf: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		Tap::write_vectored(self, buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Tap::flush(self)
+	}
+}
+
+impl<S: CaptureSink> std::io::Write for &'_ Tap<S> {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		Tap::write(self, buf)
+	}
+
+	fn write_vectored(&mut self, buf: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		Tap::write_vectored(self, buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Tap::flush(self)
+	}
+}
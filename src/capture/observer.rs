@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, SystemTime};
+
+use super::Direction;
+
+/// A chunk of traffic seen by an [`Observer`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CaptureEvent {
+	/// The time the data was captured.
+	timestamp: SystemTime,
+
+	/// The direction of the data.
+	direction: Direction,
+
+	/// The captured data.
+	data: Vec<u8>,
+}
+
+/// A read-only view of the traffic of a [`Tap`][super::Tap].
+///
+/// An observer receives a copy of all data read from and written to the tap,
+/// but it can not transmit anything itself.
+/// This makes it safe to attach debugging tools to a production connection.
+///
+/// Use [`Tap::observer()`][super::Tap::observer] to create an observer.
+pub struct Observer {
+	/// The channel that receives the captured traffic.
+	receiver: Receiver<CaptureEvent>,
+}
+
+/// The observers of a tap and the recent traffic they can replay.
+pub(super) struct Observers {
+	/// The channels to the observers.
+	senders: Vec<Sender<CaptureEvent>>,
+
+	/// The recent traffic.
+	history: VecDeque<CaptureEvent>,
+
+	/// The number of bytes in the recent traffic.
+	history_bytes: usize,
+
+	/// The maximum number of bytes of recent traffic to keep.
+	history_limit: usize,
+}
+
+impl CaptureEvent {
+	/// Get the time the data was captured.
+	pub fn timestamp(&self) -> SystemTime {
+		self.timestamp
+	}
+
+	/// Get the direction of the data.
+	pub fn direction(&self) -> Direction {
+		self.direction
+	}
+
+	/// Get the captured data.
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+
+	/// Consume the event and return the captured data.
+	pub fn into_data(self) -> Vec<u8> {
+		self.data
+	}
+}
+
+impl Observer {
+	/// Wait for the next chunk of traffic.
+	///
+	/// Returns `None` if the tap was dropped and all traffic has been received.
+	pub fn recv(&self) -> Option<CaptureEvent> {
+		self.receiver.recv().ok()
+	}
+
+	/// Wait for the next chunk of traffic, up to a maximum time.
+	///
+	/// If the timeout expires, an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	/// If the tap was dropped and all traffic has been received,
+	/// an error of the type [`std::io::ErrorKind::BrokenPipe`] is returned.
+	pub fn recv_timeout(&self, timeout: Duration) -> std::io::Result<CaptureEvent> {
+		match self.receiver.recv_timeout(timeout) {
+			Ok(event) => Ok(event),
+			Err(RecvTimeoutError::Timeout) => Err(std::io::ErrorKind::TimedOut.into()),
+			Err(RecvTimeoutError::Disconnected) => Err(std::io::ErrorKind::BrokenPipe.into()),
+		}
+	}
+
+	/// Get the next chunk of traffic if one is available, without waiting.
+	pub fn try_recv(&self) -> Option<CaptureEvent> {
+		self.receiver.try_recv().ok()
+	}
+}
+
+impl Observers {
+	/// Create a new set of observers without history.
+	pub(super) fn new() -> Self {
+		Self {
+			senders: Vec::new(),
+			history: VecDeque::new(),
+			history_bytes: 0,
+			history_limit: 0,
+		}
+	}
+
+	/// Add an observer, replaying the recent traffic to it.
+	pub(super) fn subscribe(&mut self) -> Observer {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		for event in &self.history {
+			// The receiver is still alive, so this can not fail.
+			let _ = sender.send(event.clone());
+		}
+		self.senders.push(sender);
+		Observer { receiver }
+	}
+
+	/// Set the maximum number of bytes of recent traffic to keep for new observers.
+	pub(super) fn set_history_limit(&mut self, limit: usize) {
+		self.history_limit = limit;
+		self.trim_history();
+	}
+
+	/// Send traffic to all observers and add it to the history.
+	pub(super) fn publish(&mut self, timestamp: SystemTime, direction: Direction, data: &[u8]) {
+		if self.senders.is_empty() && self.history_limit == 0 {
+			return;
+		}
+		let event = CaptureEvent {
+			timestamp,
+			direction,
+			data: data.to_vec(),
+		};
+		// Forget observers that were dropped.
+		self.senders.retain(|sender| sender.send(event.clone()).is_ok());
+		if self.history_limit > 0 {
+			self.history_bytes += event.data.len();
+			self.history.push_back(event);
+			self.trim_history();
+		}
+	}
+
+	/// Remove the oldest traffic from the history until it fits in the limit.
+	fn trim_history(&mut self) {
+		while self.history_bytes > self.history_limit {
+			match self.history.pop_front() {
+				Some(event) => self.history_bytes -= event.data.len(),
+				None => break,
+			}
+		}
+	}
+}
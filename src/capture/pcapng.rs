@@ -0,0 +1,269 @@
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{Annotation, CaptureSink, Direction, MergedCaptureSink};
+
+/// The block type of a section header block.
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+
+/// The block type of an interface description block.
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+
+/// The block type of an enhanced packet block.
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+
+/// The magic number used to detect the byte order of a section.
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+/// The option code for a comment.
+const OPTION_COMMENT: u16 = 1;
+
+/// The option code for the name of an interface.
+const OPTION_IF_NAME: u16 = 2;
+
+/// The option code for the timestamp resolution of an interface.
+const OPTION_IF_TSRESOL: u16 = 9;
+
+/// The option code that marks the end of the options.
+const OPTION_END: u16 = 0;
+
+/// The default link type: `LINKTYPE_USER0`.
+const DEFAULT_LINK_TYPE: u16 = 147;
+
+/// Writes captured data in the pcapng format.
+///
+/// The capture has one interface for each direction: interface 0 for received data and interface 1 for transmitted data.
+/// Timestamps are recorded with nanosecond resolution.
+///
+/// By default, the interfaces use link type `LINKTYPE_USER0` (147).
+/// In Wireshark you can assign a dissector for your protocol to that link type in the DLT_USER preferences.
+/// Use [`Self::with_link_type()`] to use a different link type.
+///
+/// Annotations are written as empty packets on the interface of the annotated direction,
+/// with a comment of the form `label [start..end]`.
+///
+/// A writer created with [`Self::for_merger()`] records the traffic of multiple ports
+/// as sink of a [`CaptureMerger`][super::CaptureMerger].
+/// Each port gets two interfaces, named after the port: interface `2 * port` for received data
+/// and interface `2 * port + 1` for transmitted data.
+pub struct PcapngWriter<W: Write> {
+	/// The output stream.
+	output: W,
+
+	/// The link type of the interfaces.
+	link_type: u16,
+
+	/// The number of interfaces written so far.
+	interfaces: u32,
+}
+
+impl<W: Write> PcapngWriter<W> {
+	/// Create a new pcapng writer with the default link type.
+	///
+	/// This immediately writes the section header and the interface descriptions.
+	pub fn new(output: W) -> std::io::Result<Self> {
+		Self::with_link_type(output, DEFAULT_LINK_TYPE)
+	}
+
+	/// Create a new pcapng writer with a specific link type for both interfaces.
+	///
+	/// This immediately writes the section header and the interface descriptions.
+	pub fn with_link_type(output: W, link_type: u16) -> std::io::Result<Self> {
+		let mut writer = Self::for_merger(output, link_type)?;
+		writer.write_interface_description("rx")?;
+		writer.write_interface_description("tx")?;
+		Ok(writer)
+	}
+
+	/// Create a new pcapng writer for the merged traffic of multiple ports.
+	///
+	/// This immediately writes the section header.
+	/// The interface descriptions are written when ports are added to the [`CaptureMerger`][super::CaptureMerger].
+	pub fn for_merger(output: W, link_type: u16) -> std::io::Result<Self> {
+		let mut writer = Self {
+			output,
+			link_type,
+			interfaces: 0,
+		};
+		writer.write_section_header()?;
+		Ok(writer)
+	}
+
+	/// Get a reference to the output stream.
+	pub fn get_ref(&self) -> &W {
+		&self.output
+	}
+
+	/// Get a mutable reference to the output stream.
+	///
+	/// Writing directly to the output stream will corrupt the capture.
+	pub fn get_mut(&mut self) -> &mut W {
+		&mut self.output
+	}
+
+	/// Consume the writer and return the output stream.
+	pub fn into_inner(self) -> W {
+		self.output
+	}
+
+	/// Write a packet.
+	///
+	/// Received data is written to interface 0, transmitted data to interface 1.
+	pub fn write_packet(&mut self, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()> {
+		self.write_enhanced_packet(interface(0, direction), timestamp, data, None)
+	}
+
+	/// Write an annotation as an empty packet with a comment.
+	pub fn write_annotation(&mut self, timestamp: SystemTime, annotation: &Annotation) -> std::io::Result<()> {
+		self.write_port_annotation(0, timestamp, annotation)
+	}
+
+	/// Write an annotation for a port of a merged capture as an empty packet with a comment.
+	fn write_port_annotation(
+		&mut self,
+		port: usize,
+		timestamp: SystemTime,
+		annotation: &Annotation,
+	) -> std::io::Result<()> {
+		let span = annotation.span();
+		let comment = format!("{} [{}..{}]", annotation.label(), span.start, span.end);
+		self.write_enhanced_packet(interface(port, annotation.direction()), timestamp, &[], Some(&comment))
+	}
+
+	/// Write an enhanced packet block with an optional comment.
+	fn write_enhanced_packet(
+		&mut self,
+		interface: u32,
+		timestamp: SystemTime,
+		data: &[u8],
+		comment: Option<&str>,
+	) -> std::io::Result<()> {
+		if interface >= self.interfaces {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "unknown pcapng interface"));
+		}
+		let nanos = timestamp
+			.duration_since(UNIX_EPOCH)
+			.map(|time| time.as_nanos().min(u64::MAX as u128) as u64)
+			.unwrap_or(0);
+		let len = u32::try_from(data.len())
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "packet too large for pcapng"))?;
+
+		let mut body = Vec::with_capacity(20 + padded_len(data.len()));
+		body.extend_from_slice(&interface.to_le_bytes());
+		body.extend_from_slice(&((nanos >> 32) as u32).to_le_bytes());
+		body.extend_from_slice(&(nanos as u32).to_le_bytes());
+		body.extend_from_slice(&len.to_le_bytes());
+		body.extend_from_slice(&len.to_le_bytes());
+		body.extend_from_slice(data);
+		pad(&mut body);
+		if let Some(comment) = comment {
+			push_option(&mut body, OPTION_COMMENT, comment.as_bytes());
+			push_option(&mut body, OPTION_END, &[]);
+		}
+		self.write_block(ENHANCED_PACKET_BLOCK, &body)
+	}
+
+	/// Write the section header block.
+	fn write_section_header(&mut self) -> std::io::Result<()> {
+		let mut body = Vec::with_capacity(16);
+		body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+		body.extend_from_slice(&1u16.to_le_bytes());
+		body.extend_from_slice(&0u16.to_le_bytes());
+		// The section length is unknown.
+		body.extend_from_slice(&(-1i64).to_le_bytes());
+		self.write_block(SECTION_HEADER_BLOCK, &body)
+	}
+
+	/// Write an interface description block with nanosecond timestamp resolution.
+	fn write_interface_description(&mut self, name: &str) -> std::io::Result<()> {
+		let mut body = Vec::with_capacity(32);
+		body.extend_from_slice(&self.link_type.to_le_bytes());
+		body.extend_from_slice(&0u16.to_le_bytes());
+		// The snapshot length is unlimited.
+		body.extend_from_slice(&0u32.to_le_bytes());
+		push_option(&mut body, OPTION_IF_NAME, name.as_bytes());
+		push_option(&mut body, OPTION_IF_TSRESOL, &[9]);
+		push_option(&mut body, OPTION_END, &[]);
+		self.write_block(INTERFACE_DESCRIPTION_BLOCK, &body)?;
+		self.interfaces += 1;
+		Ok(())
+	}
+
+	/// Write a block with the given type and body.
+	///
+	/// The body must already be padded to a multiple of 4 bytes.
+	fn write_block(&mut self, block_type: u32, body: &[u8]) -> std::io::Result<()> {
+		let total_len = (body.len() + 12) as u32;
+		let mut block = Vec::with_capacity(total_len as usize);
+		block.extend_from_slice(&block_type.to_le_bytes());
+		block.extend_from_slice(&total_len.to_le_bytes());
+		block.extend_from_slice(body);
+		block.extend_from_slice(&total_len.to_le_bytes());
+		self.output.write_all(&block)
+	}
+}
+
+impl<W: Write> CaptureSink for PcapngWriter<W> {
+	fn record(&mut self, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()> {
+		self.write_packet(timestamp, direction, data)
+	}
+
+	fn annotate(&mut self, timestamp: SystemTime, annotation: &Annotation) -> std::io::Result<()> {
+		self.write_annotation(timestamp, annotation)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.output.flush()
+	}
+}
+
+impl<W: Write> MergedCaptureSink for PcapngWriter<W> {
+	fn add_port(&mut self, port: usize, name: &str) -> std::io::Result<()> {
+		// Interfaces can only be added in order, so the interfaces of the port must be next.
+		if u64::from(self.interfaces) != 2 * port as u64 {
+			let message = "pcapng interfaces must be added in order";
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+		}
+		self.write_interface_description(&format!("{name} rx"))?;
+		self.write_interface_description(&format!("{name} tx"))
+	}
+
+	fn record(&mut self, port: usize, timestamp: SystemTime, direction: Direction, data: &[u8]) -> std::io::Result<()> {
+		self.write_enhanced_packet(interface(port, direction), timestamp, data, None)
+	}
+
+	fn annotate(&mut self, port: usize, timestamp: SystemTime, annotation: &Annotation) -> std::io::Result<()> {
+		self.write_port_annotation(port, timestamp, annotation)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.output.flush()
+	}
+}
+
+/// Get the interface for the traffic of a port in a given direction.
+fn interface(port: usize, direction: Direction) -> u32 {
+	let base = u32::try_from(port).unwrap_or(u32::MAX / 2).saturating_mul(2);
+	match direction {
+		Direction::Rx => base,
+		Direction::Tx => base.saturating_add(1),
+	}
+}
+
+/// Append an option to a block body.
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+	body.extend_from_slice(&code.to_le_bytes());
+	body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+	body.extend_from_slice(value);
+	pad(body);
+}
+
+/// Pad a buffer with zeroes to a multiple of 4 bytes.
+fn pad(buffer: &mut Vec<u8>) {
+	buffer.resize(padded_len(buffer.len()), 0);
+}
+
+/// Round a length up to a multiple of 4 bytes.
+fn padded_len(len: usize) -> usize {
+	(len + 3) & !3
+}
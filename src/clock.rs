@@ -0,0 +1,106 @@
+//! Injectable time source for timing logic.
+//!
+//! Some components make decisions based on elapsed time that do not involve the serial port itself,
+//! like the backoff delays and the circuit breaker of a [`RetryingTransport`][crate::retry::RetryingTransport],
+//! or the silence tracked by an [`IdleDetector`][crate::idle::IdleDetector].
+//! These components use a [`Clock`], which defaults to the system clock.
+//!
+//! In tests, a [`Clock::manual()`] clock can be used instead.
+//! It only advances when told to, so protocol logic that depends on long delays can be tested without real sleeps.
+//!
+//! Note that the timeouts of reads and writes on a serial port are enforced by the operating system,
+//! so they always use real time.
+
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// A source of time for timing logic.
+///
+/// Cloning a clock is cheap, and all clones report the same time.
+///
+/// See the [module documentation][self] for more information.
+#[derive(Clone, Default)]
+pub struct Clock {
+	/// The source of the time.
+	source: ClockSource,
+}
+
+/// The source of the time of a [`Clock`].
+#[derive(Clone, Default)]
+enum ClockSource {
+	/// The monotonic clock of the system.
+	#[default]
+	System,
+
+	/// A clock that only advances when told to.
+	Manual(Arc<Mutex<Instant>>),
+}
+
+impl Clock {
+	/// Create a clock that uses the monotonic clock of the system.
+	///
+	/// This is the default clock.
+	pub fn system() -> Self {
+		Self {
+			source: ClockSource::System,
+		}
+	}
+
+	/// Create a clock that only advances when [`Self::advance()`] or [`Self::sleep()`] is called.
+	///
+	/// The clock starts at the current time of the system clock.
+	pub fn manual() -> Self {
+		Self {
+			source: ClockSource::Manual(Arc::new(Mutex::new(Instant::now()))),
+		}
+	}
+
+	/// Check if this is a manual clock.
+	pub fn is_manual(&self) -> bool {
+		matches!(self.source, ClockSource::Manual(_))
+	}
+
+	/// Get the current time.
+	pub fn now(&self) -> Instant {
+		match &self.source {
+			ClockSource::System => Instant::now(),
+			ClockSource::Manual(now) => *now.lock().unwrap_or_else(PoisonError::into_inner),
+		}
+	}
+
+	/// Get the time elapsed since an earlier point in time.
+	///
+	/// Returns zero if the given time is in the future.
+	pub fn elapsed(&self, since: Instant) -> Duration {
+		self.now().saturating_duration_since(since)
+	}
+
+	/// Advance a manual clock.
+	///
+	/// This has no effect on the system clock.
+	pub fn advance(&self, duration: Duration) {
+		if let ClockSource::Manual(now) = &self.source {
+			*now.lock().unwrap_or_else(PoisonError::into_inner) += duration;
+		}
+	}
+
+	/// Wait for the given duration.
+	///
+	/// The system clock blocks the current thread.
+	/// A manual clock returns immediately, after advancing the clock by the duration.
+	pub fn sleep(&self, duration: Duration) {
+		match &self.source {
+			ClockSource::System => std::thread::sleep(duration),
+			ClockSource::Manual(_) => self.advance(duration),
+		}
+	}
+}
+
+impl std::fmt::Debug for Clock {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.source {
+			ClockSource::System => f.write_str("Clock::System"),
+			ClockSource::Manual(_) => f.debug_tuple("Clock::Manual").field(&self.now()).finish(),
+		}
+	}
+}
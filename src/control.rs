@@ -0,0 +1,302 @@
+use std::io::{IoSlice, IoSliceMut};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+use std::time::Duration;
+
+use crate::{ModemLines, SerialPort, Settings};
+
+/// The configuration and control side of a serial port.
+///
+/// A `ControlHandle` can change the configuration of the serial port and control the modem lines,
+/// but it can not read or write data.
+/// Use [`SerialPort::split_control()`] to create it together with the matching [`IoHandle`].
+///
+/// Configuration changes never happen in the middle of a read or write on the [`IoHandle`]:
+/// [`Self::set_configuration()`] waits until any read or write in progress is finished.
+pub struct ControlHandle {
+	/// A handle to the serial port, used only for configuration and control.
+	port: SerialPort,
+
+	/// Lock held in shared mode during I/O, and in exclusive mode while changing the configuration.
+	config_lock: Arc<RwLock<()>>,
+}
+
+/// The data side of a serial port.
+///
+/// An `IoHandle` can read and write data, but it can not change the configuration of the serial port.
+/// Use [`SerialPort::split_control()`] to create it together with the matching [`ControlHandle`].
+pub struct IoHandle {
+	/// The serial port.
+	port: SerialPort,
+
+	/// Lock held in shared mode during I/O, and in exclusive mode while changing the configuration.
+	config_lock: Arc<RwLock<()>>,
+}
+
+/// Split a serial port into a control handle and an I/O handle.
+pub(crate) fn split(port: SerialPort) -> std::io::Result<(ControlHandle, IoHandle)> {
+	let config_lock = Arc::new(RwLock::new(()));
+	let control = ControlHandle {
+		port: port.try_clone()?,
+		config_lock: config_lock.clone(),
+	};
+	let io = IoHandle { port, config_lock };
+	Ok((control, io))
+}
+
+impl ControlHandle {
+	/// Get the current configuration of the serial port.
+	///
+	/// See [`SerialPort::get_configuration()`] for more information.
+	pub fn get_configuration(&self) -> std::io::Result<Settings> {
+		self.port.get_configuration()
+	}
+
+	/// Configure (or reconfigure) the serial port.
+	///
+	/// This waits until any read or write in progress on the [`IoHandle`] is finished,
+	/// so it may block for up to the read or write timeout of the [`IoHandle`].
+	pub fn set_configuration(&mut self, settings: &Settings) -> std::io::Result<()> {
+		let _lock = self.config_lock.write().unwrap_or_else(PoisonError::into_inner);
+		self.port.set_configuration(settings)
+	}
+
+	/// Discard the kernel input and output buffers for the serial port.
+	///
+	/// See [`SerialPort::discard_buffers()`] for more information.
+	pub fn discard_buffers(&self) -> std::io::Result<()> {
+		self.port.discard_buffers()
+	}
+
+	/// Discard the kernel input buffer for the serial port.
+	///
+	/// See [`SerialPort::discard_input_buffer()`] for more information.
+	pub fn discard_input_buffer(&self) -> std::io::Result<()> {
+		self.port.discard_input_buffer()
+	}
+
+	/// Discard the kernel output buffer for the serial port.
+	///
+	/// See [`SerialPort::discard_output_buffer()`] for more information.
+	pub fn discard_output_buffer(&self) -> std::io::Result<()> {
+		self.port.discard_output_buffer()
+	}
+
+	/// Ask the peer to stop sending data.
+	///
+	/// See [`SerialPort::pause_input()`] for more information.
+	pub fn pause_input(&self) -> std::io::Result<()> {
+		self.port.pause_input()
+	}
+
+	/// Allow the peer to send data again after [`Self::pause_input()`].
+	///
+	/// See [`SerialPort::resume_input()`] for more information.
+	pub fn resume_input(&self) -> std::io::Result<()> {
+		self.port.resume_input()
+	}
+
+	/// Transmit a break condition for the given duration.
+	///
+	/// See [`SerialPort::send_break()`] for more information.
+	pub fn send_break(&self, duration: Duration) -> std::io::Result<()> {
+		self.port.send_break(duration)
+	}
+
+	/// Start or stop transmitting a break condition.
+	///
+	/// See [`SerialPort::set_break()`] for more information.
+	pub fn set_break(&self, enable: bool) -> std::io::Result<()> {
+		self.port.set_break(enable)
+	}
+
+	/// Set the state of the Ready To Send line.
+	///
+	/// See [`SerialPort::set_rts()`] for more information.
+	pub fn set_rts(&self, state: bool) -> std::io::Result<()> {
+		self.port.set_rts(state)
+	}
+
+	/// Read the state of the Clear To Send line.
+	///
+	/// See [`SerialPort::read_cts()`] for more information.
+	pub fn read_cts(&self) -> std::io::Result<bool> {
+		self.port.read_cts()
+	}
+
+	/// Set the state of the Data Terminal Ready line.
+	///
+	/// See [`SerialPort::set_dtr()`] for more information.
+	pub fn set_dtr(&self, state: bool) -> std::io::Result<()> {
+		self.port.set_dtr(state)
+	}
+
+	/// Read the state of the Data Set Ready line.
+	///
+	/// See [`SerialPort::read_dsr()`] for more information.
+	pub fn read_dsr(&self) -> std::io::Result<bool> {
+		self.port.read_dsr()
+	}
+
+	/// Read the state of the Ring Indicator line.
+	///
+	/// See [`SerialPort::read_ri()`] for more information.
+	pub fn read_ri(&self) -> std::io::Result<bool> {
+		self.port.read_ri()
+	}
+
+	/// Read the state of the Carrier Detect line.
+	///
+	/// See [`SerialPort::read_cd()`] for more information.
+	pub fn read_cd(&self) -> std::io::Result<bool> {
+		self.port.read_cd()
+	}
+
+	/// Set the state of the Ready To Send and Data Terminal Ready lines together.
+	///
+	/// See [`SerialPort::set_modem_lines()`] for more information.
+	pub fn set_modem_lines(&self, rts: Option<bool>, dtr: Option<bool>) -> std::io::Result<()> {
+		self.port.set_modem_lines(rts, dtr)
+	}
+
+	/// Read the state of all modem control lines with a single system call.
+	///
+	/// See [`SerialPort::read_modem_lines()`] for more information.
+	pub fn read_modem_lines(&self) -> std::io::Result<ModemLines> {
+		self.port.read_modem_lines()
+	}
+}
+
+impl IoHandle {
+	/// Get the serial port.
+	///
+	/// Reads and writes done directly on the serial port are not protected against concurrent configuration changes.
+	pub fn port(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Read bytes from the serial port.
+	///
+	/// See [`SerialPort::read()`] for more information.
+	pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let _lock = self.lock();
+		self.port.read(buf)
+	}
+
+	/// Read bytes from the serial port into a slice of buffers.
+	///
+	/// See [`SerialPort::read_vectored()`] for more information.
+	pub fn read_vectored(&self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		let _lock = self.lock();
+		self.port.read_vectored(buf)
+	}
+
+	/// Read the exact number of bytes required to fill the buffer from the serial port.
+	///
+	/// The configuration may change between the individual reads.
+	/// See [`SerialPort::read_exact()`] for more information.
+	pub fn read_exact(&self, buf: &mut [u8]) -> std::io::Result<()> {
+		let mut buf = buf;
+		while !buf.is_empty() {
+			match self.read(buf) {
+				Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "failed to fill whole buffer")),
+				Ok(n) => buf = &mut buf[n..],
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(())
+	}
+
+	/// Write bytes to the serial port.
+	///
+	/// See [`SerialPort::write()`] for more information.
+	pub fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
+		let _lock = self.lock();
+		self.port.write(buf)
+	}
+
+	/// Write all bytes to the serial port.
+	///
+	/// The configuration does not change until all data has been written.
+	/// See [`SerialPort::write_all()`] for more information.
+	pub fn write_all(&self, buf: &[u8]) -> std::io::Result<()> {
+		let _lock = self.lock();
+		self.port.write_all(buf)
+	}
+
+	/// Write bytes to the serial port from a slice of buffers.
+	///
+	/// See [`SerialPort::write_vectored()`] for more information.
+	pub fn write_vectored(&self, buf: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		let _lock = self.lock();
+		self.port.write_vectored(buf)
+	}
+
+	/// Flush all data queued to be written.
+	///
+	/// See [`SerialPort::flush()`] for more information.
+	pub fn flush(&self) -> std::io::Result<()> {
+		let _lock = self.lock();
+		self.port.flush()
+	}
+
+	/// Set the read timeout for the serial port.
+	///
+	/// See [`SerialPort::set_read_timeout()`] for more information.
+	pub fn set_read_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+		self.port.set_read_timeout(timeout)
+	}
+
+	/// Get the read timeout for the serial port.
+	pub fn get_read_timeout(&self) -> std::io::Result<Duration> {
+		self.port.get_read_timeout()
+	}
+
+	/// Set the write timeout for the serial port.
+	///
+	/// See [`SerialPort::set_write_timeout()`] for more information.
+	pub fn set_write_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+		self.port.set_write_timeout(timeout)
+	}
+
+	/// Get the write timeout for the serial port.
+	pub fn get_write_timeout(&self) -> std::io::Result<Duration> {
+		self.port.get_write_timeout()
+	}
+
+	/// Consume the handle and return the serial port.
+	///
+	/// The [`ControlHandle`] keeps working, but its configuration changes are no longer synchronized with I/O.
+	pub fn into_inner(self) -> SerialPort {
+		self.port
+	}
+
+	/// Lock the configuration lock in shared mode, ignoring poisoning.
+	fn lock(&self) -> RwLockReadGuard<'_, ()> {
+		self.config_lock.read().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+impl std::io::Read for IoHandle {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		IoHandle::read(self, buf)
+	}
+
+	fn read_vectored(&mut self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		IoHandle::read_vectored(self, buf)
+	}
+}
+
+impl std::io::Write for IoHandle {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		IoHandle::write(self, buf)
+	}
+
+	fn write_vectored(&mut self, buf: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		IoHandle::write_vectored(self, buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		IoHandle::flush(self)
+	}
+}
@@ -0,0 +1,205 @@
+//! Routing of received frames by frame ID.
+//!
+//! Many protocols carry an ID in each response, such as the slave address in Modbus,
+//! the servo ID in Dynamixel or the system ID in MAVLink.
+//! A [`Demux`] reads frames in a background thread and routes them to a channel per ID,
+//! so that multiple logical conversations can share one serial port.
+//!
+//! To send requests while the demuxer owns the port for reading, use a handle created with [`SerialPort::try_clone()`][crate::SerialPort::try_clone].
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::framing::{FrameReader, Framer};
+use crate::shutdown::Shutdown;
+
+/// The read timeout used by the background thread.
+///
+/// This limits how long it takes for the background thread to notice that the demuxer was dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Routes received frames to channels by frame ID.
+///
+/// See the [module documentation][self] for more information.
+///
+/// Dropping the demuxer stops the background thread.
+pub struct Demux<Id, Frame> {
+	/// The state shared with the background thread.
+	shared: Arc<Shared<Id, Frame>>,
+
+	/// The background thread.
+	thread: Option<JoinHandle<()>>,
+}
+
+impl<Id, Frame> Demux<Id, Frame>
+where
+	Id: Eq + Hash + Send + 'static,
+	Frame: Send + 'static,
+{
+	/// Create a new demuxer that reads frames from a frame reader.
+	///
+	/// The `frame_id` function extracts the ID from a decoded frame.
+	///
+	/// This spawns a background thread that reads from the serial port.
+	/// The read timeout of the serial port is changed for use by the background thread.
+	pub fn new<F, I>(mut reader: FrameReader<F>, frame_id: I) -> std::io::Result<Self>
+	where
+		F: Framer<Frame = Frame> + Send + 'static,
+		I: FnMut(&Frame) -> Id + Send + 'static,
+	{
+		reader.port_mut().set_read_timeout(POLL_INTERVAL)?;
+		let shared = Arc::new(Shared {
+			state: Mutex::new(State {
+				routes: HashMap::new(),
+				unrouted: None,
+				invalid_frames: 0,
+				error: None,
+				closed: false,
+			}),
+		});
+		let thread = std::thread::Builder::new()
+			.name("serial2-demux".into())
+			.spawn({
+				let shared = shared.clone();
+				move || shared.run(reader, frame_id)
+			})?;
+		Ok(Self {
+			shared,
+			thread: Some(thread),
+		})
+	}
+
+	/// Subscribe to frames with the given ID.
+	///
+	/// Any previous subscription for the same ID is replaced.
+	pub fn subscribe(&self, id: Id) -> Receiver<Frame> {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		self.shared.lock().routes.insert(id, sender);
+		receiver
+	}
+
+	/// Remove the subscription for the given ID.
+	///
+	/// Returns `true` if there was a subscription for the ID.
+	pub fn unsubscribe(&self, id: &Id) -> bool {
+		self.shared.lock().routes.remove(id).is_some()
+	}
+
+	/// Subscribe to frames with an ID that has no subscription.
+	///
+	/// Any previous subscription for unrouted frames is replaced.
+	/// Without a subscription, unrouted frames are discarded.
+	pub fn subscribe_unrouted(&self) -> Receiver<Frame> {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		self.shared.lock().unrouted = Some(sender);
+		receiver
+	}
+
+	/// Get the number of times the framer reported invalid data.
+	pub fn invalid_frames(&self) -> u64 {
+		self.shared.lock().invalid_frames
+	}
+
+	/// Take the error that stopped the background thread, if any.
+	///
+	/// When the background thread stops, all subscription channels are disconnected.
+	pub fn take_error(&self) -> Option<std::io::Error> {
+		self.shared.lock().error.take()
+	}
+}
+
+impl<Id, Frame> Demux<Id, Frame> {
+	/// Stop the background thread and wait for it to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		crate::shutdown::join_threads(self.thread.take(), deadline)
+	}
+}
+
+impl<Id, Frame> Drop for Demux<Id, Frame> {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl<Id, Frame> Shutdown for Demux<Id, Frame> {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// State shared between the demuxer and the background thread.
+struct Shared<Id, Frame> {
+	/// The mutable state of the demuxer.
+	state: Mutex<State<Id, Frame>>,
+}
+
+/// The mutable state of the demuxer.
+struct State<Id, Frame> {
+	/// The subscriptions by frame ID.
+	routes: HashMap<Id, Sender<Frame>>,
+
+	/// The subscription for frames without a matching route.
+	unrouted: Option<Sender<Frame>>,
+
+	/// The number of times the framer reported invalid data.
+	invalid_frames: u64,
+
+	/// The error that stopped the background thread.
+	error: Option<std::io::Error>,
+
+	/// True if the demuxer is being dropped.
+	closed: bool,
+}
+
+impl<Id, Frame> Shared<Id, Frame> {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State<Id, Frame>> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+impl<Id: Eq + Hash, Frame> Shared<Id, Frame> {
+	/// Run the background thread.
+	fn run<F, I>(&self, mut reader: FrameReader<F>, mut frame_id: I)
+	where
+		F: Framer<Frame = Frame>,
+		I: FnMut(&Frame) -> Id,
+	{
+		loop {
+			if self.lock().closed {
+				return;
+			}
+			match reader.read_frame() {
+				Ok(frame) => {
+					let id = frame_id(&frame);
+					let mut state = self.lock();
+					if let Some(sender) = state.routes.get(&id) {
+						// Drop the route if the receiver is gone.
+						if sender.send(frame).is_err() {
+							state.routes.remove(&id);
+						}
+					} else if let Some(sender) = &state.unrouted {
+						if sender.send(frame).is_err() {
+							state.unrouted = None;
+						}
+					}
+				},
+				Err(e) if e.kind() == std::io::ErrorKind::InvalidData => self.lock().invalid_frames += 1,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => {
+					let mut state = self.lock();
+					state.error = Some(e);
+					state.routes.clear();
+					state.unrouted = None;
+					return;
+				},
+			}
+		}
+	}
+}
@@ -0,0 +1,199 @@
+//! Detection of configuration changes made by other processes.
+//!
+//! On some platforms, other processes can reconfigure a serial port while it is open.
+//! For example, `getty` or ModemManager may probe a newly connected USB serial adapter
+//! and change the baud rate or the line discipline underneath the application.
+//!
+//! A [`DriftMonitor`] periodically compares the live configuration of a serial port
+//! with the configuration that was last applied with [`SerialPort::set_configuration()`],
+//! and reports a [`DriftEvent`] to all subscribers when they differ.
+//! For a one-time check, use [`SerialPort::check_configuration_drift()`].
+
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::shutdown::Shutdown;
+use crate::{SerialPort, Settings};
+
+/// The maximum time the background thread sleeps before checking if the monitor is being dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A change of the configuration of a serial port that was not made through the monitored handle.
+#[derive(Debug, Clone)]
+pub struct DriftEvent {
+	/// The configuration that was last applied through the monitored handle.
+	expected: Settings,
+
+	/// The live configuration of the serial port.
+	actual: Settings,
+}
+
+impl DriftEvent {
+	/// Get the configuration that was last applied through the monitored handle.
+	pub fn expected(&self) -> &Settings {
+		&self.expected
+	}
+
+	/// Get the live configuration of the serial port.
+	pub fn actual(&self) -> &Settings {
+		&self.actual
+	}
+}
+
+/// Periodically checks a serial port for configuration changes made by other processes.
+///
+/// See the [module documentation][self] for more information.
+///
+/// Each distinct drifted configuration is reported once.
+/// If the configuration changes again, or is restored and then changed again, a new event is reported.
+///
+/// Dropping the monitor stops the background thread.
+pub struct DriftMonitor {
+	/// The state shared with the background thread.
+	shared: Arc<Shared>,
+
+	/// The background thread.
+	thread: Option<JoinHandle<()>>,
+}
+
+impl DriftMonitor {
+	/// Start checking the configuration of a serial port at the given interval.
+	pub fn spawn(port: Arc<SerialPort>, interval: Duration) -> std::io::Result<Self> {
+		let shared = Arc::new(Shared {
+			state: Mutex::new(State {
+				subscribers: Vec::new(),
+				drift_count: 0,
+				error: None,
+				closed: false,
+			}),
+		});
+		let thread = std::thread::Builder::new()
+			.name("serial2-drift-monitor".into())
+			.spawn({
+				let shared = shared.clone();
+				move || shared.run(&port, interval)
+			})?;
+		Ok(Self {
+			shared,
+			thread: Some(thread),
+		})
+	}
+
+	/// Subscribe to drift events.
+	///
+	/// Only events that happen after subscribing are delivered.
+	pub fn subscribe(&self) -> Receiver<DriftEvent> {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		self.shared.lock().subscribers.push(sender);
+		receiver
+	}
+
+	/// Get the number of drift events that were detected.
+	pub fn drift_count(&self) -> u64 {
+		self.shared.lock().drift_count
+	}
+
+	/// Take the error that stopped the background thread, if any.
+	pub fn take_error(&self) -> Option<std::io::Error> {
+		self.shared.lock().error.take()
+	}
+}
+
+impl DriftMonitor {
+	/// Stop the background thread and wait for it to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		crate::shutdown::join_threads(self.thread.take(), deadline)
+	}
+}
+
+impl Drop for DriftMonitor {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl Shutdown for DriftMonitor {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// State shared between the monitor and the background thread.
+struct Shared {
+	/// The mutable state.
+	state: Mutex<State>,
+}
+
+/// The mutable state of a drift monitor.
+struct State {
+	/// The subscribers for drift events.
+	subscribers: Vec<Sender<DriftEvent>>,
+
+	/// The number of drift events that were detected.
+	drift_count: u64,
+
+	/// The error that stopped the background thread.
+	error: Option<std::io::Error>,
+
+	/// True if the monitor is being dropped.
+	closed: bool,
+}
+
+impl Shared {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Sleep for the given duration, unless the monitor is dropped first.
+	///
+	/// Returns `false` if the monitor is being dropped.
+	fn sleep(&self, duration: Duration) -> bool {
+		let deadline = Instant::now() + duration;
+		loop {
+			if self.lock().closed {
+				return false;
+			}
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return true;
+			}
+			std::thread::sleep(remaining.min(POLL_INTERVAL));
+		}
+	}
+
+	/// Run the background thread.
+	fn run(&self, port: &SerialPort, interval: Duration) {
+		let mut reported: Option<Settings> = None;
+		while self.sleep(interval) {
+			let actual = match port.check_configuration_drift() {
+				Ok(Some(actual)) => actual,
+				Ok(None) => {
+					reported = None;
+					continue;
+				},
+				Err(e) => {
+					self.lock().error = Some(e);
+					return;
+				},
+			};
+			if reported.as_ref().is_some_and(|reported| reported.matches(&actual)) {
+				continue;
+			}
+			let Some(expected) = port.get_applied_configuration() else {
+				continue;
+			};
+			let event = DriftEvent {
+				expected: expected.clone(),
+				actual: actual.clone(),
+			};
+			reported = Some(actual);
+			let mut state = self.lock();
+			state.drift_count += 1;
+			state.subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+		}
+	}
+}
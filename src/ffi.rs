@@ -0,0 +1,464 @@
+//! C bindings for the core API.
+//!
+//! This module exports `extern "C"` functions to open, configure, read from and write to serial ports,
+//! to control the modem lines and to list the available serial ports.
+//! The matching C header is `include/serial2.h` in the source repository.
+//!
+//! To build a shared library, compile the crate as a `cdylib` with the `ffi` feature enabled:
+//!
+//! ```sh
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! Serial ports are represented by an opaque [`Serial2Port`] handle.
+//! All functions return one of the `SERIAL2_*` status codes.
+//! If a function fails, a description of the error can be retrieved with [`serial2_last_error()`].
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::time::Duration;
+
+use crate::{CharSize, FlowControl, Parity, SerialPort, StopBits};
+
+/// The function completed successfully.
+pub const SERIAL2_OK: c_int = 0;
+
+/// An argument was invalid, for example a null pointer or an unsupported setting.
+pub const SERIAL2_ERR_INVALID_ARGUMENT: c_int = -1;
+
+/// The operation timed out.
+pub const SERIAL2_ERR_TIMED_OUT: c_int = -2;
+
+/// The serial port does not exist.
+pub const SERIAL2_ERR_NOT_FOUND: c_int = -3;
+
+/// Permission to open the serial port was denied.
+pub const SERIAL2_ERR_PERMISSION_DENIED: c_int = -4;
+
+/// The serial port is in use.
+pub const SERIAL2_ERR_BUSY: c_int = -5;
+
+/// The operation was interrupted.
+pub const SERIAL2_ERR_INTERRUPTED: c_int = -6;
+
+/// The operation is not supported by the serial port or the platform.
+pub const SERIAL2_ERR_UNSUPPORTED: c_int = -7;
+
+/// Any other error.
+pub const SERIAL2_ERR_OTHER: c_int = -99;
+
+/// No parity bit.
+pub const SERIAL2_PARITY_NONE: c_int = 0;
+
+/// Odd parity.
+pub const SERIAL2_PARITY_ODD: c_int = 1;
+
+/// Even parity.
+pub const SERIAL2_PARITY_EVEN: c_int = 2;
+
+/// No flow control.
+pub const SERIAL2_FLOW_CONTROL_NONE: c_int = 0;
+
+/// XON/XOFF flow control.
+pub const SERIAL2_FLOW_CONTROL_XON_XOFF: c_int = 1;
+
+/// RTS/CTS flow control.
+pub const SERIAL2_FLOW_CONTROL_RTS_CTS: c_int = 2;
+
+/// An opaque handle to an open serial port.
+pub struct Serial2Port {
+	/// The serial port.
+	port: SerialPort,
+}
+
+/// The configuration of a serial port.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Serial2Settings {
+	/// The baud rate in symbols per second.
+	pub baud_rate: u32,
+
+	/// The number of bits per character: 5, 6, 7 or 8.
+	pub char_size: u8,
+
+	/// The number of stop bits: 1 or 2.
+	pub stop_bits: u8,
+
+	/// The parity: one of the `SERIAL2_PARITY_*` constants.
+	pub parity: c_int,
+
+	/// The flow control method: one of the `SERIAL2_FLOW_CONTROL_*` constants.
+	pub flow_control: c_int,
+}
+
+thread_local! {
+	/// The message of the last error on this thread.
+	static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Get a description of the last error that occurred on the calling thread.
+///
+/// The returned string remains valid until the next function of this library fails on the same thread.
+#[no_mangle]
+pub extern "C" fn serial2_last_error() -> *const c_char {
+	LAST_ERROR.with(|message| message.borrow().as_ptr())
+}
+
+/// Open a serial port and configure it for raw mode with the given baud rate.
+///
+/// The character size is set to 8 bits, with 1 stop bit, no parity and no flow control.
+/// On success, the handle is stored in `port` and must be closed with [`serial2_close()`].
+///
+/// # Safety
+/// `name` must be a valid nul-terminated string and `port` must be a valid pointer to write the handle to.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_open(name: *const c_char, baud_rate: u32, port: *mut *mut Serial2Port) -> c_int {
+	if name.is_null() || port.is_null() {
+		return invalid_argument("null pointer");
+	}
+	let Ok(name) = CStr::from_ptr(name).to_str() else { return invalid_argument("the name is not valid UTF-8") };
+	match SerialPort::open(name, baud_rate) {
+		Ok(opened) => {
+			*port = Box::into_raw(Box::new(Serial2Port { port: opened }));
+			SERIAL2_OK
+		},
+		Err(e) => error_code(e),
+	}
+}
+
+/// Close a serial port.
+///
+/// Passing a null pointer does nothing.
+///
+/// # Safety
+/// `port` must be null or a handle returned by [`serial2_open()`] that has not been closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_close(port: *mut Serial2Port) {
+	if !port.is_null() {
+		drop(Box::from_raw(port));
+	}
+}
+
+/// Get the current configuration of a serial port.
+///
+/// # Safety
+/// `port` must be a valid handle and `settings` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_get_configuration(port: *const Serial2Port, settings: *mut Serial2Settings) -> c_int {
+	let (Some(port), Some(out)) = (port.as_ref(), settings.as_mut()) else { return invalid_argument("null pointer") };
+	let result = port.port.get_configuration().and_then(|settings| {
+		Ok(Serial2Settings {
+			baud_rate: settings.get_baud_rate()?,
+			char_size: settings.get_char_size()?.as_u8(),
+			stop_bits: settings.get_stop_bits()?.as_u8(),
+			parity: match settings.get_parity()? {
+				Parity::None => SERIAL2_PARITY_NONE,
+				Parity::Odd => SERIAL2_PARITY_ODD,
+				Parity::Even => SERIAL2_PARITY_EVEN,
+			},
+			flow_control: match settings.get_flow_control()? {
+				FlowControl::None => SERIAL2_FLOW_CONTROL_NONE,
+				FlowControl::XonXoff => SERIAL2_FLOW_CONTROL_XON_XOFF,
+				FlowControl::RtsCts => SERIAL2_FLOW_CONTROL_RTS_CTS,
+			},
+		})
+	});
+	match result {
+		Ok(settings) => {
+			*out = settings;
+			SERIAL2_OK
+		},
+		Err(e) => error_code(e),
+	}
+}
+
+/// Configure a serial port.
+///
+/// The serial port is put in raw mode before the settings are applied.
+///
+/// # Safety
+/// `port` must be a valid handle and `settings` must be a valid pointer.
+/// The handle must not be used by any other thread during the call.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_set_configuration(port: *mut Serial2Port, settings: *const Serial2Settings) -> c_int {
+	let (Some(port), Some(settings)) = (port.as_mut(), settings.as_ref()) else {
+		return invalid_argument("null pointer");
+	};
+	let Ok(char_size) = CharSize::try_from(settings.char_size) else {
+		return invalid_argument("invalid character size");
+	};
+	let Ok(stop_bits) = StopBits::try_from(settings.stop_bits) else {
+		return invalid_argument("invalid number of stop bits");
+	};
+	let parity = match settings.parity {
+		SERIAL2_PARITY_NONE => Parity::None,
+		SERIAL2_PARITY_ODD => Parity::Odd,
+		SERIAL2_PARITY_EVEN => Parity::Even,
+		_ => return invalid_argument("invalid parity"),
+	};
+	let flow_control = match settings.flow_control {
+		SERIAL2_FLOW_CONTROL_NONE => FlowControl::None,
+		SERIAL2_FLOW_CONTROL_XON_XOFF => FlowControl::XonXoff,
+		SERIAL2_FLOW_CONTROL_RTS_CTS => FlowControl::RtsCts,
+		_ => return invalid_argument("invalid flow control"),
+	};
+	let result = port.port.get_configuration().and_then(|mut new_settings| {
+		new_settings.set_raw();
+		new_settings.set_baud_rate(settings.baud_rate)?;
+		new_settings.set_char_size(char_size);
+		new_settings.set_stop_bits(stop_bits);
+		new_settings.set_parity(parity);
+		new_settings.set_flow_control(flow_control);
+		port.port.set_configuration(&new_settings)
+	});
+	status(result)
+}
+
+/// Set the read timeout of a serial port in milliseconds.
+///
+/// # Safety
+/// `port` must be a valid handle.
+/// The handle must not be used by any other thread during the call.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_set_read_timeout(port: *mut Serial2Port, timeout_ms: u32) -> c_int {
+	let Some(port) = port.as_mut() else { return invalid_argument("null pointer") };
+	status(port.port.set_read_timeout(Duration::from_millis(timeout_ms.into())))
+}
+
+/// Set the write timeout of a serial port in milliseconds.
+///
+/// # Safety
+/// `port` must be a valid handle.
+/// The handle must not be used by any other thread during the call.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_set_write_timeout(port: *mut Serial2Port, timeout_ms: u32) -> c_int {
+	let Some(port) = port.as_mut() else { return invalid_argument("null pointer") };
+	status(port.port.set_write_timeout(Duration::from_millis(timeout_ms.into())))
+}
+
+/// Read bytes from a serial port.
+///
+/// On success, the number of bytes read is stored in `read`.
+/// If no data is received before the read timeout expires, [`SERIAL2_ERR_TIMED_OUT`] is returned.
+///
+/// # Safety
+/// `port` must be a valid handle, `buffer` must be valid for writing `len` bytes, and `read` must be a valid pointer.
+/// The handle may be used from multiple threads at the same time.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_read(
+	port: *const Serial2Port,
+	buffer: *mut u8,
+	len: usize,
+	read: *mut usize,
+) -> c_int {
+	let (Some(port), Some(read)) = (port.as_ref(), read.as_mut()) else { return invalid_argument("null pointer") };
+	if buffer.is_null() && len > 0 {
+		return invalid_argument("null pointer");
+	}
+	let buffer = if len == 0 { &mut [][..] } else { std::slice::from_raw_parts_mut(buffer, len) };
+	match port.port.read(buffer) {
+		Ok(n) => {
+			*read = n;
+			SERIAL2_OK
+		},
+		Err(e) => error_code(e),
+	}
+}
+
+/// Write bytes to a serial port.
+///
+/// On success, the number of bytes written is stored in `written`.
+///
+/// # Safety
+/// `port` must be a valid handle, `data` must be valid for reading `len` bytes, and `written` must be a valid pointer.
+/// The handle may be used from multiple threads at the same time.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_write(
+	port: *const Serial2Port,
+	data: *const u8,
+	len: usize,
+	written: *mut usize,
+) -> c_int {
+	let (Some(port), Some(written)) = (port.as_ref(), written.as_mut()) else {
+		return invalid_argument("null pointer");
+	};
+	if data.is_null() && len > 0 {
+		return invalid_argument("null pointer");
+	}
+	let data = if len == 0 { &[][..] } else { std::slice::from_raw_parts(data, len) };
+	match port.port.write(data) {
+		Ok(n) => {
+			*written = n;
+			SERIAL2_OK
+		},
+		Err(e) => error_code(e),
+	}
+}
+
+/// Write all bytes to a serial port.
+///
+/// # Safety
+/// `port` must be a valid handle and `data` must be valid for reading `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_write_all(port: *const Serial2Port, data: *const u8, len: usize) -> c_int {
+	let Some(port) = port.as_ref() else { return invalid_argument("null pointer") };
+	if data.is_null() && len > 0 {
+		return invalid_argument("null pointer");
+	}
+	let data = if len == 0 { &[][..] } else { std::slice::from_raw_parts(data, len) };
+	status(port.port.write_all(data))
+}
+
+/// Wait until all data written to a serial port has been transmitted.
+///
+/// # Safety
+/// `port` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_flush(port: *const Serial2Port) -> c_int {
+	let Some(port) = port.as_ref() else { return invalid_argument("null pointer") };
+	status(port.port.flush())
+}
+
+/// Discard the kernel input and output buffers of a serial port.
+///
+/// # Safety
+/// `port` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_discard_buffers(port: *const Serial2Port) -> c_int {
+	let Some(port) = port.as_ref() else { return invalid_argument("null pointer") };
+	status(port.port.discard_buffers())
+}
+
+/// Set the state of the Ready To Send line.
+///
+/// # Safety
+/// `port` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_set_rts(port: *const Serial2Port, state: bool) -> c_int {
+	let Some(port) = port.as_ref() else { return invalid_argument("null pointer") };
+	status(port.port.set_rts(state))
+}
+
+/// Set the state of the Data Terminal Ready line.
+///
+/// # Safety
+/// `port` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_set_dtr(port: *const Serial2Port, state: bool) -> c_int {
+	let Some(port) = port.as_ref() else { return invalid_argument("null pointer") };
+	status(port.port.set_dtr(state))
+}
+
+/// Read the state of the Clear To Send line.
+///
+/// # Safety
+/// `port` must be a valid handle and `state` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_read_cts(port: *const Serial2Port, state: *mut bool) -> c_int {
+	read_line(port, state, SerialPort::read_cts)
+}
+
+/// Read the state of the Data Set Ready line.
+///
+/// # Safety
+/// `port` must be a valid handle and `state` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_read_dsr(port: *const Serial2Port, state: *mut bool) -> c_int {
+	read_line(port, state, SerialPort::read_dsr)
+}
+
+/// Read the state of the Ring Indicator line.
+///
+/// # Safety
+/// `port` must be a valid handle and `state` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_read_ri(port: *const Serial2Port, state: *mut bool) -> c_int {
+	read_line(port, state, SerialPort::read_ri)
+}
+
+/// Read the state of the Carrier Detect line.
+///
+/// # Safety
+/// `port` must be a valid handle and `state` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_read_cd(port: *const Serial2Port, state: *mut bool) -> c_int {
+	read_line(port, state, SerialPort::read_cd)
+}
+
+/// Call a function for each available serial port.
+///
+/// The name passed to the callback is only valid for the duration of the call.
+/// If `callback` is null, [`SERIAL2_ERR_INVALID_ARGUMENT`] is returned.
+///
+/// # Safety
+/// `callback` must be null or safe to call with a nul-terminated string and `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn serial2_available_ports(
+	callback: Option<extern "C" fn(name: *const c_char, user_data: *mut c_void)>,
+	user_data: *mut c_void,
+) -> c_int {
+	let Some(callback) = callback else { return invalid_argument("null pointer") };
+	match SerialPort::available_ports() {
+		Ok(ports) => {
+			for port in ports {
+				// Paths that contain a nul byte can not be passed to C, so they are skipped.
+				if let Ok(name) = CString::new(port.to_string_lossy().into_owned()) {
+					callback(name.as_ptr(), user_data);
+				}
+			}
+			SERIAL2_OK
+		},
+		Err(e) => error_code(e),
+	}
+}
+
+/// Read a modem line with the given function.
+unsafe fn read_line(
+	port: *const Serial2Port,
+	state: *mut bool,
+	read: fn(&SerialPort) -> std::io::Result<bool>,
+) -> c_int {
+	let (Some(port), Some(state)) = (port.as_ref(), state.as_mut()) else { return invalid_argument("null pointer") };
+	match read(&port.port) {
+		Ok(value) => {
+			*state = value;
+			SERIAL2_OK
+		},
+		Err(e) => error_code(e),
+	}
+}
+
+/// Convert a result to a status code, recording the error message.
+fn status(result: std::io::Result<()>) -> c_int {
+	match result {
+		Ok(()) => SERIAL2_OK,
+		Err(e) => error_code(e),
+	}
+}
+
+/// Convert an error to a status code, recording the error message.
+fn error_code(error: std::io::Error) -> c_int {
+	set_last_error(&error.to_string());
+	match error.kind() {
+		std::io::ErrorKind::InvalidInput => SERIAL2_ERR_INVALID_ARGUMENT,
+		std::io::ErrorKind::TimedOut => SERIAL2_ERR_TIMED_OUT,
+		std::io::ErrorKind::NotFound => SERIAL2_ERR_NOT_FOUND,
+		std::io::ErrorKind::PermissionDenied => SERIAL2_ERR_PERMISSION_DENIED,
+		std::io::ErrorKind::ResourceBusy => SERIAL2_ERR_BUSY,
+		std::io::ErrorKind::Interrupted => SERIAL2_ERR_INTERRUPTED,
+		std::io::ErrorKind::Unsupported => SERIAL2_ERR_UNSUPPORTED,
+		_ => SERIAL2_ERR_OTHER,
+	}
+}
+
+/// Record an invalid argument error.
+fn invalid_argument(message: &str) -> c_int {
+	set_last_error(message);
+	SERIAL2_ERR_INVALID_ARGUMENT
+}
+
+/// Set the message of the last error on this thread.
+fn set_last_error(message: &str) {
+	let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+	LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+}
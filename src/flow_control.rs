@@ -0,0 +1,48 @@
+/// The reason that transmission is held back by flow control.
+///
+/// Use [`SerialPort::get_flow_control_stall()`][crate::SerialPort::get_flow_control_stall] to check if transmission is currently held back.
+///
+/// When a write times out while transmission is held back by flow control,
+/// the returned [`std::io::Error`] wraps a value of this type.
+/// You can retrieve it with [`std::io::Error::get_ref()`] followed by `downcast_ref::<FlowControlStall>()`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FlowControlStall {
+	/// The peer deasserted the CTS line while RTS/CTS flow control is enabled.
+	CtsDeasserted,
+
+	/// The peer deasserted the DSR line while DSR sensitivity is enabled.
+	///
+	/// This can only be detected on Windows.
+	DsrDeasserted,
+
+	/// The carrier detect line is deasserted while carrier detect sensitivity is enabled.
+	///
+	/// This can only be detected on Windows.
+	CdDeasserted,
+
+	/// The peer sent an XOFF character while XON/XOFF flow control is enabled.
+	///
+	/// This can only be detected on Windows.
+	XoffReceived,
+}
+
+impl FlowControlStall {
+	/// Get a human readable description of the stall.
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::CtsDeasserted => "transmission stalled: the peer deasserted CTS while RTS/CTS flow control is enabled",
+			Self::DsrDeasserted => "transmission stalled: the peer deasserted DSR while DSR sensitivity is enabled",
+			Self::CdDeasserted => "transmission stalled: carrier detect is deasserted while CD sensitivity is enabled",
+			Self::XoffReceived => "transmission stalled: the peer sent XOFF while XON/XOFF flow control is enabled",
+		}
+	}
+}
+
+impl std::fmt::Display for FlowControlStall {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl std::error::Error for FlowControlStall {}
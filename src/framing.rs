@@ -0,0 +1,573 @@
+//! Splitting received data into frames.
+//!
+//! A [`Framer`] decodes frames from the start of a buffer of received data.
+//! A [`FrameReader`] combines a framer with a serial port:
+//! it reads from the port into an internal buffer and hands out complete frames.
+//!
+//! Data that does not form a complete frame yet is kept in the buffer for the next call,
+//! so read timeouts never lose data.
+//!
+//! Some protocols mark the end of a frame with a period of silence on the line.
+//! If an idle time is configured with [`FrameReader::set_idle_time()`] or [`FrameReader::set_idle_char_times()`],
+//! the framer is also given the chance to complete a frame when the line becomes idle (see [`Framer::decode_on_idle()`]).
+//! The [`GapFramer`] uses this to split data into frames purely by silence.
+//!
+//! The [`SlipFramer`] decodes frames encoded with SLIP (RFC 1055).
+//!
+//! When the framer reports invalid data, the frame reader discards data to find the start of the next frame.
+//! How much is discarded depends on the [`Resync`] policy selected with [`FrameReader::set_resync()`].
+//! [`FrameReader::resync_stats()`] counts how often this happened, which helps to judge the quality of a link.
+//!
+//! Frames can be timestamped with [`FrameReader::read_frame_timestamped()`],
+//! using the clock selected with [`FrameReader::set_clock()`].
+
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::capture::CaptureClock;
+use crate::SerialPort;
+
+/// Decodes frames from received data.
+pub trait Framer {
+	/// The type of a decoded frame.
+	type Frame;
+
+	/// Try to decode a frame from the start of the received data.
+	///
+	/// Returns the decoded frame and the number of bytes it occupies,
+	/// or `None` if more data is needed to complete the frame.
+	///
+	/// If the data at the start of the buffer can not be the start of a valid frame,
+	/// this should return an error of the type [`std::io::ErrorKind::InvalidData`].
+	fn decode(&mut self, data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>>;
+
+	/// Try to decode a frame from the received data after the line became idle.
+	///
+	/// This is only called by a [`FrameReader`] with an idle time configured,
+	/// when [`Self::decode()`] did not return a frame and no data was received for the idle time.
+	/// It is called at most once per idle period.
+	///
+	/// The default implementation returns `None`.
+	fn decode_on_idle(&mut self, data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>> {
+		let _ = data;
+		Ok(None)
+	}
+}
+
+/// A framer that splits data into frames separated by silence on the line.
+///
+/// All data received before the line becomes idle forms one frame.
+/// Use [`FrameReader::set_idle_char_times()`] or [`FrameReader::set_idle_time()`] to configure the gap between frames:
+/// without an idle time, the frame reader never produces frames with this framer.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct GapFramer;
+
+impl Framer for GapFramer {
+	type Frame = Vec<u8>;
+
+	fn decode(&mut self, _data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>> {
+		Ok(None)
+	}
+
+	fn decode_on_idle(&mut self, data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>> {
+		Ok(Some((data.to_vec(), data.len())))
+	}
+}
+
+/// A framer for SLIP (RFC 1055) encoded frames.
+///
+/// Frames are delimited by `0xC0` bytes. Empty frames are ignored,
+/// so frames may be surrounded by delimiters on both sides.
+/// Use [`SlipFramer::encode()`] to encode a frame for sending.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SlipFramer;
+
+/// The SLIP frame delimiter.
+const SLIP_END: u8 = 0xC0;
+
+/// The SLIP escape byte.
+const SLIP_ESC: u8 = 0xDB;
+
+/// The escaped form of [`SLIP_END`].
+const SLIP_ESC_END: u8 = 0xDC;
+
+/// The escaped form of [`SLIP_ESC`].
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+impl SlipFramer {
+	/// Encode a frame, including a delimiter at the start and the end.
+	pub fn encode(data: &[u8]) -> Vec<u8> {
+		let mut encoded = Vec::with_capacity(data.len() + 2);
+		encoded.push(SLIP_END);
+		for &byte in data {
+			match byte {
+				SLIP_END => encoded.extend_from_slice(&[SLIP_ESC, SLIP_ESC_END]),
+				SLIP_ESC => encoded.extend_from_slice(&[SLIP_ESC, SLIP_ESC_ESC]),
+				_ => encoded.push(byte),
+			}
+		}
+		encoded.push(SLIP_END);
+		encoded
+	}
+}
+
+impl Framer for SlipFramer {
+	type Frame = Vec<u8>;
+
+	fn decode(&mut self, data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>> {
+		let start = data.iter().position(|&byte| byte != SLIP_END).unwrap_or(data.len());
+		let Some(end) = data[start..].iter().position(|&byte| byte == SLIP_END) else { return Ok(None) };
+		let end = start + end;
+
+		let mut frame = Vec::with_capacity(end - start);
+		let mut bytes = data[start..end].iter();
+		while let Some(&byte) = bytes.next() {
+			if byte != SLIP_ESC {
+				frame.push(byte);
+				continue;
+			}
+			match bytes.next() {
+				Some(&SLIP_ESC_END) => frame.push(SLIP_END),
+				Some(&SLIP_ESC_ESC) => frame.push(SLIP_ESC),
+				_ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid SLIP escape sequence")),
+			}
+		}
+		Ok(Some((frame, end + 1)))
+	}
+}
+
+/// How a [`FrameReader`] recovers when the framer reports invalid data.
+///
+/// The right policy depends on the link and the protocol:
+/// skipping single bytes finds frames quickly on clean links,
+/// while protocols with a sync word or a gap between frames can resynchronize faster and more reliably on noisy links.
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[non_exhaustive]
+pub enum Resync {
+	/// Discard the first byte and try to decode a frame from the next byte.
+	#[default]
+	SkipByte,
+
+	/// Discard data up to the next occurrence of a sync word.
+	///
+	/// The sync word itself is kept, since it is the start of the next frame.
+	/// If the buffer does not contain the sync word, only the bytes that may be the start of a partial sync word are kept.
+	/// An empty sync word behaves like [`Self::SkipByte`].
+	SkipToSync(Vec<u8>),
+
+	/// Discard all buffered data, and all data received until the line becomes idle.
+	///
+	/// This requires an idle time, see [`FrameReader::set_idle_time()`].
+	/// Without an idle time, only the buffered data is discarded.
+	DiscardUntilIdle,
+}
+
+/// Statistics about resynchronization of a [`FrameReader`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub struct ResyncStats {
+	/// The number of times the frame reader resynchronized after invalid data.
+	events: u64,
+
+	/// The number of bytes discarded to resynchronize.
+	discarded_bytes: u64,
+}
+
+impl ResyncStats {
+	/// Get the number of times the frame reader resynchronized after invalid data.
+	pub fn events(&self) -> u64 {
+		self.events
+	}
+
+	/// Get the number of bytes discarded to resynchronize.
+	pub fn discarded_bytes(&self) -> u64 {
+		self.discarded_bytes
+	}
+}
+
+/// Reads frames from a serial port.
+///
+/// See the [module documentation][self] for more information.
+pub struct FrameReader<F> {
+	/// The serial port to read from.
+	port: SerialPort,
+
+	/// The framer used to decode frames.
+	framer: F,
+
+	/// Received data that has not been decoded yet.
+	buffer: Vec<u8>,
+
+	/// The duration of silence after which the line is considered idle.
+	idle_time: Option<Duration>,
+
+	/// The last time data was received.
+	last_receive: Instant,
+
+	/// True if the framer has already been given the current idle period.
+	idle_reported: bool,
+
+	/// The clock used to timestamp frames.
+	clock: CaptureClock,
+
+	/// An error that occurred while draining frames, to be reported by the next read.
+	pending_error: Option<std::io::Error>,
+
+	/// How to recover from invalid data.
+	resync: Resync,
+
+	/// Statistics about resynchronization.
+	resync_stats: ResyncStats,
+
+	/// True while received data is discarded until the line becomes idle.
+	discard_until_idle: bool,
+}
+
+impl<F: Framer> FrameReader<F> {
+	/// Create a new frame reader.
+	pub fn new(port: SerialPort, framer: F) -> Self {
+		Self {
+			port,
+			framer,
+			buffer: Vec::new(),
+			idle_time: None,
+			last_receive: Instant::now(),
+			idle_reported: false,
+			clock: CaptureClock::realtime(),
+			pending_error: None,
+			resync: Resync::default(),
+			resync_stats: ResyncStats::default(),
+			discard_until_idle: false,
+		}
+	}
+
+	/// Set the clock used to timestamp frames.
+	///
+	/// By default, frames are timestamped with the wall clock of the system.
+	/// Use the clock of a [`Tap`][crate::capture::Tap] or [`CaptureMerger`][crate::capture::CaptureMerger]
+	/// to get timestamps that can be compared with captured traffic.
+	pub fn set_clock(&mut self, clock: CaptureClock) {
+		self.clock = clock;
+	}
+
+	/// Get the clock used to timestamp frames.
+	pub fn clock(&self) -> &CaptureClock {
+		&self.clock
+	}
+
+	/// Set the duration of silence after which the line is considered idle.
+	///
+	/// When the line becomes idle with undecoded data in the buffer, [`Framer::decode_on_idle()`] is called.
+	/// Use `None` to disable idle detection.
+	pub fn set_idle_time(&mut self, idle_time: Option<Duration>) {
+		self.idle_time = idle_time;
+	}
+
+	/// Set the duration of silence after which the line is considered idle, in character times.
+	///
+	/// The character time is computed from the current settings of the serial port with [`Settings::get_char_time()`][crate::Settings::get_char_time].
+	/// If you change the settings of the serial port later, you must call this function again.
+	pub fn set_idle_char_times(&mut self, char_times: u32) -> std::io::Result<()> {
+		let char_time = self.port.get_configuration()?.get_char_time()?;
+		self.idle_time = Some(char_time * char_times);
+		Ok(())
+	}
+
+	/// Get the duration of silence after which the line is considered idle.
+	pub fn get_idle_time(&self) -> Option<Duration> {
+		self.idle_time
+	}
+
+	/// Set how to recover when the framer reports invalid data.
+	///
+	/// The default is [`Resync::SkipByte`].
+	pub fn set_resync(&mut self, resync: Resync) {
+		self.resync = resync;
+	}
+
+	/// Get how the frame reader recovers when the framer reports invalid data.
+	pub fn get_resync(&self) -> &Resync {
+		&self.resync
+	}
+
+	/// Get the statistics about resynchronization after invalid data.
+	pub fn resync_stats(&self) -> ResyncStats {
+		self.resync_stats
+	}
+
+	/// Reset the statistics about resynchronization to zero.
+	pub fn reset_resync_stats(&mut self) {
+		self.resync_stats = ResyncStats::default();
+	}
+
+	/// Get a reference to the serial port.
+	pub fn port(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Get a mutable reference to the serial port.
+	///
+	/// Reading directly from the serial port bypasses the frame reader and may cause frames to be lost.
+	pub fn port_mut(&mut self) -> &mut SerialPort {
+		&mut self.port
+	}
+
+	/// Get a reference to the framer.
+	pub fn framer(&self) -> &F {
+		&self.framer
+	}
+
+	/// Get a mutable reference to the framer.
+	pub fn framer_mut(&mut self) -> &mut F {
+		&mut self.framer
+	}
+
+	/// Get the received data that has not been decoded yet.
+	pub fn buffered(&self) -> &[u8] {
+		&self.buffer
+	}
+
+	/// Discard the received data that has not been decoded yet.
+	pub fn discard_buffered(&mut self) {
+		self.buffer.clear();
+	}
+
+	/// Consume the frame reader and return the serial port and the framer.
+	///
+	/// Received data that has not been decoded yet is lost.
+	pub fn into_inner(self) -> (SerialPort, F) {
+		(self.port, self.framer)
+	}
+
+	/// Read a single frame.
+	///
+	/// This reads from the serial port until the framer decodes a complete frame.
+	/// If the read timeout of the serial port expires before a frame is complete, an error is returned.
+	/// The partial frame is kept in the buffer, so you can call this function again to continue reading.
+	///
+	/// If the framer reports invalid data, data is discarded according to the [`Resync`] policy and the error is returned.
+	/// The next call continues decoding from the remaining data.
+	/// If the noise filter of the serial port waits for a valid frame (see [`NoiseFilter`][crate::NoiseFilter]),
+	/// invalid data is discarded without returning an error until the first valid frame is decoded.
+	///
+	/// If an idle time is configured, this function temporarily changes the read timeout of the serial port.
+	/// The original read timeout is restored before the function returns.
+	pub fn read_frame(&mut self) -> std::io::Result<F::Frame> {
+		if let Some(error) = self.pending_error.take() {
+			return Err(error);
+		}
+		match self.idle_time {
+			None => self.read_frame_without_idle(),
+			Some(idle_time) => {
+				let read_timeout = self.port.get_read_timeout()?;
+				let result = self.read_frame_with_idle(idle_time, Instant::now() + read_timeout);
+				self.port.set_read_timeout(read_timeout)?;
+				result
+			},
+		}
+	}
+
+	/// Read all frames that are available, waiting for at least one.
+	///
+	/// This waits for the first frame like [`Self::read_frame()`].
+	/// Then it reads all data that is available without waiting, and appends all complete frames to `frames`.
+	/// This needs fewer calls than [`Self::read_frame()`] when frames arrive at a high rate.
+	///
+	/// Returns the number of bytes held back in the buffer because they do not form a complete frame yet.
+	///
+	/// If the framer reports invalid data or reading fails after the first frame was decoded,
+	/// the frames decoded so far are returned and the error is reported by the next read.
+	///
+	/// This function temporarily changes the read timeout of the serial port.
+	/// The original read timeout is restored before the function returns.
+	pub fn read_frames(&mut self, frames: &mut Vec<F::Frame>) -> std::io::Result<usize> {
+		frames.push(self.read_frame()?);
+		let read_timeout = self.port.get_read_timeout()?;
+		self.port.set_read_timeout(Duration::ZERO)?;
+		self.drain_frames(frames);
+		self.port.set_read_timeout(read_timeout)?;
+		Ok(self.buffer.len())
+	}
+
+	/// Read a single frame, and the time at which it was received.
+	///
+	/// The timestamp is the time at which the data that completed the frame was read from the serial port.
+	/// Frames decoded from the same chunk of data get the same timestamp.
+	///
+	/// See [`Self::read_frame()`] for more information.
+	pub fn read_frame_timestamped(&mut self) -> std::io::Result<(SystemTime, F::Frame)> {
+		let frame = self.read_frame()?;
+		Ok((self.clock.at(self.last_receive), frame))
+	}
+
+	/// Read a single frame without idle detection.
+	fn read_frame_without_idle(&mut self) -> std::io::Result<F::Frame> {
+		let mut chunk = [0; 1024];
+		loop {
+			if let Some(frame) = self.decode_buffered()? {
+				return Ok(frame);
+			}
+			match self.port.read(&mut chunk) {
+				Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+				Ok(read) => self.receive(&chunk[..read]),
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Read a single frame with idle detection, until the deadline passes.
+	fn read_frame_with_idle(&mut self, idle_time: Duration, deadline: Instant) -> std::io::Result<F::Frame> {
+		let mut chunk = [0; 1024];
+		loop {
+			if let Some(frame) = self.decode_buffered()? {
+				return Ok(frame);
+			}
+
+			let now = Instant::now();
+			let mut wake_at = deadline;
+			if (!self.buffer.is_empty() || self.discard_until_idle) && !self.idle_reported {
+				let idle_at = self.last_receive + idle_time;
+				if idle_at <= now {
+					self.idle_reported = true;
+					if self.discard_until_idle {
+						self.discard_until_idle = false;
+						continue;
+					}
+					if let Some(frame) = self.decode_buffered_on_idle()? {
+						return Ok(frame);
+					}
+					continue;
+				}
+				wake_at = wake_at.min(idle_at);
+			}
+			if deadline <= now {
+				return Err(std::io::ErrorKind::TimedOut.into());
+			}
+
+			self.port.set_read_timeout(wake_at - now)?;
+			match self.port.read(&mut chunk) {
+				Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+				Ok(read) => self.receive(&chunk[..read]),
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Add received data to the buffer, or discard it while waiting for the line to become idle.
+	fn receive(&mut self, data: &[u8]) {
+		self.last_receive = Instant::now();
+		self.idle_reported = false;
+		if self.discard_until_idle {
+			self.resync_stats.discarded_bytes += data.len() as u64;
+		} else {
+			self.buffer.extend_from_slice(data);
+		}
+	}
+
+	/// Discard data after the framer reported invalid data, according to the resync policy.
+	///
+	/// If `idle` is true, the line is already idle, so no further data is discarded.
+	fn resync(&mut self, idle: bool) {
+		let discard = match &self.resync {
+			Resync::SkipByte => 1,
+			Resync::SkipToSync(sync) if sync.is_empty() => 1,
+			Resync::SkipToSync(sync) => match self.buffer.get(1..).and_then(|data| find(data, sync)) {
+				Some(index) => index + 1,
+				// Keep the bytes that may be the start of the sync word.
+				None => self.buffer.len().saturating_sub(sync.len().saturating_sub(1)),
+			},
+			Resync::DiscardUntilIdle => {
+				self.discard_until_idle = !idle && self.idle_time.is_some();
+				self.buffer.len()
+			},
+		};
+		let discard = discard.max(1).min(self.buffer.len());
+		self.buffer.drain(..discard);
+		self.resync_stats.events += 1;
+		self.resync_stats.discarded_bytes += discard as u64;
+	}
+
+	/// Decode frames from all data that is available without waiting.
+	///
+	/// Errors are stored to be reported by the next read.
+	fn drain_frames(&mut self, frames: &mut Vec<F::Frame>) {
+		let mut chunk = [0; 1024];
+		loop {
+			loop {
+				match self.decode_buffered() {
+					Ok(Some(frame)) => frames.push(frame),
+					Ok(None) => break,
+					Err(e) => {
+						self.pending_error = Some(e);
+						return;
+					},
+				}
+			}
+			match self.port.read(&mut chunk) {
+				Ok(0) => {
+					self.pending_error = Some(std::io::ErrorKind::UnexpectedEof.into());
+					return;
+				},
+				Ok(read) => self.receive(&chunk[..read]),
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => return,
+				Err(e) => {
+					self.pending_error = Some(e);
+					return;
+				},
+			}
+		}
+	}
+
+	/// Try to decode a frame from the buffered data.
+	///
+	/// While the noise filter of the serial port waits for a valid frame, invalid data is discarded silently.
+	fn decode_buffered(&mut self) -> std::io::Result<Option<F::Frame>> {
+		loop {
+			if self.buffer.is_empty() {
+				return Ok(None);
+			}
+			match self.framer.decode(&self.buffer) {
+				Ok(Some((frame, len))) => {
+					self.buffer.drain(..len.min(self.buffer.len()));
+					self.port.noise_filter_frame_received();
+					return Ok(Some(frame));
+				},
+				Ok(None) => return Ok(None),
+				Err(e) => {
+					self.resync(false);
+					if !self.port.is_noise_filter_awaiting_frame() {
+						return Err(e);
+					}
+				},
+			}
+		}
+	}
+
+	/// Try to decode a frame from the buffered data after the line became idle.
+	fn decode_buffered_on_idle(&mut self) -> std::io::Result<Option<F::Frame>> {
+		match self.framer.decode_on_idle(&self.buffer) {
+			Ok(Some((frame, len))) => {
+				self.buffer.drain(..len.min(self.buffer.len()));
+				self.port.noise_filter_frame_received();
+				Ok(Some(frame))
+			},
+			Ok(None) => Ok(None),
+			Err(e) => {
+				if self.port.is_noise_filter_awaiting_frame() {
+					// Data that does not form a valid frame before the line became idle is noise.
+					self.buffer.clear();
+					return Ok(None);
+				}
+				self.resync(true);
+				Err(e)
+			},
+		}
+	}
+}
+
+/// Find the first occurrence of a non-empty needle in data.
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+	data.windows(needle.len()).position(|window| window == needle)
+}
@@ -0,0 +1,105 @@
+//! Entry points for fuzzing, only available when compiled with `--cfg fuzzing`.
+//!
+//! The cargo-fuzz targets in the `fuzz` directory call these functions with arbitrary input.
+//! Each entry point panics when it finds a violated invariant, which the fuzzer reports as a crash.
+
+use crate::framing::{Framer, GapFramer, SlipFramer};
+use crate::mux::cmux::CmuxFramer;
+use crate::mux::MuxFramer;
+use crate::ppp::HdlcFramer;
+use crate::protocol::firmata::FirmataFramer;
+use crate::protocol::gcode::LineFramer;
+use crate::protocol::roomba::StreamFramer;
+
+/// Feed arbitrary data into each framer of the crate.
+///
+/// The data is decoded the way a [`FrameReader`][crate::framing::FrameReader] does it,
+/// both in one piece and split in two at a position chosen by the first byte.
+pub fn framers(data: &[u8]) {
+	let split = data.first().map_or(0, |&byte| usize::from(byte).min(data.len()));
+	for (first, second) in [(data, &[][..]), data.split_at(split)] {
+		decode_all(SlipFramer, first, second);
+		decode_all(GapFramer, first, second);
+		decode_all(HdlcFramer, first, second);
+		decode_all(CmuxFramer, first, second);
+		decode_all(MuxFramer, first, second);
+		decode_all(FirmataFramer, first, second);
+		decode_all(LineFramer, first, second);
+		decode_all(StreamFramer, first, second);
+	}
+}
+
+/// Decode all frames from data that arrives in two chunks, and check the invariants of the framer.
+fn decode_all<F: Framer>(mut framer: F, first: &[u8], second: &[u8]) {
+	let mut buffer = first.to_vec();
+	for chunk in [second, &[]] {
+		while !buffer.is_empty() {
+			let len = match framer.decode(&buffer) {
+				Ok(Some((_frame, len))) => len,
+				Ok(None) => break,
+				Err(_) => 1,
+			};
+			assert!(len > 0, "framer decoded a frame of zero bytes");
+			assert!(len <= buffer.len(), "framer consumed {len} bytes from a buffer of {} bytes", buffer.len());
+			buffer.drain(..len);
+		}
+		buffer.extend_from_slice(chunk);
+	}
+	// The line became idle with the remaining data.
+	while !buffer.is_empty() {
+		let len = match framer.decode_on_idle(&buffer) {
+			Ok(Some((_frame, len))) => len,
+			Ok(None) => break,
+			Err(_) => 1,
+		};
+		assert!(len > 0, "framer decoded a frame of zero bytes on idle");
+		assert!(len <= buffer.len(), "framer consumed {len} bytes from a buffer of {} bytes", buffer.len());
+		buffer.drain(..len);
+	}
+}
+
+/// Apply arbitrary settings to a pseudo-terminal and check that they read back the same.
+///
+/// The first three bytes are the baud rate, the fourth byte selects the character size, stop bits, parity and flow control.
+/// Settings that the pseudo-terminal rejects are skipped.
+#[cfg(all(unix, feature = "unix"))]
+pub fn settings_round_trip(data: &[u8]) {
+	use crate::{CharSize, FlowControl, Parity, SerialPort, StopBits};
+
+	let &[baud_low, baud_mid, baud_high, flags, ..] = data else {
+		return;
+	};
+	let baud_rate = u32::from_le_bytes([baud_low, baud_mid, baud_high, 0]);
+	let char_size = [CharSize::Bits5, CharSize::Bits6, CharSize::Bits7, CharSize::Bits8][usize::from(flags & 0x03)];
+	let stop_bits = [StopBits::One, StopBits::Two][usize::from(flags >> 2 & 0x01)];
+	let parity = [Parity::None, Parity::Odd, Parity::Even][usize::from(flags >> 3 & 0x03) % 3];
+	let flow_control = [FlowControl::None, FlowControl::XonXoff, FlowControl::RtsCts];
+	let flow_control = flow_control[usize::from(flags >> 5 & 0x03) % 3];
+
+	let Ok((_master, mut port)) = SerialPort::pair() else {
+		return;
+	};
+	let Ok(mut settings) = port.get_configuration() else {
+		return;
+	};
+	if settings.set_baud_rate(baud_rate).is_err() {
+		return;
+	}
+	settings.set_char_size(char_size);
+	settings.set_stop_bits(stop_bits);
+	settings.set_parity(parity);
+	settings.set_flow_control(flow_control);
+	if port.set_configuration(&settings).is_err() {
+		return;
+	}
+
+	let applied = match port.get_configuration() {
+		Ok(applied) => applied,
+		Err(e) => panic!("failed to read back applied settings: {e}"),
+	};
+	assert!(applied.matches(&settings), "applied settings do not match: {applied:?} != {settings:?}");
+	assert!(applied.get_char_size().ok() == Some(char_size));
+	assert!(applied.get_stop_bits().ok() == Some(stop_bits));
+	assert!(applied.get_parity().ok() == Some(parity));
+	assert!(applied.get_flow_control().ok() == Some(flow_control));
+}
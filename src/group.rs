@@ -0,0 +1,201 @@
+//! Fair reading from many serial ports in one loop.
+//!
+//! A gateway that serves dozens of serial ports from a single thread must make sure that
+//! one chatty device can not starve the others.
+//! A [`PortGroup`] waits until any of its ports has data available,
+//! and then reads at most a fixed number of bytes (the quantum) from each ready port.
+//! The port that is served first rotates between calls,
+//! so no port is always served before the others.
+//!
+//! Data that exceeds the quantum of a port stays in the kernel buffer and is read in the next call.
+
+use std::time::Duration;
+
+use crate::SerialPort;
+
+/// The default number of bytes read from each ready port per call.
+const DEFAULT_QUANTUM: usize = 4096;
+
+/// A group of serial ports that are read fairly in one loop.
+///
+/// See the [module documentation][self] for more information.
+pub struct PortGroup<K> {
+	/// The ports in the group.
+	members: Vec<Member<K>>,
+
+	/// The default number of bytes read from each ready port per call.
+	quantum: usize,
+
+	/// The index of the member to serve first in the next call.
+	#[cfg(unix)]
+	next_start: usize,
+
+	/// The buffer used for reading.
+	#[cfg(unix)]
+	buffer: Vec<u8>,
+}
+
+/// A serial port in a [`PortGroup`].
+struct Member<K> {
+	/// The key of the port.
+	key: K,
+
+	/// The serial port.
+	port: SerialPort,
+
+	/// The number of bytes read from the port per call, if it differs from the default.
+	quantum: Option<usize>,
+}
+
+impl<K: Eq> PortGroup<K> {
+	/// Create a new empty group.
+	///
+	/// The default quantum is 4096 bytes.
+	pub fn new() -> Self {
+		Self {
+			members: Vec::new(),
+			quantum: DEFAULT_QUANTUM,
+			#[cfg(unix)]
+			next_start: 0,
+			#[cfg(unix)]
+			buffer: Vec::new(),
+		}
+	}
+
+	/// Add a serial port to the group.
+	///
+	/// If the group already contains a port with the same key, it is replaced and returned.
+	pub fn insert(&mut self, key: K, port: SerialPort) -> Option<SerialPort> {
+		match self.members.iter_mut().find(|member| member.key == key) {
+			Some(member) => Some(std::mem::replace(&mut member.port, port)),
+			None => {
+				self.members.push(Member { key, port, quantum: None });
+				None
+			},
+		}
+	}
+
+	/// Remove a serial port from the group.
+	pub fn remove(&mut self, key: &K) -> Option<SerialPort> {
+		let index = self.members.iter().position(|member| member.key == *key)?;
+		Some(self.members.remove(index).port)
+	}
+
+	/// Get a reference to a serial port in the group.
+	///
+	/// You can use this to write to the serial port.
+	pub fn get(&self, key: &K) -> Option<&SerialPort> {
+		Some(&self.member(key)?.port)
+	}
+
+	/// Get the keys of all ports in the group.
+	pub fn keys(&self) -> impl Iterator<Item = &K> {
+		self.members.iter().map(|member| &member.key)
+	}
+
+	/// Get the number of ports in the group.
+	pub fn len(&self) -> usize {
+		self.members.len()
+	}
+
+	/// Check if the group is empty.
+	pub fn is_empty(&self) -> bool {
+		self.members.is_empty()
+	}
+
+	/// Set the default number of bytes read from each ready port per call to [`Self::read_ready()`].
+	///
+	/// A value of 0 is treated as 1.
+	pub fn set_quantum(&mut self, quantum: usize) {
+		self.quantum = quantum.max(1);
+	}
+
+	/// Get the default number of bytes read from each ready port per call to [`Self::read_ready()`].
+	pub fn get_quantum(&self) -> usize {
+		self.quantum
+	}
+
+	/// Set the number of bytes read from one port per call, or `None` to use the default quantum.
+	///
+	/// Use a larger quantum for ports that need more bandwidth.
+	/// A value of 0 is treated as 1.
+	///
+	/// Returns `false` if the group does not contain a port with the given key.
+	pub fn set_port_quantum(&mut self, key: &K, quantum: Option<usize>) -> bool {
+		match self.members.iter_mut().find(|member| member.key == *key) {
+			Some(member) => {
+				member.quantum = quantum.map(|quantum| quantum.max(1));
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Get the number of bytes read from one port per call, or `None` if it uses the default quantum.
+	pub fn get_port_quantum(&self, key: &K) -> Option<usize> {
+		self.member(key)?.quantum
+	}
+
+	/// Wait for data on any port, and read at most one quantum from each ready port.
+	///
+	/// The handler is called once for each ready port with the result of the read.
+	/// A read of 0 bytes means the port reported end-of-file.
+	/// Errors of individual ports are passed to the handler and do not stop the other ports from being read.
+	///
+	/// If no port is ready before the timeout expires, this returns `Ok(0)` without calling the handler.
+	/// Otherwise, it returns the number of ports that were read.
+	pub fn read_ready<F>(&mut self, timeout: Duration, mut handler: F) -> std::io::Result<usize>
+	where
+		F: FnMut(&K, std::io::Result<&[u8]>),
+	{
+		#[cfg(unix)]
+		{
+			use std::os::unix::io::AsRawFd;
+			if self.members.is_empty() {
+				std::thread::sleep(timeout);
+				return Ok(0);
+			}
+			let fds: Vec<_> = self.members.iter().map(|member| member.port.as_raw_fd()).collect();
+			let ready = crate::sys::poll_readable(&fds, timeout)?;
+
+			let count = self.members.len();
+			let start = self.next_start % count;
+			let mut served = 0;
+			for index in (start..count).chain(0..start) {
+				if !ready[index] {
+					continue;
+				}
+				let member = &self.members[index];
+				let quantum = member.quantum.unwrap_or(self.quantum);
+				if self.buffer.len() < quantum {
+					self.buffer.resize(quantum, 0);
+				}
+				match member.port.read(&mut self.buffer[..quantum]) {
+					Ok(read) => handler(&member.key, Ok(&self.buffer[..read])),
+					Err(e) => handler(&member.key, Err(e)),
+				}
+				served += 1;
+			}
+			if served > 0 {
+				self.next_start = start + 1;
+			}
+			Ok(served)
+		}
+		#[cfg(windows)]
+		{
+			let _ = (timeout, &mut handler);
+			unreachable!("this code is only enabled on Unix platforms or during documentation generation")
+		}
+	}
+
+	/// Find the member with the given key.
+	fn member(&self, key: &K) -> Option<&Member<K>> {
+		self.members.iter().find(|member| member.key == *key)
+	}
+}
+
+impl<K: Eq> Default for PortGroup<K> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -0,0 +1,376 @@
+use std::io::{IoSlice, IoSliceMut};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::SerialPort;
+
+/// The claimed state of the read and write side of a serial port, the frame lock and the guard lock.
+#[derive(Default)]
+pub(crate) struct Claims {
+	/// True if a [`Reader`] exists.
+	reader: AtomicBool,
+
+	/// True if a [`Writer`] exists.
+	writer: AtomicBool,
+
+	/// Lock held while writing a frame with [`SerialPort::write_frame()`].
+	frame: Mutex<()>,
+
+	/// Lock held in shared mode by all writes, and in exclusive mode by [`SerialPort::write_guarded()`].
+	guard: RwLock<()>,
+}
+
+impl Claims {
+	/// Lock the frame lock, ignoring poisoning.
+	pub(crate) fn lock_frame(&self) -> MutexGuard<'_, ()> {
+		self.frame.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Lock the guard lock in shared mode, ignoring poisoning.
+	pub(crate) fn lock_guard_shared(&self) -> RwLockReadGuard<'_, ()> {
+		self.guard.read().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Lock the guard lock in exclusive mode, ignoring poisoning.
+	pub(crate) fn lock_guard_exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+		self.guard.write().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+/// The read side of a serial port.
+///
+/// At most one `Reader` exists for a serial port at any time.
+/// Use [`SerialPort::reader()`] to claim it.
+pub struct Reader<'a> {
+	/// The serial port.
+	port: &'a SerialPort,
+
+	/// The claim to release on drop.
+	claim: &'a AtomicBool,
+}
+
+/// The write side of a serial port.
+///
+/// At most one `Writer` exists for a serial port at any time.
+/// Use [`SerialPort::writer()`] to claim it.
+pub struct Writer<'a> {
+	/// The serial port.
+	port: &'a SerialPort,
+
+	/// The claim to release on drop.
+	claim: &'a AtomicBool,
+}
+
+/// The owned read half of a serial port.
+///
+/// Use [`SerialPort::split()`] to create it together with the matching [`WriteHalf`].
+pub struct ReadHalf {
+	/// The serial port, shared with the write half.
+	port: Arc<SerialPort>,
+}
+
+/// The owned write half of a serial port.
+///
+/// Use [`SerialPort::split()`] to create it together with the matching [`ReadHalf`].
+pub struct WriteHalf {
+	/// The serial port, shared with the read half.
+	port: Arc<SerialPort>,
+}
+
+/// Split a serial port into an owned read half and write half.
+pub(crate) fn split(port: SerialPort) -> (ReadHalf, WriteHalf) {
+	let port = Arc::new(port);
+	(ReadHalf { port: port.clone() }, WriteHalf { port })
+}
+
+impl ReadHalf {
+	/// Get the path of the serial port.
+	///
+	/// See [`SerialPort::path()`] for more information.
+	pub fn path(&self) -> Option<PathBuf> {
+		self.port.path()
+	}
+
+	/// Get the name of the serial port, like `ttyUSB0` or `COM3`.
+	///
+	/// See [`SerialPort::name()`] for more information.
+	pub fn name(&self) -> Option<String> {
+		self.port.name()
+	}
+
+	/// Read bytes from the serial port.
+	///
+	/// See [`SerialPort::read()`] for more information.
+	pub fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		self.port.read(buf)
+	}
+
+	/// Read bytes from the serial port into a slice of buffers.
+	///
+	/// See [`SerialPort::read_vectored()`] for more information.
+	pub fn read_vectored(&mut self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		self.port.read_vectored(buf)
+	}
+
+	/// Read the exact number of bytes required to fill the buffer from the serial port.
+	///
+	/// See [`SerialPort::read_exact()`] for more information.
+	pub fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+		self.port.read_exact(buf)
+	}
+
+	/// Join the read half with its write half to get the serial port back.
+	///
+	/// If the halves do not belong to the same serial port, they are returned as error.
+	pub fn unsplit(self, write_half: WriteHalf) -> Result<SerialPort, (ReadHalf, WriteHalf)> {
+		if !Arc::ptr_eq(&self.port, &write_half.port) {
+			return Err((self, write_half));
+		}
+		drop(write_half);
+		match Arc::try_unwrap(self.port) {
+			Ok(port) => Ok(port),
+			// Unreachable: the halves hold the only references to the serial port.
+			Err(port) => Err((ReadHalf { port: port.clone() }, WriteHalf { port })),
+		}
+	}
+}
+
+impl WriteHalf {
+	/// Get the path of the serial port.
+	///
+	/// See [`SerialPort::path()`] for more information.
+	pub fn path(&self) -> Option<PathBuf> {
+		self.port.path()
+	}
+
+	/// Get the name of the serial port, like `ttyUSB0` or `COM3`.
+	///
+	/// See [`SerialPort::name()`] for more information.
+	pub fn name(&self) -> Option<String> {
+		self.port.name()
+	}
+
+	/// Write bytes to the serial port.
+	///
+	/// See [`SerialPort::write()`] for more information.
+	pub fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.port.write(buf)
+	}
+
+	/// Write all bytes to the serial port.
+	///
+	/// See [`SerialPort::write_all()`] for more information.
+	pub fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+		self.port.write_all(buf)
+	}
+
+	/// Write bytes to the serial port from a slice of buffers.
+	///
+	/// See [`SerialPort::write_vectored()`] for more information.
+	pub fn write_vectored(&mut self, buf: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		self.port.write_vectored(buf)
+	}
+
+	/// Flush all data queued to be written.
+	///
+	/// See [`SerialPort::flush()`] for more information.
+	pub fn flush(&mut self) -> std::io::Result<()> {
+		self.port.flush()
+	}
+}
+
+impl<'a> Reader<'a> {
+	/// Claim the read side of a serial port.
+	pub(crate) fn claim(port: &'a SerialPort, claims: &'a Claims) -> std::io::Result<Self> {
+		claim(&claims.reader, "the read side of the serial port is already claimed")?;
+		Ok(Self {
+			port,
+			claim: &claims.reader,
+		})
+	}
+
+	/// Read bytes from the serial port.
+	///
+	/// See [`SerialPort::read()`] for more information.
+	pub fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		self.port.read(buf)
+	}
+
+	/// Read bytes from the serial port into a slice of buffers.
+	///
+	/// See [`SerialPort::read_vectored()`] for more information.
+	pub fn read_vectored(&mut self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		self.port.read_vectored(buf)
+	}
+
+	/// Read the exact number of bytes required to fill the buffer from the serial port.
+	///
+	/// See [`SerialPort::read_exact()`] for more information.
+	pub fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+		self.port.read_exact(buf)
+	}
+}
+
+impl<'a> Writer<'a> {
+	/// Claim the write side of a serial port.
+	pub(crate) fn claim(port: &'a SerialPort, claims: &'a Claims) -> std::io::Result<Self> {
+		claim(&claims.writer, "the write side of the serial port is already claimed")?;
+		Ok(Self {
+			port,
+			claim: &claims.writer,
+		})
+	}
+
+	/// Write bytes to the serial port.
+	///
+	/// See [`SerialPort::write()`] for more information.
+	pub fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.port.write(buf)
+	}
+
+	/// Write all bytes to the serial port.
+	///
+	/// See [`SerialPort::write_all()`] for more information.
+	pub fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+		self.port.write_all(buf)
+	}
+
+	/// Write bytes to the serial port from a slice of buffers.
+	///
+	/// See [`SerialPort::write_vectored()`] for more information.
+	pub fn write_vectored(&mut self, buf: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		self.port.write_vectored(buf)
+	}
+
+	/// Flush all data queued to be written.
+	///
+	/// See [`SerialPort::flush()`] for more information.
+	pub fn flush(&mut self) -> std::io::Result<()> {
+		self.port.flush()
+	}
+}
+
+impl Drop for Reader<'_> {
+	fn drop(&mut self) {
+		self.claim.store(false, Ordering::Release);
+	}
+}
+
+impl Drop for Writer<'_> {
+	fn drop(&mut self) {
+		self.claim.store(false, Ordering::Release);
+	}
+}
+
+impl std::io::Read for Reader<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		Reader::read(self, buf)
+	}
+
+	fn read_vectored(&mut self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		Reader::read_vectored(self, buf)
+	}
+}
+
+impl std::io::Write for Writer<'_> {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		Writer::write(self, buf)
+	}
+
+	fn write_vectored(&mut self, buf: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		Writer::write_vectored(self, buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Writer::flush(self)
+	}
+}
+
+impl std::io::Read for ReadHalf {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		ReadHalf::read(self, buf)
+	}
+
+	fn read_vectored(&mut self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		ReadHalf::read_vectored(self, buf)
+	}
+}
+
+impl std::io::Write for WriteHalf {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		WriteHalf::write(self, buf)
+	}
+
+	fn write_vectored(&mut self, buf: &[IoSlice<'_>]) -> std::io::Result<usize> {
+		WriteHalf::write_vectored(self, buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		WriteHalf::flush(self)
+	}
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsFd for ReadHalf {
+	fn as_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+		self.port.as_fd()
+	}
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsFd for WriteHalf {
+	fn as_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+		self.port.as_fd()
+	}
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for ReadHalf {
+	fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+		self.port.as_raw_fd()
+	}
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for WriteHalf {
+	fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+		self.port.as_raw_fd()
+	}
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsHandle for ReadHalf {
+	fn as_handle(&self) -> std::os::windows::io::BorrowedHandle<'_> {
+		self.port.as_handle()
+	}
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsHandle for WriteHalf {
+	fn as_handle(&self) -> std::os::windows::io::BorrowedHandle<'_> {
+		self.port.as_handle()
+	}
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawHandle for ReadHalf {
+	fn as_raw_handle(&self) -> std::os::windows::io::RawHandle {
+		self.port.as_raw_handle()
+	}
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawHandle for WriteHalf {
+	fn as_raw_handle(&self) -> std::os::windows::io::RawHandle {
+		self.port.as_raw_handle()
+	}
+}
+
+/// Atomically set a claim flag, or return an error if it was already set.
+fn claim(flag: &AtomicBool, message: &'static str) -> std::io::Result<()> {
+	match flag.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed) {
+		Ok(_) => Ok(()),
+		Err(_) => Err(std::io::Error::new(std::io::ErrorKind::ResourceBusy, message)),
+	}
+}
@@ -0,0 +1,160 @@
+//! Detection of an idle receive line.
+//!
+//! Many framed protocols use a period of silence on the line as frame boundary,
+//! and applications often use silence to detect a dead peer.
+//!
+//! An [`IdleDetector`] keeps track of the last time data was received.
+//! Use [`IdleDetector::read()`] to read from a serial port until either data is received or the line becomes idle.
+//!
+//! The silence on the line is measured with a [`Clock`], which can be replaced by a manual clock in tests.
+
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+use crate::{SerialPort, Settings};
+
+/// Detects when no data has been received for a configurable duration.
+///
+/// See the [module documentation][self] for more information.
+#[derive(Debug, Clone)]
+pub struct IdleDetector {
+	/// The duration of silence after which the line is considered idle.
+	idle_time: Duration,
+
+	/// The last time data was received.
+	last_activity: Instant,
+
+	/// True if the current idle period has already been reported.
+	reported: bool,
+
+	/// The clock to measure the silence with.
+	clock: Clock,
+}
+
+/// The result of [`IdleDetector::read()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IdleEvent {
+	/// Data was received.
+	///
+	/// The value is the number of bytes read.
+	Data(usize),
+
+	/// The line became idle.
+	Idle,
+}
+
+impl IdleDetector {
+	/// Create a new idle detector for the given duration of silence.
+	///
+	/// The detector considers the time of creation as the last activity on the line.
+	pub fn new(idle_time: Duration) -> Self {
+		Self {
+			idle_time,
+			last_activity: Instant::now(),
+			reported: false,
+			clock: Clock::system(),
+		}
+	}
+
+	/// Create a new idle detector for a duration of silence expressed in character times.
+	///
+	/// The character time is computed from the given settings with [`Settings::get_char_time()`].
+	pub fn with_char_times(settings: &Settings, char_times: u32) -> std::io::Result<Self> {
+		Ok(Self::new(settings.get_char_time()? * char_times))
+	}
+
+	/// Set the duration of silence after which the line is considered idle.
+	pub fn set_idle_time(&mut self, idle_time: Duration) {
+		self.idle_time = idle_time;
+	}
+
+	/// Get the duration of silence after which the line is considered idle.
+	pub fn get_idle_time(&self) -> Duration {
+		self.idle_time
+	}
+
+	/// Set the clock used to measure the silence on the line.
+	///
+	/// This also records activity on the line at the current time of the new clock.
+	///
+	/// With a [`Clock::manual()`] clock, the line only becomes idle when the clock is advanced.
+	/// Reads from the serial port still wait in real time, so [`Self::read()`] returns an error of the type
+	/// [`std::io::ErrorKind::TimedOut`] if no data arrives within the remaining idle time.
+	pub fn set_clock(&mut self, clock: Clock) {
+		self.clock = clock;
+		self.record_activity();
+	}
+
+	/// Get the clock used to measure the silence on the line.
+	pub fn get_clock(&self) -> &Clock {
+		&self.clock
+	}
+
+	/// Get the time since the last activity on the line.
+	pub fn silence(&self) -> Duration {
+		self.clock.elapsed(self.last_activity)
+	}
+
+	/// Check if the line is currently idle.
+	pub fn is_idle(&self) -> bool {
+		self.silence() >= self.idle_time
+	}
+
+	/// Record activity on the line.
+	///
+	/// You only need to call this if you read data from the port without using [`Self::read()`].
+	pub fn record_activity(&mut self) {
+		self.last_activity = self.clock.now();
+		self.reported = false;
+	}
+
+	/// Read from the serial port until data is received or the line becomes idle.
+	///
+	/// If the line becomes idle before data is received, this returns [`IdleEvent::Idle`].
+	/// Each idle period is reported only once:
+	/// after the line became idle, this function behaves like a normal [`SerialPort::read()`] until data is received again.
+	///
+	/// This function temporarily changes the read timeout of the serial port.
+	/// The original read timeout is restored before the function returns.
+	pub fn read(&mut self, port: &mut SerialPort, buf: &mut [u8]) -> std::io::Result<IdleEvent> {
+		let result = if self.reported {
+			port.read(buf)
+		} else {
+			let original_timeout = port.get_read_timeout()?;
+			let result = self.read_until_idle(port, buf);
+			port.set_read_timeout(original_timeout)?;
+			match result? {
+				Some(read) => Ok(read),
+				None => {
+					self.reported = true;
+					return Ok(IdleEvent::Idle);
+				},
+			}
+		};
+
+		let read = result?;
+		if read > 0 {
+			self.record_activity();
+		}
+		Ok(IdleEvent::Data(read))
+	}
+
+	/// Read from the serial port until data is received or the line becomes idle.
+	///
+	/// Returns `None` if the line became idle.
+	fn read_until_idle(&self, port: &mut SerialPort, buf: &mut [u8]) -> std::io::Result<Option<usize>> {
+		loop {
+			let remaining = self.idle_time.saturating_sub(self.silence());
+			if remaining.is_zero() {
+				return Ok(None);
+			}
+			port.set_read_timeout(remaining)?;
+			match port.read(buf) {
+				// The timeout may have been truncated by the platform, so check the remaining time again.
+				// A manual clock does not advance while waiting, so checking again would never end.
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut && !self.clock.is_manual() => continue,
+				result => return result.map(Some),
+			}
+		}
+	}
+}
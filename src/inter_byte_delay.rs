@@ -0,0 +1,113 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// A pause inserted between transmitted bytes.
+///
+/// Some devices can not keep up with back-to-back characters,
+/// for example old PLCs or receivers that sample the line in software.
+/// An inter-byte delay makes the serial port wait after every byte, or after every group of bytes,
+/// before transmitting more data.
+///
+/// Use [`SerialPort::set_inter_byte_delay()`][crate::SerialPort::set_inter_byte_delay] to enable it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InterByteDelay {
+	/// The pause after each group of bytes.
+	delay: Duration,
+
+	/// The number of bytes transmitted back-to-back before pausing.
+	group_size: usize,
+}
+
+impl InterByteDelay {
+	/// Pause for the given duration between every transmitted byte.
+	pub fn new(delay: Duration) -> Self {
+		Self::after_every(1, delay)
+	}
+
+	/// Pause for the given duration after every `group_size` transmitted bytes.
+	///
+	/// # Panics
+	/// This function panics if `group_size` is zero.
+	pub fn after_every(group_size: usize, delay: Duration) -> Self {
+		assert!(group_size > 0, "group size must be at least 1");
+		Self { delay, group_size }
+	}
+
+	/// Get the duration of the pause.
+	pub fn delay(&self) -> Duration {
+		self.delay
+	}
+
+	/// Get the number of bytes transmitted back-to-back before pausing.
+	pub fn group_size(&self) -> usize {
+		self.group_size
+	}
+}
+
+/// The inter-byte delay configuration of a serial port and the state of the current group.
+#[derive(Default)]
+pub(crate) struct TxDelay {
+	/// The state, also locked for the duration of a delayed write to keep groups intact.
+	state: Mutex<TxDelayState>,
+}
+
+/// The mutable state of [`TxDelay`].
+#[derive(Default)]
+pub(crate) struct TxDelayState {
+	/// The configured delay, if any.
+	config: Option<InterByteDelay>,
+
+	/// The number of bytes written in the current group.
+	group_filled: usize,
+
+	/// The time the last complete group finished transmitting.
+	group_end: Option<Instant>,
+}
+
+impl TxDelay {
+	/// Lock the state, ignoring poisoning.
+	pub(crate) fn lock(&self) -> MutexGuard<'_, TxDelayState> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+impl TxDelayState {
+	/// Get the configured delay.
+	pub(crate) fn config(&self) -> Option<InterByteDelay> {
+		self.config
+	}
+
+	/// Set the configured delay and start a new group.
+	pub(crate) fn set_config(&mut self, config: Option<InterByteDelay>) {
+		self.config = config;
+		self.group_filled = 0;
+		self.group_end = None;
+	}
+
+	/// Wait for the pause after the previous group if needed, and get the number of bytes that may be written now.
+	pub(crate) fn begin_write(&mut self, config: &InterByteDelay) -> usize {
+		if self.group_filled >= config.group_size {
+			if let Some(group_end) = self.group_end {
+				let elapsed = group_end.elapsed();
+				if elapsed < config.delay {
+					std::thread::sleep(config.delay - elapsed);
+				}
+			}
+			self.group_filled = 0;
+		}
+		config.group_size - self.group_filled
+	}
+
+	/// Record written bytes, and return true if the group is now complete.
+	///
+	/// If the group is complete, the caller must wait for the data to be transmitted and then call [`Self::end_group()`].
+	pub(crate) fn add_written(&mut self, config: &InterByteDelay, written: usize) -> bool {
+		self.group_filled += written;
+		self.group_filled >= config.group_size
+	}
+
+	/// Record the time at which the current group finished transmitting.
+	pub(crate) fn end_group(&mut self) {
+		self.group_end = Some(Instant::now());
+	}
+}
@@ -0,0 +1,334 @@
+//! Detection of other programs that may access a serial port on Linux.
+//!
+//! On many Linux distributions, ModemManager probes every newly connected serial device with AT commands,
+//! and a `getty` may be configured to run a login prompt on a serial port.
+//! Both send and receive data on the port behind the back of the application,
+//! which shows up as unexplained garbage bytes or missing responses.
+//!
+//! An [`InterferenceGuard`] checks for these programs and for UUCP style lock files held by other processes.
+//! It can also install a udev hint for the duration of the session that tells ModemManager to ignore the device.
+//!
+//! On other platforms, the checks never find anything.
+//!
+//! # Example
+//! ```no_run
+//! # use serial2::interference::InterferenceGuard;
+//! # fn foo() -> std::io::Result<()> {
+//! let mut guard = InterferenceGuard::check("/dev/ttyUSB0")?;
+//! if let Some(diagnostic) = guard.diagnostic() {
+//!     eprintln!("{diagnostic}");
+//!     guard.set_ignore_hints()?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+
+/// The directories that may contain UUCP style lock files.
+#[cfg(target_os = "linux")]
+const LOCK_DIRS: &[&str] = &["/run/lock", "/var/lock"];
+
+/// The directory for runtime udev rules, which is cleared on reboot.
+#[cfg(target_os = "linux")]
+const UDEV_RUNTIME_RULES_DIR: &str = "/run/udev/rules.d";
+
+/// The program names of common `getty` implementations.
+#[cfg(target_os = "linux")]
+const GETTY_NAMES: &[&str] = &["agetty", "getty", "mgetty", "mingetty", "serial-getty"];
+
+/// A program that may access the serial port at the same time as the application.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Interference {
+	/// ModemManager is running and the device is not marked to be ignored by it.
+	///
+	/// ModemManager probes new serial devices by sending AT commands.
+	ModemManager {
+		/// The process ID of ModemManager.
+		pid: u32,
+	},
+
+	/// A `getty` is running on the device.
+	Getty {
+		/// The process ID of the `getty`.
+		pid: u32,
+
+		/// The program name of the `getty`.
+		program: String,
+	},
+
+	/// Another process holds a lock file for the device.
+	LockFile {
+		/// The path of the lock file.
+		path: PathBuf,
+
+		/// The process ID stored in the lock file, if it could be parsed.
+		pid: Option<u32>,
+	},
+}
+
+impl std::fmt::Display for Interference {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::ModemManager { pid } => write!(
+				f,
+				"ModemManager (pid {pid}) may probe the device: \
+				set the udev property ID_MM_DEVICE_IGNORE=1 for the device or stop ModemManager"
+			),
+			Self::Getty { pid, program } => write!(
+				f,
+				"{program} (pid {pid}) is running on the device: disable the serial-getty service for the device"
+			),
+			Self::LockFile { path, pid: Some(pid) } => {
+				write!(f, "the device is locked by process {pid} (lock file {})", path.display())
+			},
+			Self::LockFile { path, pid: None } => {
+				write!(f, "the device is locked by another process (lock file {})", path.display())
+			},
+		}
+	}
+}
+
+/// Detects programs that may interfere with a serial port.
+///
+/// See the [module documentation][self] for more information.
+///
+/// The udev hint installed by [`Self::set_ignore_hints()`] is removed again when the guard is dropped.
+#[derive(Debug)]
+pub struct InterferenceGuard {
+	/// The resolved path of the device.
+	device: PathBuf,
+
+	/// The detected interference.
+	found: Vec<Interference>,
+
+	/// The path of the installed udev rule, if any.
+	hint: Option<PathBuf>,
+}
+
+impl InterferenceGuard {
+	/// Check a serial port for programs that may interfere with it.
+	///
+	/// Symbolic links like `/dev/serial/by-id/...` are resolved to the actual device.
+	pub fn check(path: impl AsRef<Path>) -> std::io::Result<Self> {
+		let device = std::fs::canonicalize(path)?;
+		let found = detect(&device)?;
+		Ok(Self {
+			device,
+			found,
+			hint: None,
+		})
+	}
+
+	/// Get the resolved path of the device.
+	pub fn device(&self) -> &Path {
+		&self.device
+	}
+
+	/// Get the detected interference.
+	pub fn interference(&self) -> &[Interference] {
+		&self.found
+	}
+
+	/// Check if no interference was detected.
+	pub fn is_clear(&self) -> bool {
+		self.found.is_empty()
+	}
+
+	/// Check for interference again.
+	pub fn recheck(&mut self) -> std::io::Result<&[Interference]> {
+		self.found = detect(&self.device)?;
+		Ok(&self.found)
+	}
+
+	/// Get a human readable description of the detected interference and how to avoid it.
+	///
+	/// Returns `None` if no interference was detected.
+	pub fn diagnostic(&self) -> Option<String> {
+		if self.found.is_empty() {
+			return None;
+		}
+		let mut message = format!("other programs may access {}:", self.device.display());
+		for interference in &self.found {
+			message.push_str("\n  - ");
+			message.push_str(&interference.to_string());
+		}
+		Some(message)
+	}
+
+	/// Install a udev hint that tells ModemManager to ignore the device until the guard is dropped.
+	///
+	/// This writes a runtime udev rule that sets `ID_MM_DEVICE_IGNORE=1` for the device.
+	/// Writing the rule requires root privileges.
+	/// The rule is applied to udev events that happen after it is written,
+	/// so it prevents probing when the device is reconnected, but does not stop a probe that is already running.
+	///
+	/// This does nothing to stop a `getty` or to release lock files.
+	/// On platforms other than Linux, this returns an error of the type [`std::io::ErrorKind::Unsupported`].
+	pub fn set_ignore_hints(&mut self) -> std::io::Result<()> {
+		#[cfg(target_os = "linux")]
+		{
+			if self.hint.is_some() {
+				return Ok(());
+			}
+			let name = device_name(&self.device)?;
+			let rule_path = Path::new(UDEV_RUNTIME_RULES_DIR).join(format!("99-serial2-{name}.rules"));
+			std::fs::create_dir_all(UDEV_RUNTIME_RULES_DIR)?;
+			let rule = format!(
+				"# Installed by serial2 for the duration of a session.\n\
+				KERNEL==\"{name}\", ENV{{ID_MM_DEVICE_IGNORE}}=\"1\"\n"
+			);
+			std::fs::write(&rule_path, rule)?;
+			self.hint = Some(rule_path);
+			Ok(())
+		}
+		#[cfg(not(target_os = "linux"))]
+		{
+			Err(std::io::Error::new(
+				std::io::ErrorKind::Unsupported,
+				"udev hints are only supported on Linux",
+			))
+		}
+	}
+
+	/// Remove the udev hint installed by [`Self::set_ignore_hints()`], and report errors.
+	///
+	/// Dropping the guard does the same, but ignores errors.
+	pub fn clear_ignore_hints(&mut self) -> std::io::Result<()> {
+		match self.hint.take() {
+			Some(rule_path) => std::fs::remove_file(rule_path),
+			None => Ok(()),
+		}
+	}
+
+	/// Check if a udev hint is installed by this guard.
+	pub fn has_ignore_hints(&self) -> bool {
+		self.hint.is_some()
+	}
+}
+
+impl Drop for InterferenceGuard {
+	fn drop(&mut self) {
+		let _ = self.clear_ignore_hints();
+	}
+}
+
+/// Detect programs that may interfere with a device.
+#[cfg(target_os = "linux")]
+fn detect(device: &Path) -> std::io::Result<Vec<Interference>> {
+	let name = device_name(device)?;
+	let mut found = Vec::new();
+
+	let own_pid = std::process::id();
+	let ignored_by_mm = udev_property(device, "ID_MM_DEVICE_IGNORE").as_deref() == Some("1");
+	for (pid, program) in processes() {
+		if pid == own_pid {
+			continue;
+		}
+		if program == "ModemManager" {
+			if !ignored_by_mm {
+				found.push(Interference::ModemManager { pid });
+			}
+		} else if GETTY_NAMES.contains(&program.as_str()) && process_uses_device(pid, device, name) {
+			found.push(Interference::Getty { pid, program });
+		}
+	}
+
+	let mut seen_locks = Vec::new();
+	for dir in LOCK_DIRS {
+		let path = Path::new(dir).join(format!("LCK..{name}"));
+		let Ok(data) = std::fs::read(&path) else {
+			continue;
+		};
+		// /var/lock is usually a symlink to /run/lock, so don't report the same file twice.
+		let resolved = std::fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+		if seen_locks.contains(&resolved) {
+			continue;
+		}
+		seen_locks.push(resolved);
+		let pid = parse_lock_file(&data);
+		// Skip our own locks and stale locks of processes that no longer exist.
+		if pid == Some(own_pid) || pid.is_some_and(|pid| !Path::new(&format!("/proc/{pid}")).exists()) {
+			continue;
+		}
+		found.push(Interference::LockFile { path, pid });
+	}
+
+	Ok(found)
+}
+
+/// Detect programs that may interfere with a device.
+#[cfg(not(target_os = "linux"))]
+fn detect(_device: &Path) -> std::io::Result<Vec<Interference>> {
+	Ok(Vec::new())
+}
+
+/// Get the file name of a device as a string.
+#[cfg(target_os = "linux")]
+fn device_name(device: &Path) -> std::io::Result<&str> {
+	device
+		.file_name()
+		.and_then(|name| name.to_str())
+		.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid device path"))
+}
+
+/// Get the process ID and program name of all running processes.
+#[cfg(target_os = "linux")]
+fn processes() -> Vec<(u32, String)> {
+	let Ok(dir) = std::fs::read_dir("/proc") else {
+		return Vec::new();
+	};
+	let mut processes = Vec::new();
+	for entry in dir.flatten() {
+		let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse().ok()) else {
+			continue;
+		};
+		// Processes may exit while we look at them.
+		let Ok(comm) = std::fs::read_to_string(entry.path().join("comm")) else {
+			continue;
+		};
+		processes.push((pid, comm.trim_end().to_owned()));
+	}
+	processes
+}
+
+/// Check if a process was started for a device, or has it open.
+#[cfg(target_os = "linux")]
+fn process_uses_device(pid: u32, device: &Path, name: &str) -> bool {
+	if let Ok(cmdline) = std::fs::read(format!("/proc/{pid}/cmdline")) {
+		let device = device.as_os_str().as_encoded_bytes();
+		if cmdline.split(|&byte| byte == 0).any(|arg| arg == name.as_bytes() || arg == device) {
+			return true;
+		}
+	}
+	let Ok(fds) = std::fs::read_dir(format!("/proc/{pid}/fd")) else {
+		return false;
+	};
+	fds.flatten().any(|fd| std::fs::read_link(fd.path()).is_ok_and(|target| target == device))
+}
+
+/// Read a property of a device from the udev database.
+#[cfg(target_os = "linux")]
+fn udev_property(device: &Path, key: &str) -> Option<String> {
+	use std::os::unix::fs::MetadataExt;
+	let rdev = device.metadata().ok()?.rdev();
+	let path = format!("/run/udev/data/c{}:{}", libc::major(rdev), libc::minor(rdev));
+	let data = std::fs::read_to_string(path).ok()?;
+	data.lines()
+		.filter_map(|line| line.strip_prefix("E:")?.split_once('='))
+		.find(|(name, _)| *name == key)
+		.map(|(_, value)| value.to_owned())
+}
+
+/// Parse the process ID from a lock file.
+///
+/// Lock files contain the process ID as ASCII text, or as a native 4 byte integer in the old binary format.
+#[cfg(target_os = "linux")]
+fn parse_lock_file(data: &[u8]) -> Option<u32> {
+	if let Some(pid) = std::str::from_utf8(data).ok().and_then(|text| text.trim().parse().ok()) {
+		return Some(pid);
+	}
+	let bytes: [u8; 4] = data.try_into().ok()?;
+	Some(u32::from_ne_bytes(bytes))
+}
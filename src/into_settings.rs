@@ -37,6 +37,8 @@ impl IntoSettings for u32 {
 /// A serial port "configuration" that simply keeps all existing settings.
 ///
 /// You can pass this to [`SerialPort::open()`][crate::SerialPort::open()] to prevent it from changing any port settings.
+/// The configuration of the port is not written at all in that case,
+/// so this can be used to attach to a port that was already configured by another process, like a console managed by `getty`.
 ///
 /// Note: many platforms reset the configuration of a serial port when it is no longer in use.
 /// You should normally explicitly configure the settings that you care about.
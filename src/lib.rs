@@ -60,16 +60,93 @@
 
 mod sys;
 
+mod byte_transform;
+pub use byte_transform::ByteTransform;
+
+mod control;
+pub use control::{ControlHandle, IoHandle};
+
+mod flow_control;
+pub use flow_control::FlowControlStall;
+
+mod handles;
+pub use handles::{ReadHalf, Reader, WriteHalf, Writer};
+
+mod inter_byte_delay;
+pub use inter_byte_delay::InterByteDelay;
+
 mod into_settings;
 pub use into_settings::{IntoSettings, KeepSettings};
 
+mod modem_lines;
+pub use modem_lines::ModemLines;
+
+mod noise_filter;
+pub use noise_filter::NoiseFilter;
+
+mod open_options;
+pub use open_options::OpenOptions;
+
+#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+mod port_state;
+#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "unix")))]
+pub use port_state::PortState;
+
+mod priority;
+pub use priority::Priority;
+
+mod profile;
+
 mod serial_port;
 pub use serial_port::SerialPort;
 
 mod settings;
 pub use settings::{CharSize, FlowControl, Parity, Settings, StopBits, TryFromError, COMMON_BAUD_RATES};
 
+mod software_parity;
+pub use software_parity::SoftwareParity;
+
+mod thread_options;
+pub use thread_options::{RealtimeGuard, ThreadOptions, ThreadPriority};
+
+pub mod autobaud;
+pub mod bridge;
+pub mod buffered;
+pub mod bus;
+pub mod capture;
+pub mod clock;
+pub mod demux;
+pub mod drift;
+pub mod framing;
+#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "unix")))]
+pub mod group;
+pub mod idle;
+pub mod interference;
+pub mod link_test;
+pub mod log_split;
+pub mod mux;
 pub mod os;
+pub mod ppp;
+pub mod protocol;
+pub mod reconnect;
+pub mod retry;
+pub mod router;
+pub mod shutdown;
+pub mod sim;
+pub mod sniff;
+pub mod terminal;
+pub mod transaction;
+pub mod transcode;
+pub mod write_queue;
+
+#[cfg(any(feature = "doc", feature = "ffi"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "ffi")))]
+pub mod ffi;
+
+#[cfg(fuzzing)]
+pub mod fuzz;
 
 #[cfg(any(feature = "doc", feature = "rs4xx"))]
 #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "rs4xx")))]
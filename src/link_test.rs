@@ -0,0 +1,279 @@
+//! Throughput and latency measurements against an echoing peer.
+//!
+//! When commissioning a new installation, it is useful to know what a link can actually do.
+//! [`SerialPort::measure_link()`] sends test traffic to a peer that echoes everything back
+//! (or to a loopback plug that connects TX to RX) and reports the results in a [`LinkReport`].
+//!
+//! The measurement runs in two phases:
+//! first a series of small probes is sent one at a time to measure the round trip latency,
+//! then a continuous stream of data is sent and read back concurrently to measure the effective throughput.
+
+use std::time::{Duration, Instant};
+
+use crate::SerialPort;
+
+/// The length of the pattern used for the throughput test.
+///
+/// This is a prime number smaller than 256, so that a byte of the pattern identifies its position modulo the length.
+const PATTERN_LEN: u64 = 251;
+
+/// The size of the chunks written during the throughput test.
+const CHUNK_SIZE: usize = 256;
+
+/// The results of a link measurement.
+#[derive(Debug, Clone)]
+pub struct LinkReport {
+	/// The total time spent on the measurement.
+	elapsed: Duration,
+
+	/// The number of bytes sent during the throughput test.
+	bytes_sent: u64,
+
+	/// The number of bytes received during the throughput test.
+	bytes_received: u64,
+
+	/// The duration of the throughput test.
+	throughput_elapsed: Duration,
+
+	/// The round trip times of the latency probes that were echoed correctly, sorted from fast to slow.
+	round_trips: Vec<Duration>,
+
+	/// The number of latency probes that were not echoed before the read timeout.
+	probes_lost: u64,
+
+	/// The number of latency probes that were echoed with different contents.
+	probes_corrupted: u64,
+
+	/// The number of discontinuities seen in the echoed data of the throughput test.
+	stream_errors: u64,
+
+	/// The estimated number of bytes that were lost during the throughput test.
+	bytes_lost: u64,
+}
+
+impl LinkReport {
+	/// Get the total time spent on the measurement.
+	pub fn elapsed(&self) -> Duration {
+		self.elapsed
+	}
+
+	/// Get the number of bytes sent during the throughput test.
+	pub fn bytes_sent(&self) -> u64 {
+		self.bytes_sent
+	}
+
+	/// Get the number of bytes received back during the throughput test.
+	pub fn bytes_received(&self) -> u64 {
+		self.bytes_received
+	}
+
+	/// Get the effective throughput in bytes per second.
+	///
+	/// This is the number of bytes that made the round trip, divided by the duration of the throughput test.
+	/// Since every byte travels in both directions, this is the throughput of the link in each direction.
+	pub fn bytes_per_second(&self) -> f64 {
+		let seconds = self.throughput_elapsed.as_secs_f64();
+		if seconds > 0.0 {
+			self.bytes_received as f64 / seconds
+		} else {
+			0.0
+		}
+	}
+
+	/// Get the round trip times of all latency probes that were echoed correctly, sorted from fast to slow.
+	pub fn round_trips(&self) -> &[Duration] {
+		&self.round_trips
+	}
+
+	/// Get a percentile of the round trip time of the latency probes.
+	///
+	/// The percentile is clamped to the range 0 to 100.
+	/// Returns `None` if no latency probe was echoed correctly.
+	pub fn round_trip_percentile(&self, percentile: f64) -> Option<Duration> {
+		let last = self.round_trips.len().checked_sub(1)?;
+		let index = (percentile.clamp(0.0, 100.0) / 100.0 * last as f64).round() as usize;
+		self.round_trips.get(index).copied()
+	}
+
+	/// Get the number of latency probes that were not echoed before the read timeout expired.
+	pub fn probes_lost(&self) -> u64 {
+		self.probes_lost
+	}
+
+	/// Get the number of latency probes that were echoed with different contents.
+	pub fn probes_corrupted(&self) -> u64 {
+		self.probes_corrupted
+	}
+
+	/// Get the number of discontinuities in the echoed data of the throughput test.
+	///
+	/// Each dropped, inserted or corrupted sequence of bytes counts as one error.
+	pub fn stream_errors(&self) -> u64 {
+		self.stream_errors
+	}
+
+	/// Get the estimated number of bytes that were lost during the throughput test.
+	///
+	/// This includes bytes that were not echoed before the read timeout expired at the end of the test.
+	/// A corrupted byte can also show up here, since it looks like a gap in the data.
+	pub fn bytes_lost(&self) -> u64 {
+		self.bytes_lost
+	}
+
+	/// Get the total number of errors seen during the measurement.
+	///
+	/// This is the sum of the lost and corrupted latency probes and the stream errors.
+	pub fn errors(&self) -> u64 {
+		self.probes_lost + self.probes_corrupted + self.stream_errors
+	}
+}
+
+/// Get the byte of the test pattern at a position in the stream.
+fn pattern(index: u64) -> u8 {
+	(index % PATTERN_LEN) as u8
+}
+
+/// Checks the echoed data of the throughput test against the test pattern.
+#[derive(Default)]
+struct StreamChecker {
+	/// The position in the stream of the next expected byte.
+	next: u64,
+
+	/// The number of bytes received.
+	received: u64,
+
+	/// The number of discontinuities seen.
+	errors: u64,
+
+	/// The estimated number of bytes lost.
+	lost: u64,
+}
+
+impl StreamChecker {
+	/// Check a chunk of received data.
+	fn check(&mut self, data: &[u8]) {
+		for &byte in data {
+			self.received += 1;
+			if byte == pattern(self.next) {
+				self.next += 1;
+				continue;
+			}
+			self.errors += 1;
+			if u64::from(byte) < PATTERN_LEN {
+				// Assume bytes were dropped and skip ahead to the next position with this value.
+				let skipped = (u64::from(byte) + PATTERN_LEN - self.next % PATTERN_LEN) % PATTERN_LEN;
+				self.lost += skipped;
+				self.next += skipped + 1;
+			} else {
+				// The byte can not be part of the pattern, so it must be corrupted.
+				self.next += 1;
+			}
+		}
+	}
+}
+
+/// Run a link measurement.
+pub(crate) fn measure(port: &SerialPort, duration: Duration) -> std::io::Result<LinkReport> {
+	let start = Instant::now();
+	let mut report = LinkReport {
+		elapsed: Duration::ZERO,
+		bytes_sent: 0,
+		bytes_received: 0,
+		throughput_elapsed: Duration::ZERO,
+		round_trips: Vec::new(),
+		probes_lost: 0,
+		probes_corrupted: 0,
+		stream_errors: 0,
+		bytes_lost: 0,
+	};
+
+	port.discard_input_buffer()?;
+	measure_latency(port, start + duration / 4, &mut report)?;
+	measure_throughput(port, start + duration, &mut report)?;
+	report.round_trips.sort();
+	report.elapsed = start.elapsed();
+	Ok(report)
+}
+
+/// Send latency probes one at a time until the deadline expires.
+///
+/// At least one probe is always sent.
+fn measure_latency(port: &SerialPort, deadline: Instant, report: &mut LinkReport) -> std::io::Result<()> {
+	let mut sequence: u64 = 0;
+	loop {
+		let probe = sequence.to_le_bytes();
+		let mut echo = [0; 8];
+		let sent = Instant::now();
+		port.write_all(&probe)?;
+		match port.read_exact(&mut echo) {
+			Ok(()) if echo == probe => report.round_trips.push(sent.elapsed()),
+			Ok(()) => {
+				report.probes_corrupted += 1;
+				port.discard_input_buffer()?;
+			},
+			Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+				report.probes_lost += 1;
+				port.discard_input_buffer()?;
+			},
+			Err(e) => return Err(e),
+		}
+		sequence += 1;
+		if Instant::now() >= deadline {
+			return Ok(());
+		}
+	}
+}
+
+/// Stream the test pattern until the deadline expires while reading back the echoed data.
+///
+/// After the deadline, the remaining echoed data is read until it is complete or until the read timeout expires.
+fn measure_throughput(port: &SerialPort, deadline: Instant, report: &mut LinkReport) -> std::io::Result<()> {
+	let start = Instant::now();
+	let mut checker = StreamChecker::default();
+	let sent = std::thread::scope(|scope| {
+		let mut writer = Some(std::thread::Builder::new()
+			.name("serial2-link-test".into())
+			.spawn_scoped(scope, || -> std::io::Result<u64> {
+				let mut sent: u64 = 0;
+				let mut chunk = [0; CHUNK_SIZE];
+				while Instant::now() < deadline {
+					for (i, byte) in chunk.iter_mut().enumerate() {
+						*byte = pattern(sent + i as u64);
+					}
+					port.write_all(&chunk)?;
+					sent += CHUNK_SIZE as u64;
+				}
+				Ok(sent)
+			})?);
+
+		let mut sent = None;
+		let mut buffer = [0; 4096];
+		loop {
+			if writer.as_ref().is_some_and(|writer| writer.is_finished()) {
+				if let Some(writer) = writer.take() {
+					sent = Some(writer.join().unwrap_or_else(|e| std::panic::resume_unwind(e))?);
+				}
+			}
+			if sent.is_some_and(|sent| checker.next >= sent) {
+				break;
+			}
+			match port.read(&mut buffer) {
+				Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+				Ok(read) => checker.check(&buffer[..read]),
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut && sent.is_some() => break,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => (),
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+				Err(e) => return Err(e),
+			}
+		}
+		// The loop only exits normally after the writer was joined.
+		Ok(sent.unwrap_or_default())
+	})?;
+
+	report.throughput_elapsed = start.elapsed();
+	report.bytes_sent = sent;
+	report.bytes_received = checker.received;
+	report.stream_errors = checker.errors;
+	report.bytes_lost = checker.lost + sent.saturating_sub(checker.next);
+	Ok(())
+}
@@ -0,0 +1,363 @@
+//! Separation of console text and binary frames received on the same serial port.
+//!
+//! During bring-up, embedded devices often interleave binary telemetry with ASCII debug prints on one UART.
+//! A [`LogSplitter`] classifies the received data and splits it into text lines and binary frames.
+//!
+//! The classification uses a simple heuristic:
+//! a run of printable ASCII characters that ends with a newline and is at least [`LogSplitter::set_min_text_len()`] bytes long
+//! is a line of text.
+//! Everything else is passed to a [`Framer`] to decode a binary frame.
+//! Data that is neither text nor a valid frame is discarded one byte at a time.
+//!
+//! A [`LogSplitter`] does not do any I/O by itself.
+//! Use [`SplitStreams`] to read from a serial port in a background thread and route text and frames to separate channels.
+
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::framing::Framer;
+use crate::shutdown::Shutdown;
+use crate::SerialPort;
+
+/// The read timeout used by the background thread.
+///
+/// This limits how long it takes for the background thread to notice that it was dropped.
+/// It is also the time after which an unterminated line of text is emitted.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A piece of classified data.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Segment<Frame> {
+	/// A line of text, without the line terminator.
+	Text(String),
+
+	/// A binary frame.
+	Frame(Frame),
+}
+
+/// Splits received data into text lines and binary frames.
+///
+/// See the [module documentation][self] for more information.
+pub struct LogSplitter<F> {
+	/// The framer used to decode binary frames.
+	framer: F,
+
+	/// Received data that has not been classified yet.
+	buffer: Vec<u8>,
+
+	/// The minimum length of a line of text, including the line terminator.
+	min_text_len: usize,
+
+	/// The maximum length of a line of text before it is split.
+	max_line_len: usize,
+
+	/// The number of bytes that were neither text nor part of a valid frame.
+	discarded_bytes: u64,
+}
+
+impl<F: Framer> LogSplitter<F> {
+	/// Create a new splitter that uses the given framer to decode binary frames.
+	///
+	/// By default, lines of text must be at least 4 bytes long and are split after 1024 bytes.
+	pub fn new(framer: F) -> Self {
+		Self {
+			framer,
+			buffer: Vec::new(),
+			min_text_len: 4,
+			max_line_len: 1024,
+			discarded_bytes: 0,
+		}
+	}
+
+	/// Set the minimum length of a line of text, including the line terminator.
+	///
+	/// Shorter printable runs are passed to the framer instead.
+	/// Increase this if binary frames often start with a few printable bytes followed by a newline byte.
+	pub fn set_min_text_len(&mut self, len: usize) {
+		self.min_text_len = len.max(1);
+	}
+
+	/// Get the minimum length of a line of text, including the line terminator.
+	pub fn get_min_text_len(&self) -> usize {
+		self.min_text_len
+	}
+
+	/// Set the maximum length of a line of text.
+	///
+	/// Longer runs of printable characters are split into multiple lines.
+	pub fn set_max_line_len(&mut self, len: usize) {
+		self.max_line_len = len.max(1);
+	}
+
+	/// Get the maximum length of a line of text.
+	pub fn get_max_line_len(&self) -> usize {
+		self.max_line_len
+	}
+
+	/// Get a reference to the framer.
+	pub fn framer(&self) -> &F {
+		&self.framer
+	}
+
+	/// Get the data that has not been classified yet.
+	pub fn buffered(&self) -> &[u8] {
+		&self.buffer
+	}
+
+	/// Get the number of bytes that were neither text nor part of a valid frame.
+	pub fn discarded_bytes(&self) -> u64 {
+		self.discarded_bytes
+	}
+
+	/// Add received data and return the text lines and frames that are complete.
+	pub fn push(&mut self, data: &[u8]) -> Vec<Segment<F::Frame>> {
+		self.buffer.extend_from_slice(data);
+		let mut segments = Vec::new();
+		while let Some(segment) = self.next_segment() {
+			segments.push(segment);
+		}
+		segments
+	}
+
+	/// Take unterminated text from the buffer.
+	///
+	/// If all buffered data is printable, it is removed from the buffer and returned as a line of text.
+	/// Use this when the line becomes idle, to get prompts that do not end with a newline.
+	pub fn flush_text(&mut self) -> Option<String> {
+		if self.buffer.is_empty() || !self.buffer.iter().all(|&byte| is_printable(byte)) {
+			return None;
+		}
+		let text = trim_line_end(&self.buffer);
+		let text = String::from_utf8_lossy(text).into_owned();
+		self.buffer.clear();
+		Some(text)
+	}
+
+	/// Classify the data at the start of the buffer.
+	///
+	/// Returns `None` if more data is needed.
+	fn next_segment(&mut self) -> Option<Segment<F::Frame>> {
+		loop {
+			if self.buffer.is_empty() {
+				return None;
+			}
+
+			let printable = self.buffer.iter().take_while(|&&byte| is_printable(byte)).count();
+			let line_end = self.buffer[..printable].iter().position(|&byte| byte == b'\n').map(|i| i + 1);
+			let text_len = match line_end {
+				Some(end) if end >= self.min_text_len => Some(end.min(self.max_line_len)),
+				_ if printable >= self.max_line_len => Some(self.max_line_len),
+				// A long printable run interrupted by binary data is text too.
+				None if printable < self.buffer.len() && printable >= self.min_text_len => Some(printable),
+				_ => None,
+			};
+			if let Some(len) = text_len {
+				let text = String::from_utf8_lossy(trim_line_end(&self.buffer[..len])).into_owned();
+				self.buffer.drain(..len);
+				return Some(Segment::Text(text));
+			}
+
+			match self.framer.decode(&self.buffer) {
+				Ok(Some((frame, len))) => {
+					self.buffer.drain(..len.min(self.buffer.len()));
+					return Some(Segment::Frame(frame));
+				},
+				Ok(None) => return None,
+				Err(_) => {
+					self.buffer.remove(0);
+					self.discarded_bytes += 1;
+				},
+			}
+		}
+	}
+}
+
+/// Routes text lines and binary frames from a serial port to separate channels.
+///
+/// The data is read and classified by a background thread.
+/// Unterminated text is emitted as a line when no data is received for 100 milliseconds.
+///
+/// Dropping the streams stops the background thread.
+pub struct SplitStreams<Frame> {
+	/// The state shared with the background thread.
+	shared: Arc<Shared<Frame>>,
+
+	/// The background thread.
+	thread: Option<JoinHandle<()>>,
+}
+
+impl<Frame: Send + 'static> SplitStreams<Frame> {
+	/// Start reading from a serial port and splitting the data with a splitter.
+	///
+	/// The read timeout of the serial port is changed for use by the background thread.
+	pub fn spawn<F>(mut port: SerialPort, splitter: LogSplitter<F>) -> std::io::Result<Self>
+	where
+		F: Framer<Frame = Frame> + Send + 'static,
+	{
+		port.set_read_timeout(POLL_INTERVAL)?;
+		let shared = Arc::new(Shared {
+			state: Mutex::new(State {
+				text: None,
+				frames: None,
+				discarded_bytes: 0,
+				error: None,
+				closed: false,
+			}),
+		});
+		let thread = std::thread::Builder::new()
+			.name("serial2-log-split".into())
+			.spawn({
+				let shared = shared.clone();
+				move || shared.run(port, splitter)
+			})?;
+		Ok(Self {
+			shared,
+			thread: Some(thread),
+		})
+	}
+
+	/// Subscribe to the lines of text.
+	///
+	/// Any previous subscription for text is replaced.
+	/// Without a subscription, text is discarded.
+	pub fn subscribe_text(&self) -> Receiver<String> {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		self.shared.lock().text = Some(sender);
+		receiver
+	}
+
+	/// Subscribe to the binary frames.
+	///
+	/// Any previous subscription for frames is replaced.
+	/// Without a subscription, frames are discarded.
+	pub fn subscribe_frames(&self) -> Receiver<Frame> {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		self.shared.lock().frames = Some(sender);
+		receiver
+	}
+
+	/// Get the number of bytes that were neither text nor part of a valid frame.
+	pub fn discarded_bytes(&self) -> u64 {
+		self.shared.lock().discarded_bytes
+	}
+
+	/// Take the error that stopped the background thread, if any.
+	///
+	/// When the background thread stops, all subscription channels are disconnected.
+	pub fn take_error(&self) -> Option<std::io::Error> {
+		self.shared.lock().error.take()
+	}
+}
+
+impl<Frame> SplitStreams<Frame> {
+	/// Stop the background thread and wait for it to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		crate::shutdown::join_threads(self.thread.take(), deadline)
+	}
+}
+
+impl<Frame> Drop for SplitStreams<Frame> {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl<Frame> Shutdown for SplitStreams<Frame> {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// State shared between the streams and the background thread.
+struct Shared<Frame> {
+	/// The mutable state.
+	state: Mutex<State<Frame>>,
+}
+
+/// The mutable state of the streams.
+struct State<Frame> {
+	/// The subscription for text.
+	text: Option<Sender<String>>,
+
+	/// The subscription for frames.
+	frames: Option<Sender<Frame>>,
+
+	/// The number of bytes that were neither text nor part of a valid frame.
+	discarded_bytes: u64,
+
+	/// The error that stopped the background thread.
+	error: Option<std::io::Error>,
+
+	/// True if the streams are being dropped.
+	closed: bool,
+}
+
+impl<Frame> Shared<Frame> {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State<Frame>> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Run the background thread.
+	fn run<F>(&self, port: SerialPort, mut splitter: LogSplitter<F>)
+	where
+		F: Framer<Frame = Frame>,
+	{
+		let mut buffer = [0; 1024];
+		loop {
+			if self.lock().closed {
+				return;
+			}
+			let segments = match port.read(&mut buffer) {
+				Ok(0) => Err(std::io::ErrorKind::UnexpectedEof.into()),
+				Ok(read) => Ok(splitter.push(&buffer[..read])),
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+					Ok(splitter.flush_text().map(Segment::Text).into_iter().collect())
+				},
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => Err(e),
+			};
+
+			let mut state = self.lock();
+			state.discarded_bytes = splitter.discarded_bytes();
+			let segments = match segments {
+				Ok(segments) => segments,
+				Err(e) => {
+					state.error = Some(e);
+					state.text = None;
+					state.frames = None;
+					return;
+				},
+			};
+			for segment in segments {
+				match segment {
+					Segment::Text(text) => {
+						// Drop the subscription if the receiver is gone.
+						if state.text.as_ref().is_some_and(|sender| sender.send(text).is_err()) {
+							state.text = None;
+						}
+					},
+					Segment::Frame(frame) => {
+						if state.frames.as_ref().is_some_and(|sender| sender.send(frame).is_err()) {
+							state.frames = None;
+						}
+					},
+				}
+			}
+		}
+	}
+}
+
+/// Check if a byte is printable ASCII or common whitespace.
+fn is_printable(byte: u8) -> bool {
+	matches!(byte, b' '..=b'~' | b'\t' | b'\r' | b'\n')
+}
+
+/// Remove a trailing line terminator.
+fn trim_line_end(line: &[u8]) -> &[u8] {
+	let line = line.strip_suffix(b"\n").unwrap_or(line);
+	line.strip_suffix(b"\r").unwrap_or(line)
+}
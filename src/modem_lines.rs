@@ -0,0 +1,59 @@
+/// The state of the modem control lines of a serial port, read in one operation.
+///
+/// Returned by [`SerialPort::read_modem_lines()`][crate::SerialPort::read_modem_lines].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ModemLines {
+	/// The state of the Ready To Send line, if the platform reports it.
+	pub(crate) rts: Option<bool>,
+
+	/// The state of the Data Terminal Ready line, if the platform reports it.
+	pub(crate) dtr: Option<bool>,
+
+	/// The state of the Clear To Send line.
+	pub(crate) cts: bool,
+
+	/// The state of the Data Set Ready line.
+	pub(crate) dsr: bool,
+
+	/// The state of the Ring Indicator line.
+	pub(crate) ri: bool,
+
+	/// The state of the Carrier Detect line.
+	pub(crate) cd: bool,
+}
+
+impl ModemLines {
+	/// Get the state of the Ready To Send output line.
+	///
+	/// Returns `None` if the platform does not report the state of output lines, like on Windows.
+	pub fn rts(&self) -> Option<bool> {
+		self.rts
+	}
+
+	/// Get the state of the Data Terminal Ready output line.
+	///
+	/// Returns `None` if the platform does not report the state of output lines, like on Windows.
+	pub fn dtr(&self) -> Option<bool> {
+		self.dtr
+	}
+
+	/// Get the state of the Clear To Send line.
+	pub fn cts(&self) -> bool {
+		self.cts
+	}
+
+	/// Get the state of the Data Set Ready line.
+	pub fn dsr(&self) -> bool {
+		self.dsr
+	}
+
+	/// Get the state of the Ring Indicator line.
+	pub fn ri(&self) -> bool {
+		self.ri
+	}
+
+	/// Get the state of the Carrier Detect line.
+	pub fn cd(&self) -> bool {
+		self.cd
+	}
+}
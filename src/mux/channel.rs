@@ -0,0 +1,291 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Transmits data for logical channels over the physical link.
+pub(super) trait Link: Send + Sync + 'static {
+	/// Send data on a channel.
+	///
+	/// May send only part of the data, and returns the number of bytes sent.
+	fn send(&self, channel: u8, data: &[u8]) -> std::io::Result<usize>;
+
+	/// Notify the link that a channel handle was dropped.
+	fn release(&self, channel: u8) {
+		let _ = channel;
+	}
+}
+
+/// A logical channel of a multiplexed serial port.
+///
+/// A channel can be used like a serial port: it implements [`std::io::Read`] and [`std::io::Write`],
+/// and received data is buffered until it is read.
+///
+/// Read functions return end-of-file (`Ok(0)`) when the channel is closed,
+/// or when the multiplexer stopped reading from the serial port.
+pub struct MuxChannel {
+	/// The state shared with the multiplexer.
+	shared: Arc<Shared>,
+
+	/// The channel number.
+	channel: u8,
+
+	/// The maximum time to wait for data in read functions.
+	read_timeout: Duration,
+}
+
+impl MuxChannel {
+	/// Create a handle for a channel that has already been added to the shared state.
+	pub(super) fn new(shared: Arc<Shared>, channel: u8) -> Self {
+		Self {
+			shared,
+			channel,
+			read_timeout: Duration::from_secs(3),
+		}
+	}
+
+	/// Get the channel number.
+	pub fn channel(&self) -> u8 {
+		self.channel
+	}
+
+	/// Set the maximum time to wait for data in read functions.
+	pub fn set_read_timeout(&mut self, timeout: Duration) {
+		self.read_timeout = timeout;
+	}
+
+	/// Get the maximum time to wait for data in read functions.
+	pub fn get_read_timeout(&self) -> Duration {
+		self.read_timeout
+	}
+
+	/// Get the number of received bytes that have not been read yet.
+	pub fn available(&self) -> usize {
+		self.shared.lock().channels.get(&self.channel).map_or(0, |state| state.buffer.len())
+	}
+
+	/// Check if the channel is still open.
+	///
+	/// Data that was received before the channel was closed can still be read.
+	pub fn is_open(&self) -> bool {
+		let state = self.shared.lock();
+		!state.stopped && state.channels.get(&self.channel).is_some_and(|state| state.open)
+	}
+
+	/// Read bytes from the channel.
+	///
+	/// If no data is received before the read timeout expires,
+	/// an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		if buf.is_empty() {
+			return Ok(0);
+		}
+		let deadline = Instant::now() + self.read_timeout;
+		let mut state = self.shared.lock();
+		loop {
+			let stopped = state.stopped;
+			let Some(channel) = state.channels.get_mut(&self.channel) else { return Ok(0) };
+			if !channel.buffer.is_empty() {
+				let len = buf.len().min(channel.buffer.len());
+				for (dest, byte) in buf.iter_mut().zip(channel.buffer.drain(..len)) {
+					*dest = byte;
+				}
+				return Ok(len);
+			}
+			if stopped || !channel.open {
+				return Ok(0);
+			}
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return Err(std::io::ErrorKind::TimedOut.into());
+			}
+			state = self.shared.wait_timeout(state, remaining);
+		}
+	}
+
+	/// Write bytes to the channel.
+	///
+	/// Large writes may be split over multiple frames,
+	/// in which case only the bytes of the first frame are written.
+	/// Returns the number of bytes written.
+	///
+	/// If the channel is closed, an error of the type [`std::io::ErrorKind::BrokenPipe`] is returned.
+	pub fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
+		if !self.is_open() {
+			return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "the channel is closed"));
+		}
+		if buf.is_empty() {
+			return Ok(0);
+		}
+		self.shared.link.send(self.channel, buf)
+	}
+
+	/// Write all bytes to the channel.
+	///
+	/// This will repeatedly call [`Self::write()`] until the entire buffer has been written.
+	pub fn write_all(&self, buf: &[u8]) -> std::io::Result<()> {
+		let mut buf = buf;
+		while !buf.is_empty() {
+			match self.write(buf) {
+				Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "failed to write whole buffer")),
+				Ok(n) => buf = &buf[n..],
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(())
+	}
+}
+
+impl std::io::Read for MuxChannel {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		MuxChannel::read(self, buf)
+	}
+}
+
+impl std::io::Read for &'_ MuxChannel {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		MuxChannel::read(self, buf)
+	}
+}
+
+impl std::io::Write for MuxChannel {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		MuxChannel::write(self, buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+impl std::io::Write for &'_ MuxChannel {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		MuxChannel::write(self, buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+impl Drop for MuxChannel {
+	fn drop(&mut self) {
+		self.shared.lock().channels.remove(&self.channel);
+		self.shared.link.release(self.channel);
+	}
+}
+
+/// State shared between a multiplexer, its background thread and its channels.
+pub(super) struct Shared {
+	/// The link used to transmit data.
+	pub(super) link: Box<dyn Link>,
+
+	/// The mutable state.
+	state: Mutex<State>,
+
+	/// Condition variable signalled whenever data is received or a channel changes state.
+	condvar: Condvar,
+}
+
+/// The mutable state of a multiplexer.
+#[derive(Default)]
+pub(super) struct State {
+	/// The channels with a handle.
+	pub(super) channels: HashMap<u8, ChannelState>,
+
+	/// The number of times the framer reported invalid data.
+	pub(super) invalid_frames: u64,
+
+	/// The error that stopped the background thread.
+	pub(super) error: Option<std::io::Error>,
+
+	/// True if the background thread stopped.
+	pub(super) stopped: bool,
+
+	/// True if the multiplexer is being dropped.
+	pub(super) closed: bool,
+}
+
+/// The state of a single channel.
+pub(super) struct ChannelState {
+	/// Received data that has not been read yet.
+	buffer: VecDeque<u8>,
+
+	/// False if the channel was closed.
+	open: bool,
+}
+
+impl Shared {
+	/// Create the shared state for a link.
+	pub(super) fn new(link: impl Link) -> Self {
+		Self {
+			link: Box::new(link),
+			state: Mutex::new(State::default()),
+			condvar: Condvar::new(),
+		}
+	}
+
+	/// Lock the state, ignoring poisoning.
+	pub(super) fn lock(&self) -> MutexGuard<'_, State> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Wait for the state to change or for a timeout, ignoring poisoning.
+	pub(super) fn wait_timeout<'a>(&self, guard: MutexGuard<'a, State>, timeout: Duration) -> MutexGuard<'a, State> {
+		match self.condvar.wait_timeout(guard, timeout) {
+			Ok((guard, _timeout)) => guard,
+			Err(e) => e.into_inner().0,
+		}
+	}
+
+	/// Register a new channel handle.
+	///
+	/// Fails if a handle for the channel already exists.
+	pub(super) fn claim(self: &Arc<Self>, channel: u8) -> std::io::Result<MuxChannel> {
+		let mut state = self.lock();
+		if state.channels.contains_key(&channel) {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::ResourceBusy,
+				format!("channel {channel} is already in use"),
+			));
+		}
+		state.channels.insert(channel, ChannelState {
+			buffer: VecDeque::new(),
+			open: true,
+		});
+		Ok(MuxChannel::new(self.clone(), channel))
+	}
+
+	/// Add received data to the buffer of a channel.
+	///
+	/// Returns false if there is no handle for the channel, in which case the data is discarded.
+	pub(super) fn deliver(&self, channel: u8, data: &[u8]) -> bool {
+		let mut state = self.lock();
+		let Some(channel) = state.channels.get_mut(&channel) else { return false };
+		channel.buffer.extend(data);
+		drop(state);
+		self.condvar.notify_all();
+		true
+	}
+
+	/// Mark a channel as closed.
+	pub(super) fn close_channel(&self, channel: u8) {
+		let mut state = self.lock();
+		if let Some(channel) = state.channels.get_mut(&channel) {
+			channel.open = false;
+		}
+		drop(state);
+		self.condvar.notify_all();
+	}
+
+	/// Record that the background thread stopped, optionally because of an error.
+	pub(super) fn stop(&self, error: Option<std::io::Error>) {
+		let mut state = self.lock();
+		state.stopped = true;
+		if error.is_some() {
+			state.error = error;
+		}
+		drop(state);
+		self.condvar.notify_all();
+	}
+}
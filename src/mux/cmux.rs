@@ -0,0 +1,609 @@
+//! The 3GPP TS 27.010 multiplexer protocol (CMUX) used by cellular modems.
+//!
+//! After a modem is switched to multiplexer mode (usually with `AT+CMUX=0`),
+//! a [`Cmux`] starts the multiplexer and opens virtual channels (DLCIs) on request.
+//! Each channel is a [`MuxChannel`] that can be used like a serial port,
+//! so one channel can carry AT commands while another carries PPP data.
+//!
+//! Only the basic option is implemented, with UIH frames for data.
+//! The multiplexer control channel (DLCI 0) handles modem status commands (MSC) to exchange V.24 signals.
+//!
+//! The [`CmuxFramer`] and [`CmuxFrame`] types can also be used directly,
+//! for example to implement the modem side of the protocol.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::channel::{Link, Shared};
+use super::{MuxChannel, POLL_INTERVAL};
+use crate::framing::{FrameReader, Framer};
+use crate::shutdown::Shutdown;
+use crate::SerialPort;
+
+/// The flag byte that starts and ends every frame in the basic option.
+const FLAG: u8 = 0xF9;
+
+/// The poll/final bit of the control field.
+const POLL_FINAL: u8 = 0x10;
+
+/// The default maximum information field size of the basic option.
+const DEFAULT_MAX_FRAME_SIZE: usize = 31;
+
+/// The control channel message type for a multiplexer close down command.
+const MSG_CLOSE_DOWN: u8 = 0x30;
+
+/// The control channel message type for a modem status command.
+const MSG_MODEM_STATUS: u8 = 0x38;
+
+/// The type of a CMUX frame.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FrameType {
+	/// Set asynchronous balanced mode: open a channel.
+	Sabm,
+
+	/// Unnumbered acknowledgement: positive response.
+	Ua,
+
+	/// Disconnected mode: negative response.
+	Dm,
+
+	/// Disconnect: close a channel.
+	Disc,
+
+	/// Unnumbered information with header check: data.
+	Uih,
+
+	/// Unnumbered information: data, rarely used.
+	Ui,
+}
+
+impl FrameType {
+	/// Get the frame type from a control field, ignoring the poll/final bit.
+	pub fn from_control(control: u8) -> Option<Self> {
+		match control & !POLL_FINAL {
+			0x2F => Some(Self::Sabm),
+			0x63 => Some(Self::Ua),
+			0x0F => Some(Self::Dm),
+			0x43 => Some(Self::Disc),
+			0xEF => Some(Self::Uih),
+			0x03 => Some(Self::Ui),
+			_ => None,
+		}
+	}
+
+	/// Get the control field for the frame type, without the poll/final bit.
+	pub fn to_control(self) -> u8 {
+		match self {
+			Self::Sabm => 0x2F,
+			Self::Ua => 0x63,
+			Self::Dm => 0x0F,
+			Self::Disc => 0x43,
+			Self::Uih => 0xEF,
+			Self::Ui => 0x03,
+		}
+	}
+}
+
+/// A single CMUX frame in the basic option.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CmuxFrame {
+	/// The channel the frame belongs to.
+	dlci: u8,
+
+	/// The type of the frame.
+	frame_type: FrameType,
+
+	/// The command/response bit of the address field.
+	command_response: bool,
+
+	/// The poll/final bit of the control field.
+	poll_final: bool,
+
+	/// The information field.
+	data: Vec<u8>,
+}
+
+impl CmuxFrame {
+	/// Create a new frame.
+	///
+	/// # Panics
+	/// This function panics if `dlci` is larger than 63.
+	pub fn new(dlci: u8, frame_type: FrameType, command_response: bool, poll_final: bool, data: Vec<u8>) -> Self {
+		assert!(dlci < 64, "DLCI must be at most 63");
+		Self {
+			dlci,
+			frame_type,
+			command_response,
+			poll_final,
+			data,
+		}
+	}
+
+	/// Get the channel the frame belongs to.
+	pub fn dlci(&self) -> u8 {
+		self.dlci
+	}
+
+	/// Get the type of the frame.
+	pub fn frame_type(&self) -> FrameType {
+		self.frame_type
+	}
+
+	/// Get the command/response bit of the address field.
+	pub fn command_response(&self) -> bool {
+		self.command_response
+	}
+
+	/// Get the poll/final bit of the control field.
+	pub fn poll_final(&self) -> bool {
+		self.poll_final
+	}
+
+	/// Get the information field.
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+
+	/// Consume the frame and return the information field.
+	pub fn into_data(self) -> Vec<u8> {
+		self.data
+	}
+
+	/// Encode the frame, including the opening and closing flags.
+	pub fn encode(&self) -> Vec<u8> {
+		let mut frame = Vec::with_capacity(self.data.len() + 7);
+		frame.push(FLAG);
+		frame.push(self.dlci << 2 | u8::from(self.command_response) << 1 | 1);
+		frame.push(self.frame_type.to_control() | if self.poll_final { POLL_FINAL } else { 0 });
+		encode_length(&mut frame, self.data.len());
+		let fcs = 0xFF - crc(&frame[1..]);
+		frame.extend_from_slice(&self.data);
+		frame.push(fcs);
+		frame.push(FLAG);
+		frame
+	}
+}
+
+/// Decodes CMUX frames in the basic option.
+///
+/// The frame check sequence is verified, and frames with an unknown control field are reported as invalid data.
+/// A single flag byte may be shared as the closing flag of one frame and the opening flag of the next.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct CmuxFramer;
+
+impl Framer for CmuxFramer {
+	type Frame = CmuxFrame;
+
+	fn decode(&mut self, data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>> {
+		if data[0] != FLAG {
+			return Err(invalid_data("expected frame flag"));
+		}
+		// Skip repeated flags, the last one opens the frame.
+		let start = data.iter().position(|&byte| byte != FLAG).unwrap_or(data.len());
+		let header = &data[start..];
+		if header.len() < 3 {
+			return Ok(None);
+		}
+		let address = header[0];
+		let control = header[1];
+		if address & 1 == 0 {
+			return Err(invalid_data("extended addresses are not supported"));
+		}
+		let (len, len_size) = if header[2] & 1 == 1 {
+			(usize::from(header[2] >> 1), 1)
+		} else {
+			let Some(&high) = header.get(3) else { return Ok(None) };
+			(usize::from(header[2] >> 1) | usize::from(high) << 7, 2)
+		};
+		let header_len = 2 + len_size;
+		// The information field, the frame check sequence and the closing flag.
+		if header.len() < header_len + len + 2 {
+			return Ok(None);
+		}
+		let fcs = header[header_len + len];
+		if crc(&header[..header_len]) != 0xFF - fcs {
+			return Err(invalid_data("frame check sequence mismatch"));
+		}
+		if header[header_len + len + 1] != FLAG {
+			return Err(invalid_data("expected closing flag"));
+		}
+		let frame_type = FrameType::from_control(control).ok_or_else(|| invalid_data("unknown frame type"))?;
+		let frame = CmuxFrame {
+			dlci: address >> 2,
+			frame_type,
+			command_response: address & 2 != 0,
+			poll_final: control & POLL_FINAL != 0,
+			data: header[header_len..header_len + len].to_vec(),
+		};
+		// Leave the closing flag in the buffer, it may also open the next frame.
+		Ok(Some((frame, start + header_len + len + 1)))
+	}
+}
+
+/// The V.24 signals exchanged with modem status commands.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct V24Signals(u8);
+
+impl V24Signals {
+	/// Flow control: the sender is unable to accept frames.
+	pub const FLOW_CONTROL: Self = Self(0x02);
+
+	/// Ready to communicate (DSR/DTR).
+	pub const READY_TO_COMMUNICATE: Self = Self(0x04);
+
+	/// Ready to receive (CTS/RTS).
+	pub const READY_TO_RECEIVE: Self = Self(0x08);
+
+	/// Incoming call indicator (RI).
+	pub const INCOMING_CALL: Self = Self(0x40);
+
+	/// Data valid (DCD).
+	pub const DATA_VALID: Self = Self(0x80);
+
+	/// Create signals from the bits of the V.24 signals octet.
+	///
+	/// The extension bit is ignored.
+	pub fn from_bits(bits: u8) -> Self {
+		Self(bits & !1)
+	}
+
+	/// Get the bits of the V.24 signals octet, without the extension bit.
+	pub fn bits(self) -> u8 {
+		self.0
+	}
+
+	/// Check if all signals in `other` are set.
+	pub fn contains(self, other: Self) -> bool {
+		self.0 & other.0 == other.0
+	}
+}
+
+impl Default for V24Signals {
+	/// Ready to communicate, ready to receive and data valid.
+	fn default() -> Self {
+		Self::READY_TO_COMMUNICATE | Self::READY_TO_RECEIVE | Self::DATA_VALID
+	}
+}
+
+impl std::ops::BitOr for V24Signals {
+	type Output = Self;
+
+	fn bitor(self, other: Self) -> Self {
+		Self(self.0 | other.0)
+	}
+}
+
+/// A CMUX multiplexer client for a cellular modem.
+///
+/// See the [module documentation][self] for more information.
+///
+/// Dropping the multiplexer sends a close down command to the modem and stops the background thread.
+/// Channel handles that still exist report end-of-file when reading and fail when writing.
+pub struct Cmux {
+	/// The state shared with the background thread and the channels.
+	shared: Arc<Shared>,
+
+	/// The link to the modem.
+	link: Arc<CmuxLink>,
+
+	/// The background thread.
+	thread: Option<JoinHandle<()>>,
+
+	/// The maximum time to wait for a response from the modem.
+	response_timeout: Duration,
+}
+
+impl Cmux {
+	/// Start the multiplexer on a serial port.
+	///
+	/// The modem must already be in multiplexer mode.
+	/// This opens the control channel (DLCI 0) and waits for the modem to acknowledge it.
+	///
+	/// This spawns a background thread that reads from the serial port.
+	/// The read timeout of the serial port is changed for use by the background thread.
+	pub fn new(mut port: SerialPort) -> std::io::Result<Self> {
+		port.set_read_timeout(POLL_INTERVAL)?;
+		let link = Arc::new(CmuxLink {
+			port: Mutex::new(port.try_clone()?),
+			max_frame_size: AtomicUsize::new(DEFAULT_MAX_FRAME_SIZE),
+			state: Mutex::new(CmuxState::default()),
+			condvar: Condvar::new(),
+		});
+		let shared = Arc::new(Shared::new(link.clone()));
+		let reader = FrameReader::new(port, CmuxFramer);
+		let thread = std::thread::Builder::new()
+			.name("serial2-cmux".into())
+			.spawn({
+				let shared = shared.clone();
+				let link = link.clone();
+				move || run(&shared, &link, reader)
+			})?;
+		let cmux = Self {
+			shared,
+			link,
+			thread: Some(thread),
+			response_timeout: Duration::from_secs(1),
+		};
+		cmux.establish(0)?;
+		Ok(cmux)
+	}
+
+	/// Open a channel.
+	///
+	/// This sends a SABM frame for the channel and waits for the modem to acknowledge it.
+	/// If the modem refuses, an error of the type [`std::io::ErrorKind::ConnectionRefused`] is returned.
+	/// After the channel is opened, the default [`V24Signals`] are sent with a modem status command.
+	///
+	/// The DLCI must be between 1 and 63.
+	/// Only one handle can exist for each channel at a time.
+	/// Dropping the handle closes the channel.
+	pub fn open_channel(&self, dlci: u8) -> std::io::Result<MuxChannel> {
+		if !(1..64).contains(&dlci) {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				"DLCI must be between 1 and 63",
+			));
+		}
+		let channel = self.shared.claim(dlci)?;
+		self.establish(dlci)?;
+		self.set_modem_status(dlci, V24Signals::default())?;
+		Ok(channel)
+	}
+
+	/// Send V.24 signals for a channel to the modem with a modem status command.
+	pub fn set_modem_status(&self, dlci: u8, signals: V24Signals) -> std::io::Result<()> {
+		let value = [dlci << 2 | 0x03, signals.bits() | 1];
+		self.link.send_control(MSG_MODEM_STATUS, true, &value)
+	}
+
+	/// Get the last V.24 signals for a channel received from the modem, if any.
+	pub fn get_remote_modem_status(&self, dlci: u8) -> Option<V24Signals> {
+		self.link.lock().remote_signals.get(&dlci).copied()
+	}
+
+	/// Set the maximum size of the information field of transmitted frames.
+	///
+	/// This must match the frame size configured on the modem.
+	/// The default is 31 bytes, the default of the basic option.
+	pub fn set_max_frame_size(&mut self, size: usize) {
+		self.link.max_frame_size.store(size.clamp(1, 32768), Ordering::Relaxed);
+	}
+
+	/// Get the maximum size of the information field of transmitted frames.
+	pub fn get_max_frame_size(&self) -> usize {
+		self.link.max_frame_size.load(Ordering::Relaxed)
+	}
+
+	/// Set the maximum time to wait for a response from the modem.
+	pub fn set_response_timeout(&mut self, timeout: Duration) {
+		self.response_timeout = timeout;
+	}
+
+	/// Get the maximum time to wait for a response from the modem.
+	pub fn get_response_timeout(&self) -> Duration {
+		self.response_timeout
+	}
+
+	/// Get the number of corrupted frames that were skipped.
+	pub fn invalid_frames(&self) -> u64 {
+		self.shared.lock().invalid_frames
+	}
+
+	/// Take the error that stopped the background thread, if any.
+	pub fn take_error(&self) -> Option<std::io::Error> {
+		self.shared.lock().error.take()
+	}
+
+	/// Send a SABM frame and wait for the response.
+	fn establish(&self, dlci: u8) -> std::io::Result<()> {
+		self.link.lock().responses.remove(&dlci);
+		self.link.send_frame(&CmuxFrame::new(dlci, FrameType::Sabm, true, true, Vec::new()))?;
+
+		let deadline = Instant::now() + self.response_timeout;
+		let mut state = self.link.lock();
+		loop {
+			match state.responses.remove(&dlci) {
+				Some(FrameType::Ua) => return Ok(()),
+				Some(_) => {
+					return Err(std::io::Error::new(
+						std::io::ErrorKind::ConnectionRefused,
+						format!("the modem refused to open DLCI {dlci}"),
+					))
+				},
+				None => (),
+			}
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return Err(std::io::ErrorKind::TimedOut.into());
+			}
+			state = self.link.wait_timeout(state, remaining);
+		}
+	}
+}
+
+impl Cmux {
+	/// Stop the background thread and wait for it to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		// Only close down the multiplexer once, even if it is stopped again when dropped after a shutdown.
+		if self.thread.is_some() {
+			let _ = self.link.send_control(MSG_CLOSE_DOWN, true, &[]);
+		}
+		self.shared.lock().closed = true;
+		let result = crate::shutdown::join_threads(self.thread.take(), deadline);
+		self.shared.stop(None);
+		result
+	}
+}
+
+impl Drop for Cmux {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl Shutdown for Cmux {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// Transmits frames to the modem, and keeps track of responses.
+struct CmuxLink {
+	/// The serial port, locked while writing a frame.
+	port: Mutex<SerialPort>,
+
+	/// The maximum size of the information field of transmitted frames.
+	max_frame_size: AtomicUsize,
+
+	/// The protocol state.
+	state: Mutex<CmuxState>,
+
+	/// Condition variable signalled when a response is received.
+	condvar: Condvar,
+}
+
+/// The protocol state of a [`CmuxLink`].
+#[derive(Default)]
+struct CmuxState {
+	/// The last response to a SABM or DISC frame, by DLCI.
+	responses: HashMap<u8, FrameType>,
+
+	/// The last V.24 signals received from the modem, by DLCI.
+	remote_signals: HashMap<u8, V24Signals>,
+}
+
+impl CmuxLink {
+	/// Lock the protocol state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, CmuxState> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Wait for a response or a timeout, ignoring poisoning.
+	fn wait_timeout<'a>(&self, guard: MutexGuard<'a, CmuxState>, timeout: Duration) -> MutexGuard<'a, CmuxState> {
+		match self.condvar.wait_timeout(guard, timeout) {
+			Ok((guard, _timeout)) => guard,
+			Err(e) => e.into_inner().0,
+		}
+	}
+
+	/// Transmit a frame.
+	fn send_frame(&self, frame: &CmuxFrame) -> std::io::Result<()> {
+		self.port.lock().unwrap_or_else(PoisonError::into_inner).write_all(&frame.encode())
+	}
+
+	/// Transmit a message on the control channel.
+	fn send_control(&self, message_type: u8, command: bool, value: &[u8]) -> std::io::Result<()> {
+		let mut data = Vec::with_capacity(value.len() + 3);
+		data.push(message_type << 2 | u8::from(command) << 1 | 1);
+		encode_length(&mut data, value.len());
+		data.extend_from_slice(value);
+		self.send_frame(&CmuxFrame::new(0, FrameType::Uih, true, false, data))
+	}
+
+	/// Handle a message received on the control channel.
+	fn handle_control(&self, data: &[u8]) -> std::io::Result<()> {
+		let Some(&type_octet) = data.first() else { return Ok(()) };
+		let message_type = type_octet >> 2;
+		let command = type_octet & 2 != 0;
+		let Some(&len_octet) = data.get(1) else { return Ok(()) };
+		// Control messages on DLCI 0 are short, a single length octet is enough.
+		let value = data.get(2..2 + usize::from(len_octet >> 1)).unwrap_or(&[]);
+
+		if message_type == MSG_MODEM_STATUS && command {
+			if let [address, signals, ..] = *value {
+				self.lock().remote_signals.insert(address >> 2, V24Signals::from_bits(signals));
+			}
+			self.send_control(MSG_MODEM_STATUS, false, value)?;
+		}
+		Ok(())
+	}
+}
+
+impl Link for Arc<CmuxLink> {
+	fn send(&self, channel: u8, data: &[u8]) -> std::io::Result<usize> {
+		let data = &data[..data.len().min(self.max_frame_size.load(Ordering::Relaxed))];
+		self.send_frame(&CmuxFrame::new(channel, FrameType::Uih, true, false, data.to_vec()))?;
+		Ok(data.len())
+	}
+
+	fn release(&self, channel: u8) {
+		let _ = self.send_frame(&CmuxFrame::new(channel, FrameType::Disc, true, true, Vec::new()));
+	}
+}
+
+/// Run the background thread.
+fn run(shared: &Shared, link: &CmuxLink, mut reader: FrameReader<CmuxFramer>) {
+	loop {
+		if shared.lock().closed {
+			return;
+		}
+		let result = match reader.read_frame() {
+			Ok(frame) => handle_frame(shared, link, frame),
+			Err(e) => Err(e),
+		};
+		match result {
+			Ok(()) => (),
+			Err(e) if e.kind() == std::io::ErrorKind::InvalidData => shared.lock().invalid_frames += 1,
+			Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => return shared.stop(Some(e)),
+		}
+	}
+}
+
+/// Handle a frame received from the modem.
+fn handle_frame(shared: &Shared, link: &CmuxLink, frame: CmuxFrame) -> std::io::Result<()> {
+	let dlci = frame.dlci();
+	match frame.frame_type() {
+		FrameType::Ua | FrameType::Dm => {
+			link.lock().responses.insert(dlci, frame.frame_type());
+			link.condvar.notify_all();
+		},
+		FrameType::Disc => {
+			link.send_frame(&CmuxFrame::new(dlci, FrameType::Ua, true, true, Vec::new()))?;
+			shared.close_channel(dlci);
+		},
+		FrameType::Sabm => {
+			// Channels are only opened by this side.
+			link.send_frame(&CmuxFrame::new(dlci, FrameType::Dm, true, true, Vec::new()))?;
+		},
+		FrameType::Uih | FrameType::Ui => {
+			if dlci == 0 {
+				link.handle_control(frame.data())?;
+			} else {
+				shared.deliver(dlci, frame.data());
+			}
+		},
+	}
+	Ok(())
+}
+
+/// Append a length field with extension bits.
+fn encode_length(output: &mut Vec<u8>, len: usize) {
+	if len < 128 {
+		output.push((len as u8) << 1 | 1);
+	} else {
+		output.push((len as u8) << 1);
+		output.push((len >> 7) as u8);
+	}
+}
+
+/// Compute the CRC of the header of a frame, as used for the frame check sequence.
+fn crc(data: &[u8]) -> u8 {
+	let mut crc = 0xFF;
+	for &byte in data {
+		crc ^= byte;
+		for _ in 0..8 {
+			crc = if crc & 1 != 0 { (crc >> 1) ^ 0xE0 } else { crc >> 1 };
+		}
+	}
+	crc
+}
+
+/// Create an error of the type [`std::io::ErrorKind::InvalidData`].
+fn invalid_data(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_owned())
+}
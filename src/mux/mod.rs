@@ -0,0 +1,206 @@
+//! Multiplexing of several logical streams over one serial port.
+//!
+//! A [`Mux`] lets several independent streams share a single physical link,
+//! for example a console, a telemetry stream and a file transfer.
+//! Each stream is a [`MuxChannel`], identified by a channel number.
+//! Channels implement [`std::io::Read`] and [`std::io::Write`], so they can be used wherever a serial port can.
+//!
+//! The multiplexer owns a background thread that reads frames from the serial port
+//! and buffers the payload per channel.
+//! Data for channels without a handle is discarded.
+//!
+//! Both sides of the link must use a [`Mux`].
+//! Data is sent in frames with the following layout:
+//!
+//! | Field    | Size          | Description                                                     |
+//! |----------|---------------|-----------------------------------------------------------------|
+//! | Flag     | 1             | Always `0xF9`.                                                  |
+//! | Channel  | 1             | The channel number.                                             |
+//! | Length   | 2             | The length of the payload, little endian, at most 1024.         |
+//! | Payload  | Length        | The data.                                                       |
+//! | Checksum | 1             | The XOR of the channel, length and payload bytes.               |
+//!
+//! Corrupted frames are skipped, and the receiver resynchronizes on the next flag byte.
+//!
+//! To multiplex the port of a cellular modem, use the standard CMUX protocol in the [`cmux`] module instead.
+
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::framing::{FrameReader, Framer};
+use crate::shutdown::Shutdown;
+use crate::SerialPort;
+
+mod channel;
+pub use channel::MuxChannel;
+pub mod cmux;
+use channel::{Link, Shared};
+
+/// The read timeout used by the background thread.
+///
+/// This limits how long it takes for the background thread to notice that the multiplexer was dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The byte that starts every frame.
+const FLAG: u8 = 0xF9;
+
+/// The maximum payload of a single frame.
+///
+/// Larger writes are split over multiple frames, so channels take turns on the link.
+pub const MAX_PAYLOAD: usize = 1024;
+
+/// Multiplexes several logical channels over one serial port.
+///
+/// See the [module documentation][self] for more information.
+///
+/// Dropping the multiplexer stops the background thread.
+/// Channel handles that still exist report end-of-file when reading and fail when writing.
+pub struct Mux {
+	/// The state shared with the background thread and the channels.
+	shared: Arc<Shared>,
+
+	/// The background thread.
+	thread: Option<JoinHandle<()>>,
+}
+
+impl Mux {
+	/// Create a new multiplexer for a serial port.
+	///
+	/// This spawns a background thread that reads from the serial port.
+	/// The read timeout of the serial port is changed for use by the background thread.
+	pub fn new(mut port: SerialPort) -> std::io::Result<Self> {
+		port.set_read_timeout(POLL_INTERVAL)?;
+		let writer = port.try_clone()?;
+		let shared = Arc::new(Shared::new(MuxLink {
+			port: Mutex::new(writer),
+		}));
+		let reader = FrameReader::new(port, MuxFramer);
+		let thread = std::thread::Builder::new()
+			.name("serial2-mux".into())
+			.spawn({
+				let shared = shared.clone();
+				move || run(&shared, reader)
+			})?;
+		Ok(Self {
+			shared,
+			thread: Some(thread),
+		})
+	}
+
+	/// Get a handle for a channel.
+	///
+	/// Only one handle can exist for each channel at a time.
+	/// If a handle for the channel already exists, an error of the type [`std::io::ErrorKind::ResourceBusy`] is returned.
+	/// Data received for the channel before the handle was created is discarded.
+	pub fn channel(&self, channel: u8) -> std::io::Result<MuxChannel> {
+		self.shared.claim(channel)
+	}
+
+	/// Get the number of corrupted frames that were skipped.
+	pub fn invalid_frames(&self) -> u64 {
+		self.shared.lock().invalid_frames
+	}
+
+	/// Take the error that stopped the background thread, if any.
+	pub fn take_error(&self) -> Option<std::io::Error> {
+		self.shared.lock().error.take()
+	}
+}
+
+impl Mux {
+	/// Stop the background thread and wait for it to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		let result = crate::shutdown::join_threads(self.thread.take(), deadline);
+		self.shared.stop(None);
+		result
+	}
+}
+
+impl Drop for Mux {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl Shutdown for Mux {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// Transmits channel data over the serial port.
+struct MuxLink {
+	/// The serial port, locked while writing a frame.
+	port: Mutex<SerialPort>,
+}
+
+impl Link for MuxLink {
+	fn send(&self, channel: u8, data: &[u8]) -> std::io::Result<usize> {
+		let data = &data[..data.len().min(MAX_PAYLOAD)];
+		let frame = encode(channel, data);
+		self.port.lock().unwrap_or_else(PoisonError::into_inner).write_all(&frame)?;
+		Ok(data.len())
+	}
+}
+
+/// Encode a frame.
+fn encode(channel: u8, data: &[u8]) -> Vec<u8> {
+	let len = (data.len() as u16).to_le_bytes();
+	let mut frame = Vec::with_capacity(data.len() + 5);
+	frame.push(FLAG);
+	frame.push(channel);
+	frame.extend_from_slice(&len);
+	frame.extend_from_slice(data);
+	frame.push(checksum(&frame[1..]));
+	frame
+}
+
+/// Compute the checksum of the channel, length and payload bytes.
+fn checksum(data: &[u8]) -> u8 {
+	data.iter().fold(0, |checksum, byte| checksum ^ byte)
+}
+
+/// Decodes multiplexer frames into a channel number and payload.
+pub(crate) struct MuxFramer;
+
+impl Framer for MuxFramer {
+	type Frame = (u8, Vec<u8>);
+
+	fn decode(&mut self, data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>> {
+		if data[0] != FLAG {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "expected frame flag"));
+		}
+		if data.len() < 4 {
+			return Ok(None);
+		}
+		let len = usize::from(u16::from_le_bytes([data[2], data[3]]));
+		if len > MAX_PAYLOAD {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "frame too long"));
+		}
+		let Some(&received_checksum) = data.get(4 + len) else { return Ok(None) };
+		if checksum(&data[1..4 + len]) != received_checksum {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "frame checksum mismatch"));
+		}
+		Ok(Some(((data[1], data[4..4 + len].to_vec()), 5 + len)))
+	}
+}
+
+/// Run the background thread.
+fn run(shared: &Shared, mut reader: FrameReader<MuxFramer>) {
+	loop {
+		if shared.lock().closed {
+			return;
+		}
+		match reader.read_frame() {
+			Ok((channel, data)) => {
+				shared.deliver(channel, &data);
+			},
+			Err(e) if e.kind() == std::io::ErrorKind::InvalidData => shared.lock().invalid_frames += 1,
+			Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => return shared.stop(Some(e)),
+		}
+	}
+}
@@ -0,0 +1,148 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// A filter that discards the garbage received right after a serial port is opened or reconfigured.
+///
+/// Many USB serial adapters and devices emit a few garbage bytes when they are reset or change baud rate.
+/// A noise filter discards all received data for a fixed duration after it is armed.
+///
+/// The filter is armed when it is set with [`SerialPort::set_noise_filter()`][crate::SerialPort::set_noise_filter],
+/// when the baud rate is changed with [`SerialPort::set_configuration()`][crate::SerialPort::set_configuration],
+/// and when [`SerialPort::arm_noise_filter()`][crate::SerialPort::arm_noise_filter] is called.
+///
+/// With [`Self::until_valid_frame()`], a [`FrameReader`][crate::framing::FrameReader] also silently discards invalid data
+/// until it decodes the first valid frame after the filter was armed.
+///
+/// # Example
+/// ```no_run
+/// # use serial2::{NoiseFilter, SerialPort};
+/// # use std::time::Duration;
+/// # fn foo() -> std::io::Result<()> {
+/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+/// port.set_noise_filter(Some(NoiseFilter::new(Duration::from_millis(50)).until_valid_frame()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct NoiseFilter {
+	/// The duration to discard all received data.
+	discard_time: Duration,
+
+	/// If true, frame readers discard invalid data until the first valid frame.
+	until_valid_frame: bool,
+}
+
+impl NoiseFilter {
+	/// Create a filter that discards all received data for the given duration.
+	pub fn new(discard_time: Duration) -> Self {
+		Self {
+			discard_time,
+			until_valid_frame: false,
+		}
+	}
+
+	/// Also discard invalid data in a frame reader until the first valid frame is decoded.
+	///
+	/// This only affects a [`FrameReader`][crate::framing::FrameReader]:
+	/// plain reads from the serial port are only filtered for the discard time.
+	pub fn until_valid_frame(mut self) -> Self {
+		self.until_valid_frame = true;
+		self
+	}
+
+	/// Get the duration to discard all received data.
+	pub fn discard_time(&self) -> Duration {
+		self.discard_time
+	}
+
+	/// Check if frame readers discard invalid data until the first valid frame.
+	pub fn is_until_valid_frame(&self) -> bool {
+		self.until_valid_frame
+	}
+}
+
+/// The state of the noise filter of a serial port.
+#[derive(Default)]
+pub(crate) struct NoiseGate {
+	/// The mutable state.
+	state: Mutex<NoiseGateState>,
+}
+
+/// The mutable state of a [`NoiseGate`].
+#[derive(Default)]
+struct NoiseGateState {
+	/// The configured filter.
+	config: Option<NoiseFilter>,
+
+	/// The time until which all received data is discarded.
+	discard_until: Option<Instant>,
+
+	/// True if invalid frames are discarded until the first valid frame.
+	awaiting_frame: bool,
+}
+
+impl NoiseGate {
+	/// Set the filter and arm it.
+	pub(crate) fn set_config(&self, config: Option<NoiseFilter>) {
+		self.lock().config = config;
+		self.arm();
+	}
+
+	/// Get the configured filter.
+	pub(crate) fn config(&self) -> Option<NoiseFilter> {
+		self.lock().config
+	}
+
+	/// Arm the filter, if one is configured.
+	pub(crate) fn arm(&self) {
+		let mut state = self.lock();
+		match state.config {
+			Some(config) => {
+				state.discard_until = match config.discard_time.is_zero() {
+					true => None,
+					false => Some(Instant::now() + config.discard_time),
+				};
+				state.awaiting_frame = config.until_valid_frame;
+			},
+			None => {
+				state.discard_until = None;
+				state.awaiting_frame = false;
+			},
+		}
+	}
+
+	/// Check if received data should be discarded right now.
+	pub(crate) fn is_discarding(&self) -> bool {
+		self.lock().discard_until.is_some_and(|discard_until| Instant::now() < discard_until)
+	}
+
+	/// Check if the discard time just ended.
+	///
+	/// Returns true only once per time the filter is armed.
+	/// The caller must then discard the data that was received during the discard time but not read yet.
+	pub(crate) fn take_expired(&self) -> bool {
+		let mut state = self.lock();
+		match state.discard_until {
+			Some(discard_until) if Instant::now() >= discard_until => {
+				state.discard_until = None;
+				true
+			},
+			_ => false,
+		}
+	}
+
+	/// Check if invalid frames should be discarded silently.
+	pub(crate) fn is_awaiting_frame(&self) -> bool {
+		self.lock().awaiting_frame
+	}
+
+	/// Record that a valid frame was decoded.
+	pub(crate) fn frame_received(&self) {
+		self.lock().awaiting_frame = false;
+	}
+
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, NoiseGateState> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
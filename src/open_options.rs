@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use crate::SerialPort;
+
+/// Options for opening a serial port, in the style of [`std::fs::OpenOptions`].
+///
+/// Use [`SerialPort::builder()`] to create the options.
+/// All options are applied before [`Self::open()`] returns the serial port,
+/// so there is no window where the port is open with the wrong settings or line state.
+///
+/// # Example
+/// ```no_run
+/// # use serial2::SerialPort;
+/// # fn foo() -> std::io::Result<()> {
+/// let port = SerialPort::builder("/dev/ttyUSB0")
+///     .baud_rate(115200)
+///     .dtr_on_open(false)
+///     .rts_on_open(false)
+///     .exclusive(true)
+///     .open()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+	/// The path of the serial port.
+	path: PathBuf,
+
+	/// The baud rate to configure, or `None` to keep the current settings.
+	baud_rate: Option<u32>,
+
+	/// The state of the DTR line after opening, or `None` to leave it as the platform sets it.
+	dtr: Option<bool>,
+
+	/// The state of the RTS line after opening, or `None` to leave it as the platform sets it.
+	rts: Option<bool>,
+
+	/// Prevent other processes from opening the serial port.
+	exclusive: bool,
+
+	/// Open the serial port for reading only.
+	read_only: bool,
+}
+
+impl OpenOptions {
+	/// Create new options to open a serial port by path or name.
+	///
+	/// See [`SerialPort::open()`] for the supported paths and names.
+	pub fn new(path: impl AsRef<Path>) -> Self {
+		Self {
+			path: path.as_ref().to_owned(),
+			baud_rate: None,
+			dtr: None,
+			rts: None,
+			exclusive: false,
+			read_only: false,
+		}
+	}
+
+	/// Configure the serial port for raw data with the given baud rate.
+	///
+	/// This applies the same configuration as passing the baud rate to [`SerialPort::open()`]:
+	/// 8 data bits, 1 stop bit, no parity and no flow control.
+	/// Without a baud rate, the current settings of the serial port are kept.
+	pub fn baud_rate(&mut self, baud_rate: u32) -> &mut Self {
+		self.baud_rate = Some(baud_rate);
+		self
+	}
+
+	/// Set the state of the Data Terminal Ready line right after opening the serial port.
+	///
+	/// Many microcontroller boards reset when DTR changes, so disable it to open the port without a reset.
+	/// Note that the platform may already raise the line briefly while opening the port.
+	pub fn dtr_on_open(&mut self, state: bool) -> &mut Self {
+		self.dtr = Some(state);
+		self
+	}
+
+	/// Set the state of the Ready To Send line right after opening the serial port.
+	///
+	/// Note that the platform may already raise the line briefly while opening the port.
+	pub fn rts_on_open(&mut self, state: bool) -> &mut Self {
+		self.rts = Some(state);
+		self
+	}
+
+	/// Prevent other processes from opening the serial port while it is open.
+	///
+	/// On Unix platforms, this puts the terminal in exclusive mode with `TIOCEXCL`.
+	/// Processes with root privileges can still open the serial port.
+	/// On Windows, serial ports can only be opened by one process at a time anyway, so this has no effect.
+	pub fn exclusive(&mut self, exclusive: bool) -> &mut Self {
+		self.exclusive = exclusive;
+		self
+	}
+
+	/// Open the serial port for reading only.
+	///
+	/// Writing to the serial port fails.
+	/// To open a port without changing its configuration or line state at all, use a [`Sniffer`][crate::sniff::Sniffer].
+	pub fn read_only(&mut self) -> &mut Self {
+		self.read_only = true;
+		self
+	}
+
+	/// Open the serial port with these options.
+	pub fn open(&self) -> std::io::Result<SerialPort> {
+		let mut port = SerialPort::open_unconfigured(&self.path, self.read_only, self.exclusive)?;
+		let set_lines = self.rts.is_some() || self.dtr.is_some();
+		if set_lines {
+			port.set_modem_lines(self.rts, self.dtr)?;
+		}
+		if let Some(baud_rate) = self.baud_rate {
+			let mut settings = port.get_configuration()?;
+			settings.set_raw();
+			settings.set_baud_rate(baud_rate)?;
+			port.set_configuration(&settings)?;
+			// Applying the settings may change the lines on some platforms, so set them again.
+			if set_lines {
+				port.set_modem_lines(self.rts, self.dtr)?;
+			}
+		}
+		Ok(port)
+	}
+}
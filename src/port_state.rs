@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use crate::{ByteTransform, InterByteDelay, SoftwareParity};
+
+/// The prefix of a serialized [`PortState`], including the format version.
+const PREFIX: &str = "serial2-port-state-v1";
+
+/// The state of a serial port, exported to hand the port over to a new process image.
+///
+/// Use [`SerialPort::export_state()`][crate::SerialPort::export_state] to export the state before calling `exec`,
+/// and [`SerialPort::import_state()`][crate::SerialPort::import_state] in the new process image to take the port back.
+/// The file descriptor is inherited by the new process image, and the state can be passed along as a string,
+/// for example in an environment variable or a command line argument.
+/// Use the [`Display`][std::fmt::Display] and [`FromStr`][std::str::FromStr] implementations to convert it.
+///
+/// The serial port settings and the kernel buffers belong to the file descriptor, so they survive the `exec` as-is:
+/// received data that was not read yet can still be read by the new process image.
+/// The state also contains the per-handle settings of this library:
+/// the read and write timeouts, the inter-byte delay, the byte transform and software parity.
+///
+/// Data in user-space buffers, like a [`BufferedReader`][crate::buffered::BufferedReader], is not part of the state.
+/// Drain or detach those buffers before exporting the state.
+#[derive(Debug, Clone)]
+pub struct PortState {
+	/// The file descriptor of the serial port.
+	pub(crate) fd: i32,
+
+	/// The read timeout.
+	pub(crate) read_timeout: Duration,
+
+	/// The write timeout.
+	pub(crate) write_timeout: Duration,
+
+	/// The inter-byte delay.
+	pub(crate) inter_byte_delay: Option<InterByteDelay>,
+
+	/// The software parity mode and the number of data bits without the parity bit.
+	pub(crate) software_parity: Option<(SoftwareParity, u8)>,
+
+	/// The byte transform.
+	pub(crate) transform: Option<ByteTransform>,
+}
+
+impl PortState {
+	/// Get the file descriptor of the serial port.
+	pub fn fd(&self) -> i32 {
+		self.fd
+	}
+}
+
+impl std::fmt::Display for PortState {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{PREFIX};fd={}", self.fd)?;
+		write!(f, ";read_timeout_ms={}", self.read_timeout.as_millis())?;
+		write!(f, ";write_timeout_ms={}", self.write_timeout.as_millis())?;
+		if let Some(delay) = &self.inter_byte_delay {
+			write!(f, ";inter_byte_delay_us={}/{}", delay.delay().as_micros(), delay.group_size())?;
+		}
+		if let Some((parity, data_bits)) = self.software_parity {
+			let parity = match parity {
+				SoftwareParity::Odd => "odd",
+				SoftwareParity::Even => "even",
+				SoftwareParity::Mark => "mark",
+				SoftwareParity::Space => "space",
+			};
+			write!(f, ";software_parity={parity}/{data_bits}")?;
+		}
+		if let Some(transform) = &self.transform {
+			write!(f, ";transform=")?;
+			for byte in 0..=255 {
+				write!(f, "{:02x}", transform.transmit(byte))?;
+			}
+			for byte in 0..=255 {
+				write!(f, "{:02x}", transform.receive(byte))?;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl std::str::FromStr for PortState {
+	type Err = std::io::Error;
+
+	fn from_str(input: &str) -> Result<Self, Self::Err> {
+		let mut fields = input.split(';');
+		if fields.next() != Some(PREFIX) {
+			return Err(invalid_data("unsupported port state format"));
+		}
+		let mut state = PortState {
+			fd: -1,
+			read_timeout: Duration::ZERO,
+			write_timeout: Duration::ZERO,
+			inter_byte_delay: None,
+			software_parity: None,
+			transform: None,
+		};
+		for field in fields {
+			let (key, value) = field.split_once('=').ok_or_else(|| invalid_data("missing value in port state"))?;
+			match key {
+				"fd" => state.fd = parse(value)?,
+				"read_timeout_ms" => state.read_timeout = Duration::from_millis(parse(value)?),
+				"write_timeout_ms" => state.write_timeout = Duration::from_millis(parse(value)?),
+				"inter_byte_delay_us" => {
+					let (delay, group_size) = value
+						.split_once('/')
+						.ok_or_else(|| invalid_data("invalid inter-byte delay"))?;
+					let group_size = parse(group_size)?;
+					if group_size == 0 {
+						return Err(invalid_data("invalid inter-byte delay"));
+					}
+					let delay = Duration::from_micros(parse(delay)?);
+					state.inter_byte_delay = Some(InterByteDelay::after_every(group_size, delay));
+				},
+				"software_parity" => {
+					let (parity, data_bits) = value
+						.split_once('/')
+						.ok_or_else(|| invalid_data("invalid software parity"))?;
+					let parity = match parity {
+						"odd" => SoftwareParity::Odd,
+						"even" => SoftwareParity::Even,
+						"mark" => SoftwareParity::Mark,
+						"space" => SoftwareParity::Space,
+						_ => return Err(invalid_data("invalid software parity")),
+					};
+					let data_bits = parse(data_bits)?;
+					if !(5..=7).contains(&data_bits) {
+						return Err(invalid_data("invalid software parity"));
+					}
+					state.software_parity = Some((parity, data_bits));
+				},
+				"transform" => {
+					let tables = decode_hex(value).ok_or_else(|| invalid_data("invalid byte transform"))?;
+					let (tx, rx) = tables.split_at(256);
+					let transform = ByteTransform::from_fn(|byte| tx[usize::from(byte)], |byte| rx[usize::from(byte)]);
+					state.transform = Some(transform);
+				},
+				// Ignore unknown fields, so newer versions can add fields without breaking older versions.
+				_ => (),
+			}
+		}
+		if state.fd < 0 {
+			return Err(invalid_data("missing file descriptor in port state"));
+		}
+		Ok(state)
+	}
+}
+
+/// Parse a number from a field of the port state.
+fn parse<T: std::str::FromStr>(value: &str) -> std::io::Result<T> {
+	value.parse().map_err(|_| invalid_data("invalid number in port state"))
+}
+
+/// Decode the 512 bytes of the transform tables from hexadecimal.
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+	if value.len() != 1024 || !value.is_ascii() {
+		return None;
+	}
+	(0..value.len())
+		.step_by(2)
+		.map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok())
+		.collect()
+}
+
+/// Create an error of the type [`std::io::ErrorKind::InvalidData`].
+fn invalid_data(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_owned())
+}
@@ -0,0 +1,341 @@
+//! Handing a serial port to a user-space PPP implementation.
+//!
+//! Cellular and dial-up modems switch to PPP after the `ATD` command connected.
+//! From that point on, the serial port carries HDLC-like framed PPP packets as described in RFC 1662.
+//!
+//! A [`PppLink`] wraps the serial port for use by a PPP implementation.
+//! It can be used as a raw byte stream through [`std::io::Read`] and [`std::io::Write`],
+//! for implementations that do their own framing,
+//! or it can do the framing with [`PppLink::read_frame()`] and [`PppLink::write_frame()`].
+//! The state of the modem control lines, including the carrier, is available with [`PppLink::line_state()`].
+//!
+//! If the serial port is read by a [`BufferedReader`][crate::buffered::BufferedReader] during the AT command phase,
+//! use [`BufferedReader::detach()`][crate::buffered::BufferedReader::detach] to hand it over to the PPP implementation,
+//! and pass any data that was already buffered to [`PppLink::with_received()`].
+//! When the PPP session ends, drop the [`DetachedPort`][crate::buffered::DetachedPort] to return to the buffered reader.
+//!
+//! The [`HdlcFramer`] can also be used on its own, with a [`FrameReader`][crate::framing::FrameReader].
+
+use crate::framing::Framer;
+use crate::SerialPort;
+
+/// The byte that separates frames.
+const FLAG: u8 = 0x7E;
+
+/// The control escape byte.
+const ESCAPE: u8 = 0x7D;
+
+/// The value that escaped bytes are XOR-ed with.
+const ESCAPE_XOR: u8 = 0x20;
+
+/// The frame check sequence of a frame, computed over the frame and its own frame check sequence.
+const GOOD_FCS: u16 = 0xF0B8;
+
+/// The default async control character map: all control characters are escaped.
+pub const DEFAULT_ACCM: u32 = 0xFFFF_FFFF;
+
+/// A framer for HDLC-like framing as used by PPP (RFC 1662).
+///
+/// Frames are delimited by `0x7E` flag bytes and protected by a 16 bit frame check sequence.
+/// Decoded frames include the address and control fields, but not the frame check sequence.
+/// Consecutive frames may share a flag byte.
+///
+/// Use [`HdlcFramer::encode()`] to encode a frame for sending.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct HdlcFramer;
+
+impl HdlcFramer {
+	/// Encode a frame, including the frame check sequence and a flag at the start and the end.
+	///
+	/// The async control character map (ACCM) determines which control characters are escaped:
+	/// if bit `n` is set, the byte with value `n` is escaped.
+	/// The flag and escape bytes are always escaped.
+	pub fn encode(data: &[u8], accm: u32) -> Vec<u8> {
+		let fcs = !fcs16(0xFFFF, data);
+		let mut encoded = Vec::with_capacity(data.len() + 4);
+		encoded.push(FLAG);
+		for &byte in data.iter().chain(&fcs.to_le_bytes()) {
+			if byte == FLAG || byte == ESCAPE || (byte < 0x20 && accm & (1 << byte) != 0) {
+				encoded.extend_from_slice(&[ESCAPE, byte ^ ESCAPE_XOR]);
+			} else {
+				encoded.push(byte);
+			}
+		}
+		encoded.push(FLAG);
+		encoded
+	}
+}
+
+impl Framer for HdlcFramer {
+	type Frame = Vec<u8>;
+
+	fn decode(&mut self, data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>> {
+		if data[0] != FLAG {
+			return Err(invalid_data("expected frame flag"));
+		}
+		// Skip repeated flags, the last one opens the frame.
+		let Some(start) = data.iter().position(|&byte| byte != FLAG) else { return Ok(None) };
+		let Some(end) = data[start..].iter().position(|&byte| byte == FLAG) else { return Ok(None) };
+		let end = start + end;
+
+		let mut frame = Vec::with_capacity(end - start);
+		let mut bytes = data[start..end].iter();
+		while let Some(&byte) = bytes.next() {
+			if byte != ESCAPE {
+				frame.push(byte);
+				continue;
+			}
+			match bytes.next() {
+				Some(&byte) => frame.push(byte ^ ESCAPE_XOR),
+				None => return Err(invalid_data("frame aborted by the sender")),
+			}
+		}
+		if frame.len() < 3 {
+			return Err(invalid_data("frame too short"));
+		}
+		if fcs16(0xFFFF, &frame) != GOOD_FCS {
+			return Err(invalid_data("frame check sequence mismatch"));
+		}
+		frame.truncate(frame.len() - 2);
+		// Leave the closing flag in the buffer, it may also open the next frame.
+		Ok(Some((frame, end)))
+	}
+}
+
+/// The state of the modem control lines of a serial port.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct LineState {
+	/// The state of the Carrier Detect line.
+	cd: bool,
+
+	/// The state of the Data Set Ready line.
+	dsr: bool,
+
+	/// The state of the Clear To Send line.
+	cts: bool,
+
+	/// The state of the Ring Indicator line.
+	ri: bool,
+}
+
+impl LineState {
+	/// Read the state of the modem control lines of a serial port.
+	pub fn read(port: &SerialPort) -> std::io::Result<Self> {
+		Ok(Self {
+			cd: port.read_cd()?,
+			dsr: port.read_dsr()?,
+			cts: port.read_cts()?,
+			ri: port.read_ri()?,
+		})
+	}
+
+	/// Check if the Carrier Detect line is asserted.
+	///
+	/// Most modems de-assert the carrier when the connection is lost.
+	pub fn cd(&self) -> bool {
+		self.cd
+	}
+
+	/// Check if the Data Set Ready line is asserted.
+	pub fn dsr(&self) -> bool {
+		self.dsr
+	}
+
+	/// Check if the Clear To Send line is asserted.
+	pub fn cts(&self) -> bool {
+		self.cts
+	}
+
+	/// Check if the Ring Indicator line is asserted.
+	pub fn ri(&self) -> bool {
+		self.ri
+	}
+}
+
+/// A serial port handed to a PPP implementation.
+///
+/// See the [module documentation][self] for more information.
+pub struct PppLink {
+	/// The serial port.
+	port: SerialPort,
+
+	/// Received data that has not been consumed yet.
+	buffer: Vec<u8>,
+
+	/// The async control character map used for transmitted frames.
+	tx_accm: u32,
+
+	/// The number of corrupted frames that were discarded.
+	invalid_frames: u64,
+}
+
+impl PppLink {
+	/// Create a new PPP link for a serial port.
+	pub fn new(port: SerialPort) -> Self {
+		Self::with_received(port, Vec::new())
+	}
+
+	/// Create a new PPP link for a serial port, with data that was already received from it.
+	///
+	/// The received data is consumed before any new data is read from the serial port.
+	pub fn with_received(port: SerialPort, received: Vec<u8>) -> Self {
+		Self {
+			port,
+			buffer: received,
+			tx_accm: DEFAULT_ACCM,
+			invalid_frames: 0,
+		}
+	}
+
+	/// Get a reference to the serial port.
+	///
+	/// You should not read from the serial port directly, since that bypasses data that was already received.
+	pub fn port(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Get a mutable reference to the serial port.
+	///
+	/// You should not read from the serial port directly, since that bypasses data that was already received.
+	pub fn port_mut(&mut self) -> &mut SerialPort {
+		&mut self.port
+	}
+
+	/// Set the async control character map used by [`Self::write_frame()`].
+	///
+	/// If bit `n` is set, the byte with value `n` is escaped.
+	/// Use this to apply the ACCM negotiated by LCP.
+	/// The default is [`DEFAULT_ACCM`], which escapes all control characters.
+	pub fn set_tx_accm(&mut self, accm: u32) {
+		self.tx_accm = accm;
+	}
+
+	/// Get the async control character map used by [`Self::write_frame()`].
+	pub fn get_tx_accm(&self) -> u32 {
+		self.tx_accm
+	}
+
+	/// Read the state of the modem control lines.
+	///
+	/// A PPP implementation should treat a lost carrier as the end of the session.
+	pub fn line_state(&self) -> std::io::Result<LineState> {
+		LineState::read(&self.port)
+	}
+
+	/// Get the number of corrupted frames that were discarded by [`Self::read_frame()`].
+	pub fn invalid_frames(&self) -> u64 {
+		self.invalid_frames
+	}
+
+	/// Read raw data from the serial port, without decoding frames.
+	///
+	/// Data that was already received is returned first.
+	pub fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		if self.buffer.is_empty() {
+			return self.port.read(buf);
+		}
+		let len = buf.len().min(self.buffer.len());
+		buf[..len].copy_from_slice(&self.buffer[..len]);
+		self.buffer.drain(..len);
+		Ok(len)
+	}
+
+	/// Write raw data to the serial port, without encoding frames.
+	pub fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
+		self.port.write(buf)
+	}
+
+	/// Read a single frame.
+	///
+	/// This reads from the serial port until a complete frame is received.
+	/// If the read timeout of the serial port expires before a frame is complete, an error is returned.
+	/// The partial frame is kept, so you can call this function again to continue reading.
+	///
+	/// Corrupted frames and data outside of frames are silently discarded, as required by RFC 1662.
+	/// The number of corrupted frames is reported by [`Self::invalid_frames()`].
+	///
+	/// Returns the frame including the address and control fields, but without the frame check sequence.
+	pub fn read_frame(&mut self) -> std::io::Result<Vec<u8>> {
+		let mut chunk = [0; 1024];
+		loop {
+			while !self.buffer.is_empty() {
+				if self.buffer[0] != FLAG {
+					self.discard_frame();
+					continue;
+				}
+				match HdlcFramer.decode(&self.buffer) {
+					Ok(Some((frame, len))) => {
+						self.buffer.drain(..len);
+						return Ok(frame);
+					},
+					Ok(None) => break,
+					Err(_) => {
+						self.invalid_frames += 1;
+						self.discard_frame();
+					},
+				}
+			}
+			match self.port.read(&mut chunk) {
+				Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+				Ok(read) => self.buffer.extend_from_slice(&chunk[..read]),
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Encode and write a single frame.
+	///
+	/// The frame should include the address and control fields.
+	/// The frame check sequence is added by this function.
+	pub fn write_frame(&self, frame: &[u8]) -> std::io::Result<()> {
+		self.port.write_all(&HdlcFramer::encode(frame, self.tx_accm))
+	}
+
+	/// Consume the link and return the serial port and any received data that was not consumed yet.
+	pub fn into_inner(self) -> (SerialPort, Vec<u8>) {
+		(self.port, self.buffer)
+	}
+
+	/// Discard data up to the flag that ends the frame at the start of the buffer.
+	fn discard_frame(&mut self) {
+		let start = self.buffer.iter().position(|&byte| byte != FLAG).unwrap_or(self.buffer.len());
+		let end = self.buffer[start..]
+			.iter()
+			.position(|&byte| byte == FLAG)
+			.map_or(self.buffer.len(), |end| start + end);
+		self.buffer.drain(..end);
+	}
+}
+
+impl std::io::Read for PppLink {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		PppLink::read(self, buf)
+	}
+}
+
+impl std::io::Write for PppLink {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		PppLink::write(self, buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.port.flush()
+	}
+}
+
+/// Update a PPP frame check sequence (FCS-16) with more data.
+fn fcs16(fcs: u16, data: &[u8]) -> u16 {
+	data.iter().fold(fcs, |fcs, &byte| {
+		let mut fcs = fcs ^ u16::from(byte);
+		for _ in 0..8 {
+			fcs = if fcs & 1 != 0 { (fcs >> 1) ^ 0x8408 } else { fcs >> 1 };
+		}
+		fcs
+	})
+}
+
+/// Create an error of the type [`std::io::ErrorKind::InvalidData`].
+fn invalid_data(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,19 @@
+/// The priority of a transaction or write request.
+///
+/// Work with a higher priority is always started before work with a lower priority.
+/// Work with the same priority is started in the order it was submitted.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Priority {
+	/// Background work that may be delayed indefinitely by other work.
+	Low,
+
+	/// The default priority.
+	#[default]
+	Normal,
+
+	/// Work that should be started before normal work.
+	High,
+
+	/// Work that must be started as soon as possible, such as emergency stop commands.
+	Urgent,
+}
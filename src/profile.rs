@@ -0,0 +1,293 @@
+//! Persistence of serial port configurations in profile files.
+//!
+//! A profile file is a text file with one section per device.
+//! Each section starts with the identity of the device in square brackets,
+//! followed by `key = value` lines and a CRC-32 checksum of the section:
+//!
+//! ```text
+//! [usb:0403:6001:A6008CB1]
+//! baud_rate = 115200
+//! char_size = 8
+//! stop_bits = 1
+//! parity = none
+//! flow_control = none
+//! read_timeout_ms = 50
+//! write_timeout_ms = 50
+//! checksum = 89b1bf77
+//! ```
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{CharSize, FlowControl, Parity, SerialPort, StopBits};
+
+/// A section of a profile file.
+struct Section {
+	/// The identity of the device.
+	identity: String,
+
+	/// The `key = value` entries of the section, without the checksum.
+	entries: Vec<(String, String)>,
+}
+
+impl Section {
+	/// Get the value of an entry.
+	fn get(&self, key: &str) -> Option<&str> {
+		self.entries.iter().find(|(name, _)| name == key).map(|(_, value)| value.as_str())
+	}
+
+	/// Parse the value of an entry, if it is present.
+	fn parse<T: std::str::FromStr>(&self, key: &str) -> std::io::Result<Option<T>> {
+		match self.get(key) {
+			None => Ok(None),
+			Some(value) => match value.parse() {
+				Ok(value) => Ok(Some(value)),
+				Err(_) => Err(invalid_data(format!("invalid value for {key:?} in profile: {value:?}"))),
+			},
+		}
+	}
+
+	/// Get the body of the section that is covered by the checksum.
+	fn body(&self) -> String {
+		let mut body = format!("[{}]\n", self.identity);
+		for (key, value) in &self.entries {
+			body.push_str(&format!("{key} = {value}\n"));
+		}
+		body
+	}
+}
+
+/// Save the configuration of a serial port in a profile file.
+pub(crate) fn save(port: &SerialPort, path: &Path) -> std::io::Result<()> {
+	let identity = device_identity(port)?;
+	let settings = port.get_configuration()?;
+	let mut entries = vec![
+		("baud_rate".to_owned(), settings.get_baud_rate()?.to_string()),
+		("char_size".to_owned(), settings.get_char_size()?.to_string()),
+		("stop_bits".to_owned(), settings.get_stop_bits()?.to_string()),
+		("parity".to_owned(), settings.get_parity()?.to_string()),
+		("flow_control".to_owned(), settings.get_flow_control()?.to_string()),
+		("read_timeout_ms".to_owned(), port.get_read_timeout()?.as_millis().to_string()),
+		("write_timeout_ms".to_owned(), port.get_write_timeout()?.as_millis().to_string()),
+	];
+	entries.extend(rs4xx_entries(port));
+
+	let mut sections = match std::fs::read_to_string(path) {
+		Ok(data) => parse(&data)?,
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+		Err(e) => return Err(e),
+	};
+	let section = Section { identity, entries };
+	match sections.iter_mut().find(|existing| existing.identity == section.identity) {
+		Some(existing) => *existing = section,
+		None => sections.push(section),
+	}
+
+	let mut data = String::new();
+	for section in &sections {
+		if !data.is_empty() {
+			data.push('\n');
+		}
+		let body = section.body();
+		data.push_str(&format!("{body}checksum = {:08x}\n", crc32(body.as_bytes())));
+	}
+
+	// Write to a temporary file first, so a crash never leaves a truncated profile behind.
+	let mut temp_path = path.as_os_str().to_owned();
+	temp_path.push(".tmp");
+	std::fs::write(&temp_path, data)?;
+	std::fs::rename(&temp_path, path)
+}
+
+/// Load the configuration of a serial port from a profile file and apply it.
+pub(crate) fn load(port: &mut SerialPort, path: &Path) -> std::io::Result<()> {
+	let identity = device_identity(port)?;
+	let data = std::fs::read_to_string(path)?;
+	let section = parse(&data)?
+		.into_iter()
+		.find(|section| section.identity == identity)
+		.ok_or_else(|| std::io::Error::new(
+			std::io::ErrorKind::NotFound,
+			format!("no profile for device {identity} in {}", path.display()),
+		))?;
+
+	let mut settings = port.get_configuration()?;
+	if let Some(baud_rate) = section.parse("baud_rate")? {
+		settings.set_baud_rate(baud_rate)?;
+	}
+	if let Some(char_size) = section.parse::<u8>("char_size")? {
+		settings.set_char_size(CharSize::try_from(char_size).map_err(|e| invalid_data(e.to_string()))?);
+	}
+	if let Some(stop_bits) = section.parse::<u8>("stop_bits")? {
+		settings.set_stop_bits(StopBits::try_from(stop_bits).map_err(|e| invalid_data(e.to_string()))?);
+	}
+	if let Some(parity) = section.parse::<Parity>("parity")? {
+		settings.set_parity(parity);
+	}
+	if let Some(flow_control) = section.parse::<FlowControl>("flow_control")? {
+		settings.set_flow_control(flow_control);
+	}
+	port.set_configuration(&settings)?;
+	if let Some(timeout) = section.parse("read_timeout_ms")? {
+		port.set_read_timeout(Duration::from_millis(timeout))?;
+	}
+	if let Some(timeout) = section.parse("write_timeout_ms")? {
+		port.set_write_timeout(Duration::from_millis(timeout))?;
+	}
+	#[cfg(all(feature = "rs4xx", target_os = "linux"))]
+	load_rs4xx(port, &section)?;
+	Ok(())
+}
+
+/// Get a stable identity of the device of a serial port.
+///
+/// For USB devices with a serial number, this is `usb:<vendor>:<product>:<serial>`.
+/// Otherwise, it is `path:<device path>`.
+fn device_identity(port: &SerialPort) -> std::io::Result<String> {
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	{
+		use std::os::unix::io::AsRawFd;
+		let device = std::fs::read_link(format!("/proc/self/fd/{}", port.as_raw_fd()))?;
+		if let Some(identity) = device.file_name().and_then(|name| usb_identity(name.to_str()?)) {
+			return Ok(identity);
+		}
+		Ok(format!("path:{}", device.display()))
+	}
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	{
+		let _ = port;
+		Err(std::io::Error::new(
+			std::io::ErrorKind::Unsupported,
+			"device identities are only supported on Linux and Android",
+		))
+	}
+}
+
+/// Get the identity of a USB serial device from sysfs.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn usb_identity(name: &str) -> Option<String> {
+	let device = std::fs::canonicalize(format!("/sys/class/tty/{name}/device")).ok()?;
+	// Walk up to the USB device, which has the vendor and product ID.
+	let usb_device = device.ancestors().find(|dir| dir.join("idVendor").exists())?;
+	let read = |attribute: &str| -> Option<String> {
+		Some(std::fs::read_to_string(usb_device.join(attribute)).ok()?.trim().to_owned())
+	};
+	let serial = read("serial")?;
+	Some(format!("usb:{}:{}:{}", read("idVendor")?, read("idProduct")?, serial))
+}
+
+/// Get the profile entries for the RS-4xx mode of a serial port.
+///
+/// Ports that do not report an RS-4xx mode are saved without one.
+#[cfg(all(feature = "rs4xx", target_os = "linux"))]
+fn rs4xx_entries(port: &SerialPort) -> Vec<(String, String)> {
+	use crate::rs4xx::TransceiverMode;
+	let mut entries = Vec::new();
+	let Ok(mode) = port.get_rs4xx_mode() else {
+		return entries;
+	};
+	match mode {
+		TransceiverMode::Default => entries.push(("rs4xx_mode".to_owned(), "default".to_owned())),
+		TransceiverMode::Rs422 => entries.push(("rs4xx_mode".to_owned(), "rs422".to_owned())),
+		TransceiverMode::Rs485(config) => {
+			entries.push(("rs4xx_mode".to_owned(), "rs485".to_owned()));
+			entries.push(("rs485_full_duplex".to_owned(), config.get_full_duplex().to_string()));
+			entries.push(("rs485_bus_termination".to_owned(), config.get_bus_termination().to_string()));
+			entries.push(("rs485_invert_rts".to_owned(), config.get_invert_rts().to_string()));
+			let before = config.get_delay_before_send().as_millis();
+			let after = config.get_delay_after_send().as_millis();
+			entries.push(("rs485_delay_before_send_ms".to_owned(), before.to_string()));
+			entries.push(("rs485_delay_after_send_ms".to_owned(), after.to_string()));
+		},
+	}
+	entries
+}
+
+/// Get the profile entries for the RS-4xx mode of a serial port.
+#[cfg(not(all(feature = "rs4xx", target_os = "linux")))]
+fn rs4xx_entries(_port: &SerialPort) -> Vec<(String, String)> {
+	Vec::new()
+}
+
+/// Apply the RS-4xx mode from a profile section, if it has one.
+#[cfg(all(feature = "rs4xx", target_os = "linux"))]
+fn load_rs4xx(port: &SerialPort, section: &Section) -> std::io::Result<()> {
+	use crate::rs4xx::{Rs485Config, TransceiverMode};
+	let mode = match section.get("rs4xx_mode") {
+		None => return Ok(()),
+		Some("default") => TransceiverMode::Default,
+		Some("rs422") => TransceiverMode::Rs422,
+		Some("rs485") => {
+			let mut config = Rs485Config::new();
+			config.set_full_duplex(section.parse("rs485_full_duplex")?.unwrap_or(false));
+			config.set_bus_termination(section.parse("rs485_bus_termination")?.unwrap_or(false));
+			config.set_invert_rts(section.parse("rs485_invert_rts")?.unwrap_or(false));
+			let before = section.parse("rs485_delay_before_send_ms")?.unwrap_or(0);
+			let after = section.parse("rs485_delay_after_send_ms")?.unwrap_or(0);
+			config.set_delay_before_send(Duration::from_millis(before));
+			config.set_delay_after_send(Duration::from_millis(after));
+			TransceiverMode::Rs485(config)
+		},
+		Some(other) => return Err(invalid_data(format!("invalid RS-4xx mode in profile: {other:?}"))),
+	};
+	port.set_rs4xx_mode(mode)
+}
+
+/// Parse a profile file and verify the checksum of each section.
+fn parse(data: &str) -> std::io::Result<Vec<Section>> {
+	let mut sections = Vec::new();
+	let mut current: Option<Section> = None;
+	for line in data.lines() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		if let Some(identity) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+			if let Some(section) = current {
+				return Err(invalid_data(format!("missing checksum for device {} in profile", section.identity)));
+			}
+			current = Some(Section {
+				identity: identity.to_owned(),
+				entries: Vec::new(),
+			});
+			continue;
+		}
+		let Some(section) = &mut current else {
+			return Err(invalid_data(format!("entry outside of a device section in profile: {line:?}")));
+		};
+		let Some((key, value)) = line.split_once('=') else {
+			return Err(invalid_data(format!("invalid line in profile: {line:?}")));
+		};
+		let (key, value) = (key.trim(), value.trim());
+		if key != "checksum" {
+			section.entries.push((key.to_owned(), value.to_owned()));
+			continue;
+		}
+		let expected = crc32(section.body().as_bytes());
+		if u32::from_str_radix(value, 16).ok() != Some(expected) {
+			return Err(invalid_data(format!("checksum mismatch for device {} in profile", section.identity)));
+		}
+		sections.extend(current.take());
+	}
+	if let Some(section) = current {
+		return Err(invalid_data(format!("missing checksum for device {} in profile", section.identity)));
+	}
+	Ok(sections)
+}
+
+/// Compute the CRC-32 (IEEE 802.3) of data.
+fn crc32(data: &[u8]) -> u32 {
+	let mut crc = 0xFFFF_FFFF_u32;
+	for &byte in data {
+		crc ^= u32::from(byte);
+		for _ in 0..8 {
+			crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+		}
+	}
+	!crc
+}
+
+/// Create an error of the type [`std::io::ErrorKind::InvalidData`].
+fn invalid_data(message: String) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
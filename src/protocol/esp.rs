@@ -0,0 +1,295 @@
+//! The ESP8266 and ESP32 ROM loader protocol.
+//!
+//! The ROM of Espressif chips contains a serial bootloader that is used by `esptool` to program the flash.
+//! Commands and responses are SLIP encoded packets, read with a [`SlipFramer`].
+//!
+//! Most development boards connect the DTR and RTS lines of the USB serial converter to the boot mode and reset pins of the chip.
+//! [`EspLoader::reset_into_bootloader()`] uses them to start the ROM loader,
+//! after which [`EspLoader::sync()`] synchronizes with it.
+//!
+//! ```no_run
+//! # fn example() -> std::io::Result<()> {
+//! use serial2::SerialPort;
+//! use serial2::protocol::esp::EspLoader;
+//!
+//! # let firmware = Vec::new();
+//! let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+//! let mut loader = EspLoader::new(port)?;
+//! loader.reset_into_bootloader()?;
+//! loader.sync()?;
+//! loader.write_flash(0x1000, &firmware, |written, total| println!("{written}/{total}"))?;
+//! loader.flash_end(true)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::{Duration, Instant};
+
+use crate::framing::{FrameReader, SlipFramer};
+use crate::SerialPort;
+
+/// Commands of the ROM loader.
+mod command {
+	pub const FLASH_BEGIN: u8 = 0x02;
+	pub const FLASH_DATA: u8 = 0x03;
+	pub const FLASH_END: u8 = 0x04;
+	pub const MEM_BEGIN: u8 = 0x05;
+	pub const MEM_END: u8 = 0x06;
+	pub const MEM_DATA: u8 = 0x07;
+	pub const SYNC: u8 = 0x08;
+	pub const WRITE_REG: u8 = 0x09;
+	pub const READ_REG: u8 = 0x0A;
+}
+
+/// The size of the data blocks written to flash by [`EspLoader::write_flash()`].
+pub const FLASH_BLOCK_SIZE: usize = 0x400;
+
+/// The initial value of the checksum of data packets.
+const CHECKSUM_SEED: u8 = 0xEF;
+
+/// The timeout for most commands.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The timeout for a single sync attempt.
+const SYNC_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The number of sync attempts.
+const SYNC_ATTEMPTS: usize = 10;
+
+/// The time needed to erase a megabyte of flash.
+const ERASE_TIME_PER_MB: Duration = Duration::from_secs(30);
+
+/// A client for the ESP ROM loader.
+///
+/// See the [module documentation][self] for more information.
+pub struct EspLoader {
+	/// The reader for response packets.
+	reader: FrameReader<SlipFramer>,
+}
+
+impl EspLoader {
+	/// Create a new ROM loader client.
+	///
+	/// The read timeout of the serial port is changed for use by the client.
+	pub fn new(mut port: SerialPort) -> std::io::Result<Self> {
+		port.set_read_timeout(DEFAULT_TIMEOUT)?;
+		Ok(Self {
+			reader: FrameReader::new(port, SlipFramer),
+		})
+	}
+
+	/// Get a reference to the serial port.
+	pub fn port(&self) -> &SerialPort {
+		self.reader.port()
+	}
+
+	/// Consume the client and return the serial port.
+	pub fn into_inner(self) -> SerialPort {
+		self.reader.into_inner().0
+	}
+
+	/// Reset the chip into the ROM loader using the DTR and RTS lines.
+	///
+	/// This uses the reset circuit found on most development boards,
+	/// where RTS drives the enable pin and DTR drives the boot mode pin through transistors.
+	pub fn reset_into_bootloader(&mut self) -> std::io::Result<()> {
+		let port = self.reader.port();
+		port.set_dtr(false)?;
+		port.set_rts(true)?;
+		std::thread::sleep(Duration::from_millis(100));
+		port.set_dtr(true)?;
+		port.set_rts(false)?;
+		std::thread::sleep(Duration::from_millis(50));
+		port.set_dtr(false)?;
+		port.discard_input_buffer()?;
+		self.reader.discard_buffered();
+		Ok(())
+	}
+
+	/// Reset the chip using the RTS line, which starts the application.
+	pub fn hard_reset(&mut self) -> std::io::Result<()> {
+		let port = self.reader.port();
+		port.set_rts(true)?;
+		std::thread::sleep(Duration::from_millis(100));
+		port.set_rts(false)
+	}
+
+	/// Synchronize with the ROM loader.
+	///
+	/// The sync command is retried a few times, because the ROM loader needs it to detect the baud rate.
+	pub fn sync(&mut self) -> std::io::Result<()> {
+		let mut payload = vec![0x07, 0x07, 0x12, 0x20];
+		payload.resize(36, 0x55);
+		let mut last_error = None;
+		for _ in 0..SYNC_ATTEMPTS {
+			match self.command(command::SYNC, &payload, 0, SYNC_TIMEOUT) {
+				Ok(_) => {
+					// The ROM loader sends multiple responses to a sync command, discard the rest.
+					std::thread::sleep(SYNC_TIMEOUT);
+					self.reader.port().discard_input_buffer()?;
+					self.reader.discard_buffered();
+					return Ok(());
+				},
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => last_error = Some(e),
+				Err(e) => return Err(e),
+			}
+		}
+		Err(last_error.unwrap_or_else(|| std::io::ErrorKind::TimedOut.into()))
+	}
+
+	/// Read a 32 bit register.
+	pub fn read_reg(&mut self, address: u32) -> std::io::Result<u32> {
+		self.command(command::READ_REG, &address.to_le_bytes(), 0, DEFAULT_TIMEOUT)
+	}
+
+	/// Write a 32 bit register.
+	///
+	/// Only the bits set in `mask` are modified.
+	/// The ROM loader waits for `delay_us` microseconds after the write.
+	pub fn write_reg(&mut self, address: u32, value: u32, mask: u32, delay_us: u32) -> std::io::Result<()> {
+		let payload = le_words(&[address, value, mask, delay_us]);
+		self.command(command::WRITE_REG, &payload, 0, DEFAULT_TIMEOUT)?;
+		Ok(())
+	}
+
+	/// Prepare writing `size` bytes to flash at `offset`, in blocks of `block_size` bytes.
+	///
+	/// This erases the affected flash, which may take a while.
+	pub fn flash_begin(&mut self, offset: u32, size: u32, block_size: u32) -> std::io::Result<()> {
+		let blocks = size.div_ceil(block_size);
+		let payload = le_words(&[size, blocks, block_size, offset]);
+		let timeout = DEFAULT_TIMEOUT.max(ERASE_TIME_PER_MB.mul_f64(f64::from(size) / 1_000_000.0));
+		self.command(command::FLASH_BEGIN, &payload, 0, timeout)?;
+		Ok(())
+	}
+
+	/// Write a block of data to flash.
+	///
+	/// The sequence number starts at 0 after [`Self::flash_begin()`].
+	pub fn flash_data(&mut self, sequence: u32, data: &[u8]) -> std::io::Result<()> {
+		self.data_command(command::FLASH_DATA, sequence, data)
+	}
+
+	/// Finish writing to flash.
+	///
+	/// If `reboot` is true, the chip starts the application.
+	/// Otherwise it stays in the ROM loader.
+	pub fn flash_end(&mut self, reboot: bool) -> std::io::Result<()> {
+		let payload = u32::from(!reboot).to_le_bytes();
+		self.command(command::FLASH_END, &payload, 0, DEFAULT_TIMEOUT)?;
+		Ok(())
+	}
+
+	/// Write data to flash at `offset`.
+	///
+	/// The data is written in blocks of [`FLASH_BLOCK_SIZE`] bytes.
+	/// The last block is padded with `0xFF` bytes.
+	/// After each block, `progress` is called with the number of bytes written and the total number of bytes.
+	///
+	/// This does not call [`Self::flash_end()`], so you can write multiple regions before finishing.
+	pub fn write_flash(
+		&mut self,
+		offset: u32,
+		data: &[u8],
+		mut progress: impl FnMut(usize, usize),
+	) -> std::io::Result<()> {
+		let size = u32::try_from(data.len())
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "data too large for flash"))?;
+		self.flash_begin(offset, size, FLASH_BLOCK_SIZE as u32)?;
+		let mut block = Vec::with_capacity(FLASH_BLOCK_SIZE);
+		let mut written = 0;
+		for (sequence, chunk) in data.chunks(FLASH_BLOCK_SIZE).enumerate() {
+			block.clear();
+			block.extend_from_slice(chunk);
+			block.resize(FLASH_BLOCK_SIZE, 0xFF);
+			self.flash_data(sequence as u32, &block)?;
+			written += chunk.len();
+			progress(written, data.len());
+		}
+		Ok(())
+	}
+
+	/// Prepare writing `size` bytes to RAM at `offset`, in blocks of `block_size` bytes.
+	pub fn mem_begin(&mut self, offset: u32, size: u32, block_size: u32) -> std::io::Result<()> {
+		let blocks = size.div_ceil(block_size);
+		let payload = le_words(&[size, blocks, block_size, offset]);
+		self.command(command::MEM_BEGIN, &payload, 0, DEFAULT_TIMEOUT)?;
+		Ok(())
+	}
+
+	/// Write a block of data to RAM.
+	pub fn mem_data(&mut self, sequence: u32, data: &[u8]) -> std::io::Result<()> {
+		self.data_command(command::MEM_DATA, sequence, data)
+	}
+
+	/// Finish writing to RAM and optionally jump to an entry point.
+	pub fn mem_end(&mut self, entry_point: Option<u32>) -> std::io::Result<()> {
+		let payload = le_words(&[u32::from(entry_point.is_none()), entry_point.unwrap_or(0)]);
+		self.command(command::MEM_END, &payload, 0, DEFAULT_TIMEOUT)?;
+		Ok(())
+	}
+
+	/// Send a data command with a sequence number and a checksum.
+	fn data_command(&mut self, command: u8, sequence: u32, data: &[u8]) -> std::io::Result<()> {
+		let mut payload = le_words(&[data.len() as u32, sequence, 0, 0]);
+		payload.extend_from_slice(data);
+		let checksum = data.iter().fold(CHECKSUM_SEED, |checksum, &byte| checksum ^ byte);
+		self.command(command, &payload, checksum.into(), DEFAULT_TIMEOUT)?;
+		Ok(())
+	}
+
+	/// Send a command and wait for the response.
+	///
+	/// Returns the value field of the response.
+	fn command(&mut self, command: u8, payload: &[u8], checksum: u32, timeout: Duration) -> std::io::Result<u32> {
+		let size = u16::try_from(payload.len())
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "command payload too large"))?;
+		let mut packet = Vec::with_capacity(payload.len() + 8);
+		packet.extend_from_slice(&[0x00, command]);
+		packet.extend_from_slice(&size.to_le_bytes());
+		packet.extend_from_slice(&checksum.to_le_bytes());
+		packet.extend_from_slice(payload);
+		self.reader.port().write_all(&SlipFramer::encode(&packet))?;
+
+		let read_timeout = self.reader.port().get_read_timeout()?;
+		let result = self.read_response(command, Instant::now() + timeout);
+		self.reader.port_mut().set_read_timeout(read_timeout)?;
+		result
+	}
+
+	/// Read packets until the response to a command arrives, leaving the read timeout modified.
+	fn read_response(&mut self, command: u8, deadline: Instant) -> std::io::Result<u32> {
+		loop {
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for response"));
+			}
+			self.reader.port_mut().set_read_timeout(remaining)?;
+			let packet = match self.reader.read_frame() {
+				Ok(packet) => packet,
+				Err(e) if e.kind() == std::io::ErrorKind::InvalidData => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) => return Err(e),
+			};
+
+			// Skip packets that are not a response to this command, such as boot messages.
+			if packet.len() < 10 || packet[0] != 0x01 || packet[1] != command {
+				continue;
+			}
+			let value = u32::from_le_bytes([packet[4], packet[5], packet[6], packet[7]]);
+			let (status, error) = (packet[8], packet[9]);
+			if status != 0 {
+				return Err(std::io::Error::other(format!(
+					"ROM loader command 0x{command:02X} failed with error 0x{error:02X}"
+				)));
+			}
+			return Ok(value);
+		}
+	}
+}
+
+/// Serialize a list of words as little endian bytes.
+fn le_words(words: &[u32]) -> Vec<u8> {
+	words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
@@ -0,0 +1,583 @@
+//! A host implementation of the Firmata protocol.
+//!
+//! Firmata is used to control microcontroller boards such as the Arduino from a host computer.
+//! A [`Firmata`] client sends commands to the board and keeps track of the pin values reported by the board.
+//!
+//! Reporting of digital ports and analog channels is enabled with
+//! [`Firmata::report_digital()`] and [`Firmata::report_analog()`].
+//! Reports are processed by [`Firmata::read_message()`], after which the latest values are available from
+//! [`Firmata::digital_read()`] and [`Firmata::analog_read()`].
+//!
+//! ```no_run
+//! # fn example() -> std::io::Result<()> {
+//! use serial2::SerialPort;
+//! use serial2::protocol::firmata::{Firmata, PinMode};
+//! use std::time::Duration;
+//!
+//! let port = SerialPort::open("/dev/ttyACM0", 57600)?;
+//! let mut firmata = Firmata::new(port);
+//! let version = firmata.negotiate(Duration::from_secs(3))?;
+//! println!("protocol version: {}.{}", version.major(), version.minor());
+//! firmata.set_pin_mode(13, PinMode::Output)?;
+//! firmata.digital_write(13, true)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::{Duration, Instant};
+
+use crate::framing::{FrameReader, Framer};
+use crate::SerialPort;
+
+/// Digital I/O message for a port of 8 pins.
+const DIGITAL_MESSAGE: u8 = 0x90;
+
+/// Analog I/O message for a pin or channel.
+const ANALOG_MESSAGE: u8 = 0xE0;
+
+/// Enable or disable reporting of an analog channel.
+const REPORT_ANALOG: u8 = 0xC0;
+
+/// Enable or disable reporting of a digital port.
+const REPORT_DIGITAL: u8 = 0xD0;
+
+/// Set the mode of a pin.
+const SET_PIN_MODE: u8 = 0xF4;
+
+/// Set the value of a single digital pin.
+const SET_DIGITAL_PIN_VALUE: u8 = 0xF5;
+
+/// Request or report the protocol version.
+const REPORT_VERSION: u8 = 0xF9;
+
+/// Reset the board.
+const SYSTEM_RESET: u8 = 0xFF;
+
+/// Start of a sysex message.
+const START_SYSEX: u8 = 0xF0;
+
+/// End of a sysex message.
+const END_SYSEX: u8 = 0xF7;
+
+/// Sysex command to request or report the firmware name and version.
+const REPORT_FIRMWARE: u8 = 0x79;
+
+/// Sysex command to request the capabilities of all pins.
+const CAPABILITY_QUERY: u8 = 0x6B;
+
+/// Sysex command with the capabilities of all pins.
+const CAPABILITY_RESPONSE: u8 = 0x6C;
+
+/// Sysex command to write an analog value to any pin.
+const EXTENDED_ANALOG: u8 = 0x6F;
+
+/// Sysex command to set the sampling interval.
+const SAMPLING_INTERVAL: u8 = 0x7A;
+
+/// The mode of a pin.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PinMode {
+	/// Digital input.
+	Input,
+
+	/// Digital output.
+	Output,
+
+	/// Analog input.
+	Analog,
+
+	/// PWM output.
+	Pwm,
+
+	/// Servo control.
+	Servo,
+
+	/// Shift register.
+	Shift,
+
+	/// I2C bus.
+	I2c,
+
+	/// OneWire bus.
+	OneWire,
+
+	/// Stepper motor control.
+	Stepper,
+
+	/// Rotary encoder.
+	Encoder,
+
+	/// Serial port.
+	Serial,
+
+	/// Digital input with the internal pull-up resistor enabled.
+	InputPullup,
+}
+
+/// A Firmata protocol version.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Version {
+	/// The major version.
+	major: u8,
+
+	/// The minor version.
+	minor: u8,
+}
+
+/// The name and version of the firmware of a board.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Firmware {
+	/// The version of the firmware.
+	version: Version,
+
+	/// The name of the firmware.
+	name: String,
+}
+
+/// A message received from a board.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Message {
+	/// The protocol version of the board.
+	ProtocolVersion(Version),
+
+	/// The values of the pins of a digital port.
+	DigitalPort {
+		/// The port number: pin `n` is bit `n % 8` of port `n / 8`.
+		port: u8,
+
+		/// The value of the pins, one bit per pin.
+		value: u8,
+	},
+
+	/// The value of an analog channel.
+	Analog {
+		/// The analog channel.
+		channel: u8,
+
+		/// The value of the channel.
+		value: u16,
+	},
+
+	/// A sysex message.
+	Sysex {
+		/// The sysex command.
+		command: u8,
+
+		/// The payload of the message, without the command byte.
+		data: Vec<u8>,
+	},
+}
+
+/// A framer for messages sent by a Firmata board.
+///
+/// This can be used with a [`FrameReader`] to read raw messages without the state tracking of [`Firmata`].
+#[derive(Debug, Default, Copy, Clone)]
+pub struct FirmataFramer;
+
+/// A Firmata client.
+///
+/// See the [module documentation][self] for more information.
+pub struct Firmata {
+	/// The reader for messages from the board.
+	reader: FrameReader<FirmataFramer>,
+
+	/// The protocol version reported by the board.
+	protocol_version: Option<Version>,
+
+	/// The last reported values of the digital ports.
+	digital_ports: [u8; 16],
+
+	/// The last reported values of the analog channels.
+	analog_channels: [Option<u16>; 16],
+}
+
+impl PinMode {
+	/// Get the mode from the value used in the protocol.
+	pub fn from_u8(value: u8) -> Option<Self> {
+		match value {
+			0x00 => Some(Self::Input),
+			0x01 => Some(Self::Output),
+			0x02 => Some(Self::Analog),
+			0x03 => Some(Self::Pwm),
+			0x04 => Some(Self::Servo),
+			0x05 => Some(Self::Shift),
+			0x06 => Some(Self::I2c),
+			0x07 => Some(Self::OneWire),
+			0x08 => Some(Self::Stepper),
+			0x09 => Some(Self::Encoder),
+			0x0A => Some(Self::Serial),
+			0x0B => Some(Self::InputPullup),
+			_ => None,
+		}
+	}
+
+	/// Get the value used in the protocol for the mode.
+	pub fn to_u8(self) -> u8 {
+		match self {
+			Self::Input => 0x00,
+			Self::Output => 0x01,
+			Self::Analog => 0x02,
+			Self::Pwm => 0x03,
+			Self::Servo => 0x04,
+			Self::Shift => 0x05,
+			Self::I2c => 0x06,
+			Self::OneWire => 0x07,
+			Self::Stepper => 0x08,
+			Self::Encoder => 0x09,
+			Self::Serial => 0x0A,
+			Self::InputPullup => 0x0B,
+		}
+	}
+}
+
+impl Version {
+	/// Create a new version.
+	pub fn new(major: u8, minor: u8) -> Self {
+		Self { major, minor }
+	}
+
+	/// Get the major version.
+	pub fn major(&self) -> u8 {
+		self.major
+	}
+
+	/// Get the minor version.
+	pub fn minor(&self) -> u8 {
+		self.minor
+	}
+}
+
+impl Firmware {
+	/// Get the version of the firmware.
+	pub fn version(&self) -> Version {
+		self.version
+	}
+
+	/// Get the name of the firmware.
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+}
+
+impl Framer for FirmataFramer {
+	type Frame = Message;
+
+	fn decode(&mut self, data: &[u8]) -> std::io::Result<Option<(Message, usize)>> {
+		let command = data[0];
+		match command {
+			START_SYSEX => {
+				let end = match data[1..].iter().position(|&byte| byte & 0x80 != 0) {
+					Some(index) => index + 1,
+					None => return Ok(None),
+				};
+				if data[end] != END_SYSEX || end < 2 {
+					return Err(invalid_data("malformed sysex message"));
+				}
+				let message = Message::Sysex {
+					command: data[1],
+					data: data[2..end].to_vec(),
+				};
+				Ok(Some((message, end + 1)))
+			},
+			REPORT_VERSION => {
+				let Some(args) = read_args(data)? else { return Ok(None) };
+				Ok(Some((Message::ProtocolVersion(Version::new(args[0], args[1])), 3)))
+			},
+			_ if command & 0xF0 == DIGITAL_MESSAGE => {
+				let Some(args) = read_args(data)? else { return Ok(None) };
+				let message = Message::DigitalPort {
+					port: command & 0x0F,
+					value: decode_u14(args[0], args[1]) as u8,
+				};
+				Ok(Some((message, 3)))
+			},
+			_ if command & 0xF0 == ANALOG_MESSAGE => {
+				let Some(args) = read_args(data)? else { return Ok(None) };
+				let message = Message::Analog {
+					channel: command & 0x0F,
+					value: decode_u14(args[0], args[1]),
+				};
+				Ok(Some((message, 3)))
+			},
+			_ => Err(invalid_data("unexpected byte in Firmata stream")),
+		}
+	}
+}
+
+impl Firmata {
+	/// Create a new Firmata client.
+	pub fn new(port: SerialPort) -> Self {
+		Self {
+			reader: FrameReader::new(port, FirmataFramer),
+			protocol_version: None,
+			digital_ports: [0; 16],
+			analog_channels: [None; 16],
+		}
+	}
+
+	/// Get a reference to the serial port.
+	pub fn port(&self) -> &SerialPort {
+		self.reader.port()
+	}
+
+	/// Consume the client and return the serial port.
+	pub fn into_inner(self) -> SerialPort {
+		self.reader.into_inner().0
+	}
+
+	/// Request the protocol version of the board and wait for the response.
+	///
+	/// Many boards reset when the serial port is opened, so the timeout should allow for the board to boot.
+	/// Other messages received while waiting are processed normally.
+	pub fn negotiate(&mut self, timeout: Duration) -> std::io::Result<Version> {
+		self.write(&[REPORT_VERSION])?;
+		self.wait_for(timeout, |message| match message {
+			Message::ProtocolVersion(version) => Some(*version),
+			_ => None,
+		})
+	}
+
+	/// Get the protocol version reported by the board, if any.
+	pub fn protocol_version(&self) -> Option<Version> {
+		self.protocol_version
+	}
+
+	/// Request the name and version of the firmware and wait for the response.
+	pub fn query_firmware(&mut self, timeout: Duration) -> std::io::Result<Firmware> {
+		self.send_sysex(REPORT_FIRMWARE, &[])?;
+		self.wait_for(timeout, |message| match message {
+			Message::Sysex { command: REPORT_FIRMWARE, data } if data.len() >= 2 => Some(Firmware {
+				version: Version::new(data[0], data[1]),
+				name: decode_string(&data[2..]),
+			}),
+			_ => None,
+		})
+	}
+
+	/// Request the capabilities of all pins and wait for the response.
+	///
+	/// The result has one entry per pin, with the supported modes and the resolution of each mode.
+	/// Modes not known by this library are left out.
+	pub fn query_capabilities(&mut self, timeout: Duration) -> std::io::Result<Vec<Vec<(PinMode, u8)>>> {
+		self.send_sysex(CAPABILITY_QUERY, &[])?;
+		self.wait_for(timeout, |message| match message {
+			Message::Sysex { command: CAPABILITY_RESPONSE, data } => Some(decode_capabilities(data)),
+			_ => None,
+		})
+	}
+
+	/// Set the mode of a pin.
+	pub fn set_pin_mode(&mut self, pin: u8, mode: PinMode) -> std::io::Result<()> {
+		check_u7(pin, "pin")?;
+		self.write(&[SET_PIN_MODE, pin, mode.to_u8()])
+	}
+
+	/// Set the value of a digital output pin.
+	pub fn digital_write(&mut self, pin: u8, value: bool) -> std::io::Result<()> {
+		check_u7(pin, "pin")?;
+		self.write(&[SET_DIGITAL_PIN_VALUE, pin, value.into()])
+	}
+
+	/// Write an analog value to a pin, such as the duty cycle of a PWM pin or the position of a servo.
+	///
+	/// Values up to 14 bits are supported.
+	pub fn analog_write(&mut self, pin: u8, value: u16) -> std::io::Result<()> {
+		check_u7(pin, "pin")?;
+		if value >= 1 << 14 {
+			return Err(invalid_input("analog value exceeds 14 bits"));
+		}
+		let [lsb, msb] = encode_u14(value);
+		if pin < 16 {
+			self.write(&[ANALOG_MESSAGE | pin, lsb, msb])
+		} else {
+			self.send_sysex(EXTENDED_ANALOG, &[pin, lsb, msb])
+		}
+	}
+
+	/// Enable or disable reporting of a digital port.
+	///
+	/// Pin `n` belongs to port `n / 8`.
+	pub fn report_digital(&mut self, port: u8, enable: bool) -> std::io::Result<()> {
+		if port >= 16 {
+			return Err(invalid_input("digital port out of range"));
+		}
+		self.write(&[REPORT_DIGITAL | port, enable.into()])
+	}
+
+	/// Enable or disable reporting of an analog channel.
+	pub fn report_analog(&mut self, channel: u8, enable: bool) -> std::io::Result<()> {
+		if channel >= 16 {
+			return Err(invalid_input("analog channel out of range"));
+		}
+		self.write(&[REPORT_ANALOG | channel, enable.into()])
+	}
+
+	/// Set the interval between analog reports.
+	pub fn set_sampling_interval(&mut self, interval: Duration) -> std::io::Result<()> {
+		let millis = interval.as_millis();
+		if millis >= 1 << 14 {
+			return Err(invalid_input("sampling interval too large"));
+		}
+		self.send_sysex(SAMPLING_INTERVAL, &encode_u14(millis as u16))
+	}
+
+	/// Send a sysex message.
+	///
+	/// The command and all data bytes must fit in 7 bits.
+	pub fn send_sysex(&mut self, command: u8, data: &[u8]) -> std::io::Result<()> {
+		check_u7(command, "sysex command")?;
+		if data.iter().any(|&byte| byte & 0x80 != 0) {
+			return Err(invalid_input("sysex data bytes must fit in 7 bits"));
+		}
+		let mut message = Vec::with_capacity(data.len() + 3);
+		message.push(START_SYSEX);
+		message.push(command);
+		message.extend_from_slice(data);
+		message.push(END_SYSEX);
+		self.write(&message)
+	}
+
+	/// Reset the board.
+	///
+	/// This also forgets all reported pin values.
+	pub fn system_reset(&mut self) -> std::io::Result<()> {
+		self.digital_ports = [0; 16];
+		self.analog_channels = [None; 16];
+		self.write(&[SYSTEM_RESET])
+	}
+
+	/// Read and process a single message from the board.
+	///
+	/// This uses the read timeout of the serial port.
+	/// Reported pin values are recorded before the message is returned.
+	pub fn read_message(&mut self) -> std::io::Result<Message> {
+		let message = self.reader.read_frame()?;
+		match &message {
+			Message::ProtocolVersion(version) => self.protocol_version = Some(*version),
+			Message::DigitalPort { port, value } => self.digital_ports[usize::from(*port)] = *value,
+			Message::Analog { channel, value } => self.analog_channels[usize::from(*channel)] = Some(*value),
+			Message::Sysex { .. } => (),
+		}
+		Ok(message)
+	}
+
+	/// Get the last reported value of a digital pin.
+	///
+	/// Returns `false` if no value was reported for the pin.
+	pub fn digital_read(&self, pin: u8) -> bool {
+		match self.digital_ports.get(usize::from(pin / 8)) {
+			Some(port) => port & (1 << (pin % 8)) != 0,
+			None => false,
+		}
+	}
+
+	/// Get the last reported value of an analog channel, if any.
+	pub fn analog_read(&self, channel: u8) -> Option<u16> {
+		self.analog_channels.get(usize::from(channel)).copied().flatten()
+	}
+
+	/// Write raw bytes to the board.
+	fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+		self.reader.port().write_all(data)
+	}
+
+	/// Process messages until `matches` returns a value or the timeout expires.
+	fn wait_for<T>(&mut self, timeout: Duration, matches: impl FnMut(&Message) -> Option<T>) -> std::io::Result<T> {
+		let read_timeout = self.reader.port().get_read_timeout()?;
+		let result = self.wait_for_inner(Instant::now() + timeout, matches);
+		self.reader.port_mut().set_read_timeout(read_timeout)?;
+		result
+	}
+
+	/// Process messages until `matches` returns a value or the deadline passes, leaving the read timeout modified.
+	fn wait_for_inner<T>(
+		&mut self,
+		deadline: Instant,
+		mut matches: impl FnMut(&Message) -> Option<T>,
+	) -> std::io::Result<T> {
+		loop {
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for response"));
+			}
+			self.reader.port_mut().set_read_timeout(remaining)?;
+			match self.read_message() {
+				Ok(message) => {
+					if let Some(value) = matches(&message) {
+						return Ok(value);
+					}
+				},
+				Err(e) if e.kind() == std::io::ErrorKind::InvalidData => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+}
+
+/// Get the two argument bytes of a three byte message, if they have been received.
+fn read_args(data: &[u8]) -> std::io::Result<Option<[u8; 2]>> {
+	if data[1..].iter().take(2).any(|&byte| byte & 0x80 != 0) {
+		return Err(invalid_data("truncated Firmata message"));
+	}
+	match data {
+		[_, a, b, ..] => Ok(Some([*a, *b])),
+		_ => Ok(None),
+	}
+}
+
+/// Decode a 14 bit value from two 7 bit bytes.
+fn decode_u14(lsb: u8, msb: u8) -> u16 {
+	u16::from(lsb & 0x7F) | u16::from(msb & 0x7F) << 7
+}
+
+/// Encode a 14 bit value as two 7 bit bytes.
+fn encode_u14(value: u16) -> [u8; 2] {
+	[(value & 0x7F) as u8, (value >> 7 & 0x7F) as u8]
+}
+
+/// Decode a string sent as pairs of 7 bit bytes.
+fn decode_string(data: &[u8]) -> String {
+	data.chunks(2)
+		.map(|pair| decode_u14(pair[0], pair.get(1).copied().unwrap_or(0)))
+		.map(|c| char::from_u32(c.into()).unwrap_or(char::REPLACEMENT_CHARACTER))
+		.collect()
+}
+
+/// Decode the payload of a capability response.
+fn decode_capabilities(data: &[u8]) -> Vec<Vec<(PinMode, u8)>> {
+	// Each pin is a list of (mode, resolution) pairs terminated by 0x7F.
+	let mut pins = Vec::new();
+	for pin in data.split(|&byte| byte == 0x7F) {
+		let modes = pin
+			.chunks_exact(2)
+			.filter_map(|pair| Some((PinMode::from_u8(pair[0])?, pair[1])))
+			.collect();
+		pins.push(modes);
+	}
+	// The response ends with a terminator, so the split yields an empty trailing entry.
+	if data.last() == Some(&0x7F) {
+		pins.pop();
+	}
+	pins
+}
+
+/// Check that a value fits in 7 bits.
+fn check_u7(value: u8, what: &str) -> std::io::Result<()> {
+	if value & 0x80 != 0 {
+		Err(invalid_input(&format!("{what} must fit in 7 bits")))
+	} else {
+		Ok(())
+	}
+}
+
+/// Create an error of the type [`std::io::ErrorKind::InvalidInput`].
+fn invalid_input(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+/// Create an error of the type [`std::io::ErrorKind::InvalidData`].
+fn invalid_data(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,384 @@
+//! Streaming G-code to CNC controllers and 3D printers.
+//!
+//! Firmware such as GRBL and Marlin acknowledges every line of G-code with `ok` or an error.
+//! A [`GcodeStreamer`] keeps a queue of lines and sends them as fast as the firmware allows,
+//! using one of two flow control protocols:
+//!
+//! * [`Protocol::SendResponse`]: send a line and wait for the response before sending the next line.
+//!   This works with all firmware.
+//! * [`Protocol::CharacterCounting`]: keep track of the number of bytes in the receive buffer of the firmware,
+//!   and send lines as long as they fit in the buffer. This is the fast streaming protocol of GRBL.
+//!
+//! Streaming can be paused and resumed on the host with [`GcodeStreamer::pause()`] and [`GcodeStreamer::resume()`].
+//! Realtime commands such as a feed hold or a status query are sent immediately with [`GcodeStreamer::realtime()`],
+//! bypassing the queue.
+//!
+//! ```no_run
+//! # fn example() -> std::io::Result<()> {
+//! use serial2::SerialPort;
+//! use serial2::protocol::gcode::{GcodeStreamer, Protocol};
+//! use std::time::Duration;
+//!
+//! let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+//! let mut streamer = GcodeStreamer::new(port, Protocol::CharacterCounting { buffer_size: 128 });
+//! streamer.push_line("G21");
+//! streamer.push_line("G0 X10 Y10 ; move to the start");
+//! streamer.wait_idle(Duration::from_secs(60))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::framing::{FrameReader, Framer};
+use crate::SerialPort;
+
+/// The flow control protocol used to stream lines.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Protocol {
+	/// Wait for the response to each line before sending the next line.
+	SendResponse,
+
+	/// Send lines as long as they fit in the receive buffer of the firmware.
+	CharacterCounting {
+		/// The size of the receive buffer of the firmware in bytes (128 for GRBL).
+		buffer_size: usize,
+	},
+}
+
+/// A realtime command.
+///
+/// Realtime commands are single bytes that are executed immediately by GRBL,
+/// even if the receive buffer of the firmware is full.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Realtime {
+	/// Request a status report (`?`).
+	StatusQuery,
+
+	/// Pause motion (`!`).
+	FeedHold,
+
+	/// Resume motion after a feed hold (`~`).
+	CycleStart,
+
+	/// Reset the controller (`Ctrl-X`).
+	SoftReset,
+}
+
+/// A line received from the firmware.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Response {
+	/// The oldest unacknowledged line was accepted, with any additional text on the same line.
+	Ok(String),
+
+	/// The oldest unacknowledged line was rejected, with the error message.
+	Error(String),
+
+	/// The controller entered the alarm state, with the alarm message.
+	Alarm(String),
+
+	/// A status report, without the surrounding angle brackets.
+	Status(String),
+
+	/// Any other message.
+	Message(String),
+}
+
+/// A line of G-code that was rejected by the firmware.
+///
+/// When the firmware rejects a line, [`GcodeStreamer::poll()`] returns an [`std::io::Error`]
+/// of the type [`std::io::ErrorKind::Other`] that wraps a value of this type.
+/// You can retrieve it with [`std::io::Error::get_ref()`] followed by `downcast_ref::<CommandError>()`.
+#[derive(Debug, Clone)]
+pub struct CommandError {
+	/// The rejected line.
+	line: String,
+
+	/// The error message of the firmware.
+	message: String,
+}
+
+/// Streams G-code to a controller.
+///
+/// See the [module documentation][self] for more information.
+pub struct GcodeStreamer {
+	/// The reader for responses from the firmware.
+	reader: FrameReader<LineFramer>,
+
+	/// The flow control protocol.
+	protocol: Protocol,
+
+	/// The lines that have not been sent yet.
+	queue: VecDeque<String>,
+
+	/// The lines that have been sent but not acknowledged yet.
+	in_flight: VecDeque<String>,
+
+	/// The number of bytes in the unacknowledged lines, including the newlines.
+	in_flight_bytes: usize,
+
+	/// True if streaming is paused.
+	paused: bool,
+}
+
+/// A framer that splits received data into trimmed lines.
+pub(crate) struct LineFramer;
+
+impl Realtime {
+	/// Get the byte that represents the command.
+	pub fn to_u8(self) -> u8 {
+		match self {
+			Self::StatusQuery => b'?',
+			Self::FeedHold => b'!',
+			Self::CycleStart => b'~',
+			Self::SoftReset => 0x18,
+		}
+	}
+}
+
+impl Response {
+	/// Parse a response line.
+	pub fn parse(line: &str) -> Self {
+		let line = line.trim();
+		let lower = line.to_ascii_lowercase();
+		if lower == "ok" || lower.starts_with("ok ") {
+			Self::Ok(line[2..].trim_start().into())
+		} else if lower.starts_with("error") {
+			Self::Error(strip_label(line, 5))
+		} else if lower.starts_with("alarm") {
+			Self::Alarm(strip_label(line, 5))
+		} else if line.starts_with('<') && line.ends_with('>') {
+			Self::Status(line[1..line.len() - 1].into())
+		} else {
+			Self::Message(line.into())
+		}
+	}
+}
+
+impl CommandError {
+	/// Get the rejected line.
+	pub fn line(&self) -> &str {
+		&self.line
+	}
+
+	/// Get the error message of the firmware.
+	pub fn message(&self) -> &str {
+		&self.message
+	}
+}
+
+impl std::fmt::Display for CommandError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "G-code line {:?} rejected: {}", self.line, self.message)
+	}
+}
+
+impl std::error::Error for CommandError {}
+
+impl GcodeStreamer {
+	/// Create a new G-code streamer.
+	pub fn new(port: SerialPort, protocol: Protocol) -> Self {
+		Self {
+			reader: FrameReader::new(port, LineFramer),
+			protocol,
+			queue: VecDeque::new(),
+			in_flight: VecDeque::new(),
+			in_flight_bytes: 0,
+			paused: false,
+		}
+	}
+
+	/// Get a reference to the serial port.
+	pub fn port(&self) -> &SerialPort {
+		self.reader.port()
+	}
+
+	/// Consume the streamer and return the serial port.
+	///
+	/// Queued lines are discarded.
+	pub fn into_inner(self) -> SerialPort {
+		self.reader.into_inner().0
+	}
+
+	/// Add a line to the queue.
+	///
+	/// Comments and surrounding whitespace are removed.
+	/// Lines without a command are ignored.
+	///
+	/// Lines are only sent by [`Self::poll()`] and [`Self::wait_idle()`].
+	pub fn push_line(&mut self, line: &str) {
+		let line = strip_comments(line);
+		if !line.is_empty() {
+			self.queue.push_back(line);
+		}
+	}
+
+	/// Get the number of lines that have not been sent yet.
+	pub fn queued(&self) -> usize {
+		self.queue.len()
+	}
+
+	/// Get the number of lines that have been sent but not acknowledged yet.
+	pub fn in_flight(&self) -> usize {
+		self.in_flight.len()
+	}
+
+	/// Discard all lines that have not been sent yet.
+	pub fn discard_queue(&mut self) {
+		self.queue.clear();
+	}
+
+	/// Stop sending queued lines.
+	///
+	/// Lines that have already been sent are still executed by the firmware.
+	/// To stop motion immediately, also send [`Realtime::FeedHold`].
+	pub fn pause(&mut self) {
+		self.paused = true;
+	}
+
+	/// Continue sending queued lines.
+	pub fn resume(&mut self) {
+		self.paused = false;
+	}
+
+	/// Check if streaming is paused.
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	/// Send a realtime command immediately, bypassing the queue.
+	pub fn realtime(&self, command: Realtime) -> std::io::Result<()> {
+		self.reader.port().write_all(&[command.to_u8()])
+	}
+
+	/// Send queued lines and process a single response.
+	///
+	/// This waits for a response using the read timeout of the serial port.
+	/// If the timeout expires, `None` is returned.
+	///
+	/// If the firmware rejected a line, an error with a [`CommandError`] payload is returned.
+	/// Streaming of the remaining lines continues on the next call unless you discard the queue.
+	pub fn poll(&mut self) -> std::io::Result<Option<Response>> {
+		self.send_queued()?;
+		let line = loop {
+			match self.reader.read_frame() {
+				Ok(line) => break line,
+				// Empty lines are reported as invalid data by the framer.
+				Err(e) if e.kind() == std::io::ErrorKind::InvalidData => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => return Ok(None),
+				Err(e) => return Err(e),
+			}
+		};
+		let response = Response::parse(&line);
+		match &response {
+			Response::Ok(_) => {
+				self.acknowledge();
+			},
+			Response::Error(message) => {
+				let line = self.acknowledge().unwrap_or_default();
+				let message = message.clone();
+				return Err(std::io::Error::other(CommandError { line, message }));
+			},
+			_ => (),
+		}
+		self.send_queued()?;
+		Ok(Some(response))
+	}
+
+	/// Stream all queued lines and wait until they have been acknowledged.
+	///
+	/// If the firmware rejects a line, an error with a [`CommandError`] payload is returned immediately.
+	/// If streaming is paused, this only waits for the lines that have already been sent.
+	pub fn wait_idle(&mut self, timeout: Duration) -> std::io::Result<()> {
+		let read_timeout = self.reader.port().get_read_timeout()?;
+		let result = self.wait_idle_inner(Instant::now() + timeout);
+		self.reader.port_mut().set_read_timeout(read_timeout)?;
+		result
+	}
+
+	/// Wait until all lines have been acknowledged, leaving the read timeout modified.
+	fn wait_idle_inner(&mut self, deadline: Instant) -> std::io::Result<()> {
+		loop {
+			if self.in_flight.is_empty() && (self.paused || self.queue.is_empty()) {
+				return Ok(());
+			}
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while streaming G-code"));
+			}
+			self.reader.port_mut().set_read_timeout(remaining)?;
+			self.poll()?;
+		}
+	}
+
+	/// Send as many queued lines as the protocol allows.
+	fn send_queued(&mut self) -> std::io::Result<()> {
+		while !self.paused {
+			let Some(line) = self.queue.front() else { break };
+			let len = line.len() + 1;
+			let fits = match self.protocol {
+				Protocol::SendResponse => self.in_flight.is_empty(),
+				// Always allow one line, even if it is larger than the buffer.
+				Protocol::CharacterCounting { buffer_size } => {
+					self.in_flight.is_empty() || self.in_flight_bytes + len <= buffer_size
+				},
+			};
+			if !fits {
+				break;
+			}
+			let mut data = Vec::with_capacity(len);
+			data.extend_from_slice(line.as_bytes());
+			data.push(b'\n');
+			self.reader.port().write_all(&data)?;
+			if let Some(line) = self.queue.pop_front() {
+				self.in_flight_bytes += len;
+				self.in_flight.push_back(line);
+			}
+		}
+		Ok(())
+	}
+
+	/// Remove the oldest unacknowledged line.
+	fn acknowledge(&mut self) -> Option<String> {
+		let line = self.in_flight.pop_front()?;
+		self.in_flight_bytes -= line.len() + 1;
+		Some(line)
+	}
+}
+
+impl Framer for LineFramer {
+	type Frame = String;
+
+	fn decode(&mut self, data: &[u8]) -> std::io::Result<Option<(String, usize)>> {
+		let Some(end) = data.iter().position(|&byte| byte == b'\n') else { return Ok(None) };
+		let line = String::from_utf8_lossy(&data[..end]).trim().to_string();
+		if line.is_empty() {
+			// Skip empty lines, such as the second half of a CRLF line ending.
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "empty line"));
+		}
+		Ok(Some((line, end + 1)))
+	}
+}
+
+/// Remove comments and surrounding whitespace from a line of G-code.
+fn strip_comments(line: &str) -> String {
+	let mut result = String::with_capacity(line.len());
+	let mut in_parens = false;
+	for c in line.chars() {
+		match c {
+			';' if !in_parens => break,
+			'(' => in_parens = true,
+			')' if in_parens => in_parens = false,
+			_ if !in_parens => result.push(c),
+			_ => (),
+		}
+	}
+	result.trim().to_string()
+}
+
+/// Remove a label such as `error` or `ALARM` and the following colon from a response.
+fn strip_label(line: &str, label_len: usize) -> String {
+	let rest = &line[label_len..];
+	rest.strip_prefix(':').unwrap_or(rest).trim().into()
+}
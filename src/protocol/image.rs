@@ -0,0 +1,416 @@
+//! Streaming parsers for firmware images in the Intel HEX and Motorola S-record formats.
+//!
+//! The [`IntelHexReader`] and [`SrecReader`] read a firmware image line by line
+//! and produce [`Chunk`]s of contiguous data with a bounded size.
+//! Only one chunk is kept in memory at a time, so arbitrarily large images can be streamed to a bootloader.
+//!
+//! The chunks can be passed directly to [`Stm32Bootloader::write_image()`][super::stm32::Stm32Bootloader::write_image].
+//!
+//! ```no_run
+//! # fn example() -> std::io::Result<()> {
+//! use serial2::protocol::image::IntelHexReader;
+//! use serial2::protocol::stm32::Stm32Bootloader;
+//! use std::io::BufReader;
+//! use std::time::Duration;
+//!
+//! let file = std::fs::File::open("firmware.hex")?;
+//! let mut image = IntelHexReader::new(BufReader::new(file));
+//!
+//! let mut bootloader = Stm32Bootloader::open("/dev/ttyUSB0", 115200)?;
+//! bootloader.connect()?;
+//! bootloader.erase_all(Duration::from_secs(30))?;
+//! bootloader.write_image(image.by_ref(), |written| println!("{written} bytes written"))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+/// The default maximum size of a chunk, which matches the maximum transfer size of the STM32 bootloader.
+pub const DEFAULT_CHUNK_SIZE: usize = 256;
+
+/// A block of contiguous data from a firmware image.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Chunk {
+	/// The address of the first byte.
+	address: u32,
+
+	/// The data.
+	data: Vec<u8>,
+}
+
+/// A streaming parser for Intel HEX files.
+///
+/// The reader is an iterator over the [`Chunk`]s in the file.
+/// Parse errors are reported as errors of the type [`std::io::ErrorKind::InvalidData`].
+pub struct IntelHexReader<R> {
+	/// The common state of the parser.
+	parser: Parser<R>,
+
+	/// The base address set by extended address records.
+	base_address: u32,
+}
+
+/// A streaming parser for Motorola S-record files.
+///
+/// The reader is an iterator over the [`Chunk`]s in the file.
+/// Parse errors are reported as errors of the type [`std::io::ErrorKind::InvalidData`].
+pub struct SrecReader<R> {
+	/// The common state of the parser.
+	parser: Parser<R>,
+}
+
+/// The state shared by the Intel HEX and S-record parsers.
+struct Parser<R> {
+	/// The input stream.
+	input: R,
+
+	/// The line buffer.
+	line: String,
+
+	/// The number of the current line.
+	line_number: usize,
+
+	/// The number of bytes read from the input.
+	bytes_read: u64,
+
+	/// The start address of the image, if specified.
+	start_address: Option<u32>,
+
+	/// True if the end of the image was reached.
+	done: bool,
+
+	/// The collector for chunks.
+	chunks: ChunkBuilder,
+}
+
+/// Collects data into chunks of contiguous data.
+struct ChunkBuilder {
+	/// The maximum size of a chunk.
+	chunk_size: usize,
+
+	/// The chunk being filled.
+	current: Option<Chunk>,
+
+	/// Completed chunks.
+	ready: VecDeque<Chunk>,
+}
+
+impl Chunk {
+	/// Create a new chunk.
+	pub fn new(address: u32, data: Vec<u8>) -> Self {
+		Self { address, data }
+	}
+
+	/// Get the address of the first byte.
+	pub fn address(&self) -> u32 {
+		self.address
+	}
+
+	/// Get the data.
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+
+	/// Consume the chunk and return the data.
+	pub fn into_data(self) -> Vec<u8> {
+		self.data
+	}
+
+	/// Get the address one past the last byte.
+	fn end(&self) -> u64 {
+		u64::from(self.address) + self.data.len() as u64
+	}
+}
+
+impl<R: BufRead> IntelHexReader<R> {
+	/// Create a new parser with the default chunk size.
+	pub fn new(input: R) -> Self {
+		Self {
+			parser: Parser::new(input),
+			base_address: 0,
+		}
+	}
+
+	/// Set the maximum size of the produced chunks.
+	///
+	/// # Panics
+	/// This function panics if `chunk_size` is zero.
+	pub fn set_chunk_size(&mut self, chunk_size: usize) {
+		self.parser.set_chunk_size(chunk_size);
+	}
+
+	/// Get the number of bytes read from the input so far.
+	///
+	/// Together with the size of the input file, this can be used to report progress.
+	pub fn bytes_read(&self) -> u64 {
+		self.parser.bytes_read
+	}
+
+	/// Get the start address of the image, if specified by the file.
+	///
+	/// The start address is only known after the record that specifies it has been read,
+	/// so this should be called after the iterator is exhausted.
+	pub fn start_address(&self) -> Option<u32> {
+		self.parser.start_address
+	}
+
+	/// Parse a single line.
+	fn parse_line(&mut self) -> std::io::Result<()> {
+		let line = self.parser.line.trim();
+		if line.is_empty() {
+			return Ok(());
+		}
+		let record = line
+			.strip_prefix(':')
+			.ok_or_else(|| self.parser.error("missing start code"))?;
+		let bytes = self.parser.decode_hex(record)?;
+		if bytes.len() < 5 || bytes.len() != usize::from(bytes[0]) + 5 {
+			return Err(self.parser.error("invalid record length"));
+		}
+		if bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) != 0 {
+			return Err(self.parser.error("invalid checksum"));
+		}
+
+		let offset = u32::from(u16::from_be_bytes([bytes[1], bytes[2]]));
+		let data = &bytes[4..bytes.len() - 1];
+		match bytes[3] {
+			0x00 => {
+				let address = self.base_address.wrapping_add(offset);
+				self.parser.push_data(address, data)?;
+			},
+			0x01 => self.parser.done = true,
+			0x02 if data.len() == 2 => self.base_address = u32::from(u16::from_be_bytes([data[0], data[1]])) << 4,
+			0x03 if data.len() == 4 => {
+				let segment = u32::from(u16::from_be_bytes([data[0], data[1]]));
+				let offset = u32::from(u16::from_be_bytes([data[2], data[3]]));
+				self.parser.start_address = Some((segment << 4).wrapping_add(offset));
+			},
+			0x04 if data.len() == 2 => self.base_address = u32::from(u16::from_be_bytes([data[0], data[1]])) << 16,
+			0x05 if data.len() == 4 => {
+				self.parser.start_address = Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]));
+			},
+			_ => return Err(self.parser.error("invalid record")),
+		}
+		Ok(())
+	}
+}
+
+impl<R: BufRead> Iterator for IntelHexReader<R> {
+	type Item = std::io::Result<Chunk>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			match self.parser.next_line() {
+				Ok(Some(())) => {
+					if let Err(e) = self.parse_line() {
+						return Some(Err(e));
+					}
+				},
+				Ok(None) => return self.parser.take_chunk("missing end of file record"),
+				Err(e) => return Some(Err(e)),
+			}
+		}
+	}
+}
+
+impl<R: BufRead> SrecReader<R> {
+	/// Create a new parser with the default chunk size.
+	pub fn new(input: R) -> Self {
+		Self {
+			parser: Parser::new(input),
+		}
+	}
+
+	/// Set the maximum size of the produced chunks.
+	///
+	/// # Panics
+	/// This function panics if `chunk_size` is zero.
+	pub fn set_chunk_size(&mut self, chunk_size: usize) {
+		self.parser.set_chunk_size(chunk_size);
+	}
+
+	/// Get the number of bytes read from the input so far.
+	///
+	/// Together with the size of the input file, this can be used to report progress.
+	pub fn bytes_read(&self) -> u64 {
+		self.parser.bytes_read
+	}
+
+	/// Get the start address of the image, if specified by the file.
+	///
+	/// The start address is only known after the termination record has been read,
+	/// so this should be called after the iterator is exhausted.
+	pub fn start_address(&self) -> Option<u32> {
+		self.parser.start_address
+	}
+
+	/// Parse a single line.
+	fn parse_line(&mut self) -> std::io::Result<()> {
+		let line = self.parser.line.trim();
+		if line.is_empty() {
+			return Ok(());
+		}
+		let record = line
+			.strip_prefix('S')
+			.ok_or_else(|| self.parser.error("missing start code"))?;
+		let (record_type, record) = record.split_at_checked(1).ok_or_else(|| self.parser.error("missing record type"))?;
+		let address_len = match record_type {
+			"0" | "1" | "5" | "9" => 2,
+			"2" | "6" | "8" => 3,
+			"3" | "7" => 4,
+			_ => return Err(self.parser.error("invalid record type")),
+		};
+		let bytes = self.parser.decode_hex(record)?;
+		if bytes.len() < address_len + 2 || bytes.len() != usize::from(bytes[0]) + 1 {
+			return Err(self.parser.error("invalid record length"));
+		}
+		if bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) != 0xFF {
+			return Err(self.parser.error("invalid checksum"));
+		}
+
+		let address = bytes[1..=address_len]
+			.iter()
+			.fold(0u32, |address, &byte| address << 8 | u32::from(byte));
+		let data = &bytes[address_len + 1..bytes.len() - 1];
+		match record_type {
+			"1" | "2" | "3" => self.parser.push_data(address, data)?,
+			"7" | "8" | "9" => {
+				self.parser.start_address = Some(address);
+				self.parser.done = true;
+			},
+			_ => (),
+		}
+		Ok(())
+	}
+}
+
+impl<R: BufRead> Iterator for SrecReader<R> {
+	type Item = std::io::Result<Chunk>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			match self.parser.next_line() {
+				Ok(Some(())) => {
+					if let Err(e) = self.parse_line() {
+						return Some(Err(e));
+					}
+				},
+				Ok(None) => return self.parser.take_chunk("missing termination record"),
+				Err(e) => return Some(Err(e)),
+			}
+		}
+	}
+}
+
+impl<R: BufRead> Parser<R> {
+	/// Create a new parser.
+	fn new(input: R) -> Self {
+		Self {
+			input,
+			line: String::new(),
+			line_number: 0,
+			bytes_read: 0,
+			start_address: None,
+			done: false,
+			chunks: ChunkBuilder {
+				chunk_size: DEFAULT_CHUNK_SIZE,
+				current: None,
+				ready: VecDeque::new(),
+			},
+		}
+	}
+
+	/// Set the maximum size of the produced chunks.
+	fn set_chunk_size(&mut self, chunk_size: usize) {
+		assert!(chunk_size > 0, "chunk size must be greater than zero");
+		self.chunks.chunk_size = chunk_size;
+	}
+
+	/// Read the next line into the line buffer.
+	///
+	/// Returns `None` if a chunk is ready, the image is done or the input is exhausted.
+	fn next_line(&mut self) -> std::io::Result<Option<()>> {
+		if !self.chunks.ready.is_empty() || self.done {
+			return Ok(None);
+		}
+		self.line.clear();
+		let read = self.input.read_line(&mut self.line)?;
+		if read == 0 {
+			return Ok(None);
+		}
+		self.bytes_read += read as u64;
+		self.line_number += 1;
+		Ok(Some(()))
+	}
+
+	/// Take the next chunk after [`Self::next_line()`] returned `None`.
+	fn take_chunk(&mut self, missing_end: &str) -> Option<std::io::Result<Chunk>> {
+		if let Some(chunk) = self.chunks.ready.pop_front() {
+			return Some(Ok(chunk));
+		}
+		if !self.done {
+			// Report the error only once.
+			self.done = true;
+			return Some(Err(self.error(missing_end)));
+		}
+		self.chunks.finish().map(Ok)
+	}
+
+	/// Add data at the given address.
+	fn push_data(&mut self, address: u32, data: &[u8]) -> std::io::Result<()> {
+		if u64::from(address) + data.len() as u64 > 1 << 32 {
+			return Err(self.error("data exceeds the 32 bit address space"));
+		}
+		self.chunks.push(address, data);
+		Ok(())
+	}
+
+	/// Decode a string of hexadecimal digits.
+	fn decode_hex(&self, hex: &str) -> std::io::Result<Vec<u8>> {
+		if !hex.len().is_multiple_of(2) {
+			return Err(self.error("odd number of hexadecimal digits"));
+		}
+		(0..hex.len())
+			.step_by(2)
+			.map(|i| {
+				hex.get(i..i + 2)
+					.and_then(|digits| u8::from_str_radix(digits, 16).ok())
+					.ok_or_else(|| self.error("invalid hexadecimal digit"))
+			})
+			.collect()
+	}
+
+	/// Create an error for the current line.
+	fn error(&self, message: &str) -> std::io::Error {
+		std::io::Error::new(std::io::ErrorKind::InvalidData, format!("line {}: {}", self.line_number, message))
+	}
+}
+
+impl ChunkBuilder {
+	/// Add data at the given address.
+	fn push(&mut self, mut address: u32, mut data: &[u8]) {
+		while !data.is_empty() {
+			match &mut self.current {
+				Some(chunk) if chunk.end() == u64::from(address) && chunk.data.len() < self.chunk_size => {
+					let len = (self.chunk_size - chunk.data.len()).min(data.len());
+					chunk.data.extend_from_slice(&data[..len]);
+					address = address.wrapping_add(len as u32);
+					data = &data[len..];
+				},
+				current => {
+					if let Some(chunk) = current.take() {
+						self.ready.push_back(chunk);
+					}
+					*current = Some(Chunk::new(address, Vec::with_capacity(self.chunk_size)));
+				},
+			}
+		}
+	}
+
+	/// Take the last chunk.
+	fn finish(&mut self) -> Option<Chunk> {
+		self.current.take().filter(|chunk| !chunk.data.is_empty())
+	}
+}
@@ -0,0 +1,8 @@
+//! Implementations of common device protocols on top of a serial port.
+
+pub mod esp;
+pub mod firmata;
+pub mod gcode;
+pub mod image;
+pub mod roomba;
+pub mod stm32;
@@ -0,0 +1,411 @@
+//! A helper for the iRobot Roomba and Create Open Interface.
+//!
+//! The Open Interface is a binary protocol to control iRobot robots and read their sensors.
+//! A [`Roomba`] serializes commands, performs mode transitions and reads sensor packets.
+//!
+//! Sensor data can be requested once with [`Roomba::query()`],
+//! or streamed continuously with [`Roomba::start_stream()`] and [`Roomba::read_stream()`].
+//! Stream packets are validated with their checksum.
+//!
+//! ```no_run
+//! # fn example() -> std::io::Result<()> {
+//! use serial2::protocol::roomba::{Mode, Roomba, packet};
+//!
+//! let mut roomba = Roomba::open("/dev/ttyUSB0")?;
+//! roomba.set_mode(Mode::Safe)?;
+//! roomba.start_stream(&[packet::BUMPS_AND_WHEEL_DROPS, packet::DISTANCE])?;
+//! roomba.drive(200, 0)?;
+//! for sensor in roomba.read_stream()? {
+//!     println!("packet {}: {:?}", sensor.id(), sensor.value());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::framing::{FrameReader, Framer};
+use crate::transaction::Length;
+use crate::SerialPort;
+
+/// The default baud rate of the Open Interface.
+pub const BAUD_RATE: u32 = 115200;
+
+/// The header byte of a stream packet.
+const STREAM_HEADER: u8 = 19;
+
+/// Opcodes of the Open Interface.
+mod opcode {
+	pub const RESET: u8 = 7;
+	pub const START: u8 = 128;
+	pub const SAFE: u8 = 131;
+	pub const FULL: u8 = 132;
+	pub const POWER: u8 = 133;
+	pub const SPOT: u8 = 134;
+	pub const CLEAN: u8 = 135;
+	pub const DRIVE: u8 = 137;
+	pub const LEDS: u8 = 139;
+	pub const SONG: u8 = 140;
+	pub const PLAY: u8 = 141;
+	pub const SENSORS: u8 = 142;
+	pub const SEEK_DOCK: u8 = 143;
+	pub const DRIVE_DIRECT: u8 = 145;
+	pub const STREAM: u8 = 148;
+	pub const PAUSE_RESUME_STREAM: u8 = 150;
+	pub const STOP: u8 = 173;
+}
+
+/// IDs of commonly used sensor packets.
+pub mod packet {
+	/// The bump and wheel drop sensors.
+	pub const BUMPS_AND_WHEEL_DROPS: u8 = 7;
+
+	/// The wall sensor.
+	pub const WALL: u8 = 8;
+
+	/// The buttons.
+	pub const BUTTONS: u8 = 18;
+
+	/// The distance travelled since the last request in mm.
+	pub const DISTANCE: u8 = 19;
+
+	/// The angle turned since the last request in degrees.
+	pub const ANGLE: u8 = 20;
+
+	/// The charging state.
+	pub const CHARGING_STATE: u8 = 21;
+
+	/// The battery voltage in mV.
+	pub const VOLTAGE: u8 = 22;
+
+	/// The battery current in mA.
+	pub const CURRENT: u8 = 23;
+
+	/// The battery temperature in degrees Celsius.
+	pub const TEMPERATURE: u8 = 24;
+
+	/// The battery charge in mAh.
+	pub const BATTERY_CHARGE: u8 = 25;
+
+	/// The battery capacity in mAh.
+	pub const BATTERY_CAPACITY: u8 = 26;
+
+	/// The current mode of the Open Interface.
+	pub const OI_MODE: u8 = 35;
+
+	/// The cumulative count of left wheel encoder counts.
+	pub const LEFT_ENCODER_COUNTS: u8 = 43;
+
+	/// The cumulative count of right wheel encoder counts.
+	pub const RIGHT_ENCODER_COUNTS: u8 = 44;
+
+	/// The light bumper sensors.
+	pub const LIGHT_BUMPER: u8 = 45;
+}
+
+/// The mode of the Open Interface.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Mode {
+	/// The Open Interface is not started.
+	Off,
+
+	/// Sensors can be read, but actuators can not be controlled.
+	Passive,
+
+	/// Actuators can be controlled, with safety features enabled.
+	Safe,
+
+	/// Actuators can be controlled without any safety features.
+	Full,
+}
+
+/// A sensor packet.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SensorPacket {
+	/// The packet ID.
+	id: u8,
+
+	/// The raw data of the packet.
+	data: Vec<u8>,
+}
+
+/// The Roomba Open Interface.
+///
+/// See the [module documentation][self] for more information.
+pub struct Roomba {
+	/// The reader for stream packets.
+	reader: FrameReader<StreamFramer>,
+}
+
+/// A framer for sensor stream packets.
+///
+/// This can be used with a [`FrameReader`] to read stream packets without a [`Roomba`].
+/// Each frame contains the sensor packets of one stream packet.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct StreamFramer;
+
+impl Mode {
+	/// Get the mode from the value of the [`packet::OI_MODE`] sensor packet.
+	pub fn from_u8(value: u8) -> Option<Self> {
+		match value {
+			0 => Some(Self::Off),
+			1 => Some(Self::Passive),
+			2 => Some(Self::Safe),
+			3 => Some(Self::Full),
+			_ => None,
+		}
+	}
+}
+
+impl SensorPacket {
+	/// Get the packet ID.
+	pub fn id(&self) -> u8 {
+		self.id
+	}
+
+	/// Get the raw data of the packet.
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+
+	/// Get the value of a single sensor packet.
+	///
+	/// Two byte values are decoded as big endian numbers, signed or unsigned as specified by the Open Interface.
+	/// Returns `None` for group packets, which contain multiple values.
+	pub fn value(&self) -> Option<i32> {
+		if is_group(self.id) {
+			return None;
+		}
+		let signed = is_signed(self.id);
+		match self.data[..] {
+			[a] if signed => Some(i32::from(a as i8)),
+			[a] => Some(i32::from(a)),
+			[a, b] if signed => Some(i32::from(i16::from_be_bytes([a, b]))),
+			[a, b] => Some(i32::from(u16::from_be_bytes([a, b]))),
+			_ => None,
+		}
+	}
+}
+
+impl Roomba {
+	/// Open a serial port at the default baud rate of the Open Interface.
+	pub fn open(name: impl AsRef<Path>) -> std::io::Result<Self> {
+		Ok(Self::new(SerialPort::open(name, BAUD_RATE)?))
+	}
+
+	/// Create a new Open Interface helper for an opened serial port.
+	pub fn new(port: SerialPort) -> Self {
+		Self {
+			reader: FrameReader::new(port, StreamFramer),
+		}
+	}
+
+	/// Get a reference to the serial port.
+	pub fn port(&self) -> &SerialPort {
+		self.reader.port()
+	}
+
+	/// Consume the helper and return the serial port.
+	pub fn into_inner(self) -> SerialPort {
+		self.reader.into_inner().0
+	}
+
+	/// Start the Open Interface, which puts it in passive mode.
+	pub fn start(&mut self) -> std::io::Result<()> {
+		self.write(&[opcode::START])
+	}
+
+	/// Switch to a different mode.
+	///
+	/// [`Mode::Passive`] starts the Open Interface, and [`Mode::Off`] stops it.
+	/// The Open Interface must be started before switching to [`Mode::Safe`] or [`Mode::Full`].
+	pub fn set_mode(&mut self, mode: Mode) -> std::io::Result<()> {
+		match mode {
+			Mode::Off => self.write(&[opcode::STOP]),
+			Mode::Passive => self.write(&[opcode::START]),
+			Mode::Safe => self.write(&[opcode::START, opcode::SAFE]),
+			Mode::Full => self.write(&[opcode::START, opcode::FULL]),
+		}
+	}
+
+	/// Reset the robot, as if the battery was removed and reinserted.
+	pub fn reset(&mut self) -> std::io::Result<()> {
+		self.write(&[opcode::RESET])
+	}
+
+	/// Power down the robot.
+	pub fn power_down(&mut self) -> std::io::Result<()> {
+		self.write(&[opcode::POWER])
+	}
+
+	/// Start the default cleaning mode.
+	pub fn clean(&mut self) -> std::io::Result<()> {
+		self.write(&[opcode::CLEAN])
+	}
+
+	/// Start spot cleaning.
+	pub fn spot(&mut self) -> std::io::Result<()> {
+		self.write(&[opcode::SPOT])
+	}
+
+	/// Seek the dock.
+	pub fn seek_dock(&mut self) -> std::io::Result<()> {
+		self.write(&[opcode::SEEK_DOCK])
+	}
+
+	/// Drive with a velocity in mm/s and a turn radius in mm.
+	///
+	/// The special radius values 32767 and 32768 (or -32768) drive straight,
+	/// and -1 and 1 turn in place clockwise and counter-clockwise.
+	pub fn drive(&mut self, velocity: i16, radius: i16) -> std::io::Result<()> {
+		let [v0, v1] = velocity.to_be_bytes();
+		let [r0, r1] = radius.to_be_bytes();
+		self.write(&[opcode::DRIVE, v0, v1, r0, r1])
+	}
+
+	/// Drive by setting the velocity of each wheel in mm/s.
+	pub fn drive_direct(&mut self, right: i16, left: i16) -> std::io::Result<()> {
+		let [r0, r1] = right.to_be_bytes();
+		let [l0, l1] = left.to_be_bytes();
+		self.write(&[opcode::DRIVE_DIRECT, r0, r1, l0, l1])
+	}
+
+	/// Set the LEDs.
+	pub fn set_leds(&mut self, leds: u8, power_color: u8, power_intensity: u8) -> std::io::Result<()> {
+		self.write(&[opcode::LEDS, leds, power_color, power_intensity])
+	}
+
+	/// Store a song of up to 16 notes, given as (note number, duration in 1/64 s) pairs.
+	pub fn set_song(&mut self, number: u8, notes: &[(u8, u8)]) -> std::io::Result<()> {
+		if notes.len() > 16 {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "a song can have at most 16 notes"));
+		}
+		let mut command = Vec::with_capacity(3 + notes.len() * 2);
+		command.extend_from_slice(&[opcode::SONG, number, notes.len() as u8]);
+		for &(note, duration) in notes {
+			command.extend_from_slice(&[note, duration]);
+		}
+		self.write(&command)
+	}
+
+	/// Play a stored song.
+	pub fn play_song(&mut self, number: u8) -> std::io::Result<()> {
+		self.write(&[opcode::PLAY, number])
+	}
+
+	/// Request a single sensor packet and wait for the response.
+	///
+	/// This discards all received data that has not been read yet, so pause any active stream first.
+	pub fn query(&mut self, id: u8, timeout: Duration) -> std::io::Result<SensorPacket> {
+		let len = packet_len(id)
+			.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "unknown sensor packet ID"))?;
+		self.reader.discard_buffered();
+		let data = self.reader.port_mut().transact(&[opcode::SENSORS, id], Length(len), timeout)?;
+		Ok(SensorPacket { id, data })
+	}
+
+	/// Start streaming a list of sensor packets.
+	///
+	/// The robot sends the packets every 15 ms. Use [`Self::read_stream()`] to read them.
+	pub fn start_stream(&mut self, ids: &[u8]) -> std::io::Result<()> {
+		if let Some(&id) = ids.iter().find(|&&id| packet_len(id).is_none()) {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				format!("unknown sensor packet ID: {id}"),
+			));
+		}
+		let mut command = Vec::with_capacity(2 + ids.len());
+		command.extend_from_slice(&[opcode::STREAM, ids.len() as u8]);
+		command.extend_from_slice(ids);
+		self.write(&command)
+	}
+
+	/// Pause or resume the sensor stream.
+	pub fn set_stream_paused(&mut self, paused: bool) -> std::io::Result<()> {
+		self.write(&[opcode::PAUSE_RESUME_STREAM, (!paused).into()])
+	}
+
+	/// Read the next stream packet.
+	///
+	/// This uses the read timeout of the serial port.
+	/// If the checksum of a stream packet is wrong, an error of the type [`std::io::ErrorKind::InvalidData`] is returned.
+	/// The next call will search for the start of the next stream packet.
+	pub fn read_stream(&mut self) -> std::io::Result<Vec<SensorPacket>> {
+		self.reader.read_frame()
+	}
+
+	/// Write a command.
+	fn write(&mut self, command: &[u8]) -> std::io::Result<()> {
+		self.reader.port().write_all(command)
+	}
+}
+
+impl Framer for StreamFramer {
+	type Frame = Vec<SensorPacket>;
+
+	fn decode(&mut self, data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>> {
+		if data[0] != STREAM_HEADER {
+			return Err(invalid_data("missing stream packet header"));
+		}
+		let Some(&len) = data.get(1) else { return Ok(None) };
+		let total = usize::from(len) + 3;
+		if data.len() < total {
+			return Ok(None);
+		}
+		let checksum = data[..total].iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+		if checksum != 0 {
+			return Err(invalid_data("invalid stream packet checksum"));
+		}
+
+		let mut payload = &data[2..total - 1];
+		let mut packets = Vec::new();
+		while let Some((&id, rest)) = payload.split_first() {
+			let len = packet_len(id).ok_or_else(|| invalid_data("unknown sensor packet ID in stream"))?;
+			if rest.len() < len {
+				return Err(invalid_data("truncated sensor packet in stream"));
+			}
+			packets.push(SensorPacket {
+				id,
+				data: rest[..len].to_vec(),
+			});
+			payload = &rest[len..];
+		}
+		Ok(Some((packets, total)))
+	}
+}
+
+/// Get the length of the data of a sensor packet.
+fn packet_len(id: u8) -> Option<usize> {
+	match id {
+		0 => Some(26),
+		1 => Some(10),
+		2 => Some(6),
+		3 => Some(10),
+		4 => Some(14),
+		5 => Some(12),
+		6 => Some(52),
+		100 => Some(80),
+		101 => Some(28),
+		106 => Some(12),
+		107 => Some(9),
+		19 | 20 | 22 | 23 | 25..=31 | 33 | 39..=44 | 46..=51 | 54..=57 => Some(2),
+		7..=58 => Some(1),
+		_ => None,
+	}
+}
+
+/// Check if a sensor packet is a group of other packets.
+fn is_group(id: u8) -> bool {
+	matches!(id, 0..=6 | 100 | 101 | 106 | 107)
+}
+
+/// Check if the value of a sensor packet is signed.
+fn is_signed(id: u8) -> bool {
+	matches!(id, 19 | 20 | 23 | 24 | 39..=42 | 54..=57)
+}
+
+/// Create an error of the type [`std::io::ErrorKind::InvalidData`].
+fn invalid_data(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
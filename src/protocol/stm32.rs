@@ -0,0 +1,361 @@
+//! The STM32 USART bootloader protocol.
+//!
+//! The system memory of STM32 microcontrollers contains a bootloader that can program the flash over a serial port,
+//! as described in ST application note AN3155.
+//! The bootloader uses 8 data bits with even parity.
+//! [`Stm32Bootloader::open()`] configures the serial port accordingly.
+//!
+//! After reset into the bootloader, [`Stm32Bootloader::connect()`] sends the `0x7F` byte
+//! that the bootloader uses to detect the baud rate.
+//!
+//! ```no_run
+//! # fn example() -> std::io::Result<()> {
+//! use serial2::protocol::stm32::Stm32Bootloader;
+//! use std::time::Duration;
+//!
+//! let mut bootloader = Stm32Bootloader::open("/dev/ttyUSB0", 115200)?;
+//! bootloader.connect()?;
+//! println!("product ID: 0x{:04X}", bootloader.get_id()?);
+//! bootloader.erase_all(Duration::from_secs(30))?;
+//! bootloader.write_memory(0x0800_0000, &[0x00, 0x50, 0x00, 0x20])?;
+//! bootloader.go(0x0800_0000)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::Path;
+use std::time::Duration;
+
+use super::image::Chunk;
+use crate::{Parity, SerialPort};
+
+/// The byte sent to let the bootloader detect the baud rate.
+const SYNC: u8 = 0x7F;
+
+/// Positive acknowledgement.
+const ACK: u8 = 0x79;
+
+/// Negative acknowledgement.
+const NACK: u8 = 0x1F;
+
+/// Commands of the bootloader.
+mod command {
+	pub const GET: u8 = 0x00;
+	pub const GET_VERSION: u8 = 0x01;
+	pub const GET_ID: u8 = 0x02;
+	pub const READ_MEMORY: u8 = 0x11;
+	pub const GO: u8 = 0x21;
+	pub const WRITE_MEMORY: u8 = 0x31;
+	pub const ERASE: u8 = 0x43;
+	pub const EXTENDED_ERASE: u8 = 0x44;
+}
+
+/// The maximum number of bytes that can be read or written with a single command.
+pub const MAX_TRANSFER: usize = 256;
+
+/// The default read timeout for bootloader responses.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Information reported by the bootloader with the `Get` command.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BootloaderInfo {
+	/// The bootloader version.
+	version: u8,
+
+	/// The supported commands.
+	commands: Vec<u8>,
+}
+
+/// A client for the STM32 USART bootloader.
+///
+/// See the [module documentation][self] for more information.
+pub struct Stm32Bootloader {
+	/// The serial port connected to the microcontroller.
+	port: SerialPort,
+
+	/// The commands supported by the bootloader, if known.
+	commands: Option<Vec<u8>>,
+}
+
+impl BootloaderInfo {
+	/// Get the bootloader version.
+	///
+	/// The high nibble is the major version and the low nibble the minor version.
+	pub fn version(&self) -> u8 {
+		self.version
+	}
+
+	/// Get the supported commands.
+	pub fn commands(&self) -> &[u8] {
+		&self.commands
+	}
+}
+
+impl Stm32Bootloader {
+	/// Open a serial port with the settings required by the bootloader.
+	pub fn open(name: impl AsRef<Path>, baud_rate: u32) -> std::io::Result<Self> {
+		let port = SerialPort::open(name, |mut settings: crate::Settings| {
+			settings.set_raw();
+			settings.set_baud_rate(baud_rate)?;
+			settings.set_parity(Parity::Even);
+			Ok(settings)
+		})?;
+		Self::new(port)
+	}
+
+	/// Create a new bootloader client for an opened serial port.
+	///
+	/// The serial port must be configured for 8 data bits with even parity.
+	/// The read timeout of the serial port is changed for use by the bootloader client.
+	pub fn new(mut port: SerialPort) -> std::io::Result<Self> {
+		port.set_read_timeout(DEFAULT_TIMEOUT)?;
+		Ok(Self { port, commands: None })
+	}
+
+	/// Get a reference to the serial port.
+	pub fn port(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Consume the client and return the serial port.
+	pub fn into_inner(self) -> SerialPort {
+		self.port
+	}
+
+	/// Send the synchronization byte and wait for the bootloader to acknowledge it.
+	///
+	/// A bootloader that already detected the baud rate may reply with a NACK, which is also accepted.
+	pub fn connect(&mut self) -> std::io::Result<()> {
+		self.port.discard_input_buffer()?;
+		self.port.write_all(&[SYNC])?;
+		match self.read_byte()? {
+			ACK | NACK => Ok(()),
+			_ => Err(unexpected_response()),
+		}
+	}
+
+	/// Get the bootloader version and the supported commands.
+	pub fn get(&mut self) -> std::io::Result<BootloaderInfo> {
+		self.send_command(command::GET)?;
+		let len = usize::from(self.read_byte()?) + 1;
+		let mut data = vec![0; len];
+		self.port.read_exact(&mut data)?;
+		self.read_ack()?;
+		let info = BootloaderInfo {
+			version: data[0],
+			commands: data[1..].to_vec(),
+		};
+		self.commands = Some(info.commands.clone());
+		Ok(info)
+	}
+
+	/// Get the bootloader version and the two option bytes.
+	pub fn get_version(&mut self) -> std::io::Result<(u8, [u8; 2])> {
+		self.send_command(command::GET_VERSION)?;
+		let mut data = [0; 3];
+		self.port.read_exact(&mut data)?;
+		self.read_ack()?;
+		Ok((data[0], [data[1], data[2]]))
+	}
+
+	/// Get the product ID of the microcontroller.
+	pub fn get_id(&mut self) -> std::io::Result<u16> {
+		self.send_command(command::GET_ID)?;
+		let len = usize::from(self.read_byte()?) + 1;
+		let mut data = vec![0; len];
+		self.port.read_exact(&mut data)?;
+		self.read_ack()?;
+		match data[..] {
+			[high, low] => Ok(u16::from_be_bytes([high, low])),
+			_ => Err(unexpected_response()),
+		}
+	}
+
+	/// Read up to [`MAX_TRANSFER`] bytes from memory.
+	pub fn read_memory(&mut self, address: u32, buffer: &mut [u8]) -> std::io::Result<()> {
+		check_transfer_len(buffer.len())?;
+		self.send_command(command::READ_MEMORY)?;
+		self.send_address(address)?;
+		let len = (buffer.len() - 1) as u8;
+		self.port.write_all(&[len, !len])?;
+		self.read_ack()?;
+		self.port.read_exact(buffer)
+	}
+
+	/// Write up to [`MAX_TRANSFER`] bytes to memory.
+	///
+	/// The length of the data must be a multiple of 4.
+	/// The flash must be erased before writing.
+	pub fn write_memory(&mut self, address: u32, data: &[u8]) -> std::io::Result<()> {
+		check_transfer_len(data.len())?;
+		if !data.len().is_multiple_of(4) {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "data length must be a multiple of 4"));
+		}
+		self.send_command(command::WRITE_MEMORY)?;
+		self.send_address(address)?;
+		let len = (data.len() - 1) as u8;
+		let mut packet = Vec::with_capacity(data.len() + 2);
+		packet.push(len);
+		packet.extend_from_slice(data);
+		packet.push(checksum(&packet));
+		self.port.write_all(&packet)?;
+		self.read_ack()
+	}
+
+	/// Write a firmware image to memory.
+	///
+	/// The image is given as a sequence of chunks, such as produced by
+	/// [`IntelHexReader`][super::image::IntelHexReader] or [`SrecReader`][super::image::SrecReader].
+	/// Chunks larger than [`MAX_TRANSFER`] are split, and data is padded with `0xFF` bytes to a multiple of 4 bytes.
+	/// After each write, `progress` is called with the total number of bytes written so far.
+	///
+	/// The flash must be erased before writing.
+	/// Returns the total number of bytes written.
+	pub fn write_image<I>(&mut self, chunks: I, mut progress: impl FnMut(usize)) -> std::io::Result<usize>
+	where
+		I: IntoIterator<Item = std::io::Result<Chunk>>,
+	{
+		let mut written = 0;
+		let mut block = Vec::with_capacity(MAX_TRANSFER);
+		for chunk in chunks {
+			let chunk = chunk?;
+			let mut address = chunk.address();
+			for data in chunk.data().chunks(MAX_TRANSFER) {
+				block.clear();
+				block.extend_from_slice(data);
+				block.resize(data.len().next_multiple_of(4), 0xFF);
+				self.write_memory(address, &block)?;
+				address = address.wrapping_add(data.len() as u32);
+				written += data.len();
+				progress(written);
+			}
+		}
+		Ok(written)
+	}
+
+	/// Erase the entire flash memory.
+	///
+	/// Erasing can take a long time, so this uses a separate timeout.
+	pub fn erase_all(&mut self, timeout: Duration) -> std::io::Result<()> {
+		if self.supports_extended_erase()? {
+			self.send_command(command::EXTENDED_ERASE)?;
+			self.port.write_all(&[0xFF, 0xFF, 0x00])?;
+		} else {
+			self.send_command(command::ERASE)?;
+			self.port.write_all(&[0xFF, 0x00])?;
+		}
+		self.read_ack_with_timeout(timeout)
+	}
+
+	/// Erase a list of flash pages.
+	///
+	/// Erasing can take a long time, so this uses a separate timeout.
+	pub fn erase_pages(&mut self, pages: &[u16], timeout: Duration) -> std::io::Result<()> {
+		if pages.is_empty() {
+			return Ok(());
+		}
+		let mut packet = Vec::with_capacity(pages.len() * 2 + 3);
+		if self.supports_extended_erase()? {
+			if pages.len() > 0xFFF0 {
+				return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "too many pages to erase"));
+			}
+			packet.extend_from_slice(&(pages.len() as u16 - 1).to_be_bytes());
+			for page in pages {
+				packet.extend_from_slice(&page.to_be_bytes());
+			}
+			packet.push(checksum(&packet));
+			self.send_command(command::EXTENDED_ERASE)?;
+		} else {
+			if pages.len() > 255 || pages.iter().any(|&page| page > 0xFF) {
+				return Err(std::io::Error::new(
+					std::io::ErrorKind::InvalidInput,
+					"the bootloader only supports erasing up to 255 pages below page 256",
+				));
+			}
+			packet.push((pages.len() - 1) as u8);
+			packet.extend(pages.iter().map(|&page| page as u8));
+			packet.push(checksum(&packet));
+			self.send_command(command::ERASE)?;
+		}
+		self.port.write_all(&packet)?;
+		self.read_ack_with_timeout(timeout)
+	}
+
+	/// Jump to the application code at the given address.
+	pub fn go(&mut self, address: u32) -> std::io::Result<()> {
+		self.send_command(command::GO)?;
+		self.send_address(address)
+	}
+
+	/// Check if the bootloader supports the extended erase command.
+	fn supports_extended_erase(&mut self) -> std::io::Result<bool> {
+		if self.commands.is_none() {
+			self.get()?;
+		}
+		let commands = self.commands.as_deref().unwrap_or_default();
+		Ok(commands.contains(&command::EXTENDED_ERASE))
+	}
+
+	/// Send a command and wait for the acknowledgement.
+	fn send_command(&mut self, command: u8) -> std::io::Result<()> {
+		self.port.write_all(&[command, !command])?;
+		self.read_ack()
+	}
+
+	/// Send an address with checksum and wait for the acknowledgement.
+	fn send_address(&mut self, address: u32) -> std::io::Result<()> {
+		let address = address.to_be_bytes();
+		let mut packet = [0; 5];
+		packet[..4].copy_from_slice(&address);
+		packet[4] = checksum(&address);
+		self.port.write_all(&packet)?;
+		self.read_ack()
+	}
+
+	/// Wait for an acknowledgement using a specific timeout.
+	fn read_ack_with_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+		let read_timeout = self.port.get_read_timeout()?;
+		self.port.set_read_timeout(timeout)?;
+		let result = self.read_ack();
+		self.port.set_read_timeout(read_timeout)?;
+		result
+	}
+
+	/// Wait for an acknowledgement.
+	fn read_ack(&mut self) -> std::io::Result<()> {
+		match self.read_byte()? {
+			ACK => Ok(()),
+			NACK => Err(std::io::Error::other("command rejected by the bootloader")),
+			_ => Err(unexpected_response()),
+		}
+	}
+
+	/// Read a single byte.
+	fn read_byte(&mut self) -> std::io::Result<u8> {
+		let mut byte = [0];
+		self.port.read_exact(&mut byte)?;
+		Ok(byte[0])
+	}
+}
+
+/// Compute the XOR checksum of a packet.
+fn checksum(data: &[u8]) -> u8 {
+	data.iter().fold(0, |checksum, &byte| checksum ^ byte)
+}
+
+/// Check that a transfer length is supported by the bootloader.
+fn check_transfer_len(len: usize) -> std::io::Result<()> {
+	if len == 0 || len > MAX_TRANSFER {
+		Err(std::io::Error::new(
+			std::io::ErrorKind::InvalidInput,
+			format!("transfer length must be between 1 and {MAX_TRANSFER} bytes"),
+		))
+	} else {
+		Ok(())
+	}
+}
+
+/// Create an error for an unexpected response from the bootloader.
+fn unexpected_response() -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, "unexpected response from the bootloader")
+}
@@ -0,0 +1,520 @@
+//! Automatic reconnection after a serial port disappears.
+//!
+//! USB serial adapters disappear when they are unplugged or when the device resets.
+//! An [`AutoReconnectPort`] owns a background thread that (re)opens the serial port whenever it is not connected.
+//! Failed attempts are retried with exponential backoff.
+//!
+//! Reads wait for the connection to come back, within the read timeout.
+//! Writes made while disconnected are kept in a bounded buffer and transmitted as soon as the port is reopened.
+//! Use [`AutoReconnectPort::subscribe()`] to be notified when the connection is established or lost.
+//!
+//! Some failures are never reported by the operating system: a device can hang while its serial port stays open.
+//! To recover from those, set a watchdog with [`AutoReconnectPort::set_watchdog()`].
+//! If the watchdog is not fed within its timeout, the connection is dropped and reopened.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::shutdown::Shutdown;
+use crate::{IntoSettings, SerialPort, ThreadOptions};
+
+/// The read timeout set on each opened serial port.
+///
+/// This limits how long it takes for a read to notice that its own timeout expired.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The default delay before the first retry after a failed attempt to open the serial port.
+const DEFAULT_MIN_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The default maximum delay between attempts to open the serial port.
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// The default capacity of the write buffer used while disconnected.
+const DEFAULT_WRITE_BUFFER_CAPACITY: usize = 4096;
+
+/// A change in the connection state of an [`AutoReconnectPort`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ConnectionEvent {
+	/// The serial port was opened.
+	///
+	/// Data written while disconnected has already been transmitted when this event is emitted.
+	Connected,
+
+	/// The serial port was lost because of an error of the given kind.
+	///
+	/// If the watchdog expired, the error kind is [`std::io::ErrorKind::TimedOut`].
+	Disconnected(std::io::ErrorKind),
+}
+
+/// A serial port that is automatically reopened after it disappears.
+///
+/// See the [module documentation][self] for more information.
+pub struct AutoReconnectPort {
+	/// The state shared with the background thread.
+	shared: Arc<Shared>,
+
+	/// The background thread.
+	thread: Option<JoinHandle<()>>,
+
+	/// The maximum time to wait in read functions.
+	read_timeout: Duration,
+}
+
+impl AutoReconnectPort {
+	/// Open a serial port by path or name, and reopen it whenever it disappears.
+	///
+	/// The settings are applied every time the serial port is opened.
+	///
+	/// This function does not wait for the serial port to be opened.
+	/// Use [`Self::wait_connected()`] if you need to.
+	pub fn open<S>(name: impl AsRef<Path>, settings: S) -> std::io::Result<Self>
+	where
+		S: IntoSettings + Clone + Send + 'static,
+	{
+		let name = name.as_ref().to_owned();
+		Self::new(move || SerialPort::open(&name, settings.clone()))
+	}
+
+	/// Create an auto-reconnecting serial port that uses a custom function to open the port.
+	///
+	/// The function is called from the background thread every time a connection attempt is made.
+	pub fn new<F>(open: F) -> std::io::Result<Self>
+	where
+		F: FnMut() -> std::io::Result<SerialPort> + Send + 'static,
+	{
+		Self::with_thread_options(open, &ThreadOptions::default())
+	}
+
+	/// Create an auto-reconnecting serial port that uses a custom function to open the port,
+	/// with options for the background thread.
+	///
+	/// See [`Self::new()`] and [`ThreadOptions`] for more information.
+	pub fn with_thread_options<F>(open: F, thread_options: &ThreadOptions) -> std::io::Result<Self>
+	where
+		F: FnMut() -> std::io::Result<SerialPort> + Send + 'static,
+	{
+		let shared = Arc::new(Shared {
+			state: Mutex::new(State {
+				port: None,
+				pending: VecDeque::new(),
+				write_buffer_capacity: DEFAULT_WRITE_BUFFER_CAPACITY,
+				min_backoff: DEFAULT_MIN_BACKOFF,
+				max_backoff: DEFAULT_MAX_BACKOFF,
+				subscribers: Vec::new(),
+				watchdog: None,
+				watchdog_fed_by_reads: true,
+				last_fed: Instant::now(),
+				closed: false,
+			}),
+			condvar: Condvar::new(),
+		});
+		let thread = thread_options.spawn("serial2-reconnect", {
+			let shared = shared.clone();
+			move || shared.run(open)
+		})?;
+		Ok(Self {
+			shared,
+			thread: Some(thread),
+			read_timeout: Duration::from_secs(3),
+		})
+	}
+
+	/// Get the currently connected serial port, if any.
+	///
+	/// The returned port stops being used when the connection is lost,
+	/// but it is not closed until all references to it are dropped.
+	pub fn port(&self) -> Option<Arc<SerialPort>> {
+		self.shared.lock().port.clone()
+	}
+
+	/// Check if the serial port is currently connected.
+	pub fn is_connected(&self) -> bool {
+		self.shared.lock().port.is_some()
+	}
+
+	/// Wait until the serial port is connected, up to a maximum time.
+	///
+	/// If the serial port is not connected before the timeout expires,
+	/// an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	pub fn wait_connected(&self, timeout: Duration) -> std::io::Result<()> {
+		self.shared.wait_connected(Instant::now() + timeout)?;
+		Ok(())
+	}
+
+	/// Subscribe to connection events.
+	///
+	/// Only events that happen after subscribing are delivered.
+	pub fn subscribe(&self) -> Receiver<ConnectionEvent> {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		self.shared.lock().subscribers.push(sender);
+		receiver
+	}
+
+	/// Set the minimum and maximum delay between attempts to open the serial port.
+	///
+	/// After a failed attempt, the next attempt is made after the minimum delay.
+	/// The delay doubles after every failed attempt, up to the maximum delay.
+	/// The default is 100 milliseconds to 10 seconds.
+	pub fn set_backoff(&mut self, min: Duration, max: Duration) {
+		let mut state = self.shared.lock();
+		state.min_backoff = min;
+		state.max_backoff = max.max(min);
+	}
+
+	/// Get the minimum and maximum delay between attempts to open the serial port.
+	pub fn get_backoff(&self) -> (Duration, Duration) {
+		let state = self.shared.lock();
+		(state.min_backoff, state.max_backoff)
+	}
+
+	/// Set the maximum number of bytes buffered while disconnected.
+	///
+	/// The default is 4096 bytes.
+	pub fn set_write_buffer_capacity(&mut self, capacity: usize) {
+		self.shared.lock().write_buffer_capacity = capacity;
+	}
+
+	/// Get the maximum number of bytes buffered while disconnected.
+	pub fn get_write_buffer_capacity(&self) -> usize {
+		self.shared.lock().write_buffer_capacity
+	}
+
+	/// Get the number of bytes buffered while disconnected that have not been transmitted yet.
+	pub fn buffered_bytes(&self) -> usize {
+		self.shared.lock().pending.len()
+	}
+
+	/// Set the watchdog timeout, or `None` to disable the watchdog.
+	///
+	/// If the watchdog is not fed for longer than the timeout while connected,
+	/// the connection is dropped, a [`ConnectionEvent::Disconnected`] event is emitted
+	/// with the error kind [`std::io::ErrorKind::TimedOut`], and the serial port is reopened.
+	///
+	/// The watchdog is fed when the serial port is opened, by calling [`Self::feed_watchdog()`],
+	/// and by every successful read unless disabled with [`Self::set_watchdog_fed_by_reads()`].
+	///
+	/// The watchdog is disabled by default.
+	pub fn set_watchdog(&mut self, timeout: Option<Duration>) {
+		let mut state = self.shared.lock();
+		state.watchdog = timeout;
+		state.last_fed = Instant::now();
+		drop(state);
+		self.shared.condvar.notify_all();
+	}
+
+	/// Get the watchdog timeout, or `None` if the watchdog is disabled.
+	pub fn get_watchdog(&self) -> Option<Duration> {
+		self.shared.lock().watchdog
+	}
+
+	/// Set if successful reads feed the watchdog.
+	///
+	/// Disable this if only complete, valid frames prove that the peer is alive.
+	/// Call [`Self::feed_watchdog()`] for every valid frame instead.
+	///
+	/// The default is `true`.
+	pub fn set_watchdog_fed_by_reads(&mut self, enable: bool) {
+		self.shared.lock().watchdog_fed_by_reads = enable;
+	}
+
+	/// Check if successful reads feed the watchdog.
+	pub fn get_watchdog_fed_by_reads(&self) -> bool {
+		self.shared.lock().watchdog_fed_by_reads
+	}
+
+	/// Feed the watchdog, to signal that the connection is alive.
+	pub fn feed_watchdog(&self) {
+		self.shared.lock().last_fed = Instant::now();
+	}
+
+	/// Get the time since the watchdog was last fed.
+	pub fn time_since_watchdog_fed(&self) -> Duration {
+		self.shared.lock().last_fed.elapsed()
+	}
+
+	/// Set the maximum time to wait in read functions, including the time spent waiting for a connection.
+	pub fn set_read_timeout(&mut self, timeout: Duration) {
+		self.read_timeout = timeout;
+	}
+
+	/// Get the maximum time to wait in read functions, including the time spent waiting for a connection.
+	pub fn get_read_timeout(&self) -> Duration {
+		self.read_timeout
+	}
+
+	/// Read bytes from the serial port.
+	///
+	/// If the serial port is not connected, this waits for the connection to come back.
+	/// If the connection is lost while reading, the read continues on the new connection.
+	///
+	/// If no data is received before the read timeout expires,
+	/// an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		if buf.is_empty() {
+			return Ok(0);
+		}
+		let deadline = Instant::now() + self.read_timeout;
+		loop {
+			let port = self.shared.wait_connected(deadline)?;
+			match port.read(buf) {
+				Ok(0) => self.shared.disconnected(&port, std::io::ErrorKind::UnexpectedEof),
+				Ok(read) => {
+					let mut state = self.shared.lock();
+					if state.watchdog_fed_by_reads {
+						state.last_fed = Instant::now();
+					}
+					return Ok(read);
+				},
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+					if Instant::now() >= deadline {
+						return Err(e);
+					}
+				},
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => self.shared.disconnected(&port, e.kind()),
+			}
+		}
+	}
+
+	/// Write all bytes to the serial port.
+	///
+	/// If the serial port is not connected, the data is added to the write buffer
+	/// and transmitted as soon as the serial port is reopened.
+	/// If the data does not fit in the write buffer, nothing is buffered and an error of the type
+	/// [`std::io::ErrorKind::NotConnected`] is returned.
+	///
+	/// If the connection is lost during the write, the remaining data is buffered in the same way.
+	/// Timeouts are returned as errors, without buffering the remaining data.
+	pub fn write_all(&self, buf: &[u8]) -> std::io::Result<()> {
+		let mut buf = buf;
+		while !buf.is_empty() {
+			let mut state = self.shared.lock();
+			let port = match &state.port {
+				Some(port) => port.clone(),
+				None => return state.buffer(buf),
+			};
+			drop(state);
+
+			match port.write(buf) {
+				Ok(0) => self.shared.disconnected(&port, std::io::ErrorKind::WriteZero),
+				Ok(written) => buf = &buf[written..],
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => return Err(e),
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => self.shared.disconnected(&port, e.kind()),
+			}
+		}
+		Ok(())
+	}
+}
+
+impl std::io::Read for AutoReconnectPort {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		AutoReconnectPort::read(self, buf)
+	}
+}
+
+impl std::io::Read for &'_ AutoReconnectPort {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		AutoReconnectPort::read(self, buf)
+	}
+}
+
+impl AutoReconnectPort {
+	/// Stop the background thread and wait for it to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		self.shared.condvar.notify_all();
+		crate::shutdown::join_threads(self.thread.take(), deadline)
+	}
+}
+
+impl Drop for AutoReconnectPort {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl Shutdown for AutoReconnectPort {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// State shared between the port and the background thread.
+struct Shared {
+	/// The mutable state.
+	state: Mutex<State>,
+
+	/// Condition variable signalled whenever the connection state changes.
+	condvar: Condvar,
+}
+
+/// The mutable state of an [`AutoReconnectPort`].
+struct State {
+	/// The connected serial port, if any.
+	port: Option<Arc<SerialPort>>,
+
+	/// Data written while disconnected.
+	pending: VecDeque<u8>,
+
+	/// The maximum number of bytes in `pending`.
+	write_buffer_capacity: usize,
+
+	/// The delay after the first failed attempt to open the serial port.
+	min_backoff: Duration,
+
+	/// The maximum delay between attempts to open the serial port.
+	max_backoff: Duration,
+
+	/// The subscribers for connection events.
+	subscribers: Vec<Sender<ConnectionEvent>>,
+
+	/// The watchdog timeout, if enabled.
+	watchdog: Option<Duration>,
+
+	/// If true, successful reads feed the watchdog.
+	watchdog_fed_by_reads: bool,
+
+	/// The last time the watchdog was fed.
+	last_fed: Instant,
+
+	/// True if the port is being dropped.
+	closed: bool,
+}
+
+impl Shared {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Wait for the state to change or for a timeout, ignoring poisoning.
+	fn wait_timeout<'a>(&self, guard: MutexGuard<'a, State>, timeout: Duration) -> MutexGuard<'a, State> {
+		match self.condvar.wait_timeout(guard, timeout) {
+			Ok((guard, _timeout)) => guard,
+			Err(e) => e.into_inner().0,
+		}
+	}
+
+	/// Wait until the serial port is connected or the deadline passes.
+	fn wait_connected(&self, deadline: Instant) -> std::io::Result<Arc<SerialPort>> {
+		let mut state = self.lock();
+		loop {
+			if let Some(port) = &state.port {
+				return Ok(port.clone());
+			}
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return Err(std::io::ErrorKind::TimedOut.into());
+			}
+			state = self.wait_timeout(state, remaining);
+		}
+	}
+
+	/// Mark a serial port as disconnected, if it is still the current connection.
+	fn disconnected(&self, port: &Arc<SerialPort>, kind: std::io::ErrorKind) {
+		let mut state = self.lock();
+		if !state.port.as_ref().is_some_and(|current| Arc::ptr_eq(current, port)) {
+			return;
+		}
+		state.port = None;
+		state.emit(ConnectionEvent::Disconnected(kind));
+		drop(state);
+		self.condvar.notify_all();
+	}
+
+	/// Run the background thread.
+	fn run(&self, mut open: impl FnMut() -> std::io::Result<SerialPort>) {
+		let mut backoff = None;
+		loop {
+			let mut state = self.lock();
+			while state.port.is_some() && !state.closed {
+				let wait = match state.watchdog_remaining() {
+					None => POLL_INTERVAL,
+					Some(Duration::ZERO) => {
+						state.port = None;
+						state.emit(ConnectionEvent::Disconnected(std::io::ErrorKind::TimedOut));
+						self.condvar.notify_all();
+						break;
+					},
+					Some(remaining) => remaining.min(POLL_INTERVAL),
+				};
+				state = self.wait_timeout(state, wait);
+			}
+			if let Some(delay) = backoff {
+				let deadline = Instant::now() + delay;
+				while !state.closed {
+					let remaining = deadline.saturating_duration_since(Instant::now());
+					if remaining.is_zero() {
+						break;
+					}
+					state = self.wait_timeout(state, remaining);
+				}
+			}
+			if state.closed {
+				return;
+			}
+			drop(state);
+
+			match self.connect(&mut open) {
+				Ok(()) => backoff = None,
+				Err(_) => {
+					let state = self.lock();
+					backoff = Some(match backoff {
+						None => state.min_backoff,
+						Some(backoff) => (backoff * 2).clamp(state.min_backoff, state.max_backoff),
+					});
+				},
+			}
+		}
+	}
+
+	/// Try to open the serial port, and transmit the buffered data.
+	fn connect(&self, open: &mut impl FnMut() -> std::io::Result<SerialPort>) -> std::io::Result<()> {
+		let mut port = open()?;
+		port.set_read_timeout(POLL_INTERVAL)?;
+
+		// Keep the state locked while transmitting the buffered data,
+		// so that new writes are buffered behind it instead of overtaking it.
+		let mut state = self.lock();
+		let (first, second) = state.pending.as_slices();
+		port.write_all(first)?;
+		port.write_all(second)?;
+		state.pending.clear();
+		state.port = Some(Arc::new(port));
+		state.last_fed = Instant::now();
+		state.emit(ConnectionEvent::Connected);
+		drop(state);
+		self.condvar.notify_all();
+		Ok(())
+	}
+}
+
+impl State {
+	/// Add data to the write buffer if it fits completely.
+	fn buffer(&mut self, data: &[u8]) -> std::io::Result<()> {
+		if self.pending.len() + data.len() > self.write_buffer_capacity {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::NotConnected,
+				"serial port is disconnected and the write buffer is full",
+			));
+		}
+		self.pending.extend(data);
+		Ok(())
+	}
+
+	/// Get the time until the watchdog expires, or `None` if the watchdog is disabled.
+	fn watchdog_remaining(&self) -> Option<Duration> {
+		let timeout = self.watchdog?;
+		Some(timeout.saturating_sub(self.last_fed.elapsed()))
+	}
+
+	/// Send an event to all subscribers, and forget subscribers that are gone.
+	fn emit(&mut self, event: ConnectionEvent) {
+		self.subscribers.retain(|subscriber| subscriber.send(event).is_ok());
+	}
+}
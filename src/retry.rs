@@ -0,0 +1,421 @@
+//! Request/response transactions with retries.
+//!
+//! Field protocols over serial links have to deal with lost and corrupted frames.
+//! A [`RetryingTransport`] sends a request and reads the response with a [`FrameReader`].
+//! If no valid response arrives in time, or if the framer reports a corrupted frame
+//! (an error of the type [`std::io::ErrorKind::InvalidData`], for example a CRC mismatch),
+//! the request is sent again after a backoff delay.
+//!
+//! The backoff delay doubles with every retry, up to a maximum,
+//! and a random part of the delay is removed (jitter) so multiple masters on a bus do not retry in lock step.
+//!
+//! When a number of transactions in a row failed after all retries, the transport opens a circuit breaker:
+//! further transactions fail immediately, without touching the serial port, until the open time passes.
+//! After that, the next transaction is a trial: if it succeeds the circuit closes again, otherwise it opens again.
+//!
+//! The backoff delays and the circuit breaker use a [`Clock`], which can be replaced by a manual clock in tests.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+use crate::framing::{FrameReader, Framer};
+use crate::SerialPort;
+
+/// The configuration of a [`RetryingTransport`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RetryPolicy {
+	/// The maximum number of attempts for each transaction, including the first one.
+	max_attempts: u32,
+
+	/// If true, transactions are retried when no response arrives in time.
+	retry_on_timeout: bool,
+
+	/// If true, transactions are retried when the framer reports a corrupted frame.
+	retry_on_invalid_frame: bool,
+
+	/// The delay before the first retry.
+	min_backoff: Duration,
+
+	/// The maximum delay between retries.
+	max_backoff: Duration,
+
+	/// The fraction of the backoff delay that is randomized.
+	jitter: f64,
+
+	/// The number of failed transactions in a row that opens the circuit breaker, or 0 to disable it.
+	failure_threshold: u32,
+
+	/// The time the circuit breaker stays open.
+	open_time: Duration,
+}
+
+impl RetryPolicy {
+	/// Create a new retry policy with default settings.
+	///
+	/// The default policy makes at most 3 attempts, retries on timeouts and on corrupted frames,
+	/// uses a backoff from 10 milliseconds up to 1 second with 50% jitter,
+	/// and does not use a circuit breaker.
+	pub fn new() -> Self {
+		Self {
+			max_attempts: 3,
+			retry_on_timeout: true,
+			retry_on_invalid_frame: true,
+			min_backoff: Duration::from_millis(10),
+			max_backoff: Duration::from_secs(1),
+			jitter: 0.5,
+			failure_threshold: 0,
+			open_time: Duration::from_secs(5),
+		}
+	}
+
+	/// Set the maximum number of attempts for each transaction, including the first one.
+	///
+	/// A value of 0 is treated as 1.
+	pub fn set_max_attempts(&mut self, max_attempts: u32) {
+		self.max_attempts = max_attempts.max(1);
+	}
+
+	/// Get the maximum number of attempts for each transaction, including the first one.
+	pub fn get_max_attempts(&self) -> u32 {
+		self.max_attempts
+	}
+
+	/// Set if transactions are retried when no response arrives in time.
+	pub fn set_retry_on_timeout(&mut self, retry: bool) {
+		self.retry_on_timeout = retry;
+	}
+
+	/// Check if transactions are retried when no response arrives in time.
+	pub fn get_retry_on_timeout(&self) -> bool {
+		self.retry_on_timeout
+	}
+
+	/// Set if transactions are retried when the framer reports a corrupted frame.
+	pub fn set_retry_on_invalid_frame(&mut self, retry: bool) {
+		self.retry_on_invalid_frame = retry;
+	}
+
+	/// Check if transactions are retried when the framer reports a corrupted frame.
+	pub fn get_retry_on_invalid_frame(&self) -> bool {
+		self.retry_on_invalid_frame
+	}
+
+	/// Set the delay before the first retry and the maximum delay between retries.
+	///
+	/// The delay doubles with every retry of the same transaction.
+	pub fn set_backoff(&mut self, min: Duration, max: Duration) {
+		self.min_backoff = min;
+		self.max_backoff = max.max(min);
+	}
+
+	/// Get the delay before the first retry and the maximum delay between retries.
+	pub fn get_backoff(&self) -> (Duration, Duration) {
+		(self.min_backoff, self.max_backoff)
+	}
+
+	/// Set the fraction of the backoff delay that is randomized, between 0.0 and 1.0.
+	///
+	/// With a jitter of 0.5, each delay is chosen randomly between 50% and 100% of the nominal delay.
+	pub fn set_jitter(&mut self, jitter: f64) {
+		self.jitter = jitter.clamp(0.0, 1.0);
+	}
+
+	/// Get the fraction of the backoff delay that is randomized.
+	pub fn get_jitter(&self) -> f64 {
+		self.jitter
+	}
+
+	/// Set the circuit breaker: the number of failed transactions in a row that open it, and the time it stays open.
+	///
+	/// A threshold of 0 disables the circuit breaker.
+	pub fn set_circuit_breaker(&mut self, failure_threshold: u32, open_time: Duration) {
+		self.failure_threshold = failure_threshold;
+		self.open_time = open_time;
+	}
+
+	/// Get the number of failed transactions in a row that open the circuit breaker, and the time it stays open.
+	pub fn get_circuit_breaker(&self) -> (u32, Duration) {
+		(self.failure_threshold, self.open_time)
+	}
+
+	/// Get the nominal backoff delay before the given retry, starting at 1.
+	fn backoff(&self, retry: u32) -> Duration {
+		let factor = 1u32.checked_shl(retry.saturating_sub(1)).unwrap_or(u32::MAX);
+		self.min_backoff.saturating_mul(factor).min(self.max_backoff)
+	}
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// The state of the circuit breaker of a [`RetryingTransport`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CircuitState {
+	/// Transactions are performed normally.
+	Closed,
+
+	/// Transactions fail immediately until the given time.
+	Open {
+		/// The time at which the circuit becomes half-open.
+		until: Instant,
+	},
+
+	/// The open time has passed: the next transaction decides if the circuit closes or opens again.
+	HalfOpen,
+}
+
+/// Statistics of a [`RetryingTransport`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct RetryStats {
+	/// The number of successful transactions.
+	succeeded: u64,
+
+	/// The number of transactions that failed after all attempts.
+	failed: u64,
+
+	/// The number of retries.
+	retries: u64,
+
+	/// The number of transactions rejected by the open circuit breaker.
+	rejected: u64,
+}
+
+impl RetryStats {
+	/// Get the number of successful transactions.
+	pub fn succeeded(&self) -> u64 {
+		self.succeeded
+	}
+
+	/// Get the number of transactions that failed after all attempts.
+	pub fn failed(&self) -> u64 {
+		self.failed
+	}
+
+	/// Get the number of retries.
+	pub fn retries(&self) -> u64 {
+		self.retries
+	}
+
+	/// Get the number of transactions rejected by the open circuit breaker.
+	pub fn rejected(&self) -> u64 {
+		self.rejected
+	}
+}
+
+/// Performs request/response transactions with retries and a circuit breaker.
+///
+/// See the [module documentation][self] for more information.
+pub struct RetryingTransport<F> {
+	/// The frame reader for the responses.
+	reader: FrameReader<F>,
+
+	/// The retry policy.
+	policy: RetryPolicy,
+
+	/// The number of failed transactions in a row.
+	consecutive_failures: u32,
+
+	/// The time until which the circuit breaker is open.
+	open_until: Option<Instant>,
+
+	/// The statistics.
+	stats: RetryStats,
+
+	/// The state of the random number generator for the jitter.
+	rng: u64,
+
+	/// The clock for the backoff delays and the circuit breaker.
+	clock: Clock,
+}
+
+impl<F: Framer> RetryingTransport<F> {
+	/// Create a new retrying transport.
+	pub fn new(port: SerialPort, framer: F, policy: RetryPolicy) -> Self {
+		Self::with_reader(FrameReader::new(port, framer), policy)
+	}
+
+	/// Create a new retrying transport from an existing frame reader.
+	///
+	/// Use this if the frame reader needs special configuration, like an idle time.
+	pub fn with_reader(reader: FrameReader<F>, policy: RetryPolicy) -> Self {
+		let seed = RandomState::new().build_hasher().finish();
+		Self {
+			reader,
+			policy,
+			consecutive_failures: 0,
+			open_until: None,
+			stats: RetryStats::default(),
+			// The xorshift generator must not start at zero.
+			rng: seed | 1,
+			clock: Clock::system(),
+		}
+	}
+
+	/// Get a reference to the frame reader.
+	pub fn reader(&self) -> &FrameReader<F> {
+		&self.reader
+	}
+
+	/// Get a mutable reference to the frame reader.
+	pub fn reader_mut(&mut self) -> &mut FrameReader<F> {
+		&mut self.reader
+	}
+
+	/// Get a reference to the serial port.
+	pub fn port(&self) -> &SerialPort {
+		self.reader.port()
+	}
+
+	/// Set the retry policy.
+	pub fn set_policy(&mut self, policy: RetryPolicy) {
+		self.policy = policy;
+	}
+
+	/// Get the retry policy.
+	pub fn get_policy(&self) -> RetryPolicy {
+		self.policy
+	}
+
+	/// Set the clock used for the backoff delays and the circuit breaker.
+	///
+	/// With a [`Clock::manual()`] clock, backoff delays advance the clock instead of sleeping,
+	/// and the circuit breaker only closes when the clock is advanced past the open time.
+	/// The timeout for the response of each attempt always uses real time, since it is enforced by the operating system.
+	pub fn set_clock(&mut self, clock: Clock) {
+		self.clock = clock;
+	}
+
+	/// Get the clock used for the backoff delays and the circuit breaker.
+	pub fn get_clock(&self) -> &Clock {
+		&self.clock
+	}
+
+	/// Get the state of the circuit breaker.
+	pub fn circuit_state(&self) -> CircuitState {
+		match self.open_until {
+			None => CircuitState::Closed,
+			Some(until) if self.clock.now() < until => CircuitState::Open { until },
+			Some(_) => CircuitState::HalfOpen,
+		}
+	}
+
+	/// Close the circuit breaker and reset the number of failed transactions in a row.
+	pub fn reset_circuit(&mut self) {
+		self.open_until = None;
+		self.consecutive_failures = 0;
+	}
+
+	/// Get the number of failed transactions in a row.
+	pub fn consecutive_failures(&self) -> u32 {
+		self.consecutive_failures
+	}
+
+	/// Get the statistics of the transport.
+	pub fn stats(&self) -> RetryStats {
+		self.stats
+	}
+
+	/// Consume the transport and return the frame reader.
+	pub fn into_reader(self) -> FrameReader<F> {
+		self.reader
+	}
+
+	/// Perform a transaction: send a request and read one response frame.
+	///
+	/// Each attempt discards the input buffer and any partially received frame, writes the request,
+	/// and waits up to `timeout` for a complete frame.
+	/// Failed attempts are retried according to the [`RetryPolicy`].
+	/// If all attempts fail, the error of the last attempt is returned.
+	///
+	/// While the circuit breaker is open, this fails immediately with an error of the type
+	/// [`std::io::ErrorKind::ConnectionRefused`].
+	///
+	/// This function temporarily changes the read timeout of the serial port.
+	/// The original read timeout is restored before the function returns.
+	pub fn transact(&mut self, request: &[u8], timeout: Duration) -> std::io::Result<F::Frame> {
+		if let CircuitState::Open { .. } = self.circuit_state() {
+			self.stats.rejected += 1;
+			return Err(std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "circuit breaker is open"));
+		}
+
+		let original_timeout = self.reader.port().get_read_timeout()?;
+		let result = self.transact_with_retries(request, timeout);
+		self.reader.port_mut().set_read_timeout(original_timeout)?;
+
+		match &result {
+			Ok(_) => {
+				self.stats.succeeded += 1;
+				self.reset_circuit();
+			},
+			Err(_) => {
+				self.stats.failed += 1;
+				self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+				let threshold = self.policy.failure_threshold;
+				let half_open = self.circuit_state() == CircuitState::HalfOpen;
+				if half_open || (threshold > 0 && self.consecutive_failures >= threshold) {
+					self.open_until = Some(self.clock.now() + self.policy.open_time);
+				}
+			},
+		}
+		result
+	}
+
+	/// Perform the attempts of a transaction, leaving the read timeout modified.
+	fn transact_with_retries(&mut self, request: &[u8], timeout: Duration) -> std::io::Result<F::Frame> {
+		let mut attempt = 1;
+		loop {
+			let error = match self.attempt(request, Instant::now() + timeout) {
+				Ok(frame) => return Ok(frame),
+				Err(e) => e,
+			};
+			let retry = match error.kind() {
+				std::io::ErrorKind::TimedOut => self.policy.retry_on_timeout,
+				std::io::ErrorKind::InvalidData => self.policy.retry_on_invalid_frame,
+				_ => false,
+			};
+			if !retry || attempt >= self.policy.max_attempts {
+				return Err(error);
+			}
+			let delay = self.jittered(self.policy.backoff(attempt));
+			self.clock.sleep(delay);
+			self.stats.retries += 1;
+			attempt += 1;
+		}
+	}
+
+	/// Perform a single attempt of a transaction.
+	fn attempt(&mut self, request: &[u8], deadline: Instant) -> std::io::Result<F::Frame> {
+		self.reader.discard_buffered();
+		self.reader.port().discard_input_buffer()?;
+		self.reader.port().write_all(request)?;
+		loop {
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for response"));
+			}
+			self.reader.port_mut().set_read_timeout(remaining)?;
+			match self.reader.read_frame() {
+				Ok(frame) => return Ok(frame),
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				// The timeout may have been truncated by the platform, so check the remaining time again.
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Remove a random part of a delay, according to the jitter of the policy.
+	fn jittered(&mut self, delay: Duration) -> Duration {
+		// xorshift64
+		self.rng ^= self.rng << 13;
+		self.rng ^= self.rng >> 7;
+		self.rng ^= self.rng << 17;
+		let random = (self.rng >> 11) as f64 / (1u64 << 53) as f64;
+		delay.mul_f64(1.0 - self.policy.jitter * random)
+	}
+}
@@ -0,0 +1,288 @@
+//! Routing of received frames to subscribers by filter.
+//!
+//! A [`Router`] reads frames in a background thread and delivers each frame to every subscriber with a matching [`Filter`].
+//! This allows independent protocol handlers, like telemetry, logging and firmware updates, to share one serial port.
+//!
+//! Filters can match on the kind of a frame, on a range of frame IDs, or on any custom condition.
+//! Unlike a [`Demux`][crate::demux::Demux], which routes each frame to exactly one channel by ID,
+//! a frame is delivered to all matching subscribers, so the frame type must implement [`Clone`].
+//!
+//! A subscription ends when its receiver is dropped.
+//!
+//! # Example
+//! ```no_run
+//! # use serial2::SerialPort;
+//! # use serial2::framing::{FrameReader, SlipFramer};
+//! # use serial2::router::{Filter, Router};
+//! # fn foo() -> std::io::Result<()> {
+//! let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+//! let router = Router::new(FrameReader::new(port, SlipFramer))?;
+//! let telemetry = router.subscribe(Filter::kind(|frame: &Vec<u8>| frame.first().copied(), Some(b'T')));
+//! let log = router.subscribe(Filter::all());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ops::RangeBounds;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::framing::{FrameReader, Framer};
+use crate::shutdown::Shutdown;
+
+/// The read timeout used by the background thread.
+///
+/// This limits how long it takes for the background thread to notice that the router was dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A condition that selects the frames delivered to a subscriber.
+pub struct Filter<Frame> {
+	/// The condition.
+	predicate: Box<dyn Fn(&Frame) -> bool + Send>,
+}
+
+impl<Frame: 'static> Filter<Frame> {
+	/// Create a filter that matches frames for which `predicate` returns true.
+	pub fn new<P>(predicate: P) -> Self
+	where
+		P: Fn(&Frame) -> bool + Send + 'static,
+	{
+		Self {
+			predicate: Box::new(predicate),
+		}
+	}
+
+	/// Create a filter that matches all frames.
+	pub fn all() -> Self {
+		Self::new(|_| true)
+	}
+
+	/// Create a filter that matches frames of one kind.
+	///
+	/// The `frame_kind` function extracts the kind from a frame, like a message type or a command code.
+	pub fn kind<K, F>(frame_kind: F, kind: K) -> Self
+	where
+		K: PartialEq + Send + 'static,
+		F: Fn(&Frame) -> K + Send + 'static,
+	{
+		Self::new(move |frame| frame_kind(frame) == kind)
+	}
+
+	/// Create a filter that matches frames with an ID in a range.
+	///
+	/// The `frame_id` function extracts the ID from a frame.
+	pub fn id_range<Id, F, R>(frame_id: F, range: R) -> Self
+	where
+		Id: PartialOrd,
+		F: Fn(&Frame) -> Id + Send + 'static,
+		R: RangeBounds<Id> + Send + 'static,
+	{
+		Self::new(move |frame| range.contains(&frame_id(frame)))
+	}
+
+	/// Create a filter that matches frames that match both this filter and `other`.
+	pub fn and(self, other: Self) -> Self {
+		Self::new(move |frame| self.matches(frame) && other.matches(frame))
+	}
+
+	/// Create a filter that matches frames that match this filter, `other`, or both.
+	pub fn or(self, other: Self) -> Self {
+		Self::new(move |frame| self.matches(frame) || other.matches(frame))
+	}
+}
+
+impl<Frame> Filter<Frame> {
+	/// Check if a frame matches the filter.
+	pub fn matches(&self, frame: &Frame) -> bool {
+		(self.predicate)(frame)
+	}
+}
+
+/// Delivers received frames to all subscribers with a matching filter.
+///
+/// See the [module documentation][self] for more information.
+///
+/// Dropping the router stops the background thread.
+pub struct Router<Frame> {
+	/// The state shared with the background thread.
+	shared: Arc<Shared<Frame>>,
+
+	/// The background thread.
+	thread: Option<JoinHandle<()>>,
+}
+
+impl<Frame> Router<Frame>
+where
+	Frame: Clone + Send + 'static,
+{
+	/// Create a new router that reads frames from a frame reader.
+	///
+	/// This spawns a background thread that reads from the serial port.
+	/// The read timeout of the serial port is changed for use by the background thread.
+	pub fn new<F>(mut reader: FrameReader<F>) -> std::io::Result<Self>
+	where
+		F: Framer<Frame = Frame> + Send + 'static,
+	{
+		reader.port_mut().set_read_timeout(POLL_INTERVAL)?;
+		let shared = Arc::new(Shared {
+			state: Mutex::new(State {
+				subscribers: Vec::new(),
+				unmatched: None,
+				invalid_frames: 0,
+				error: None,
+				closed: false,
+			}),
+		});
+		let thread = std::thread::Builder::new()
+			.name("serial2-router".into())
+			.spawn({
+				let shared = shared.clone();
+				move || shared.run(reader)
+			})?;
+		Ok(Self {
+			shared,
+			thread: Some(thread),
+		})
+	}
+
+	/// Subscribe to frames that match a filter.
+	///
+	/// Subscriptions are checked in the order they were made, and each matching subscriber receives a copy of the frame.
+	/// The subscription ends when the receiver is dropped.
+	pub fn subscribe(&self, filter: Filter<Frame>) -> Receiver<Frame> {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		self.shared.lock().subscribers.push((filter, sender));
+		receiver
+	}
+
+	/// Subscribe to frames that match no other subscription.
+	///
+	/// Any previous subscription for unmatched frames is replaced.
+	/// Without a subscription, unmatched frames are discarded.
+	pub fn subscribe_unmatched(&self) -> Receiver<Frame> {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		self.shared.lock().unmatched = Some(sender);
+		receiver
+	}
+
+	/// Get the number of subscriptions, not counting the subscription for unmatched frames.
+	///
+	/// Subscriptions with a dropped receiver are counted until the next frame is routed.
+	pub fn subscriptions(&self) -> usize {
+		self.shared.lock().subscribers.len()
+	}
+
+	/// Get the number of times the framer reported invalid data.
+	pub fn invalid_frames(&self) -> u64 {
+		self.shared.lock().invalid_frames
+	}
+
+	/// Take the error that stopped the background thread, if any.
+	///
+	/// When the background thread stops, all subscription channels are disconnected.
+	pub fn take_error(&self) -> Option<std::io::Error> {
+		self.shared.lock().error.take()
+	}
+}
+
+impl<Frame> Router<Frame> {
+	/// Stop the background thread and wait for it to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		crate::shutdown::join_threads(self.thread.take(), deadline)
+	}
+}
+
+impl<Frame> Drop for Router<Frame> {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl<Frame> Shutdown for Router<Frame> {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// State shared between the router and the background thread.
+struct Shared<Frame> {
+	/// The mutable state of the router.
+	state: Mutex<State<Frame>>,
+}
+
+/// The mutable state of the router.
+struct State<Frame> {
+	/// The subscriptions, in the order they were made.
+	subscribers: Vec<(Filter<Frame>, Sender<Frame>)>,
+
+	/// The subscription for frames that match no filter.
+	unmatched: Option<Sender<Frame>>,
+
+	/// The number of times the framer reported invalid data.
+	invalid_frames: u64,
+
+	/// The error that stopped the background thread.
+	error: Option<std::io::Error>,
+
+	/// True if the router is being dropped.
+	closed: bool,
+}
+
+impl<Frame> Shared<Frame> {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State<Frame>> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+impl<Frame: Clone> Shared<Frame> {
+	/// Run the background thread.
+	fn run<F>(&self, mut reader: FrameReader<F>)
+	where
+		F: Framer<Frame = Frame>,
+	{
+		loop {
+			if self.lock().closed {
+				return;
+			}
+			match reader.read_frame() {
+				Ok(frame) => self.lock().route(frame),
+				Err(e) if e.kind() == std::io::ErrorKind::InvalidData => self.lock().invalid_frames += 1,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => {
+					let mut state = self.lock();
+					state.error = Some(e);
+					state.subscribers.clear();
+					state.unmatched = None;
+					return;
+				},
+			}
+		}
+	}
+}
+
+impl<Frame: Clone> State<Frame> {
+	/// Deliver a frame to all matching subscribers, or to the subscription for unmatched frames.
+	fn route(&mut self, frame: Frame) {
+		let mut matched = false;
+		// Drop subscriptions if the receiver is gone.
+		self.subscribers.retain(|(filter, sender)| {
+			if !filter.matches(&frame) {
+				return true;
+			}
+			matched = true;
+			sender.send(frame.clone()).is_ok()
+		});
+		if !matched {
+			if let Some(sender) = &self.unmatched {
+				if sender.send(frame).is_err() {
+					self.unmatched = None;
+				}
+			}
+		}
+	}
+}
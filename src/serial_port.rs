@@ -1,8 +1,23 @@
 use std::io::{IoSlice, IoSliceMut};
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::{sys, IntoSettings, Settings};
+use crate::byte_transform::TransformSlot;
+use crate::control;
+use crate::handles::{self, Claims};
+use crate::inter_byte_delay::TxDelay;
+use crate::noise_filter::NoiseGate;
+use crate::profile;
+use crate::software_parity::ParityCodec;
+use crate::transaction::ResponseMatcher;
+use crate::{
+	sys, ByteTransform, CharSize, ControlHandle, FlowControl, FlowControlStall, InterByteDelay, IntoSettings, IoHandle,
+	ModemLines, NoiseFilter, OpenOptions, Parity, ReadHalf, Reader, Settings, SoftwareParity, StopBits, WriteHalf,
+	Writer,
+};
+
+#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+use crate::PortState;
 
 #[cfg(any(feature = "doc", all(feature = "rs4xx", target_os = "linux")))]
 use crate::rs4xx;
@@ -10,6 +25,13 @@ use crate::rs4xx;
 /// A serial port.
 pub struct SerialPort {
 	inner: sys::SerialPort,
+	claims: Claims,
+	tx_delay: TxDelay,
+	transform: TransformSlot,
+	software_parity: Option<ParityCodec>,
+	noise_gate: NoiseGate,
+	applied_settings: Option<Settings>,
+	path: Option<PathBuf>,
 }
 
 impl SerialPort {
@@ -24,6 +46,11 @@ impl SerialPort {
 	///
 	/// The library automatically uses the win32 device namespace on Windows,
 	/// so COM ports above COM9 are supported out of the box.
+	/// Names that already start with `\\`, like `\\.\COM12`, are used as-is.
+	///
+	/// On Windows, you can also pass a device instance ID, such as `USB\VID_0403&PID_6001\A6008CB1`.
+	/// The library will then open the COM port that is currently assigned to that device.
+	/// Unlike the COM port name, the instance ID of a USB device with a serial number stays the same when it is plugged into a different USB port.
 	///
 	/// # Example 1: Open a serial port with a specific baud rate and default settings.
 	/// ```
@@ -51,12 +78,42 @@ impl SerialPort {
 	/// # }
 	/// ```
 	pub fn open(name: impl AsRef<Path>, settings: impl IntoSettings) -> std::io::Result<Self> {
-		let mut serial_port = Self {
-			inner: sys::SerialPort::open(name.as_ref())?,
-		};
-		let mut port_settings = serial_port.get_configuration()?;
+		let mut serial_port = Self::from_inner(sys::SerialPort::open(name.as_ref())?);
+		serial_port.path = Some(name.as_ref().to_owned());
+		let current_settings = serial_port.get_configuration()?;
+		let mut port_settings = current_settings.clone();
 		settings.apply_to_settings(&mut port_settings)?;
-		serial_port.set_configuration(&port_settings)?;
+		if port_settings.matches(&current_settings) {
+			// Do not touch the configuration at all if nothing changes,
+			// so that ports configured by another process (like `getty`) are left exactly as they are.
+			serial_port.applied_settings = Some(current_settings);
+		} else {
+			serial_port.set_configuration(&port_settings)?;
+		}
+		Ok(serial_port)
+	}
+
+	/// Create options to open a serial port, for more control than [`Self::open()`] offers.
+	///
+	/// See [`OpenOptions`] for more information.
+	pub fn builder(name: impl AsRef<Path>) -> OpenOptions {
+		OpenOptions::new(name)
+	}
+
+	/// Open a serial port without configuring it, for [`OpenOptions::open()`].
+	pub(crate) fn open_unconfigured(name: &Path, read_only: bool, exclusive: bool) -> std::io::Result<Self> {
+		let inner = match read_only {
+			false => sys::SerialPort::open(name)?,
+			true => sys::SerialPort::open_read_only(name)?,
+		};
+		#[cfg(unix)]
+		if exclusive {
+			inner.set_exclusive(true)?;
+		}
+		#[cfg(windows)]
+		let _ = exclusive;
+		let mut serial_port = Self::from_inner(inner);
+		serial_port.path = Some(name.to_owned());
 		Ok(serial_port)
 	}
 
@@ -66,8 +123,8 @@ impl SerialPort {
 	pub fn pair() -> std::io::Result<(Self, Self)> {
 		#[cfg(unix)] {
 			let (pty_a, pty_b) = sys::SerialPort::pair()?;
-			let mut pty_a = Self { inner: pty_a };
-			let mut pty_b = Self { inner: pty_b };
+			let mut pty_a = Self::from_inner(pty_a);
+			let mut pty_b = Self::from_inner(pty_b);
 			{
 				let mut settings = pty_a.get_configuration()?;
 				settings.set_raw();
@@ -86,6 +143,100 @@ impl SerialPort {
 		}
 	}
 
+	/// Export the state of the serial port, to hand it over to a new process image with `exec`.
+	///
+	/// This clears the close-on-exec flag of the file descriptor, so it is inherited by the new process image.
+	/// Pass the returned state to the new process image as a string, and use [`Self::import_state()`] to take the port back.
+	/// See [`PortState`] for more information.
+	///
+	/// This handle should not be used anymore after exporting the state,
+	/// except to restore the close-on-exec flag with [`Self::import_state()`] if the `exec` fails.
+	#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "unix")))]
+	pub fn export_state(&self) -> std::io::Result<PortState> {
+		#[cfg(unix)] {
+			use std::os::unix::io::AsRawFd;
+			let fd = self.inner.file.as_raw_fd();
+			sys::set_close_on_exec(fd, false)?;
+			Ok(PortState {
+				fd,
+				read_timeout: self.get_read_timeout()?,
+				write_timeout: self.get_write_timeout()?,
+				inter_byte_delay: self.get_inter_byte_delay(),
+				software_parity: self.software_parity.as_ref().map(|codec| (codec.parity(), codec.data_bits())),
+				transform: self.get_byte_transform(),
+			})
+		}
+		#[cfg(windows)] {
+			unreachable!("this code is only enabled on Unix platforms or during documentation generation")
+		}
+	}
+
+	/// Import the state of a serial port that was exported with [`Self::export_state()`].
+	///
+	/// This takes ownership of the file descriptor in the state, sets its close-on-exec flag again,
+	/// and restores the per-handle settings of this library.
+	///
+	/// If the file descriptor is not open, an error is returned.
+	///
+	/// # Safety
+	/// The file descriptor in the state must refer to a serial port,
+	/// and it must not be owned by anything else in this process.
+	/// In particular, the state must not be imported twice.
+	#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "unix")))]
+	pub unsafe fn import_state(state: &PortState) -> std::io::Result<Self> {
+		#[cfg(unix)] {
+			use std::os::unix::io::FromRawFd;
+			sys::set_close_on_exec(state.fd, true)?;
+			let file = std::fs::File::from_raw_fd(state.fd);
+			let mut port = Self::from_inner(sys::SerialPort::from_file(file));
+			port.set_read_timeout(state.read_timeout)?;
+			port.set_write_timeout(state.write_timeout)?;
+			port.set_inter_byte_delay(state.inter_byte_delay);
+			port.set_byte_transform(state.transform.clone());
+			port.software_parity = state
+				.software_parity
+				.map(|(parity, data_bits)| ParityCodec::new(parity, data_bits));
+			Ok(port)
+		}
+		#[cfg(windows)] {
+			let _ = state;
+			unreachable!("this code is only enabled on Unix platforms or during documentation generation")
+		}
+	}
+
+	/// Open the master side of a new pseudo-terminal.
+	///
+	/// Returns the master side as a [`SerialPort`] and the path of the slave side.
+	/// The slave side behaves like a real serial port,
+	/// so the path can be handed to other programs to emulate a serial device towards them.
+	/// Everything written to the master side can be read from the slave side, and vice versa.
+	///
+	/// The pseudo-terminal is put in raw mode before it is returned.
+	/// Programs that open the slave side may still change the settings.
+	///
+	/// On most platforms, reading from the master side fails with an error while the slave side is not opened by anyone.
+	/// Open the slave side yourself and keep it open if you need to read from the master side without a connected program.
+	///
+	/// The master side can be registered with an event loop through the raw file descriptor,
+	/// for example with `mio::unix::SourceFd`.
+	#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "unix")))]
+	pub fn open_pty() -> std::io::Result<(Self, PathBuf)> {
+		#[cfg(unix)] {
+			let (master, slave_name) = sys::SerialPort::open_pty()?;
+			let mut master = Self::from_inner(master);
+			let mut settings = master.get_configuration()?;
+			settings.set_raw();
+			master.set_configuration(&settings)?;
+			Ok((master, slave_name))
+		}
+		#[cfg(windows)] {
+			unreachable!("this code is only enabled on Unix platforms or during documentation generation")
+		}
+	}
+
 	/// Get a list of available serial ports.
 	///
 	/// Not currently supported on all platforms.
@@ -95,8 +246,236 @@ impl SerialPort {
 	}
 
 	/// Configure (or reconfigure) the serial port.
+	///
+	/// If a noise filter is set with [`Self::set_noise_filter()`] and the baud rate changes, the noise filter is armed.
+	///
+	/// The applied configuration is remembered, so that changes made by other processes can be detected
+	/// with [`Self::check_configuration_drift()`].
 	pub fn set_configuration(&mut self, settings: &Settings) -> std::io::Result<()> {
-		self.inner.set_configuration(&settings.inner)
+		let old_baud_rate = match self.noise_gate.config() {
+			Some(_) => Some(self.get_configuration()?.get_baud_rate().ok()),
+			None => None,
+		};
+		self.inner.set_configuration(&settings.inner)?;
+		// Remember the configuration as reported back, since the OS may adjust some settings.
+		self.applied_settings = self.get_configuration().ok();
+		if old_baud_rate.is_some_and(|old_baud_rate| settings.get_baud_rate().ok() != old_baud_rate) {
+			self.noise_gate.arm();
+		}
+		Ok(())
+	}
+
+	/// Change the baud rate, keeping all other settings.
+	///
+	/// See [`Self::update_configuration()`] for the guarantees of a partial configuration update.
+	pub fn set_baud_rate(&mut self, baud_rate: u32) -> std::io::Result<()> {
+		self.update_configuration(|settings| settings.set_baud_rate(baud_rate))
+	}
+
+	/// Change the character size, keeping all other settings.
+	///
+	/// See [`Self::update_configuration()`] for the guarantees of a partial configuration update.
+	pub fn set_char_size(&mut self, char_size: CharSize) -> std::io::Result<()> {
+		self.update_configuration(|settings| {
+			settings.set_char_size(char_size);
+			Ok(())
+		})
+	}
+
+	/// Change the number of stop bits, keeping all other settings.
+	///
+	/// See [`Self::update_configuration()`] for the guarantees of a partial configuration update.
+	pub fn set_stop_bits(&mut self, stop_bits: StopBits) -> std::io::Result<()> {
+		self.update_configuration(|settings| {
+			settings.set_stop_bits(stop_bits);
+			Ok(())
+		})
+	}
+
+	/// Change the parity mode, keeping all other settings.
+	///
+	/// This is cheap when the parity does not change,
+	/// so protocols that select the parity per frame can call it before every frame.
+	///
+	/// See [`Self::update_configuration()`] for the guarantees of a partial configuration update.
+	pub fn set_parity(&mut self, parity: Parity) -> std::io::Result<()> {
+		self.update_configuration(|settings| {
+			settings.set_parity(parity);
+			Ok(())
+		})
+	}
+
+	/// Change the flow control mode, keeping all other settings.
+	///
+	/// See [`Self::update_configuration()`] for the guarantees of a partial configuration update.
+	pub fn set_flow_control(&mut self, flow_control: FlowControl) -> std::io::Result<()> {
+		self.update_configuration(|settings| {
+			settings.set_flow_control(flow_control);
+			Ok(())
+		})
+	}
+
+	/// Change part of the configuration, keeping all other settings.
+	///
+	/// This reads the current configuration, lets `update` modify it, and applies the result with [`Self::set_configuration()`].
+	/// If the configuration does not change, nothing is written to the serial port.
+	///
+	/// If applying the new configuration fails, for example because the OS did not accept all settings,
+	/// the previous configuration is restored before the error is returned.
+	pub fn update_configuration<F>(&mut self, update: F) -> std::io::Result<()>
+	where
+		F: FnOnce(&mut Settings) -> std::io::Result<()>,
+	{
+		let previous = self.get_configuration()?;
+		let mut settings = previous.clone();
+		update(&mut settings)?;
+		if settings.matches(&previous) {
+			return Ok(());
+		}
+		match self.set_configuration(&settings) {
+			Ok(()) => Ok(()),
+			Err(e) => {
+				// The new settings may be partially applied, so restore the previous ones.
+				let _ = self.set_configuration(&previous);
+				Err(e)
+			},
+		}
+	}
+
+	/// Get the configuration that was last applied through this handle, if any.
+	///
+	/// This is the configuration as reported by the operating system right after [`Self::set_configuration()`].
+	pub fn get_applied_configuration(&self) -> Option<&Settings> {
+		self.applied_settings.as_ref()
+	}
+
+	/// Check if the configuration of the serial port was changed behind the back of this handle.
+	///
+	/// On some platforms, other processes (like `getty` or ModemManager) can reconfigure a serial port that is open.
+	/// This compares the live configuration with the configuration that was last applied through this handle.
+	///
+	/// Returns the live configuration if it differs, or `None` if it matches
+	/// or if no configuration was applied through this handle.
+	/// Note that changes made through a clone of this handle (see [`Self::try_clone()`]) are reported as drift too.
+	///
+	/// To check periodically in a background thread, use [`drift::DriftMonitor`][crate::drift::DriftMonitor].
+	pub fn check_configuration_drift(&self) -> std::io::Result<Option<Settings>> {
+		let Some(applied) = &self.applied_settings else {
+			return Ok(None);
+		};
+		let live = self.get_configuration()?;
+		if live.matches(applied) {
+			Ok(None)
+		} else {
+			Ok(Some(live))
+		}
+	}
+
+	/// Measure the throughput and latency of the link against an echoing peer.
+	///
+	/// The other end of the link must echo back everything it receives,
+	/// either with a loopback plug that connects TX to RX or with a device or program that echoes the data.
+	/// The measurement takes roughly the given duration, plus up to one read timeout to collect the last echoed data.
+	/// A quarter of the time is spent on round trip latency probes, the rest on a throughput test in both directions.
+	///
+	/// Any data in the input buffer is discarded before the measurement starts.
+	/// See [`link_test`][crate::link_test] for more details.
+	pub fn measure_link(&self, duration: Duration) -> std::io::Result<crate::link_test::LinkReport> {
+		crate::link_test::measure(self, duration)
+	}
+
+	/// Change the baud rate after all queued data has been transmitted.
+	///
+	/// This is intended for protocols that negotiate a speed switch in the middle of a session.
+	/// It performs the following steps:
+	/// * Wait for all data in the output buffer to be transmitted (see [`Self::flush()`]).
+	/// * Wait for `guard_time`, to give the peer time to switch to the new baud rate.
+	/// * Apply the new baud rate, keeping all other settings.
+	/// * If `discard_input` is true, discard the input buffer.
+	///
+	/// Data received before the baud rate changed was received at the old baud rate.
+	/// If the peer switches speed at the same time, that data may be garbage and you should discard it.
+	/// If the peer waits for an acknowledgement at the old speed, you should preserve it instead.
+	pub fn change_baud_rate_drained(
+		&mut self,
+		baud_rate: u32,
+		guard_time: Duration,
+		discard_input: bool,
+	) -> std::io::Result<()> {
+		self.flush()?;
+		if !guard_time.is_zero() {
+			std::thread::sleep(guard_time);
+		}
+		let mut settings = self.get_configuration()?;
+		settings.set_baud_rate(baud_rate)?;
+		self.set_configuration(&settings)?;
+		if discard_input {
+			self.discard_input_buffer()?;
+		}
+		Ok(())
+	}
+
+	/// Perform a request/response transaction.
+	///
+	/// This performs the following steps:
+	/// * Discard the input buffer, to get rid of stale data from earlier transactions or noise on the line.
+	/// * Write the request.
+	/// * Read until the `matcher` reports a complete response, or until the timeout expires.
+	///
+	/// On success, the response is returned.
+	/// Data received after the end of the response is discarded.
+	/// If the timeout expires, an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	///
+	/// See the [`transaction`][crate::transaction] module for the available matchers.
+	/// A closure that takes `&[u8]` and returns the length of the response as `Option<usize>` can also be used.
+	///
+	/// This function temporarily changes the read timeout of the serial port.
+	/// The original read timeout is restored before the function returns.
+	pub fn transact(
+		&mut self,
+		request: &[u8],
+		mut matcher: impl ResponseMatcher,
+		timeout: Duration,
+	) -> std::io::Result<Vec<u8>> {
+		let deadline = std::time::Instant::now() + timeout;
+		let original_timeout = self.get_read_timeout()?;
+		let result = self.transact_inner(request, &mut matcher, deadline);
+		self.set_read_timeout(original_timeout)?;
+		result
+	}
+
+	/// Perform a transaction, leaving the read timeout modified.
+	fn transact_inner(
+		&mut self,
+		request: &[u8],
+		matcher: &mut impl ResponseMatcher,
+		deadline: std::time::Instant,
+	) -> std::io::Result<Vec<u8>> {
+		self.discard_input_buffer()?;
+		self.write_all(request)?;
+
+		let mut response = Vec::new();
+		let mut chunk = [0; 256];
+		loop {
+			if let Some(len) = matcher.match_response(&response) {
+				response.truncate(len);
+				return Ok(response);
+			}
+			let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+			if remaining.is_zero() {
+				return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for response"));
+			}
+			self.set_read_timeout(remaining)?;
+			let max_read = matcher.max_read(&response).clamp(1, chunk.len());
+			match self.read(&mut chunk[..max_read]) {
+				Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+				Ok(read) => response.extend_from_slice(&chunk[..read]),
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				// The timeout may have been truncated by the platform, so check the remaining time again.
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) => return Err(e),
+			}
+		}
 	}
 
 	/// Get the current configuration of the serial port.
@@ -109,16 +488,125 @@ impl SerialPort {
 		})
 	}
 
+	/// Save the configuration of the serial port in a profile file.
+	///
+	/// The profile stores the baud rate, character size, stop bits, parity, flow control and timeouts.
+	/// With the `"rs4xx"` feature on Linux, the RS-4xx mode is saved too, if the port reports one.
+	///
+	/// A profile file can hold the configuration of multiple devices.
+	/// Each configuration is keyed by a stable identity of the device:
+	/// for USB devices with a serial number, the vendor ID, product ID and serial number,
+	/// so the configuration is found again when the device is assigned a different path.
+	/// Other devices are identified by their path.
+	/// An existing configuration for the same device is replaced.
+	///
+	/// Each configuration is protected by a checksum, which is verified by [`Self::load_profile()`].
+	///
+	/// This is currently only supported on Linux and Android.
+	/// On other platforms, an error of the type [`std::io::ErrorKind::Unsupported`] is returned.
+	pub fn save_profile(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+		profile::save(self, path.as_ref())
+	}
+
+	/// Load the configuration of the serial port from a profile file and apply it.
+	///
+	/// If the profile file has no configuration for this device, an error of the type [`std::io::ErrorKind::NotFound`] is returned.
+	/// If the checksum of a configuration in the file does not match, an error of the type [`std::io::ErrorKind::InvalidData`] is returned.
+	///
+	/// See [`Self::save_profile()`] for more information.
+	pub fn load_profile(&mut self, path: impl AsRef<Path>) -> std::io::Result<()> {
+		profile::load(self, path.as_ref())
+	}
+
 	/// Try to clone the serial port handle.
 	///
 	/// The cloned object refers to the same serial port.
+	/// On Unix, the clone duplicates the file descriptor (close-on-exec).
+	/// On Windows, it duplicates the handle with `DuplicateHandle()`.
+	/// Either way, the clone stays valid when the original is dropped.
+	///
+	/// The clone starts with the same inter-byte delay, byte transform and software parity as the original,
+	/// but changing them on one handle does not affect the other.
+	/// The noise filter is not copied to the clone.
 	///
 	/// Mixing reads and writes on different handles to the same serial port from different threads may lead to unexpect results.
 	/// The data may end up interleaved in unpredictable ways.
 	pub fn try_clone(&self) -> std::io::Result<Self> {
-		Ok(Self {
-			inner: self.inner.try_clone()?,
-		})
+		let mut clone = Self::from_inner(self.inner.try_clone()?);
+		clone.path = self.path.clone();
+		clone.set_inter_byte_delay(self.get_inter_byte_delay());
+		clone.set_byte_transform(self.get_byte_transform());
+		clone.software_parity = self
+			.software_parity
+			.as_ref()
+			.map(|codec| ParityCodec::new(codec.parity(), codec.data_bits()));
+		Ok(clone)
+	}
+
+	/// Get the path of the serial port.
+	///
+	/// For ports opened with [`Self::open()`], this is the path or name that was passed to it.
+	/// For ports created in other ways, like from a raw file descriptor, the path is resolved from the file descriptor or handle.
+	/// This is supported on Linux, Android, macOS, iOS and Windows, but the Windows driver of the device may not support it.
+	/// If the path can not be resolved, this returns `None`.
+	pub fn path(&self) -> Option<PathBuf> {
+		self.path.clone().or_else(|| self.inner.device_path())
+	}
+
+	/// Get the name of the serial port, like `ttyUSB0` or `COM3`.
+	///
+	/// This is the last component of [`Self::path()`].
+	pub fn name(&self) -> Option<String> {
+		let path = self.path()?;
+		Some(path.file_name()?.to_string_lossy().into_owned())
+	}
+
+	/// Claim the read side of the serial port.
+	///
+	/// At most one [`Reader`] can exist for a serial port at any time.
+	/// If the read side is already claimed, an error of the type [`std::io::ErrorKind::ResourceBusy`] is returned.
+	/// The claim is released when the [`Reader`] is dropped.
+	///
+	/// Together with [`Self::writer()`], this enforces the use of at most one thread for reading and one thread for writing.
+	/// Note that the claim only covers this handle:
+	/// the functions on [`SerialPort`] itself and handles created with [`Self::try_clone()`] can still be used directly.
+	pub fn reader(&self) -> std::io::Result<Reader<'_>> {
+		Reader::claim(self, &self.claims)
+	}
+
+	/// Claim the write side of the serial port.
+	///
+	/// At most one [`Writer`] can exist for a serial port at any time.
+	/// If the write side is already claimed, an error of the type [`std::io::ErrorKind::ResourceBusy`] is returned.
+	/// The claim is released when the [`Writer`] is dropped.
+	///
+	/// See [`Self::reader()`] for more information.
+	pub fn writer(&self) -> std::io::Result<Writer<'_>> {
+		Writer::claim(self, &self.claims)
+	}
+
+	/// Split the serial port into an owned read half and write half.
+	///
+	/// The halves can be moved to different threads or different parts of an application.
+	/// They do not share a lock, so reads and writes never wait for each other.
+	/// Use [`ReadHalf::unsplit()`] to join the halves again.
+	///
+	/// Both halves implement `AsRawFd` on Unix and `AsRawHandle` on Windows,
+	/// so they can be registered with an event loop separately.
+	pub fn split(self) -> (ReadHalf, WriteHalf) {
+		handles::split(self)
+	}
+
+	/// Split the serial port into a control handle and an I/O handle.
+	///
+	/// The [`ControlHandle`] can change the configuration and control the modem lines,
+	/// and the [`IoHandle`] can read and write data.
+	/// Both handles can be moved to different threads.
+	///
+	/// Configuration changes through the [`ControlHandle`] wait until any read or write on the [`IoHandle`] is finished,
+	/// so the settings never change in the middle of an I/O operation.
+	pub fn split_control(self) -> std::io::Result<(ControlHandle, IoHandle)> {
+		control::split(self)
 	}
 
 	/// Read bytes from the serial port.
@@ -128,8 +616,18 @@ impl SerialPort {
 	///
 	/// Note that there are no guarantees on which thread receives what data when multiple threads are reading from the serial port.
 	/// You should normally limit yourself to a single reading thread and a single writing thread.
+	///
+	/// If a noise filter is armed, data received during the discard time of the filter is silently discarded.
 	pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
-		self.inner.read(buf)
+		loop {
+			self.discard_expired_noise()?;
+			let read = self.inner.read(buf)?;
+			if read > 0 && self.noise_gate.is_discarding() {
+				continue;
+			}
+			self.decode(&mut buf[..read]);
+			return Ok(read);
+		}
 	}
 
 	/// Read bytes from the serial port into a slice of buffers.
@@ -139,8 +637,23 @@ impl SerialPort {
 	///
 	/// Note that there are no guarantees on which thread receives what data when multiple threads are reading from the serial port.
 	/// You should normally limit yourself to a single reading thread and a single writing thread.
+	///
+	/// If a noise filter is armed, data received during the discard time of the filter is silently discarded.
 	pub fn read_vectored(&self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
-		self.inner.read_vectored(buf)
+		let read = loop {
+			self.discard_expired_noise()?;
+			let read = self.inner.read_vectored(buf)?;
+			if read == 0 || !self.noise_gate.is_discarding() {
+				break read;
+			}
+		};
+		let mut remaining = read;
+		for buf in buf.iter_mut() {
+			let len = buf.len().min(remaining);
+			self.decode(&mut buf[..len]);
+			remaining -= len;
+		}
+		Ok(read)
 	}
 
 	/// Check if the implementation supports vectored reads.
@@ -189,8 +702,38 @@ impl SerialPort {
 	///
 	/// Note that data written to the same serial port from multiple threads may end up interleaved at the receiving side.
 	/// You should normally limit yourself to a single reading thread and a single writing thread.
+	///
+	/// If the write times out because transmission is held back by flow control,
+	/// the returned error wraps a [`FlowControlStall`] describing the reason.
+	///
+	/// If an inter-byte delay is configured with [`Self::set_inter_byte_delay()`],
+	/// this function writes at most one group of bytes and may block for the delay.
 	pub fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
-		self.inner.write(buf)
+		let _guard = self.claims.lock_guard_shared();
+		self.write_unguarded(buf)
+	}
+
+	/// Write bytes to the serial port without taking the guard lock.
+	fn write_unguarded(&self, buf: &[u8]) -> std::io::Result<usize> {
+		let encoded = self.encode(buf);
+		let buf = encoded.as_deref().unwrap_or(buf);
+
+		let mut tx_delay = self.tx_delay.lock();
+		let config = match tx_delay.config() {
+			Some(config) if !buf.is_empty() => config,
+			_ => {
+				drop(tx_delay);
+				return self.inner.write(buf).map_err(|e| self.diagnose_write_error(e));
+			},
+		};
+
+		let len = tx_delay.begin_write(&config).min(buf.len());
+		let written = self.inner.write(&buf[..len]).map_err(|e| self.diagnose_write_error(e))?;
+		if tx_delay.add_written(&config, written) {
+			self.inner.flush_output()?;
+			tx_delay.end_group();
+		}
+		Ok(written)
 	}
 
 	/// Write all bytes to the serial port.
@@ -224,6 +767,80 @@ impl SerialPort {
 		Ok(())
 	}
 
+	/// Read the exact number of bytes required to fill the buffer, or fail when the deadline passes.
+	///
+	/// Unlike [`Self::read_exact()`], which applies the read timeout to each individual read,
+	/// this function limits the total time spent reading.
+	/// If the deadline passes before the buffer is filled, an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	///
+	/// If this function returns an error, it may already have read some data from the serial port into the provided buffer.
+	///
+	/// This function temporarily changes the read timeout of the serial port.
+	/// The original read timeout is restored before the function returns.
+	pub fn read_exact_deadline(&mut self, buf: &mut [u8], deadline: Instant) -> std::io::Result<()> {
+		let read_timeout = self.get_read_timeout()?;
+		let result = self.read_exact_until(buf, deadline);
+		self.set_read_timeout(read_timeout)?;
+		result
+	}
+
+	/// Read the exact number of bytes required to fill the buffer, changing the read timeout to respect the deadline.
+	fn read_exact_until(&mut self, buf: &mut [u8], deadline: Instant) -> std::io::Result<()> {
+		let mut buf = buf;
+		while !buf.is_empty() {
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return Err(std::io::ErrorKind::TimedOut.into());
+			}
+			self.set_read_timeout(remaining)?;
+			match self.read(buf) {
+				Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "failed to fill whole buffer")),
+				Ok(n) => buf = &mut buf[n..],
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(())
+	}
+
+	/// Write all bytes to the serial port, or fail when the deadline passes.
+	///
+	/// Unlike [`Self::write_all()`], which applies the write timeout to each individual write,
+	/// this function limits the total time spent writing.
+	/// If the deadline passes before all data is written, an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	///
+	/// If this function returns an error, it may already have transmitted some data from the buffer over the serial port.
+	///
+	/// This function temporarily changes the write timeout of the serial port.
+	/// The original write timeout is restored before the function returns.
+	pub fn write_all_deadline(&mut self, buf: &[u8], deadline: Instant) -> std::io::Result<()> {
+		let write_timeout = self.get_write_timeout()?;
+		let result = self.write_all_until(buf, deadline);
+		self.set_write_timeout(write_timeout)?;
+		result
+	}
+
+	/// Write all bytes to the serial port, changing the write timeout to respect the deadline.
+	fn write_all_until(&mut self, buf: &[u8], deadline: Instant) -> std::io::Result<()> {
+		let mut buf = buf;
+		while !buf.is_empty() {
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return Err(std::io::ErrorKind::TimedOut.into());
+			}
+			self.set_write_timeout(remaining)?;
+			match self.write(buf) {
+				Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "failed to write whole buffer")),
+				Ok(n) => buf = &buf[n..],
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) => return Err(e),
+			}
+		}
+		Ok(())
+	}
+
 	/// Write bytes to the serial port from a slice of buffers.
 	///
 	/// This is identical to [`std::io::Write::write_vectored()`], except that this function takes a const reference `&self`.
@@ -231,8 +848,110 @@ impl SerialPort {
 	///
 	/// Note that data written to the same serial port from multiple threads may end up interleaved at the receiving side.
 	/// You should normally limit yourself to a single reading thread and a single writing thread.
+	///
+	/// If the write times out because transmission is held back by flow control,
+	/// the returned error wraps a [`FlowControlStall`] describing the reason.
 	pub fn write_vectored(&self, buf: &[IoSlice<'_>]) -> std::io::Result<usize> {
-		self.inner.write_vectored(buf)
+		let _guard = self.claims.lock_guard_shared();
+		if self.get_inter_byte_delay().is_some() || self.transform.is_set() || self.software_parity.is_some() {
+			let buf = buf.iter().find(|buf| !buf.is_empty()).map_or(&[][..], |buf| &buf[..]);
+			return self.write_unguarded(buf);
+		}
+		self.inner.write_vectored(buf).map_err(|e| self.diagnose_write_error(e))
+	}
+
+	/// Write data with a period of silence on the transmit line before and after it.
+	///
+	/// This first waits until all previously written data has been transmitted,
+	/// then keeps the line silent for `pre_silence`, writes all data,
+	/// waits for it to be transmitted and keeps the line silent for `post_silence`.
+	/// A typical use is the `+++` escape sequence of Hayes compatible modems, which needs a guard time of one second on both sides.
+	///
+	/// Other writes to the same [`SerialPort`] are blocked during the entire operation, so they can not violate the guard times.
+	/// This includes writes from other threads and from a [`WriteQueue`][crate::write_queue::WriteQueue].
+	/// A frame written with [`Self::write_frame()`] is never split by a guarded write.
+	/// Note that this does not protect against writes through handles created with [`Self::try_clone()`].
+	pub fn write_guarded(&self, data: &[u8], pre_silence: Duration, post_silence: Duration) -> std::io::Result<()> {
+		let _frame = self.claims.lock_frame();
+		let _guard = self.claims.lock_guard_exclusive();
+		self.inner.flush_output()?;
+		std::thread::sleep(pre_silence);
+
+		let mut data = data;
+		while !data.is_empty() {
+			match self.write_unguarded(data) {
+				Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "failed to write whole buffer")),
+				Ok(n) => data = &data[n..],
+				Err(e) => {
+					if e.kind() != std::io::ErrorKind::Interrupted {
+						return Err(e);
+					}
+				},
+			}
+		}
+
+		self.inner.flush_output()?;
+		std::thread::sleep(post_silence);
+		Ok(())
+	}
+
+	/// Write urgent data to the serial port, bypassing the inter-byte delay.
+	///
+	/// This is intended for emergency commands, like an emergency stop.
+	/// The data is written as soon as any frame that is currently being written with [`Self::write_frame()`]
+	/// or [`Self::write_guarded()`] is complete, so frames are never split by urgent data.
+	/// The inter-byte delay configured with [`Self::set_inter_byte_delay()`] is not applied to the urgent data,
+	/// but the byte transform and software parity are.
+	///
+	/// Plain writes from other threads are not held back and may still be interleaved with the urgent data.
+	/// To give urgent data priority over queued writes, use
+	/// [`WriteQueue::write_urgent()`][crate::write_queue::WriteQueue::write_urgent].
+	pub fn write_urgent(&self, data: &[u8]) -> std::io::Result<()> {
+		let _frame = self.claims.lock_frame();
+		let _guard = self.claims.lock_guard_shared();
+		let encoded = self.encode(data);
+		let mut data = encoded.as_deref().unwrap_or(data);
+		while !data.is_empty() {
+			match self.inner.write(data) {
+				Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "failed to write whole buffer")),
+				Ok(n) => data = &data[n..],
+				Err(e) => {
+					if e.kind() != std::io::ErrorKind::Interrupted {
+						return Err(self.diagnose_write_error(e));
+					}
+				},
+			}
+		}
+		Ok(())
+	}
+
+	/// Write a frame consisting of multiple buffers to the serial port.
+	///
+	/// The frame is written as a whole: concurrent calls to `write_frame()` on the same [`SerialPort`] are serialized,
+	/// so frames from different threads are never interleaved.
+	/// Note that this does not protect against concurrent calls to other write functions,
+	/// or writes through handles created with [`Self::try_clone()`].
+	///
+	/// Like [`Self::write_all()`], this function may already have transmitted part of the frame if it returns an error.
+	/// If you need to guarantee that a frame is queued either completely or not at all,
+	/// use [`WriteQueue::queue_frame()`][crate::write_queue::WriteQueue::queue_frame].
+	pub fn write_frame(&self, frame: &[IoSlice<'_>]) -> std::io::Result<()> {
+		let _lock = self.claims.lock_frame();
+		let mut frame = frame.to_vec();
+		let mut frame = frame.as_mut_slice();
+		IoSlice::advance_slices(&mut frame, 0);
+		while !frame.is_empty() {
+			match self.write_vectored(frame) {
+				Ok(0) => return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "failed to write whole frame")),
+				Ok(n) => IoSlice::advance_slices(&mut frame, n),
+				Err(e) => {
+					if e.kind() != std::io::ErrorKind::Interrupted {
+						return Err(e);
+					}
+				},
+			}
+		}
+		Ok(())
 	}
 
 	/// Check if the implementation supports vectored writes.
@@ -252,6 +971,162 @@ impl SerialPort {
 		self.inner.flush_output()
 	}
 
+	/// Check if all data in the output buffer has been transmitted, without blocking.
+	///
+	/// This is the non-blocking variant of [`Self::wait_tx_empty()`],
+	/// for event loops that can not afford to block until the transmitter is empty.
+	/// Such a loop can check this function from a timer after writing, until it returns `true`.
+	///
+	/// See [`Self::wait_tx_empty()`] for the platform specific details.
+	pub fn is_tx_empty(&self) -> std::io::Result<bool> {
+		self.inner.is_tx_empty()
+	}
+
+	/// Wait for all data in the output buffer to be transmitted, up to a maximum time.
+	///
+	/// Unlike [`Self::flush()`], this function has a timeout.
+	/// If the data is not transmitted before the timeout expires,
+	/// an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	///
+	/// This is useful to switch the direction of a half-duplex transceiver (like RS-485) at the right time,
+	/// or to know when a device may be put to sleep.
+	///
+	/// On Linux, this checks the line status register of the UART if the driver supports it,
+	/// so the function only returns when the last bit left the shift register.
+	/// Otherwise, it waits for the kernel output queue to become empty,
+	/// which may be slightly before the last character is fully transmitted.
+	/// On Windows, this waits for the output queue of the driver to become empty.
+	/// On other platforms, an error of the type [`std::io::ErrorKind::Unsupported`] may be returned.
+	pub fn wait_tx_empty(&self, timeout: Duration) -> std::io::Result<()> {
+		let deadline = std::time::Instant::now() + timeout;
+		loop {
+			if self.is_tx_empty()? {
+				return Ok(());
+			}
+			let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+			if remaining.is_zero() {
+				return Err(std::io::ErrorKind::TimedOut.into());
+			}
+			std::thread::sleep(remaining.min(TX_EMPTY_POLL_INTERVAL));
+		}
+	}
+
+	/// Insert a pause between transmitted bytes, or between groups of bytes.
+	///
+	/// After each group, the write functions wait until the data has been transmitted,
+	/// and the next write waits until the configured delay has passed.
+	/// Because this is done in [`Self::write()`], it also applies to
+	/// [`Self::write_all()`], [`Self::write_frame()`] and everything built on top of them,
+	/// such as a [`WriteQueue`][crate::write_queue::WriteQueue].
+	///
+	/// Note that the write timeout applies to each individual group, not to a complete call to `write_all()`.
+	///
+	/// Pass `None` to disable the delay.
+	pub fn set_inter_byte_delay(&self, delay: Option<InterByteDelay>) {
+		self.tx_delay.lock().set_config(delay);
+	}
+
+	/// Get the pause inserted between transmitted bytes, if any.
+	pub fn get_inter_byte_delay(&self) -> Option<InterByteDelay> {
+		self.tx_delay.lock().config()
+	}
+
+	/// Set a transformation to apply to all transmitted and received bytes.
+	///
+	/// The transform is applied directly to the data read from and written to the device,
+	/// so it applies to all read and write functions and everything built on top of them.
+	///
+	/// Pass `None` to disable the transform.
+	pub fn set_byte_transform(&self, transform: Option<ByteTransform>) {
+		self.transform.set(transform);
+	}
+
+	/// Get the transformation applied to all transmitted and received bytes, if any.
+	pub fn get_byte_transform(&self) -> Option<ByteTransform> {
+		self.transform.get()
+	}
+
+	/// Set a filter to discard the garbage received after the serial port is opened or the baud rate changes.
+	///
+	/// Setting a filter immediately arms it, so call this right after opening the serial port.
+	/// The filter is armed again when the baud rate is changed with [`Self::set_configuration()`],
+	/// or when you call [`Self::arm_noise_filter()`].
+	///
+	/// While the filter is armed, all data returned by the kernel is discarded.
+	/// The read timeout applies to each read from the kernel, so a read may block longer than the timeout while data is discarded.
+	/// When the first read after the discard time starts, the kernel input buffer is discarded.
+	/// This also discards data that was received after the discard time but before that read,
+	/// so keep reading from the serial port during the discard time if that matters.
+	///
+	/// The noise filter is not applied to handles created with [`Self::try_clone()`].
+	///
+	/// Pass `None` to disable the filter.
+	pub fn set_noise_filter(&self, filter: Option<NoiseFilter>) {
+		self.noise_gate.set_config(filter);
+	}
+
+	/// Get the filter for the garbage received after the serial port is opened or the baud rate changes, if any.
+	pub fn get_noise_filter(&self) -> Option<NoiseFilter> {
+		self.noise_gate.config()
+	}
+
+	/// Arm the noise filter again.
+	///
+	/// Use this after an event that causes the device to emit garbage, like a reset through the DTR line.
+	/// If no noise filter is set, this does nothing.
+	pub fn arm_noise_filter(&self) {
+		self.noise_gate.arm();
+	}
+
+	/// Enable or disable parity computed and checked in software.
+	///
+	/// When enabling software parity, the current character size is used for the data bits.
+	/// The serial port is reconfigured without parity and with one extra data bit that carries the parity bit.
+	/// This fails with [`std::io::ErrorKind::InvalidInput`] if the character size is already 8 bits.
+	/// When disabling software parity, the original character size is restored, without parity.
+	///
+	/// Transmitted characters get a parity bit, and data bits that do not fit in the character size are discarded.
+	/// The parity bit of received characters is checked and removed.
+	/// Characters with a wrong parity bit are still returned by [`Self::read()`],
+	/// but they are counted and the count can be retrieved with [`Self::take_software_parity_errors()`].
+	///
+	/// Changing the character size with [`Self::set_configuration()`] while software parity is enabled is not supported.
+	/// Software parity is not applied to handles created with [`Self::try_clone()`].
+	pub fn set_software_parity(&mut self, parity: Option<SoftwareParity>) -> std::io::Result<()> {
+		let mut settings = self.get_configuration()?;
+		let data_bits = match &self.software_parity {
+			Some(codec) => codec.data_bits(),
+			None => settings.get_char_size()?.as_u8(),
+		};
+		let char_size = match parity {
+			Some(_) => data_bits + 1,
+			None => data_bits,
+		};
+		let char_size = CharSize::try_from(char_size).map_err(|_| {
+			std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				"software parity requires a character size of 7 bits or less",
+			)
+		})?;
+		settings.set_char_size(char_size);
+		settings.set_parity(Parity::None);
+		self.set_configuration(&settings)?;
+		self.software_parity = parity.map(|parity| ParityCodec::new(parity, data_bits));
+		Ok(())
+	}
+
+	/// Get the software parity mode, if enabled.
+	pub fn get_software_parity(&self) -> Option<SoftwareParity> {
+		self.software_parity.as_ref().map(|codec| codec.parity())
+	}
+
+	/// Get the number of received characters with a wrong software parity bit, and reset the count to zero.
+	///
+	/// This always returns zero if software parity is disabled.
+	pub fn take_software_parity_errors(&self) -> u64 {
+		self.software_parity.as_ref().map_or(0, |codec| codec.take_errors())
+	}
+
 	/// Set the read timeout for the serial port.
 	///
 	/// The timeout set by this function is an upper bound on individual calls to [`read()`][Self::read].
@@ -329,6 +1204,31 @@ impl SerialPort {
 		}
 	}
 
+	/// Get the number of bytes in the kernel input buffer, that can be read without waiting.
+	///
+	/// This can be used to size a read buffer, or to skip a read when no data is available.
+	/// Note that more data may arrive at any time, so the input buffer may contain more data by the time it is read.
+	///
+	/// On Unix, this uses the `FIONREAD` ioctl.
+	/// On Windows, this uses `ClearCommError()`, which also clears any pending communication errors of the device.
+	pub fn bytes_to_read(&self) -> std::io::Result<usize> {
+		self.inner.bytes_to_read()
+	}
+
+	/// Get the number of bytes in the kernel output buffer, that have not been transmitted yet.
+	///
+	/// Unlike [`Self::flush()`], this does not block, so it can be used to pace writes.
+	/// Note that a byte that left the output buffer may still be in the transmit shift register of the UART.
+	/// To know when the last bit was transmitted, for example to switch the direction of an RS-485 transceiver,
+	/// use [`Self::wait_tx_empty()`].
+	///
+	/// On Unix, this uses the `TIOCOUTQ` ioctl, which is not supported on all platforms.
+	/// On unsupported platforms, an error of the type [`std::io::ErrorKind::Unsupported`] is returned.
+	/// On Windows, this uses `ClearCommError()`, which also clears any pending communication errors of the device.
+	pub fn bytes_to_write(&self) -> std::io::Result<usize> {
+		self.inner.bytes_to_write()
+	}
+
 	/// Discard the kernel input and output buffers for the serial port.
 	///
 	/// When you write to a serial port, the data may be put in a buffer by the OS to be transmitted by the actual device later.
@@ -357,6 +1257,111 @@ impl SerialPort {
 		self.inner.discard_buffers(false, true)
 	}
 
+	/// Transmit a break condition for the given duration.
+	///
+	/// During a break, the transmit line is held in the spacing state (logical zero) for longer than a character frame.
+	/// Some devices use a break as an attention or reset signal.
+	///
+	/// This function blocks for the duration of the break.
+	/// Data that is still in the kernel output buffer may be held back until the break is over,
+	/// so call [`Self::flush()`] first if the break must follow previously written data.
+	///
+	/// To hold the break condition for as long as you want, use [`Self::set_break()`].
+	pub fn send_break(&self, duration: Duration) -> std::io::Result<()> {
+		self.set_break(true)?;
+		std::thread::sleep(duration);
+		self.set_break(false)
+	}
+
+	/// Start or stop transmitting a break condition.
+	///
+	/// While the break condition is set, the transmit line is held in the spacing state (logical zero),
+	/// and no data is transmitted.
+	/// The break condition stays set until it is cleared again with `set_break(false)`.
+	///
+	/// On Unix, this uses the `TIOCSBRK` and `TIOCCBRK` ioctls.
+	/// On Windows, this uses `SetCommBreak()` and `ClearCommBreak()`.
+	pub fn set_break(&self, enable: bool) -> std::io::Result<()> {
+		self.inner.set_break(enable)
+	}
+
+	/// Check if transmission is currently held back by flow control.
+	///
+	/// Returns `Ok(None)` if no reason to hold back transmission was detected.
+	///
+	/// On Windows, the driver reports exactly why transmission is held back.
+	/// On other platforms, only a deasserted CTS line with RTS/CTS flow control enabled can be detected.
+	/// A received XOFF character can not be detected on those platforms.
+	///
+	/// This is mainly useful to diagnose writes that time out, for example because of mis-wired RTS/CTS lines.
+	pub fn get_flow_control_stall(&self) -> std::io::Result<Option<FlowControlStall>> {
+		self.inner.get_flow_control_stall()
+	}
+
+	/// Check if transmission is currently suspended because the peer sent an XOFF character.
+	///
+	/// This is only supported on Windows.
+	/// On other platforms, an error of the type [`std::io::ErrorKind::Unsupported`] is returned.
+	pub fn is_output_suspended_by_xoff(&self) -> std::io::Result<bool> {
+		self.inner.is_output_suspended_by_xoff()
+	}
+
+	/// Send an XON character to the peer, to ask it to resume transmission.
+	///
+	/// The character is sent as soon as possible, ahead of any data waiting in the output buffer.
+	/// This can be used to recover a peer that is stuck after a lost XON character.
+	pub fn send_xon(&self) -> std::io::Result<()> {
+		self.inner.send_xon()
+	}
+
+	/// Send an XOFF character to the peer, to ask it to suspend transmission.
+	///
+	/// The character is sent as soon as possible, ahead of any data waiting in the output buffer.
+	pub fn send_xoff(&self) -> std::io::Result<()> {
+		self.inner.send_xoff()
+	}
+
+	/// Resume transmission if it was suspended by a received XOFF character.
+	///
+	/// This makes the OS act as if an XON character was received from the peer.
+	/// It can be used to recover from a lost XON character without discarding the output buffer.
+	pub fn resume_output(&self) -> std::io::Result<()> {
+		self.inner.resume_output()
+	}
+
+	/// Ask the peer to stop sending data, using the configured flow control mechanism.
+	///
+	/// With XON/XOFF flow control, this sends an XOFF character.
+	/// With RTS/CTS flow control, this deasserts the RTS line.
+	/// If flow control is disabled, an error of the type [`std::io::ErrorKind::Unsupported`] is returned.
+	///
+	/// This allows you to apply backpressure while processing a backlog, without losing data.
+	/// Note that the peer may still send a few bytes before it reacts.
+	/// Use [`Self::resume_input()`] to let the peer continue sending.
+	///
+	/// On some platforms the OS manages the RTS line itself when RTS/CTS flow control is enabled.
+	/// In that case, this function may fail, or the OS may assert the RTS line again on its own.
+	pub fn pause_input(&self) -> std::io::Result<()> {
+		match self.get_configuration()?.get_flow_control()? {
+			FlowControl::XonXoff => self.send_xoff(),
+			FlowControl::RtsCts => self.set_rts(false),
+			FlowControl::None => Err(flow_control_disabled()),
+		}
+	}
+
+	/// Allow the peer to continue sending data after a call to [`Self::pause_input()`].
+	///
+	/// With XON/XOFF flow control, this sends an XON character.
+	/// With RTS/CTS flow control, this asserts the RTS line.
+	/// If flow control is disabled, an error of the type [`std::io::ErrorKind::Unsupported`] is returned.
+	pub fn resume_input(&self) -> std::io::Result<()> {
+		match self.get_configuration()?.get_flow_control()? {
+			FlowControl::XonXoff => self.send_xon(),
+			FlowControl::RtsCts => self.set_rts(true),
+			FlowControl::None => Err(flow_control_disabled()),
+		}
+	}
+
 	/// Set the state of the Ready To Send line.
 	///
 	/// If hardware flow control is enabled on the serial port, it is platform specific what will happen.
@@ -405,6 +1410,26 @@ impl SerialPort {
 		self.inner.read_cd()
 	}
 
+	/// Set the state of the Ready To Send and Data Terminal Ready lines together.
+	///
+	/// Lines given as `None` are left unchanged.
+	///
+	/// On Unix platforms, both lines are changed with a single system call,
+	/// so the peer never sees an intermediate state during reset sequences.
+	/// On Windows, the lines are changed one after the other, RTS first.
+	///
+	/// See [`Self::set_rts()`] and [`Self::set_dtr()`] for the interaction with hardware flow control.
+	pub fn set_modem_lines(&self, rts: Option<bool>, dtr: Option<bool>) -> std::io::Result<()> {
+		self.inner.set_modem_lines(rts, dtr)
+	}
+
+	/// Read the state of all modem control lines with a single system call.
+	///
+	/// On Windows, the state of the output lines (RTS and DTR) is not available.
+	pub fn read_modem_lines(&self) -> std::io::Result<ModemLines> {
+		self.inner.read_modem_lines()
+	}
+
 	/// Get the RS-4xx mode of the serial port transceiver.
 	///
 	/// This is currently only supported on Linux.
@@ -449,6 +1474,102 @@ impl SerialPort {
 			panic!("unsupported platform");
 		}
 	}
+
+	/// Apply software parity and the byte transform to data to be transmitted.
+	///
+	/// Returns `None` if the data does not need to be changed.
+	fn encode(&self, data: &[u8]) -> Option<Vec<u8>> {
+		if self.software_parity.is_none() && !self.transform.is_set() {
+			return None;
+		}
+		let mut data = data.to_vec();
+		if let Some(codec) = &self.software_parity {
+			codec.encode(&mut data);
+		}
+		self.transform.with(|transform| transform.transmit_in_place(&mut data));
+		Some(data)
+	}
+
+	/// Undo the byte transform and check software parity of received data in place.
+	fn decode(&self, data: &mut [u8]) {
+		self.transform.with(|transform| transform.receive_in_place(data));
+		if let Some(codec) = &self.software_parity {
+			codec.decode(data);
+		}
+	}
+
+	/// Discard the data received during the discard time of the noise filter, if the discard time just ended.
+	fn discard_expired_noise(&self) -> std::io::Result<()> {
+		if self.noise_gate.take_expired() {
+			self.discard_input_buffer()?;
+		}
+		Ok(())
+	}
+
+	/// Check if invalid frames should be discarded silently because the noise filter waits for a valid frame.
+	pub(crate) fn is_noise_filter_awaiting_frame(&self) -> bool {
+		self.noise_gate.is_awaiting_frame()
+	}
+
+	/// Record that a valid frame was decoded, for the noise filter.
+	pub(crate) fn noise_filter_frame_received(&self) {
+		self.noise_gate.frame_received();
+	}
+
+	/// Wrap a platform specific serial port.
+	fn from_inner(inner: sys::SerialPort) -> Self {
+		Self {
+			inner,
+			claims: Claims::default(),
+			tx_delay: TxDelay::default(),
+			transform: TransformSlot::default(),
+			software_parity: None,
+			noise_gate: NoiseGate::default(),
+			applied_settings: None,
+			path: None,
+		}
+	}
+
+	/// Add a flow control diagnostic to a write timeout, if transmission is held back by flow control.
+	fn diagnose_write_error(&self, error: std::io::Error) -> std::io::Error {
+		if error.kind() != std::io::ErrorKind::TimedOut {
+			return error;
+		}
+		match self.get_flow_control_stall() {
+			Ok(Some(stall)) => std::io::Error::new(std::io::ErrorKind::TimedOut, stall),
+			Ok(None) | Err(_) => error,
+		}
+	}
+}
+
+impl std::fmt::Debug for SerialPort {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let mut debug = f.debug_struct("SerialPort");
+		#[cfg(unix)]
+		debug.field("fd", &std::os::unix::io::AsRawFd::as_raw_fd(&self.inner.file));
+		#[cfg(windows)]
+		debug.field("handle", &std::os::windows::io::AsRawHandle::as_raw_handle(&self.inner.file));
+		if let Some(path) = self.path() {
+			debug.field("path", &path);
+		}
+		// The settings are read from the device, so only show them if that works.
+		if let Ok(settings) = self.inner.get_configuration() {
+			let settings = Settings { inner: settings };
+			if let Ok(baud_rate) = settings.get_baud_rate() {
+				debug.field("baud_rate", &baud_rate);
+			}
+			if let Ok(char_size) = settings.get_char_size() {
+				debug.field("char_size", &char_size);
+			}
+			if let Ok(parity) = settings.get_parity() {
+				debug.field("parity", &parity);
+			}
+			if let Ok(stop_bits) = settings.get_stop_bits() {
+				debug.field("stop_bits", &stop_bits);
+			}
+		}
+		debug.finish_non_exhaustive()
+	}
 }
 
 impl std::io::Read for SerialPort {
@@ -509,9 +1630,7 @@ impl From<SerialPort> for std::os::unix::io::OwnedFd {
 #[cfg(unix)]
 impl From<std::os::unix::io::OwnedFd> for SerialPort {
 	fn from(value: std::os::unix::io::OwnedFd) -> Self {
-		Self {
-			inner: sys::SerialPort::from_file(value.into()),
-		}
+		Self::from_inner(sys::SerialPort::from_file(value.into()))
 	}
 }
 
@@ -540,9 +1659,7 @@ impl std::os::unix::io::IntoRawFd for SerialPort {
 impl std::os::unix::io::FromRawFd for SerialPort {
 	unsafe fn from_raw_fd(fd: std::os::unix::io::RawFd) -> Self {
 		use std::fs::File;
-		Self {
-			inner: sys::SerialPort::from_file(File::from_raw_fd(fd)),
-		}
+		Self::from_inner(sys::SerialPort::from_file(File::from_raw_fd(fd)))
 	}
 }
 
@@ -559,9 +1676,7 @@ impl From<SerialPort> for std::os::windows::io::OwnedHandle {
 #[cfg(windows)]
 impl From<std::os::windows::io::OwnedHandle> for SerialPort {
 	fn from(value: std::os::windows::io::OwnedHandle) -> Self {
-		Self {
-			inner: sys::SerialPort::from_file(value.into()),
-		}
+		Self::from_inner(sys::SerialPort::from_file(value.into()))
 	}
 }
 
@@ -593,8 +1708,17 @@ impl std::os::windows::io::IntoRawHandle for SerialPort {
 impl std::os::windows::io::FromRawHandle for SerialPort {
 	unsafe fn from_raw_handle(handle: std::os::windows::io::RawHandle) -> Self {
 		use std::fs::File;
-		Self {
-			inner: sys::SerialPort::from_file(File::from_raw_handle(handle)),
-		}
+		Self::from_inner(sys::SerialPort::from_file(File::from_raw_handle(handle)))
 	}
 }
+
+/// The interval for polling the transmitter state in [`SerialPort::wait_tx_empty()`].
+const TX_EMPTY_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Create the error returned when pausing or resuming input without flow control.
+fn flow_control_disabled() -> std::io::Error {
+	std::io::Error::new(
+		std::io::ErrorKind::Unsupported,
+		"can not pause or resume input: flow control is disabled",
+	)
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 /// The settings of a serial port.
 #[derive(Clone)]
 pub struct Settings {
@@ -375,6 +377,11 @@ impl std::str::FromStr for FlowControl {
 }
 
 impl Settings {
+	/// Check if two settings configure the serial port in exactly the same way.
+	pub(crate) fn matches(&self, other: &Settings) -> bool {
+		self.inner.matches(&other.inner)
+	}
+
 	/// Disable all OS level input and output processing.
 	///
 	/// All input and output processing will be disabled,
@@ -442,6 +449,27 @@ impl Settings {
 		self.inner.get_flow_control()
 	}
 
+	/// Get the time it takes to transmit a single character with these settings.
+	///
+	/// This includes the start bit, the data bits, the parity bit (if any) and the stop bits.
+	///
+	/// Many protocols specify timing requirements in character times,
+	/// such as the minimum silence between frames.
+	pub fn get_char_time(&self) -> std::io::Result<Duration> {
+		let baud_rate = self.get_baud_rate()?;
+		if baud_rate == 0 {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "baud rate is zero"));
+		}
+		let parity_bits = match self.get_parity()? {
+			Parity::None => 0,
+			Parity::Odd | Parity::Even => 1,
+		};
+		let data_bits = u64::from(self.get_char_size()?.as_u8());
+		let stop_bits = u64::from(self.get_stop_bits()?.as_u8());
+		let bits = 1 + data_bits + parity_bits + stop_bits;
+		Ok(Duration::from_nanos(bits * 1_000_000_000 / u64::from(baud_rate)))
+	}
+
 	/// Get a reference to the raw `termios` struct.
 	///
 	/// On Linux and Android this is actually a `termios2` struct.
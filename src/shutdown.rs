@@ -0,0 +1,81 @@
+//! Stopping background threads with a timeout.
+//!
+//! Many components of this crate run one or more background threads,
+//! like the [`BufferedReader`][crate::buffered::BufferedReader], the [`Router`][crate::router::Router]
+//! and the [`AutoReconnectPort`][crate::reconnect::AutoReconnectPort].
+//! Dropping such a component stops the threads and waits for them to finish, for as long as it takes.
+//!
+//! All of these components also implement the [`Shutdown`] trait.
+//! [`Shutdown::shutdown()`] stops the threads in the same way,
+//! but gives up waiting when the timeout expires.
+//! A thread that is still running at that point is detached: it exits on its own as soon as it notices the shutdown.
+//!
+//! # Example
+//! ```no_run
+//! # use serial2::SerialPort;
+//! # use serial2::buffered::BufferedReader;
+//! # use serial2::shutdown::Shutdown;
+//! # use std::time::Duration;
+//! # fn foo() -> std::io::Result<()> {
+//! let reader = BufferedReader::new(SerialPort::open("/dev/ttyUSB0", 115200)?)?;
+//! // ...
+//! if let Err(e) = reader.shutdown(Duration::from_secs(1)) {
+//!     eprintln!("The background thread did not stop in time: {e}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// The interval for checking if a background thread finished.
+const JOIN_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A component with background threads that can be stopped with a timeout.
+pub trait Shutdown: Sized {
+	/// Stop the background threads and wait for them to finish, for at most the given timeout.
+	///
+	/// If a thread is still running when the timeout expires,
+	/// it is detached and an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	/// The detached thread exits on its own as soon as it notices the shutdown,
+	/// for example when a blocking read on the serial port returns.
+	fn shutdown(self, timeout: Duration) -> std::io::Result<()>;
+}
+
+/// Wait for background threads to finish.
+///
+/// Without a deadline, this waits as long as it takes.
+/// Threads that are still running when the deadline expires are detached.
+pub(crate) fn join_threads(
+	threads: impl IntoIterator<Item = JoinHandle<()>>,
+	deadline: Option<Instant>,
+) -> std::io::Result<()> {
+	let mut timed_out = false;
+	for thread in threads {
+		let Some(deadline) = deadline else {
+			let _ = thread.join();
+			continue;
+		};
+		while !thread.is_finished() {
+			let now = Instant::now();
+			if now >= deadline {
+				break;
+			}
+			std::thread::sleep(JOIN_POLL_INTERVAL.min(deadline - now));
+		}
+		if thread.is_finished() {
+			let _ = thread.join();
+		} else {
+			timed_out = true;
+		}
+	}
+	if timed_out {
+		Err(std::io::Error::new(
+			std::io::ErrorKind::TimedOut,
+			"background thread did not stop before the timeout expired",
+		))
+	} else {
+		Ok(())
+	}
+}
@@ -0,0 +1,376 @@
+//! Simulated devices for integration tests.
+//!
+//! A [`SimulatedDevice`] runs a [`DeviceScript`] on one side of a serial port,
+//! typically one end of `SerialPort::pair()` or the slave side of `SerialPort::open_pty()`.
+//! The code under test talks to the other side as if it was a real peripheral, like a GPS receiver, a modem or a PLC.
+//!
+//! A script is a state machine described by a list of [`Rule`]s.
+//! Each rule waits for a byte pattern, optionally only in a specific state,
+//! and answers with a response after an optional delay.
+//! A rule can also move the device to a different state.
+//!
+//! # Example
+//! ```no_run
+//! # use serial2::SerialPort;
+//! # use serial2::sim::{DeviceScript, Rule, SimulatedDevice};
+//! # use std::time::{Duration, Instant};
+//! # fn foo() -> std::io::Result<()> {
+//! let script = DeviceScript::new("command")
+//!     .rule(Rule::new("AT\r", "OK\r\n"))
+//!     .rule(Rule::new("ATD", "CONNECT\r\n").in_state("command").after(Duration::from_millis(50)).goto("data"))
+//!     .rule(Rule::new("+++", "OK\r\n").in_state("data").goto("command"));
+//!
+//! let (port, device_port) = SerialPort::pair()?;
+//! let device = SimulatedDevice::spawn(device_port, script)?;
+//! // Use `port` like a real modem.
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::shutdown::Shutdown;
+use crate::SerialPort;
+
+/// The interval at which the background thread checks if the device is being dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The description of a simulated device.
+///
+/// See the [module documentation][self] for more information.
+#[derive(Debug, Clone)]
+pub struct DeviceScript {
+	/// The state the device starts in.
+	initial_state: String,
+
+	/// The rules, in order of priority.
+	rules: Vec<Rule>,
+}
+
+/// A rule of a [`DeviceScript`].
+///
+/// When the device receives the pattern of a rule, it waits for the delay of the rule,
+/// writes the response and moves to the next state of the rule, if any.
+#[derive(Debug, Clone)]
+pub struct Rule {
+	/// The state in which the rule applies, or `None` if it applies in all states.
+	state: Option<String>,
+
+	/// The pattern to wait for.
+	pattern: Vec<u8>,
+
+	/// The response to write.
+	response: Vec<u8>,
+
+	/// The delay before writing the response.
+	delay: Duration,
+
+	/// The state to move to after writing the response.
+	next_state: Option<String>,
+}
+
+impl DeviceScript {
+	/// Create a new script without rules.
+	pub fn new(initial_state: impl Into<String>) -> Self {
+		Self {
+			initial_state: initial_state.into(),
+			rules: Vec::new(),
+		}
+	}
+
+	/// Add a rule to the script.
+	///
+	/// If multiple rules match the received data, the rule that matches the earliest data wins.
+	/// If multiple rules match at the same position, the rule that was added first wins.
+	pub fn rule(mut self, rule: Rule) -> Self {
+		self.rules.push(rule);
+		self
+	}
+
+	/// Get the state the device starts in.
+	pub fn initial_state(&self) -> &str {
+		&self.initial_state
+	}
+
+	/// Get the rules of the script.
+	pub fn rules(&self) -> &[Rule] {
+		&self.rules
+	}
+
+	/// Find the rule that matches the received data in the given state.
+	///
+	/// Returns the index of the rule and the end of the matched data.
+	fn find_match(&self, state: &str, data: &[u8]) -> Option<(usize, usize)> {
+		let mut best: Option<(usize, usize, usize)> = None;
+		for (index, rule) in self.rules.iter().enumerate() {
+			if rule.state.as_deref().is_some_and(|rule_state| rule_state != state) {
+				continue;
+			}
+			let Some(start) = find(data, &rule.pattern) else {
+				continue;
+			};
+			if best.is_none_or(|(best_start, _, _)| start < best_start) {
+				best = Some((start, index, start + rule.pattern.len()));
+			}
+		}
+		best.map(|(_, index, end)| (index, end))
+	}
+
+	/// Get the number of bytes to keep from unmatched data.
+	///
+	/// This allows a pattern that is split over multiple reads to match.
+	fn keep_unmatched(&self) -> usize {
+		self.rules.iter().map(|rule| rule.pattern.len().saturating_sub(1)).max().unwrap_or(0)
+	}
+}
+
+impl Rule {
+	/// Create a rule that answers a pattern with a response in all states.
+	///
+	/// An empty pattern matches as soon as the rule applies, without waiting for data.
+	/// Combined with a delay and a state transition, this can be used to send unsolicited data,
+	/// like the periodic messages of a GPS receiver.
+	pub fn new(pattern: impl Into<Vec<u8>>, response: impl Into<Vec<u8>>) -> Self {
+		Self {
+			state: None,
+			pattern: pattern.into(),
+			response: response.into(),
+			delay: Duration::ZERO,
+			next_state: None,
+		}
+	}
+
+	/// Only apply the rule in the given state.
+	pub fn in_state(mut self, state: impl Into<String>) -> Self {
+		self.state = Some(state.into());
+		self
+	}
+
+	/// Wait for the given delay before writing the response.
+	pub fn after(mut self, delay: Duration) -> Self {
+		self.delay = delay;
+		self
+	}
+
+	/// Move to the given state after writing the response.
+	pub fn goto(mut self, state: impl Into<String>) -> Self {
+		self.next_state = Some(state.into());
+		self
+	}
+
+	/// Get the state in which the rule applies, or `None` if it applies in all states.
+	pub fn state(&self) -> Option<&str> {
+		self.state.as_deref()
+	}
+
+	/// Get the pattern of the rule.
+	pub fn pattern(&self) -> &[u8] {
+		&self.pattern
+	}
+
+	/// Get the response of the rule.
+	pub fn response(&self) -> &[u8] {
+		&self.response
+	}
+
+	/// Get the delay before writing the response.
+	pub fn delay(&self) -> Duration {
+		self.delay
+	}
+
+	/// Get the state to move to after writing the response, if any.
+	pub fn next_state(&self) -> Option<&str> {
+		self.next_state.as_deref()
+	}
+}
+
+/// A simulated device running a [`DeviceScript`] on a serial port.
+///
+/// See the [module documentation][self] for more information.
+///
+/// Dropping the device stops the background thread.
+pub struct SimulatedDevice {
+	/// The state shared with the background thread.
+	shared: Arc<Shared>,
+
+	/// The background thread.
+	thread: Option<JoinHandle<()>>,
+}
+
+impl SimulatedDevice {
+	/// Run a script on a serial port.
+	///
+	/// This spawns a background thread that reads from the serial port and writes the responses.
+	/// The read timeout of the serial port is changed for use by the background thread.
+	pub fn spawn(port: SerialPort, script: DeviceScript) -> std::io::Result<Self> {
+		let mut port = port;
+		port.set_read_timeout(POLL_INTERVAL)?;
+		let shared = Arc::new(Shared {
+			state: Mutex::new(State {
+				current: script.initial_state.clone(),
+				matched_rules: 0,
+				error: None,
+				closed: false,
+			}),
+		});
+		let thread = std::thread::Builder::new()
+			.name("serial2-simulated-device".into())
+			.spawn({
+				let shared = shared.clone();
+				move || run(&shared, port, &script)
+			})?;
+		Ok(Self {
+			shared,
+			thread: Some(thread),
+		})
+	}
+
+	/// Get the current state of the device.
+	pub fn state(&self) -> String {
+		self.shared.lock().current.clone()
+	}
+
+	/// Move the device to a different state.
+	///
+	/// This can be used to simulate events that are not triggered by received data, like a lost connection.
+	pub fn set_state(&self, state: impl Into<String>) {
+		self.shared.lock().current = state.into();
+	}
+
+	/// Get the number of times a rule matched.
+	pub fn matched_rules(&self) -> u64 {
+		self.shared.lock().matched_rules
+	}
+
+	/// Take the error that stopped the background thread, if any.
+	pub fn take_error(&self) -> Option<std::io::Error> {
+		self.shared.lock().error.take()
+	}
+}
+
+impl SimulatedDevice {
+	/// Stop the background thread and wait for it to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		crate::shutdown::join_threads(self.thread.take(), deadline)
+	}
+}
+
+impl Drop for SimulatedDevice {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl Shutdown for SimulatedDevice {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// State shared between the device and the background thread.
+struct Shared {
+	/// The mutable state.
+	state: Mutex<State>,
+}
+
+/// The mutable state of a simulated device.
+struct State {
+	/// The current state of the script.
+	current: String,
+
+	/// The number of times a rule matched.
+	matched_rules: u64,
+
+	/// The error that stopped the background thread.
+	error: Option<std::io::Error>,
+
+	/// True if the device is being dropped.
+	closed: bool,
+}
+
+impl Shared {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Sleep for the given duration, unless the device is dropped first.
+	///
+	/// Returns `false` if the device is being dropped.
+	fn sleep(&self, duration: Duration) -> bool {
+		let deadline = Instant::now() + duration;
+		loop {
+			if self.lock().closed {
+				return false;
+			}
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return true;
+			}
+			std::thread::sleep(remaining.min(POLL_INTERVAL));
+		}
+	}
+}
+
+/// Run the script until the device is dropped or an error occurs.
+fn run(shared: &Shared, port: SerialPort, script: &DeviceScript) {
+	let mut received = Vec::new();
+	let mut buffer = [0; 1024];
+	loop {
+		let current = {
+			let state = shared.lock();
+			if state.closed {
+				return;
+			}
+			state.current.clone()
+		};
+
+		if let Some((index, end)) = script.find_match(&current, &received) {
+			received.drain(..end);
+			let rule = &script.rules[index];
+			if !shared.sleep(rule.delay) {
+				return;
+			}
+			// Update the state before writing the response,
+			// so the new state is visible as soon as the response is received.
+			{
+				let mut state = shared.lock();
+				state.matched_rules += 1;
+				if let Some(next_state) = &rule.next_state {
+					state.current = next_state.clone();
+				}
+			}
+			if let Err(e) = port.write_all(&rule.response) {
+				shared.lock().error = Some(e);
+				return;
+			}
+			continue;
+		}
+
+		let keep = script.keep_unmatched();
+		if received.len() > keep {
+			received.drain(..received.len() - keep);
+		}
+
+		match port.read(&mut buffer) {
+			Ok(read) => received.extend_from_slice(&buffer[..read]),
+			Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => {
+				shared.lock().error = Some(e);
+				return;
+			},
+		}
+	}
+}
+
+/// Find the first occurrence of a pattern in the data.
+fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+	if pattern.is_empty() {
+		return Some(0);
+	}
+	data.windows(pattern.len()).position(|window| window == pattern)
+}
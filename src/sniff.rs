@@ -0,0 +1,134 @@
+//! Passive sniffing of a serial port that is used by other devices.
+//!
+//! A [`Sniffer`] opens a serial port read-only and never changes the configuration, the modem control lines or the buffers of the port.
+//! It only provides operations that observe the port, so the guarantee is enforced by the type system:
+//! there are no functions to write data, to change settings, to set modem lines or to discard buffers.
+//!
+//! Note that reading consumes data from the receive buffer of the port.
+//! If another program reads from the same port, the sniffer and the other program compete for the received data.
+//!
+//! # Platform fidelity
+//! The platforms themselves may still change the state of the port when it is opened or closed:
+//!
+//! * On Unix platforms, the port is opened with `O_RDONLY | O_NOCTTY`, and the termios settings are never written.
+//!   However, if the sniffer is the first process to open the port, the kernel may raise DTR and RTS.
+//!   If it is the last process to close the port and `HUPCL` is set, the kernel lowers DTR and RTS again.
+//!   To avoid this, open the port with the program that uses it before starting the sniffer, and stop the sniffer first.
+//!   To sniff a bus without taking data from another program, connect a separate serial port to the bus.
+//! * On Windows, serial ports can not be opened by more than one process at a time, so opening a port in use fails.
+//!   Opening a port may toggle DTR and RTS depending on the driver.
+//!   The read timeouts of the port are changed, but they only apply to the handle of the sniffer.
+
+use std::io::IoSliceMut;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{sys, ModemLines, Settings};
+
+/// A read-only serial port that never affects the line state.
+///
+/// See the [module documentation][self] for more information.
+pub struct Sniffer {
+	inner: sys::SerialPort,
+}
+
+impl Sniffer {
+	/// Open a serial port for passive sniffing.
+	///
+	/// The configuration of the port is not changed.
+	/// Use [`Self::get_configuration()`] to check the baud rate and framing used by the other devices.
+	pub fn open(name: impl AsRef<Path>) -> std::io::Result<Self> {
+		Ok(Self {
+			inner: sys::SerialPort::open_read_only(name.as_ref())?,
+		})
+	}
+
+	/// Get the current configuration of the serial port.
+	pub fn get_configuration(&self) -> std::io::Result<Settings> {
+		Ok(Settings {
+			inner: self.inner.get_configuration()?,
+		})
+	}
+
+	/// Read bytes from the serial port.
+	///
+	/// This is identical to [`std::io::Read::read()`], except that this function takes a const reference `&self`.
+	pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		self.inner.read(buf)
+	}
+
+	/// Read bytes from the serial port into a slice of buffers.
+	///
+	/// This is identical to [`std::io::Read::read_vectored()`], except that this function takes a const reference `&self`.
+	pub fn read_vectored(&self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		self.inner.read_vectored(buf)
+	}
+
+	/// Set the read timeout for the sniffer.
+	///
+	/// The timeout only applies to reads from this sniffer.
+	pub fn set_read_timeout(&mut self, timeout: Duration) -> std::io::Result<()> {
+		self.inner.set_read_timeout(timeout)
+	}
+
+	/// Get the read timeout for the sniffer.
+	pub fn get_read_timeout(&self) -> std::io::Result<Duration> {
+		self.inner.get_read_timeout()
+	}
+
+	/// Read the state of all modem control lines.
+	///
+	/// This only observes the lines, it does not change them.
+	/// On Windows, the state of the output lines (RTS and DTR) is not available.
+	pub fn read_modem_lines(&self) -> std::io::Result<ModemLines> {
+		self.inner.read_modem_lines()
+	}
+}
+
+impl std::io::Read for Sniffer {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		Sniffer::read(self, buf)
+	}
+
+	fn read_vectored(&mut self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		Sniffer::read_vectored(self, buf)
+	}
+}
+
+impl std::io::Read for &'_ Sniffer {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		Sniffer::read(self, buf)
+	}
+
+	fn read_vectored(&mut self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		Sniffer::read_vectored(self, buf)
+	}
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsFd for Sniffer {
+	fn as_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+		self.inner.file.as_fd()
+	}
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for Sniffer {
+	fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+		self.inner.file.as_raw_fd()
+	}
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsHandle for Sniffer {
+	fn as_handle(&self) -> std::os::windows::io::BorrowedHandle<'_> {
+		self.inner.file.as_handle()
+	}
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawHandle for Sniffer {
+	fn as_raw_handle(&self) -> std::os::windows::io::RawHandle {
+		self.inner.file.as_raw_handle()
+	}
+}
@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A parity bit computed and checked in software.
+///
+/// Some serial adapters mishandle parity, especially mark and space parity or odd parity at high baud rates.
+/// With software parity, the serial port is configured without parity and one extra data bit,
+/// and the library computes the parity bit of transmitted characters and checks the parity bit of received characters.
+///
+/// Because the parity bit becomes a data bit on the wire, this only works for characters of 7 bits or less.
+///
+/// Use [`SerialPort::set_software_parity()`][crate::SerialPort::set_software_parity] to enable software parity.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SoftwareParity {
+	/// The parity bit makes the number of set bits odd.
+	Odd,
+
+	/// The parity bit makes the number of set bits even.
+	Even,
+
+	/// The parity bit is always set.
+	Mark,
+
+	/// The parity bit is always cleared.
+	Space,
+}
+
+impl SoftwareParity {
+	/// Compute the parity bit for a character.
+	pub fn parity_bit(self, data: u8) -> bool {
+		match self {
+			Self::Odd => data.count_ones().is_multiple_of(2),
+			Self::Even => !data.count_ones().is_multiple_of(2),
+			Self::Mark => true,
+			Self::Space => false,
+		}
+	}
+}
+
+/// The software parity state of a serial port.
+pub(crate) struct ParityCodec {
+	/// The parity mode.
+	parity: SoftwareParity,
+
+	/// The number of data bits in a character, not including the parity bit.
+	data_bits: u8,
+
+	/// The number of received characters with a wrong parity bit since the counter was last reset.
+	errors: AtomicU64,
+}
+
+impl ParityCodec {
+	/// Create a new codec for characters with the given number of data bits.
+	pub(crate) fn new(parity: SoftwareParity, data_bits: u8) -> Self {
+		Self {
+			parity,
+			data_bits,
+			errors: AtomicU64::new(0),
+		}
+	}
+
+	/// Get the parity mode.
+	pub(crate) fn parity(&self) -> SoftwareParity {
+		self.parity
+	}
+
+	/// Get the number of data bits in a character, not including the parity bit.
+	pub(crate) fn data_bits(&self) -> u8 {
+		self.data_bits
+	}
+
+	/// Get and reset the number of parity errors.
+	pub(crate) fn take_errors(&self) -> u64 {
+		self.errors.swap(0, Ordering::Relaxed)
+	}
+
+	/// Add a parity bit to characters in place, discarding data bits that do not fit.
+	pub(crate) fn encode(&self, data: &mut [u8]) {
+		let mask = self.data_mask();
+		for byte in data {
+			let data = *byte & mask;
+			*byte = data | (u8::from(self.parity.parity_bit(data)) << self.data_bits);
+		}
+	}
+
+	/// Check and remove the parity bit of characters in place.
+	pub(crate) fn decode(&self, data: &mut [u8]) {
+		let mask = self.data_mask();
+		let mut errors = 0;
+		for byte in data {
+			let data = *byte & mask;
+			let parity_bit = (*byte >> self.data_bits) & 1 != 0;
+			if parity_bit != self.parity.parity_bit(data) {
+				errors += 1;
+			}
+			*byte = data;
+		}
+		if errors > 0 {
+			self.errors.fetch_add(errors, Ordering::Relaxed);
+		}
+	}
+
+	/// Get the bit mask for the data bits of a character.
+	fn data_mask(&self) -> u8 {
+		(1 << self.data_bits) - 1
+	}
+}
@@ -48,6 +48,9 @@ cfg_if! {
 	}
 }
 
+mod terminal;
+pub use terminal::RawMode;
+
 cfg_if! {
 	if #[cfg(all(
 		any(target_os = "android", target_os = "linux"),
@@ -106,10 +109,18 @@ cfg_if! {
 
 impl SerialPort {
 	pub fn open(path: &Path) -> std::io::Result<Self> {
+		Self::open_with_access(path, true)
+	}
+
+	pub fn open_read_only(path: &Path) -> std::io::Result<Self> {
+		Self::open_with_access(path, false)
+	}
+
+	fn open_with_access(path: &Path, write: bool) -> std::io::Result<Self> {
 		use std::os::unix::fs::OpenOptionsExt;
 		let file = std::fs::OpenOptions::new()
 			.read(true)
-			.write(true)
+			.write(write)
 			.create(false)
 			.custom_flags(libc::O_NONBLOCK | libc::O_NOCTTY)
 			.open(path)?;
@@ -124,6 +135,7 @@ impl SerialPort {
 			let pty_a = check(libc::posix_openpt(libc::O_RDWR | libc::O_CLOEXEC | libc::O_NOCTTY))?;
 			let pty_a = std::fs::File::from_raw_fd(pty_a);
 			let pty_a = Self::from_file(pty_a);
+			set_nonblocking(&pty_a.file)?;
 			let pty_b_name = pts_name(&pty_a)?;
 			check(libc::unlockpt(pty_a.file.as_raw_fd()))?;
 			check(libc::grantpt(pty_a.file.as_raw_fd()))?;
@@ -132,6 +144,20 @@ impl SerialPort {
 		}
 	}
 
+	#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+	pub fn open_pty() -> std::io::Result<(Self, std::path::PathBuf)> {
+		use std::os::unix::io::FromRawFd;
+		unsafe {
+			let master = check(libc::posix_openpt(libc::O_RDWR | libc::O_CLOEXEC | libc::O_NOCTTY))?;
+			let master = Self::from_file(std::fs::File::from_raw_fd(master));
+			set_nonblocking(&master.file)?;
+			let slave_name = pts_name(&master)?;
+			check(libc::grantpt(master.file.as_raw_fd()))?;
+			check(libc::unlockpt(master.file.as_raw_fd()))?;
+			Ok((master, slave_name))
+		}
+	}
+
 	pub fn from_file(file: std::fs::File) -> Self {
 		Self {
 			file,
@@ -296,6 +322,17 @@ impl SerialPort {
 		}
 	}
 
+	pub fn set_break(&self, enable: bool) -> std::io::Result<()> {
+		unsafe {
+			if enable {
+				check(libc::ioctl(self.file.as_raw_fd(), libc::TIOCSBRK as _))?;
+			} else {
+				check(libc::ioctl(self.file.as_raw_fd(), libc::TIOCCBRK as _))?;
+			}
+			Ok(())
+		}
+	}
+
 	pub fn set_rts(&self, state: bool) -> std::io::Result<()> {
 		set_pin(&self.file, libc::TIOCM_RTS, state)
 	}
@@ -319,6 +356,315 @@ impl SerialPort {
 	pub fn read_cd(&self) -> std::io::Result<bool> {
 		read_pin(&self.file, libc::TIOCM_CD)
 	}
+
+	pub fn set_modem_lines(&self, rts: Option<bool>, dtr: Option<bool>) -> std::io::Result<()> {
+		unsafe {
+			let mut bits: c_int = 0;
+			check(libc::ioctl(self.file.as_raw_fd(), libc::TIOCMGET as _, &mut bits))?;
+			let mut new_bits = bits;
+			for (pin, state) in [(libc::TIOCM_RTS, rts), (libc::TIOCM_DTR, dtr)] {
+				match state {
+					Some(true) => new_bits |= pin,
+					Some(false) => new_bits &= !pin,
+					None => (),
+				}
+			}
+			if new_bits != bits {
+				check(libc::ioctl(self.file.as_raw_fd(), libc::TIOCMSET as _, &new_bits))?;
+			}
+			Ok(())
+		}
+	}
+
+	pub fn read_modem_lines(&self) -> std::io::Result<crate::ModemLines> {
+		unsafe {
+			let mut bits: c_int = 0;
+			check(libc::ioctl(self.file.as_raw_fd(), libc::TIOCMGET as _, &mut bits))?;
+			Ok(crate::ModemLines {
+				rts: Some(bits & libc::TIOCM_RTS != 0),
+				dtr: Some(bits & libc::TIOCM_DTR != 0),
+				cts: bits & libc::TIOCM_CTS != 0,
+				dsr: bits & libc::TIOCM_DSR != 0,
+				ri: bits & libc::TIOCM_RI != 0,
+				cd: bits & libc::TIOCM_CD != 0,
+			})
+		}
+	}
+
+	pub fn get_flow_control_stall(&self) -> std::io::Result<Option<crate::FlowControlStall>> {
+		// There is no portable way to check if output is suspended by a received XOFF character,
+		// so we can only check the CTS line.
+		let settings = self.get_configuration()?;
+		if settings.termios.c_cflag & libc::CRTSCTS != 0 && !self.read_cts()? {
+			Ok(Some(crate::FlowControlStall::CtsDeasserted))
+		} else {
+			Ok(None)
+		}
+	}
+
+	pub fn bytes_to_read(&self) -> std::io::Result<usize> {
+		unsafe {
+			let mut queued: c_int = 0;
+			check(libc::ioctl(self.file.as_raw_fd(), libc::FIONREAD as _, &mut queued))?;
+			Ok(queued.max(0) as usize)
+		}
+	}
+
+	pub fn bytes_to_write(&self) -> std::io::Result<usize> {
+		cfg_if! {
+			if #[cfg(any(
+				target_os = "android",
+				target_os = "linux",
+				target_os = "dragonfly",
+				target_os = "freebsd",
+				target_os = "illumos",
+				target_os = "solaris",
+			))] {
+				output_queue_len(&self.file)
+			} else {
+				Err(std::io::Error::new(
+					std::io::ErrorKind::Unsupported,
+					"getting the number of bytes in the output buffer is not supported on this platform",
+				))
+			}
+		}
+	}
+
+	pub fn is_tx_empty(&self) -> std::io::Result<bool> {
+		cfg_if! {
+			if #[cfg(target_os = "linux")] {
+				// The line status register tells us if the shift register is empty too,
+				// but not all drivers support it, so fall back to the output queue.
+				const TIOCSER_TEMT: c_int = 1;
+				unsafe {
+					let mut status: c_int = 0;
+					if libc::ioctl(self.file.as_raw_fd(), libc::TIOCSERGETLSR as _, &mut status) == 0 {
+						return Ok(status & TIOCSER_TEMT != 0);
+					}
+				}
+				is_output_queue_empty(&self.file)
+			} else if #[cfg(any(
+				target_os = "android",
+				target_os = "dragonfly",
+				target_os = "freebsd",
+				target_os = "illumos",
+				target_os = "solaris",
+			))] {
+				is_output_queue_empty(&self.file)
+			} else {
+				Err(std::io::Error::new(
+					std::io::ErrorKind::Unsupported,
+					"checking if the transmitter is empty is not supported on this platform",
+				))
+			}
+		}
+	}
+
+	pub fn is_output_suspended_by_xoff(&self) -> std::io::Result<bool> {
+		Err(std::io::Error::new(
+			std::io::ErrorKind::Unsupported,
+			"checking for a received XOFF character is not supported on this platform",
+		))
+	}
+
+	pub fn send_xon(&self) -> std::io::Result<()> {
+		unsafe {
+			check(libc::tcflow(self.file.as_raw_fd(), libc::TCION))?;
+			Ok(())
+		}
+	}
+
+	pub fn send_xoff(&self) -> std::io::Result<()> {
+		unsafe {
+			check(libc::tcflow(self.file.as_raw_fd(), libc::TCIOFF))?;
+			Ok(())
+		}
+	}
+
+	pub fn resume_output(&self) -> std::io::Result<()> {
+		unsafe {
+			check(libc::tcflow(self.file.as_raw_fd(), libc::TCOON))?;
+			Ok(())
+		}
+	}
+
+	pub fn set_exclusive(&self, exclusive: bool) -> std::io::Result<()> {
+		let request = if exclusive { libc::TIOCEXCL } else { libc::TIOCNXCL };
+		unsafe {
+			check(libc::ioctl(self.file.as_raw_fd(), request as _))?;
+			Ok(())
+		}
+	}
+
+	/// Resolve the path of the device from the file descriptor, if the platform supports it.
+	pub fn device_path(&self) -> Option<std::path::PathBuf> {
+		#[cfg(any(target_os = "linux", target_os = "android"))]
+		{
+			std::fs::read_link(format!("/proc/self/fd/{}", self.file.as_raw_fd())).ok()
+		}
+		#[cfg(any(target_os = "ios", target_os = "macos"))]
+		{
+			use std::os::unix::ffi::OsStrExt;
+			let mut buffer = [0u8; libc::PATH_MAX as usize];
+			unsafe { check(libc::fcntl(self.file.as_raw_fd(), libc::F_GETPATH, buffer.as_mut_ptr())).ok()? };
+			let len = buffer.iter().position(|&byte| byte == 0)?;
+			Some(std::ffi::OsStr::from_bytes(&buffer[..len]).into())
+		}
+		#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "ios", target_os = "macos")))]
+		{
+			None
+		}
+	}
+}
+
+/// Set or clear the close-on-exec flag of a file descriptor.
+///
+/// This also verifies that the file descriptor is open.
+#[cfg(any(feature = "doc", feature = "unix"))]
+pub fn set_close_on_exec(fd: std::os::unix::io::RawFd, close_on_exec: bool) -> std::io::Result<()> {
+	unsafe {
+		let flags = check(libc::fcntl(fd, libc::F_GETFD))?;
+		let flags = if close_on_exec {
+			flags | libc::FD_CLOEXEC
+		} else {
+			flags & !libc::FD_CLOEXEC
+		};
+		check(libc::fcntl(fd, libc::F_SETFD, flags))?;
+		Ok(())
+	}
+}
+
+/// Wait for any of the given file descriptors to be readable.
+///
+/// Returns for each file descriptor if it is readable, or has an error or hang-up condition pending.
+#[cfg(any(feature = "doc", feature = "unix"))]
+pub fn poll_readable(fds: &[std::os::unix::io::RawFd], timeout: std::time::Duration) -> std::io::Result<Vec<bool>> {
+	let mut poll_fds: Vec<_> = fds
+		.iter()
+		.map(|&fd| libc::pollfd {
+			fd,
+			events: libc::POLLIN,
+			revents: 0,
+		})
+		.collect();
+	let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+	unsafe {
+		check(libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as libc::nfds_t, timeout_ms))?;
+	}
+	Ok(poll_fds.iter().map(|poll_fd| poll_fd.revents != 0).collect())
+}
+
+/// Restrict the calling thread to the given CPUs.
+pub fn set_current_thread_affinity(cpus: &[usize]) -> std::io::Result<()> {
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	unsafe {
+		let mut set: libc::cpu_set_t = std::mem::zeroed();
+		libc::CPU_ZERO(&mut set);
+		for &cpu in cpus {
+			if cpu >= libc::CPU_SETSIZE as usize {
+				return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "CPU index out of range"));
+			}
+			libc::CPU_SET(cpu, &mut set);
+		}
+		check(libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set))?;
+		Ok(())
+	}
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	{
+		let _ = cpus;
+		Err(std::io::Error::new(
+			std::io::ErrorKind::Unsupported,
+			"thread CPU affinity is not supported on this platform",
+		))
+	}
+}
+
+/// Set the scheduling priority of the calling thread.
+pub fn set_current_thread_priority(priority: crate::ThreadPriority) -> std::io::Result<()> {
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	unsafe {
+		// On Linux, the nice value is a per-thread attribute when addressed by thread ID.
+		let nice = match priority {
+			crate::ThreadPriority::Lowest => 10,
+			crate::ThreadPriority::Low => 5,
+			crate::ThreadPriority::Normal => 0,
+			crate::ThreadPriority::High => -5,
+			crate::ThreadPriority::Highest => -10,
+		};
+		check(libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, nice))?;
+		Ok(())
+	}
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	{
+		let _ = priority;
+		Err(std::io::Error::new(
+			std::io::ErrorKind::Unsupported,
+			"thread priorities are not supported on this platform",
+		))
+	}
+}
+
+/// The scheduling policy and parameters of a thread, saved to restore them later.
+pub struct SavedScheduling {
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	policy: c_int,
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	param: libc::sched_param,
+}
+
+/// Switch the calling thread to the `SCHED_FIFO` realtime policy with the given priority.
+///
+/// Returns the previous scheduling policy and parameters.
+pub fn enter_realtime(priority: u8) -> std::io::Result<SavedScheduling> {
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	unsafe {
+		let min = check(libc::sched_get_priority_min(libc::SCHED_FIFO))?;
+		let max = check(libc::sched_get_priority_max(libc::SCHED_FIFO))?;
+		if !(min..=max).contains(&c_int::from(priority)) {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				format!("realtime priority must be between {min} and {max}"),
+			));
+		}
+
+		let thread = libc::pthread_self();
+		let mut saved = SavedScheduling {
+			policy: 0,
+			param: std::mem::zeroed(),
+		};
+		check_errno(libc::pthread_getschedparam(thread, &mut saved.policy, &mut saved.param))?;
+		let mut param: libc::sched_param = std::mem::zeroed();
+		param.sched_priority = priority.into();
+		match check_errno(libc::pthread_setschedparam(thread, libc::SCHED_FIFO, &param)) {
+			Ok(()) => Ok(saved),
+			Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Err(std::io::Error::new(
+				std::io::ErrorKind::PermissionDenied,
+				"realtime scheduling requires CAP_SYS_NICE or a sufficient RLIMIT_RTPRIO",
+			)),
+			Err(e) => Err(e),
+		}
+	}
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	{
+		let _ = priority;
+		Err(std::io::Error::new(
+			std::io::ErrorKind::Unsupported,
+			"realtime scheduling is not supported on this platform",
+		))
+	}
+}
+
+/// Restore the scheduling policy and parameters of the calling thread.
+pub fn restore_scheduling(saved: &SavedScheduling) -> std::io::Result<()> {
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	unsafe {
+		check_errno(libc::pthread_setschedparam(libc::pthread_self(), saved.policy, &saved.param))
+	}
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	{
+		let _ = saved;
+		Ok(())
+	}
 }
 
 /// Wait for a file to be readable or writable.
@@ -353,6 +699,48 @@ fn read_pin(file: &std::fs::File, pin: c_int) -> std::io::Result<bool> {
 	}
 }
 
+/// Check if the kernel output queue of a terminal is empty.
+#[cfg(any(
+	target_os = "android",
+	target_os = "linux",
+	target_os = "dragonfly",
+	target_os = "freebsd",
+	target_os = "illumos",
+	target_os = "solaris",
+))]
+fn is_output_queue_empty(file: &std::fs::File) -> std::io::Result<bool> {
+	Ok(output_queue_len(file)? == 0)
+}
+
+/// Get the number of bytes in the kernel output queue of a terminal.
+#[cfg(any(
+	target_os = "android",
+	target_os = "linux",
+	target_os = "dragonfly",
+	target_os = "freebsd",
+	target_os = "illumos",
+	target_os = "solaris",
+))]
+fn output_queue_len(file: &std::fs::File) -> std::io::Result<usize> {
+	unsafe {
+		let mut queued: c_int = 0;
+		check(libc::ioctl(file.as_raw_fd(), libc::TIOCOUTQ as _, &mut queued))?;
+		Ok(queued.max(0) as usize)
+	}
+}
+
+/// Put a file descriptor in non-blocking mode.
+///
+/// `posix_openpt()` does not accept `O_NONBLOCK` on all platforms, so it is set afterwards.
+#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+fn set_nonblocking(file: &std::fs::File) -> std::io::Result<()> {
+	unsafe {
+		let flags = check(libc::fcntl(file.as_raw_fd(), libc::F_GETFL))?;
+		check(libc::fcntl(file.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK))?;
+	}
+	Ok(())
+}
+
 /// Check the return value of a syscall for errors.
 fn check(ret: i32) -> std::io::Result<i32> {
 	if ret == -1 {
@@ -362,6 +750,16 @@ fn check(ret: i32) -> std::io::Result<i32> {
 	}
 }
 
+/// Check the return value of a function that returns an error number directly.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn check_errno(ret: c_int) -> std::io::Result<()> {
+	if ret == 0 {
+		Ok(())
+	} else {
+		Err(std::io::Error::from_raw_os_error(ret))
+	}
+}
+
 /// Check the return value of a syscall for errors.
 fn check_isize(ret: isize) -> std::io::Result<usize> {
 	if ret == -1 {
@@ -376,7 +774,7 @@ fn other_error<E>(msg: E) -> std::io::Error
 where
 	E: Into<Box<dyn std::error::Error + Send + Sync>>,
 {
-	std::io::Error::new(std::io::ErrorKind::Other, msg)
+	std::io::Error::other(msg)
 }
 
 #[cfg(any(doc, feature = "doc", all(unix, feature = "unix")))]
@@ -423,6 +821,16 @@ fn pts_name(master: &SerialPort) -> std::io::Result<std::path::PathBuf> {
 }
 
 impl Settings {
+	pub fn matches(&self, other: &Self) -> bool {
+		let (a, b) = (&self.termios, &other.termios);
+		a.c_iflag == b.c_iflag
+			&& a.c_oflag == b.c_oflag
+			&& a.c_cflag == b.c_cflag
+			&& a.c_lflag == b.c_lflag
+			&& a.c_cc == b.c_cc
+			&& self.get_baud_rate().ok() == other.get_baud_rate().ok()
+	}
+
 	pub fn set_raw(&mut self) {
 		unsafe {
 			#[allow(clippy::unnecessary_cast)] // not unnecessary for all targets
@@ -494,7 +902,7 @@ impl Settings {
 					let baud_rate = libc::cfgetospeed(&self.termios);
 					#[allow(clippy::useless_conversion)] // Not useless on all platforms.
 					baud_rate.try_into()
-						.map_err(|_| std::io::Error::new(std::io::ErrorKind::Other, format!("baud rate out of range: {} > {}", baud_rate, u32::MAX)))
+						.map_err(|_| std::io::Error::other(format!("baud rate out of range: {} > {}", baud_rate, u32::MAX)))
 				}
 			} else {
 				#[cfg(all(
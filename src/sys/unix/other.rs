@@ -25,8 +25,5 @@ pub const BAUD_RATES: [(u32, u32); 18] = [
 ];
 
 pub fn enumerate() -> std::io::Result<Vec<PathBuf>> {
-	Err(std::io::Error::new(
-		std::io::ErrorKind::Other,
-		"port enumeration is not implemented for this platform",
-	))
+	Err(std::io::Error::other("port enumeration is not implemented for this platform"))
 }
@@ -0,0 +1,27 @@
+use super::check;
+
+/// Puts the terminal connected to standard input in raw mode, and restores the original mode on drop.
+pub struct RawMode {
+	original: libc::termios,
+}
+
+impl RawMode {
+	pub fn enable() -> std::io::Result<Self> {
+		unsafe {
+			let mut original: libc::termios = std::mem::zeroed();
+			check(libc::tcgetattr(libc::STDIN_FILENO, &mut original))?;
+			let mut raw = original;
+			libc::cfmakeraw(&mut raw);
+			check(libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw))?;
+			Ok(Self { original })
+		}
+	}
+}
+
+impl Drop for RawMode {
+	fn drop(&mut self) {
+		unsafe {
+			libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+		}
+	}
+}
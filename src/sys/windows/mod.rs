@@ -6,7 +6,12 @@ use std::time::Duration;
 
 use winapi::shared::minwindef::{BOOL, HKEY};
 use winapi::shared::winerror;
-use winapi::um::{commapi, fileapi, handleapi, ioapiset, minwinbase, synchapi, winbase, winnt, winreg};
+use winapi::um::{
+	commapi, fileapi, handleapi, ioapiset, minwinbase, processthreadsapi, synchapi, winbase, winnt, winreg,
+};
+
+mod terminal;
+pub use terminal::RawMode;
 
 pub struct SerialPort {
 	pub file: std::fs::File,
@@ -19,17 +24,21 @@ pub struct Settings {
 
 impl SerialPort {
 	pub fn open(name: &Path) -> std::io::Result<Self> {
+		Self::open_with_access(name, true)
+	}
+
+	pub fn open_read_only(name: &Path) -> std::io::Result<Self> {
+		Self::open_with_access(name, false)
+	}
+
+	fn open_with_access(name: &Path, write: bool) -> std::io::Result<Self> {
 		use std::os::windows::fs::OpenOptionsExt;
 
-		// Use the win32 device namespace, otherwise we're limited to COM1-9.
-		// This also works with higher numbers.
-		// https://docs.microsoft.com/en-us/windows/win32/fileio/naming-a-file#win32-device-namespaces
-		let mut path = OsString::from("\\\\.\\");
-		path.push(name.as_os_str());
+		let path = resolve_device_path(name)?;
 
 		let file = std::fs::OpenOptions::new()
 			.read(true)
-			.write(true)
+			.write(write)
 			.create(false)
 			.custom_flags(winbase::FILE_FLAG_OVERLAPPED)
 			.open(path)?;
@@ -247,6 +256,14 @@ impl SerialPort {
 		}
 	}
 
+	pub fn set_break(&self, enable: bool) -> std::io::Result<()> {
+		if enable {
+			escape_comm_function(&self.file, winbase::SETBREAK)
+		} else {
+			escape_comm_function(&self.file, winbase::CLRBREAK)
+		}
+	}
+
 	pub fn set_rts(&self, state: bool) -> std::io::Result<()> {
 		if state {
 			escape_comm_function(&self.file, winbase::SETRTS)
@@ -281,6 +298,147 @@ impl SerialPort {
 		// I think.
 		read_pin(&self.file, winbase::MS_RLSD_ON)
 	}
+
+	pub fn set_modem_lines(&self, rts: Option<bool>, dtr: Option<bool>) -> std::io::Result<()> {
+		// There is no single call to change multiple lines, so set them back to back.
+		if let Some(rts) = rts {
+			self.set_rts(rts)?;
+		}
+		if let Some(dtr) = dtr {
+			self.set_dtr(dtr)?;
+		}
+		Ok(())
+	}
+
+	pub fn read_modem_lines(&self) -> std::io::Result<crate::ModemLines> {
+		unsafe {
+			let mut bits: u32 = 0;
+			check_bool(commapi::GetCommModemStatus(self.file.as_raw_handle(), &mut bits))?;
+			Ok(crate::ModemLines {
+				rts: None,
+				dtr: None,
+				cts: bits & winbase::MS_CTS_ON != 0,
+				dsr: bits & winbase::MS_DSR_ON != 0,
+				ri: bits & winbase::MS_RING_ON != 0,
+				cd: bits & winbase::MS_RLSD_ON != 0,
+			})
+		}
+	}
+
+	pub fn get_flow_control_stall(&self) -> std::io::Result<Option<crate::FlowControlStall>> {
+		let status = self.get_comm_status()?;
+		if status.fCtsHold() != 0 {
+			Ok(Some(crate::FlowControlStall::CtsDeasserted))
+		} else if status.fDsrHold() != 0 {
+			Ok(Some(crate::FlowControlStall::DsrDeasserted))
+		} else if status.fRlsdHold() != 0 {
+			Ok(Some(crate::FlowControlStall::CdDeasserted))
+		} else if status.fXoffHold() != 0 {
+			Ok(Some(crate::FlowControlStall::XoffReceived))
+		} else {
+			Ok(None)
+		}
+	}
+
+	pub fn bytes_to_read(&self) -> std::io::Result<usize> {
+		Ok(self.get_comm_status()?.cbInQue as usize)
+	}
+
+	pub fn bytes_to_write(&self) -> std::io::Result<usize> {
+		Ok(self.get_comm_status()?.cbOutQue as usize)
+	}
+
+	pub fn is_tx_empty(&self) -> std::io::Result<bool> {
+		Ok(self.get_comm_status()?.cbOutQue == 0)
+	}
+
+	pub fn is_output_suspended_by_xoff(&self) -> std::io::Result<bool> {
+		Ok(self.get_comm_status()?.fXoffHold() != 0)
+	}
+
+	pub fn send_xon(&self) -> std::io::Result<()> {
+		let settings = self.get_configuration()?;
+		unsafe { check_bool(commapi::TransmitCommChar(self.file.as_raw_handle(), settings.dcb.XonChar)) }
+	}
+
+	pub fn send_xoff(&self) -> std::io::Result<()> {
+		let settings = self.get_configuration()?;
+		unsafe { check_bool(commapi::TransmitCommChar(self.file.as_raw_handle(), settings.dcb.XoffChar)) }
+	}
+
+	pub fn resume_output(&self) -> std::io::Result<()> {
+		// SETXON makes the driver act as if an XON character was received.
+		escape_comm_function(&self.file, winbase::SETXON)
+	}
+
+	/// Resolve the path of the device from the file handle, if the driver supports it.
+	pub fn device_path(&self) -> Option<PathBuf> {
+		use std::os::windows::ffi::OsStringExt;
+		let mut buffer = vec![0u16; 1024];
+		let len = unsafe {
+			fileapi::GetFinalPathNameByHandleW(self.file.as_raw_handle(), buffer.as_mut_ptr(), buffer.len() as u32, 0)
+		};
+		if len == 0 || len as usize >= buffer.len() {
+			return None;
+		}
+		Some(OsString::from_wide(&buffer[..len as usize]).into())
+	}
+
+	/// Get the communication status of the device.
+	///
+	/// Note that this also clears any pending communication errors.
+	fn get_comm_status(&self) -> std::io::Result<winbase::COMSTAT> {
+		unsafe {
+			let mut errors = 0;
+			let mut status: winbase::COMSTAT = std::mem::zeroed();
+			check_bool(commapi::ClearCommError(self.file.as_raw_handle(), &mut errors, &mut status))?;
+			Ok(status)
+		}
+	}
+}
+
+/// Resolve the name of a serial port to a path that can be opened.
+///
+/// The name can be a path in the win32 device namespace (`\\.\COM12`), which is used as-is,
+/// a device instance ID (`USB\VID_0403&PID_6001\A6008cB1`), which is resolved to the COM port assigned to the device,
+/// or a plain COM port name (`COM12`), which is opened in the win32 device namespace.
+fn resolve_device_path(name: &Path) -> std::io::Result<OsString> {
+	let name = name.as_os_str();
+	let port_name = match name.to_str() {
+		// Already a path in the win32 device namespace or a literal path.
+		Some(name) if name.starts_with("\\\\") => return Ok(name.into()),
+		// A device instance ID: look up the COM port assigned to it.
+		Some(name) if name.contains('\\') => get_instance_port_name(name)?,
+		_ => name.to_owned(),
+	};
+
+	// Use the win32 device namespace, otherwise we're limited to COM1-9.
+	// This also works with higher numbers.
+	// https://docs.microsoft.com/en-us/windows/win32/fileio/naming-a-file#win32-device-namespaces
+	let mut path = OsString::from("\\\\.\\");
+	path.push(port_name);
+	Ok(path)
+}
+
+/// Get the COM port name assigned to a device instance.
+fn get_instance_port_name(instance_id: &str) -> std::io::Result<OsString> {
+	let subkey = format!("SYSTEM\\CurrentControlSet\\Enum\\{}\\Device Parameters", instance_id);
+	let subkey = std::ffi::CString::new(subkey)
+		.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "device instance ID contains a null byte"))?;
+	let key = match RegKey::open(winreg::HKEY_LOCAL_MACHINE, &subkey, winnt::KEY_READ) {
+		Ok(x) => x,
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+			return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no device found with the given instance ID"));
+		},
+		Err(e) => return Err(e),
+	};
+	let mut name = key.get_string_value_by_name(c"PortName")?;
+	if let Some(i) = name.iter().rposition(|&b| b != 0) {
+		name.truncate(i + 1);
+	}
+	String::from_utf8(name)
+		.map(OsString::from)
+		.map_err(|_| other_error("COM port name of device is not valid UTF-8"))
 }
 
 struct Event {
@@ -349,6 +507,62 @@ fn read_pin(file: &std::fs::File, pin: u32) -> std::io::Result<bool> {
 	}
 }
 
+/// The priority of a thread, saved to restore it later.
+pub struct SavedScheduling {
+	priority: i32,
+}
+
+/// Switch the calling thread to the time critical priority.
+///
+/// Windows has no separate realtime priorities per thread, so the priority is ignored.
+/// Returns the previous thread priority.
+pub fn enter_realtime(priority: u8) -> std::io::Result<SavedScheduling> {
+	let _ = priority;
+	unsafe {
+		let thread = processthreadsapi::GetCurrentThread();
+		let previous = processthreadsapi::GetThreadPriority(thread);
+		if previous == winbase::THREAD_PRIORITY_ERROR_RETURN as i32 {
+			return Err(std::io::Error::last_os_error());
+		}
+		check_bool(processthreadsapi::SetThreadPriority(thread, winbase::THREAD_PRIORITY_TIME_CRITICAL as i32))?;
+		Ok(SavedScheduling { priority: previous })
+	}
+}
+
+/// Restore the priority of the calling thread.
+pub fn restore_scheduling(saved: &SavedScheduling) -> std::io::Result<()> {
+	unsafe { check_bool(processthreadsapi::SetThreadPriority(processthreadsapi::GetCurrentThread(), saved.priority)) }
+}
+
+/// Restrict the calling thread to the given CPUs.
+pub fn set_current_thread_affinity(cpus: &[usize]) -> std::io::Result<()> {
+	let mut mask: usize = 0;
+	for &cpu in cpus {
+		if cpu >= usize::BITS as usize {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "CPU index out of range"));
+		}
+		mask |= 1 << cpu;
+	}
+	unsafe {
+		if winbase::SetThreadAffinityMask(processthreadsapi::GetCurrentThread(), mask) == 0 {
+			return Err(std::io::Error::last_os_error());
+		}
+	}
+	Ok(())
+}
+
+/// Set the scheduling priority of the calling thread.
+pub fn set_current_thread_priority(priority: crate::ThreadPriority) -> std::io::Result<()> {
+	let priority = match priority {
+		crate::ThreadPriority::Lowest => winbase::THREAD_PRIORITY_LOWEST,
+		crate::ThreadPriority::Low => winbase::THREAD_PRIORITY_BELOW_NORMAL,
+		crate::ThreadPriority::Normal => winbase::THREAD_PRIORITY_NORMAL,
+		crate::ThreadPriority::High => winbase::THREAD_PRIORITY_ABOVE_NORMAL,
+		crate::ThreadPriority::Highest => winbase::THREAD_PRIORITY_HIGHEST,
+	};
+	unsafe { check_bool(processthreadsapi::SetThreadPriority(processthreadsapi::GetCurrentThread(), priority as i32)) }
+}
+
 /// Check the return value of a syscall for errors.
 fn check_bool(ret: BOOL) -> std::io::Result<()> {
 	if ret == 0 {
@@ -372,10 +586,26 @@ fn other_error<E>(msg: E) -> std::io::Error
 where
 	E: Into<Box<dyn std::error::Error + Send + Sync>>,
 {
-	std::io::Error::new(std::io::ErrorKind::Other, msg)
+	std::io::Error::other(msg)
 }
 
 impl Settings {
+	pub fn matches(&self, other: &Self) -> bool {
+		let (a, b) = (&self.dcb, &other.dcb);
+		a.BaudRate == b.BaudRate
+			&& a.BitFields == b.BitFields
+			&& a.XonLim == b.XonLim
+			&& a.XoffLim == b.XoffLim
+			&& a.ByteSize == b.ByteSize
+			&& a.Parity == b.Parity
+			&& a.StopBits == b.StopBits
+			&& a.XonChar == b.XonChar
+			&& a.XoffChar == b.XoffChar
+			&& a.ErrorChar == b.ErrorChar
+			&& a.EofChar == b.EofChar
+			&& a.EvtChar == b.EvtChar
+	}
+
 	pub fn set_raw(&mut self) {
 		self.set_char_size(crate::CharSize::Bits8);
 		self.set_stop_bits(crate::StopBits::One);
@@ -592,6 +822,44 @@ impl RegKey {
 	}
 }
 
+impl RegKey {
+	fn get_string_value_by_name(&self, name: &CStr) -> std::io::Result<Vec<u8>> {
+		unsafe {
+			let mut data_len: u32 = 0;
+			let mut kind = 0;
+			let status = winreg::RegQueryValueExA(
+				self.key,
+				name.as_ptr(),
+				std::ptr::null_mut(),
+				&mut kind,
+				std::ptr::null_mut(),
+				&mut data_len,
+			);
+			if status != 0 {
+				return Err(std::io::Error::from_raw_os_error(status));
+			} else if kind != winnt::REG_SZ {
+				return Err(other_error("registry value is not a string"));
+			}
+
+			let mut data = vec![0u8; data_len as usize];
+			let status = winreg::RegQueryValueExA(
+				self.key,
+				name.as_ptr(),
+				std::ptr::null_mut(),
+				&mut kind,
+				data.as_mut_ptr(),
+				&mut data_len,
+			);
+			if status != 0 {
+				Err(std::io::Error::from_raw_os_error(status))
+			} else {
+				data.truncate(data_len as usize);
+				Ok(data)
+			}
+		}
+	}
+}
+
 impl Drop for RegKey {
 	fn drop(&mut self) {
 		unsafe {
@@ -601,7 +869,7 @@ impl Drop for RegKey {
 }
 
 pub fn enumerate() -> std::io::Result<Vec<PathBuf>> {
-	let subkey = unsafe { CStr::from_bytes_with_nul_unchecked(b"Hardware\\DEVICEMAP\\SERIALCOMM\x00") };
+	let subkey = c"Hardware\\DEVICEMAP\\SERIALCOMM";
 	let device_map = match RegKey::open(winreg::HKEY_LOCAL_MACHINE, subkey, winnt::KEY_READ) {
 		Ok(x) => x,
 		Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
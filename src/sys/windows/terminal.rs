@@ -0,0 +1,42 @@
+use winapi::um::{consoleapi, processenv, winbase, wincon};
+
+use super::check_bool;
+
+/// Puts the console in raw mode, and restores the original mode on drop.
+pub struct RawMode {
+	input_mode: u32,
+	output_mode: u32,
+}
+
+impl RawMode {
+	pub fn enable() -> std::io::Result<Self> {
+		unsafe {
+			let input = processenv::GetStdHandle(winbase::STD_INPUT_HANDLE);
+			let output = processenv::GetStdHandle(winbase::STD_OUTPUT_HANDLE);
+			let mut input_mode = 0;
+			let mut output_mode = 0;
+			check_bool(consoleapi::GetConsoleMode(input, &mut input_mode))?;
+			check_bool(consoleapi::GetConsoleMode(output, &mut output_mode))?;
+
+			let raw_input_mode = input_mode
+				& !(wincon::ENABLE_ECHO_INPUT | wincon::ENABLE_LINE_INPUT | wincon::ENABLE_PROCESSED_INPUT)
+				| wincon::ENABLE_VIRTUAL_TERMINAL_INPUT;
+			check_bool(consoleapi::SetConsoleMode(input, raw_input_mode))?;
+			let raw_output_mode = output_mode | wincon::ENABLE_VIRTUAL_TERMINAL_PROCESSING;
+			if let Err(e) = check_bool(consoleapi::SetConsoleMode(output, raw_output_mode)) {
+				consoleapi::SetConsoleMode(input, input_mode);
+				return Err(e);
+			}
+			Ok(Self { input_mode, output_mode })
+		}
+	}
+}
+
+impl Drop for RawMode {
+	fn drop(&mut self) {
+		unsafe {
+			consoleapi::SetConsoleMode(processenv::GetStdHandle(winbase::STD_INPUT_HANDLE), self.input_mode);
+			consoleapi::SetConsoleMode(processenv::GetStdHandle(winbase::STD_OUTPUT_HANDLE), self.output_mode);
+		}
+	}
+}
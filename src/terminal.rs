@@ -0,0 +1,293 @@
+//! An interactive serial terminal.
+//!
+//! A [`Miniterm`] connects the local terminal to a serial port:
+//! every key press is sent to the serial port, and everything received is written to the terminal.
+//! The local terminal is put in raw mode with [`RawMode`], so control keys are passed through as well.
+//!
+//! The terminal is controlled with two special keys:
+//! * The exit key (`Ctrl+]` by default) stops the terminal.
+//! * The menu key (`Ctrl+T` by default) followed by another key changes a setting:
+//!   `h` toggles the hex view, `e` toggles local echo,
+//!   and pressing the menu key twice sends it to the serial port.
+//!
+//! See the `miniterm` example for a complete program.
+
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::{sys, SerialPort};
+
+/// The read timeout used by the thread that reads from the serial port.
+///
+/// This limits how long it takes for the thread to notice that the terminal was stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The number of bytes per line in the hex view.
+const HEX_BYTES_PER_LINE: usize = 16;
+
+/// Puts the local terminal in raw mode.
+///
+/// In raw mode, key presses are passed through immediately and without interpretation.
+/// The original mode is restored when the value is dropped.
+pub struct RawMode {
+	/// The platform specific implementation.
+	_inner: sys::RawMode,
+}
+
+impl RawMode {
+	/// Put the terminal connected to standard input in raw mode.
+	pub fn enable() -> std::io::Result<Self> {
+		Ok(Self {
+			_inner: sys::RawMode::enable()?,
+		})
+	}
+}
+
+/// An interactive serial terminal.
+///
+/// See the [module documentation][self] for more information.
+pub struct Miniterm {
+	/// The serial port.
+	port: SerialPort,
+
+	/// The key that stops the terminal.
+	exit_key: u8,
+
+	/// The key that starts a menu command.
+	menu_key: u8,
+
+	/// True if typed keys are also written to the local terminal.
+	local_echo: AtomicBool,
+
+	/// True if received data is shown as hexadecimal bytes.
+	hex_view: AtomicBool,
+}
+
+impl Miniterm {
+	/// Create a new terminal for a serial port.
+	pub fn new(port: SerialPort) -> Self {
+		Self {
+			port,
+			exit_key: 0x1D,
+			menu_key: 0x14,
+			local_echo: AtomicBool::new(false),
+			hex_view: AtomicBool::new(false),
+		}
+	}
+
+	/// Get a reference to the serial port.
+	pub fn port(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Consume the terminal and return the serial port.
+	pub fn into_inner(self) -> SerialPort {
+		self.port
+	}
+
+	/// Set the key that stops the terminal.
+	///
+	/// The default is `Ctrl+]` (`0x1D`).
+	pub fn set_exit_key(&mut self, key: u8) {
+		self.exit_key = key;
+	}
+
+	/// Get the key that stops the terminal.
+	pub fn get_exit_key(&self) -> u8 {
+		self.exit_key
+	}
+
+	/// Set the key that starts a menu command.
+	///
+	/// The default is `Ctrl+T` (`0x14`).
+	pub fn set_menu_key(&mut self, key: u8) {
+		self.menu_key = key;
+	}
+
+	/// Get the key that starts a menu command.
+	pub fn get_menu_key(&self) -> u8 {
+		self.menu_key
+	}
+
+	/// Enable or disable local echo of typed keys.
+	pub fn set_local_echo(&mut self, enable: bool) {
+		*self.local_echo.get_mut() = enable;
+	}
+
+	/// Check if local echo of typed keys is enabled.
+	pub fn get_local_echo(&self) -> bool {
+		self.local_echo.load(Ordering::Relaxed)
+	}
+
+	/// Enable or disable the hex view of received data.
+	pub fn set_hex_view(&mut self, enable: bool) {
+		*self.hex_view.get_mut() = enable;
+	}
+
+	/// Check if the hex view of received data is enabled.
+	pub fn get_hex_view(&self) -> bool {
+		self.hex_view.load(Ordering::Relaxed)
+	}
+
+	/// Run the terminal on standard input and standard output until the exit key is pressed.
+	///
+	/// The local terminal is put in raw mode while the terminal runs.
+	pub fn run(&mut self) -> std::io::Result<()> {
+		let _raw_mode = RawMode::enable()?;
+		self.run_with(std::io::stdin(), std::io::stdout())
+	}
+
+	/// Run the terminal with custom input and output streams until the exit key is read.
+	///
+	/// This does not change the mode of the local terminal.
+	/// The read timeout of the serial port is changed while the terminal runs, and restored afterwards.
+	pub fn run_with<R, W>(&mut self, input: R, output: W) -> std::io::Result<()>
+	where
+		R: Read,
+		W: Write + Send,
+	{
+		let read_timeout = self.port.get_read_timeout()?;
+		self.port.set_read_timeout(POLL_INTERVAL)?;
+		let output = Mutex::new(output);
+		let stopped = AtomicBool::new(false);
+		let result = std::thread::scope(|scope| {
+			let receiver = std::thread::Builder::new()
+				.name("serial2-miniterm".into())
+				.spawn_scoped(scope, || self.receive_loop(&output, &stopped))?;
+			let result = self.input_loop(input, &output, &receiver);
+			stopped.store(true, Ordering::Relaxed);
+			let receive_result = receiver.join().unwrap_or(Ok(()));
+			result.and(receive_result)
+		});
+		self.port.set_read_timeout(read_timeout)?;
+		result
+	}
+
+	/// Read keys from the input and send them to the serial port, until the exit key is read.
+	fn input_loop<R: Read, W: Write>(
+		&self,
+		mut input: R,
+		output: &Mutex<W>,
+		receiver: &std::thread::ScopedJoinHandle<std::io::Result<()>>,
+	) -> std::io::Result<()> {
+		let mut buffer = [0; 256];
+		let mut in_menu = false;
+		loop {
+			let read = match input.read(&mut buffer) {
+				Ok(0) => return Ok(()),
+				Ok(read) => read,
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			};
+			if receiver.is_finished() {
+				// The receive thread stopped because of an error, which is reported by the caller.
+				return Ok(());
+			}
+
+			let mut send = Vec::with_capacity(read);
+			for &key in &buffer[..read] {
+				if in_menu {
+					in_menu = false;
+					self.menu_command(key, &mut send, output)?;
+				} else if key == self.exit_key {
+					self.send(&send, output)?;
+					return Ok(());
+				} else if key == self.menu_key {
+					in_menu = true;
+				} else {
+					send.push(key);
+				}
+			}
+			self.send(&send, output)?;
+		}
+	}
+
+	/// Execute a menu command.
+	fn menu_command<W: Write>(&self, key: u8, send: &mut Vec<u8>, output: &Mutex<W>) -> std::io::Result<()> {
+		let message = match key {
+			b'h' | b'H' => {
+				let enabled = !self.hex_view.fetch_xor(true, Ordering::Relaxed);
+				if enabled {
+					"hex view on"
+				} else {
+					"hex view off"
+				}
+			},
+			b'e' | b'E' => {
+				let enabled = !self.local_echo.fetch_xor(true, Ordering::Relaxed);
+				if enabled {
+					"local echo on"
+				} else {
+					"local echo off"
+				}
+			},
+			key if key == self.menu_key => {
+				send.push(key);
+				return Ok(());
+			},
+			_ => return Ok(()),
+		};
+		let mut output = lock(output);
+		write!(output, "\r\n[{message}]\r\n")?;
+		output.flush()
+	}
+
+	/// Send typed keys to the serial port, and echo them locally if enabled.
+	fn send<W: Write>(&self, data: &[u8], output: &Mutex<W>) -> std::io::Result<()> {
+		if data.is_empty() {
+			return Ok(());
+		}
+		self.port.write_all(data)?;
+		if self.get_local_echo() {
+			let mut output = lock(output);
+			output.write_all(data)?;
+			output.flush()?;
+		}
+		Ok(())
+	}
+
+	/// Read from the serial port and write to the output until the terminal is stopped.
+	fn receive_loop<W: Write>(&self, output: &Mutex<W>, stopped: &AtomicBool) -> std::io::Result<()> {
+		let mut buffer = [0; 512];
+		let mut column = 0;
+		while !stopped.load(Ordering::Relaxed) {
+			let read = match self.port.read(&mut buffer) {
+				Ok(0) => return Ok(()),
+				Ok(read) => read,
+				Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			};
+			let mut output = lock(output);
+			if self.get_hex_view() {
+				write_hex(&mut *output, &buffer[..read], &mut column)?;
+			} else {
+				column = 0;
+				output.write_all(&buffer[..read])?;
+			}
+			output.flush()?;
+		}
+		Ok(())
+	}
+}
+
+/// Write data as hexadecimal bytes, with a fixed number of bytes per line.
+fn write_hex<W: Write>(output: &mut W, data: &[u8], column: &mut usize) -> std::io::Result<()> {
+	let mut text = String::with_capacity(data.len() * 3 + 2);
+	for byte in data {
+		text.push_str(&format!("{byte:02X} "));
+		*column += 1;
+		if *column == HEX_BYTES_PER_LINE {
+			text.push_str("\r\n");
+			*column = 0;
+		}
+	}
+	output.write_all(text.as_bytes())
+}
+
+/// Lock a mutex, ignoring poisoning.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+	mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,239 @@
+use std::marker::PhantomData;
+use std::thread::JoinHandle;
+
+/// The scheduling priority of a thread, relative to other threads of the system.
+///
+/// On Linux and Android, the priority is mapped to a nice value between 10 (lowest) and -10 (highest).
+/// Raising the priority above normal usually requires elevated privileges, like `CAP_SYS_NICE`.
+/// On Windows, the priority is mapped to the thread priorities from `THREAD_PRIORITY_LOWEST` to `THREAD_PRIORITY_HIGHEST`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub enum ThreadPriority {
+	/// The lowest priority.
+	Lowest,
+
+	/// A priority below normal.
+	Low,
+
+	/// The normal priority.
+	#[default]
+	Normal,
+
+	/// A priority above normal.
+	High,
+
+	/// The highest priority that is not a realtime priority.
+	Highest,
+}
+
+/// Options for the background threads spawned by this crate.
+///
+/// Latency sensitive deployments can use this to give background threads a recognizable name,
+/// to change their priority, or to pin them to specific CPUs, away from noisy cores.
+///
+/// The options are accepted by the constructors of components that spawn a background thread,
+/// like [`BufferedReader::with_thread_options()`][crate::buffered::BufferedReader::with_thread_options],
+/// [`WriteQueue::with_thread_options()`][crate::write_queue::WriteQueue::with_thread_options] and
+/// [`AutoReconnectPort::with_thread_options()`][crate::reconnect::AutoReconnectPort::with_thread_options].
+/// If the options can not be applied, the constructor fails and no thread is left running.
+///
+/// Thread priorities and CPU affinity are supported on Linux, Android and Windows.
+/// On other platforms, setting them results in an error of the type [`std::io::ErrorKind::Unsupported`].
+///
+/// # Example
+/// ```no_run
+/// # use serial2::{SerialPort, ThreadOptions, ThreadPriority};
+/// # use serial2::buffered::BufferedReader;
+/// # fn foo() -> std::io::Result<()> {
+/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+/// let mut options = ThreadOptions::new();
+/// options.set_name("motor-rx");
+/// options.set_priority(Some(ThreadPriority::Highest));
+/// options.set_affinity(Some(vec![3]));
+/// let reader = BufferedReader::with_thread_options(port, 4096, &options)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ThreadOptions {
+	/// The name of the thread, if it should not use the default name.
+	name: Option<String>,
+
+	/// The priority of the thread, if it should be changed.
+	priority: Option<ThreadPriority>,
+
+	/// The CPUs the thread may run on, if it should be restricted.
+	affinity: Option<Vec<usize>>,
+
+	/// The realtime priority of the thread, if it should use realtime scheduling.
+	realtime_priority: Option<u8>,
+}
+
+impl ThreadOptions {
+	/// Create new thread options that keep all defaults.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the name of the thread.
+	///
+	/// By default, each component uses a name that starts with `serial2-`.
+	pub fn set_name(&mut self, name: impl Into<String>) {
+		self.name = Some(name.into());
+	}
+
+	/// Get the name of the thread, if set.
+	pub fn get_name(&self) -> Option<&str> {
+		self.name.as_deref()
+	}
+
+	/// Set the priority of the thread, or `None` to inherit the priority of the spawning thread.
+	pub fn set_priority(&mut self, priority: Option<ThreadPriority>) {
+		self.priority = priority;
+	}
+
+	/// Get the priority of the thread, if set.
+	pub fn get_priority(&self) -> Option<ThreadPriority> {
+		self.priority
+	}
+
+	/// Set the indices of the CPUs the thread may run on, or `None` to inherit the affinity of the spawning thread.
+	pub fn set_affinity(&mut self, cpus: Option<Vec<usize>>) {
+		self.affinity = cpus;
+	}
+
+	/// Get the indices of the CPUs the thread may run on, if set.
+	pub fn get_affinity(&self) -> Option<&[usize]> {
+		self.affinity.as_deref()
+	}
+
+	/// Set the realtime priority of the thread, or `None` to keep the normal scheduling policy.
+	///
+	/// See [`RealtimeGuard`] for more information.
+	/// The thread keeps the realtime priority until it exits.
+	pub fn set_realtime_priority(&mut self, priority: Option<u8>) {
+		self.realtime_priority = priority;
+	}
+
+	/// Get the realtime priority of the thread, if set.
+	pub fn get_realtime_priority(&self) -> Option<u8> {
+		self.realtime_priority
+	}
+
+	/// Apply the priority, the realtime priority and the CPU affinity to the calling thread.
+	///
+	/// The name is ignored, since the name of a running thread can not be changed.
+	pub fn apply_to_current_thread(&self) -> std::io::Result<()> {
+		if let Some(cpus) = &self.affinity {
+			if cpus.is_empty() {
+				return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "empty CPU affinity list"));
+			}
+			crate::sys::set_current_thread_affinity(cpus)?;
+		}
+		if let Some(priority) = self.priority {
+			crate::sys::set_current_thread_priority(priority)?;
+		}
+		if let Some(priority) = self.realtime_priority {
+			crate::sys::enter_realtime(priority)?;
+		}
+		Ok(())
+	}
+
+	/// Spawn a thread with these options.
+	///
+	/// The options are applied by the new thread before it runs `function`.
+	/// If that fails, `function` is dropped without running, and the error is returned.
+	pub(crate) fn spawn<F>(&self, default_name: &str, function: F) -> std::io::Result<JoinHandle<()>>
+	where
+		F: FnOnce() + Send + 'static,
+	{
+		let name = self.name.clone().unwrap_or_else(|| default_name.into());
+		let options = self.clone();
+		let (result_sender, result) = std::sync::mpsc::sync_channel(1);
+		let thread = std::thread::Builder::new().name(name).spawn(move || {
+			let applied = options.apply_to_current_thread();
+			let ok = applied.is_ok();
+			let _ = result_sender.send(applied);
+			if ok {
+				function();
+			}
+		})?;
+		match result.recv() {
+			Ok(Ok(())) => Ok(thread),
+			Ok(Err(e)) => {
+				let _ = thread.join();
+				Err(e)
+			},
+			Err(_) => Err(std::io::Error::other("background thread exited unexpectedly")),
+		}
+	}
+}
+
+/// Realtime scheduling for the calling thread, reverted when dropped.
+///
+/// Control loops that poll a serial port at a high rate can miss their deadlines when the system is under load.
+/// A realtime thread is always scheduled before normal threads, so it is not delayed by other work.
+///
+/// On Linux and Android, this switches the calling thread to the `SCHED_FIFO` policy with the given priority,
+/// which must be between 1 and 99.
+/// This requires the `CAP_SYS_NICE` capability or a sufficient `RLIMIT_RTPRIO` resource limit.
+/// Without it, [`Self::enter()`] fails with an error of the type [`std::io::ErrorKind::PermissionDenied`].
+///
+/// On Windows, this raises the thread to the time critical priority and the priority value is ignored.
+/// On other platforms, [`Self::enter()`] fails with an error of the type [`std::io::ErrorKind::Unsupported`].
+///
+/// A realtime thread that never blocks can starve the rest of the system.
+/// Make sure the thread waits for I/O or sleeps regularly.
+///
+/// To run background threads of this crate with realtime scheduling, use [`ThreadOptions::set_realtime_priority()`].
+///
+/// # Example
+/// ```no_run
+/// # use serial2::{RealtimeGuard, SerialPort};
+/// # fn foo() -> std::io::Result<()> {
+/// let port = SerialPort::open("/dev/ttyUSB0", 1_000_000)?;
+/// let realtime = match RealtimeGuard::enter(50) {
+///     Ok(guard) => Some(guard),
+///     Err(e) => {
+///         eprintln!("Running without realtime scheduling: {e}");
+///         None
+///     },
+/// };
+/// // Run the control loop.
+/// drop(realtime);
+/// # Ok(())
+/// # }
+/// ```
+pub struct RealtimeGuard {
+	/// The scheduling policy and parameters before entering realtime scheduling.
+	saved: crate::sys::SavedScheduling,
+
+	/// The guard must be dropped on the thread that created it.
+	_not_send: PhantomData<*const ()>,
+}
+
+impl RealtimeGuard {
+	/// Switch the calling thread to realtime scheduling with the given priority.
+	///
+	/// The previous scheduling policy and priority are restored when the guard is dropped.
+	pub fn enter(priority: u8) -> std::io::Result<Self> {
+		Ok(Self {
+			saved: crate::sys::enter_realtime(priority)?,
+			_not_send: PhantomData,
+		})
+	}
+
+	/// Restore the previous scheduling policy and priority, and report errors.
+	///
+	/// Dropping the guard does the same, but ignores errors.
+	pub fn leave(self) -> std::io::Result<()> {
+		let result = crate::sys::restore_scheduling(&self.saved);
+		std::mem::forget(self);
+		result
+	}
+}
+
+impl Drop for RealtimeGuard {
+	fn drop(&mut self) {
+		let _ = crate::sys::restore_scheduling(&self.saved);
+	}
+}
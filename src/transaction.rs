@@ -0,0 +1,64 @@
+//! Request/response transactions.
+//!
+//! Most serial device drivers send a request to a device and then collect the response.
+//! [`SerialPort::transact()`][crate::SerialPort::transact] implements that loop:
+//! it discards stale input, writes the request and reads until a [`ResponseMatcher`] reports a complete response.
+//!
+//! This module provides matchers for fixed length responses ([`Length`]) and terminated responses ([`Terminator`]).
+//! Any closure that takes `&[u8]` and returns `Option<usize>` can also be used as matcher.
+
+/// Decides when a response is complete.
+pub trait ResponseMatcher {
+	/// Check if the received data contains a complete response.
+	///
+	/// Returns the length of the response if it is complete, or `None` if more data is needed.
+	fn match_response(&mut self, data: &[u8]) -> Option<usize>;
+
+	/// Get the maximum number of bytes to read for the next read call.
+	///
+	/// This can be used to avoid reading past the end of the response.
+	/// The default implementation does not limit the reads.
+	fn max_read(&self, data: &[u8]) -> usize {
+		let _ = data;
+		usize::MAX
+	}
+}
+
+/// Matches a response with a fixed length.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Length(pub usize);
+
+/// Matches a response that ends with a terminator byte.
+///
+/// The terminator is included in the response.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Terminator(pub u8);
+
+impl ResponseMatcher for Length {
+	fn match_response(&mut self, data: &[u8]) -> Option<usize> {
+		if data.len() >= self.0 {
+			Some(self.0)
+		} else {
+			None
+		}
+	}
+
+	fn max_read(&self, data: &[u8]) -> usize {
+		self.0.saturating_sub(data.len())
+	}
+}
+
+impl ResponseMatcher for Terminator {
+	fn match_response(&mut self, data: &[u8]) -> Option<usize> {
+		data.iter().position(|&byte| byte == self.0).map(|index| index + 1)
+	}
+}
+
+impl<F> ResponseMatcher for F
+where
+	F: FnMut(&[u8]) -> Option<usize>,
+{
+	fn match_response(&mut self, data: &[u8]) -> Option<usize> {
+		self(data)
+	}
+}
@@ -0,0 +1,226 @@
+//! Decoding of text received in legacy encodings.
+//!
+//! Many instruments do not send UTF-8, but Latin-1, the IBM PC code page 437 or Shift-JIS.
+//! Converting their output with [`String::from_utf8()`] fails on the first byte outside of the ASCII range.
+//!
+//! A [`Transcoder`] decodes a stream of bytes in a selected [`Encoding`] to UTF-8.
+//! Multi-byte characters that are split over multiple reads are decoded correctly.
+//! In [`DecodeMode::Lossy`] mode, invalid input is replaced by U+FFFD (the replacement character),
+//! and in [`DecodeMode::Strict`] mode it is reported as an error of the type [`std::io::ErrorKind::InvalidData`].
+//!
+//! To read lines of text from a serial port, use [`BufferedReader::read_line()`][crate::buffered::BufferedReader::read_line].
+
+mod tables;
+
+/// A text encoding.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Encoding {
+	/// UTF-8.
+	Utf8,
+
+	/// ISO 8859-1, also known as Latin-1.
+	///
+	/// Every byte maps to the Unicode code point with the same value, so decoding never fails.
+	Latin1,
+
+	/// The IBM PC code page 437.
+	///
+	/// The bytes 0x00 to 0x7F are decoded as ASCII, including control characters.
+	/// The bytes 0x80 to 0xFF are decoded as the box drawing, accented and Greek characters of the code page.
+	Cp437,
+
+	/// Shift-JIS, as defined by JIS X 0208.
+	///
+	/// The bytes 0x00 to 0x7F are decoded as ASCII.
+	ShiftJis,
+}
+
+/// How to handle input that is not valid in the selected encoding.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum DecodeMode {
+	/// Replace invalid input with U+FFFD (the replacement character).
+	#[default]
+	Lossy,
+
+	/// Report invalid input as an error of the type [`std::io::ErrorKind::InvalidData`].
+	Strict,
+}
+
+impl Encoding {
+	/// Decode complete text.
+	///
+	/// An incomplete multi-byte character at the end of the input is treated as invalid input.
+	pub fn decode(self, data: &[u8], mode: DecodeMode) -> std::io::Result<String> {
+		let mut transcoder = Transcoder::new(self, mode);
+		let mut text = transcoder.decode(data)?;
+		text.push_str(&transcoder.finish()?);
+		Ok(text)
+	}
+}
+
+/// Decodes a stream of bytes in a legacy encoding to UTF-8.
+///
+/// See the [module documentation][self] for more information.
+#[derive(Debug, Clone)]
+pub struct Transcoder {
+	/// The encoding of the input.
+	encoding: Encoding,
+
+	/// How to handle invalid input.
+	mode: DecodeMode,
+
+	/// The start of a multi-byte character that was split over multiple calls.
+	pending: Vec<u8>,
+}
+
+impl Transcoder {
+	/// Create a new transcoder for the given encoding.
+	pub fn new(encoding: Encoding, mode: DecodeMode) -> Self {
+		Self {
+			encoding,
+			mode,
+			pending: Vec::new(),
+		}
+	}
+
+	/// Get the encoding of the input.
+	pub fn encoding(&self) -> Encoding {
+		self.encoding
+	}
+
+	/// Get the mode used for invalid input.
+	pub fn mode(&self) -> DecodeMode {
+		self.mode
+	}
+
+	/// Decode the next chunk of input.
+	///
+	/// If the chunk ends with an incomplete multi-byte character, it is kept for the next call.
+	///
+	/// In [`DecodeMode::Strict`] mode, invalid input results in an error of the type [`std::io::ErrorKind::InvalidData`].
+	/// The whole chunk is discarded in that case.
+	pub fn decode(&mut self, data: &[u8]) -> std::io::Result<String> {
+		let mut input = std::mem::take(&mut self.pending);
+		input.extend_from_slice(data);
+		let mut text = String::with_capacity(input.len());
+		let consumed = decode_into(self.encoding, self.mode, &input, &mut text)?;
+		self.pending = input.split_off(consumed);
+		Ok(text)
+	}
+
+	/// Finish decoding, and handle an incomplete multi-byte character at the end of the input.
+	///
+	/// In [`DecodeMode::Lossy`] mode, the incomplete character is decoded as U+FFFD.
+	/// In [`DecodeMode::Strict`] mode, it results in an error of the type [`std::io::ErrorKind::InvalidData`].
+	pub fn finish(&mut self) -> std::io::Result<String> {
+		if self.pending.is_empty() {
+			return Ok(String::new());
+		}
+		self.pending.clear();
+		match self.mode {
+			DecodeMode::Lossy => Ok(char::REPLACEMENT_CHARACTER.into()),
+			DecodeMode::Strict => Err(invalid_data(self.encoding, "incomplete character at end of input")),
+		}
+	}
+}
+
+/// Decode as much of the input as possible.
+///
+/// Returns the number of bytes consumed.
+/// Only an incomplete multi-byte character at the end of the input is left unconsumed.
+fn decode_into(encoding: Encoding, mode: DecodeMode, input: &[u8], output: &mut String) -> std::io::Result<usize> {
+	let invalid = |output: &mut String| match mode {
+		DecodeMode::Lossy => {
+			output.push(char::REPLACEMENT_CHARACTER);
+			Ok(())
+		},
+		DecodeMode::Strict => Err(invalid_data(encoding, "invalid byte sequence")),
+	};
+
+	match encoding {
+		Encoding::Utf8 => {
+			let mut remaining = input;
+			loop {
+				match std::str::from_utf8(remaining) {
+					Ok(valid) => {
+						output.push_str(valid);
+						return Ok(input.len());
+					},
+					Err(e) => {
+						let (valid, rest) = remaining.split_at(e.valid_up_to());
+						output.push_str(std::str::from_utf8(valid).unwrap_or_default());
+						match e.error_len() {
+							None => return Ok(input.len() - rest.len()),
+							Some(len) => {
+								invalid(output)?;
+								remaining = &rest[len..];
+							},
+						}
+					},
+				}
+			}
+		},
+		Encoding::Latin1 => {
+			output.extend(input.iter().map(|&byte| char::from(byte)));
+			Ok(input.len())
+		},
+		Encoding::Cp437 => {
+			output.extend(input.iter().map(|&byte| match byte {
+				0x00..=0x7F => char::from(byte),
+				_ => tables::CP437_HIGH[usize::from(byte - 0x80)],
+			}));
+			Ok(input.len())
+		},
+		Encoding::ShiftJis => {
+			let mut i = 0;
+			while i < input.len() {
+				let lead = input[i];
+				match lead {
+					0x00..=0x7F => output.push(char::from(lead)),
+					// Half-width katakana.
+					0xA1..=0xDF => output.push(char::from_u32(0xFF61 + u32::from(lead - 0xA1)).unwrap_or_default()),
+					0x81..=0x9F | 0xE0..=0xFC => {
+						let Some(&trail) = input.get(i + 1) else {
+							return Ok(i);
+						};
+						match shift_jis_double(lead, trail) {
+							Some(c) => {
+								output.push(c);
+								i += 1;
+							},
+							// Only consume the lead byte, the trail byte may start a new character.
+							None => invalid(output)?,
+						}
+					},
+					_ => invalid(output)?,
+				}
+				i += 1;
+			}
+			Ok(input.len())
+		},
+	}
+}
+
+/// Decode a double byte Shift-JIS character.
+fn shift_jis_double(lead: u8, trail: u8) -> Option<char> {
+	let row = match lead {
+		0x81..=0x9F => usize::from(lead - 0x81),
+		0xE0..=0xEF => usize::from(lead - tables::SHIFT_JIS_LEAD_HIGH) + 0x1F,
+		_ => return None,
+	};
+	let column = match trail {
+		0x40..=0x7E => usize::from(trail - 0x40),
+		0x80..=0xFC => usize::from(trail - 0x41),
+		_ => return None,
+	};
+	match tables::SHIFT_JIS_DOUBLE[row * tables::SHIFT_JIS_TRAILS + column] {
+		0 => None,
+		code => char::from_u32(code.into()),
+	}
+}
+
+/// Create an error for invalid input.
+fn invalid_data(encoding: Encoding, message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{message} for encoding {encoding:?}"))
+}
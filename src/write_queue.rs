@@ -0,0 +1,752 @@
+//! Queued writes with priorities.
+//!
+//! A [`WriteQueue`] owns a background thread that writes queued data to a serial port.
+//! Each queued write is written as a whole with [`SerialPort::write_all()`],
+//! so writes from different threads never end up interleaved.
+//!
+//! Pending writes are handed to the kernel in order of their [`Priority`].
+//! Writes with the same priority are handed to the kernel in the order they were queued.
+//! This allows urgent frames (like emergency stop commands) to jump ahead of queued bulk data.
+//! Writes that have not been handed to the kernel yet can be cancelled or given a different priority.
+//!
+//! Writes queued with [`WriteQueue::queue_guarded()`] are surrounded by line silence,
+//! using [`SerialPort::write_guarded()`].
+//!
+//! Writes queued with [`WriteQueue::queue_tracked()`] return a [`WriteToken`],
+//! which can be used to wait until exactly that data has been handed to the kernel, or has been transmitted completely.
+//!
+//! For emergency commands that can not wait for the queue at all, [`WriteQueue::write_urgent()`] bypasses it:
+//! the data is handed to the kernel as soon as the write in progress is complete, before any pending write.
+//!
+//! Some links, like radio modems and PLC connections, drop the session when the line is idle for too long.
+//! With [`WriteQueue::set_keepalive()`], the queue transmits a keepalive message whenever no data was written for a while.
+
+use std::cmp::Reverse;
+use std::io::IoSlice;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::shutdown::Shutdown;
+use crate::{Priority, SerialPort, ThreadOptions};
+
+/// A queue of pending writes, serviced by a background thread.
+///
+/// See the [module documentation][self] for more information.
+///
+/// Dropping the queue waits for all pending writes to be handed to the kernel.
+pub struct WriteQueue {
+	/// The state shared with the background thread.
+	shared: Arc<Shared>,
+
+	/// The background thread.
+	thread: Option<JoinHandle<()>>,
+}
+
+/// An identifier for a queued write.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct WriteId(u64);
+
+/// A token to wait for the completion of a queued write.
+///
+/// Created by [`WriteQueue::queue_tracked()`].
+pub struct WriteToken {
+	/// The identifier of the write.
+	id: WriteId,
+
+	/// The number of bytes in the write.
+	len: usize,
+
+	/// The completion of the write, shared with the queue.
+	completion: Arc<Completion>,
+
+	/// The serial port the data is written to.
+	port: Arc<SerialPort>,
+}
+
+impl WriteQueue {
+	/// Create a new write queue for a serial port.
+	///
+	/// This spawns a background thread to perform the writes.
+	/// The serial port can still be used to read data while the queue exists.
+	pub fn new(port: Arc<SerialPort>) -> std::io::Result<Self> {
+		Self::with_thread_options(port, &ThreadOptions::default())
+	}
+
+	/// Create a new write queue for a serial port, with options for the background thread.
+	///
+	/// See [`ThreadOptions`] for more information.
+	pub fn with_thread_options(port: Arc<SerialPort>, thread_options: &ThreadOptions) -> std::io::Result<Self> {
+		let shared = Arc::new(Shared {
+			port,
+			state: Mutex::new(State::default()),
+			condvar: Condvar::new(),
+		});
+		let thread = thread_options.spawn("serial2-write-queue", {
+			let shared = shared.clone();
+			move || shared.run()
+		})?;
+		Ok(Self {
+			shared,
+			thread: Some(thread),
+		})
+	}
+
+	/// Get a reference to the serial port.
+	pub fn port(&self) -> &Arc<SerialPort> {
+		&self.shared.port
+	}
+
+	/// Queue data to be written with the given priority.
+	///
+	/// Returns an identifier that can be used to cancel the write or change the priority,
+	/// as long as the data has not been handed to the kernel yet.
+	pub fn queue(&self, data: impl Into<Vec<u8>>, priority: Priority) -> WriteId {
+		self.push(data.into(), priority, None, None)
+	}
+
+	/// Queue data to be written with the given priority, and get a token to wait for its completion.
+	///
+	/// The returned [`WriteToken`] resolves when exactly this data has been handed to the kernel,
+	/// or when the write failed or was cancelled.
+	/// Use [`WriteToken::wait_drained()`] to also wait for the data to be transmitted.
+	pub fn queue_tracked(&self, data: impl Into<Vec<u8>>, priority: Priority) -> WriteToken {
+		let data = data.into();
+		let len = data.len();
+		let completion = Arc::new(Completion::default());
+		let id = self.push(data, priority, None, Some(completion.clone()));
+		WriteToken {
+			id,
+			len,
+			completion,
+			port: self.shared.port.clone(),
+		}
+	}
+
+	/// Queue data to be written with a period of silence before and after it.
+	///
+	/// When the write is handed to the kernel, the queue waits for all previous data to be transmitted,
+	/// keeps the line silent for `pre_silence`, writes the data and keeps the line silent for `post_silence`.
+	/// Other queued writes are not written until the guard times have passed.
+	///
+	/// See [`SerialPort::write_guarded()`] for more information.
+	pub fn queue_guarded(
+		&self,
+		data: impl Into<Vec<u8>>,
+		pre_silence: Duration,
+		post_silence: Duration,
+		priority: Priority,
+	) -> WriteId {
+		self.push(data.into(), priority, Some((pre_silence, post_silence)), None)
+	}
+
+	/// Queue a frame consisting of multiple buffers to be written with the given priority.
+	///
+	/// The buffers are queued as a single write, so the frame is either queued completely or not at all,
+	/// and it is never interleaved with other queued writes.
+	pub fn queue_frame(&self, frame: &[IoSlice<'_>], priority: Priority) -> WriteId {
+		let mut data = Vec::with_capacity(frame.iter().map(|part| part.len()).sum());
+		for part in frame {
+			data.extend_from_slice(part);
+		}
+		self.queue(data, priority)
+	}
+
+	/// Cancel a pending write.
+	///
+	/// Returns `true` if the write was removed from the queue,
+	/// or `false` if it was already handed to the kernel.
+	pub fn cancel(&self, id: WriteId) -> bool {
+		let mut state = self.shared.lock();
+		let Some(index) = state.pending.iter().position(|entry| entry.id == id) else {
+			return false;
+		};
+		let entry = state.pending.remove(index);
+		drop(state);
+		self.shared.condvar.notify_all();
+		if let Some(completion) = entry.completion {
+			completion.complete(Err(std::io::Error::other("the write was cancelled")));
+		}
+		true
+	}
+
+	/// Change the priority of a pending write.
+	///
+	/// Among writes with the same priority, the write keeps its place based on the time it was originally queued.
+	///
+	/// Returns `true` if the priority was changed,
+	/// or `false` if the write was already handed to the kernel.
+	pub fn set_priority(&self, id: WriteId, priority: Priority) -> bool {
+		let mut state = self.shared.lock();
+		match state.pending.iter_mut().find(|entry| entry.id == id) {
+			Some(entry) => {
+				entry.priority = priority;
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Write urgent data, bypassing the queue.
+	///
+	/// This waits for the write that is currently being handed to the kernel to complete,
+	/// and then writes the data with [`SerialPort::write_urgent()`] before any pending write.
+	/// Pending writes stay in the queue and are written afterwards.
+	///
+	/// Unlike queued writes, this function blocks until the data is handed to the kernel and returns the result directly.
+	pub fn write_urgent(&self, data: &[u8]) -> std::io::Result<()> {
+		let mut state = self.shared.lock();
+		state.urgent += 1;
+		while state.writing {
+			state = self.shared.wait(state);
+		}
+		state.writing = true;
+		drop(state);
+
+		let result = self.shared.port.write_urgent(data);
+
+		let mut state = self.shared.lock();
+		state.writing = false;
+		state.urgent -= 1;
+		state.last_write = Some(Instant::now());
+		if result.is_ok() {
+			state.bytes_written += data.len() as u64;
+		}
+		drop(state);
+		self.shared.condvar.notify_all();
+		result
+	}
+
+	/// Transmit a keepalive message when no data was written for the given idle time.
+	///
+	/// The keepalive is written by the background thread like a queued write,
+	/// but only when no other writes are pending, so it never delays real traffic.
+	/// Only data written through the queue (including urgent writes) counts as traffic:
+	/// data written directly to the serial port does not suppress the keepalive.
+	///
+	/// The idle timer restarts when the keepalive is set.
+	/// This replaces any previously set keepalive.
+	pub fn set_keepalive(&self, idle_time: Duration, data: impl Into<Vec<u8>>) {
+		let mut state = self.shared.lock();
+		state.keepalive = Some(Keepalive {
+			idle_time,
+			data: data.into(),
+		});
+		state.last_write = Some(Instant::now());
+		drop(state);
+		self.shared.condvar.notify_all();
+	}
+
+	/// Stop transmitting keepalive messages.
+	pub fn clear_keepalive(&self) {
+		self.shared.lock().keepalive = None;
+		self.shared.condvar.notify_all();
+	}
+
+	/// Get the idle time and the message of the keepalive, if one is set.
+	pub fn get_keepalive(&self) -> Option<(Duration, Vec<u8>)> {
+		let state = self.shared.lock();
+		let keepalive = state.keepalive.as_ref()?;
+		Some((keepalive.idle_time, keepalive.data.clone()))
+	}
+
+	/// Get the number of keepalive messages that were handed to the kernel.
+	pub fn keepalives_sent(&self) -> u64 {
+		self.shared.lock().keepalives_sent
+	}
+
+	/// Get the total number of bytes in queued writes and urgent writes that were handed to the kernel successfully.
+	///
+	/// Keepalive messages are not included.
+	pub fn bytes_written(&self) -> u64 {
+		self.shared.lock().bytes_written
+	}
+
+	/// Get the number of writes that have not been handed to the kernel yet.
+	pub fn pending(&self) -> usize {
+		self.shared.lock().pending.len()
+	}
+
+	/// Get the total number of bytes in writes that have not been handed to the kernel yet.
+	pub fn pending_bytes(&self) -> usize {
+		self.shared.lock().pending.iter().map(|entry| entry.data.len()).sum()
+	}
+
+	/// Wait for all pending writes to be handed to the kernel.
+	///
+	/// If any write failed since the last call to `flush()`, the first error is returned.
+	/// Note that a failed write does not stop the queue: later writes are still attempted.
+	///
+	/// This does not wait for the data to be transmitted by the device.
+	/// Use [`SerialPort::flush()`] for that.
+	pub fn flush(&self) -> std::io::Result<()> {
+		let mut state = self.shared.lock();
+		while !state.pending.is_empty() || state.writing {
+			state = self.shared.wait(state);
+		}
+		match state.error.take() {
+			Some(error) => Err(error),
+			None => Ok(()),
+		}
+	}
+}
+
+impl WriteQueue {
+	/// Add a write to the queue and wake up the background thread.
+	fn push(
+		&self,
+		data: Vec<u8>,
+		priority: Priority,
+		guard: Option<(Duration, Duration)>,
+		completion: Option<Arc<Completion>>,
+	) -> WriteId {
+		let mut state = self.shared.lock();
+		let id = WriteId(state.next_id);
+		state.next_id += 1;
+		state.pending.push(Entry {
+			id,
+			priority,
+			data,
+			guard,
+			completion,
+		});
+		drop(state);
+		self.shared.condvar.notify_all();
+		id
+	}
+}
+
+impl WriteQueue {
+	/// Stop the background thread and wait for it to finish, until the deadline expires.
+	fn stop(&mut self, deadline: Option<Instant>) -> std::io::Result<()> {
+		self.shared.lock().closed = true;
+		self.shared.condvar.notify_all();
+		crate::shutdown::join_threads(self.thread.take(), deadline)
+	}
+}
+
+impl Drop for WriteQueue {
+	fn drop(&mut self) {
+		let _ = self.stop(None);
+	}
+}
+
+impl Shutdown for WriteQueue {
+	fn shutdown(mut self, timeout: Duration) -> std::io::Result<()> {
+		self.stop(Some(Instant::now() + timeout))
+	}
+}
+
+/// State shared between the queue and the background thread.
+struct Shared {
+	/// The serial port to write to.
+	port: Arc<SerialPort>,
+
+	/// The mutable state of the queue.
+	state: Mutex<State>,
+
+	/// Condition variable signalled whenever the state changes.
+	condvar: Condvar,
+}
+
+/// The mutable state of the queue.
+#[derive(Default)]
+struct State {
+	/// The pending writes.
+	pending: Vec<Entry>,
+
+	/// The identifier for the next write.
+	next_id: u64,
+
+	/// True while the background thread or an urgent write is writing data.
+	writing: bool,
+
+	/// The number of urgent writes waiting for the write in progress.
+	urgent: usize,
+
+	/// The keepalive to transmit when the line is idle.
+	keepalive: Option<Keepalive>,
+
+	/// The time the last write was handed to the kernel, or the keepalive was set.
+	last_write: Option<Instant>,
+
+	/// The number of keepalive messages that were handed to the kernel.
+	keepalives_sent: u64,
+
+	/// The number of bytes of queued and urgent writes that were handed to the kernel.
+	bytes_written: u64,
+
+	/// The first error that occurred since the last flush.
+	error: Option<std::io::Error>,
+
+	/// True if the queue is being dropped.
+	closed: bool,
+}
+
+/// A keepalive message.
+struct Keepalive {
+	/// The time without writes after which the message is transmitted.
+	idle_time: Duration,
+
+	/// The message.
+	data: Vec<u8>,
+}
+
+/// A pending write.
+struct Entry {
+	/// The identifier of the write.
+	id: WriteId,
+
+	/// The priority of the write.
+	priority: Priority,
+
+	/// The data to write.
+	data: Vec<u8>,
+
+	/// The silence to keep before and after the data, if any.
+	guard: Option<(Duration, Duration)>,
+
+	/// The completion to signal when the write is done, if the write is tracked.
+	completion: Option<Arc<Completion>>,
+}
+
+/// The completion of a tracked write.
+#[derive(Default)]
+struct Completion {
+	/// The result of the write, once it is done.
+	result: Mutex<Option<std::io::Result<()>>>,
+
+	/// Condition variable signalled when the write is done.
+	condvar: Condvar,
+}
+
+impl WriteToken {
+	/// Get the identifier of the write.
+	///
+	/// The identifier can be used with [`WriteQueue::cancel()`] and [`WriteQueue::set_priority()`].
+	pub fn id(&self) -> WriteId {
+		self.id
+	}
+
+	/// Get the number of bytes in the write.
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	/// Check if the write is empty.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Check if the write is done: handed to the kernel, failed or cancelled.
+	pub fn is_done(&self) -> bool {
+		self.completion.lock().is_some()
+	}
+
+	/// Wait until the data has been handed to the kernel.
+	///
+	/// Returns the result of the write, or an error of the type [`std::io::ErrorKind::Other`] if the write was cancelled.
+	/// If the write is not done before the timeout expires,
+	/// an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	///
+	/// This does not wait for the data to be transmitted by the device.
+	/// Use [`Self::wait_drained()`] for that.
+	pub fn wait(&self, timeout: Duration) -> std::io::Result<()> {
+		self.wait_until(Instant::now() + timeout)
+	}
+
+	/// Wait until the data has been handed to the kernel and the output buffer of the serial port is empty.
+	///
+	/// See [`SerialPort::wait_tx_empty()`] for the details and limitations of detecting an empty output buffer.
+	/// Note that the output buffer may also contain data written after this write,
+	/// in which case this also waits for that data to be transmitted.
+	pub fn wait_drained(&self, timeout: Duration) -> std::io::Result<()> {
+		let deadline = Instant::now() + timeout;
+		self.wait_until(deadline)?;
+		self.port.wait_tx_empty(deadline.saturating_duration_since(Instant::now()))
+	}
+
+	/// Wait until the write is done or the deadline expires.
+	fn wait_until(&self, deadline: Instant) -> std::io::Result<()> {
+		let mut result = self.completion.lock();
+		loop {
+			match &*result {
+				Some(Ok(())) => return Ok(()),
+				Some(Err(e)) => return Err(std::io::Error::new(e.kind(), e.to_string())),
+				None => (),
+			}
+			let now = Instant::now();
+			if now >= deadline {
+				return Err(std::io::ErrorKind::TimedOut.into());
+			}
+			result = match self.completion.condvar.wait_timeout(result, deadline - now) {
+				Ok((guard, _timeout)) => guard,
+				Err(e) => e.into_inner().0,
+			};
+		}
+	}
+}
+
+impl std::fmt::Debug for WriteToken {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("WriteToken")
+			.field("id", &self.id)
+			.field("len", &self.len)
+			.field("done", &self.is_done())
+			.finish()
+	}
+}
+
+impl Completion {
+	/// Lock the result, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, Option<std::io::Result<()>>> {
+		self.result.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Store the result of the write and wake up all waiters.
+	fn complete(&self, result: std::io::Result<()>) {
+		*self.lock() = Some(result);
+		self.condvar.notify_all();
+	}
+}
+
+impl Shared {
+	/// Lock the state, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, State> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Wait for the state to change, ignoring poisoning.
+	fn wait<'a>(&self, guard: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+		self.condvar.wait(guard).unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Wait for the state to change or for a timeout, ignoring poisoning.
+	fn wait_timeout<'a>(&self, guard: MutexGuard<'a, State>, timeout: Duration) -> MutexGuard<'a, State> {
+		match self.condvar.wait_timeout(guard, timeout) {
+			Ok((guard, _timeout)) => guard,
+			Err(e) => e.into_inner().0,
+		}
+	}
+
+	/// Run the background thread.
+	fn run(&self) {
+		loop {
+			let mut state = self.lock();
+			let (data, guard, completion, is_keepalive) = loop {
+				if state.urgent == 0 && !state.writing {
+					if let Some(entry) = state.take_next() {
+						break (entry.data, entry.guard, entry.completion, false);
+					}
+					if state.closed {
+						return;
+					}
+					if state.keepalive_due() == Some(Duration::ZERO) {
+						let data = state.keepalive.as_ref().map(|keepalive| keepalive.data.clone()).unwrap_or_default();
+						break (data, None, None, true);
+					}
+				}
+				state = match state.keepalive_due() {
+					Some(timeout) if !timeout.is_zero() => self.wait_timeout(state, timeout),
+					_ => self.wait(state),
+				};
+			};
+			state.writing = true;
+			drop(state);
+
+			let result = match guard {
+				Some((pre_silence, post_silence)) => self.port.write_guarded(&data, pre_silence, post_silence),
+				None => self.port.write_all(&data),
+			};
+
+			let mut state = self.lock();
+			state.writing = false;
+			state.last_write = Some(Instant::now());
+			let completion_result = match result {
+				Ok(()) if is_keepalive => {
+					state.keepalives_sent += 1;
+					Ok(())
+				},
+				Ok(()) => {
+					state.bytes_written += data.len() as u64;
+					Ok(())
+				},
+				Err(e) => {
+					let copy = std::io::Error::new(e.kind(), e.to_string());
+					state.error.get_or_insert(e);
+					Err(copy)
+				},
+			};
+			drop(state);
+			self.condvar.notify_all();
+			if let Some(completion) = completion {
+				completion.complete(completion_result);
+			}
+		}
+	}
+}
+
+impl State {
+	/// Get the time until the keepalive must be transmitted, or `None` if no keepalive is set.
+	fn keepalive_due(&self) -> Option<Duration> {
+		let keepalive = self.keepalive.as_ref()?;
+		let idle = self.last_write.map_or(Duration::MAX, |last_write| last_write.elapsed());
+		Some(keepalive.idle_time.saturating_sub(idle))
+	}
+
+	/// Remove the pending write that should be handed to the kernel next.
+	fn take_next(&mut self) -> Option<Entry> {
+		let (index, _) = self
+			.pending
+			.iter()
+			.enumerate()
+			.max_by_key(|(_, entry)| (entry.priority, Reverse(entry.id)))?;
+		Some(self.pending.remove(index))
+	}
+}
+
+/// A cloneable handle to write frames from many threads, served in the order they arrive.
+///
+/// Concurrent calls to [`SerialPort::write_frame()`] are serialized, but not fairly:
+/// a thread that writes frames in a loop can starve the others.
+/// A [`SharedWriter`] hands out turns first come, first served,
+/// and each call can give up if it did not get its turn before a deadline.
+///
+/// Unlike a [`WriteQueue`], the frame is written by the calling thread itself, so the call returns after the frame is written.
+/// All clones share the same turn order.
+///
+/// Note that this does not serialize writes that bypass the [`SharedWriter`], except for other calls to [`SerialPort::write_frame()`].
+///
+/// This crate has no async runtime integration, so a [`SharedWriter`] blocks the calling thread while it waits for its turn.
+/// Async code should call it from a task that is allowed to block, like one started with `tokio::task::spawn_blocking()`.
+#[derive(Clone)]
+pub struct SharedWriter {
+	/// The state shared between all clones.
+	shared: Arc<WriterShared>,
+}
+
+impl SharedWriter {
+	/// Create a new shared writer for a serial port.
+	pub fn new(port: Arc<SerialPort>) -> Self {
+		Self {
+			shared: Arc::new(WriterShared {
+				port,
+				turns: Mutex::new(Turns::default()),
+				condvar: Condvar::new(),
+			}),
+		}
+	}
+
+	/// Get the serial port used by the writer.
+	pub fn port(&self) -> &Arc<SerialPort> {
+		&self.shared.port
+	}
+
+	/// Get the number of calls that are waiting for their turn or are writing a frame.
+	pub fn waiting(&self) -> usize {
+		let turns = self.shared.lock();
+		(turns.next_ticket - turns.serving) as usize - turns.abandoned.len()
+	}
+
+	/// Wait for the turn of the calling thread and write a frame.
+	///
+	/// The frame is written with [`SerialPort::write_frame()`], so it is never interleaved with other frames.
+	pub fn write_frame(&self, frame: &[IoSlice<'_>]) -> std::io::Result<()> {
+		self.write_frame_with_deadline(frame, None)
+	}
+
+	/// Wait for the turn of the calling thread until a deadline, and write a frame.
+	///
+	/// If the deadline has passed before the turn of the calling thread came, nothing is written,
+	/// and an error of the type [`std::io::ErrorKind::TimedOut`] is returned.
+	/// Once the turn has come, the frame is written completely, and the write timeout of the serial port applies.
+	pub fn write_frame_before(&self, frame: &[IoSlice<'_>], deadline: Instant) -> std::io::Result<()> {
+		self.write_frame_with_deadline(frame, Some(deadline))
+	}
+
+	/// Wait for a turn with an optional deadline, and write a frame.
+	fn write_frame_with_deadline(&self, frame: &[IoSlice<'_>], deadline: Option<Instant>) -> std::io::Result<()> {
+		if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+			return Err(turn_timed_out());
+		}
+		let mut turns = self.shared.lock();
+		let ticket = turns.next_ticket;
+		turns.next_ticket += 1;
+		while turns.serving != ticket {
+			match deadline {
+				None => turns = self.shared.condvar.wait(turns).unwrap_or_else(PoisonError::into_inner),
+				Some(deadline) => {
+					let now = Instant::now();
+					if now >= deadline {
+						turns.abandoned.push(ticket);
+						return Err(turn_timed_out());
+					}
+					turns = self
+						.shared
+						.condvar
+						.wait_timeout(turns, deadline - now)
+						.unwrap_or_else(PoisonError::into_inner)
+						.0;
+				},
+			}
+		}
+		drop(turns);
+
+		// Pass the turn on when done, even if writing panics.
+		let _turn = Turn(&self.shared);
+		self.shared.port.write_frame(frame)
+	}
+}
+
+/// Create the error for a call to [`SharedWriter::write_frame_before()`] that did not get its turn in time.
+fn turn_timed_out() -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out waiting for the turn to write a frame")
+}
+
+/// State shared between clones of a [`SharedWriter`].
+struct WriterShared {
+	/// The serial port to write to.
+	port: Arc<SerialPort>,
+
+	/// The turn order.
+	turns: Mutex<Turns>,
+
+	/// Condition variable signalled whenever a turn ends.
+	condvar: Condvar,
+}
+
+impl WriterShared {
+	/// Lock the turn order, ignoring poisoning.
+	fn lock(&self) -> MutexGuard<'_, Turns> {
+		self.turns.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+/// The turn order of a [`SharedWriter`].
+#[derive(Default)]
+struct Turns {
+	/// The ticket for the next call.
+	next_ticket: u64,
+
+	/// The ticket of the call that may write now.
+	serving: u64,
+
+	/// Tickets of calls that gave up waiting before their turn.
+	abandoned: Vec<u64>,
+}
+
+/// The turn of a call to [`SharedWriter::write_frame()`], passed on to the next ticket when dropped.
+struct Turn<'a>(&'a WriterShared);
+
+impl Drop for Turn<'_> {
+	fn drop(&mut self) {
+		let mut turns = self.0.lock();
+		turns.serving += 1;
+		while let Some(index) = turns.abandoned.iter().position(|&ticket| ticket == turns.serving) {
+			turns.abandoned.swap_remove(index);
+			turns.serving += 1;
+		}
+		drop(turns);
+		self.0.condvar.notify_all();
+	}
+}
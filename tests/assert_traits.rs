@@ -19,3 +19,13 @@ fn assert_write_is_implemented() {
 	assert_write::<&mut SerialPort>();
 	assert_write::<&mut &SerialPort>();
 }
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn assert_split_handles_are_send() {
+	assert_send::<serial2::ControlHandle>();
+	assert_send::<serial2::IoHandle>();
+	assert_read::<serial2::IoHandle>();
+	assert_write::<serial2::IoHandle>();
+}
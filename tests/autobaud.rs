@@ -0,0 +1,62 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::autobaud::AutoBaud;
+use serial2::SerialPort;
+use std::time::Duration;
+
+#[test]
+fn detect_sync_byte() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_millis(750)));
+	let mut autobaud = AutoBaud::new([19200, 115200]);
+	autobaud.set_dwell_time(Duration::from_millis(50));
+
+	std::thread::scope(|scope| {
+		scope.spawn(|| {
+			for _ in 0..20 {
+				assert!(let Ok(()) = b.write_all(&[0xFF, 0x55, 0x01]));
+				std::thread::sleep(Duration::from_millis(10));
+			}
+		});
+		let_assert!(Ok(baud_rate) = autobaud.detect(&mut a, Duration::from_secs(1)));
+		assert!(baud_rate == 19200);
+		let_assert!(Ok(settings) = a.get_configuration());
+		assert!(let Ok(19200) = settings.get_baud_rate());
+
+		// Data after the sync byte is left for the caller.
+		let mut byte = [0];
+		assert!(let Ok(()) = a.read_exact(&mut byte));
+		assert!(byte == [0x01]);
+	});
+	let_assert!(Ok(timeout) = a.get_read_timeout());
+	assert!(timeout == Duration::from_millis(750));
+}
+
+#[test]
+fn detect_break_and_sync_byte() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	let_assert!(Ok(mut settings) = a.get_configuration());
+	assert!(let Ok(()) = settings.set_baud_rate(9600));
+	assert!(let Ok(()) = a.set_configuration(&settings));
+	let mut autobaud = AutoBaud::new([19200]);
+	autobaud.set_expect_break(true);
+	autobaud.set_dwell_time(Duration::from_millis(50));
+
+	// Without a break, the sync byte is not accepted and the original baud rate is restored.
+	assert!(let Ok(()) = b.write_all(&[0x55, 0x55]));
+	let_assert!(Err(e) = autobaud.detect(&mut a, Duration::from_millis(200)));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+	let_assert!(Ok(settings) = a.get_configuration());
+	assert!(let Ok(9600) = settings.get_baud_rate());
+
+	std::thread::scope(|scope| {
+		scope.spawn(|| {
+			for _ in 0..20 {
+				assert!(let Ok(()) = b.write_all(&[0x00, 0x55]));
+				std::thread::sleep(Duration::from_millis(10));
+			}
+		});
+		let_assert!(Ok(19200) = autobaud.detect(&mut a, Duration::from_secs(1)));
+	});
+}
@@ -0,0 +1,132 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::framing::{FrameReader, SlipFramer};
+use serial2::protocol::esp::EspLoader;
+use serial2::protocol::image::Chunk;
+use serial2::protocol::stm32::Stm32Bootloader;
+use serial2::SerialPort;
+use std::time::Duration;
+
+/// Read exactly `len` bytes from a serial port.
+fn read_exact(port: &SerialPort, len: usize) -> Vec<u8> {
+	let mut buffer = vec![0; len];
+	assert!(let Ok(()) = port.read_exact(&mut buffer));
+	buffer
+}
+
+#[test]
+fn stm32() {
+	let_assert!(Ok((a, mut b)) = SerialPort::pair());
+	assert!(let Ok(()) = b.set_read_timeout(Duration::from_secs(1)));
+	let device = std::thread::spawn(move || {
+		assert!(read_exact(&b, 1) == [0x7F]);
+		assert!(let Ok(()) = b.write_all(&[0x79]));
+
+		// Get
+		assert!(read_exact(&b, 2) == [0x00, 0xFF]);
+		assert!(let Ok(()) = b.write_all(&[0x79, 3, 0x31, 0x00, 0x11, 0x44, 0x79]));
+
+		// Get ID
+		assert!(read_exact(&b, 2) == [0x02, 0xFD]);
+		assert!(let Ok(()) = b.write_all(&[0x79, 1, 0x04, 0x10, 0x79]));
+
+		// Write memory
+		assert!(read_exact(&b, 2) == [0x31, 0xCE]);
+		assert!(let Ok(()) = b.write_all(&[0x79]));
+		assert!(read_exact(&b, 5) == [0x08, 0x00, 0x00, 0x00, 0x08]);
+		assert!(let Ok(()) = b.write_all(&[0x79]));
+		assert!(read_exact(&b, 6) == [3, 1, 2, 3, 4, 0x07]);
+		assert!(let Ok(()) = b.write_all(&[0x79]));
+
+		// Extended erase of all pages, rejected.
+		assert!(read_exact(&b, 2) == [0x44, 0xBB]);
+		assert!(let Ok(()) = b.write_all(&[0x79]));
+		assert!(read_exact(&b, 3) == [0xFF, 0xFF, 0x00]);
+		assert!(let Ok(()) = b.write_all(&[0x1F]));
+	});
+
+	let_assert!(Ok(mut bootloader) = Stm32Bootloader::new(a));
+	assert!(let Ok(()) = bootloader.connect());
+	let_assert!(Ok(info) = bootloader.get());
+	assert!(info.version() == 0x31);
+	assert!(info.commands() == [0x00, 0x11, 0x44]);
+	assert!(let Ok(0x0410) = bootloader.get_id());
+	assert!(let Err(_) = bootloader.write_memory(0x0800_0000, &[1, 2, 3]));
+	assert!(let Ok(()) = bootloader.write_memory(0x0800_0000, &[1, 2, 3, 4]));
+	assert!(let Err(_) = bootloader.erase_all(Duration::from_secs(1)));
+	device.join().unwrap();
+}
+
+#[test]
+fn esp() {
+	let_assert!(Ok((a, mut b)) = SerialPort::pair());
+	assert!(let Ok(()) = b.set_read_timeout(Duration::from_secs(1)));
+	let device = std::thread::spawn(move || {
+		let mut reader = FrameReader::new(b, SlipFramer);
+		let respond = |port: &SerialPort, command: u8, value: u32, status: u8| {
+			let mut response = vec![0x01, command, 2, 0];
+			response.extend_from_slice(&value.to_le_bytes());
+			response.extend_from_slice(&[status, 0x05]);
+			assert!(let Ok(()) = port.write_all(&SlipFramer::encode(&response)));
+		};
+
+		// Sync, with some boot messages first.
+		let_assert!(Ok(packet) = reader.read_frame());
+		assert!(packet[..4] == [0x00, 0x08, 36, 0]);
+		assert!(let Ok(()) = reader.port().write_all(b"ets Jan  8 2013\r\n"));
+		respond(reader.port(), 0x08, 0, 0);
+		respond(reader.port(), 0x08, 0, 0);
+
+		// Read register
+		let_assert!(Ok(packet) = reader.read_frame());
+		assert!(packet == [0x00, 0x0A, 4, 0, 0, 0, 0, 0, 0x00, 0x0C, 0x00, 0x60]);
+		respond(reader.port(), 0x0A, 0xC0DB_0001, 0);
+
+		// Flash begin and data.
+		let_assert!(Ok(packet) = reader.read_frame());
+		assert!(packet[..2] == [0x00, 0x02]);
+		assert!(packet[8..] == [3, 0, 0, 0, 1, 0, 0, 0, 0, 4, 0, 0, 0, 0x10, 0, 0]);
+		respond(reader.port(), 0x02, 0, 0);
+		let_assert!(Ok(packet) = reader.read_frame());
+		assert!(packet[..2] == [0x00, 0x03]);
+		let checksum = packet[24..].iter().fold(0xEF, |checksum, &byte| checksum ^ byte);
+		assert!(packet[4..8] == [checksum, 0, 0, 0]);
+		assert!(packet[24..27] == [0xC0, 0xDB, 0x01]);
+		assert!(packet.len() == 24 + 0x400);
+		respond(reader.port(), 0x03, 0, 1);
+	});
+
+	let_assert!(Ok(mut loader) = EspLoader::new(a));
+	assert!(let Ok(()) = loader.sync());
+	assert!(let Ok(0xC0DB_0001) = loader.read_reg(0x6000_0C00));
+	let mut progress = Vec::new();
+	let_assert!(Err(e) = loader.write_flash(0x1000, &[0xC0, 0xDB, 0x01], |written, total| {
+		progress.push((written, total))
+	}));
+	assert!(e.to_string().contains("0x05"));
+	assert!(progress.is_empty());
+	device.join().unwrap();
+}
+
+#[test]
+fn stm32_write_image() {
+	let_assert!(Ok((a, mut b)) = SerialPort::pair());
+	assert!(let Ok(()) = b.set_read_timeout(Duration::from_secs(1)));
+	let device = std::thread::spawn(move || {
+		assert!(read_exact(&b, 2) == [0x31, 0xCE]);
+		assert!(let Ok(()) = b.write_all(&[0x79]));
+		assert!(read_exact(&b, 5) == [0x08, 0x00, 0x01, 0x00, 0x09]);
+		assert!(let Ok(()) = b.write_all(&[0x79]));
+		let packet = read_exact(&b, 10);
+		assert!(packet[..9] == [7, 1, 2, 3, 4, 5, 6, 0xFF, 0xFF]);
+		assert!(let Ok(()) = b.write_all(&[0x79]));
+	});
+
+	let_assert!(Ok(mut bootloader) = Stm32Bootloader::new(a));
+	let mut progress = Vec::new();
+	let chunks = [Ok(Chunk::new(0x0800_0100, vec![1, 2, 3, 4, 5, 6]))];
+	assert!(let Ok(6) = bootloader.write_image(chunks, |written| progress.push(written)));
+	assert!(progress == [6]);
+	device.join().unwrap();
+}
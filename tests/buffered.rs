@@ -0,0 +1,193 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::buffered::{BufferedReader, BufferedSerialPort, OverrunPolicy};
+use serial2::transcode::{DecodeMode, Encoding};
+use serial2::SerialPort;
+use std::time::Duration;
+
+#[test]
+fn read_buffered() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(reader) = BufferedReader::new(a));
+
+	assert!(let Ok(()) = b.write_all(b"Hello!"));
+	let mut buffer = [0; 6];
+	let mut read = 0;
+	while read < buffer.len() {
+		let_assert!(Ok(n) = reader.read(&mut buffer[read..]));
+		read += n;
+	}
+	assert!(&buffer == b"Hello!");
+}
+
+#[test]
+fn read_until_sentinel() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(mut reader) = BufferedReader::new(a));
+	reader.set_read_timeout(Duration::from_millis(200));
+
+	assert!(let Ok(()) = b.write_all(b"partial"));
+	let mut line = Vec::new();
+	let_assert!(Err(e) = reader.read_until(b'\n', &mut line));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+	assert!(line.is_empty());
+
+	assert!(let Ok(()) = b.write_all(b" line\nnext"));
+	assert!(let Ok(13) = reader.read_until(b'\n', &mut line));
+	assert!(line == b"partial line\n");
+}
+
+#[test]
+fn read_line_transcoded() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(mut reader) = BufferedReader::new(a));
+	reader.set_read_timeout(Duration::from_millis(500));
+
+	assert!(let Ok(()) = b.write_all(b"T=25\xF8C\nbad \xFF\n"));
+	let mut line = String::new();
+	assert!(let Ok(7) = reader.read_line(Encoding::Cp437, DecodeMode::Strict, &mut line));
+	assert!(line == "T=25\u{B0}C\n");
+	let_assert!(Err(e) = reader.read_line(Encoding::Utf8, DecodeMode::Strict, &mut line));
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+	assert!(line == "T=25\u{B0}C\n");
+}
+
+#[test]
+fn read_watermark() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(mut reader) = BufferedReader::new(a));
+	reader.set_read_timeout(Duration::from_secs(1));
+	reader.set_read_watermark(8, Duration::from_millis(100));
+
+	// The watermark is reached before the delay expires.
+	assert!(let Ok(()) = b.write_all(b"Hello"));
+	std::thread::sleep(Duration::from_millis(10));
+	assert!(let Ok(()) = b.write_all(b" world!"));
+	let mut buffer = [0; 32];
+	let_assert!(Ok(read) = reader.read(&mut buffer));
+	assert!(read >= 8);
+
+	// The delay expires before the watermark is reached.
+	let mut rest = buffer[..read].to_vec();
+	while rest.len() < 12 {
+		let_assert!(Ok(read) = reader.read(&mut buffer));
+		rest.extend_from_slice(&buffer[..read]);
+	}
+	assert!(rest == b"Hello world!");
+	assert!(let Ok(()) = b.write_all(b"Hi"));
+	let start = std::time::Instant::now();
+	assert!(let Ok(2) = reader.read(&mut buffer));
+	assert!(start.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+fn overrun_drop_oldest() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(mut reader) = BufferedReader::with_capacity(a, 8));
+	reader.set_overrun_policy(OverrunPolicy::DropOldest);
+	let (sender, receiver) = std::sync::mpsc::channel();
+	reader.set_high_watermark_callback(4, move |level| sender.send(level).unwrap());
+
+	assert!(let Ok(()) = b.write_all(b"0123456789ABCDEF"));
+	let start = std::time::Instant::now();
+	while reader.dropped_bytes() < 8 && start.elapsed() < Duration::from_secs(1) {
+		std::thread::sleep(Duration::from_millis(5));
+	}
+	assert!(reader.dropped_bytes() == 8);
+	let_assert!(Ok(level) = receiver.recv_timeout(Duration::from_secs(1)));
+	assert!(level > 4);
+
+	let mut buffer = [0; 8];
+	assert!(let Ok(8) = reader.read(&mut buffer));
+	assert!(&buffer == b"89ABCDEF");
+}
+
+#[test]
+fn byte_pattern_triggers() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(mut reader) = BufferedReader::new(a));
+	let (sender, events) = std::sync::mpsc::channel();
+	let error = reader.add_trigger("ERROR", {
+		let sender = sender.clone();
+		move || sender.send("error").unwrap()
+	});
+	reader.add_trigger([0x7E], move || sender.send("flag").unwrap());
+
+	// The pattern is detected even when it is split over multiple writes, without reading from the buffer.
+	assert!(let Ok(()) = b.write_all(b"boot ERR"));
+	std::thread::sleep(Duration::from_millis(50));
+	assert!(let Ok(()) = b.write_all(b"OR\x7E"));
+	assert!(let Ok("error") = events.recv_timeout(Duration::from_secs(1)));
+	assert!(let Ok("flag") = events.recv_timeout(Duration::from_secs(1)));
+
+	assert!(reader.remove_trigger(error));
+	assert!(!reader.remove_trigger(error));
+	assert!(let Ok(()) = b.write_all(b"ERROR\x7E"));
+	assert!(let Ok("flag") = events.recv_timeout(Duration::from_secs(1)));
+
+	// All data is still available to the consumer.
+	let mut buffer = Vec::new();
+	let_assert!(Ok(_) = reader.read_until(0x7E, &mut buffer));
+	assert!(buffer == b"boot ERROR\x7E");
+}
+
+#[test]
+fn adaptive_capacity() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(mut reader) = BufferedReader::with_capacity(a, 16));
+	let (sender, resizes) = std::sync::mpsc::channel();
+	reader.set_resize_callback(move |old, new| sender.send((old, new)).unwrap());
+	reader.set_adaptive_capacity(16, 1024);
+	assert!(reader.get_adaptive_capacity() == Some((16, 1024)));
+
+	// The buffer grows when the consumer falls behind.
+	assert!(let Ok(()) = b.write_all(&[0x55; 200]));
+	assert!(let Ok((16, 32)) = resizes.recv_timeout(Duration::from_secs(1)));
+	std::thread::sleep(Duration::from_millis(100));
+	assert!(reader.available() == 200);
+	assert!(reader.capacity() == 512);
+	assert!(reader.dropped_bytes() == 0);
+
+	// The buffer shrinks again when the data rate drops.
+	let mut buffer = [0; 200];
+	assert!(let Ok(()) = std::io::Read::read_exact(&mut &reader, &mut buffer));
+	let shrink = loop {
+		let_assert!(Ok((old, new)) = resizes.recv_timeout(Duration::from_secs(3)));
+		if new < old {
+			break (old, new);
+		}
+	};
+	assert!(shrink == (512, 256));
+}
+
+#[test]
+fn buffered_serial_port() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_secs(1)));
+	let_assert!(Ok(original_timeout) = b.get_write_timeout());
+	let_assert!(Ok(mut port) = BufferedSerialPort::new(b));
+
+	// Queue more data than fits in the kernel buffers: the rest stays queued without blocking.
+	let data: Vec<u8> = (0..1 << 20).map(|i| i as u8).collect();
+	assert!(let Ok(()) = port.queue_write(&data));
+	assert!(port.wants_write());
+	assert!(port.pending_bytes() > 0);
+
+	let mut received = Vec::with_capacity(data.len());
+	let mut buffer = [0; 4096];
+	while received.len() < data.len() {
+		let_assert!(Ok(read) = a.read(&mut buffer));
+		received.extend_from_slice(&buffer[..read]);
+		assert!(let Ok(_) = port.write_pending());
+	}
+	assert!(received == data);
+	assert!(!port.wants_write());
+
+	assert!(let Ok(()) = port.queue_write(b"left behind"));
+	assert!(port.discard_pending() == 0);
+	let_assert!(Ok((b, pending)) = port.into_inner());
+	assert!(pending.is_empty());
+	let_assert!(Ok(timeout) = b.get_write_timeout());
+	assert!(timeout == original_timeout);
+}
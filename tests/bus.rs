@@ -0,0 +1,127 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::bus::{BusConfig, BusScheduler, EchoMismatch};
+use serial2::{Priority, SerialPort};
+use std::time::Duration;
+
+#[test]
+fn transaction_request_response() {
+	let_assert!(Ok((master, device)) = SerialPort::pair());
+	let_assert!(Ok(bus) = BusScheduler::new(master, BusConfig::new()));
+
+	let device = std::thread::spawn(move || {
+		let mut request = [0; 4];
+		assert!(let Ok(()) = device.read_exact(&mut request));
+		assert!(&request == b"ping");
+		assert!(let Ok(()) = device.write_all(b"pong"));
+	});
+
+	let response = bus.transaction(Priority::Normal, |transaction| {
+		transaction.write_all(b"ping")?;
+		let mut response = [0; 4];
+		transaction.read_exact(&mut response)?;
+		Ok(response)
+	});
+	assert!(let Ok(b"pong") = response.as_ref());
+	device.join().unwrap();
+}
+
+#[test]
+fn transaction_timeout() {
+	let_assert!(Ok((mut master, _device)) = SerialPort::pair());
+	assert!(let Ok(()) = master.set_read_timeout(Duration::from_secs(3)));
+	assert!(let Ok(()) = master.set_write_timeout(Duration::from_secs(4)));
+	let mut config = BusConfig::new();
+	config.set_transaction_timeout(Duration::from_millis(50));
+	let_assert!(Ok(bus) = BusScheduler::new(master, config));
+
+	let_assert!(Err(e) = bus.transaction(Priority::Normal, |transaction| {
+		transaction.write_all(b"ping")?;
+		let mut response = [0; 4];
+		transaction.read_exact(&mut response)
+	}));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+
+	// The timeouts of the serial port are restored after the transaction.
+	let master = bus.into_inner();
+	let_assert!(Ok(timeout) = master.get_read_timeout());
+	assert!(timeout == Duration::from_secs(3));
+	let_assert!(Ok(timeout) = master.get_write_timeout());
+	assert!(timeout == Duration::from_secs(4));
+}
+
+/// Simulate a two-wire bus by echoing received bytes, optionally corrupting the first byte.
+fn spawn_echo(device: SerialPort, corrupt: bool) -> std::thread::JoinHandle<()> {
+	std::thread::spawn(move || {
+		let mut request = [0; 4];
+		assert!(let Ok(()) = device.read_exact(&mut request));
+		if corrupt {
+			request[0] ^= 0xFF;
+		}
+		assert!(let Ok(()) = device.write_all(&request));
+		assert!(let Ok(()) = device.write_all(b"pong"));
+	})
+}
+
+#[test]
+fn echo_cancellation() {
+	let_assert!(Ok((master, device)) = SerialPort::pair());
+	let mut config = BusConfig::new();
+	config.set_echo_cancellation(true);
+	let_assert!(Ok(bus) = BusScheduler::new(master, config));
+
+	let device = spawn_echo(device, false);
+	let response = bus.transaction(Priority::Normal, |transaction| {
+		transaction.write_all(b"ping")?;
+		let mut response = [0; 4];
+		transaction.read_exact(&mut response)?;
+		Ok(response)
+	});
+	assert!(let Ok(b"pong") = response.as_ref());
+	device.join().unwrap();
+}
+
+#[test]
+fn echo_mismatch() {
+	let_assert!(Ok((master, device)) = SerialPort::pair());
+	let mut config = BusConfig::new();
+	config.set_echo_cancellation(true);
+	let_assert!(Ok(bus) = BusScheduler::new(master, config));
+
+	let device = spawn_echo(device, true);
+	let_assert!(Err(e) = bus.transaction(Priority::Normal, |transaction| transaction.write_all(b"ping")));
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+	let_assert!(Some(mismatch) = e.get_ref().and_then(|e| e.downcast_ref::<EchoMismatch>()));
+	assert!(mismatch.position() == 0);
+	assert!(mismatch.sent() == b"ping");
+	device.join().unwrap();
+}
+
+#[test]
+fn collision_retransmit() {
+	let_assert!(Ok((master, device)) = SerialPort::pair());
+	let mut config = BusConfig::new();
+	config.set_echo_cancellation(true);
+	config.set_collision_retries(2);
+	config.set_collision_backoff(Duration::from_millis(5));
+	let_assert!(Ok(bus) = BusScheduler::new(master, config));
+
+	let device = std::thread::spawn(move || {
+		let mut request = [0; 4];
+		assert!(let Ok(()) = device.read_exact(&mut request));
+		assert!(let Ok(()) = device.write_all(b"p\0ng"));
+		assert!(let Ok(()) = device.read_exact(&mut request));
+		assert!(&request == b"ping");
+		assert!(let Ok(()) = device.write_all(b"pingpong"));
+	});
+
+	let response = bus.transaction(Priority::Normal, |transaction| {
+		transaction.write_all(b"ping")?;
+		let mut response = [0; 4];
+		transaction.read_exact(&mut response)?;
+		Ok(response)
+	});
+	assert!(let Ok(b"pong") = response.as_ref());
+	device.join().unwrap();
+}
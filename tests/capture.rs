@@ -0,0 +1,201 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::capture::{
+	Annotation, AsciicastWriter, CaptureMerger, CaptureSink, Direction, MergedEvent, PcapngWriter, Tap,
+};
+use serial2::SerialPort;
+use std::io::Read;
+use std::time::Duration;
+
+/// Split a pcapng file into (block type, block body) pairs.
+fn parse_blocks(mut data: &[u8]) -> Vec<(u32, Vec<u8>)> {
+	let mut blocks = Vec::new();
+	while !data.is_empty() {
+		let block_type = u32::from_le_bytes(data[0..4].try_into().unwrap());
+		let len = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+		assert!(len % 4 == 0);
+		assert!(data[len - 4..len] == data[4..8]);
+		blocks.push((block_type, data[8..len - 4].to_vec()));
+		data = &data[len..];
+	}
+	blocks
+}
+
+#[test]
+fn capture_pcapng() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(writer) = PcapngWriter::new(Vec::new()));
+	let tap = Tap::new(a, writer);
+
+	assert!(let Ok(()) = tap.write_all(b"ping"));
+	let mut buffer = [0; 4];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(let Ok(()) = b.write_all(b"pong!"));
+	let mut buffer = [0; 5];
+	assert!(let Ok(5) = tap.read(&mut buffer));
+	assert!(tap.take_capture_error().is_none());
+
+	let (_port, writer) = tap.into_inner();
+	let blocks = parse_blocks(writer.get_ref());
+	assert!(blocks.len() == 5);
+	assert!(blocks[0].0 == 0x0A0D0D0A);
+	assert!(blocks[1].0 == 1);
+	assert!(blocks[2].0 == 1);
+
+	let (block_type, body) = &blocks[3];
+	assert!(*block_type == 6);
+	assert!(body[0..4] == 1u32.to_le_bytes());
+	assert!(body[12..16] == 4u32.to_le_bytes());
+	assert!(&body[20..24] == b"ping");
+
+	let (block_type, body) = &blocks[4];
+	assert!(*block_type == 6);
+	assert!(body[0..4] == 0u32.to_le_bytes());
+	assert!(body[12..16] == 5u32.to_le_bytes());
+	assert!(&body[20..25] == b"pong!");
+	assert!(body.len() == 28);
+}
+
+#[test]
+fn capture_annotations() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(writer) = PcapngWriter::new(Vec::new()));
+	let tap = Tap::new(a, writer);
+	tap.set_analyzer(|direction, offset: u64, data: &[u8], annotations: &mut Vec<Annotation>| {
+		if let Some(end) = data.iter().position(|&byte| byte == b'\n') {
+			annotations.push(Annotation::new(direction, 0..offset + end as u64 + 1, "line"));
+		}
+	});
+
+	assert!(let Ok(()) = tap.write_all(b"ab"));
+	assert!(let Ok(()) = tap.write_all(b"c\n"));
+	let mut buffer = [0; 4];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+
+	let (_port, writer) = tap.into_inner();
+	let blocks = parse_blocks(writer.get_ref());
+	assert!(blocks.len() == 6);
+	let (block_type, body) = &blocks[5];
+	assert!(*block_type == 6);
+	assert!(body[0..4] == 1u32.to_le_bytes());
+	assert!(body[12..16] == 0u32.to_le_bytes());
+	assert!(body[20..22] == 1u16.to_le_bytes());
+	let comment_len = u16::from_le_bytes([body[22], body[23]]) as usize;
+	assert!(&body[24..24 + comment_len] == b"line [0..4]");
+}
+
+#[test]
+fn observer() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let tap = Tap::new(a, Vec::<PcapngWriter<Vec<u8>>>::new());
+	tap.set_observer_history(4);
+
+	assert!(let Ok(()) = tap.write_all(b"old"));
+	assert!(let Ok(()) = tap.write_all(b"ping"));
+	let observer = tap.observer();
+	let_assert!(Some(event) = observer.try_recv());
+	assert!(event.direction() == Direction::Tx);
+	assert!(event.data() == b"ping");
+	assert!(observer.try_recv().is_none());
+
+	assert!(let Ok(()) = b.write_all(b"pong"));
+	let mut buffer = [0; 4];
+	assert!(let Ok(()) = (&tap).read_exact(&mut buffer));
+	let mut received = Vec::new();
+	while received.len() < 4 {
+		let_assert!(Ok(event) = observer.recv_timeout(Duration::from_secs(1)));
+		assert!(event.direction() == Direction::Rx);
+		received.extend_from_slice(event.data());
+	}
+	assert!(received == b"pong");
+
+	drop(tap);
+	assert!(observer.recv().is_none());
+}
+
+#[test]
+fn capture_asciicast() {
+	let start = std::time::SystemTime::now();
+	let_assert!(Ok(mut writer) = AsciicastWriter::with_size(Vec::new(), 100, 30));
+	let later = |millis: u64| start + Duration::from_millis(1000 + millis);
+	assert!(let Ok(()) = writer.record(later(0), Direction::Rx, b"login: \"root\"\r\n"));
+	assert!(let Ok(()) = writer.record(later(500), Direction::Tx, b"l"));
+	assert!(let Ok(()) = writer.record(later(600), Direction::Rx, &[b'\x1b', b'[', b'm', 0xC3]));
+	assert!(let Ok(()) = writer.record(later(700), Direction::Rx, &[0xA9, 0xFF]));
+	assert!(let Ok(()) = writer.annotate(later(800), &Annotation::new(Direction::Rx, 0..7, "prompt")));
+
+	let output = String::from_utf8(writer.into_inner()).unwrap();
+	let lines: Vec<&str> = output.lines().collect();
+	assert!(lines.len() == 6);
+	assert!(lines[0].starts_with(r#"{"version": 2, "width": 100, "height": 30, "timestamp": "#));
+	assert!(lines[1].ends_with(r#", "o", "login: \"root\"\r\n"]"#));
+	assert!(lines[2].ends_with(r#", "i", "l"]"#));
+	assert!(lines[3].ends_with(r#", "o", "\u001b[m"]"#));
+	assert!(lines[4].ends_with(", \"o\", \"é\u{FFFD}\"]"));
+	assert!(lines[5].ends_with(r#", "m", "prompt"]"#));
+
+	let time: f64 = lines[2][1..].split(',').next().unwrap().parse().unwrap();
+	assert!(time >= 1.5);
+	assert!(time < 2.5);
+}
+
+#[test]
+fn merge_captures() {
+	let_assert!(Ok((a, peer_a)) = SerialPort::pair());
+	let_assert!(Ok((b, peer_b)) = SerialPort::pair());
+	let merger = CaptureMerger::new(Vec::<MergedEvent>::new());
+	let_assert!(Ok(input_a) = merger.add_port("request"));
+	let_assert!(Ok(input_b) = merger.add_port("response"));
+	assert!(input_a.port() == 0);
+	assert!(input_b.port() == 1);
+	let tap_a = Tap::new(a, input_a);
+	let tap_b = Tap::new(b, input_b);
+	tap_a.set_clock(merger.clock());
+	tap_b.set_clock(merger.clock());
+
+	assert!(let Ok(()) = tap_a.write_all(b"request"));
+	let mut buffer = [0; 7];
+	assert!(let Ok(()) = peer_a.read_exact(&mut buffer));
+	assert!(let Ok(()) = peer_b.write_all(b"response"));
+	let mut buffer = [0; 8];
+	assert!(let Ok(()) = (&tap_b).read_exact(&mut buffer));
+
+	drop((tap_a, tap_b));
+	let_assert!(Ok(events) = merger.try_into_inner());
+	let received: Vec<u8> = events[1..].iter().flat_map(|event| event.data().to_vec()).collect();
+	assert!(events[0].port() == 0);
+	assert!(events[0].direction() == Direction::Tx);
+	assert!(events[0].data() == b"request");
+	assert!(events[1..].iter().all(|event| event.port() == 1 && event.direction() == Direction::Rx));
+	assert!(received == b"response");
+	assert!(events.windows(2).all(|pair| pair[0].timestamp() <= pair[1].timestamp()));
+}
+
+#[test]
+fn merge_captures_pcapng() {
+	let_assert!(Ok((a, peer_a)) = SerialPort::pair());
+	let_assert!(Ok((b, _peer_b)) = SerialPort::pair());
+	let_assert!(Ok(writer) = PcapngWriter::for_merger(Vec::new(), 147));
+	let merger = CaptureMerger::new(writer);
+	let_assert!(Ok(input_a) = merger.add_port("a"));
+	let_assert!(Ok(input_b) = merger.add_port("b"));
+	let tap_a = Tap::new(a, input_a);
+	let tap_b = Tap::new(b, input_b);
+
+	assert!(let Ok(()) = tap_a.write_all(b"ping"));
+	let mut buffer = [0; 4];
+	assert!(let Ok(()) = peer_a.read_exact(&mut buffer));
+	assert!(let Ok(()) = tap_b.write_all(b"pong"));
+	let_assert!(Err(merger) = merger.try_into_inner());
+	drop((tap_a, tap_b));
+	let_assert!(Ok(writer) = merger.try_into_inner());
+
+	let blocks = parse_blocks(writer.get_ref());
+	assert!(blocks.len() == 7);
+	assert!(blocks[1..5].iter().all(|(block_type, _)| *block_type == 1));
+	assert!(blocks[5].0 == 6);
+	assert!(blocks[5].1[0..4] == 1u32.to_le_bytes());
+	assert!(blocks[6].0 == 6);
+	assert!(blocks[6].1[0..4] == 3u32.to_le_bytes());
+}
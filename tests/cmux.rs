@@ -0,0 +1,101 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::framing::{FrameReader, Framer};
+use serial2::mux::cmux::{Cmux, CmuxFrame, CmuxFramer, FrameType, V24Signals};
+use serial2::SerialPort;
+use std::io::Read;
+use std::time::Duration;
+
+#[test]
+fn frame_encoding() {
+	// SABM for the control channel, with the frame check sequence from the specification examples.
+	let frame = CmuxFrame::new(0, FrameType::Sabm, true, true, Vec::new());
+	assert!(frame.encode() == [0xF9, 0x03, 0x3F, 0x01, 0x1C, 0xF9]);
+
+	// Frames may share a flag, and long frames use a two byte length.
+	let long = CmuxFrame::new(2, FrameType::Uih, true, false, vec![0x55; 200]);
+	let mut data = frame.encode();
+	data.pop();
+	data.extend_from_slice(&long.encode());
+	let mut framer = CmuxFramer;
+	let_assert!(Ok(Some((decoded, len))) = framer.decode(&data));
+	assert!(decoded == frame);
+	let_assert!(Ok(Some((decoded, _))) = framer.decode(&data[len..]));
+	assert!(decoded == long);
+}
+
+/// Simulate a modem that accepts DLCI 0 and 1, refuses other channels and echoes data.
+fn modem(port: SerialPort) {
+	let mut reader = FrameReader::new(port, CmuxFramer);
+	loop {
+		let frame = match reader.read_frame() {
+			Ok(frame) => frame,
+			Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+			Err(_) => return,
+		};
+		let dlci = frame.dlci();
+		let reply = match frame.frame_type() {
+			FrameType::Sabm if dlci <= 1 => CmuxFrame::new(dlci, FrameType::Ua, true, true, Vec::new()),
+			FrameType::Sabm => CmuxFrame::new(dlci, FrameType::Dm, true, true, Vec::new()),
+			FrameType::Disc => CmuxFrame::new(dlci, FrameType::Ua, true, true, Vec::new()),
+			FrameType::Uih if dlci == 0 && frame.data()[0] == 0xC3 => return,
+			FrameType::Uih if dlci == 0 => continue,
+			FrameType::Uih => CmuxFrame::new(dlci, FrameType::Uih, false, false, frame.into_data()),
+			_ => continue,
+		};
+		reader.port().write_all(&reply.encode()).unwrap();
+	}
+}
+
+#[test]
+fn open_channels() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let modem = std::thread::spawn(move || modem(b));
+
+	let_assert!(Ok(mut cmux) = Cmux::new(a));
+	cmux.set_max_frame_size(4);
+	let_assert!(Ok(mut channel) = cmux.open_channel(1));
+	assert!(channel.channel() == 1);
+
+	let_assert!(Err(e) = cmux.open_channel(2));
+	assert!(e.kind() == std::io::ErrorKind::ConnectionRefused);
+	let_assert!(Err(e) = cmux.open_channel(64));
+	assert!(e.kind() == std::io::ErrorKind::InvalidInput);
+
+	assert!(let Ok(()) = channel.write_all(b"AT+CSQ\r"));
+	let mut buffer = [0; 7];
+	assert!(let Ok(()) = channel.read_exact(&mut buffer));
+	assert!(&buffer == b"AT+CSQ\r");
+	assert!(cmux.invalid_frames() == 0);
+
+	drop(channel);
+	drop(cmux);
+	modem.join().unwrap();
+}
+
+#[test]
+fn modem_status() {
+	let_assert!(Ok((a, mut b)) = SerialPort::pair());
+	let_assert!(Ok(()) = b.set_read_timeout(Duration::from_secs(1)));
+	let modem = std::thread::spawn(move || {
+		let mut reader = FrameReader::new(b, CmuxFramer);
+		let_assert!(Ok(sabm) = reader.read_frame());
+		assert!(sabm.frame_type() == FrameType::Sabm);
+		let ua = CmuxFrame::new(0, FrameType::Ua, true, true, Vec::new());
+		reader.port().write_all(&ua.encode()).unwrap();
+
+		// Modem status command: DLCI 1 has ring indicator and carrier.
+		let msc = CmuxFrame::new(0, FrameType::Uih, true, false, vec![0xE3, 0x05, 0x07, 0xC1]);
+		reader.port().write_all(&msc.encode()).unwrap();
+		let_assert!(Ok(response) = reader.read_frame());
+		assert!(response.data() == [0xE1, 0x05, 0x07, 0xC1]);
+	});
+
+	let_assert!(Ok(cmux) = Cmux::new(a));
+	modem.join().unwrap();
+	let_assert!(Some(signals) = cmux.get_remote_modem_status(1));
+	assert!(signals.contains(V24Signals::INCOMING_CALL | V24Signals::DATA_VALID));
+	assert!(!signals.contains(V24Signals::READY_TO_RECEIVE));
+	assert!(cmux.get_remote_modem_status(2) == None);
+}
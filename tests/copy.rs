@@ -0,0 +1,62 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::bridge::copy_bidirectional;
+use serial2::SerialPort;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::time::Duration;
+
+#[test]
+fn copy_between_ports() {
+	let_assert!(Ok((mut a, a_peer)) = SerialPort::pair());
+	let_assert!(Ok((mut b, b_peer)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_millis(50)));
+	assert!(let Ok(()) = b.set_read_timeout(Duration::from_millis(50)));
+
+	std::thread::scope(|scope| {
+		let copy = scope.spawn(|| copy_bidirectional(&a, &b));
+		assert!(let Ok(()) = a_peer.write_all(b"Hello!"));
+		let mut buffer = [0; 6];
+		assert!(let Ok(()) = b_peer.read_exact(&mut buffer));
+		assert!(&buffer == b"Hello!");
+		assert!(let Ok(()) = b_peer.write_all(b"Goodbye!"));
+		let mut buffer = [0; 8];
+		assert!(let Ok(()) = a_peer.read_exact(&mut buffer));
+		assert!(&buffer == b"Goodbye!");
+
+		// Closing a peer makes reading from the port fail, which stops the copy.
+		drop(a_peer);
+		let_assert!(Ok(result) = copy.join());
+		assert!(let Err(_) = result);
+	});
+}
+
+#[test]
+fn copy_between_port_and_socket() {
+	let_assert!(Ok((mut port, port_peer)) = SerialPort::pair());
+	assert!(let Ok(()) = port.set_read_timeout(Duration::from_millis(50)));
+	let_assert!(Ok(listener) = TcpListener::bind("127.0.0.1:0"));
+	let_assert!(Ok(address) = listener.local_addr());
+	let_assert!(Ok(mut client) = TcpStream::connect(address));
+	let_assert!(Ok((server, _)) = listener.accept());
+	assert!(let Ok(()) = server.set_read_timeout(Some(Duration::from_millis(50))));
+
+	std::thread::scope(|scope| {
+		let copy = scope.spawn(|| copy_bidirectional(&port, &server));
+		assert!(let Ok(()) = client.write_all(b"*IDN?\n"));
+		let mut buffer = [0; 6];
+		assert!(let Ok(()) = port_peer.read_exact(&mut buffer));
+		assert!(&buffer == b"*IDN?\n");
+		assert!(let Ok(()) = port_peer.write_all(b"serial2\n"));
+		let mut buffer = [0; 8];
+		assert!(let Ok(()) = client.read_exact(&mut buffer));
+		assert!(&buffer == b"serial2\n");
+
+		// The end of the data on the socket stops the copy.
+		assert!(let Ok(()) = client.shutdown(Shutdown::Write));
+		let_assert!(Ok(Ok(stats)) = copy.join());
+		assert!(stats.a_to_b() == 8);
+		assert!(stats.b_to_a() == 6);
+	});
+}
@@ -0,0 +1,40 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::demux::Demux;
+use serial2::framing::{FrameReader, Framer};
+use serial2::SerialPort;
+use std::time::Duration;
+
+/// A frame consisting of an ID byte, a length byte and the payload.
+struct IdFramer;
+
+impl Framer for IdFramer {
+	type Frame = (u8, Vec<u8>);
+
+	fn decode(&mut self, data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>> {
+		if data.len() < 2 || data.len() < 2 + data[1] as usize {
+			return Ok(None);
+		}
+		let len = 2 + data[1] as usize;
+		Ok(Some(((data[0], data[2..len].to_vec()), len)))
+	}
+}
+
+#[test]
+fn route_frames_by_id() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(demux) = Demux::new(FrameReader::new(a, IdFramer), |frame: &(u8, Vec<u8>)| frame.0));
+	let one = demux.subscribe(1);
+	let two = demux.subscribe(2);
+	let unrouted = demux.subscribe_unrouted();
+
+	assert!(let Ok(()) = b.write_all(b"\x02\x03two\x01\x03one\x03\x05three"));
+	let_assert!(Ok(frame) = one.recv_timeout(Duration::from_secs(1)));
+	assert!(frame == (1, b"one".to_vec()));
+	let_assert!(Ok(frame) = two.recv_timeout(Duration::from_secs(1)));
+	assert!(frame == (2, b"two".to_vec()));
+	let_assert!(Ok(frame) = unrouted.recv_timeout(Duration::from_secs(1)));
+	assert!(frame == (3, b"three".to_vec()));
+	assert!(demux.take_error().is_none());
+}
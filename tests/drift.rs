@@ -0,0 +1,48 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::drift::DriftMonitor;
+use serial2::SerialPort;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn check_configuration_drift() {
+	let_assert!(Ok((mut a, _b)) = SerialPort::pair());
+	let_assert!(Ok(mut settings) = a.get_configuration());
+	assert!(let Ok(()) = settings.set_baud_rate(9600));
+	assert!(let Ok(()) = a.set_configuration(&settings));
+	assert!(let Ok(None) = a.check_configuration_drift());
+
+	// Another handle for the same device reconfigures it.
+	let_assert!(Ok(mut other) = a.try_clone());
+	assert!(let Ok(()) = settings.set_baud_rate(115200));
+	assert!(let Ok(()) = other.set_configuration(&settings));
+	let_assert!(Ok(Some(live)) = a.check_configuration_drift());
+	assert!(let Ok(115200) = live.get_baud_rate());
+	let_assert!(Some(applied) = a.get_applied_configuration());
+	assert!(let Ok(9600) = applied.get_baud_rate());
+}
+
+#[test]
+fn drift_monitor() {
+	let_assert!(Ok((mut a, _b)) = SerialPort::pair());
+	let_assert!(Ok(mut settings) = a.get_configuration());
+	assert!(let Ok(()) = settings.set_baud_rate(9600));
+	assert!(let Ok(()) = a.set_configuration(&settings));
+	let_assert!(Ok(mut other) = a.try_clone());
+
+	let_assert!(Ok(monitor) = DriftMonitor::spawn(Arc::new(a), Duration::from_millis(20)));
+	let events = monitor.subscribe();
+	assert!(let Ok(()) = settings.set_baud_rate(19200));
+	assert!(let Ok(()) = other.set_configuration(&settings));
+
+	let_assert!(Ok(event) = events.recv_timeout(Duration::from_secs(1)));
+	assert!(let Ok(9600) = event.expected().get_baud_rate());
+	assert!(let Ok(19200) = event.actual().get_baud_rate());
+
+	// The same drift is only reported once.
+	std::thread::sleep(Duration::from_millis(100));
+	assert!(monitor.drift_count() == 1);
+	assert!(monitor.take_error().is_none());
+}
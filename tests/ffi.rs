@@ -0,0 +1,61 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::ffi::*;
+use serial2::SerialPort;
+use std::ffi::{CStr, CString};
+
+#[test]
+fn open_errors() {
+	let name = CString::new("/dev/serial2-does-not-exist").unwrap();
+	let mut port = std::ptr::null_mut();
+	unsafe {
+		assert!(serial2_open(name.as_ptr(), 9600, &mut port) == SERIAL2_ERR_NOT_FOUND);
+		assert!(port.is_null());
+		assert!(!CStr::from_ptr(serial2_last_error()).to_bytes().is_empty());
+
+		assert!(serial2_open(std::ptr::null(), 9600, &mut port) == SERIAL2_ERR_INVALID_ARGUMENT);
+		let_assert!(Ok(message) = CStr::from_ptr(serial2_last_error()).to_str());
+		assert!(message == "null pointer");
+		serial2_close(std::ptr::null_mut());
+
+		assert!(serial2_available_ports(None, std::ptr::null_mut()) == SERIAL2_ERR_INVALID_ARGUMENT);
+	}
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn read_write_configure() {
+	use std::os::unix::io::AsRawFd;
+
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(path) = std::fs::read_link(format!("/proc/self/fd/{}", b.as_raw_fd())));
+	let name = CString::new(path.into_os_string().into_encoded_bytes()).unwrap();
+
+	let mut port = std::ptr::null_mut();
+	unsafe {
+		assert!(serial2_open(name.as_ptr(), 115200, &mut port) == SERIAL2_OK);
+		assert!(serial2_set_read_timeout(port, 1000) == SERIAL2_OK);
+
+		let mut settings = std::mem::zeroed();
+		assert!(serial2_get_configuration(port, &mut settings) == SERIAL2_OK);
+		assert!(settings.baud_rate == 115200);
+		assert!(settings.char_size == 8);
+		assert!(settings.parity == SERIAL2_PARITY_NONE);
+		settings.parity = 7;
+		assert!(serial2_set_configuration(port, &settings) == SERIAL2_ERR_INVALID_ARGUMENT);
+
+		assert!(serial2_write_all(port, b"Hello!".as_ptr(), 6) == SERIAL2_OK);
+		let mut buffer = [0; 6];
+		assert!(let Ok(()) = a.read_exact(&mut buffer));
+		assert!(&buffer == b"Hello!");
+
+		assert!(let Ok(()) = a.write_all(b"Bye"));
+		let mut read = 0;
+		assert!(serial2_read(port, buffer.as_mut_ptr(), buffer.len(), &mut read) == SERIAL2_OK);
+		assert!(read == 3);
+		assert!(&buffer[..read] == b"Bye");
+
+		serial2_close(port);
+	}
+}
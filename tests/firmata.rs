@@ -0,0 +1,56 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::protocol::firmata::{Firmata, Message, PinMode, Version};
+use serial2::SerialPort;
+use std::time::Duration;
+
+#[test]
+fn firmata() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let mut firmata = Firmata::new(a);
+
+	// Stale garbage before the version report is skipped.
+	assert!(let Ok(()) = b.write_all(&[0x12, 0xF9, 0x02, 0x05]));
+	let_assert!(Ok(version) = firmata.negotiate(Duration::from_secs(1)));
+	assert!(version == Version::new(2, 5));
+	assert!(firmata.protocol_version() == Some(version));
+
+	assert!(let Ok(()) = b.write_all(&[0xF0, 0x79, 0x02, 0x05, b'A', 0, b'B', 0, 0xF7]));
+	let_assert!(Ok(firmware) = firmata.query_firmware(Duration::from_secs(1)));
+	assert!(firmware.version() == Version::new(2, 5));
+	assert!(firmware.name() == "AB");
+
+	assert!(let Ok(()) = b.write_all(&[0xF0, 0x6C, 0x00, 0x01, 0x01, 0x01, 0x7F, 0x02, 0x0A, 0x7F, 0xF7]));
+	let_assert!(Ok(capabilities) = firmata.query_capabilities(Duration::from_secs(1)));
+	assert!(capabilities == [vec![(PinMode::Input, 1), (PinMode::Output, 1)], vec![(PinMode::Analog, 10)]]);
+
+	assert!(let Ok(()) = firmata.set_pin_mode(13, PinMode::Output));
+	assert!(let Ok(()) = firmata.digital_write(13, true));
+	assert!(let Ok(()) = firmata.analog_write(3, 200));
+	assert!(let Ok(()) = firmata.report_digital(0, true));
+	assert!(let Ok(()) = firmata.report_analog(1, true));
+	let mut buffer = [0; 20];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(buffer[..3] == [0xF9, 0xF0, 0x79]);
+	assert!(buffer[3..6] == [0xF7, 0xF0, 0x6B]);
+	assert!(buffer[6..7] == [0xF7]);
+	assert!(buffer[7..10] == [0xF4, 13, 0x01]);
+	assert!(buffer[10..13] == [0xF5, 13, 0x01]);
+	assert!(buffer[13..16] == [0xE3, 0x48, 0x01]);
+	assert!(buffer[16..20] == [0xD0, 0x01, 0xC1, 0x01]);
+
+	assert!(let Ok(()) = b.write_all(&[0x90, 0x05, 0x00, 0xE1, 0x7F, 0x01, 0xF0, 0x71, b'h', 0, 0xF7]));
+	assert!(let Ok(Message::DigitalPort { port: 0, value: 5 }) = firmata.read_message());
+	assert!(firmata.digital_read(0));
+	assert!(!firmata.digital_read(1));
+	assert!(firmata.digital_read(2));
+	assert!(let Ok(Message::Analog { channel: 1, value: 255 }) = firmata.read_message());
+	assert!(firmata.analog_read(1) == Some(255));
+	assert!(firmata.analog_read(2) == None);
+	let_assert!(Ok(Message::Sysex { command: 0x71, data }) = firmata.read_message());
+	assert!(data == [b'h', 0]);
+
+	assert!(let Err(_) = firmata.set_pin_mode(200, PinMode::Input));
+	assert!(let Err(_) = firmata.send_sysex(0x71, &[0x80]));
+}
@@ -0,0 +1,190 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::framing::{FrameReader, Framer, GapFramer, Resync, SlipFramer};
+use serial2::capture::CaptureClock;
+use serial2::{NoiseFilter, SerialPort};
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn gap_framer() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_secs(1)));
+	let mut reader = FrameReader::new(a, GapFramer);
+	assert!(let Ok(()) = reader.set_idle_char_times(4));
+	let_assert!(Some(idle_time) = reader.get_idle_time());
+	assert!(idle_time > Duration::ZERO);
+	reader.set_idle_time(Some(Duration::from_millis(50)));
+
+	let writer = std::thread::spawn(move || {
+		assert!(let Ok(()) = b.write_all(b"Hello"));
+		std::thread::sleep(Duration::from_millis(200));
+		assert!(let Ok(()) = b.write_all(b"world!"));
+		b
+	});
+	let_assert!(Ok(frame) = reader.read_frame());
+	assert!(frame == b"Hello");
+	let_assert!(Ok(frame) = reader.read_frame());
+	assert!(frame == b"world!");
+	let _b = writer.join().unwrap();
+
+	let_assert!(Ok(timeout) = reader.port().get_read_timeout());
+	assert!(timeout == Duration::from_secs(1));
+}
+
+#[test]
+fn slip_framer() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_secs(1)));
+	let mut reader = FrameReader::new(a, SlipFramer);
+
+	let encoded = SlipFramer::encode(&[1, 0xC0, 2, 0xDB, 3]);
+	assert!(encoded == [0xC0, 1, 0xDB, 0xDC, 2, 0xDB, 0xDD, 3, 0xC0]);
+	assert!(let Ok(()) = b.write_all(&encoded));
+	assert!(let Ok(()) = b.write_all(&SlipFramer::encode(b"hello")));
+	assert!(let Ok(()) = b.write_all(&[0xDB, 0x00, 0xC0]));
+	let_assert!(Ok(frame) = reader.read_frame());
+	assert!(frame == [1, 0xC0, 2, 0xDB, 3]);
+	let_assert!(Ok(frame) = reader.read_frame());
+	assert!(frame == b"hello");
+	assert!(let Err(_) = reader.read_frame());
+}
+
+#[test]
+fn noise_filter_until_valid_frame() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_secs(1)));
+	a.set_noise_filter(Some(NoiseFilter::new(Duration::ZERO).until_valid_frame()));
+	let mut reader = FrameReader::new(a, SlipFramer);
+
+	assert!(let Ok(()) = b.write_all(&[0xDB, 0xC0]));
+	assert!(let Ok(()) = b.write_all(&SlipFramer::encode(b"hello")));
+	let_assert!(Ok(frame) = reader.read_frame());
+	assert!(frame == b"hello");
+
+	// After the first valid frame, invalid data is reported again.
+	assert!(let Ok(()) = b.write_all(&[0xDB, 0xC0]));
+	assert!(let Err(_) = reader.read_frame());
+}
+
+#[test]
+fn timestamped_frames() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_secs(1)));
+	let mut reader = FrameReader::new(a, SlipFramer);
+	reader.set_clock(CaptureClock::monotonic_from(UNIX_EPOCH));
+	assert!(reader.clock().is_monotonic());
+
+	std::thread::sleep(Duration::from_millis(50));
+	assert!(let Ok(()) = b.write_all(&SlipFramer::encode(b"hello")));
+	let_assert!(Ok((timestamp, frame)) = reader.read_frame_timestamped());
+	assert!(frame == b"hello");
+	let_assert!(Ok(elapsed) = timestamp.duration_since(UNIX_EPOCH));
+	assert!(elapsed >= Duration::from_millis(50));
+	assert!(elapsed < Duration::from_secs(5));
+
+	// A custom clock is used for every timestamp.
+	reader.set_clock(CaptureClock::custom(|| UNIX_EPOCH + Duration::from_secs(1000)));
+	assert!(!reader.clock().is_monotonic());
+	assert!(let Ok(()) = b.write_all(&SlipFramer::encode(b"world")));
+	let_assert!(Ok((timestamp, frame)) = reader.read_frame_timestamped());
+	assert!(frame == b"world");
+	assert!(timestamp <= UNIX_EPOCH + Duration::from_secs(1000));
+	assert!(timestamp > UNIX_EPOCH + Duration::from_secs(990));
+}
+
+#[test]
+fn read_multiple_frames() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_millis(500)));
+	let mut reader = FrameReader::new(a, SlipFramer);
+
+	let mut data = Vec::new();
+	for i in 0..10u8 {
+		data.extend(SlipFramer::encode(&[i; 3]));
+	}
+	data.extend(&[0xC0, 1, 2]);
+	assert!(let Ok(()) = b.write_all(&data));
+	std::thread::sleep(Duration::from_millis(50));
+
+	let mut frames = Vec::new();
+	assert!(let Ok(3) = reader.read_frames(&mut frames));
+	assert!(frames.len() == 10);
+	assert!(frames.iter().enumerate().all(|(i, frame)| frame == &[i as u8; 3]));
+	let_assert!(Ok(timeout) = reader.port().get_read_timeout());
+	assert!(timeout == Duration::from_millis(500));
+
+	assert!(let Ok(()) = b.write_all(&[3, 0xC0]));
+	frames.clear();
+	assert!(let Ok(0) = reader.read_frames(&mut frames));
+	assert!(frames == [vec![1, 2, 3]]);
+}
+
+/// A frame consisting of the sync word `0xAA 0x55`, a length byte and the payload.
+struct SyncFramer;
+
+impl Framer for SyncFramer {
+	type Frame = Vec<u8>;
+
+	fn decode(&mut self, data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>> {
+		if data[0] != 0xAA || data.get(1).is_some_and(|&byte| byte != 0x55) {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "missing sync word"));
+		}
+		if data.len() < 3 || data.len() < 3 + data[2] as usize {
+			return Ok(None);
+		}
+		let len = 3 + data[2] as usize;
+		Ok(Some((data[3..len].to_vec(), len)))
+	}
+}
+
+#[test]
+fn resync_policies() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_secs(1)));
+	let mut reader = FrameReader::new(a, SyncFramer);
+	assert!(reader.get_resync() == &Resync::SkipByte);
+
+	assert!(let Ok(()) = b.write_all(b"xyz\xAA\x55\x02hi"));
+	for _ in 0..3 {
+		let_assert!(Err(e) = reader.read_frame());
+		assert!(e.kind() == std::io::ErrorKind::InvalidData);
+	}
+	let_assert!(Ok(frame) = reader.read_frame());
+	assert!(frame == b"hi");
+	assert!(reader.resync_stats().events() == 3);
+	assert!(reader.resync_stats().discarded_bytes() == 3);
+
+	reader.reset_resync_stats();
+	reader.set_resync(Resync::SkipToSync(vec![0xAA, 0x55]));
+	assert!(let Ok(()) = b.write_all(b"\xAAxyz\xAA\x55\x02hi"));
+	let_assert!(Err(_) = reader.read_frame());
+	let_assert!(Ok(frame) = reader.read_frame());
+	assert!(frame == b"hi");
+	assert!(reader.resync_stats().events() == 1);
+	assert!(reader.resync_stats().discarded_bytes() == 4);
+}
+
+#[test]
+fn resync_discard_until_idle() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_secs(1)));
+	let mut reader = FrameReader::new(a, SyncFramer);
+	reader.set_idle_time(Some(Duration::from_millis(50)));
+	reader.set_resync(Resync::DiscardUntilIdle);
+
+	// The valid looking frame is part of the corrupt burst, so it is discarded too.
+	assert!(let Ok(()) = b.write_all(b"x\xAA\x55\x02ab"));
+	let_assert!(Err(_) = reader.read_frame());
+	let writer = std::thread::spawn(move || {
+		assert!(let Ok(()) = b.write_all(b"\xAA\x55"));
+		std::thread::sleep(Duration::from_millis(200));
+		assert!(let Ok(()) = b.write_all(b"\xAA\x55\x02cd"));
+		b
+	});
+	let_assert!(Ok(frame) = reader.read_frame());
+	assert!(frame == b"cd");
+	let _b = writer.join().unwrap();
+	assert!(reader.resync_stats().events() == 1);
+	assert!(reader.resync_stats().discarded_bytes() == 8);
+}
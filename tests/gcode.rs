@@ -0,0 +1,85 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::protocol::gcode::{CommandError, GcodeStreamer, Protocol, Realtime, Response};
+use serial2::SerialPort;
+use std::time::Duration;
+
+/// Read exactly `len` bytes from a serial port.
+fn read_exact(port: &SerialPort, len: usize) -> Vec<u8> {
+	let mut buffer = vec![0; len];
+	assert!(let Ok(()) = port.read_exact(&mut buffer));
+	buffer
+}
+
+#[test]
+fn parse_response() {
+	assert!(Response::parse("ok\r") == Response::Ok(String::new()));
+	assert!(Response::parse("ok T:21.0 /0.0") == Response::Ok("T:21.0 /0.0".into()));
+	assert!(Response::parse("error:20") == Response::Error("20".into()));
+	assert!(Response::parse("Error:Unknown command") == Response::Error("Unknown command".into()));
+	assert!(Response::parse("ALARM:1") == Response::Alarm("1".into()));
+	assert!(Response::parse("<Idle|MPos:0.000,0.000,0.000>") == Response::Status("Idle|MPos:0.000,0.000,0.000".into()));
+	assert!(Response::parse("[MSG:Reset]") == Response::Message("[MSG:Reset]".into()));
+}
+
+#[test]
+fn character_counting() {
+	let_assert!(Ok((mut a, mut b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_millis(50)));
+	assert!(let Ok(()) = b.set_read_timeout(Duration::from_millis(500)));
+	let mut streamer = GcodeStreamer::new(a, Protocol::CharacterCounting { buffer_size: 13 });
+	streamer.push_line("G0 X1");
+	streamer.push_line("G0 Y2 ; comment");
+	streamer.push_line("(only a comment)");
+	streamer.push_line("G1 (inline) Z3");
+	assert!(streamer.queued() == 3);
+
+	// Two lines fit in the buffer of the firmware.
+	assert!(let Ok(None) = streamer.poll());
+	assert!(streamer.in_flight() == 2);
+	assert!(read_exact(&b, 12) == b"G0 X1\nG0 Y2\n");
+
+	// Acknowledging the first line makes room for the third line.
+	assert!(let Ok(()) = b.write_all(b"ok\r\n"));
+	assert!(let Ok(Some(Response::Ok(_))) = streamer.poll());
+	assert!(read_exact(&b, 7) == b"G1  Z3\n");
+
+	assert!(let Ok(()) = b.write_all(b"error:20\r\n"));
+	let_assert!(Err(e) = streamer.poll());
+	let_assert!(Some(error) = e.get_ref().and_then(|e| e.downcast_ref::<CommandError>()));
+	assert!(error.line() == "G0 Y2");
+	assert!(error.message() == "20");
+
+	streamer.pause();
+	streamer.push_line("G0 X0");
+	assert!(let Ok(()) = streamer.realtime(Realtime::FeedHold));
+	assert!(let Ok(()) = b.write_all(b"ok\n"));
+	assert!(let Ok(()) = streamer.wait_idle(Duration::from_secs(1)));
+	assert!(streamer.queued() == 1);
+	assert!(read_exact(&b, 1) == b"!");
+
+	streamer.resume();
+	assert!(let Ok(None) = streamer.poll());
+	assert!(read_exact(&b, 6) == b"G0 X0\n");
+}
+
+#[test]
+fn send_response() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_millis(50)));
+	let mut streamer = GcodeStreamer::new(a, Protocol::SendResponse);
+	streamer.push_line("M105");
+	streamer.push_line("M114");
+	assert!(let Ok(None) = streamer.poll());
+	assert!(streamer.in_flight() == 1);
+	assert!(read_exact(&b, 5) == b"M105\n");
+
+	let device = std::thread::spawn(move || {
+		assert!(let Ok(()) = b.write_all(b"ok T:21.0\n"));
+		assert!(read_exact(&b, 5) == b"M114\n");
+		assert!(let Ok(()) = b.write_all(b"X:0.00 Y:0.00\nok\n"));
+	});
+	assert!(let Ok(()) = streamer.wait_idle(Duration::from_secs(1)));
+	device.join().unwrap();
+}
@@ -0,0 +1,45 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::group::PortGroup;
+use serial2::SerialPort;
+use std::time::Duration;
+
+#[test]
+fn read_ready_is_fair() {
+	let_assert!(Ok((chatty, chatty_peer)) = SerialPort::pair());
+	let_assert!(Ok((quiet, quiet_peer)) = SerialPort::pair());
+	let mut group = PortGroup::new();
+	assert!(group.insert("chatty", chatty).is_none());
+	assert!(group.insert("quiet", quiet).is_none());
+	group.set_quantum(16);
+	assert!(group.set_port_quantum(&"quiet", Some(4)));
+	assert!(group.get_port_quantum(&"quiet") == Some(4));
+	assert!(group.get_port_quantum(&"chatty") == None);
+
+	// Nothing is ready yet.
+	assert!(let Ok(0) = group.read_ready(Duration::from_millis(10), |_, _| panic!("no port should be ready")));
+
+	assert!(let Ok(()) = chatty_peer.write_all(&[0xAA; 100]));
+	assert!(let Ok(()) = quiet_peer.write_all(b"hello"));
+	std::thread::sleep(Duration::from_millis(50));
+
+	// Every call reads at most one quantum from each ready port.
+	let mut reads = Vec::new();
+	let_assert!(Ok(2) = group.read_ready(Duration::from_secs(1), |key, data| {
+		let_assert!(Ok(data) = data);
+		reads.push((*key, data.to_vec()));
+	}));
+	assert!(reads.len() == 2);
+	assert!(reads.contains(&("chatty", vec![0xAA; 16])));
+	assert!(reads.contains(&("quiet", b"hell".to_vec())));
+
+	// The port that is served first rotates.
+	let mut order = Vec::new();
+	let_assert!(Ok(2) = group.read_ready(Duration::from_secs(1), |key, _| order.push(*key)));
+	assert!(order == ["quiet", "chatty"]);
+
+	let_assert!(Some(_) = group.remove(&"chatty"));
+	assert!(group.len() == 1);
+	assert!(group.get(&"chatty").is_none());
+}
@@ -0,0 +1,54 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::clock::Clock;
+use serial2::idle::{IdleDetector, IdleEvent};
+use serial2::{CharSize, Parity, SerialPort, StopBits};
+use std::time::Duration;
+
+#[test]
+fn char_time() {
+	let_assert!(Ok((a, _b)) = SerialPort::pair());
+	let_assert!(Ok(mut settings) = a.get_configuration());
+	assert!(let Ok(()) = settings.set_baud_rate(9600));
+	let_assert!(Ok(char_time) = settings.get_char_time());
+	assert!(char_time == Duration::from_nanos(10 * 1_000_000_000 / 9600));
+
+	settings.set_char_size(CharSize::Bits7);
+	settings.set_parity(Parity::Even);
+	settings.set_stop_bits(StopBits::Two);
+	let_assert!(Ok(char_time) = settings.get_char_time());
+	assert!(char_time == Duration::from_nanos(11 * 1_000_000_000 / 9600));
+}
+
+#[test]
+fn detect_idle_line() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	let mut detector = IdleDetector::new(Duration::from_millis(50));
+	let mut buffer = [0; 16];
+
+	assert!(let Ok(()) = b.write_all(b"Hello!"));
+	assert!(let Ok(IdleEvent::Data(6)) = detector.read(&mut a, &mut buffer));
+	assert!(!detector.is_idle());
+	assert!(let Ok(IdleEvent::Idle) = detector.read(&mut a, &mut buffer));
+	assert!(detector.is_idle());
+	assert!(detector.silence() >= Duration::from_millis(50));
+}
+
+#[test]
+fn manual_clock() {
+	let_assert!(Ok((mut a, _b)) = SerialPort::pair());
+	let clock = Clock::manual();
+	let mut detector = IdleDetector::new(Duration::from_secs(3600));
+	detector.set_clock(clock.clone());
+	assert!(!detector.is_idle());
+
+	clock.advance(Duration::from_secs(1800));
+	assert!(detector.silence() == Duration::from_secs(1800));
+	assert!(!detector.is_idle());
+
+	clock.advance(Duration::from_secs(1800));
+	assert!(detector.is_idle());
+	let mut buffer = [0; 8];
+	assert!(let Ok(IdleEvent::Idle) = detector.read(&mut a, &mut buffer));
+}
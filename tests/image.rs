@@ -0,0 +1,61 @@
+use assert2::{assert, let_assert};
+use serial2::protocol::image::{Chunk, IntelHexReader, SrecReader};
+
+const HEX: &str = "\
+:020000040800F2
+:06000000010203040506E5
+:04001000AABBCCDDDE
+:0400000508000131BD
+:00000001FF
+";
+
+const SREC: &str = "\
+S00600004844521B
+S107000001020304EE
+S10500040506EB
+S1050010AABB85
+S9030000FC
+";
+
+#[test]
+fn intel_hex() {
+	let mut reader = IntelHexReader::new(HEX.as_bytes());
+	reader.set_chunk_size(4);
+	let chunks: Result<Vec<_>, _> = reader.by_ref().collect();
+	let_assert!(Ok(chunks) = chunks);
+	assert!(
+		chunks
+			== [
+				Chunk::new(0x0800_0000, vec![1, 2, 3, 4]),
+				Chunk::new(0x0800_0004, vec![5, 6]),
+				Chunk::new(0x0800_0010, vec![0xAA, 0xBB, 0xCC, 0xDD]),
+			]
+	);
+	assert!(reader.start_address() == Some(0x0800_0131));
+	assert!(reader.bytes_read() == HEX.len() as u64);
+}
+
+#[test]
+fn intel_hex_errors() {
+	let bad_checksum = HEX.replace(":06000000010203040506E5", ":06000000010203040506E6");
+	let_assert!(Some(Err(e)) = IntelHexReader::new(bad_checksum.as_bytes()).next());
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+	assert!(e.to_string() == "line 2: invalid checksum");
+
+	let truncated = &HEX[..HEX.len() - 12];
+	let results: Vec<_> = IntelHexReader::new(truncated.as_bytes()).collect();
+	assert!(results.iter().any(|result| result.is_err()));
+}
+
+#[test]
+fn srec() {
+	let mut reader = SrecReader::new(SREC.as_bytes());
+	let chunks: Result<Vec<_>, _> = reader.by_ref().collect();
+	let_assert!(Ok(chunks) = chunks);
+	assert!(chunks == [Chunk::new(0, vec![1, 2, 3, 4, 5, 6]), Chunk::new(0x10, vec![0xAA, 0xBB])]);
+	assert!(reader.start_address() == Some(0));
+
+	let bad_checksum = SREC.replace("S1050010AABB85", "S1050010AABB86");
+	let_assert!(Some(Err(e)) = SrecReader::new(bad_checksum.as_bytes()).next());
+	assert!(e.to_string() == "line 4: invalid checksum");
+}
@@ -0,0 +1,37 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::interference::{Interference, InterferenceGuard};
+use serial2::SerialPort;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+
+#[test]
+fn check_pseudo_terminal() {
+	let_assert!(Ok((a, _b)) = SerialPort::pair());
+	let_assert!(Ok(path) = std::fs::read_link(format!("/dev/fd/{}", a.as_raw_fd())));
+	let_assert!(Ok(guard) = InterferenceGuard::check(&path));
+	assert!(guard.device() == path);
+	// Nobody else uses a freshly created pseudo terminal.
+	for interference in guard.interference() {
+		assert!(let Interference::ModemManager { .. } = interference);
+	}
+	assert!(!guard.has_ignore_hints());
+}
+
+#[test]
+fn check_missing_device() {
+	let_assert!(Err(e) = InterferenceGuard::check("/dev/serial2-does-not-exist"));
+	assert!(e.kind() == std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn diagnostic_message() {
+	let interference = Interference::LockFile {
+		path: PathBuf::from("/run/lock/LCK..ttyUSB0"),
+		pid: Some(1234),
+	};
+	assert!(interference.to_string() == "the device is locked by process 1234 (lock file /run/lock/LCK..ttyUSB0)");
+	let interference = Interference::ModemManager { pid: 42 };
+	assert!(interference.to_string().contains("ID_MM_DEVICE_IGNORE=1"));
+}
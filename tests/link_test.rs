@@ -0,0 +1,76 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::SerialPort;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Echo everything received on the port, except every `drop_every`-th byte.
+fn spawn_echo(mut port: SerialPort, drop_every: Option<u64>) -> (Arc<AtomicBool>, std::thread::JoinHandle<()>) {
+	let stop = Arc::new(AtomicBool::new(false));
+	let thread = std::thread::spawn({
+		let stop = stop.clone();
+		move || {
+			port.set_read_timeout(Duration::from_millis(20)).unwrap();
+			let mut buffer = [0; 1024];
+			let mut count = 0u64;
+			while !stop.load(Ordering::Relaxed) {
+				let read = match port.read(&mut buffer) {
+					Ok(read) => read,
+					Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+					Err(e) => panic!("failed to read: {e}"),
+				};
+				let mut echo = Vec::with_capacity(read);
+				for &byte in &buffer[..read] {
+					count += 1;
+					if drop_every.is_none_or(|n| !count.is_multiple_of(n)) {
+						echo.push(byte);
+					}
+				}
+				port.write_all(&echo).unwrap();
+			}
+		}
+	});
+	(stop, thread)
+}
+
+#[test]
+fn measure_link() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_millis(500)));
+	assert!(let Ok(()) = a.set_write_timeout(Duration::from_secs(1)));
+	let (stop, echo) = spawn_echo(b, None);
+
+	let_assert!(Ok(report) = a.measure_link(Duration::from_millis(400)));
+	stop.store(true, Ordering::Relaxed);
+	echo.join().unwrap();
+
+	assert!(report.errors() == 0);
+	assert!(report.bytes_lost() == 0);
+	assert!(report.bytes_sent() > 0);
+	assert!(report.bytes_received() == report.bytes_sent());
+	assert!(report.bytes_per_second() > 0.0);
+	assert!(!report.round_trips().is_empty());
+	let_assert!(Some(median) = report.round_trip_percentile(50.0));
+	let_assert!(Some(max) = report.round_trip_percentile(100.0));
+	assert!(median <= max);
+	assert!(report.elapsed() >= Duration::from_millis(400));
+}
+
+#[test]
+fn measure_link_lossy() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_millis(200)));
+	assert!(let Ok(()) = a.set_write_timeout(Duration::from_secs(1)));
+	let (stop, echo) = spawn_echo(b, Some(1000));
+
+	let_assert!(Ok(report) = a.measure_link(Duration::from_millis(300)));
+	stop.store(true, Ordering::Relaxed);
+	echo.join().unwrap();
+
+	assert!(report.stream_errors() > 0);
+	assert!(report.bytes_lost() > 0);
+	assert!(report.bytes_received() < report.bytes_sent());
+	assert!(report.bytes_received() + report.bytes_lost() == report.bytes_sent());
+}
@@ -0,0 +1,46 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::framing::SlipFramer;
+use serial2::log_split::{LogSplitter, Segment, SplitStreams};
+use serial2::SerialPort;
+use std::time::Duration;
+
+#[test]
+fn split_text_and_frames() {
+	let mut splitter = LogSplitter::new(SlipFramer);
+	let mut data = b"boot ok\r\n".to_vec();
+	data.extend(SlipFramer::encode(&[1, 2, 0xC0]));
+	data.extend(b"temp=2");
+	let segments = splitter.push(&data);
+	assert!(segments == [Segment::Text("boot ok".into()), Segment::Frame(vec![1, 2, 0xC0])]);
+
+	// Lines and frames that are split over multiple reads are completed later.
+	assert!(splitter.push(b"0\n\xC0\x03") == [Segment::Text("temp=20".into())]);
+	assert!(splitter.push(b"\xC0") == [Segment::Frame(vec![3])]);
+
+	// Unterminated text can be flushed when the line is idle.
+	assert!(splitter.push(b"login: ").is_empty());
+	assert!(splitter.flush_text().as_deref() == Some("login: "));
+	assert!(splitter.buffered().is_empty());
+	assert!(splitter.discarded_bytes() == 0);
+}
+
+#[test]
+fn split_streams() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(streams) = SplitStreams::spawn(a, LogSplitter::new(SlipFramer)));
+	let text = streams.subscribe_text();
+	let frames = streams.subscribe_frames();
+
+	assert!(let Ok(()) = b.write_all(b"hello world\n"));
+	assert!(let Ok(()) = b.write_all(&SlipFramer::encode(b"\x00\x01")));
+	assert!(let Ok(()) = b.write_all(b"> "));
+	assert!(let Ok("hello world") = text.recv_timeout(Duration::from_secs(1)).as_deref());
+	assert!(let Ok(b"\x00\x01") = frames.recv_timeout(Duration::from_secs(1)).as_deref());
+	assert!(let Ok("> ") = text.recv_timeout(Duration::from_secs(1)).as_deref());
+
+	drop(b);
+	std::thread::sleep(Duration::from_millis(200));
+	assert!(let Some(_) = streams.take_error());
+}
@@ -0,0 +1,81 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::bridge::mqtt::{MqttBridge, MqttConfig};
+use serial2::framing::SlipFramer;
+use serial2::reconnect::AutoReconnectPort;
+use serial2::SerialPort;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+/// Read a packet from the bridge, returning the fixed header byte and the body.
+fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+	let mut header = [0; 2];
+	assert!(let Ok(()) = stream.read_exact(&mut header));
+	// The tests only use packets with a remaining length below 128.
+	assert!(header[1] < 0x80);
+	let mut body = vec![0; header[1].into()];
+	assert!(let Ok(()) = stream.read_exact(&mut body));
+	(header[0], body)
+}
+
+/// Accept a connection from the bridge and complete the handshake.
+fn accept(listener: &TcpListener) -> TcpStream {
+	let_assert!(Ok((mut stream, _)) = listener.accept());
+	assert!(let Ok(()) = stream.set_read_timeout(Some(Duration::from_secs(2))));
+	let (header, body) = read_packet(&mut stream);
+	assert!(header == 0x10);
+	assert!(body[..6] == *b"\x00\x04MQTT");
+	assert!(body.ends_with(b"\x00\x07gateway"));
+	assert!(let Ok(()) = stream.write_all(&[0x20, 0x02, 0x00, 0x00]));
+
+	let (header, body) = read_packet(&mut stream);
+	assert!(header == 0x82);
+	assert!(body[2..] == *b"\x00\x0Cdevice/input\x00");
+	assert!(let Ok(()) = stream.write_all(&[0x90, 0x03, body[0], body[1], 0x00]));
+	stream
+}
+
+#[test]
+fn publish_and_command() {
+	let_assert!(Ok((a, mut b)) = SerialPort::pair());
+	assert!(let Ok(()) = b.set_read_timeout(Duration::from_secs(2)));
+	let mut a = Some(a);
+	let_assert!(Ok(port) = AutoReconnectPort::new(move || a.take().ok_or(std::io::ErrorKind::NotFound.into())));
+
+	let_assert!(Ok(listener) = TcpListener::bind("127.0.0.1:0"));
+	let_assert!(Ok(broker) = listener.local_addr());
+	let mut config = MqttConfig::new(broker.to_string(), "gateway", "device/output");
+	config.set_command_topic(Some("device/input".into()));
+	let_assert!(Ok(bridge) = MqttBridge::new(port, SlipFramer, config));
+	let mut stream = accept(&listener);
+	while !bridge.is_connected() {
+		std::thread::sleep(Duration::from_millis(10));
+	}
+
+	// Frames from the serial port are published.
+	assert!(let Ok(()) = b.write_all(&SlipFramer::encode(b"temperature=21")));
+	let (header, body) = read_packet(&mut stream);
+	assert!(header == 0x30);
+	assert!(body == b"\x00\x0Ddevice/outputtemperature=21");
+
+	// Commands are written to the serial port.
+	assert!(let Ok(()) = stream.write_all(b"\x30\x11\x00\x0Cdevice/inputon\r"));
+	let mut buffer = [0; 3];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"on\r");
+
+	// The bridge reconnects when the broker drops the connection.
+	drop(stream);
+	let _stream = accept(&listener);
+	while bridge.stats().connections() < 2 || !bridge.is_connected() {
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	let_assert!(Some(e) = bridge.take_error());
+	assert!(e.kind() == std::io::ErrorKind::UnexpectedEof);
+
+	let stats = bridge.stats();
+	assert!(stats.frames_published() == 1);
+	assert!(stats.commands_received() == 1);
+}
@@ -0,0 +1,63 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::mux::{Mux, MAX_PAYLOAD};
+use serial2::SerialPort;
+use std::io::Read;
+use std::time::Duration;
+
+#[test]
+fn channels_are_independent() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(mux_a) = Mux::new(a));
+	let_assert!(Ok(mux_b) = Mux::new(b));
+
+	let_assert!(Ok(console_a) = mux_a.channel(1));
+	let_assert!(Ok(telemetry_a) = mux_a.channel(2));
+	let_assert!(Ok(mut console_b) = mux_b.channel(1));
+	let_assert!(Ok(mut telemetry_b) = mux_b.channel(2));
+	let_assert!(Err(e) = mux_a.channel(1));
+	assert!(e.kind() == std::io::ErrorKind::ResourceBusy);
+
+	assert!(let Ok(()) = telemetry_a.write_all(b"telemetry"));
+	assert!(let Ok(()) = console_a.write_all(b"console"));
+
+	let mut buffer = [0; 7];
+	assert!(let Ok(()) = console_b.read_exact(&mut buffer));
+	assert!(&buffer == b"console");
+	let mut buffer = [0; 9];
+	assert!(let Ok(()) = telemetry_b.read_exact(&mut buffer));
+	assert!(&buffer == b"telemetry");
+
+	// Large writes are split over multiple frames.
+	let data: Vec<u8> = (0..MAX_PAYLOAD * 3).map(|i| i as u8).collect();
+	assert!(let Ok(()) = console_b.write_all(&data));
+	let mut received = vec![0; data.len()];
+	assert!(let Ok(()) = (&console_a).read_exact(&mut received));
+	assert!(received == data);
+
+	// Dropping the multiplexer ends the channels.
+	drop(mux_b);
+	assert!(console_b.is_open() == false);
+	let mut buffer = [0; 1];
+	assert!(let Ok(0) = console_b.read(&mut buffer));
+}
+
+#[test]
+fn corrupted_frames_are_skipped() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(mux) = Mux::new(a));
+	let_assert!(Ok(mut channel) = mux.channel(3));
+	channel.set_read_timeout(Duration::from_secs(1));
+
+	// Garbage, a frame with a bad checksum, and a valid frame.
+	let checksum = 3 ^ 2 ^ b'h' ^ b'i';
+	assert!(let Ok(()) = b.write_all(&[0x00, 0x12]));
+	assert!(let Ok(()) = b.write_all(&[0xF9, 3, 2, 0, b'h', b'i', !checksum]));
+	assert!(let Ok(()) = b.write_all(&[0xF9, 3, 2, 0, b'h', b'i', checksum]));
+
+	let mut buffer = [0; 2];
+	assert!(let Ok(()) = channel.read_exact(&mut buffer));
+	assert!(&buffer == b"hi");
+	assert!(mux.invalid_frames() >= 3);
+}
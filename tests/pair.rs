@@ -1,7 +1,9 @@
 #![cfg(unix)]
 
 use assert2::{assert, let_assert};
-use serial2::SerialPort;
+use serial2::{ByteTransform, InterByteDelay, KeepSettings, NoiseFilter, SerialPort, SoftwareParity};
+use std::io::IoSlice;
+use std::time::{Duration, Instant};
 
 #[test]
 fn open_pair() {
@@ -16,3 +18,547 @@ fn open_pair() {
 	assert!(let Ok(()) = a.read_exact(&mut buffer));
 	assert!(&buffer == b"Goodbye!");
 }
+
+#[test]
+fn open_pty() {
+	let_assert!(Ok((master, slave_name)) = SerialPort::open_pty());
+	let_assert!(Ok(slave) = SerialPort::open(&slave_name, KeepSettings));
+	assert!(let Ok(()) = master.write_all(b"$GPGGA\r\n"));
+	let mut buffer = [0; 8];
+	assert!(let Ok(()) = slave.read_exact(&mut buffer));
+	assert!(&buffer == b"$GPGGA\r\n");
+
+	assert!(let Ok(()) = slave.write_all(b"Hello!"));
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = master.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello!");
+}
+
+#[test]
+fn pair_write_does_not_block() {
+	// A single write of more data than fits in the kernel buffers returns a partial write instead of blocking.
+	let_assert!(Ok((a, _b)) = SerialPort::pair());
+	let data = vec![0; 1 << 20];
+	let_assert!(Ok(written) = a.write(&data));
+	assert!(written > 0);
+	assert!(written < data.len());
+}
+
+#[test]
+fn noise_filter() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_secs(1)));
+	a.set_noise_filter(Some(NoiseFilter::new(Duration::from_millis(200))));
+	std::thread::scope(|scope| {
+		scope.spawn(|| {
+			assert!(let Ok(()) = b.write_all(b"garbage"));
+			std::thread::sleep(Duration::from_millis(50));
+			assert!(let Ok(()) = b.write_all(b"more garbage"));
+			std::thread::sleep(Duration::from_millis(250));
+			assert!(let Ok(()) = b.write_all(b"Hello!"));
+		});
+		let mut buffer = [0; 6];
+		assert!(let Ok(()) = a.read_exact(&mut buffer));
+		assert!(&buffer == b"Hello!");
+	});
+
+	// Changing the baud rate arms the filter again.
+	let_assert!(Ok(mut settings) = a.get_configuration());
+	assert!(let Ok(()) = settings.set_baud_rate(9600));
+	assert!(let Ok(()) = a.set_configuration(&settings));
+	assert!(let Ok(()) = b.write_all(b"garbage"));
+	let mut buffer = [0; 16];
+	let_assert!(Err(e) = a.read(&mut buffer));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+
+	// Data received during the discard time is discarded, even if it is read later.
+	a.arm_noise_filter();
+	assert!(let Ok(()) = b.write_all(b"garbage"));
+	std::thread::sleep(Duration::from_millis(250));
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_millis(100)));
+	let_assert!(Err(e) = a.read(&mut buffer));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn change_baud_rate_drained() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = b.write_all(b"Hello!"));
+	assert!(let Ok(()) = a.write_all(b"Goodbye!"));
+	assert!(let Ok(()) = a.change_baud_rate_drained(9600, Duration::from_millis(10), false));
+	let_assert!(Ok(settings) = a.get_configuration());
+	assert!(let Ok(9600) = settings.get_baud_rate());
+
+	let mut buffer = [0; 8];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"Goodbye!");
+
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = a.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello!");
+}
+
+#[test]
+fn partial_configuration_updates() {
+	let_assert!(Ok((mut a, _b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_baud_rate(19200));
+	assert!(let Ok(()) = a.set_stop_bits(serial2::StopBits::Two));
+	assert!(let Ok(()) = a.set_flow_control(serial2::FlowControl::XonXoff));
+	let_assert!(Ok(settings) = a.get_configuration());
+	assert!(let Ok(19200) = settings.get_baud_rate());
+	assert!(let Ok(serial2::StopBits::Two) = settings.get_stop_bits());
+	assert!(let Ok(serial2::FlowControl::XonXoff) = settings.get_flow_control());
+
+	// Pseudo-terminals do not support parity or other character sizes, so the previous configuration is restored.
+	assert!(let Err(_) = a.set_parity(serial2::Parity::Even));
+	assert!(let Err(_) = a.set_char_size(serial2::CharSize::Bits7));
+	let_assert!(Ok(settings) = a.get_configuration());
+	assert!(let Ok(serial2::Parity::None) = settings.get_parity());
+	assert!(let Ok(serial2::CharSize::Bits8) = settings.get_char_size());
+	assert!(let Ok(19200) = settings.get_baud_rate());
+	assert!(let Ok(None) = a.check_configuration_drift());
+
+	// Setting the current value again does not touch the serial port.
+	assert!(let Ok(()) = a.set_parity(serial2::Parity::None));
+}
+
+#[test]
+fn send_xon_xoff() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = b.send_xoff());
+	assert!(let Ok(()) = b.send_xon());
+	assert!(let Ok(()) = b.resume_output());
+	let mut buffer = [0; 2];
+	assert!(let Ok(()) = a.read_exact(&mut buffer));
+	assert!(buffer == [0x13, 0x11]);
+}
+
+#[test]
+fn pause_resume_input() {
+	let_assert!(Ok((a, mut b)) = SerialPort::pair());
+	let_assert!(Err(e) = b.pause_input());
+	assert!(e.kind() == std::io::ErrorKind::Unsupported);
+
+	let_assert!(Ok(mut settings) = b.get_configuration());
+	settings.set_flow_control(serial2::FlowControl::XonXoff);
+	assert!(let Ok(()) = b.set_configuration(&settings));
+	assert!(let Ok(()) = b.pause_input());
+	assert!(let Ok(()) = b.resume_input());
+	let mut buffer = [0; 2];
+	assert!(let Ok(()) = a.read_exact(&mut buffer));
+	assert!(buffer == [0x13, 0x11]);
+}
+
+#[test]
+fn wait_tx_empty() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.write_all(b"Hello!"));
+	assert!(let Ok(()) = a.wait_tx_empty(Duration::from_secs(1)));
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello!");
+}
+
+#[test]
+fn is_tx_empty() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	assert!(let Ok(true) = a.is_tx_empty());
+	assert!(let Ok(()) = a.write_all(b"Hello!"));
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(let Ok(true) = a.is_tx_empty());
+}
+
+#[test]
+fn send_break() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let start = Instant::now();
+	assert!(let Ok(()) = b.send_break(Duration::from_millis(50)));
+	assert!(start.elapsed() >= Duration::from_millis(50));
+
+	// The port is usable again after the break.
+	assert!(let Ok(()) = b.write_all(b"Hello!"));
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = a.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello!");
+}
+
+#[test]
+fn set_break() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = b.set_break(true));
+	assert!(let Ok(()) = b.set_break(false));
+
+	let_assert!(Ok((control, _io)) = a.split_control());
+	assert!(let Ok(()) = control.set_break(true));
+	assert!(let Ok(()) = control.set_break(false));
+}
+
+#[test]
+fn bytes_to_read() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	assert!(let Ok(0) = b.bytes_to_read());
+	assert!(let Ok(()) = a.write_all(b"Hello!"));
+	let deadline = Instant::now() + Duration::from_secs(1);
+	while b.bytes_to_read().ok() != Some(6) && Instant::now() < deadline {
+		std::thread::sleep(Duration::from_millis(1));
+	}
+	assert!(let Ok(6) = b.bytes_to_read());
+
+	let mut buffer = [0; 4];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(let Ok(2) = b.bytes_to_read());
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bytes_to_write() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	assert!(let Ok(0) = a.bytes_to_write());
+	assert!(let Ok(()) = a.write_all(b"Hello!"));
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(let Ok(0) = a.bytes_to_write());
+}
+
+#[test]
+fn reader_writer_claims() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(mut reader) = a.reader());
+	let_assert!(Err(e) = a.reader());
+	assert!(e.kind() == std::io::ErrorKind::ResourceBusy);
+
+	let_assert!(Ok(mut writer) = b.writer());
+	assert!(let Err(_) = b.writer());
+	assert!(let Ok(_) = b.reader());
+	assert!(let Ok(()) = writer.write_all(b"Hello!"));
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = reader.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello!");
+
+	drop(reader);
+	assert!(let Ok(_) = a.reader());
+}
+
+#[test]
+fn split_halves() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok((c, _d)) = SerialPort::pair());
+	let (mut read_half, mut write_half) = a.split();
+	let reader = std::thread::spawn(move || {
+		let mut buffer = [0; 6];
+		assert!(let Ok(()) = read_half.read_exact(&mut buffer));
+		assert!(&buffer == b"Hello!");
+		read_half
+	});
+	assert!(let Ok(()) = write_half.write_all(b"ping"));
+	let mut buffer = [0; 4];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(let Ok(()) = b.write_all(b"Hello!"));
+	let read_half = reader.join().unwrap();
+
+	// Halves of different serial ports can not be joined.
+	let (other_read_half, other_write_half) = c.split();
+	let_assert!(Err((read_half, other_write_half)) = read_half.unsplit(other_write_half));
+	assert!(let Ok(_) = read_half.unsplit(write_half));
+	assert!(let Ok(_) = other_read_half.unsplit(other_write_half));
+}
+
+#[test]
+fn write_frame() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let frame = [IoSlice::new(b""), IoSlice::new(b"Hello"), IoSlice::new(b"!")];
+	assert!(let Ok(()) = a.write_frame(&frame));
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello!");
+}
+
+#[test]
+fn inter_byte_delay() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	assert!(a.get_inter_byte_delay() == None);
+
+	let delay = InterByteDelay::after_every(2, Duration::from_millis(50));
+	a.set_inter_byte_delay(Some(delay));
+	assert!(a.get_inter_byte_delay() == Some(delay));
+
+	// Each write transmits at most one group.
+	assert!(let Ok(2) = a.write(b"Hello"));
+
+	// Two more pauses are needed for the remaining three bytes.
+	let start = std::time::Instant::now();
+	assert!(let Ok(()) = a.write_all(b"llo"));
+	assert!(start.elapsed() >= Duration::from_millis(100));
+
+	let mut buffer = [0; 5];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello");
+
+	a.set_inter_byte_delay(None);
+	assert!(a.get_inter_byte_delay() == None);
+}
+
+#[test]
+fn write_guarded() {
+	let_assert!(Ok((a, mut b)) = SerialPort::pair());
+	let_assert!(Ok(()) = b.set_read_timeout(Duration::from_secs(1)));
+
+	std::thread::scope(|scope| {
+		let start = std::time::Instant::now();
+		scope.spawn(|| {
+			// This write is held back until the guard time after the escape sequence has passed.
+			std::thread::sleep(Duration::from_millis(20));
+			assert!(let Ok(()) = a.write_all(b"ATH"));
+		});
+		assert!(let Ok(()) = a.write_guarded(b"+++", Duration::from_millis(50), Duration::from_millis(100)));
+		assert!(start.elapsed() >= Duration::from_millis(150));
+
+		let mut buffer = [0; 3];
+		assert!(let Ok(()) = b.read_exact(&mut buffer));
+		assert!(&buffer == b"+++");
+	});
+
+	let mut buffer = [0; 3];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"ATH");
+}
+
+#[test]
+fn byte_transform() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	assert!(a.get_byte_transform().is_none());
+
+	// Transmitted data is inverted and bit-reversed on the wire.
+	a.set_byte_transform(Some(ByteTransform::new().invert().reverse_bits()));
+	assert!(let Ok(()) = a.write_all(&[0x01, 0x0F]));
+	let mut buffer = [0; 2];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(buffer == [0x7F, 0x0F]);
+
+	// Received data is transformed back.
+	assert!(let Ok(()) = b.write_all(&[0x7F, 0x0F]));
+	assert!(let Ok(()) = a.read_exact(&mut buffer));
+	assert!(buffer == [0x01, 0x0F]);
+
+	// Custom transforms are applied as-is.
+	b.set_byte_transform(Some(ByteTransform::from_fn(|byte| byte.wrapping_add(1), |byte| byte.wrapping_sub(1))));
+	a.set_byte_transform(None);
+	assert!(let Ok(()) = b.write_all(b"HAL"));
+	let mut buffer = [0; 3];
+	assert!(let Ok(()) = a.read_exact(&mut buffer));
+	assert!(&buffer == b"IBM");
+}
+
+#[test]
+fn try_clone_keeps_handle_state() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let delay = InterByteDelay::after_every(2, Duration::from_millis(1));
+	a.set_inter_byte_delay(Some(delay));
+	a.set_byte_transform(Some(ByteTransform::new().invert()));
+
+	let_assert!(Ok(clone) = a.try_clone());
+	assert!(clone.get_inter_byte_delay() == Some(delay));
+	assert!(let Ok(()) = clone.write_all(&[0x00, 0x0F]));
+	let mut buffer = [0; 2];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(buffer == [0xFF, 0xF0]);
+
+	// The state is copied, not shared.
+	clone.set_byte_transform(None);
+	assert!(a.get_byte_transform().is_some());
+}
+
+#[test]
+fn software_parity() {
+	let_assert!(Ok((mut a, _b)) = SerialPort::pair());
+	assert!(a.get_software_parity() == None);
+	assert!(a.take_software_parity_errors() == 0);
+
+	// Eight data bits leave no room for a parity bit.
+	let_assert!(Err(e) = a.set_software_parity(Some(SoftwareParity::Even)));
+	assert!(e.kind() == std::io::ErrorKind::InvalidInput);
+	assert!(a.get_software_parity() == None);
+
+	assert!(SoftwareParity::Even.parity_bit(0x01) == true);
+	assert!(SoftwareParity::Even.parity_bit(0x03) == false);
+	assert!(SoftwareParity::Odd.parity_bit(0x01) == false);
+	assert!(SoftwareParity::Odd.parity_bit(0x03) == true);
+	assert!(SoftwareParity::Mark.parity_bit(0x00) == true);
+	assert!(SoftwareParity::Space.parity_bit(0x7F) == false);
+}
+
+#[test]
+fn deadline_helpers() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_secs(5)));
+
+	// Data that trickles in before the deadline is read completely.
+	let writer = std::thread::spawn(move || {
+		for chunk in [&b"Hel"[..], b"lo", b"!"] {
+			std::thread::sleep(Duration::from_millis(20));
+			assert!(let Ok(()) = b.write_all(chunk));
+		}
+		b
+	});
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = a.read_exact_deadline(&mut buffer, Instant::now() + Duration::from_secs(2)));
+	assert!(&buffer == b"Hello!");
+	let_assert!(Ok(mut b) = writer.join());
+
+	// The deadline limits the total time, and the read timeout is restored afterwards.
+	let start = Instant::now();
+	assert!(let Ok(()) = b.write_all(b"Hi"));
+	let_assert!(Err(e) = a.read_exact_deadline(&mut buffer, Instant::now() + Duration::from_millis(100)));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+	assert!(start.elapsed() < Duration::from_secs(1));
+	let_assert!(Ok(timeout) = a.get_read_timeout());
+	assert!(timeout == Duration::from_secs(5));
+
+	assert!(let Ok(()) = b.write_all_deadline(b"Bye", Instant::now() + Duration::from_secs(1)));
+	let mut buffer = [0; 3];
+	assert!(let Ok(()) = a.read_exact(&mut buffer));
+	assert!(&buffer == b"Bye");
+}
+
+#[test]
+fn split_control() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok((mut control, mut io)) = a.split_control());
+	assert!(let Ok(()) = io.set_read_timeout(Duration::from_millis(300)));
+
+	assert!(let Ok(()) = b.write_all(b"Hello!"));
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = io.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello!");
+
+	// A configuration change waits for the read in progress.
+	let reader = std::thread::spawn(move || {
+		let mut buffer = [0; 1];
+		let_assert!(Err(e) = io.read(&mut buffer));
+		assert!(e.kind() == std::io::ErrorKind::TimedOut);
+		io
+	});
+	std::thread::sleep(Duration::from_millis(50));
+	let start = Instant::now();
+	let_assert!(Ok(settings) = control.get_configuration());
+	assert!(let Ok(()) = control.set_configuration(&settings));
+	assert!(start.elapsed() >= Duration::from_millis(150));
+	let_assert!(Ok(io) = reader.join());
+
+	assert!(let Ok(()) = io.write_all(b"Bye"));
+	let mut buffer = [0; 3];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"Bye");
+}
+
+#[test]
+fn modem_lines_match_individual_reads() {
+	let_assert!(Ok((a, _b)) = SerialPort::pair());
+	// Pseudo terminals may not support modem control lines, but both functions must agree.
+	match a.read_modem_lines() {
+		Ok(_) => {
+			assert!(let Ok(()) = a.set_modem_lines(Some(true), Some(false)));
+			let_assert!(Ok(lines) = a.read_modem_lines());
+			assert!(lines.rts() == Some(true));
+			assert!(lines.dtr() == Some(false));
+			let_assert!(Ok(cts) = a.read_cts());
+			assert!(lines.cts() == cts);
+		},
+		Err(e) => {
+			let_assert!(Err(e2) = a.read_cts());
+			assert!(e.kind() == e2.kind());
+			let_assert!(Err(_) = a.set_modem_lines(Some(true), None));
+		},
+	}
+}
+
+#[test]
+fn owned_fd_conversions() {
+	use std::os::unix::io::{AsFd, AsRawFd, OwnedFd};
+
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let raw_fd = a.as_raw_fd();
+	assert!(a.as_fd().as_raw_fd() == raw_fd);
+	let (read_half, write_half) = a.split();
+	assert!(read_half.as_fd().as_raw_fd() == raw_fd);
+	assert!(write_half.as_fd().as_raw_fd() == raw_fd);
+	let_assert!(Ok(a) = read_half.unsplit(write_half));
+
+	let fd = OwnedFd::from(a);
+	assert!(fd.as_raw_fd() == raw_fd);
+	let a = SerialPort::from(fd);
+	assert!(let Ok(()) = a.write_all(b"Hello!"));
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello!");
+}
+
+#[test]
+fn debug_format() {
+	use std::os::unix::io::AsRawFd;
+
+	let_assert!(Ok((_a, b)) = SerialPort::pair());
+	let debug = format!("{b:?}");
+	assert!(debug.starts_with("SerialPort {"));
+	assert!(debug.contains(&format!("fd: {}", b.as_raw_fd())));
+	assert!(debug.contains("baud_rate: "));
+	#[cfg(target_os = "linux")]
+	assert!(debug.contains("path: \"/dev/pts/"));
+}
+
+#[test]
+fn port_path() {
+	use std::os::unix::io::AsRawFd;
+
+	let_assert!(Ok((_a, b)) = SerialPort::pair());
+	let_assert!(Ok(device) = std::fs::read_link(format!("/dev/fd/{}", b.as_raw_fd())));
+	#[cfg(target_os = "linux")]
+	assert!(b.path().as_ref() == Some(&device));
+
+	let_assert!(Ok(port) = SerialPort::open(&device, KeepSettings));
+	assert!(port.path().as_ref() == Some(&device));
+	assert!(port.name().as_deref() == device.file_name().and_then(|name| name.to_str()));
+	let_assert!(Ok(clone) = port.try_clone());
+	assert!(clone.path().as_ref() == Some(&device));
+
+	let (read_half, write_half) = port.split();
+	assert!(read_half.path().as_ref() == Some(&device));
+	assert!(write_half.name() == read_half.name());
+}
+
+#[test]
+fn open_with_builder() {
+	use std::os::unix::io::AsRawFd;
+
+	let_assert!(Ok((_a, b)) = SerialPort::pair());
+	let_assert!(Ok(device) = std::fs::read_link(format!("/dev/fd/{}", b.as_raw_fd())));
+
+	let_assert!(Ok(port) = SerialPort::builder(&device).read_only().open());
+	assert!(let Err(_) = port.write(b"Hello!"));
+	drop(port);
+
+	// Open exclusively last: the exclusive mode outlives the port as long as the other side is open.
+	let_assert!(Ok(port) = SerialPort::builder(&device).baud_rate(19200).exclusive(true).open());
+	let_assert!(Ok(settings) = port.get_configuration());
+	assert!(let Ok(19200) = settings.get_baud_rate());
+	assert!(port.path().as_ref() == Some(&device));
+}
+
+#[test]
+fn open_keep_settings() {
+	use std::os::unix::io::AsRawFd;
+
+	let_assert!(Ok((_a, mut b)) = SerialPort::pair());
+	let_assert!(Ok(device) = std::fs::read_link(format!("/dev/fd/{}", b.as_raw_fd())));
+	let_assert!(Ok(mut settings) = b.get_configuration());
+	assert!(let Ok(()) = settings.set_baud_rate(19200));
+	assert!(let Ok(()) = b.set_configuration(&settings));
+
+	let_assert!(Ok(port) = SerialPort::open(&device, KeepSettings));
+	let_assert!(Ok(settings) = port.get_configuration());
+	assert!(let Ok(19200) = settings.get_baud_rate());
+	let_assert!(Some(applied) = port.get_applied_configuration());
+	assert!(let Ok(19200) = applied.get_baud_rate());
+	assert!(let Ok(None) = port.check_configuration_drift());
+}
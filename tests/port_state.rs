@@ -0,0 +1,64 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::{ByteTransform, InterByteDelay, PortState, SerialPort};
+use std::os::unix::io::{AsRawFd, IntoRawFd};
+use std::time::Duration;
+
+/// Check if the close-on-exec flag of a file descriptor is set.
+#[cfg(target_os = "linux")]
+fn close_on_exec(fd: i32) -> bool {
+	let_assert!(Ok(info) = std::fs::read_to_string(format!("/proc/self/fdinfo/{fd}")));
+	let_assert!(Some(flags) = info.lines().find_map(|line| line.strip_prefix("flags:")));
+	let_assert!(Ok(flags) = u32::from_str_radix(flags.trim(), 8));
+	flags & 0o2000000 != 0
+}
+
+#[test]
+fn export_and_import() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_millis(1234)));
+	a.set_inter_byte_delay(Some(InterByteDelay::after_every(4, Duration::from_micros(250))));
+	a.set_byte_transform(Some(ByteTransform::new().xor(0x5A)));
+	#[cfg(target_os = "linux")]
+	assert!(close_on_exec(a.as_raw_fd()));
+
+	// Received data stays in the kernel buffer across the handover.
+	assert!(let Ok(()) = b.write_all(&[0x12 ^ 0x5A, 0x34 ^ 0x5A]));
+
+	let_assert!(Ok(state) = a.export_state());
+	assert!(state.fd() == a.as_raw_fd());
+	#[cfg(target_os = "linux")]
+	assert!(!close_on_exec(a.as_raw_fd()));
+	let serialized = state.to_string();
+	let fd = a.into_raw_fd();
+
+	let_assert!(Ok(state) = serialized.parse::<PortState>());
+	let_assert!(Ok(port) = unsafe { SerialPort::import_state(&state) });
+	assert!(port.as_raw_fd() == fd);
+	#[cfg(target_os = "linux")]
+	assert!(close_on_exec(fd));
+	let_assert!(Ok(read_timeout) = port.get_read_timeout());
+	assert!(read_timeout == Duration::from_millis(1234));
+	let_assert!(Some(delay) = port.get_inter_byte_delay());
+	assert!(delay.group_size() == 4);
+	assert!(delay.delay() == Duration::from_micros(250));
+
+	let mut buffer = [0; 2];
+	assert!(let Ok(()) = port.read_exact(&mut buffer));
+	assert!(buffer == [0x12, 0x34]);
+}
+
+#[test]
+fn parse_errors() {
+	let_assert!(Err(e) = "something else".parse::<PortState>());
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+	let_assert!(Err(_) = "serial2-port-state-v1;read_timeout_ms=10".parse::<PortState>());
+	let_assert!(Err(_) = "serial2-port-state-v1;fd=3;transform=00".parse::<PortState>());
+	let_assert!(Ok(state) = "serial2-port-state-v1;fd=3;future_field=1".parse::<PortState>());
+	assert!(state.fd() == 3);
+
+	// Importing a closed file descriptor fails without taking ownership of it.
+	let_assert!(Ok(state) = "serial2-port-state-v1;fd=999999".parse::<PortState>());
+	let_assert!(Err(_) = unsafe { SerialPort::import_state(&state) });
+}
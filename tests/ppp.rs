@@ -0,0 +1,92 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::buffered::BufferedReader;
+use serial2::framing::Framer;
+use serial2::ppp::{HdlcFramer, PppLink, DEFAULT_ACCM};
+use serial2::SerialPort;
+use std::time::Duration;
+
+#[test]
+fn hdlc_encoding() {
+	let frame = [0xFF, 0x03, 0xC0, 0x21, 0x7E, 0x7D, 0x11];
+
+	let encoded = HdlcFramer::encode(&frame, DEFAULT_ACCM);
+	assert!(encoded[..8] == [0x7E, 0xFF, 0x7D, 0x23, 0xC0, 0x21, 0x7D, 0x5E]);
+	assert!(encoded[8..11] == [0x7D, 0x5D, 0x7D]);
+	assert!(encoded.last() == Some(&0x7E));
+
+	// Without an ACCM, only the flag and escape bytes are escaped.
+	let encoded = HdlcFramer::encode(&frame, 0);
+	assert!(encoded[..6] == [0x7E, 0xFF, 0x03, 0xC0, 0x21, 0x7D]);
+
+	// Frames share the flag byte between them.
+	let mut data = HdlcFramer::encode(&frame, DEFAULT_ACCM);
+	data.extend_from_slice(&HdlcFramer::encode(b"\xFF\x03next", DEFAULT_ACCM)[1..]);
+	let_assert!(Ok(Some((decoded, len))) = HdlcFramer.decode(&data));
+	assert!(decoded == frame);
+	assert!(data[len] == 0x7E);
+	let_assert!(Ok(Some((decoded, _))) = HdlcFramer.decode(&data[len..]));
+	assert!(decoded == b"\xFF\x03next");
+
+	// A corrupted frame is rejected.
+	let mut corrupted = HdlcFramer::encode(&frame, DEFAULT_ACCM);
+	corrupted[2] ^= 1;
+	let_assert!(Err(e) = HdlcFramer.decode(&corrupted));
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn ppp_link_frames() {
+	let_assert!(Ok((a, mut b)) = SerialPort::pair());
+	assert!(let Ok(()) = b.set_read_timeout(Duration::from_secs(1)));
+	let mut link = PppLink::with_received(a, b"\r\nCONNECT\r\n".to_vec());
+	assert!(let Ok(()) = link.port_mut().set_read_timeout(Duration::from_secs(1)));
+
+	let mut corrupted = HdlcFramer::encode(b"\xFF\x03bad", DEFAULT_ACCM);
+	corrupted[3] ^= 1;
+	assert!(let Ok(()) = b.write_all(&corrupted));
+	assert!(let Ok(()) = b.write_all(&HdlcFramer::encode(b"\xFF\x03good", DEFAULT_ACCM)));
+	let_assert!(Ok(frame) = link.read_frame());
+	assert!(frame == b"\xFF\x03good");
+	assert!(link.invalid_frames() == 1);
+
+	link.set_tx_accm(0);
+	assert!(let Ok(()) = link.write_frame(b"\xFF\x03\x11"));
+	let expected = HdlcFramer::encode(b"\xFF\x03\x11", 0);
+	let mut received = vec![0; expected.len()];
+	assert!(let Ok(()) = b.read_exact(&mut received));
+	assert!(received == expected);
+}
+
+#[test]
+fn detach_buffered_reader() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(mut reader) = BufferedReader::new(a));
+	reader.set_read_timeout(Duration::from_secs(1));
+
+	assert!(let Ok(()) = b.write_all(b"CONNECT\r\n"));
+	let mut line = Vec::new();
+	assert!(let Ok(9) = reader.read_until(b'\n', &mut line));
+
+	// Data received before detaching is handed over with the port.
+	assert!(let Ok(()) = b.write_all(b"\x7E"));
+	while reader.available() == 0 {
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	let mut detached = reader.detach();
+	let_assert!(Ok(mut port) = detached.port().try_clone());
+	assert!(let Ok(()) = port.set_read_timeout(Duration::from_secs(1)));
+	let mut link = PppLink::with_received(port, detached.take_buffered());
+	assert!(let Ok(()) = b.write_all(&HdlcFramer::encode(b"\xFF\x03lcp", DEFAULT_ACCM)[1..]));
+	let_assert!(Ok(frame) = link.read_frame());
+	assert!(frame == b"\xFF\x03lcp");
+	drop(link);
+	drop(detached);
+
+	// The reader continues after the port is re-attached.
+	assert!(let Ok(()) = b.write_all(b"NO CARRIER\r\n"));
+	line.clear();
+	assert!(let Ok(_) = reader.read_until(b'\n', &mut line));
+	assert!(line == b"NO CARRIER\r\n");
+}
@@ -0,0 +1,54 @@
+#![cfg(target_os = "linux")]
+
+use assert2::{assert, let_assert};
+use serial2::{FlowControl, SerialPort};
+use std::time::Duration;
+
+/// Create a unique path for a profile file.
+fn profile_path(name: &str) -> std::path::PathBuf {
+	std::env::temp_dir().join(format!("serial2-{name}-{}.profile", std::process::id()))
+}
+
+#[test]
+fn save_and_load_profile() {
+	let path = profile_path("save-load");
+	// All pseudo terminal masters share the path /dev/ptmx, so use the slave side to get a unique identity.
+	let_assert!(Ok((_master, mut a)) = SerialPort::pair());
+	let_assert!(Ok(mut settings) = a.get_configuration());
+	assert!(let Ok(()) = settings.set_baud_rate(19200));
+	settings.set_flow_control(FlowControl::XonXoff);
+	assert!(let Ok(()) = a.set_configuration(&settings));
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_millis(123)));
+	assert!(let Ok(()) = a.save_profile(&path));
+
+	// Change the configuration, and restore it from the profile.
+	assert!(let Ok(()) = settings.set_baud_rate(9600));
+	settings.set_flow_control(FlowControl::None);
+	assert!(let Ok(()) = a.set_configuration(&settings));
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_secs(1)));
+	assert!(let Ok(()) = a.load_profile(&path));
+	let_assert!(Ok(settings) = a.get_configuration());
+	assert!(let Ok(19200) = settings.get_baud_rate());
+	assert!(let Ok(FlowControl::XonXoff) = settings.get_flow_control());
+	assert!(a.get_read_timeout().ok() == Some(Duration::from_millis(123)));
+
+	// Another device has no configuration in the profile.
+	let_assert!(Ok((_master, mut c)) = SerialPort::pair());
+	let_assert!(Err(e) = c.load_profile(&path));
+	assert!(e.kind() == std::io::ErrorKind::NotFound);
+	let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn corrupted_profile() {
+	let path = profile_path("corrupted");
+	let_assert!(Ok((_master, mut a)) = SerialPort::pair());
+	assert!(let Ok(()) = a.save_profile(&path));
+	let_assert!(Ok(data) = std::fs::read_to_string(&path));
+	let_assert!(Some(baud_rate) = data.lines().find(|line| line.starts_with("baud_rate")));
+	assert!(let Ok(()) = std::fs::write(&path, data.replace(baud_rate, "baud_rate = 1234")));
+
+	let_assert!(Err(e) = a.load_profile(&path));
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+	let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,104 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::reconnect::{AutoReconnectPort, ConnectionEvent};
+use serial2::SerialPort;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+#[test]
+fn reconnect_after_disconnect() {
+	let (peers, new_peer) = channel();
+	let mut attempts = 0;
+	let_assert!(Ok(mut port) = AutoReconnectPort::new(move || {
+		attempts += 1;
+		if attempts == 1 {
+			return Err(std::io::ErrorKind::NotFound.into());
+		}
+		let (a, b) = SerialPort::pair()?;
+		peers.send(b).unwrap();
+		Ok(a)
+	}));
+	port.set_backoff(Duration::from_millis(50), Duration::from_millis(200));
+	assert!(port.get_backoff() == (Duration::from_millis(50), Duration::from_millis(200)));
+	let events = port.subscribe();
+
+	// Data written before the first connection is buffered.
+	assert!(let Ok(()) = port.write_all(b"early"));
+	let_assert!(Ok(mut peer) = new_peer.recv_timeout(Duration::from_secs(2)));
+	let_assert!(Ok(ConnectionEvent::Connected) = events.recv_timeout(Duration::from_secs(1)));
+	assert!(port.is_connected());
+	assert!(port.buffered_bytes() == 0);
+	let_assert!(Ok(()) = peer.set_read_timeout(Duration::from_secs(1)));
+	let mut buffer = [0; 5];
+	assert!(let Ok(()) = peer.read_exact(&mut buffer));
+	assert!(&buffer == b"early");
+
+	assert!(let Ok(()) = peer.write_all(b"hi"));
+	let mut buffer = [0; 2];
+	let_assert!(Ok(2) = port.read(&mut buffer));
+	assert!(&buffer == b"hi");
+
+	// Losing the peer is detected by a read, which continues on the new connection.
+	drop(peer);
+	let read = std::thread::scope(|scope| {
+		let reader = scope.spawn(|| port.read(&mut buffer));
+		let_assert!(Ok(peer) = new_peer.recv_timeout(Duration::from_secs(2)));
+		assert!(let Ok(()) = peer.write_all(b"ok"));
+		reader.join().unwrap()
+	});
+	assert!(let Ok(2) = read);
+	assert!(&buffer == b"ok");
+	let_assert!(Ok(ConnectionEvent::Disconnected(_)) = events.recv_timeout(Duration::from_secs(1)));
+	let_assert!(Ok(ConnectionEvent::Connected) = events.recv_timeout(Duration::from_secs(1)));
+}
+
+#[test]
+fn write_buffer_is_bounded() {
+	let_assert!(Ok(mut port) = AutoReconnectPort::new(|| Err(std::io::ErrorKind::NotFound.into())));
+	port.set_write_buffer_capacity(4);
+	port.set_read_timeout(Duration::from_millis(50));
+	assert!(port.is_connected() == false);
+
+	assert!(let Ok(()) = port.write_all(b"abc"));
+	let_assert!(Err(e) = port.write_all(b"de"));
+	assert!(e.kind() == std::io::ErrorKind::NotConnected);
+	assert!(port.buffered_bytes() == 3);
+
+	let mut buffer = [0; 4];
+	let_assert!(Err(e) = port.read(&mut buffer));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn watchdog_reconnects_silent_port() {
+	let (peers, new_peer) = channel();
+	let_assert!(Ok(mut port) = AutoReconnectPort::new(move || {
+		let (a, b) = SerialPort::pair()?;
+		peers.send(b).unwrap();
+		Ok(a)
+	}));
+	let events = port.subscribe();
+	let_assert!(Ok(peer) = new_peer.recv_timeout(Duration::from_secs(2)));
+	// The first connection may be made before subscribing, so do not wait for the event.
+	assert!(let Ok(()) = port.wait_connected(Duration::from_secs(1)));
+	port.set_watchdog(Some(Duration::from_millis(300)));
+	assert!(port.get_watchdog() == Some(Duration::from_millis(300)));
+
+	// Received data feeds the watchdog.
+	let mut buffer = [0; 2];
+	for _ in 0..3 {
+		std::thread::sleep(Duration::from_millis(150));
+		assert!(let Ok(()) = peer.write_all(b"hi"));
+		let_assert!(Ok(2) = port.read(&mut buffer));
+	}
+	assert!(port.is_connected());
+
+	// A silent peer is disconnected, even though the port never reports an error.
+	let_assert!(
+		Ok(ConnectionEvent::Disconnected(std::io::ErrorKind::TimedOut)) = events.recv_timeout(Duration::from_secs(1))
+	);
+	let_assert!(Ok(_peer) = new_peer.recv_timeout(Duration::from_secs(2)));
+	let_assert!(Ok(ConnectionEvent::Connected) = events.recv_timeout(Duration::from_secs(1)));
+	drop(peer);
+}
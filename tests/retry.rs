@@ -0,0 +1,86 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::clock::Clock;
+use serial2::framing::SlipFramer;
+use serial2::retry::{CircuitState, RetryPolicy, RetryingTransport};
+use serial2::sim::{DeviceScript, Rule, SimulatedDevice};
+use serial2::SerialPort;
+use std::time::{Duration, Instant};
+
+#[test]
+fn retry_on_invalid_frame() {
+	let_assert!(Ok((port, device_port)) = SerialPort::pair());
+	// The first response is corrupted, the second one is valid.
+	let script = DeviceScript::new("corrupt")
+		.rule(Rule::new("req", [0xDB, 0xC0]).in_state("corrupt").goto("ok"))
+		.rule(Rule::new("req", SlipFramer::encode(b"pong")).in_state("ok"));
+	let_assert!(Ok(_device) = SimulatedDevice::spawn(device_port, script));
+
+	let mut policy = RetryPolicy::new();
+	policy.set_backoff(Duration::from_millis(1), Duration::from_millis(10));
+	let mut transport = RetryingTransport::new(port, SlipFramer, policy);
+	let_assert!(Ok(frame) = transport.transact(b"req", Duration::from_millis(500)));
+	assert!(frame == b"pong");
+	assert!(transport.stats().retries() == 1);
+	assert!(transport.stats().succeeded() == 1);
+	assert!(transport.circuit_state() == CircuitState::Closed);
+}
+
+#[test]
+fn circuit_breaker() {
+	let_assert!(Ok((mut port, _peer)) = SerialPort::pair());
+	assert!(let Ok(()) = port.set_read_timeout(Duration::from_secs(2)));
+	let mut policy = RetryPolicy::new();
+	policy.set_max_attempts(2);
+	policy.set_backoff(Duration::from_millis(1), Duration::from_millis(1));
+	policy.set_circuit_breaker(2, Duration::from_millis(200));
+	let mut transport = RetryingTransport::new(port, SlipFramer, policy);
+
+	for _ in 0..2 {
+		let_assert!(Err(e) = transport.transact(b"req", Duration::from_millis(20)));
+		assert!(e.kind() == std::io::ErrorKind::TimedOut);
+	}
+	assert!(transport.stats().retries() == 2);
+	assert!(transport.consecutive_failures() == 2);
+	assert!(let CircuitState::Open { .. } = transport.circuit_state());
+	let_assert!(Err(e) = transport.transact(b"req", Duration::from_millis(20)));
+	assert!(e.kind() == std::io::ErrorKind::ConnectionRefused);
+	assert!(transport.stats().rejected() == 1);
+
+	// A failed trial after the open time opens the circuit again.
+	std::thread::sleep(Duration::from_millis(200));
+	assert!(transport.circuit_state() == CircuitState::HalfOpen);
+	let_assert!(Err(_) = transport.transact(b"req", Duration::from_millis(20)));
+	assert!(let CircuitState::Open { .. } = transport.circuit_state());
+
+	transport.reset_circuit();
+	assert!(transport.circuit_state() == CircuitState::Closed);
+	let_assert!(Ok(timeout) = transport.port().get_read_timeout());
+	assert!(timeout == Duration::from_secs(2));
+}
+
+#[test]
+fn manual_clock() {
+	let_assert!(Ok((port, _peer)) = SerialPort::pair());
+	let mut policy = RetryPolicy::new();
+	policy.set_max_attempts(3);
+	policy.set_jitter(0.0);
+	policy.set_backoff(Duration::from_secs(10), Duration::from_secs(60));
+	policy.set_circuit_breaker(1, Duration::from_secs(3600));
+	let clock = Clock::manual();
+	let start = clock.now();
+	let mut transport = RetryingTransport::new(port, SlipFramer, policy);
+	transport.set_clock(clock.clone());
+
+	// The backoff delays of 10 and 20 seconds only advance the manual clock.
+	let real_start = Instant::now();
+	let_assert!(Err(e) = transport.transact(b"req", Duration::from_millis(20)));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+	assert!(real_start.elapsed() < Duration::from_secs(5));
+	assert!(clock.elapsed(start) == Duration::from_secs(30));
+
+	let_assert!(CircuitState::Open { .. } = transport.circuit_state());
+	clock.advance(Duration::from_secs(3600));
+	assert!(transport.circuit_state() == CircuitState::HalfOpen);
+}
@@ -0,0 +1,90 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::protocol::roomba::{packet, Mode, Roomba};
+use serial2::SerialPort;
+use std::time::Duration;
+
+/// Read exactly `len` bytes from a serial port.
+fn read_exact(port: &SerialPort, len: usize) -> Vec<u8> {
+	let mut buffer = vec![0; len];
+	assert!(let Ok(()) = port.read_exact(&mut buffer));
+	buffer
+}
+
+/// Create a stream packet with a valid checksum.
+fn stream_packet(payload: &[u8]) -> Vec<u8> {
+	let mut packet = vec![19, payload.len() as u8];
+	packet.extend_from_slice(payload);
+	let sum = packet.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+	packet.push(sum.wrapping_neg());
+	packet
+}
+
+#[test]
+fn commands() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let mut roomba = Roomba::new(a);
+	assert!(let Ok(()) = roomba.set_mode(Mode::Safe));
+	assert!(let Ok(()) = roomba.drive(-200, 500));
+	assert!(let Ok(()) = roomba.drive_direct(100, -100));
+	assert!(let Ok(()) = roomba.set_song(0, &[(60, 32), (64, 16)]));
+	assert!(let Ok(()) = roomba.start_stream(&[packet::BUMPS_AND_WHEEL_DROPS, packet::DISTANCE]));
+	assert!(let Err(_) = roomba.start_stream(&[200]));
+
+	assert!(read_exact(&b, 2) == [128, 131]);
+	assert!(read_exact(&b, 5) == [137, 0xFF, 0x38, 0x01, 0xF4]);
+	assert!(read_exact(&b, 5) == [145, 0x00, 0x64, 0xFF, 0x9C]);
+	assert!(read_exact(&b, 7) == [140, 0, 2, 60, 32, 64, 16]);
+	assert!(read_exact(&b, 4) == [148, 2, 7, 19]);
+}
+
+#[test]
+fn stream() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_millis(500)));
+	let mut roomba = Roomba::new(a);
+
+	let mut corrupt = stream_packet(&[7, 0x02]);
+	corrupt[3] = 0x03;
+	assert!(let Ok(()) = b.write_all(&[0x42]));
+	assert!(let Ok(()) = b.write_all(&stream_packet(&[7, 0x01, 19, 0xFF, 0xF6])));
+	assert!(let Ok(()) = b.write_all(&corrupt));
+	assert!(let Ok(()) = b.write_all(&stream_packet(&[35, 0x02])));
+
+	// The leading garbage byte is reported as invalid data.
+	let_assert!(Err(e) = roomba.read_stream());
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+
+	let_assert!(Ok(packets) = roomba.read_stream());
+	assert!(packets.len() == 2);
+	assert!(packets[0].id() == packet::BUMPS_AND_WHEEL_DROPS);
+	assert!(packets[0].value() == Some(1));
+	assert!(packets[1].id() == packet::DISTANCE);
+	assert!(packets[1].value() == Some(-10));
+
+	let_assert!(Err(e) = roomba.read_stream());
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+	let packets = loop {
+		match roomba.read_stream() {
+			Ok(packets) => break packets,
+			Err(e) => assert!(e.kind() == std::io::ErrorKind::InvalidData),
+		}
+	};
+	assert!(packets.len() == 1);
+	let_assert!(Some(value) = packets[0].value());
+	assert!(Mode::from_u8(value as u8) == Some(Mode::Safe));
+}
+
+#[test]
+fn query() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let mut roomba = Roomba::new(a);
+	let device = std::thread::spawn(move || {
+		assert!(read_exact(&b, 2) == [142, packet::VOLTAGE]);
+		assert!(let Ok(()) = b.write_all(&[0x3A, 0x98]));
+	});
+	let_assert!(Ok(sensor) = roomba.query(packet::VOLTAGE, Duration::from_secs(1)));
+	assert!(sensor.value() == Some(15000));
+	device.join().unwrap();
+}
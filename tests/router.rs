@@ -0,0 +1,53 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::framing::{FrameReader, Framer};
+use serial2::router::{Filter, Router};
+use serial2::SerialPort;
+use std::time::Duration;
+
+/// A frame consisting of an ID byte, a length byte and the payload.
+struct IdFramer;
+
+impl Framer for IdFramer {
+	type Frame = (u8, Vec<u8>);
+
+	fn decode(&mut self, data: &[u8]) -> std::io::Result<Option<(Self::Frame, usize)>> {
+		if data.len() < 2 || data.len() < 2 + data[1] as usize {
+			return Ok(None);
+		}
+		let len = 2 + data[1] as usize;
+		Ok(Some(((data[0], data[2..len].to_vec()), len)))
+	}
+}
+
+#[test]
+fn route_frames_by_filter() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(router) = Router::new(FrameReader::new(a, IdFramer)));
+	let low = router.subscribe(Filter::id_range(|frame: &(u8, Vec<u8>)| frame.0, 1..=2));
+	let hello = router.subscribe(Filter::new(|frame: &(u8, Vec<u8>)| frame.1 == b"hello"));
+	let three = router.subscribe(Filter::kind(|frame: &(u8, Vec<u8>)| frame.0, 3).and(Filter::all()));
+	let unmatched = router.subscribe_unmatched();
+	assert!(router.subscriptions() == 3);
+
+	assert!(let Ok(()) = b.write_all(b"\x01\x05hello\x03\x03abc\x09\x01x"));
+	let_assert!(Ok(frame) = low.recv_timeout(Duration::from_secs(1)));
+	assert!(frame == (1, b"hello".to_vec()));
+	let_assert!(Ok(frame) = hello.recv_timeout(Duration::from_secs(1)));
+	assert!(frame == (1, b"hello".to_vec()));
+	let_assert!(Ok(frame) = three.recv_timeout(Duration::from_secs(1)));
+	assert!(frame == (3, b"abc".to_vec()));
+	let_assert!(Ok(frame) = unmatched.recv_timeout(Duration::from_secs(1)));
+	assert!(frame == (9, b"x".to_vec()));
+	assert!(low.try_recv().is_err());
+	assert!(hello.try_recv().is_err());
+
+	// Dropped receivers end their subscription.
+	drop(hello);
+	assert!(let Ok(()) = b.write_all(b"\x02\x05hello"));
+	let_assert!(Ok(frame) = low.recv_timeout(Duration::from_secs(1)));
+	assert!(frame == (2, b"hello".to_vec()));
+	assert!(router.subscriptions() == 2);
+	assert!(router.take_error().is_none());
+}
@@ -0,0 +1,47 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::buffered::BufferedReader;
+use serial2::reconnect::AutoReconnectPort;
+use serial2::shutdown::Shutdown;
+use serial2::write_queue::WriteQueue;
+use serial2::SerialPort;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[test]
+fn shutdown_buffered_reader() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(reader) = BufferedReader::new(a));
+	assert!(let Ok(()) = b.write_all(b"Hello!"));
+	let mut buffer = [0; 6];
+	let_assert!(Ok(read) = reader.read(&mut buffer));
+	assert!(read > 0);
+
+	let start = Instant::now();
+	assert!(let Ok(()) = reader.shutdown(Duration::from_secs(2)));
+	assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn shutdown_write_queue() {
+	let_assert!(Ok((a, _b)) = SerialPort::pair());
+	let_assert!(Ok(queue) = WriteQueue::new(Arc::new(a)));
+	assert!(let Ok(()) = queue.shutdown(Duration::from_secs(2)));
+}
+
+#[test]
+fn shutdown_reconnect_port() {
+	// Keep the other side of the pair alive for as long as the port may be reopened.
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let a = Mutex::new(Some(a));
+	let_assert!(Ok(port) = AutoReconnectPort::new(move || {
+		a.lock().unwrap().take().ok_or_else(|| std::io::ErrorKind::NotFound.into())
+	}));
+	assert!(let Ok(()) = port.wait_connected(Duration::from_secs(1)));
+
+	let start = Instant::now();
+	assert!(let Ok(()) = port.shutdown(Duration::from_secs(2)));
+	assert!(start.elapsed() < Duration::from_secs(2));
+	drop(b);
+}
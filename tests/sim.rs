@@ -0,0 +1,64 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::sim::{DeviceScript, Rule, SimulatedDevice};
+use serial2::SerialPort;
+use std::time::{Duration, Instant};
+
+fn modem() -> DeviceScript {
+	DeviceScript::new("command")
+		.rule(Rule::new("AT\r", "OK\r\n").in_state("command"))
+		.rule(Rule::new("ATD", "CONNECT\r\n").in_state("command").after(Duration::from_millis(200)).goto("data"))
+		.rule(Rule::new("+++", "OK\r\n").in_state("data").goto("command"))
+}
+
+#[test]
+fn respond_to_patterns() {
+	let_assert!(Ok((mut port, device_port)) = SerialPort::pair());
+	let_assert!(Ok(device) = SimulatedDevice::spawn(device_port, modem()));
+	assert!(let Ok(()) = port.set_read_timeout(Duration::from_secs(2)));
+
+	// The pattern may arrive in pieces and between unrelated data.
+	assert!(let Ok(()) = port.write_all(b"noise A"));
+	assert!(let Ok(()) = port.write_all(b"T\r"));
+	let mut buffer = [0; 4];
+	assert!(let Ok(()) = port.read_exact(&mut buffer));
+	assert!(&buffer == b"OK\r\n");
+	assert!(device.state() == "command");
+
+	let start = Instant::now();
+	assert!(let Ok(()) = port.write_all(b"ATD5551234\r"));
+	let mut buffer = [0; 9];
+	assert!(let Ok(()) = port.read_exact(&mut buffer));
+	assert!(&buffer == b"CONNECT\r\n");
+	assert!(start.elapsed() >= Duration::from_millis(200));
+	assert!(device.state() == "data");
+
+	// Commands are not answered in the data state.
+	assert!(let Ok(()) = port.write_all(b"AT\r+++"));
+	let mut buffer = [0; 4];
+	assert!(let Ok(()) = port.read_exact(&mut buffer));
+	assert!(&buffer == b"OK\r\n");
+	assert!(device.state() == "command");
+	assert!(device.matched_rules() == 3);
+	assert!(device.take_error().is_none());
+}
+
+#[test]
+fn unsolicited_data() {
+	let script = DeviceScript::new("off")
+		.rule(Rule::new("", "$GPGGA\r\n").in_state("on").after(Duration::from_millis(50)));
+	let_assert!(Ok((mut port, device_port)) = SerialPort::pair());
+	let_assert!(Ok(device) = SimulatedDevice::spawn(device_port, script));
+	assert!(let Ok(()) = port.set_read_timeout(Duration::from_millis(300)));
+
+	let mut buffer = [0; 8];
+	let_assert!(Err(e) = port.read(&mut buffer));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+
+	device.set_state("on");
+	for _ in 0..3 {
+		assert!(let Ok(()) = port.read_exact(&mut buffer));
+		assert!(&buffer == b"$GPGGA\r\n");
+	}
+}
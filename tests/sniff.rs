@@ -0,0 +1,29 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::sniff::Sniffer;
+use serial2::SerialPort;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+#[test]
+fn sniff_without_changing_settings() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(path) = std::fs::read_link(format!("/dev/fd/{}", b.as_raw_fd())));
+	let_assert!(Ok(before) = b.get_configuration());
+
+	let_assert!(Ok(mut sniffer) = Sniffer::open(&path));
+	assert!(let Ok(()) = sniffer.set_read_timeout(Duration::from_millis(500)));
+	let_assert!(Ok(sniffed) = sniffer.get_configuration());
+	assert!(sniffed.get_baud_rate().ok() == before.get_baud_rate().ok());
+
+	assert!(let Ok(5) = a.write(b"hello"));
+	let mut buffer = [0; 16];
+	let_assert!(Ok(read) = sniffer.read(&mut buffer));
+	assert!(buffer[..read] == b"hello"[..read]);
+	drop(sniffer);
+
+	let_assert!(Ok(after) = b.get_configuration());
+	assert!(let Ok(None) = b.check_configuration_drift());
+	assert!(after.get_baud_rate().ok() == before.get_baud_rate().ok());
+}
@@ -0,0 +1,108 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::bridge::tcp::{TcpGateway, WriterPolicy};
+use serial2::SerialPort;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+fn gateway() -> (TcpGateway, SerialPort) {
+	let_assert!(Ok((port, mut peer)) = SerialPort::pair());
+	assert!(let Ok(()) = peer.set_read_timeout(Duration::from_millis(500)));
+	let_assert!(Ok(listener) = TcpListener::bind("127.0.0.1:0"));
+	let_assert!(Ok(gateway) = TcpGateway::new(port, listener));
+	(gateway, peer)
+}
+
+fn connect(gateway: &TcpGateway, clients: usize) -> TcpStream {
+	let_assert!(Ok(address) = gateway.local_addr());
+	let_assert!(Ok(stream) = TcpStream::connect(address));
+	assert!(let Ok(()) = stream.set_read_timeout(Some(Duration::from_secs(2))));
+	let start = Instant::now();
+	while gateway.clients().len() < clients {
+		assert!(start.elapsed() < Duration::from_secs(2));
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	stream
+}
+
+#[test]
+fn exclusive_writer() {
+	let (gateway, peer) = gateway();
+	let mut first = connect(&gateway, 1);
+	let mut second = connect(&gateway, 2);
+	let_assert!(Ok(first_address) = first.local_addr());
+	assert!(gateway.writer() == Some(first_address));
+
+	// All clients receive the data from the serial port.
+	assert!(let Ok(()) = peer.write_all(b"MEAS 1.25\n"));
+	for client in [&mut first, &mut second] {
+		let mut buffer = [0; 10];
+		assert!(let Ok(()) = client.read_exact(&mut buffer));
+		assert!(&buffer == b"MEAS 1.25\n");
+	}
+
+	// Only the writer can write to the serial port.
+	assert!(let Ok(()) = second.write_all(b"RESET\n"));
+	assert!(let Ok(()) = first.write_all(b"*IDN?\n"));
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = peer.read_exact(&mut buffer));
+	assert!(&buffer == b"*IDN?\n");
+	let mut buffer = [0; 6];
+	let_assert!(Err(e) = peer.read(&mut buffer));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+	assert!(gateway.stats().bytes_discarded() == 6);
+
+	// When the writer disconnects, the next client takes over.
+	drop(first);
+	let start = Instant::now();
+	while gateway.clients().len() > 1 {
+		assert!(start.elapsed() < Duration::from_secs(2));
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	let_assert!(Ok(second_address) = second.local_addr());
+	assert!(gateway.writer() == Some(second_address));
+	assert!(let Ok(()) = second.write_all(b"RESET\n"));
+	assert!(let Ok(()) = peer.read_exact(&mut buffer));
+	assert!(&buffer == b"RESET\n");
+}
+
+#[test]
+fn read_only_and_idle_kick() {
+	let (gateway, peer) = gateway();
+	gateway.set_writer_policy(WriterPolicy::ReadOnly);
+	gateway.set_idle_timeout(Some(Duration::from_millis(200)));
+	let mut observer = connect(&gateway, 1);
+	assert!(let Ok(()) = observer.write_all(b"RESET\n"));
+
+	// Read-only observers are not kicked.
+	std::thread::sleep(Duration::from_millis(400));
+	assert!(gateway.clients().len() == 1);
+	let mut buffer = [0; 6];
+	let_assert!(Err(_) = peer.read(&mut buffer));
+
+	// Idle clients that may write are kicked.
+	gateway.set_writer_policy(WriterPolicy::AllClients);
+	std::thread::sleep(Duration::from_millis(150));
+	assert!(gateway.clients().is_empty());
+	let mut buffer = [0; 1];
+	assert!(let Ok(0) = observer.read(&mut buffer));
+
+	let mut client = connect(&gateway, 1);
+	std::thread::sleep(Duration::from_millis(400));
+	assert!(gateway.clients().is_empty());
+	assert!(gateway.stats().clients_kicked() == 2);
+	assert!(let Ok(0) = client.read(&mut buffer));
+}
+
+#[test]
+fn max_clients() {
+	let (gateway, _peer) = gateway();
+	gateway.set_max_clients(1);
+	let _first = connect(&gateway, 1);
+	let mut second = connect(&gateway, 1);
+	let mut buffer = [0; 1];
+	assert!(let Ok(0) = second.read(&mut buffer));
+	assert!(gateway.clients().len() == 1);
+}
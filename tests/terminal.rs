@@ -0,0 +1,64 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::terminal::Miniterm;
+use serial2::SerialPort;
+use std::io::Read;
+use std::time::Duration;
+
+/// Input that returns each chunk from a separate read, with a delay before each chunk.
+struct SlowInput {
+	chunks: Vec<&'static [u8]>,
+}
+
+impl Read for SlowInput {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		std::thread::sleep(Duration::from_millis(200));
+		if self.chunks.is_empty() {
+			return Ok(0);
+		}
+		let chunk = self.chunks.remove(0);
+		buf[..chunk.len()].copy_from_slice(chunk);
+		Ok(chunk.len())
+	}
+}
+
+#[test]
+fn miniterm() {
+	let_assert!(Ok((mut a, mut b)) = SerialPort::pair());
+	let_assert!(Ok(()) = a.set_read_timeout(Duration::from_secs(3)));
+	let_assert!(Ok(()) = b.set_read_timeout(Duration::from_secs(1)));
+	let mut terminal = Miniterm::new(a);
+	assert!(terminal.get_exit_key() == 0x1D);
+	assert!(terminal.get_menu_key() == 0x14);
+	assert!(terminal.get_local_echo() == false);
+	assert!(terminal.get_hex_view() == false);
+
+	let_assert!(Ok(()) = b.write_all(b"hi"));
+	let input = SlowInput {
+		chunks: vec![b"ab", b"\x14e\x14hcd", b"\x14\x14", b"ef\x1Dgh"],
+	};
+	let mut output = Vec::new();
+	std::thread::scope(|scope| {
+		scope.spawn(|| {
+			std::thread::sleep(Duration::from_millis(500));
+			b.write_all(b"\x01\xAB").unwrap();
+		});
+		let_assert!(Ok(()) = terminal.run_with(input, &mut output));
+	});
+
+	// Everything before the exit key is sent, except menu commands.
+	let mut received = [0; 7];
+	let_assert!(Ok(()) = b.read_exact(&mut received));
+	assert!(&received == b"abcd\x14ef");
+
+	assert!(terminal.get_local_echo() == true);
+	assert!(terminal.get_hex_view() == true);
+	let output = String::from_utf8_lossy(&output);
+	assert!(output == "hi\r\n[local echo on]\r\n\r\n[hex view on]\r\ncd01 AB \x14ef");
+
+	// The read timeout is restored.
+	let port = terminal.into_inner();
+	let_assert!(Ok(timeout) = port.get_read_timeout());
+	assert!(timeout == Duration::from_secs(3));
+}
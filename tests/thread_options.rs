@@ -0,0 +1,62 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::buffered::BufferedReader;
+use serial2::write_queue::WriteQueue;
+use serial2::{SerialPort, ThreadOptions, ThreadPriority};
+use std::sync::Arc;
+
+#[test]
+fn thread_name() {
+	let_assert!(Ok((a, _b)) = SerialPort::pair());
+	let mut options = ThreadOptions::new();
+	options.set_name("custom-writer");
+	assert!(options.get_name() == Some("custom-writer"));
+	let_assert!(Ok(_queue) = WriteQueue::with_thread_options(Arc::new(a), &options));
+
+	#[cfg(target_os = "linux")]
+	{
+		let_assert!(Ok(tasks) = std::fs::read_dir("/proc/self/task"));
+		let found = tasks
+			.filter_map(|task| std::fs::read_to_string(task.ok()?.path().join("comm")).ok())
+			.any(|name| name.trim_end() == "custom-writer");
+		assert!(found);
+	}
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn thread_priority_and_affinity() {
+	let_assert!(Ok((a, _b)) = SerialPort::pair());
+	let mut options = ThreadOptions::new();
+	// Lowering the priority never requires special privileges.
+	options.set_priority(Some(ThreadPriority::Low));
+	options.set_affinity(Some(vec![0]));
+	let_assert!(Ok(_reader) = BufferedReader::with_thread_options(a, 1024, &options));
+}
+
+#[test]
+fn invalid_options_fail() {
+	let_assert!(Ok((a, _b)) = SerialPort::pair());
+	let mut options = ThreadOptions::new();
+	options.set_affinity(Some(Vec::new()));
+	let_assert!(Err(e) = BufferedReader::with_thread_options(a, 1024, &options));
+	assert!(e.kind() == std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn realtime_guard() {
+	use serial2::RealtimeGuard;
+
+	let_assert!(Err(e) = RealtimeGuard::enter(0));
+	assert!(e.kind() == std::io::ErrorKind::InvalidInput);
+
+	// Realtime scheduling may not be permitted in the test environment.
+	std::thread::spawn(|| match RealtimeGuard::enter(10) {
+		Ok(guard) => assert!(let Ok(()) = guard.leave()),
+		Err(e) => assert!(e.kind() == std::io::ErrorKind::PermissionDenied),
+	})
+	.join()
+	.unwrap();
+}
@@ -0,0 +1,37 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::transaction::{Length, Terminator};
+use serial2::SerialPort;
+use std::time::Duration;
+
+#[test]
+fn transact_with_matchers() {
+	let_assert!(Ok((mut a, b)) = SerialPort::pair());
+	let responder = std::thread::spawn(move || {
+		let mut buffer = [0; 4];
+		for response in [b"OK\r\nstale".as_slice(), b"12345678", b"[data]"] {
+			assert!(let Ok(()) = b.read_exact(&mut buffer));
+			assert!(let Ok(()) = b.write_all(response));
+		}
+	});
+
+	let_assert!(Ok(response) = a.transact(b"CMD1", Terminator(b'\n'), Duration::from_secs(1)));
+	assert!(response == b"OK\r\n");
+	let_assert!(Ok(response) = a.transact(b"CMD2", Length(8), Duration::from_secs(1)));
+	assert!(response == b"12345678");
+	let matcher = |data: &[u8]| data.iter().position(|&b| b == b']').map(|i| i + 1);
+	let_assert!(Ok(response) = a.transact(b"CMD3", matcher, Duration::from_secs(1)));
+	assert!(response == b"[data]");
+	responder.join().unwrap();
+}
+
+#[test]
+fn transact_timeout() {
+	let_assert!(Ok((mut a, _b)) = SerialPort::pair());
+	assert!(let Ok(()) = a.set_read_timeout(Duration::from_secs(3)));
+	let_assert!(Err(e) = a.transact(b"CMD", Length(1), Duration::from_millis(50)));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+	let_assert!(Ok(timeout) = a.get_read_timeout());
+	assert!(timeout == Duration::from_secs(3));
+}
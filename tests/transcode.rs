@@ -0,0 +1,40 @@
+use assert2::{assert, let_assert};
+use serial2::transcode::{DecodeMode, Encoding, Transcoder};
+
+#[test]
+fn decode_single_byte_encodings() {
+	assert!(let Ok("caf\u{E9}") = Encoding::Latin1.decode(b"caf\xE9", DecodeMode::Strict).as_deref());
+	let_assert!(Ok(text) = Encoding::Cp437.decode(b"25\xF8C \xC9\xCD\xBB", DecodeMode::Strict));
+	assert!(text == "25\u{B0}C \u{2554}\u{2550}\u{2557}");
+}
+
+#[test]
+fn decode_shift_jis() {
+	// "日本" followed by half-width katakana "ｱ".
+	let data = b"\x93\xFA\x96\x7B\xB1";
+	assert!(let Ok("\u{65E5}\u{672C}\u{FF71}") = Encoding::ShiftJis.decode(data, DecodeMode::Strict).as_deref());
+
+	// A character split over two chunks.
+	let mut transcoder = Transcoder::new(Encoding::ShiftJis, DecodeMode::Strict);
+	assert!(let Ok("") = transcoder.decode(&data[..1]).as_deref());
+	assert!(let Ok("\u{65E5}") = transcoder.decode(&data[1..3]).as_deref());
+	assert!(let Ok("\u{672C}\u{FF71}") = transcoder.decode(&data[3..]).as_deref());
+	assert!(let Ok("") = transcoder.finish().as_deref());
+}
+
+#[test]
+fn invalid_input() {
+	assert!(let Ok("a\u{FFFD}b") = Encoding::Utf8.decode(b"a\xFFb", DecodeMode::Lossy).as_deref());
+	let_assert!(Err(e) = Encoding::Utf8.decode(b"a\xFFb", DecodeMode::Strict));
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+
+	// An invalid trail byte only consumes the lead byte.
+	assert!(let Ok("\u{FFFD} A") = Encoding::ShiftJis.decode(b"\x81 A", DecodeMode::Lossy).as_deref());
+	let_assert!(Err(e) = Encoding::ShiftJis.decode(b"\xF0\x40", DecodeMode::Strict));
+	assert!(e.kind() == std::io::ErrorKind::InvalidData);
+
+	// An incomplete character at the end of the input.
+	let mut transcoder = Transcoder::new(Encoding::Utf8, DecodeMode::Lossy);
+	assert!(let Ok("ok") = transcoder.decode(b"ok\xE2\x82").as_deref());
+	assert!(let Ok("\u{FFFD}") = transcoder.finish().as_deref());
+}
@@ -0,0 +1,82 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::bridge::udp::UdpBridge;
+use serial2::framing::SlipFramer;
+use serial2::SerialPort;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+fn socket() -> UdpSocket {
+	let_assert!(Ok(socket) = UdpSocket::bind("127.0.0.1:0"));
+	assert!(let Ok(()) = socket.set_read_timeout(Some(Duration::from_secs(2))));
+	socket
+}
+
+#[test]
+fn forward_frames() {
+	let_assert!(Ok((a, mut b)) = SerialPort::pair());
+	assert!(let Ok(()) = b.set_read_timeout(Duration::from_secs(2)));
+	let_assert!(Ok(bridge) = UdpBridge::new(a, SlipFramer, socket()));
+	let_assert!(Ok(bridge_addr) = bridge.local_addr());
+	bridge.set_frame_encoder(SlipFramer::encode);
+
+	let listener = socket();
+	let_assert!(Ok(listener_addr) = listener.local_addr());
+	bridge.add_peer(listener_addr);
+	assert!(bridge.peers() == [listener_addr]);
+
+	// Serial frames are sent as datagrams.
+	assert!(let Ok(()) = b.write_all(&SlipFramer::encode(b"telemetry")));
+	let mut buffer = [0; 64];
+	let_assert!(Ok((len, _)) = listener.recv_from(&mut buffer));
+	assert!(&buffer[..len] == b"telemetry");
+
+	// Datagrams are written to the serial port.
+	assert!(let Ok(_) = listener.send_to(b"command", bridge_addr));
+	let expected = SlipFramer::encode(b"command");
+	let mut received = vec![0; expected.len()];
+	assert!(let Ok(()) = b.read_exact(&mut received));
+	assert!(received == expected);
+
+	// Datagrams from unknown senders are discarded.
+	let stranger = socket();
+	assert!(let Ok(_) = stranger.send_to(b"ignored", bridge_addr));
+	while bridge.stats().dropped_datagrams() == 0 {
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	let stats = bridge.stats();
+	assert!(stats.frames_sent() == 1);
+	assert!(stats.datagrams_received() == 1);
+}
+
+#[test]
+fn sequence_numbers() {
+	let_assert!(Ok((a, mut b)) = SerialPort::pair());
+	assert!(let Ok(()) = b.set_read_timeout(Duration::from_secs(2)));
+	let_assert!(Ok(bridge) = UdpBridge::new(a, SlipFramer, socket()));
+	let_assert!(Ok(bridge_addr) = bridge.local_addr());
+	bridge.set_sequence_numbers(true);
+	bridge.set_learn_peers(true);
+
+	// The first datagram subscribes the listener.
+	let listener = socket();
+	assert!(let Ok(_) = listener.send_to(b"\x00\x00\x00\x05first", bridge_addr));
+	let mut buffer = [0; 64];
+	assert!(let Ok(()) = b.read_exact(&mut buffer[..5]));
+	assert!(&buffer[..5] == b"first");
+
+	// Old sequence numbers are discarded.
+	assert!(let Ok(_) = listener.send_to(b"\x00\x00\x00\x04stale", bridge_addr));
+	assert!(let Ok(_) = listener.send_to(b"\x00\x00\x00\x06fresh", bridge_addr));
+	assert!(let Ok(()) = b.read_exact(&mut buffer[..5]));
+	assert!(&buffer[..5] == b"fresh");
+	assert!(bridge.stats().dropped_datagrams() == 1);
+
+	for expected in [0u32, 1] {
+		assert!(let Ok(()) = b.write_all(&SlipFramer::encode(b"data")));
+		let_assert!(Ok((len, _)) = listener.recv_from(&mut buffer));
+		assert!(buffer[..4] == expected.to_be_bytes());
+		assert!(&buffer[4..len] == b"data");
+	}
+}
@@ -0,0 +1,189 @@
+#![cfg(unix)]
+
+use assert2::{assert, let_assert};
+use serial2::write_queue::{SharedWriter, WriteQueue};
+use serial2::{Priority, SerialPort};
+use std::io::IoSlice;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[test]
+fn queued_writes_are_written_whole() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(queue) = WriteQueue::new(Arc::new(a)));
+
+	queue.queue(b"Hello ".as_slice(), Priority::Normal);
+	queue.queue(b"world!".as_slice(), Priority::Normal);
+	assert!(let Ok(()) = queue.flush());
+	assert!(queue.pending() == 0);
+	assert!(queue.pending_bytes() == 0);
+
+	let mut buffer = [0; 12];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello world!");
+}
+
+#[test]
+fn cancel_written_data_fails() {
+	let_assert!(Ok((a, _b)) = SerialPort::pair());
+	let_assert!(Ok(queue) = WriteQueue::new(Arc::new(a)));
+
+	let id = queue.queue(b"Hello!".as_slice(), Priority::Urgent);
+	assert!(let Ok(()) = queue.flush());
+	assert!(queue.cancel(id) == false);
+	assert!(queue.set_priority(id, Priority::Low) == false);
+}
+
+#[test]
+fn queue_frame() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(queue) = WriteQueue::new(Arc::new(a)));
+
+	let frame = [IoSlice::new(b"Hello"), IoSlice::new(b" "), IoSlice::new(b"world!")];
+	queue.queue_frame(&frame, Priority::Normal);
+	assert!(queue.pending() <= 1);
+	assert!(let Ok(()) = queue.flush());
+
+	let mut buffer = [0; 12];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello world!");
+}
+
+#[test]
+fn queue_guarded() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(queue) = WriteQueue::new(Arc::new(a)));
+
+	let start = Instant::now();
+	queue.queue(b"AT".as_slice(), Priority::Normal);
+	queue.queue_guarded(b"+++".as_slice(), Duration::from_millis(50), Duration::from_millis(50), Priority::Normal);
+	queue.queue(b"ATH".as_slice(), Priority::Normal);
+	assert!(let Ok(()) = queue.flush());
+	assert!(start.elapsed() >= Duration::from_millis(100));
+
+	let mut buffer = [0; 8];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"AT+++ATH");
+}
+
+#[test]
+fn write_urgent() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	a.set_inter_byte_delay(Some(serial2::InterByteDelay::after_every(1, Duration::from_millis(10))));
+	let_assert!(Ok(queue) = WriteQueue::new(Arc::new(a)));
+
+	queue.queue(b"bulk-1".as_slice(), Priority::Normal);
+	std::thread::sleep(Duration::from_millis(20));
+	queue.queue(b"bulk-2".as_slice(), Priority::Urgent);
+	let start = Instant::now();
+	assert!(let Ok(()) = queue.write_urgent(b"STOP"));
+	// The first write is completed, but the pending write and the inter-byte delay are bypassed.
+	assert!(start.elapsed() < Duration::from_millis(100));
+	assert!(let Ok(()) = queue.flush());
+
+	let mut buffer = [0; 16];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"bulk-1STOPbulk-2");
+}
+
+#[test]
+fn keepalive() {
+	let_assert!(Ok((a, mut b)) = SerialPort::pair());
+	let_assert!(Ok(queue) = WriteQueue::new(Arc::new(a)));
+	queue.set_keepalive(Duration::from_millis(200), b"PING".as_slice());
+	assert!(queue.get_keepalive() == Some((Duration::from_millis(200), b"PING".to_vec())));
+
+	// The keepalive is transmitted when the line is idle.
+	assert!(let Ok(()) = b.set_read_timeout(Duration::from_secs(1)));
+	let mut buffer = [0; 4];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"PING");
+	assert!(queue.keepalives_sent() == 1);
+
+	// Real traffic suppresses the keepalive.
+	for _ in 0..6 {
+		queue.queue(b"data".as_slice(), Priority::Normal);
+		std::thread::sleep(Duration::from_millis(50));
+		assert!(let Ok(()) = b.read_exact(&mut buffer));
+		assert!(&buffer == b"data");
+	}
+	assert!(queue.keepalives_sent() == 1);
+
+	queue.clear_keepalive();
+	assert!(queue.get_keepalive() == None);
+	assert!(let Ok(()) = b.set_read_timeout(Duration::from_millis(300)));
+	let_assert!(Err(e) = b.read_exact(&mut buffer));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn shared_writer() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let writer = SharedWriter::new(Arc::new(a));
+
+	let threads: Vec<_> = (0..4u8)
+		.map(|i| {
+			let writer = writer.clone();
+			std::thread::spawn(move || writer.write_frame(&[IoSlice::new(&[b'a' + i; 4])]))
+		})
+		.collect();
+	for thread in threads {
+		assert!(let Ok(Ok(())) = thread.join());
+	}
+	assert!(writer.waiting() == 0);
+
+	let mut buffer = [0; 16];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	for frame in buffer.chunks(4) {
+		assert!(frame.iter().all(|&byte| byte == frame[0]));
+	}
+
+	// An expired deadline never writes anything, and does not block later calls.
+	let expired = Instant::now() - Duration::from_millis(1);
+	let_assert!(Err(e) = writer.write_frame_before(&[IoSlice::new(b"late")], expired));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+	let deadline = Instant::now() + Duration::from_secs(1);
+	assert!(let Ok(()) = writer.write_frame_before(&[IoSlice::new(b"on time")], deadline));
+	let mut buffer = [0; 7];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"on time");
+}
+
+#[test]
+fn queue_tracked() {
+	let_assert!(Ok((a, b)) = SerialPort::pair());
+	let_assert!(Ok(queue) = WriteQueue::new(Arc::new(a)));
+
+	let token = queue.queue_tracked(b"Hello!".as_slice(), Priority::Normal);
+	assert!(token.len() == 6);
+	assert!(let Ok(()) = token.wait(Duration::from_secs(1)));
+	assert!(token.is_done());
+	assert!(queue.bytes_written() == 6);
+	assert!(let Ok(()) = token.wait_drained(Duration::from_secs(1)));
+
+	let mut buffer = [0; 6];
+	assert!(let Ok(()) = b.read_exact(&mut buffer));
+	assert!(&buffer == b"Hello!");
+}
+
+#[test]
+fn queue_tracked_cancelled() {
+	let_assert!(Ok((a, _b)) = SerialPort::pair());
+	a.set_inter_byte_delay(Some(serial2::InterByteDelay::after_every(1, Duration::from_millis(10))));
+	let_assert!(Ok(queue) = WriteQueue::new(Arc::new(a)));
+
+	// Keep the background thread busy, so the tracked writes stay pending.
+	queue.queue(b"bulk-1".as_slice(), Priority::Normal);
+	std::thread::sleep(Duration::from_millis(20));
+	let cancelled = queue.queue_tracked(b"cancelled".as_slice(), Priority::Normal);
+	let pending = queue.queue_tracked(b"pending".as_slice(), Priority::Normal);
+
+	let_assert!(Err(e) = pending.wait(Duration::ZERO));
+	assert!(e.kind() == std::io::ErrorKind::TimedOut);
+	assert!(queue.cancel(cancelled.id()));
+	let_assert!(Err(e) = cancelled.wait(Duration::ZERO));
+	assert!(e.kind() == std::io::ErrorKind::Other);
+
+	assert!(let Ok(()) = pending.wait(Duration::from_secs(1)));
+	assert!(queue.bytes_written() == 13);
+}